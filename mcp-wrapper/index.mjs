@@ -449,8 +449,9 @@ server.tool(
     file_path: z.string().describe("Path to the Elm file"),
     line: z.number().describe("Line number (0-indexed)"),
     character: z.number().describe("Character position (0-indexed)"),
+    include_tests: z.boolean().optional().describe("Include usages in tests/ (default: the server's includeTests setting)"),
   },
-  async ({ file_path, line, character }) => {
+  async ({ file_path, line, character, include_tests }) => {
     const absPath = resolveFilePath(file_path);
     const workspaceRoot = findWorkspaceRoot(absPath);
     if (!workspaceRoot) {
@@ -462,7 +463,9 @@ server.tool(
     const content = readFileSync(absPath, "utf-8");
     await client.openDocument(uri, content);
 
-    const result = await client.getReferences(uri, line, character);
+    const result = include_tests === undefined
+      ? await client.getReferences(uri, line, character)
+      : (await client.executeCommand("elm.findReferences", [uri, line, character, include_tests]))?.references;
     if (!result || result.length === 0) {
       return { content: [{ type: "text", text: "No references found" }] };
     }
@@ -524,7 +527,8 @@ server.tool(
     const symbols = paginated.map((s) => {
       const kind = kindNames[s.kind] || `Kind${s.kind}`;
       const line = s.location?.range?.start?.line ?? s.range?.start?.line ?? 0;
-      return `${s.name} (${kind}) at line ${line + 1}`;
+      const detail = s.detail ? ` - ${s.detail}` : "";
+      return `${s.name} (${kind}) at line ${line + 1}${detail}`;
     });
 
     let text = `Found ${total} symbols`;
@@ -686,8 +690,10 @@ server.tool(
     character: z.number().describe("Character position within the variant name (0-indexed)"),
     old_name: z.string().describe("Expected current variant name (must match what's at the position)"),
     newName: z.string().describe("The new name for the variant"),
+    include_tests: z.boolean().optional().describe("Also rename usages in tests/ (default: the server's includeTests setting)"),
+    rename_in_docs: z.boolean().optional().describe("Also rename mentions in doc comments and matching string literals (default: the server's renameInDocs setting)"),
   },
-  async ({ file_path, line, character, old_name, newName }) => {
+  async ({ file_path, line, character, old_name, newName, include_tests, rename_in_docs }) => {
     // Validate that variant name starts with uppercase
     if (!newName || !/^[A-Z]/.test(newName)) {
       return { content: [{ type: "text", text: "Variant names must start with an uppercase letter" }] };
@@ -704,7 +710,10 @@ server.tool(
     const content = readFileSync(absPath, "utf-8");
     await client.openDocument(uri, content);
 
-    const result = await client.executeCommand("elm.renameVariant", [uri, line, character, newName]);
+    const args = [uri, line, character, newName];
+    if (include_tests !== undefined || rename_in_docs !== undefined) args.push(include_tests ?? null);
+    if (rename_in_docs !== undefined) args.push(rename_in_docs);
+    const result = await client.executeCommand("elm.renameVariant", args);
 
     if (!result) {
       return { content: [{ type: "text", text: `No variant found at line ${line + 1}. Expected: ${old_name}` }] };
@@ -755,8 +764,10 @@ server.tool(
     character: z.number().describe("Character position within the type name (0-indexed)"),
     old_name: z.string().describe("Expected current type name (must match what's at the position)"),
     newName: z.string().describe("The new name for the type"),
+    include_tests: z.boolean().optional().describe("Also rename usages in tests/ (default: the server's includeTests setting)"),
+    rename_in_docs: z.boolean().optional().describe("Also rename mentions in doc comments and matching string literals (default: the server's renameInDocs setting)"),
   },
-  async ({ file_path, line, character, old_name, newName }) => {
+  async ({ file_path, line, character, old_name, newName, include_tests, rename_in_docs }) => {
     const absPath = resolveFilePath(file_path);
     const workspaceRoot = findWorkspaceRoot(absPath);
     if (!workspaceRoot) {
@@ -768,7 +779,10 @@ server.tool(
     const content = readFileSync(absPath, "utf-8");
     await client.openDocument(uri, content);
 
-    const result = await client.executeCommand("elm.renameType", [uri, line, character, newName]);
+    const args = [uri, line, character, newName];
+    if (include_tests !== undefined || rename_in_docs !== undefined) args.push(include_tests ?? null);
+    if (rename_in_docs !== undefined) args.push(rename_in_docs);
+    const result = await client.executeCommand("elm.renameType", args);
 
     if (!result) {
       return { content: [{ type: "text", text: `No type found at line ${line + 1}. Expected: ${old_name}` }] };
@@ -819,8 +833,10 @@ server.tool(
     character: z.number().describe("Character position within the function name (0-indexed)"),
     old_name: z.string().describe("Expected current function name (must match what's at the position)"),
     newName: z.string().describe("The new name for the function"),
+    include_tests: z.boolean().optional().describe("Also rename usages in tests/ (default: the server's includeTests setting)"),
+    rename_in_docs: z.boolean().optional().describe("Also rename mentions in doc comments and matching string literals (default: the server's renameInDocs setting)"),
   },
-  async ({ file_path, line, character, old_name, newName }) => {
+  async ({ file_path, line, character, old_name, newName, include_tests, rename_in_docs }) => {
     const absPath = resolveFilePath(file_path);
     const workspaceRoot = findWorkspaceRoot(absPath);
     if (!workspaceRoot) {
@@ -832,7 +848,10 @@ server.tool(
     const content = readFileSync(absPath, "utf-8");
     await client.openDocument(uri, content);
 
-    const result = await client.executeCommand("elm.renameFunction", [uri, line, character, newName]);
+    const args = [uri, line, character, newName];
+    if (include_tests !== undefined || rename_in_docs !== undefined) args.push(include_tests ?? null);
+    if (rename_in_docs !== undefined) args.push(rename_in_docs);
+    const result = await client.executeCommand("elm.renameFunction", args);
 
     if (!result) {
       return { content: [{ type: "text", text: `No function found at line ${line + 1}. Expected: ${old_name}` }] };
@@ -1365,14 +1384,16 @@ server.tool(
 
 server.tool(
   "elm_remove_variant",
-  "Remove a variant from a custom type. Constructor usages are replaced with Debug.todo, pattern matches are removed.",
+  "Remove a variant from a custom type. Constructor usages are replaced with Debug.todo (or a given expression, or another variant), pattern matches are removed (or mapped to that variant).",
   {
     file_path: z.string().describe("Path to the Elm file containing the type definition"),
     line: z.number().describe("Line number of the variant name (0-indexed)"),
     character: z.number().describe("Character position within the variant name (0-indexed)"),
     variant_name: z.string().describe("Expected variant name (must match what's at the position)"),
+    replacement_expression: z.string().optional().describe("Expression to replace constructor usages with instead of Debug.todo"),
+    replacement_variant: z.string().optional().describe("Variant of the same type (same arguments) to map constructor usages and patterns to"),
   },
-  async ({ file_path, line, character, variant_name }) => {
+  async ({ file_path, line, character, variant_name, replacement_expression, replacement_variant }) => {
     const absPath = resolveFilePath(file_path);
     const workspaceRoot = findWorkspaceRoot(absPath);
     if (!workspaceRoot) {
@@ -1384,7 +1405,10 @@ server.tool(
     const content = readFileSync(absPath, "utf-8");
     await client.openDocument(uri, content);
 
-    const result = await client.executeCommand("elm.removeVariant", [uri, line, character]);
+    const args = [uri, line, character];
+    if (replacement_variant !== undefined) args.push({ Variant: replacement_variant });
+    else if (replacement_expression !== undefined) args.push({ Expression: replacement_expression });
+    const result = await client.executeCommand("elm.removeVariant", args);
 
     if (!result) {
       return { content: [{ type: "text", text: `No variant found at line ${line + 1}. Expected: ${variant_name}` }] };
@@ -1989,6 +2013,267 @@ server.tool(
   }
 );
 
+server.tool(
+  "elm_reorder",
+  "Reorder the variants of a custom type or the fields of a record type alias (alphabetically by default). " +
+  "For records, positional constructor calls like `User \"Ann\" 42` are reordered to match.",
+  {
+    file_path: z.string().describe("Path to the Elm file containing the type definition"),
+    line: z.number().describe("Line number of the type name (0-indexed)"),
+    character: z.number().describe("Character position within the type name (0-indexed)"),
+    order: z.array(z.string()).optional().describe("All variant or field names in the new order (default: alphabetical)"),
+    update_call_sites: z.boolean().optional().describe("Also reorder record constructor call arguments (default: true)"),
+  },
+  async ({ file_path, line, character, order, update_call_sites }) => {
+    const absPath = resolveFilePath(file_path);
+    const workspaceRoot = findWorkspaceRoot(absPath);
+    if (!workspaceRoot) {
+      return { content: [{ type: "text", text: "No elm.json found in parent directories" }] };
+    }
+
+    const client = await ensureClient(workspaceRoot);
+    const uri = `file://${absPath}`;
+    const content = readFileSync(absPath, "utf-8");
+    await client.openDocument(uri, content);
+
+    const args = [uri, line, character, order ?? null];
+    if (update_call_sites !== undefined) args.push(update_call_sites);
+    const result = await client.executeCommand("elm.reorder", args);
+
+    if (!result?.success) {
+      return { content: [{ type: "text", text: `Error: ${result?.error || "Reorder failed"}` }] };
+    }
+
+    let text = result.message;
+    if (result.changes && Object.keys(result.changes).length > 0) {
+      const applied = await applyWorkspaceEdit(result.changes, client, workspaceRoot);
+      text += `\nApplied edits to ${applied.length} file(s)`;
+    }
+    for (const usage of result.unchangedUsages || []) {
+      const path = usage.uri.replace("file://", "");
+      text += `\n- Review ${path}:${usage.range.start.line + 1}`;
+    }
+    return { content: [{ type: "text", text }] };
+  }
+);
+
+server.tool(
+  "elm_tuple_to_record",
+  "Replace a tuple type such as `( String, Int )` with a new named record alias. " +
+  "Annotations that take or return exactly that tuple use the alias, and the tuple literals, " +
+  "tuple patterns and `Tuple.first`/`Tuple.second` calls that go with them become record forms.",
+  {
+    file_path: z.string().describe("Path to the Elm file containing the tuple type"),
+    line: z.number().describe("Line number of the tuple type (0-indexed)"),
+    character: z.number().describe("Character position within the tuple type (0-indexed)"),
+    alias_name: z.string().describe("Name of the new record type alias"),
+    field_names: z.array(z.string()).describe("One field name per tuple element, in order"),
+  },
+  async ({ file_path, line, character, alias_name, field_names }) => {
+    const absPath = resolveFilePath(file_path);
+    const workspaceRoot = findWorkspaceRoot(absPath);
+    if (!workspaceRoot) {
+      return { content: [{ type: "text", text: "No elm.json found in parent directories" }] };
+    }
+
+    const client = await ensureClient(workspaceRoot);
+    const uri = `file://${absPath}`;
+    const content = readFileSync(absPath, "utf-8");
+    await client.openDocument(uri, content);
+
+    const result = await client.executeCommand("elm.tupleToRecord", [uri, line, character, alias_name, field_names]);
+
+    if (!result?.success) {
+      return { content: [{ type: "text", text: `Error: ${result?.error || "Conversion failed"}` }] };
+    }
+
+    let text = result.message;
+    if (result.changes && Object.keys(result.changes).length > 0) {
+      const applied = await applyWorkspaceEdit(result.changes, client, workspaceRoot);
+      text += `\nApplied edits to ${applied.length} file(s)`;
+    }
+    for (const usage of result.unchangedUsages || []) {
+      const path = usage.uri.replace("file://", "");
+      text += `\n- Review ${path}:${usage.range.start.line + 1}`;
+    }
+    return { content: [{ type: "text", text }] };
+  }
+);
+
+server.tool(
+  "elm_introduce_parameter",
+  "Turn a selected expression into a new first parameter of its top-level function. " +
+  "Every use of the function passes the expression (or `Debug.todo` when it refers to local variables).",
+  {
+    file_path: z.string().describe("Path to the Elm file"),
+    start_line: z.number().describe("Start line of the expression (0-indexed)"),
+    start_character: z.number().describe("Start character of the expression (0-indexed)"),
+    end_line: z.number().describe("End line of the expression (0-indexed)"),
+    end_character: z.number().describe("End character of the expression (0-indexed, exclusive)"),
+    name: z.string().describe("Name of the new parameter"),
+  },
+  async ({ file_path, start_line, start_character, end_line, end_character, name }) => {
+    const absPath = resolveFilePath(file_path);
+    const workspaceRoot = findWorkspaceRoot(absPath);
+    if (!workspaceRoot) {
+      return { content: [{ type: "text", text: "No elm.json found in parent directories" }] };
+    }
+
+    const client = await ensureClient(workspaceRoot);
+    const uri = `file://${absPath}`;
+    const content = readFileSync(absPath, "utf-8");
+    await client.openDocument(uri, content);
+
+    const result = await client.executeCommand("elm.introduceParameter", [
+      uri, start_line, start_character, end_line, end_character, name,
+    ]);
+
+    if (!result?.success) {
+      return { content: [{ type: "text", text: `Error: ${result?.error || "Introduce parameter failed"}` }] };
+    }
+
+    let text = result.message;
+    if (result.changes && Object.keys(result.changes).length > 0) {
+      const applied = await applyWorkspaceEdit(result.changes, client, workspaceRoot);
+      text += `\nApplied edits to ${applied.length} file(s)`;
+    }
+    for (const usage of result.unchangedUsages || []) {
+      const path = usage.uri.replace("file://", "");
+      text += `\n- Review ${path}:${usage.range.start.line + 1}`;
+    }
+    return { content: [{ type: "text", text }] };
+  }
+);
+
+server.tool(
+  "elm_hoist_constant",
+  "Move a selected expression that uses no local variables to a new top-level constant, " +
+  "annotated with its inferred type, and replace every identical expression in the module with it.",
+  {
+    file_path: z.string().describe("Path to the Elm file"),
+    start_line: z.number().describe("Start line of the expression (0-indexed)"),
+    start_character: z.number().describe("Start character of the expression (0-indexed)"),
+    end_line: z.number().describe("End line of the expression (0-indexed)"),
+    end_character: z.number().describe("End character of the expression (0-indexed, exclusive)"),
+    name: z.string().describe("Name of the new constant"),
+  },
+  async ({ file_path, start_line, start_character, end_line, end_character, name }) => {
+    const absPath = resolveFilePath(file_path);
+    const workspaceRoot = findWorkspaceRoot(absPath);
+    if (!workspaceRoot) {
+      return { content: [{ type: "text", text: "No elm.json found in parent directories" }] };
+    }
+
+    const client = await ensureClient(workspaceRoot);
+    const uri = `file://${absPath}`;
+    const content = readFileSync(absPath, "utf-8");
+    await client.openDocument(uri, content);
+
+    const result = await client.executeCommand("elm.hoistConstant", [
+      uri, start_line, start_character, end_line, end_character, name,
+    ]);
+
+    if (!result?.success) {
+      return { content: [{ type: "text", text: `Error: ${result?.error || "Hoisting failed"}` }] };
+    }
+
+    let text = result.message;
+    if (result.changes && Object.keys(result.changes).length > 0) {
+      const applied = await applyWorkspaceEdit(result.changes, client, workspaceRoot);
+      text += `\nApplied edits to ${applied.length} file(s)`;
+    }
+    return { content: [{ type: "text", text }] };
+  }
+);
+
+server.tool(
+  "elm_organize_declarations",
+  "Reorder a module's top-level declarations, moving doc comments, annotations and comments with them. " +
+  "By default exposed declarations come first, types before values, and main/init/update/subscriptions/view are grouped.",
+  {
+    file_path: z.string().describe("Path to the Elm file"),
+    exposed_first: z.boolean().optional().describe("Put exposed declarations before private ones (default: true)"),
+    types_first: z.boolean().optional().describe("Put types and aliases before ports, and ports before values (default: true)"),
+    group_update_view: z.boolean().optional().describe("Put main, init, update, subscriptions and view first among values (default: true)"),
+  },
+  async ({ file_path, exposed_first, types_first, group_update_view }) => {
+    const absPath = resolveFilePath(file_path);
+    const workspaceRoot = findWorkspaceRoot(absPath);
+    if (!workspaceRoot) {
+      return { content: [{ type: "text", text: "No elm.json found in parent directories" }] };
+    }
+
+    const client = await ensureClient(workspaceRoot);
+    const uri = `file://${absPath}`;
+    const content = readFileSync(absPath, "utf-8");
+    await client.openDocument(uri, content);
+
+    const rules = [exposed_first, types_first, group_update_view];
+    const order = rules.every((rule) => rule === undefined)
+      ? null
+      : {
+          exposedFirst: exposed_first ?? true,
+          typesFirst: types_first ?? true,
+          groupUpdateView: group_update_view ?? true,
+        };
+    const result = await client.executeCommand("elm.organizeDeclarations", [uri, order]);
+
+    if (!result?.success) {
+      return { content: [{ type: "text", text: `Error: ${result?.error || "Organizing failed"}` }] };
+    }
+
+    let text = result.message;
+    if (result.changes && Object.keys(result.changes).length > 0) {
+      const applied = await applyWorkspaceEdit(result.changes, client, workspaceRoot);
+      text += `\nApplied edits to ${applied.length} file(s)`;
+    }
+    return { content: [{ type: "text", text }] };
+  }
+);
+
+server.tool(
+  "elm_importers",
+  "List the modules that import an Elm file's module, directly or transitively. " +
+  "Use before large refactors to see which modules a change can affect.",
+  {
+    file_path: z.string().describe("Path to the Elm file"),
+    max_depth: z.number().optional().describe("Maximum import hops to follow (default: unlimited)"),
+  },
+  async ({ file_path, max_depth }) => {
+    const absPath = resolveFilePath(file_path);
+    const workspaceRoot = findWorkspaceRoot(absPath);
+    if (!workspaceRoot) {
+      return { content: [{ type: "text", text: "No elm.json found in parent directories" }] };
+    }
+
+    const client = await ensureClient(workspaceRoot);
+    const uri = `file://${absPath}`;
+    const args = max_depth === undefined ? [uri] : [uri, max_depth];
+    const result = await client.executeCommand("elm.getImporters", args);
+
+    if (!result?.success) {
+      return {
+        content: [{
+          type: "text",
+          text: `Failed to get importers: ${result?.error || "Unknown error"}`,
+        }],
+      };
+    }
+
+    if (result.importers.length === 0) {
+      return { content: [{ type: "text", text: `No modules import ${result.module}` }] };
+    }
+
+    let text = `${result.importers.length} module(s) import ${result.module}:`;
+    for (const importer of result.importers) {
+      const path = importer.uri.replace("file://", "");
+      const via = importer.depth === 1 ? "directly" : `${importer.depth} imports away`;
+      text += `\n- ${importer.module} (${via}) ${path}`;
+    }
+    return { content: [{ type: "text", text }] };
+  }
+);
+
 }
 
 // Helper to extract module name from Elm source
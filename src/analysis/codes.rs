@@ -0,0 +1,165 @@
+//! The `code` of every diagnostic the server finds itself, with the Elm
+//! documentation explaining the problem when there is some. Codes are
+//! stable: settings and clients refer to them.
+
+use tower_lsp::lsp_types::{
+    CodeDescription, Diagnostic, DiagnosticSeverity, NumberOrString, Range, Url,
+};
+
+use std::collections::BTreeMap;
+
+use crate::config::Severity;
+
+use super::SOURCE;
+
+/// What kind of problem a diagnostic reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Code {
+    /// The diagnostic's `code`, e.g. `unused-parameter`
+    pub name: &'static str,
+    /// Elm documentation about the problem, linked as the `codeDescription`
+    pub docs: Option<&'static str>,
+}
+
+impl Code {
+    /// A diagnostic of this kind, from this server
+    pub fn diagnostic(
+        &self,
+        range: Range,
+        severity: DiagnosticSeverity,
+        message: String,
+    ) -> Diagnostic {
+        Diagnostic {
+            range,
+            severity: Some(severity),
+            code: Some(NumberOrString::String(self.name.to_string())),
+            code_description: self
+                .docs
+                .and_then(|docs| Url::parse(docs).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some(SOURCE.to_string()),
+            message,
+            ..Default::default()
+        }
+    }
+
+    /// The code `name`, if this server reports it
+    pub fn named(name: &str) -> Option<Code> {
+        ALL.iter().find(|code| code.name == name).copied()
+    }
+}
+
+/// Give `diagnostic` the severity the settings choose for its code. False
+/// when they turn it off.
+pub fn configure_severity(
+    diagnostic: &mut Diagnostic,
+    severities: &BTreeMap<String, Severity>,
+) -> bool {
+    if diagnostic.source.as_deref() != Some(SOURCE) {
+        return true;
+    }
+    let Some(NumberOrString::String(code)) = &diagnostic.code else {
+        return true;
+    };
+    match severities.get(code).map(|severity| severity.to_lsp()) {
+        Some(Some(severity)) => {
+            diagnostic.severity = Some(severity);
+            true
+        }
+        Some(None) => false,
+        None => true,
+    }
+}
+
+/// [`configure_severity`] for each of `diagnostics`, dropping those turned off
+pub fn apply_severities(
+    diagnostics: &mut Vec<Diagnostic>,
+    severities: &BTreeMap<String, Severity>,
+) {
+    diagnostics.retain_mut(|diagnostic| configure_severity(diagnostic, severities));
+}
+
+const fn code(name: &'static str, docs: Option<&'static str>) -> Code {
+    Code { name, docs }
+}
+
+const SYNTAX_DOCS: &str = "https://elm-lang.org/docs/syntax";
+const IMPORTS_DOCS: &str = "https://elm-lang.org/0.19.1/imports";
+const PORTS_DOCS: &str = "https://elm-lang.org/0.19.1/port-modules";
+const RECURSION_DOCS: &str = "https://elm-lang.org/0.19.1/bad-recursion";
+const NAVIGATION_DOCS: &str =
+    "https://package.elm-lang.org/packages/elm/browser/latest/Browser-Navigation#Key";
+const DOCUMENTATION_DOCS: &str = "https://package.elm-lang.org/help/documentation-format";
+const DESIGN_DOCS: &str = "https://package.elm-lang.org/help/design-guidelines";
+const ELM_JSON_DOCS: &str = "https://github.com/elm/compiler/blob/master/docs/elm.json/package.md";
+
+pub const SYNTAX_ERROR: Code = code("syntax-error", Some(SYNTAX_DOCS));
+pub const MODULE_NAME_MISMATCH: Code = code("module-name-mismatch", None);
+pub const DUPLICATE_IMPORT: Code = code("duplicate-import", Some(IMPORTS_DOCS));
+pub const REPEATED_EXPOSED: Code = code("repeated-exposed", Some(IMPORTS_DOCS));
+pub const UNUSED_IMPORT: Code = code("unused-import", Some(IMPORTS_DOCS));
+pub const UNUSED_EXPOSED: Code = code("unused-exposed", Some(IMPORTS_DOCS));
+pub const UNUSED_PARAMETER: Code = code("unused-parameter", None);
+pub const UNUSED_LET_BINDING: Code = code("unused-let-binding", None);
+pub const PORTS_IN_PLAIN_MODULE: Code = code("ports-in-plain-module", Some(PORTS_DOCS));
+pub const PORT_MODULE_WITHOUT_PORTS: Code = code("port-module-without-ports", Some(PORTS_DOCS));
+pub const INFINITE_RECURSION: Code = code("infinite-recursion", Some(RECURSION_DOCS));
+pub const LIST_APPEND_IN_LOOP: Code = code("list-append-in-loop", None);
+pub const NOT_EXPOSED: Code = code("not-exposed", Some(IMPORTS_DOCS));
+pub const NAVIGATION_WITHOUT_KEY: Code = code("navigation-without-key", Some(NAVIGATION_DOCS));
+pub const MISSING_FROM_DOCS: Code = code("missing-from-docs", Some(DOCUMENTATION_DOCS));
+pub const DOCS_NOT_EXPOSED: Code = code("docs-not-exposed", Some(DOCUMENTATION_DOCS));
+pub const MISSING_DOC_COMMENT: Code = code("missing-doc-comment", Some(DOCUMENTATION_DOCS));
+pub const INTERNAL_TYPE_EXPOSED: Code = code("internal-type-exposed", Some(DESIGN_DOCS));
+pub const MISSING_EXPOSED_MODULE: Code = code("missing-exposed-module", Some(ELM_JSON_DOCS));
+
+/// Every code, in the order the README lists them
+pub const ALL: &[Code] = &[
+    SYNTAX_ERROR,
+    MODULE_NAME_MISMATCH,
+    DUPLICATE_IMPORT,
+    REPEATED_EXPOSED,
+    UNUSED_IMPORT,
+    UNUSED_EXPOSED,
+    UNUSED_PARAMETER,
+    UNUSED_LET_BINDING,
+    PORTS_IN_PLAIN_MODULE,
+    PORT_MODULE_WITHOUT_PORTS,
+    INFINITE_RECURSION,
+    LIST_APPEND_IN_LOOP,
+    NOT_EXPOSED,
+    NAVIGATION_WITHOUT_KEY,
+    MISSING_FROM_DOCS,
+    DOCS_NOT_EXPOSED,
+    MISSING_DOC_COMMENT,
+    INTERNAL_TYPE_EXPOSED,
+    MISSING_EXPOSED_MODULE,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::Position;
+
+    #[test]
+    fn test_apply_severities() {
+        let range = Range::new(Position::new(0, 0), Position::new(0, 1));
+        let warning =
+            |code: Code| code.diagnostic(range, DiagnosticSeverity::WARNING, String::new());
+        let mut diagnostics = vec![
+            warning(UNUSED_PARAMETER),
+            warning(INFINITE_RECURSION),
+            warning(DUPLICATE_IMPORT),
+        ];
+        let severities = BTreeMap::from([
+            ("unused-parameter".to_string(), Severity::Hint),
+            ("infinite-recursion".to_string(), Severity::Off),
+        ]);
+        apply_severities(&mut diagnostics, &severities);
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::HINT));
+        assert_eq!(diagnostics[1].code, warning(DUPLICATE_IMPORT).code);
+        assert_eq!(diagnostics[1].severity, Some(DiagnosticSeverity::WARNING));
+    }
+}
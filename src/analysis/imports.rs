@@ -0,0 +1,335 @@
+//! Duplicate imports and repeated names in exposing lists.
+//!
+//! Elm accepts `import Html exposing (div)` next to `import Html exposing
+//! (text)`, and `exposing (view, view)`, but both are noise. Every problem
+//! comes with a fix: imports of the same module merge into one, and a
+//! repeated name is removed.
+
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range, TextEdit};
+use tree_sitter::{Node, Tree};
+
+use crate::document::node_range;
+
+use super::codes::{self, Code};
+use super::{related_here, QuickFix};
+
+/// Report imports of an already imported module and repeated exposed names
+pub fn duplicate_import_diagnostics(tree: &Tree, source: &str) -> Vec<Diagnostic> {
+    problems(tree, source)
+        .into_iter()
+        .map(|fix| fix.diagnostic)
+        .collect()
+}
+
+/// Fixes for the problems of [`duplicate_import_diagnostics`] in `range`
+pub fn duplicate_import_fixes(tree: &Tree, source: &str, range: Range) -> Vec<QuickFix> {
+    problems(tree, source)
+        .into_iter()
+        .filter(|fix| {
+            let found = fix.diagnostic.range;
+            found.start <= range.end && range.start <= found.end
+        })
+        .collect()
+}
+
+fn problems(tree: &Tree, source: &str) -> Vec<QuickFix> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let imports: Vec<Node> = root
+        .named_children(&mut cursor)
+        .filter(|n| n.kind() == "import_clause")
+        .collect();
+
+    let mut fixes = Vec::new();
+    if let Some(exposing) = root
+        .child_by_field_name("moduleDeclaration")
+        .and_then(|m| m.child_by_field_name("exposing"))
+    {
+        fixes.extend(repeated_items(exposing, source));
+    }
+
+    let mut seen: Vec<&str> = Vec::new();
+    for import in &imports {
+        let Some(name) = module_name(*import, source) else {
+            continue;
+        };
+        if seen.contains(&name) {
+            continue;
+        }
+        seen.push(name);
+        let same: Vec<Node> = imports
+            .iter()
+            .filter(|i| module_name(**i, source) == Some(name))
+            .copied()
+            .collect();
+        if same.len() == 1 {
+            if let Some(exposing) = import.child_by_field_name("exposing") {
+                fixes.extend(repeated_items(exposing, source));
+            }
+            continue;
+        }
+        let Some(merged) = merge(&same, source) else {
+            continue;
+        };
+        let line = same[0].start_position().row + 1;
+        for duplicate in &same[1..] {
+            let mut edits = vec![TextEdit {
+                range: node_range(same[0]),
+                new_text: merged.clone(),
+            }];
+            edits.extend(same[1..].iter().map(|i| TextEdit {
+                range: whole_lines(*i),
+                new_text: String::new(),
+            }));
+            fixes.push(QuickFix {
+                title: format!("Merge imports of {}", name),
+                diagnostic: Diagnostic {
+                    related_information: Some(vec![related_here(
+                        node_range(same[0]),
+                        "first imported here",
+                    )]),
+                    ..warning(
+                        codes::DUPLICATE_IMPORT,
+                        node_range(*duplicate),
+                        format!("`{}` is already imported on line {}", name, line),
+                    )
+                },
+                edits,
+            });
+        }
+    }
+    fixes
+}
+
+fn module_name<'a>(import: Node, source: &'a str) -> Option<&'a str> {
+    import
+        .child_by_field_name("moduleName")
+        .map(|n| &source[n.byte_range()])
+}
+
+/// One import equivalent to all of `imports`, or None when they do not
+/// share the same alias: merging `import Html` into `import Html as H`
+/// would leave every `Html.` reference unresolved
+fn merge(imports: &[Node], source: &str) -> Option<String> {
+    let mut alias: Option<Option<&str>> = None;
+    let mut everything = false;
+    let mut items: Vec<String> = Vec::new();
+    for import in imports {
+        let name = import
+            .child_by_field_name("asClause")
+            .and_then(|a| a.child_by_field_name("name"))
+            .map(|name| &source[name.byte_range()]);
+        if alias.is_some_and(|a| a != name) {
+            return None;
+        }
+        alias = Some(name);
+        let Some(exposing) = import.child_by_field_name("exposing") else {
+            continue;
+        };
+        let mut cursor = exposing.walk();
+        for item in exposing.named_children(&mut cursor) {
+            if item.kind() == "double_dot" {
+                everything = true;
+                continue;
+            }
+            if !is_item(item) {
+                continue;
+            }
+            let text = item_text(item, source);
+            match items.iter().position(|i| item_key(i) == item_key(&text)) {
+                Some(i) if text.ends_with("(..)") => items[i] = text,
+                Some(_) => {}
+                None => items.push(text),
+            }
+        }
+    }
+
+    let mut merged = format!("import {}", module_name(imports[0], source)?);
+    if let Some(Some(alias)) = alias {
+        merged.push_str(&format!(" as {}", alias));
+    }
+    if everything {
+        merged.push_str(" exposing (..)");
+    } else if !items.is_empty() {
+        merged.push_str(&format!(" exposing ({})", items.join(", ")));
+    }
+    Some(merged)
+}
+
+/// Items of an exposing list that repeat an earlier one
+fn repeated_items(exposing: Node, source: &str) -> Vec<QuickFix> {
+    let mut cursor = exposing.walk();
+    let items: Vec<Node> = exposing
+        .named_children(&mut cursor)
+        .filter(|n| is_item(*n))
+        .collect();
+
+    let mut fixes = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        let text = item_text(*item, source);
+        let Some(earlier) = items[..index]
+            .iter()
+            .find(|e| item_key(&item_text(**e, source)) == item_key(&text))
+        else {
+            continue;
+        };
+        let Some(comma) = item.prev_sibling().filter(|s| s.kind() == ",") else {
+            continue;
+        };
+        let mut edits = vec![TextEdit {
+            range: Range::new(node_range(comma).start, node_range(*item).end),
+            new_text: String::new(),
+        }];
+        // Keep the constructors when only the repeat exposes them
+        if text.ends_with("(..)") && !item_text(*earlier, source).ends_with("(..)") {
+            edits.insert(
+                0,
+                TextEdit {
+                    range: node_range(*earlier),
+                    new_text: text.clone(),
+                },
+            );
+        }
+        let key = item_key(&text);
+        fixes.push(QuickFix {
+            title: format!("Remove repeated {}", key),
+            diagnostic: Diagnostic {
+                related_information: Some(vec![related_here(
+                    node_range(*earlier),
+                    "first exposed here",
+                )]),
+                ..warning(
+                    codes::REPEATED_EXPOSED,
+                    node_range(*item),
+                    format!("`{}` is already exposed", key),
+                )
+            },
+            edits,
+        });
+    }
+    fixes
+}
+
+fn is_item(node: Node) -> bool {
+    matches!(
+        node.kind(),
+        "exposed_value" | "exposed_type" | "exposed_operator"
+    )
+}
+
+/// An exposed item without whitespace, e.g. `Msg(..)`
+fn item_text(item: Node, source: &str) -> String {
+    source[item.byte_range()].split_whitespace().collect()
+}
+
+/// The name an exposed item is about: `Msg` for both `Msg` and `Msg(..)`
+pub(crate) fn item_key(text: &str) -> &str {
+    text.strip_suffix("(..)").unwrap_or(text)
+}
+
+/// The lines of `node`, including the final line break
+pub(super) fn whole_lines(node: Node) -> Range {
+    Range::new(
+        Position::new(node.start_position().row as u32, 0),
+        Position::new(node.end_position().row as u32 + 1, 0),
+    )
+}
+
+fn warning(code: Code, range: Range, message: String) -> Diagnostic {
+    code.diagnostic(range, DiagnosticSeverity::WARNING, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ElmParser;
+
+    fn fixes(source: &str) -> Vec<QuickFix> {
+        let tree = ElmParser::new().parse(source).unwrap();
+        let everything = Range::new(Position::new(0, 0), Position::new(u32::MAX, 0));
+        duplicate_import_fixes(&tree, source, everything)
+    }
+
+    #[test]
+    fn test_merge_duplicate_imports() {
+        let source = "module A exposing (..)\n\nimport Html as H exposing (div, Html)\nimport Json.Decode as D\nimport Html as H exposing (text, Html)\n";
+        let fixes = fixes(source);
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(fixes[0].title, "Merge imports of Html");
+        assert_eq!(fixes[0].diagnostic.range.start, Position::new(4, 0));
+        assert_eq!(
+            fixes[0].diagnostic.message,
+            "`Html` is already imported on line 3"
+        );
+        assert_eq!(
+            fixes[0].edits[0].new_text,
+            "import Html as H exposing (div, Html, text)"
+        );
+        assert_eq!(
+            fixes[0].edits[1].range,
+            Range::new(Position::new(4, 0), Position::new(5, 0))
+        );
+    }
+
+    #[test]
+    fn test_imports_with_different_aliases_are_kept() {
+        let source = "module A exposing (..)\n\nimport Html as H\nimport Html as Hm\n";
+        assert!(fixes(source).is_empty());
+    }
+
+    #[test]
+    fn test_aliased_and_unaliased_imports_are_kept() {
+        // Merging would drop `Html.` as a qualifier, breaking `Html.text`
+        let source = "module A exposing (..)\n\nimport Html exposing (..)\nimport Html as H\n\nview =\n    Html.text \"\"\n";
+        assert!(fixes(source).is_empty());
+    }
+
+    #[test]
+    fn test_repeated_exposed_names() {
+        let source =
+            "module A exposing (view, Msg, view)\n\nimport Html exposing (Html, div, Html(..))\n";
+        let fixes = fixes(source);
+        assert_eq!(fixes.len(), 2);
+        assert_eq!(fixes[0].diagnostic.message, "`view` is already exposed");
+        assert_eq!(
+            fixes[0].edits[0].range,
+            Range::new(Position::new(0, 28), Position::new(0, 34))
+        );
+        // The repeat is the one exposing constructors, so the first takes them
+        assert_eq!(fixes[1].edits.len(), 2);
+        assert_eq!(fixes[1].edits[0].new_text, "Html(..)");
+        assert_eq!(fixes[1].edits[0].range.start, Position::new(2, 22));
+    }
+
+    #[test]
+    fn test_duplicate_import_points_at_first_import_and_carries_its_fix() {
+        let source =
+            "module A exposing (..)\n\nimport Html exposing (div)\nimport Html exposing (text)\n";
+        let tree = ElmParser::new().parse(source).unwrap();
+        let mut diagnostics = duplicate_import_diagnostics(&tree, source);
+        assert_eq!(
+            diagnostics[0].code,
+            Some(tower_lsp::lsp_types::NumberOrString::String(
+                "duplicate-import".to_string()
+            ))
+        );
+
+        let uri = tower_lsp::lsp_types::Url::parse("file:///project/src/A.elm").unwrap();
+        crate::analysis::locate_related(&mut diagnostics, &uri);
+        let related = diagnostics[0].related_information.as_ref().unwrap();
+        assert_eq!(related[0].location.uri, uri);
+        assert_eq!(related[0].location.range.start, Position::new(2, 0));
+
+        let fix = fixes(source).remove(0);
+        let changes = std::collections::HashMap::from([(uri.clone(), fix.edits.clone())]);
+        let fix_data = crate::analysis::Fix {
+            title: fix.title.clone(),
+            changes,
+        };
+        crate::analysis::attach_fixes(&mut diagnostics, vec![(fix.diagnostic, fix_data)], 7);
+        let data = crate::analysis::FixData::of(&diagnostics[0]).unwrap();
+        assert_eq!(data.version, 7);
+        assert_eq!(data.fixes[0].title, "Merge imports of Html");
+        assert_eq!(data.fixes[0].changes[&uri], fix.edits);
+    }
+}
@@ -0,0 +1,138 @@
+//! Diagnostics computed by the server itself from the parse tree, without
+//! running the Elm compiler. These are cheap enough to publish on every change.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, Location, Range, TextEdit, Url,
+};
+use tree_sitter::Tree;
+
+pub mod codes;
+mod imports;
+mod performance;
+mod ports;
+mod recursion;
+mod suppression;
+mod syntax;
+mod unused;
+mod unused_imports;
+
+pub use codes::Code;
+pub(crate) use imports::item_key;
+pub use imports::{duplicate_import_diagnostics, duplicate_import_fixes};
+pub use performance::{performance_diagnostics, performance_fixes};
+pub use ports::{port_header_diagnostics, port_header_fixes};
+pub use recursion::{recursion_diagnostics, recursive_functions, Recursion};
+pub use suppression::{drop_suppressed, is_suppressed, suppression_edit, DISABLE_NEXT_LINE};
+pub use syntax::syntax_diagnostics;
+pub(crate) use unused::binding_removal;
+pub use unused::{unused_diagnostics, unused_fixes};
+pub use unused_imports::{unused_import_diagnostics, unused_import_fixes, ImportedModules};
+
+/// Source name for diagnostics produced by this module
+pub const SOURCE: &str = "elm-lsp";
+
+/// A problem with the edits that fix it
+pub struct QuickFix {
+    pub title: String,
+    pub diagnostic: Diagnostic,
+    pub edits: Vec<TextEdit>,
+}
+
+/// The fixes of a diagnostic, carried in its `data` so that code actions
+/// can be made from the diagnostic alone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FixData {
+    /// The version of the document the edits apply to
+    pub version: i32,
+    pub fixes: Vec<Fix>,
+}
+
+/// One way to resolve a diagnostic
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fix {
+    pub title: String,
+    pub changes: HashMap<Url, Vec<TextEdit>>,
+}
+
+impl FixData {
+    /// The fix data `diagnostic` carries, if any
+    pub fn of(diagnostic: &Diagnostic) -> Option<FixData> {
+        serde_json::from_value(diagnostic.data.clone()?).ok()
+    }
+}
+
+/// Give each of `diagnostics` the fixes found for it, as [`FixData`] for the
+/// document at `version`. A fix belongs to the diagnostic with the same
+/// range, code and message.
+pub fn attach_fixes(diagnostics: &mut [Diagnostic], fixes: Vec<(Diagnostic, Fix)>, version: i32) {
+    for diagnostic in diagnostics {
+        let found: Vec<Fix> = fixes
+            .iter()
+            .filter(|(fixed, _)| {
+                fixed.range == diagnostic.range
+                    && fixed.code == diagnostic.code
+                    && fixed.message == diagnostic.message
+            })
+            .map(|(_, fix)| fix.clone())
+            .collect();
+        if found.is_empty() {
+            continue;
+        }
+        diagnostic.data = serde_json::to_value(FixData {
+            version,
+            fixes: found,
+        })
+        .ok();
+    }
+}
+
+/// Related information at `range` in the diagnostic's own document. The
+/// analyses here only see the tree, so the location has a placeholder URI
+/// until [`locate_related`] sets the document's.
+pub(crate) fn related_here(range: Range, message: &str) -> DiagnosticRelatedInformation {
+    DiagnosticRelatedInformation {
+        location: Location {
+            uri: same_document(),
+            range,
+        },
+        message: message.to_string(),
+    }
+}
+
+/// Point the related information [`related_here`] made at the document `uri`
+pub fn locate_related<'a>(diagnostics: impl IntoIterator<Item = &'a mut Diagnostic>, uri: &Url) {
+    let placeholder = same_document();
+    for diagnostic in diagnostics {
+        for related in diagnostic.related_information.iter_mut().flatten() {
+            if related.location.uri == placeholder {
+                related.location.uri = uri.clone();
+            }
+        }
+    }
+}
+
+fn same_document() -> Url {
+    Url::parse("elm-lsp:same-document").expect("a valid URL")
+}
+
+/// Warnings about code that compiles but has something to clean up or
+/// recursion that never stops, and a module header that does not match the
+/// module's ports
+pub fn lint_diagnostics(tree: &Tree, source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = duplicate_import_diagnostics(tree, source);
+    diagnostics.extend(unused_diagnostics(tree, source));
+    diagnostics.extend(port_header_diagnostics(tree, source));
+    diagnostics.extend(recursion_diagnostics(tree, source));
+    diagnostics
+}
+
+/// Fixes for the warnings of [`lint_diagnostics`] in `range`
+pub fn lint_fixes(tree: &Tree, source: &str, range: Range) -> Vec<QuickFix> {
+    let mut fixes = duplicate_import_fixes(tree, source, range);
+    fixes.extend(unused_fixes(tree, source, range));
+    fixes.extend(port_header_fixes(tree, source, range));
+    fixes
+}
@@ -0,0 +1,245 @@
+//! Performance hints: code that is correct but slower than it needs to be.
+//!
+//! These are opt-in (the `performanceHints` setting) since the slow version
+//! is often fine for small inputs. Each hint is a [`Rule`] that looks at one
+//! node and may come with a fix; adding a hint means adding a rule to
+//! [`RULES`].
+
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Range, TextEdit};
+use tree_sitter::{Node, Tree};
+
+use crate::document::node_range;
+
+use super::codes::{self, Code};
+use super::QuickFix;
+
+/// A performance hint
+struct Rule {
+    /// Diagnostic code, e.g. `list-append-in-loop`
+    code: Code,
+    check: fn(Node, &str) -> Option<Hint>,
+}
+
+/// What a rule found, with a fix when one is safe to apply
+struct Hint {
+    range: Range,
+    message: String,
+    fix: Option<(String, Vec<TextEdit>)>,
+}
+
+const RULES: &[Rule] = &[Rule {
+    code: codes::LIST_APPEND_IN_LOOP,
+    check: list_append_in_loop,
+}];
+
+/// Report code matching one of the [`RULES`]
+pub fn performance_diagnostics(tree: &Tree, source: &str) -> Vec<Diagnostic> {
+    hints(tree, source)
+        .into_iter()
+        .map(|(code, hint)| diagnostic(code, &hint))
+        .collect()
+}
+
+/// Fixes for the hints of [`performance_diagnostics`] in `range`
+pub fn performance_fixes(tree: &Tree, source: &str, range: Range) -> Vec<QuickFix> {
+    hints(tree, source)
+        .into_iter()
+        .filter(|(_, hint)| hint.range.start <= range.end && range.start <= hint.range.end)
+        .filter_map(|(code, hint)| {
+            let diagnostic = diagnostic(code, &hint);
+            let (title, edits) = hint.fix?;
+            Some(QuickFix {
+                title,
+                diagnostic,
+                edits,
+            })
+        })
+        .collect()
+}
+
+fn hints(tree: &Tree, source: &str) -> Vec<(Code, Hint)> {
+    let mut found = Vec::new();
+    let mut stack = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        if node.is_error() {
+            continue;
+        }
+        for rule in RULES {
+            if let Some(hint) = (rule.check)(node, source) {
+                found.push((rule.code, hint));
+            }
+        }
+        let mut cursor = node.walk();
+        stack.extend(node.named_children(&mut cursor));
+    }
+    found.sort_by_key(|(_, hint)| hint.range.start);
+    found
+}
+
+fn diagnostic(code: Code, hint: &Hint) -> Diagnostic {
+    code.diagnostic(
+        hint.range,
+        DiagnosticSeverity::INFORMATION,
+        hint.message.clone(),
+    )
+}
+
+/// `acc ++ [ x ]` in a fold or a recursive function copies `acc` on every
+/// step, which is quadratic. Consing and reversing once at the end is linear.
+fn list_append_in_loop(node: Node, source: &str) -> Option<Hint> {
+    if node.kind() != "bin_op_expr" {
+        return None;
+    }
+    let mut cursor = node.walk();
+    let parts: Vec<Node> = node.named_children(&mut cursor).collect();
+    let index = (2..parts.len()).find(|&i| {
+        parts[i].kind() == "list_expr"
+            && parts[i].named_child_count() == 1
+            && &source[parts[i - 1].byte_range()] == "++"
+    })?;
+    let fold = enclosing_fold(node, source);
+    if fold.is_none() && !in_recursive_function(node, source) {
+        return None;
+    }
+
+    let range = Range::new(
+        node_range(parts[index - 2]).start,
+        node_range(parts[index]).end,
+    );
+    let message = match fold {
+        Some(_) => "Appending to the accumulator copies it on every step of the fold. Consider `x :: acc` and reversing the result once.",
+        None => "Appending to a list on every recursive call copies it each time. Consider `x :: acc` and reversing the result once.",
+    }
+    .to_string();
+
+    // Only `\x acc -> acc ++ [ x ]` is rewritten: anything else may use the
+    // accumulator's order along the way
+    let fix = fold
+        .filter(|(_, lambda)| {
+            parts.len() == 3
+                && lambda
+                    .child_by_field_name("expr")
+                    .is_some_and(|body| body.id() == node.id())
+        })
+        .map(|(call, _)| {
+            let element = parts[2].named_child(0).unwrap_or(parts[2]);
+            let element_text = &source[element.byte_range()];
+            let element_text = if element.kind() == "bin_op_expr" {
+                format!("({})", element_text)
+            } else {
+                element_text.to_string()
+            };
+            let call_range = node_range(call);
+            let edits = vec![
+                TextEdit {
+                    range: Range::new(call_range.start, call_range.start),
+                    new_text: "List.reverse (".to_string(),
+                },
+                TextEdit {
+                    range: node_range(node),
+                    new_text: format!("{} :: {}", element_text, &source[parts[0].byte_range()]),
+                },
+                TextEdit {
+                    range: Range::new(call_range.end, call_range.end),
+                    new_text: ")".to_string(),
+                },
+            ];
+            (
+                "Cons onto the accumulator and reverse once".to_string(),
+                edits,
+            )
+        });
+
+    Some(Hint {
+        range,
+        message,
+        fix,
+    })
+}
+
+/// The `foldl`/`foldr` call whose function argument is a lambda containing
+/// `node`, and that lambda
+fn enclosing_fold<'a>(node: Node<'a>, source: &str) -> Option<(Node<'a>, Node<'a>)> {
+    let lambda = std::iter::successors(node.parent(), |n| n.parent())
+        .take_while(|n| !matches!(n.kind(), "value_declaration" | "file"))
+        .find(|n| n.kind() == "anonymous_function_expr")?;
+    let call = std::iter::successors(lambda.parent(), |n| n.parent())
+        .find(|n| n.kind() != "parenthesized_expr")
+        .filter(|n| n.kind() == "function_call_expr")?;
+    let target = call.child_by_field_name("target")?;
+    let name = &source[target.byte_range()];
+    let name = name.rsplit('.').next().unwrap_or(name);
+    matches!(name, "foldl" | "foldr").then_some((call, lambda))
+}
+
+/// Whether `node` is in a function that calls itself
+fn in_recursive_function(node: Node, source: &str) -> bool {
+    let Some(declaration) = std::iter::successors(node.parent(), |n| n.parent())
+        .find(|n| n.kind() == "value_declaration")
+    else {
+        return false;
+    };
+    let Some(name) = declaration
+        .child_by_field_name("functionDeclarationLeft")
+        .and_then(|left| left.named_child(0))
+        .map(|n| &source[n.byte_range()])
+    else {
+        return false;
+    };
+    let Some(body) = declaration.child_by_field_name("body") else {
+        return false;
+    };
+    let mut stack = vec![body];
+    while let Some(n) = stack.pop() {
+        if n.kind() == "value_qid" && &source[n.byte_range()] == name {
+            return true;
+        }
+        let mut cursor = n.walk();
+        stack.extend(n.named_children(&mut cursor));
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ElmParser;
+    use tower_lsp::lsp_types::{NumberOrString, Position};
+
+    fn parse(source: &str) -> Tree {
+        ElmParser::new().parse(source).unwrap()
+    }
+
+    #[test]
+    fn test_append_in_fold_is_rewritten() {
+        let source = "module A exposing (..)\n\nf xs =\n    List.foldl (\\x acc -> acc ++ [ x + 1 ]) [] xs\n";
+        let tree = parse(source);
+        let everything = Range::new(Position::new(0, 0), Position::new(u32::MAX, 0));
+        let fixes = performance_fixes(&tree, source, everything);
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(
+            fixes[0].diagnostic.code,
+            Some(NumberOrString::String("list-append-in-loop".to_string()))
+        );
+        let texts: Vec<&str> = fixes[0].edits.iter().map(|e| e.new_text.as_str()).collect();
+        assert_eq!(texts, ["List.reverse (", "(x + 1) :: acc", ")"]);
+        assert_eq!(fixes[0].edits[0].range.start, Position::new(3, 4));
+    }
+
+    #[test]
+    fn test_append_in_recursion_is_reported_without_fix() {
+        let source = "module A exposing (..)\n\ngo acc xs =\n    case xs of\n        [] ->\n            acc\n\n        x :: rest ->\n            go (acc ++ [ x ]) rest\n";
+        let tree = parse(source);
+        let diagnostics = performance_diagnostics(&tree, source);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(8, 16));
+        let everything = Range::new(Position::new(0, 0), Position::new(u32::MAX, 0));
+        assert!(performance_fixes(&tree, source, everything).is_empty());
+    }
+
+    #[test]
+    fn test_plain_append_is_fine() {
+        let source = "module A exposing (..)\n\nf xs =\n    xs ++ [ 1 ]\n";
+        assert!(performance_diagnostics(&parse(source), source).is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+//! Port declarations that do not match the module header.
+//!
+//! Only a `port module` may declare ports, and a `port module` must declare
+//! at least one. The compiler reports either mistake only once the rest of
+//! the module compiles, so it is flagged here with a fix for the header.
+
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Range, TextEdit};
+use tree_sitter::{Node, Tree};
+
+use crate::document::node_range;
+
+use super::codes::{self, Code};
+use super::{related_here, QuickFix};
+
+/// Report a header that says `module` above ports, or `port module` above none
+pub fn port_header_diagnostics(tree: &Tree, source: &str) -> Vec<Diagnostic> {
+    problem(tree, source)
+        .into_iter()
+        .map(|fix| fix.diagnostic)
+        .collect()
+}
+
+/// The fix for the problem of [`port_header_diagnostics`] in `range`
+pub fn port_header_fixes(tree: &Tree, source: &str, range: Range) -> Vec<QuickFix> {
+    problem(tree, source)
+        .into_iter()
+        .filter(|fix| {
+            let found = fix.diagnostic.range;
+            found.start <= range.end && range.start <= found.end
+        })
+        .collect()
+}
+
+fn problem(tree: &Tree, source: &str) -> Option<QuickFix> {
+    let root = tree.root_node();
+    let header = root.child_by_field_name("moduleDeclaration")?;
+    let keyword = |kind: &str| {
+        let mut cursor = header.walk();
+        let found = header.children(&mut cursor).find(|n| n.kind() == kind);
+        found
+    };
+    // Effect modules are for the core packages, which have their own rules
+    if keyword("effect").is_some() {
+        return None;
+    }
+    let module = keyword("module")?;
+    let name = header.child_by_field_name("name")?;
+    let name = &source[name.byte_range()];
+
+    let mut cursor = root.walk();
+    let ports: Vec<Node> = root
+        .named_children(&mut cursor)
+        .filter(|n| n.kind() == "port_annotation")
+        .collect();
+
+    match (keyword("port"), ports.first()) {
+        (None, Some(first)) => {
+            let port = first
+                .child_by_field_name("name")
+                .map_or("", |n| &source[n.byte_range()]);
+            let range = node_range(module);
+            let mut diagnostic = error(
+                codes::PORTS_IN_PLAIN_MODULE,
+                range,
+                format!(
+                    "`{}` declares ports (`{}` on line {}) but is not a `port module`",
+                    name,
+                    port,
+                    first.start_position().row + 1
+                ),
+            );
+            diagnostic.related_information =
+                Some(vec![related_here(node_range(*first), "port declared here")]);
+            Some(QuickFix {
+                title: "Make this a port module".to_string(),
+                diagnostic,
+                edits: vec![TextEdit {
+                    range: Range::new(range.start, range.start),
+                    new_text: "port ".to_string(),
+                }],
+            })
+        }
+        (Some(port), None) => {
+            let range = Range::new(node_range(port).start, node_range(module).end);
+            Some(QuickFix {
+                title: "Make this a plain module".to_string(),
+                diagnostic: error(
+                    codes::PORT_MODULE_WITHOUT_PORTS,
+                    range,
+                    format!("`{}` is a `port module` but declares no ports", name),
+                ),
+                edits: vec![TextEdit {
+                    range: Range::new(range.start, node_range(module).start),
+                    new_text: String::new(),
+                }],
+            })
+        }
+        _ => None,
+    }
+}
+
+fn error(code: Code, range: Range, message: String) -> Diagnostic {
+    code.diagnostic(range, DiagnosticSeverity::ERROR, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ElmParser;
+    use tower_lsp::lsp_types::Position;
+
+    fn fixes(source: &str) -> Vec<QuickFix> {
+        let tree = ElmParser::new().parse(source).unwrap();
+        let everything = Range::new(Position::new(0, 0), Position::new(u32::MAX, 0));
+        port_header_fixes(&tree, source, everything)
+    }
+
+    #[test]
+    fn test_port_header_matches_ports() {
+        let fixes_for_plain =
+            fixes("module Ports exposing (..)\n\n\nport send : String -> Cmd msg\n");
+        assert_eq!(fixes_for_plain.len(), 1);
+        assert_eq!(
+            fixes_for_plain[0].diagnostic.message,
+            "`Ports` declares ports (`send` on line 4) but is not a `port module`"
+        );
+        assert_eq!(
+            fixes_for_plain[0].diagnostic.range,
+            Range::new(Position::new(0, 0), Position::new(0, 6))
+        );
+        assert_eq!(fixes_for_plain[0].edits[0].new_text, "port ");
+        assert_eq!(
+            fixes_for_plain[0].edits[0].range,
+            Range::new(Position::new(0, 0), Position::new(0, 0))
+        );
+
+        let fixes_for_port = fixes("port module Main exposing (main)\n\n\nmain =\n    1\n");
+        assert_eq!(fixes_for_port.len(), 1);
+        assert_eq!(
+            fixes_for_port[0].diagnostic.message,
+            "`Main` is a `port module` but declares no ports"
+        );
+        assert_eq!(fixes_for_port[0].edits[0].new_text, "");
+        assert_eq!(
+            fixes_for_port[0].edits[0].range,
+            Range::new(Position::new(0, 0), Position::new(0, 5))
+        );
+
+        assert!(
+            fixes("port module Ports exposing (..)\n\n\nport send : String -> Cmd msg\n")
+                .is_empty()
+        );
+        assert!(fixes("module Main exposing (main)\n\n\nmain =\n    1\n").is_empty());
+    }
+}
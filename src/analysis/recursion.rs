@@ -0,0 +1,230 @@
+//! Functions that call themselves.
+//!
+//! Elm does not allow shadowing, so inside a function's body its name can
+//! only mean the function itself. Every such use is collected, for top-level
+//! functions and functions in `let` alike, so editors can highlight the
+//! recursive calls and list them.
+//!
+//! A function is reported when every path through its body calls it again,
+//! as nothing then stops the recursion. Only calls that surely run count:
+//! the scrutinee of a `case` and the first condition of an `if` always do,
+//! a branch only when all of them do, and nothing inside a lambda or right
+//! of `&&` and `||` does. Passing the function along, as in
+//! `List.map view children`, is not a call.
+
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Range};
+use tree_sitter::{Node, Tree};
+
+use crate::document::node_range;
+use crate::parser::unqualified_references;
+
+use super::{codes, related_here};
+
+/// A function that refers to itself
+pub struct Recursion {
+    pub name: String,
+    /// The name where the function is declared
+    pub declaration: Range,
+    /// Where the body refers to the function
+    pub calls: Vec<Range>,
+    /// Every path through the body calls the function again
+    pub unbounded: bool,
+}
+
+/// The functions of the module, top-level or in `let`, that refer to
+/// themselves
+pub fn recursive_functions(tree: &Tree, source: &str) -> Vec<Recursion> {
+    let mut found = Vec::new();
+    let mut stack = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        let mut cursor = node.walk();
+        stack.extend(node.named_children(&mut cursor));
+        if node.kind() != "value_declaration" {
+            continue;
+        }
+        let Some(left) = node.child_by_field_name("functionDeclarationLeft") else {
+            continue;
+        };
+        let (Some(name), Some(body)) = (left.named_child(0), node.child_by_field_name("body"))
+        else {
+            continue;
+        };
+        let name_text = &source[name.byte_range()];
+        let calls: Vec<Node> = unqualified_references(body, &["value_qid"])
+            .into_iter()
+            .filter(|reference| &source[reference.byte_range()] == name_text)
+            .collect();
+        if calls.is_empty() {
+            continue;
+        }
+        let has_parameters = left.named_child_count() > 1;
+        found.push(Recursion {
+            name: name_text.to_string(),
+            declaration: node_range(name),
+            calls: calls.into_iter().map(node_range).collect(),
+            unbounded: has_parameters && always_calls(body, name_text, source),
+        });
+    }
+    found.sort_by_key(|recursion| recursion.declaration.start);
+    found
+}
+
+/// Report functions that call themselves on every path
+pub fn recursion_diagnostics(tree: &Tree, source: &str) -> Vec<Diagnostic> {
+    recursive_functions(tree, source)
+        .into_iter()
+        .filter(|recursion| recursion.unbounded)
+        .map(|recursion| Diagnostic {
+            related_information: Some(
+                recursion
+                    .calls
+                    .iter()
+                    .map(|call| related_here(*call, "calls itself here"))
+                    .collect(),
+            ),
+            ..codes::INFINITE_RECURSION.diagnostic(
+                recursion.declaration,
+                DiagnosticSeverity::WARNING,
+                format!(
+                    "`{}` calls itself on every path, so it never returns: \
+                     no branch stops the recursion",
+                    recursion.name
+                ),
+            )
+        })
+        .collect()
+}
+
+fn is_reference_to(node: Node, name: &str, source: &str) -> bool {
+    node.kind() == "value_qid"
+        && node.named_child_count() == 1
+        && &source[node.byte_range()] == name
+}
+
+/// Whether evaluating `node` surely calls `name`
+fn always_calls(node: Node, name: &str, source: &str) -> bool {
+    let calls = |child: Node| always_calls(child, name, source);
+    let mut cursor = node.walk();
+    match node.kind() {
+        "anonymous_function_expr" => false,
+        "function_call_expr" => {
+            let target = node
+                .child_by_field_name("target")
+                .and_then(|target| target.named_child(0));
+            target.is_some_and(|target| is_reference_to(target, name, source))
+                || node.named_children(&mut cursor).any(calls)
+        }
+        "case_of_expr" => {
+            let branches: Vec<Node> = node
+                .children_by_field_name("branch", &mut cursor)
+                .filter_map(|branch| branch.child_by_field_name("expr"))
+                .collect();
+            node.child_by_field_name("expr").is_some_and(calls)
+                || !branches.is_empty() && branches.into_iter().all(calls)
+        }
+        "if_else_expr" => {
+            // Conditions and branches alternate, then the `else` branch
+            let parts: Vec<Node> = node
+                .children_by_field_name("exprList", &mut cursor)
+                .collect();
+            let last = parts.len().saturating_sub(1);
+            parts.first().is_some_and(|condition| calls(*condition))
+                || parts.len() > 1
+                    && parts
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| i % 2 == 1 || *i == last)
+                        .all(|(_, branch)| calls(*branch))
+        }
+        "bin_op_expr" => {
+            for part in node.named_children(&mut cursor) {
+                if part.kind() == "operator" {
+                    if matches!(&source[part.byte_range()], "&&" | "||") {
+                        return false;
+                    }
+                } else if calls(part) {
+                    return true;
+                }
+            }
+            false
+        }
+        "let_in_expr" => node.child_by_field_name("body").is_some_and(calls),
+        _ => node.named_children(&mut cursor).any(calls),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ElmParser;
+
+    fn recursions(source: &str) -> Vec<(String, usize, bool)> {
+        let tree = ElmParser::new().parse(source).unwrap();
+        recursive_functions(&tree, source)
+            .into_iter()
+            .map(|r| (r.name, r.calls.len(), r.unbounded))
+            .collect()
+    }
+
+    #[test]
+    fn test_recursive_functions() {
+        let source = r#"module Main exposing (..)
+
+
+length list =
+    case list of
+        [] ->
+            0
+
+        _ :: rest ->
+            1 + length rest
+
+
+view (Node children) =
+    div [] (List.map view children)
+
+
+forever n =
+    if n > 0 then
+        forever (n - 1)
+
+    else
+        forever (n + 1)
+
+
+loop n =
+    let
+        go m =
+            go m
+    in
+    always (go n) (loop n)
+
+
+lazily n =
+    n > 0 && lazily (n - 1)
+
+
+plain x =
+    x
+"#;
+        assert_eq!(
+            recursions(source),
+            [
+                ("length".to_string(), 1, false),
+                ("view".to_string(), 1, false),
+                ("forever".to_string(), 2, true),
+                ("loop".to_string(), 1, true),
+                ("go".to_string(), 1, true),
+                ("lazily".to_string(), 1, false),
+            ]
+        );
+
+        let tree = ElmParser::new().parse(source).unwrap();
+        let diagnostics = recursion_diagnostics(&tree, source);
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(
+            diagnostics[0].message,
+            "`forever` calls itself on every path, so it never returns: no branch stops the recursion"
+        );
+    }
+}
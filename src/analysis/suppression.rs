@@ -0,0 +1,138 @@
+//! Suppression comments for the server's own diagnostics.
+//!
+//! A line comment `-- elm-lsp-disable-next-line unused-parameter` hides the
+//! diagnostics with that code starting on the next line. Several codes can
+//! be listed, separated by spaces or commas, and a directive without codes
+//! hides every diagnostic of the line. Compiler errors are not affected:
+//! the module would still not compile.
+
+use tower_lsp::lsp_types::{Diagnostic, NumberOrString, Position, Range, TextEdit};
+
+use super::{codes, SOURCE};
+
+/// The directive, after `--`
+pub const DISABLE_NEXT_LINE: &str = "elm-lsp-disable-next-line";
+
+/// True when a directive on the line above `diagnostic` hides it
+pub fn is_suppressed(source: &str, diagnostic: &Diagnostic) -> bool {
+    if !can_suppress(diagnostic) {
+        return false;
+    }
+    let Some(line) = line_above(source, diagnostic.range.start.line) else {
+        return false;
+    };
+    match directive_codes(line) {
+        Some(listed) => listed.is_empty() || listed.contains(&code_name(diagnostic)),
+        None => false,
+    }
+}
+
+/// Drop the diagnostics a directive hides
+pub fn drop_suppressed(source: &str, diagnostics: &mut Vec<Diagnostic>) {
+    diagnostics.retain(|diagnostic| !is_suppressed(source, diagnostic));
+}
+
+/// The edit hiding `diagnostic`: a directive above its line, or its code
+/// added to the directive already there. None for diagnostics that cannot
+/// be suppressed.
+pub fn suppression_edit(source: &str, diagnostic: &Diagnostic) -> Option<TextEdit> {
+    if !can_suppress(diagnostic) || is_suppressed(source, diagnostic) {
+        return None;
+    }
+    let code = code_name(diagnostic);
+    let line = diagnostic.range.start.line;
+    if let Some(above) = line_above(source, line) {
+        if directive_codes(above).is_some() {
+            let end = Position::new(line - 1, above.trim_end().len() as u32);
+            return Some(TextEdit {
+                range: Range::new(end, end),
+                new_text: format!(" {}", code),
+            });
+        }
+    }
+    let text = source.lines().nth(line as usize).unwrap_or("");
+    let indent = &text[..text.len() - text.trim_start().len()];
+    let start = Position::new(line, 0);
+    Some(TextEdit {
+        range: Range::new(start, start),
+        new_text: format!("{}-- {} {}\n", indent, DISABLE_NEXT_LINE, code),
+    })
+}
+
+/// Diagnostics from this server with a code, except syntax errors, which
+/// are never false positives
+fn can_suppress(diagnostic: &Diagnostic) -> bool {
+    diagnostic.source.as_deref() == Some(SOURCE)
+        && matches!(&diagnostic.code, Some(NumberOrString::String(code)) if code != codes::SYNTAX_ERROR.name)
+}
+
+fn code_name(diagnostic: &Diagnostic) -> &str {
+    match &diagnostic.code {
+        Some(NumberOrString::String(code)) => code,
+        _ => "",
+    }
+}
+
+fn line_above(source: &str, line: u32) -> Option<&str> {
+    let above = line.checked_sub(1)?;
+    source.lines().nth(above as usize)
+}
+
+/// The codes a directive line lists, or None when the line is no directive
+fn directive_codes(line: &str) -> Option<Vec<&str>> {
+    let rest = line
+        .trim()
+        .strip_prefix("--")?
+        .trim_start()
+        .strip_prefix(DISABLE_NEXT_LINE)?;
+    if !rest.is_empty() && !rest.starts_with([' ', '\t', ',']) {
+        return None;
+    }
+    Some(
+        rest.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|code| !code.is_empty())
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::DiagnosticSeverity;
+
+    fn unused_at(line: u32) -> Diagnostic {
+        let range = Range::new(Position::new(line, 4), Position::new(line, 5));
+        codes::UNUSED_PARAMETER.diagnostic(range, DiagnosticSeverity::WARNING, String::new())
+    }
+
+    #[test]
+    fn test_directive_hides_listed_codes_on_next_line() {
+        let source = "f =\n    -- elm-lsp-disable-next-line unused-parameter, infinite-recursion\n    \\x -> 1\n";
+        assert!(is_suppressed(source, &unused_at(2)));
+
+        let other = "f =\n    -- elm-lsp-disable-next-line infinite-recursion\n    \\x -> 1\n";
+        assert!(!is_suppressed(other, &unused_at(2)));
+
+        let every = "f =\n    -- elm-lsp-disable-next-line\n    \\x -> 1\n";
+        assert!(is_suppressed(every, &unused_at(2)));
+
+        let lookalike = "f =\n    -- elm-lsp-disable-next-lines\n    \\x -> 1\n";
+        assert!(!is_suppressed(lookalike, &unused_at(2)));
+    }
+
+    #[test]
+    fn test_suppression_edit_adds_or_extends_directive() {
+        let source = "f =\n    \\x -> 1\n";
+        let edit = suppression_edit(source, &unused_at(1)).unwrap();
+        assert_eq!(edit.range.start, Position::new(1, 0));
+        assert_eq!(
+            edit.new_text,
+            "    -- elm-lsp-disable-next-line unused-parameter\n"
+        );
+
+        let listed = "f =\n    -- elm-lsp-disable-next-line infinite-recursion\n    \\x -> 1\n";
+        let edit = suppression_edit(listed, &unused_at(2)).unwrap();
+        assert_eq!(edit.range.start, Position::new(1, 51));
+        assert_eq!(edit.new_text, " unused-parameter");
+    }
+}
@@ -0,0 +1,263 @@
+//! Syntax errors from tree-sitter ERROR and MISSING nodes.
+
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+use tree_sitter::{Node, Tree};
+
+use super::codes;
+use crate::document::node_range;
+
+/// Report a diagnostic for every ERROR or MISSING node in the tree
+pub fn syntax_diagnostics(tree: &Tree, source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    if tree.root_node().has_error() {
+        collect(tree.root_node(), source, &mut diagnostics);
+    }
+    diagnostics
+}
+
+fn collect(node: Node, source: &str, diagnostics: &mut Vec<Diagnostic>) {
+    if node.is_missing() {
+        diagnostics.push(diagnostic(node_range(node), missing_message(node.kind())));
+        return;
+    }
+    if node.is_error() {
+        let (range, message) = describe_error(node, source);
+        diagnostics.push(diagnostic(range, message));
+        // Nested errors are part of the same problem
+        return;
+    }
+    if !node.has_error() {
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect(child, source, diagnostics);
+    }
+}
+
+fn diagnostic(range: Range, message: String) -> Diagnostic {
+    codes::SYNTAX_ERROR.diagnostic(range, DiagnosticSeverity::ERROR, message)
+}
+
+fn missing_message(kind: &str) -> String {
+    match kind {
+        "arrow" => "Missing `->`".to_string(),
+        "eq" => "Missing `=`".to_string(),
+        "colon" => "Missing `:`".to_string(),
+        ")" | "]" | "}" => format!("Unbalanced brackets: missing `{}`", kind),
+        k if k.starts_with("_virtual") => {
+            "Bad indentation: this line does not line up with the code around it".to_string()
+        }
+        k => format!("Missing `{}`", k),
+    }
+}
+
+/// Pick the most specific explanation for an ERROR node and where to show it
+fn describe_error(node: Node, source: &str) -> (Range, String) {
+    let mut leaves = Vec::new();
+    collect_leaves(node, &mut leaves);
+    let has = |kind: &str| leaves.iter().any(|leaf| leaf.kind() == kind);
+
+    if let Some(quote) = unterminated_string(&leaves) {
+        return (
+            node_range(quote),
+            "Unterminated string: missing closing `\"`".to_string(),
+        );
+    }
+
+    if let Some((bracket, message)) = unbalanced_bracket(&leaves) {
+        return (node_range(bracket), message);
+    }
+
+    let in_case = node.parent().is_some_and(|p| p.kind() == "case_of_expr");
+    if in_case && !has("arrow") {
+        return (
+            first_line_range(node, source),
+            "Missing `->` after this case pattern".to_string(),
+        );
+    }
+    if has("backslash") && !has("arrow") {
+        return (
+            first_line_range(node, source),
+            "Missing `->` in this anonymous function".to_string(),
+        );
+    }
+    if has("let") && !has("in") {
+        return (
+            first_line_range(node, source),
+            "This `let` block is missing its `in`".to_string(),
+        );
+    }
+    if has("if") && !has("else") {
+        return (
+            first_line_range(node, source),
+            "This `if` expression is missing its `else` branch".to_string(),
+        );
+    }
+
+    if let Some(line) = misaligned_line(node, source) {
+        return (
+            line,
+            "Bad indentation: this line does not line up with the lines above".to_string(),
+        );
+    }
+
+    (
+        first_line_range(node, source),
+        "Syntax error: I could not parse this code".to_string(),
+    )
+}
+
+fn collect_leaves<'tree>(node: Node<'tree>, leaves: &mut Vec<Node<'tree>>) {
+    if node.child_count() == 0 {
+        leaves.push(node);
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_leaves(child, leaves);
+    }
+}
+
+fn unterminated_string<'tree>(leaves: &[Node<'tree>]) -> Option<Node<'tree>> {
+    let mut open = None;
+    for leaf in leaves {
+        match leaf.kind() {
+            "open_quote" => open = Some(*leaf),
+            "close_quote" => open = None,
+            _ => {}
+        }
+    }
+    open
+}
+
+fn unbalanced_bracket<'tree>(leaves: &[Node<'tree>]) -> Option<(Node<'tree>, String)> {
+    let mut stack: Vec<Node> = Vec::new();
+    for leaf in leaves {
+        let closer = match leaf.kind() {
+            "(" | "[" | "{" => {
+                stack.push(*leaf);
+                continue;
+            }
+            ")" => "(",
+            "]" => "[",
+            "}" => "{",
+            _ => continue,
+        };
+        match stack.pop() {
+            Some(open) if open.kind() == closer => {}
+            Some(open) => {
+                return Some((
+                    *leaf,
+                    format!(
+                        "Unbalanced brackets: `{}` does not match `{}` on line {}",
+                        leaf.kind(),
+                        open.kind(),
+                        open.start_position().row + 1
+                    ),
+                ));
+            }
+            None => {
+                return Some((
+                    *leaf,
+                    format!("Unbalanced brackets: unexpected `{}`", leaf.kind()),
+                ));
+            }
+        }
+    }
+    stack.pop().map(|open| {
+        let message = format!("Unbalanced brackets: `{}` is never closed", open.kind());
+        (open, message)
+    })
+}
+
+/// A line that is indented less than the line before it but more than the
+/// line the error starts on, i.e. it does not line up with either
+fn misaligned_line(node: Node, source: &str) -> Option<Range> {
+    let start_row = node.start_position().row;
+    let end_row = node.end_position().row;
+    if start_row == end_row {
+        return None;
+    }
+
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let mut lines = source.lines().enumerate().skip(start_row);
+    let base = indent(lines.next()?.1);
+    let mut previous = base;
+
+    for (row, line) in lines.take(end_row - start_row) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let current = indent(line);
+        if current < previous && current > base {
+            return Some(Range::new(
+                Position::new(row as u32, current as u32),
+                Position::new(row as u32, line.trim_end().len() as u32),
+            ));
+        }
+        previous = current;
+    }
+    None
+}
+
+/// The node's range clipped to its first line, so large errors stay readable
+fn first_line_range(node: Node, source: &str) -> Range {
+    let range = node_range(node);
+    if range.start.line == range.end.line {
+        return range;
+    }
+    let line_end = source[node.start_byte()..]
+        .find('\n')
+        .map(|offset| node.start_position().column + offset)
+        .unwrap_or(node.end_position().column);
+    Range::new(
+        range.start,
+        Position::new(range.start.line, line_end as u32),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ElmParser;
+
+    fn diagnostics(source: &str) -> Vec<Diagnostic> {
+        let tree = ElmParser::new().parse(source).unwrap();
+        syntax_diagnostics(&tree, source)
+    }
+
+    #[test]
+    fn test_valid_file_has_no_syntax_errors() {
+        assert!(diagnostics("module A exposing (..)\n\nf : Int -> Int\nf x = x + 1\n").is_empty());
+    }
+
+    #[test]
+    fn test_unclosed_paren() {
+        let diags =
+            diagnostics("module A exposing (..)\n\nf : Int -> Int\nf x = (x + 1\n\ng = 2\n");
+        assert_eq!(diags.len(), 1);
+        assert!(diags[0].message.contains("`(` is never closed"));
+        assert_eq!(diags[0].range.start, Position::new(3, 6));
+    }
+
+    #[test]
+    fn test_missing_arrow_in_case_branch() {
+        let diags = diagnostics(
+            "module A exposing (..)\n\nf x =\n    case x of\n        1 2\n\n        _ -> 3\n",
+        );
+        assert_eq!(diags.len(), 1);
+        assert!(diags[0].message.contains("Missing `->`"));
+        assert_eq!(diags[0].range.start.line, 4);
+    }
+
+    #[test]
+    fn test_misaligned_case_branch() {
+        let diags = diagnostics(
+            "module A exposing (..)\n\nf x =\n    case x of\n        1 -> 2\n      _ -> 3\n",
+        );
+        assert_eq!(diags.len(), 1);
+        assert!(diags[0].message.contains("Bad indentation"));
+        assert_eq!(diags[0].range.start, Position::new(5, 6));
+    }
+}
@@ -0,0 +1,413 @@
+//! Parameters and let bindings that are never used.
+//!
+//! The binder knows which names each function, lambda and `let` introduces.
+//! A parameter no value in the body refers to is reported, with a fix that
+//! depends on where it sits: a plain parameter becomes `_`, a field of a
+//! record pattern is dropped from the pattern (its name has to match the
+//! field, so it cannot be renamed), and an unused `as` alias is removed.
+//!
+//! A let binding neither the `in` expression nor the other bindings refer
+//! to is removed along with its annotation; removing the last binding
+//! removes the whole `let`.
+
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::{
+    Diagnostic, DiagnosticSeverity, DiagnosticTag, Position, Range, TextEdit,
+};
+use tree_sitter::{Node, Tree};
+
+use super::codes::{self, Code};
+use super::QuickFix;
+use crate::binder::{bind_tree, BoundSymbolKind, SymbolLinks};
+use crate::document::node_range;
+use crate::parser::annotation_of;
+
+/// Report parameters and let bindings that are never referred to
+pub fn unused_diagnostics(tree: &Tree, source: &str) -> Vec<Diagnostic> {
+    unused(tree, source)
+        .into_iter()
+        .map(|fix| fix.diagnostic)
+        .collect()
+}
+
+/// Fixes for the problems of [`unused_diagnostics`] in `range`
+pub fn unused_fixes(tree: &Tree, source: &str, range: Range) -> Vec<QuickFix> {
+    unused(tree, source)
+        .into_iter()
+        .filter(|fix| {
+            let found = fix.diagnostic.range;
+            found.start <= range.end && range.start <= found.end
+        })
+        .collect()
+}
+
+fn unused(tree: &Tree, source: &str) -> Vec<QuickFix> {
+    let links = bind_tree(source, tree);
+    let mut nodes = HashMap::new();
+    let mut stack = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        nodes.insert(node.id(), node);
+        let mut cursor = node.walk();
+        stack.extend(node.named_children(&mut cursor));
+    }
+
+    let mut fixes = Vec::new();
+    for node in nodes.values() {
+        // Half-typed code does not say what it refers to yet
+        if node.has_error() {
+            continue;
+        }
+        match node.kind() {
+            "value_declaration" | "anonymous_function_expr" => {
+                fixes.extend(unused_parameters(*node, source, &links, &nodes))
+            }
+            "let_in_expr" => fixes.extend(unused_let_bindings(*node, source, &links, &nodes)),
+            _ => {}
+        }
+    }
+    fixes.sort_by_key(|fix| fix.diagnostic.range.start);
+    fixes
+}
+
+/// Parameters of a function or lambda its body never refers to
+fn unused_parameters(
+    function: Node,
+    source: &str,
+    links: &SymbolLinks,
+    nodes: &HashMap<usize, Node>,
+) -> Vec<QuickFix> {
+    let body = match function.kind() {
+        "value_declaration" => function.child_by_field_name("body"),
+        _ => function.child_by_field_name("expr"),
+    };
+    let (Some(body), Some(symbols)) = (body, links.get_container(function.id())) else {
+        return Vec::new();
+    };
+    symbols
+        .values()
+        .flatten()
+        .filter(|symbol| {
+            matches!(
+                symbol.kind,
+                BoundSymbolKind::FunctionParameter | BoundSymbolKind::AnonymousFunctionParameter
+            )
+        })
+        .filter(|symbol| !refers_to(body, source, &symbol.name))
+        .filter_map(|symbol| unused_fix(*nodes.get(&symbol.node_id)?, &symbol.name))
+        .collect()
+}
+
+/// Bindings of a `let` that neither its `in` expression nor its other
+/// bindings refer to
+fn unused_let_bindings(
+    let_in: Node,
+    source: &str,
+    links: &SymbolLinks,
+    nodes: &HashMap<usize, Node>,
+) -> Vec<QuickFix> {
+    let (Some(body), Some(symbols)) = (
+        let_in.child_by_field_name("body"),
+        links.get_container(let_in.id()),
+    ) else {
+        return Vec::new();
+    };
+    let mut cursor = let_in.walk();
+    let declarations: Vec<Node> = let_in
+        .children_by_field_name("valueDeclaration", &mut cursor)
+        .collect();
+    // The binding's own declaration does not count, so a function that only
+    // calls itself is unused too
+    let used = |name: &str, declaration: Node| {
+        refers_to(body, source, name)
+            || declarations
+                .iter()
+                .any(|d| d.id() != declaration.id() && refers_to(*d, source, name))
+    };
+
+    let mut fixes = Vec::new();
+    for symbol in symbols.values().flatten() {
+        let Some(node) = nodes.get(&symbol.node_id) else {
+            continue;
+        };
+        let Some(declaration) = std::iter::successors(Some(*node), |n| n.parent())
+            .find(|n| n.parent().is_some_and(|p| p.id() == let_in.id()))
+        else {
+            continue;
+        };
+        if used(&symbol.name, declaration) {
+            continue;
+        }
+
+        let name_node = match node.kind() {
+            "function_declaration_left" => node.named_child(0).unwrap_or(*node),
+            _ => *node,
+        };
+        // `( a, b ) = pair` stays when `b` is used, with `a` replaced by `_`
+        let keeps_others = symbols.values().flatten().any(|other| {
+            other.name != symbol.name
+                && nodes.get(&other.node_id).is_some_and(|n| {
+                    n.start_byte() >= declaration.start_byte()
+                        && n.end_byte() <= declaration.end_byte()
+                })
+                && used(&other.name, declaration)
+        });
+        let (title, edit) = if keeps_others {
+            (
+                format!("Replace unused `{}` with `_`", symbol.name),
+                replace(name_node, "_"),
+            )
+        } else {
+            (
+                format!("Remove unused `{}`", symbol.name),
+                TextEdit {
+                    range: binding_removal(let_in, declaration, body, source),
+                    new_text: String::new(),
+                },
+            )
+        };
+        fixes.push(QuickFix {
+            title,
+            diagnostic: unused_warning(
+                codes::UNUSED_LET_BINDING,
+                node_range(name_node),
+                format!("The let binding `{}` is never used", symbol.name),
+            ),
+            edits: vec![edit],
+        });
+    }
+    fixes
+}
+
+/// What to delete to remove `declaration` and its annotation from `let_in`:
+/// its lines, or the whole `let ... in` when it is the only binding
+pub(crate) fn binding_removal(let_in: Node, declaration: Node, body: Node, source: &str) -> Range {
+    let annotation = annotation_of(declaration, source);
+    let first = annotation.unwrap_or(declaration);
+
+    let bindings = let_in
+        .named_children(&mut let_in.walk())
+        .filter(|n| matches!(n.kind(), "value_declaration" | "type_annotation"))
+        .count();
+    if bindings == usize::from(annotation.is_some()) + 1 {
+        return Range::new(node_range(let_in).start, node_range(body).start);
+    }
+
+    let next = declaration
+        .next_named_sibling()
+        .filter(|n| n.id() != body.id());
+    match (next, first.prev_named_sibling()) {
+        (Some(next), _) => Range::new(
+            Position::new(first.start_position().row as u32, 0),
+            Position::new(next.start_position().row as u32, 0),
+        ),
+        // The last binding: up to the end of the one before, so the blank
+        // line between them goes too
+        (None, Some(previous)) => Range::new(node_range(previous).end, node_range(declaration).end),
+        (None, None) => node_range(declaration),
+    }
+}
+
+/// Whether a value in `body` is the unqualified `name`
+fn refers_to(body: Node, source: &str, name: &str) -> bool {
+    let mut stack = vec![body];
+    while let Some(node) = stack.pop() {
+        if matches!(node.kind(), "value_qid" | "record_base_identifier")
+            && &source[node.byte_range()] == name
+        {
+            return true;
+        }
+        let mut cursor = node.walk();
+        stack.extend(node.named_children(&mut cursor));
+    }
+    false
+}
+
+fn unused_fix(pattern: Node, name: &str) -> Option<QuickFix> {
+    let parent = pattern.parent()?;
+    let is_alias = parent.kind() == "pattern"
+        && parent
+            .child_by_field_name("patternAs")
+            .is_some_and(|alias| alias.id() == pattern.id());
+
+    let (title, message, edit) = if parent.kind() == "record_pattern" {
+        let fields = parent.named_child_count();
+        let edit = if fields == 1 {
+            replace(parent, "_")
+        } else {
+            TextEdit {
+                range: without_separator(pattern)?,
+                new_text: String::new(),
+            }
+        };
+        (
+            format!("Remove unused field `{}` from the pattern", name),
+            format!("The field `{}` is never used", name),
+            edit,
+        )
+    } else if is_alias {
+        let aliased = parent.child_by_field_name("child")?;
+        let range = Range::new(node_range(aliased).end, node_range(pattern).end);
+        (
+            format!("Remove unused alias `{}`", name),
+            format!("The alias `{}` is never used", name),
+            TextEdit {
+                range,
+                new_text: String::new(),
+            },
+        )
+    } else {
+        (
+            format!("Replace unused `{}` with `_`", name),
+            format!("The parameter `{}` is never used", name),
+            replace(pattern, "_"),
+        )
+    };
+
+    Some(QuickFix {
+        title,
+        diagnostic: unused_warning(codes::UNUSED_PARAMETER, node_range(pattern), message),
+        edits: vec![edit],
+    })
+}
+
+pub(super) fn unused_warning(code: Code, range: Range, message: String) -> Diagnostic {
+    Diagnostic {
+        tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+        ..code.diagnostic(range, DiagnosticSeverity::WARNING, message)
+    }
+}
+
+/// The range of a list element together with the comma that separates it
+/// from its neighbours
+pub(super) fn without_separator(node: Node) -> Option<Range> {
+    if let Some(comma) = node.prev_sibling().filter(|s| s.kind() == ",") {
+        return Some(Range::new(node_range(comma).start, node_range(node).end));
+    }
+    let comma = node.next_sibling().filter(|s| s.kind() == ",")?;
+    let next = comma.next_named_sibling()?;
+    Some(Range::new(node_range(node).start, node_range(next).start))
+}
+
+fn replace(node: Node, text: &str) -> TextEdit {
+    TextEdit {
+        range: node_range(node),
+        new_text: text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ElmParser;
+
+    fn fixes(source: &str) -> Vec<QuickFix> {
+        let tree = ElmParser::new().parse(source).unwrap();
+        let everything = Range::new(Position::new(0, 0), Position::new(u32::MAX, 0));
+        unused_fixes(&tree, source, everything)
+    }
+
+    #[test]
+    fn test_unused_parameters() {
+        let source = "module A exposing (..)\n\nupdate msg model =\n    let\n        helper x y =\n            y\n    in\n    List.map (\\item -> model) [ helper ]\n";
+        let fixes = fixes(source);
+        let messages: Vec<&str> = fixes
+            .iter()
+            .map(|f| f.diagnostic.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "The parameter `msg` is never used",
+                "The parameter `x` is never used",
+                "The parameter `item` is never used",
+            ]
+        );
+        assert_eq!(fixes[0].edits[0].new_text, "_");
+        assert_eq!(fixes[0].edits[0].range.start, Position::new(2, 7));
+    }
+
+    #[test]
+    fn test_unused_record_fields_and_aliases() {
+        let source = "module A exposing (..)\n\nview ({ name, age } as user) { id } =\n    name\n";
+        let fixes = fixes(source);
+        let titles: Vec<&str> = fixes.iter().map(|f| f.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            [
+                "Remove unused field `age` from the pattern",
+                "Remove unused alias `user`",
+                "Remove unused field `id` from the pattern",
+            ]
+        );
+        // `, age`
+        assert_eq!(
+            fixes[0].edits[0].range,
+            Range::new(Position::new(2, 12), Position::new(2, 17))
+        );
+        // ` as user`
+        assert_eq!(
+            fixes[1].edits[0].range,
+            Range::new(Position::new(2, 19), Position::new(2, 27))
+        );
+        // The only field: the whole record pattern becomes `_`
+        assert_eq!(fixes[2].edits[0].new_text, "_");
+    }
+
+    #[test]
+    fn test_unused_let_bindings() {
+        let source = "module A exposing (..)\n\nf input =\n    let\n        unused : Int\n        unused =\n            loop input\n\n        ( first, rest ) =\n            input\n\n        loop n =\n            loop n\n\n        double =\n            first * 2\n    in\n    double\n";
+        let found = fixes(source);
+        let titles: Vec<&str> = found.iter().map(|f| f.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            ["Remove unused `unused`", "Replace unused `rest` with `_`"]
+        );
+        // The annotation goes too, up to the next binding
+        assert_eq!(
+            found[0].edits[0].range,
+            Range::new(Position::new(4, 0), Position::new(8, 0))
+        );
+
+        // `loop` is only used by `unused`, so it shows up once that is gone
+        let source = "module A exposing (..)\n\nf input =\n    let\n        loop n =\n            loop n\n\n        double =\n            input * 2\n    in\n    double\n";
+        let fixes = fixes(source);
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(fixes[0].title, "Remove unused `loop`");
+        assert_eq!(
+            fixes[0].edits[0].range,
+            Range::new(Position::new(4, 0), Position::new(7, 0))
+        );
+    }
+
+    #[test]
+    fn test_removing_the_only_let_binding_removes_the_let() {
+        let source =
+            "module A exposing (..)\n\nf input =\n    let\n        x =\n            1\n    in\n    input\n";
+        let fixes = fixes(source);
+        assert_eq!(fixes.len(), 1);
+        let edit = &fixes[0].edits[0];
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(3, 4), Position::new(7, 4))
+        );
+        assert_eq!(edit.new_text, "");
+    }
+
+    #[test]
+    fn test_removing_the_last_let_binding_takes_the_blank_line() {
+        let source = "module A exposing (..)\n\nf input =\n    let\n        x =\n            input\n\n        y =\n            2\n    in\n    x\n";
+        let fixes = fixes(source);
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(
+            fixes[0].edits[0].range,
+            Range::new(Position::new(5, 17), Position::new(8, 13))
+        );
+    }
+
+    #[test]
+    fn test_used_through_record_update_and_field_access() {
+        let source = "module A exposing (..)\n\nf r s =\n    { r | a = s.b }\n";
+        assert!(fixes(source).is_empty());
+    }
+}
@@ -0,0 +1,329 @@
+//! Imports, and names in their exposing lists, that a module never uses.
+//!
+//! The names a module refers to are collected from its syntax tree, outside
+//! its header and imports: qualifiers like `Date` in `Date.format` and
+//! unqualified names, operators included. An import is used when its alias
+//! (or module name) qualifies something or a name it exposes is referred
+//! to. `exposing (..)` and `Type(..)` count as used when a name the module
+//! declares is, which only the workspace index knows (see
+//! [`ImportedModules`]); for package modules, whose variants are not
+//! indexed, any capitalized name the file does not declare itself may be one.
+//!
+//! Each problem comes with a fix removing the import, or the name from its
+//! exposing list.
+
+use std::collections::HashSet;
+
+use tower_lsp::lsp_types::{Diagnostic, Range, TextEdit};
+use tree_sitter::{Node, Tree};
+
+use super::codes;
+use super::imports::whole_lines;
+use super::unused::{unused_warning, without_separator};
+use super::QuickFix;
+use crate::document::node_range;
+
+/// What the workspace index knows of the modules a file imports
+pub trait ImportedModules {
+    /// Whether `module_name` is a module of the workspace or of a package
+    fn is_known(&self, module_name: &str) -> bool;
+    /// Whether `module_name` is a workspace module, whose variants are indexed
+    fn in_workspace(&self, module_name: &str) -> bool;
+    /// Whether the module `module_name` declares `name`
+    fn defines(&self, module_name: &str, name: &str) -> bool;
+    /// The variants of the workspace type `module_name.type_name`
+    fn variants(&self, module_name: &str, type_name: &str) -> Vec<String>;
+}
+
+/// The names a module refers to
+#[derive(Default)]
+struct Usage<'a> {
+    /// `Date` for `Date.format`, `Json.Decode` for `Json.Decode.int`
+    qualifiers: HashSet<&'a str>,
+    /// Unqualified values, types, variants and operators
+    names: HashSet<&'a str>,
+}
+
+/// Report unused imports and unused names in import exposing lists
+pub fn unused_import_diagnostics(
+    tree: &Tree,
+    source: &str,
+    modules: &impl ImportedModules,
+) -> Vec<Diagnostic> {
+    unused_imports(tree, source, modules)
+        .into_iter()
+        .map(|fix| fix.diagnostic)
+        .collect()
+}
+
+/// Fixes for the problems of [`unused_import_diagnostics`] in `range`
+pub fn unused_import_fixes(
+    tree: &Tree,
+    source: &str,
+    modules: &impl ImportedModules,
+    range: Range,
+) -> Vec<QuickFix> {
+    unused_imports(tree, source, modules)
+        .into_iter()
+        .filter(|fix| {
+            let found = fix.diagnostic.range;
+            found.start <= range.end && range.start <= found.end
+        })
+        .collect()
+}
+
+fn unused_imports(tree: &Tree, source: &str, modules: &impl ImportedModules) -> Vec<QuickFix> {
+    // Half-typed code does not say what it uses yet
+    if tree.root_node().has_error() {
+        return Vec::new();
+    }
+
+    let usage = usage(tree, source);
+    let declared = declared_types(tree, source);
+    // A capitalized name nothing here declares may be a package's variant
+    let maybe_variant = |module_name: &str| {
+        !modules.in_workspace(module_name)
+            && usage
+                .names
+                .iter()
+                .any(|name| name.starts_with(char::is_uppercase) && !declared.contains(name))
+    };
+
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let imports: Vec<Node> = root
+        .named_children(&mut cursor)
+        .filter(|n| n.kind() == "import_clause")
+        .collect();
+
+    let mut fixes = Vec::new();
+    for import in imports {
+        let Some(name_node) = import.child_by_field_name("moduleName") else {
+            continue;
+        };
+        let module_name = &source[name_node.byte_range()];
+        let alias = import
+            .child_by_field_name("asClause")
+            .and_then(|a| a.child_by_field_name("name"))
+            .map(|n| &source[n.byte_range()]);
+        let qualified = usage.qualifiers.contains(alias.unwrap_or(module_name));
+
+        let exposed: Vec<Node> = match import.child_by_field_name("exposing") {
+            Some(exposing) => {
+                let mut cursor = exposing.walk();
+                exposing.named_children(&mut cursor).collect()
+            }
+            None => Vec::new(),
+        };
+        let everything = exposed.iter().any(|n| n.kind() == "double_dot");
+        let items: Vec<Node> = exposed
+            .into_iter()
+            .filter(|n| {
+                matches!(
+                    n.kind(),
+                    "exposed_value" | "exposed_type" | "exposed_operator"
+                )
+            })
+            .collect();
+
+        let item_used = |item: &Node| {
+            let text: String = source[item.byte_range()].split_whitespace().collect();
+            match text.strip_suffix("(..)") {
+                Some(type_name) => {
+                    usage.names.contains(type_name)
+                        || modules
+                            .variants(module_name, type_name)
+                            .iter()
+                            .any(|variant| usage.names.contains(variant.as_str()))
+                        || maybe_variant(module_name)
+                }
+                None => usage
+                    .names
+                    .contains(text.trim_start_matches('(').trim_end_matches(')')),
+            }
+        };
+        let used = qualified
+            || items.iter().any(&item_used)
+            || (everything
+                && (!modules.is_known(module_name)
+                    || maybe_variant(module_name)
+                    || usage
+                        .names
+                        .iter()
+                        .any(|name| modules.defines(module_name, name))));
+
+        if !used {
+            fixes.push(QuickFix {
+                title: format!("Remove unused import {}", module_name),
+                diagnostic: unused_warning(
+                    codes::UNUSED_IMPORT,
+                    node_range(import),
+                    format!("`{}` is imported but never used", module_name),
+                ),
+                edits: vec![TextEdit {
+                    range: whole_lines(import),
+                    new_text: String::new(),
+                }],
+            });
+            continue;
+        }
+        for item in &items {
+            if item_used(item) {
+                continue;
+            }
+            // The only name goes with its `exposing (..)`
+            let removal = match items.len() {
+                1 => item.parent().map(|exposing| {
+                    let start = exposing.prev_sibling().unwrap_or(exposing);
+                    Range::new(node_range(start).end, node_range(exposing).end)
+                }),
+                _ => without_separator(*item),
+            };
+            let Some(range) = removal else {
+                continue;
+            };
+            let text: String = source[item.byte_range()].split_whitespace().collect();
+            fixes.push(QuickFix {
+                title: format!("Remove unused {} from the import", text),
+                diagnostic: unused_warning(
+                    codes::UNUSED_EXPOSED,
+                    node_range(*item),
+                    format!(
+                        "`{}` is exposed by the import of `{}` but never used",
+                        text, module_name
+                    ),
+                ),
+                edits: vec![TextEdit {
+                    range,
+                    new_text: String::new(),
+                }],
+            });
+        }
+    }
+    fixes
+}
+
+/// The names referred to outside the module header and imports
+fn usage<'a>(tree: &Tree, source: &'a str) -> Usage<'a> {
+    let mut usage = Usage::default();
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let mut stack: Vec<Node> = root
+        .named_children(&mut cursor)
+        .filter(|n| !matches!(n.kind(), "module_declaration" | "import_clause"))
+        .collect();
+    while let Some(node) = stack.pop() {
+        let text = &source[node.byte_range()];
+        match node.kind() {
+            "value_qid" | "upper_case_qid" => match text.rsplit_once('.') {
+                Some((qualifier, _)) => {
+                    usage.qualifiers.insert(qualifier);
+                }
+                None => {
+                    usage.names.insert(text);
+                }
+            },
+            "operator_identifier" | "record_base_identifier" => {
+                usage.names.insert(text);
+            }
+            _ => {
+                let mut cursor = node.walk();
+                stack.extend(node.named_children(&mut cursor));
+            }
+        }
+    }
+    usage
+}
+
+/// The types, type aliases and variants the module declares
+fn declared_types<'a>(tree: &Tree, source: &'a str) -> HashSet<&'a str> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let mut declared = HashSet::new();
+    for declaration in root.named_children(&mut cursor) {
+        if !matches!(
+            declaration.kind(),
+            "type_declaration" | "type_alias_declaration"
+        ) {
+            continue;
+        }
+        declared.extend(
+            declaration
+                .child_by_field_name("name")
+                .map(|name| &source[name.byte_range()]),
+        );
+        let mut cursor = declaration.walk();
+        declared.extend(
+            declaration
+                .children_by_field_name("unionVariant", &mut cursor)
+                .filter_map(|variant| variant.child_by_field_name("name"))
+                .map(|name| &source[name.byte_range()]),
+        );
+    }
+    declared
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ElmParser;
+    use tower_lsp::lsp_types::Position;
+
+    /// Modules known to a test: `Html`, a package, and `Page`, with
+    /// `type Tab = Home | Settings`
+    struct Modules;
+
+    impl ImportedModules for Modules {
+        fn is_known(&self, module_name: &str) -> bool {
+            matches!(module_name, "Html" | "Page")
+        }
+
+        fn in_workspace(&self, module_name: &str) -> bool {
+            module_name == "Page"
+        }
+
+        fn defines(&self, module_name: &str, name: &str) -> bool {
+            match module_name {
+                "Html" => matches!(name, "div" | "text"),
+                "Page" => matches!(name, "Tab" | "Home" | "Settings"),
+                _ => false,
+            }
+        }
+
+        fn variants(&self, module_name: &str, type_name: &str) -> Vec<String> {
+            match (module_name, type_name) {
+                ("Page", "Tab") => vec!["Home".to_string(), "Settings".to_string()],
+                _ => Vec::new(),
+            }
+        }
+    }
+
+    fn fixes(source: &str) -> Vec<QuickFix> {
+        let tree = ElmParser::new().parse(source).unwrap();
+        let everything = Range::new(Position::new(0, 0), Position::new(u32::MAX, 0));
+        unused_import_fixes(&tree, source, &Modules, everything)
+    }
+
+    #[test]
+    fn test_unused_imports_through_variants_and_everything() {
+        let source = "module Main exposing (main)\n\nimport Html exposing (..)\nimport Page exposing (Tab(..))\nimport Set\n\n\nmain =\n    always Home (div [] [])\n";
+        let messages: Vec<String> = fixes(source)
+            .into_iter()
+            .map(|fix| fix.diagnostic.message)
+            .collect();
+        // `div` comes from Html and `Home` from `Tab(..)`; nothing is
+        // qualified with `Set`
+        assert_eq!(messages, ["`Set` is imported but never used"]);
+    }
+
+    #[test]
+    fn test_unused_only_exposed_name_drops_the_exposing_list() {
+        let source = "module Main exposing (main)\n\nimport Html exposing (text)\n\n\nmain =\n    Html.div [] []\n";
+        let fixes = fixes(source);
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(
+            fixes[0].edits[0].range,
+            Range::new(Position::new(2, 11), Position::new(2, 27))
+        );
+    }
+}
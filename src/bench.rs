@@ -0,0 +1,222 @@
+//! `elm-lsp --bench-index <project>`: timings of indexing a project.
+//!
+//! The report covers a cold index of the whole project, a warm update of its
+//! largest module after an edit and back, and the queries editors send most:
+//! references to the most imported module's first declaration and workspace
+//! symbol searches. Each query runs a few times and reports its median. The
+//! report is plain text meant to be pasted into a performance issue.
+
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use tower_lsp::lsp_types::Url;
+
+use crate::document::read_source;
+use crate::workspace::Workspace;
+
+/// How often each query runs
+const RUNS: usize = 5;
+
+/// Benchmark indexing the project at `project` and write the report to `out`
+pub fn run(project: &Path, out: &mut impl Write) -> anyhow::Result<()> {
+    let project = project
+        .canonicalize()
+        .with_context(|| format!("No project at {}", project.display()))?;
+    if !project.join("elm.json").is_file() {
+        anyhow::bail!("No elm.json in {}", project.display());
+    }
+
+    writeln!(out, "elm-lsp {} --bench-index", env!("CARGO_PKG_VERSION"))?;
+    writeln!(out, "project: {}", project.display())?;
+    writeln!(
+        out,
+        "platform: {}-{}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    )?;
+    writeln!(out)?;
+
+    let rss_before = resident_memory();
+    let start = Instant::now();
+    let mut workspace = Workspace::new(project.clone());
+    workspace.initialize()?;
+    let cold = start.elapsed();
+    let stats = workspace.index_stats();
+    writeln!(
+        out,
+        "cold index: {} ({} modules, {} light, {} symbols, {} package modules)",
+        format_duration(cold),
+        stats.modules,
+        stats.light_modules,
+        stats.symbols,
+        stats.external_modules
+    )?;
+
+    // The largest module, edited and restored
+    if let Some(module) = workspace
+        .modules
+        .values()
+        .filter(|m| !m.light)
+        .max_by_key(|m| m.symbols.len())
+    {
+        let path = module.path.clone();
+        let name = module.module_name.clone();
+        let content = read_source(&path)?;
+        let uri = Url::from_file_path(&path).map_err(|_| anyhow::anyhow!("Invalid path"))?;
+        let edited = format!("{}\n-- bench-index\n", content);
+        let update = median(|| {
+            workspace.update_file(&uri, &edited);
+            workspace.update_file(&uri, &content);
+        }) / 2;
+        writeln!(out, "warm update: {} ({})", format_duration(update), name)?;
+    }
+
+    // References to the first declaration of the most imported module
+    let mut importers: Vec<(usize, &str)> = workspace
+        .modules
+        .values()
+        .map(|m| {
+            let count = workspace
+                .modules
+                .values()
+                .filter(|other| other.imports.iter().any(|i| i.module_name == m.module_name))
+                .count();
+            (count, m.module_name.as_str())
+        })
+        .collect();
+    importers.sort();
+    let target = importers.iter().rev().find_map(|(_, module_name)| {
+        let module = workspace.modules.get(*module_name)?;
+        let symbol = module.symbols.first()?;
+        Some((module_name.to_string(), symbol.name.clone()))
+    });
+    if let Some((module_name, name)) = target {
+        let mut count = 0;
+        let time = median(|| count = workspace.find_references(&name, Some(&module_name)).len());
+        writeln!(
+            out,
+            "references: {} ({}.{}, {} found)",
+            format_duration(time),
+            module_name,
+            name,
+            count
+        )?;
+    }
+
+    for query in ["", "view", "msg"] {
+        let mut count = 0;
+        let time = median(|| count = workspace.search_symbols(query).len());
+        writeln!(
+            out,
+            "workspace symbols {:?}: {} ({} found)",
+            query,
+            format_duration(time),
+            count
+        )?;
+    }
+
+    writeln!(out)?;
+    match (rss_before, resident_memory()) {
+        (Some((before, _)), Some((after, peak))) => writeln!(
+            out,
+            "memory: {} resident ({} for the index), {} peak",
+            format_bytes(after),
+            format_bytes(after.saturating_sub(before)),
+            format_bytes(peak)
+        )?,
+        _ => writeln!(out, "memory: not available on this platform")?,
+    }
+    Ok(())
+}
+
+/// The median time of running `f` [`RUNS`] times
+fn median(mut f: impl FnMut()) -> Duration {
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    times[RUNS / 2]
+}
+
+/// The current and peak resident memory of this process, in bytes
+fn resident_memory() -> Option<(u64, u64)> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let field = |name: &str| {
+        let line = status.lines().find(|line| line.starts_with(name))?;
+        let kilobytes: u64 = line[name.len()..]
+            .trim()
+            .trim_end_matches("kB")
+            .trim()
+            .parse()
+            .ok()?;
+        Some(kilobytes * 1024)
+    };
+    Some((field("VmRSS:")?, field("VmHWM:")?))
+}
+
+fn format_duration(duration: Duration) -> String {
+    let micros = duration.as_micros();
+    if micros >= 1_000_000 {
+        format!("{:.2}s", duration.as_secs_f64())
+    } else if micros >= 1_000 {
+        format!("{:.1}ms", micros as f64 / 1_000.0)
+    } else {
+        format!("{}µs", micros)
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_bench_index_reports_each_step() {
+        let temp_dir = TempDir::new().unwrap();
+        let src = temp_dir.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(
+            temp_dir.path().join("elm.json"),
+            r#"{ "type": "application", "source-directories": ["src"] }"#,
+        )
+        .unwrap();
+        fs::write(
+            src.join("Helper.elm"),
+            "module Helper exposing (helper)\n\n\nhelper =\n    1\n",
+        )
+        .unwrap();
+        fs::write(
+            src.join("Main.elm"),
+            "module Main exposing (main)\n\nimport Helper\n\n\nmain =\n    Helper.helper\n",
+        )
+        .unwrap();
+
+        let mut out = Vec::new();
+        run(temp_dir.path(), &mut out).unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.contains("cold index: "), "{}", report);
+        assert!(report.contains("(2 modules"), "{}", report);
+        assert!(report.contains("warm update: "), "{}", report);
+        assert!(report.contains("references: "), "{}", report);
+        assert!(report.contains("(Helper.helper, "), "{}", report);
+        assert!(
+            report.contains("workspace symbols \"view\": "),
+            "{}",
+            report
+        );
+        assert!(report.contains("memory: "), "{}", report);
+
+        assert!(run(&src, &mut Vec::new()).is_err());
+    }
+}
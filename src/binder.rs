@@ -219,6 +219,28 @@ impl<'a> Binder<'a> {
 
         // Bind the rest as a container (for let bindings)
         self.push_container(node.id());
+
+        // Parameters written without parentheses, like `x` or `( a, b )`, are
+        // not wrapped in a `pattern` node, so bind_pattern does not see them
+        if let Some(func_decl_left) = node.child_by_field_name("functionDeclarationLeft") {
+            let mut cursor = func_decl_left.walk();
+            for param in func_decl_left.children_by_field_name("pattern", &mut cursor) {
+                match param.kind() {
+                    "lower_pattern" => {
+                        let name = self.node_text(param).to_string();
+                        self.add_symbol(BoundSymbol {
+                            name,
+                            node_id: param.id(),
+                            kind: BoundSymbolKind::FunctionParameter,
+                            constructors: vec![],
+                        });
+                    }
+                    "pattern" => {}
+                    _ => self.bind_function_parameters(param),
+                }
+            }
+        }
+
         self.bind_children(node);
         self.pop_container();
     }
@@ -597,6 +619,27 @@ hello name = name
         assert!(symbols.contains_key("hello"));
     }
 
+    #[test]
+    fn test_bind_function_parameters() {
+        let source = r#"
+module Test exposing (..)
+
+f x ( y, _ ) ({ a } as r) = x
+"#;
+        let tree = parse(source);
+        let links = bind_tree(source, &tree);
+
+        let declaration = tree.root_node().named_child(1).unwrap();
+        let symbols = links.get_container(declaration.id()).unwrap();
+        let mut names: Vec<&String> = symbols.keys().collect();
+        names.sort();
+        assert_eq!(names, ["a", "r", "x", "y"]);
+        assert!(symbols
+            .values()
+            .flatten()
+            .all(|s| s.kind == BoundSymbolKind::FunctionParameter));
+    }
+
     #[test]
     fn test_bind_type_alias() {
         let source = r#"
@@ -0,0 +1,169 @@
+//! Limits on the work the server does at once.
+//!
+//! Indexing parses and type checks files on a few threads, and each project
+//! runs a bounded number of compiler processes. Both limits come from the
+//! settings, where 0 means a default derived from the machine's cores.
+
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Threads for indexing: every core
+pub fn default_index_threads() -> usize {
+    cores()
+}
+
+/// Compiler processes per project: half the cores, since each `elm make`
+/// uses several threads of its own
+pub fn default_max_processes() -> usize {
+    (cores() / 2).max(1)
+}
+
+fn cores() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// `f` applied to every item on up to `threads` threads, in the items' order
+pub fn map_in_parallel<T, R, F>(items: Vec<T>, threads: usize, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    let threads = threads.clamp(1, items.len().max(1));
+    if threads == 1 {
+        return items.into_iter().map(f).collect();
+    }
+
+    // Item `i` goes to thread `i % threads`, which keeps the load even when
+    // similar files sit next to each other
+    let mut queues: Vec<Vec<(usize, T)>> = (0..threads).map(|_| Vec::new()).collect();
+    for (i, item) in items.into_iter().enumerate() {
+        queues[i % threads].push((i, item));
+    }
+    let f = &f;
+    let mut results: Vec<(usize, R)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = queues
+            .into_iter()
+            .map(|queue| {
+                scope.spawn(move || {
+                    queue
+                        .into_iter()
+                        .map(|(i, item)| (i, f(item)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// A bound on processes running at once, shared by everything that runs
+/// them for one project
+#[derive(Debug)]
+pub struct ProcessLimit {
+    state: Mutex<Slots>,
+    freed: Condvar,
+}
+
+#[derive(Debug)]
+struct Slots {
+    limit: usize,
+    running: usize,
+}
+
+/// A slot taken from a [`ProcessLimit`], given back when dropped
+pub struct ProcessPermit {
+    limit: Arc<ProcessLimit>,
+}
+
+impl ProcessLimit {
+    /// At most `limit` processes at once, or the default for 0
+    pub fn new(limit: usize) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(Slots {
+                limit: resolve(limit, default_max_processes),
+                running: 0,
+            }),
+            freed: Condvar::new(),
+        })
+    }
+
+    /// Change the limit; processes already running keep going
+    pub fn set_limit(&self, limit: usize) {
+        if let Ok(mut slots) = self.state.lock() {
+            slots.limit = resolve(limit, default_max_processes);
+        }
+        self.freed.notify_all();
+    }
+
+    /// Wait for a free slot
+    pub fn acquire(self: &Arc<Self>) -> ProcessPermit {
+        let mut slots = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        while slots.running >= slots.limit {
+            slots = self.freed.wait(slots).unwrap_or_else(|e| e.into_inner());
+        }
+        slots.running += 1;
+        ProcessPermit {
+            limit: Arc::clone(self),
+        }
+    }
+}
+
+impl Drop for ProcessPermit {
+    fn drop(&mut self) {
+        if let Ok(mut slots) = self.limit.state.lock() {
+            slots.running -= 1;
+        }
+        self.limit.freed.notify_one();
+    }
+}
+
+/// `value`, or the default when it is 0
+pub fn resolve(value: usize, default: fn() -> usize) -> usize {
+    if value == 0 {
+        default()
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_map_in_parallel_keeps_order() {
+        let items: Vec<usize> = (0..100).collect();
+        let doubled = map_in_parallel(items, 4, |i| i * 2);
+        assert_eq!(doubled, (0..100).map(|i| i * 2).collect::<Vec<_>>());
+        assert!(map_in_parallel(Vec::<usize>::new(), 4, |i| i).is_empty());
+    }
+
+    #[test]
+    fn test_process_limit_bounds_running_processes() {
+        let limit = ProcessLimit::new(2);
+        let running = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    let _permit = limit.acquire();
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        assert!(most.load(Ordering::SeqCst) <= 2);
+    }
+}
@@ -0,0 +1,245 @@
+//! Server settings.
+//!
+//! Settings come from the client's `initializationOptions` and from
+//! `workspace/didChangeConfiguration`, either at the top level or nested under
+//! an `elmLsp` section. Unknown or malformed values fall back to the defaults.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tower_lsp::lsp_types::DiagnosticSeverity;
+
+use crate::concurrency;
+use crate::logging;
+
+/// User-configurable settings
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    /// Files larger than this many bytes only get basic navigation (0 disables the limit)
+    pub max_file_size: usize,
+    /// Glob patterns, relative to the workspace root, for files that only get basic navigation
+    pub exclude: Vec<String>,
+    /// Glob patterns, relative to the workspace root, for generated code: indexed for
+    /// navigation only, left out of refactors and of unused-code warnings
+    pub generated: Vec<String>,
+    /// Include usages in `tests/` in find-references and rename results
+    pub include_tests: bool,
+    /// Also rename the old name inside doc comments and matching string literals
+    pub rename_in_docs: bool,
+    /// Most results returned by workspace/symbol, best matches first (0 disables the limit)
+    pub workspace_symbol_limit: usize,
+    /// Report code that is correct but slow, like `acc ++ [ x ]` in a fold
+    pub performance_hints: bool,
+    /// Severity of the server's own diagnostics by code, like
+    /// `{ "unused-parameter": "hint" }`; codes not listed keep their own
+    pub diagnostic_severity: BTreeMap<String, Severity>,
+    /// How `elm.organizeDeclarations` orders a module's declarations
+    pub declaration_order: DeclarationOrder,
+    /// Log filter, like `info` or `warn,elm_lsp::refactor=debug`; empty uses `RUST_LOG`
+    pub log_level: String,
+    /// Threads that parse and type check files while indexing (0 uses every core)
+    pub index_threads: usize,
+    /// Compiler processes each project runs at once (0 uses half the cores)
+    pub max_processes: usize,
+    /// For untrusted projects: never run the compiler, formatter or other
+    /// external tools, and never write files except through edits the client applies
+    pub restricted: bool,
+}
+
+/// How a diagnostic code is reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    #[serde(alias = "info")]
+    Information,
+    Hint,
+    /// Not reported at all
+    Off,
+}
+
+impl Severity {
+    /// The LSP severity, or None for [`Severity::Off`]
+    pub fn to_lsp(self) -> Option<DiagnosticSeverity> {
+        match self {
+            Severity::Error => Some(DiagnosticSeverity::ERROR),
+            Severity::Warning => Some(DiagnosticSeverity::WARNING),
+            Severity::Information => Some(DiagnosticSeverity::INFORMATION),
+            Severity::Hint => Some(DiagnosticSeverity::HINT),
+            Severity::Off => None,
+        }
+    }
+}
+
+/// Policy for ordering top-level declarations. Each rule applies after the
+/// ones above it; declarations the rules do not tell apart keep their order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DeclarationOrder {
+    /// What the module exposes comes before what it keeps private
+    pub exposed_first: bool,
+    /// Types and type aliases come before ports, which come before values
+    pub types_first: bool,
+    /// `main`, `init`, `update`, `subscriptions` and `view` (and Lamdera's
+    /// `app`, `updateFromFrontend` and `updateFromBackend`) come first among
+    /// values, in that order
+    pub group_update_view: bool,
+}
+
+impl Default for DeclarationOrder {
+    fn default() -> Self {
+        Self {
+            exposed_first: true,
+            types_first: true,
+            group_update_view: true,
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            max_file_size: 1024 * 1024,
+            exclude: vec!["**/Generated/**".to_string(), "**/.elm-spa/**".to_string()],
+            generated: vec![
+                "**/.elm-pages/**".to_string(),
+                "**/.elm-spa/gen/**".to_string(),
+                "**/src/Evergreen/**".to_string(),
+            ],
+            include_tests: true,
+            rename_in_docs: false,
+            workspace_symbol_limit: 500,
+            performance_hints: false,
+            diagnostic_severity: BTreeMap::new(),
+            declaration_order: DeclarationOrder::default(),
+            log_level: String::new(),
+            index_threads: 0,
+            max_processes: 0,
+            restricted: false,
+        }
+    }
+}
+
+impl Settings {
+    /// Read settings from a client JSON payload
+    pub fn from_json(value: &serde_json::Value) -> Self {
+        let section = value.get("elmLsp").unwrap_or(value);
+        serde_json::from_value(section.clone()).unwrap_or_else(|e| {
+            tracing::warn!(target: logging::INDEX, "Ignoring invalid settings: {}", e);
+            Self::default()
+        })
+    }
+
+    /// Threads to index with, from `indexThreads` or the number of cores
+    pub fn index_thread_count(&self) -> usize {
+        concurrency::resolve(self.index_threads, concurrency::default_index_threads)
+    }
+
+    /// True if a file should skip heavy analyses (references, type inference)
+    pub fn is_light_file(&self, relative_path: &Path, size: usize) -> bool {
+        if self.max_file_size > 0 && size > self.max_file_size {
+            return true;
+        }
+        let path = relative_path.to_string_lossy().replace('\\', "/");
+        self.exclude
+            .iter()
+            .any(|pattern| glob_match(pattern, &path))
+            || self.is_generated(relative_path)
+    }
+
+    /// True if a file is generated, so refactors must not change it
+    pub fn is_generated(&self, relative_path: &Path) -> bool {
+        let path = relative_path.to_string_lossy().replace('\\', "/");
+        self.generated
+            .iter()
+            .any(|pattern| glob_match(pattern, &path))
+    }
+}
+
+/// Match a path against a glob supporting `**` (any number of directories),
+/// `*` (anything within one path segment) and `?` (one character)
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').filter(|s| !s.is_empty()).collect();
+    let path: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    match_segments(&pattern, &path)
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_segments(rest, &path[skip..])),
+        Some((segment, rest)) => path.split_first().is_some_and(|(name, path_rest)| {
+            match_segment(segment.as_bytes(), name.as_bytes()) && match_segments(rest, path_rest)
+        }),
+    }
+}
+
+fn match_segment(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| match_segment(rest, &name[skip..])),
+        Some((b'?', rest)) => !name.is_empty() && match_segment(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_segment(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("**/Generated/**", "src/Generated/Api.elm"));
+        assert!(glob_match("**/Generated/**", "Generated/Api/Types.elm"));
+        assert!(!glob_match("**/Generated/**", "src/GeneratedApi.elm"));
+        assert!(glob_match("**/.elm-spa/**", ".elm-spa/defaults/Main.elm"));
+        assert!(glob_match("src/*.elm", "src/Main.elm"));
+        assert!(!glob_match("src/*.elm", "src/Pages/Home.elm"));
+        assert!(glob_match("src/Page?.elm", "src/Pages.elm"));
+    }
+
+    #[test]
+    fn test_settings_from_json() {
+        let settings = Settings::from_json(&serde_json::json!({
+            "elmLsp": { "maxFileSize": 10, "exclude": ["gen/**"] }
+        }));
+        assert_eq!(settings.max_file_size, 10);
+        assert!(settings.is_light_file(Path::new("src/Main.elm"), 11));
+        assert!(settings.is_light_file(Path::new("gen/Api.elm"), 1));
+        assert!(!settings.is_light_file(Path::new("src/Main.elm"), 10));
+
+        // Missing fields keep their defaults
+        let settings = Settings::from_json(&serde_json::json!({ "maxFileSize": 0 }));
+        assert_eq!(settings.exclude, Settings::default().exclude);
+        assert!(!settings.is_light_file(Path::new("src/Main.elm"), usize::MAX));
+
+        // Generated code is navigation only too
+        assert!(settings.is_generated(Path::new(".elm-pages/Main.elm")));
+        assert!(settings.is_generated(Path::new("src/Evergreen/V1/Types.elm")));
+        assert!(settings.is_light_file(Path::new(".elm-spa/gen/Pages.elm"), 1));
+        assert!(!settings.is_generated(Path::new("src/Types.elm")));
+
+        // 0 picks a thread count from the cores
+        assert!(settings.index_thread_count() >= 1);
+        let settings = Settings::from_json(&serde_json::json!({ "indexThreads": 3 }));
+        assert_eq!(settings.index_thread_count(), 3);
+        assert!(!settings.restricted);
+        let restricted = serde_json::json!({ "elmLsp": { "restricted": true } });
+        assert!(Settings::from_json(&restricted).restricted);
+    }
+
+    #[test]
+    fn test_diagnostic_severity_from_json() {
+        let settings = Settings::from_json(&serde_json::json!({
+            "diagnosticSeverity": { "unused-parameter": "hint", "infinite-recursion": "off", "not-exposed": "info" }
+        }));
+        let severity = |code: &str| settings.diagnostic_severity.get(code).copied();
+        assert_eq!(severity("unused-parameter"), Some(Severity::Hint));
+        assert_eq!(severity("infinite-recursion"), Some(Severity::Off));
+        assert_eq!(severity("not-exposed"), Some(Severity::Information));
+        assert_eq!(Severity::Off.to_lsp(), None);
+        assert_eq!(severity("syntax-error"), None);
+    }
+}
@@ -1,7 +1,17 @@
+//! Compiler diagnostics: `elm make` or `lamdera make` run on a file, with
+//! their JSON report turned into LSP diagnostics.
+
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::path::Path;
 use std::process::Command;
-use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range, Url};
+use std::sync::{Arc, Mutex};
+use tower_lsp::lsp_types::{
+    CodeDescription, Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range, Url,
+};
+
+use crate::concurrency::ProcessLimit;
+use crate::logging;
 
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
@@ -69,18 +79,61 @@ impl std::fmt::Display for MessagePart {
 }
 
 pub struct DiagnosticsProvider {
-    workspace_root: Option<String>,
+    workspace_roots: Vec<String>,
+    /// Compiler processes each project may run at once (0: the default)
+    max_processes: usize,
+    /// Each project's running compiler processes, keyed by its root
+    limits: Mutex<HashMap<String, Arc<ProcessLimit>>>,
+    /// Restricted mode: never run the compiler
+    restricted: bool,
 }
 
 impl DiagnosticsProvider {
     pub fn new() -> Self {
         Self {
-            workspace_root: None,
+            workspace_roots: Vec::new(),
+            max_processes: 0,
+            limits: Mutex::new(HashMap::new()),
+            restricted: false,
+        }
+    }
+
+    pub fn add_workspace_root(&mut self, root: &str) {
+        if !self.workspace_roots.iter().any(|r| r == root) {
+            self.workspace_roots.push(root.to_string());
+        }
+    }
+
+    pub fn remove_workspace_root(&mut self, root: &str) {
+        self.workspace_roots.retain(|r| r != root);
+        if let Ok(mut limits) = self.limits.lock() {
+            limits.remove(root);
         }
     }
 
-    pub fn set_workspace_root(&mut self, root: &str) {
-        self.workspace_root = Some(root.to_string());
+    /// Apply the `maxProcesses` setting, to running projects too
+    pub fn set_max_processes(&mut self, max_processes: usize) {
+        self.max_processes = max_processes;
+        if let Ok(limits) = self.limits.lock() {
+            for limit in limits.values() {
+                limit.set_limit(max_processes);
+            }
+        }
+    }
+
+    /// Apply the `restricted` setting: compiler diagnostics stop until it is lifted
+    pub fn set_restricted(&mut self, restricted: bool) {
+        self.restricted = restricted;
+    }
+
+    /// The process limit of the project at `root`
+    fn limit(&self, root: &str) -> Arc<ProcessLimit> {
+        let mut limits = self.limits.lock().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(
+            limits
+                .entry(root.to_string())
+                .or_insert_with(|| ProcessLimit::new(self.max_processes)),
+        )
     }
 
     /// Find elm.json in parent directories
@@ -97,16 +150,24 @@ impl DiagnosticsProvider {
         }
     }
 
-    /// Run elm/lamdera make and get diagnostics
-    pub fn get_diagnostics(&self, file_uri: &Url) -> Vec<Diagnostic> {
+    /// Run `elm make`, or `lamdera make` for a Lamdera project, on the file
+    /// as saved on disk and get diagnostics
+    pub fn get_diagnostics(&self, file_uri: &Url, lamdera: bool) -> Vec<Diagnostic> {
+        if self.restricted {
+            return vec![];
+        }
         let file_path = match file_uri.to_file_path() {
             Ok(p) => p.to_string_lossy().to_string(),
             Err(_) => return vec![],
         };
 
+        // The deepest open folder containing the file, else the nearest elm.json
         let workspace_root = self
-            .workspace_root
-            .clone()
+            .workspace_roots
+            .iter()
+            .filter(|root| Path::new(&file_path).starts_with(root))
+            .max_by_key(|root| root.len())
+            .cloned()
             .or_else(|| Self::find_workspace_root(&file_path));
 
         let workspace_root = match workspace_root {
@@ -114,22 +175,31 @@ impl DiagnosticsProvider {
             None => return vec![],
         };
 
-        // Try lamdera first, fall back to elm
-        let output = Command::new("lamdera")
-            .args(["make", &file_path, "--report=json", "--output=/dev/null"])
-            .current_dir(&workspace_root)
-            .output()
-            .or_else(|_| {
-                Command::new("elm")
-                    .args(["make", &file_path, "--report=json", "--output=/dev/null"])
-                    .current_dir(&workspace_root)
-                    .output()
-            });
+        // The project's own compiler first; a Lamdera project falls back to
+        // elm when lamdera is not installed
+        let elm_home = crate::tools::elm_home();
+        let tool = |name| crate::tools::find_tool(Path::new(&workspace_root), &elm_home, name);
+        let make = |name| {
+            Command::new(tool(name))
+                .args(["make", &file_path, "--report=json", "--output=/dev/null"])
+                .current_dir(&workspace_root)
+                .output()
+        };
+        let _permit = self.limit(&workspace_root).acquire();
+        let output = if lamdera {
+            make("lamdera").or_else(|_| make("elm"))
+        } else {
+            make("elm")
+        };
 
         let output = match output {
             Ok(o) => o,
             Err(e) => {
-                tracing::error!("Failed to run elm/lamdera make: {}", e);
+                tracing::error!(
+                    target: logging::DIAGNOSTICS,
+                    "Failed to run elm/lamdera make: {}",
+                    e
+                );
                 return vec![];
             }
         };
@@ -177,8 +247,12 @@ impl DiagnosticsProvider {
                 }]
             }
             Err(e) => {
-                tracing::error!("Failed to parse elm make output: {}", e);
-                tracing::debug!("Output was: {}", output);
+                tracing::error!(
+                    target: logging::DIAGNOSTICS,
+                    "Failed to parse elm make output: {}",
+                    e
+                );
+                tracing::debug!(target: logging::DIAGNOSTICS, "Output was: {}", output);
                 vec![]
             }
         }
@@ -206,8 +280,8 @@ impl DiagnosticsProvider {
             range: Range::new(start, end),
             severity: Some(DiagnosticSeverity::ERROR),
             source: Some("elm".to_string()),
-            code: None,
-            code_description: None,
+            code: Some(NumberOrString::String(problem_code(&problem.title))),
+            code_description: hint_link(&message_text).map(|href| CodeDescription { href }),
             message: format!("{}\n\n{}", problem.title, message_text.trim()),
             related_information: None,
             tags: None,
@@ -216,6 +290,23 @@ impl DiagnosticsProvider {
     }
 }
 
+/// The code of a compiler problem: its title in kebab case, like
+/// `type-mismatch` for `TYPE MISMATCH`
+fn problem_code(title: &str) -> String {
+    title
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// The first hint the compiler links to, written `<https://elm-lang.org/0.19.1/...>`
+fn hint_link(message: &str) -> Option<Url> {
+    let start = message.find("<https://")? + 1;
+    let end = start + message[start..].find('>')?;
+    Url::parse(&message[start..end]).ok()
+}
+
 impl Default for DiagnosticsProvider {
     fn default() -> Self {
         Self::new()
@@ -238,4 +329,24 @@ mod tests {
         assert_eq!(diagnostics[0].range.start.line, 2); // 0-indexed
         assert_eq!(diagnostics[0].range.start.character, 6); // 0-indexed
     }
+
+    #[test]
+    fn test_compile_error_code_and_hint_link() {
+        let json = r#"{"type":"compile-errors","errors":[{"path":"/test/Bad.elm","name":"Bad","problems":[{"title":"BAD RECURSION","region":{"start":{"line":3,"column":1},"end":{"line":3,"column":2}},"message":["`x` is defined in terms of itself.\n\nRead <https://elm-lang.org/0.19.1/bad-recursion> for more."]}]}]}"#;
+
+        let provider = DiagnosticsProvider::new();
+        let diagnostics = provider.parse_elm_output(json, "/test/Bad.elm");
+
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String("bad-recursion".to_string()))
+        );
+        assert_eq!(
+            diagnostics[0]
+                .code_description
+                .as_ref()
+                .map(|d| d.href.as_str()),
+            Some("https://elm-lang.org/0.19.1/bad-recursion")
+        );
+    }
 }
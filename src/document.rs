@@ -1,5 +1,137 @@
+//! Open documents and the symbols extracted from them.
+
+use std::borrow::Cow;
+use std::path::Path;
 use tower_lsp::lsp_types::*;
 
+/// Line ending style of a file, so edits can be written back in the same style
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// Detect the style from the first line break in the text
+    pub fn detect(text: &str) -> Self {
+        match text.find('\n') {
+            Some(i) if text[..i].ends_with('\r') => LineEnding::CrLf,
+            _ => LineEnding::Lf,
+        }
+    }
+
+    /// Detect the style of a file on disk (LF if it cannot be read)
+    pub fn of_file(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .map(|text| Self::detect(&text))
+            .unwrap_or_default()
+    }
+
+    /// Convert LF line breaks in generated text to this style
+    pub fn apply<'a>(self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            LineEnding::CrLf if text.contains('\n') => Cow::Owned(text.replace('\n', "\r\n")),
+            _ => Cow::Borrowed(text),
+        }
+    }
+}
+
+/// Strip a UTF-8 BOM and convert CRLF to LF, so tree-sitter rows and columns match
+/// LSP positions (clients neither count the BOM nor the `\r` before a line break)
+pub fn normalize_text(text: &str) -> Cow<'_, str> {
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(text);
+    if text.contains('\r') {
+        Cow::Owned(text.replace("\r\n", "\n"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Owned version of `normalize_text` that avoids a copy when nothing changes
+fn into_normalized(text: String) -> String {
+    match normalize_text(&text) {
+        Cow::Borrowed(_) => text,
+        Cow::Owned(normalized) => normalized,
+    }
+}
+
+/// Read an Elm source file with its text normalized by `normalize_text`
+pub fn read_source(path: &Path) -> std::io::Result<String> {
+    std::fs::read_to_string(path).map(into_normalized)
+}
+
+/// The byte offset of `position` in `text`. Columns are byte offsets within
+/// the line, as in the edits computed from the syntax tree. A column past the
+/// end of its line stands for the line's end, and a line past the end of the
+/// text for the text's end.
+pub fn position_to_offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let line_end = text[line_start..]
+        .find('\n')
+        .map_or(text.len(), |i| line_start + i);
+    (line_start + position.character as usize).min(line_end)
+}
+
+/// The position of byte `offset` in `text`, the inverse of
+/// [`position_to_offset`]: columns are byte offsets within the line.
+pub fn offset_to_position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1);
+    Position::new(line as u32, column as u32)
+}
+
+/// The range of a syntax node. Columns are byte offsets within the line, as
+/// tree-sitter counts them.
+pub fn node_range(node: tree_sitter::Node) -> Range {
+    let (start, end) = (node.start_position(), node.end_position());
+    Range::new(
+        Position::new(start.row as u32, start.column as u32),
+        Position::new(end.row as u32, end.column as u32),
+    )
+}
+
+/// Apply `edits` to `text`. Every range refers to `text` as it is before any
+/// edit, as in a `WorkspaceEdit`, so the order of the edits only matters for
+/// insertions at the same position, which keep it. Fails when two edits
+/// overlap or a range splits a character.
+pub fn apply_edits(text: &str, edits: &[TextEdit]) -> anyhow::Result<String> {
+    let mut spans: Vec<(usize, usize, &str)> = edits
+        .iter()
+        .map(|edit| {
+            let start = position_to_offset(text, edit.range.start);
+            let end = position_to_offset(text, edit.range.end).max(start);
+            (start, end, edit.new_text.as_str())
+        })
+        .collect();
+    // Stable, so insertions at one position stay in order, before a
+    // replacement starting there
+    spans.sort_by_key(|&(start, end, _)| (start, end));
+
+    let mut result = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (start, end, new_text) in spans {
+        if start < copied {
+            return Err(anyhow::anyhow!("Edits overlap at byte {}", start));
+        }
+        if !text.is_char_boundary(start) || !text.is_char_boundary(end) {
+            return Err(anyhow::anyhow!("Edit splits a character at byte {}", start));
+        }
+        result.push_str(&text[copied..start]);
+        result.push_str(new_text);
+        copied = end;
+    }
+    result.push_str(&text[copied..]);
+    Ok(result)
+}
+
 #[derive(Debug, Clone)]
 pub struct VariantInfo {
     pub name: String,
@@ -16,6 +148,10 @@ pub struct ElmSymbol {
     pub type_annotation_range: Option<Range>,
     pub signature: Option<String>,
     pub documentation: Option<String>,
+    /// `--` comments directly above the declaration, without the `--` prefix
+    pub leading_comments: Vec<String>,
+    /// Range covering the doc comment and leading comments attached to the declaration
+    pub comments_range: Option<Range>,
     pub references: Vec<Range>,
     pub variants: Vec<VariantInfo>,
 }
@@ -30,6 +166,8 @@ impl ElmSymbol {
             type_annotation_range: None,
             signature: None,
             documentation: None,
+            leading_comments: Vec::new(),
+            comments_range: None,
             references: Vec::new(),
             variants: Vec::new(),
         }
@@ -49,6 +187,25 @@ impl ElmSymbol {
         true
     }
 
+    /// The type annotation on one line, cut to `max_width` characters, for
+    /// outline views. Types and aliases have none.
+    pub fn annotation_detail(&self, max_width: usize) -> Option<String> {
+        if !matches!(self.kind, SymbolKind::FUNCTION | SymbolKind::INTERFACE) {
+            return None;
+        }
+        let line = self
+            .signature
+            .as_deref()?
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if line.chars().count() <= max_width {
+            return Some(line);
+        }
+        let cut: String = line.chars().take(max_width.saturating_sub(1)).collect();
+        Some(format!("{}…", cut.trim_end()))
+    }
+
     pub fn is_position_on_name(&self, position: Position) -> bool {
         let name_range = self.definition_range.unwrap_or(self.range);
         position.line >= name_range.start.line
@@ -63,7 +220,10 @@ impl ElmSymbol {
 #[derive(Debug, Clone)]
 pub struct Document {
     pub uri: Url,
+    /// Normalized text (no BOM, LF line breaks)
     pub text: String,
+    /// Line ending style of the text as the client sent it
+    pub line_ending: LineEnding,
     pub version: i32,
     pub symbols: Vec<ElmSymbol>,
 }
@@ -72,7 +232,8 @@ impl Document {
     pub fn new(uri: Url, text: String, version: i32) -> Self {
         Self {
             uri,
-            text,
+            line_ending: LineEnding::detect(&text),
+            text: into_normalized(text),
             version,
             symbols: Vec::new(),
         }
@@ -88,6 +249,33 @@ impl Document {
         self.text.lines().nth(line as usize)
     }
 
+    /// Range of the identifier (without module qualifier) at `position`,
+    /// which may also be just after its last character
+    pub fn identifier_range_at(&self, position: Position) -> Option<Range> {
+        let line = self.get_line(position.line)?;
+        let chars: Vec<char> = line.chars().collect();
+        let is_word_char = |c: &char| c.is_alphanumeric() || *c == '_';
+        let col = position.character as usize;
+        let col = if chars.get(col).is_some_and(is_word_char) {
+            col
+        } else {
+            col.checked_sub(1)
+                .filter(|before| chars.get(*before).is_some_and(is_word_char))?
+        };
+        let start = chars[..col]
+            .iter()
+            .rposition(|c| !is_word_char(c))
+            .map_or(0, |i| i + 1);
+        let end = chars[col..]
+            .iter()
+            .position(|c| !is_word_char(c))
+            .map_or(chars.len(), |i| col + i);
+        Some(Range::new(
+            Position::new(position.line, start as u32),
+            Position::new(position.line, end as u32),
+        ))
+    }
+
     pub fn offset_to_position(&self, offset: usize) -> Position {
         let mut line = 0u32;
         let mut col = 0u32;
@@ -105,3 +293,196 @@ impl Document {
         Position::new(line, col)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_strips_bom_and_crlf() {
+        let text = "\u{FEFF}module A exposing (..)\r\n\r\nvalue = 1\r\n";
+        assert_eq!(
+            normalize_text(text),
+            "module A exposing (..)\n\nvalue = 1\n"
+        );
+        assert!(matches!(normalize_text("a\nb\n"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_document_tracks_line_ending() {
+        let uri = Url::parse("file:///A.elm").unwrap();
+        let doc = Document::new(uri, "module A exposing (..)\r\n".to_string(), 1);
+        assert_eq!(doc.line_ending, LineEnding::CrLf);
+        assert_eq!(doc.text, "module A exposing (..)\n");
+        assert_eq!(doc.line_ending.apply("import B\n"), "import B\r\n");
+        assert_eq!(LineEnding::Lf.apply("import B\n"), "import B\n");
+    }
+
+    #[test]
+    fn test_identifier_range_at() {
+        let uri = Url::parse("file:///A.elm").unwrap();
+        let doc = Document::new(uri, "main =\n    Html.div [] (view model)\n".to_string(), 1);
+        let range = |line, from, to| {
+            Some(Range::new(
+                Position::new(line, from),
+                Position::new(line, to),
+            ))
+        };
+        // The qualifier and the name are separate identifiers
+        assert_eq!(doc.identifier_range_at(Position::new(1, 5)), range(1, 4, 8));
+        assert_eq!(
+            doc.identifier_range_at(Position::new(1, 10)),
+            range(1, 9, 12)
+        );
+        // Just after the last character still counts
+        assert_eq!(
+            doc.identifier_range_at(Position::new(1, 12)),
+            range(1, 9, 12)
+        );
+        assert_eq!(
+            doc.identifier_range_at(Position::new(1, 27)),
+            range(1, 22, 27)
+        );
+        assert_eq!(doc.identifier_range_at(Position::new(1, 4)), range(1, 4, 8));
+        // Between brackets there is nothing
+        assert_eq!(doc.identifier_range_at(Position::new(1, 14)), None);
+        assert_eq!(doc.identifier_range_at(Position::new(5, 0)), None);
+    }
+
+    fn edit(start: (u32, u32), end: (u32, u32), new_text: &str) -> TextEdit {
+        TextEdit {
+            range: Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1)),
+            new_text: new_text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let text = "ab\ncd\n";
+        // Ranges refer to the original text, whatever order the edits come in
+        let edits = [
+            edit((1, 0), (1, 1), "C"),
+            edit((0, 0), (0, 2), "x\ny"),
+            edit((1, 2), (1, 2), "!"),
+        ];
+        assert_eq!(apply_edits(text, &edits).unwrap(), "x\ny\nCd!\n");
+
+        // Insertions at one position keep their order, before a replacement there
+        let edits = [
+            edit((0, 1), (0, 1), "1"),
+            edit((0, 1), (0, 2), "B"),
+            edit((0, 1), (0, 1), "2"),
+        ];
+        assert_eq!(apply_edits(text, &edits).unwrap(), "a12B\ncd\n");
+
+        // Past the end of a line or of the text
+        assert_eq!(position_to_offset(text, Position::new(0, 9)), 2);
+        assert_eq!(position_to_offset(text, Position::new(5, 0)), text.len());
+        let edits = [edit((1, 0), (9, 0), "")];
+        assert_eq!(apply_edits(text, &edits).unwrap(), "ab\n");
+
+        let overlapping = [edit((0, 0), (0, 2), "x"), edit((0, 1), (1, 0), "y")];
+        assert!(apply_edits(text, &overlapping).is_err());
+        let inside = [edit((0, 0), (0, 2), "x"), edit((0, 1), (0, 1), "y")];
+        assert!(apply_edits(text, &inside).is_err());
+        assert!(apply_edits("é", &[edit((0, 1), (0, 1), "x")]).is_err());
+    }
+
+    /// Random edits checked against a model of the text as slots: what is
+    /// inserted before each byte, and whether the byte is kept
+    #[test]
+    fn test_apply_edits_matches_model() {
+        // xorshift, so every run checks the same cases
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+        let alphabet = ['a', 'b', ' ', '\n', 'é', '→'];
+
+        for _ in 0..500 {
+            let text: String = (0..next(40))
+                .map(|_| alphabet[next(alphabet.len())])
+                .collect();
+            let boundaries: Vec<usize> = (0..=text.len())
+                .filter(|&i| text.is_char_boundary(i))
+                .collect();
+
+            let mut spans: Vec<(usize, usize, String)> = Vec::new();
+            for n in 0..next(6) {
+                let a = boundaries[next(boundaries.len())];
+                let b = boundaries[next(boundaries.len())];
+                let (start, end) = (a.min(b), a.max(b));
+                // Sharing more than an end, or an insertion inside a range
+                if !spans.iter().any(|&(s, e, _)| start < e && s < end) {
+                    spans.push((start, end, format!("<{}>", n)));
+                }
+            }
+
+            // The model: insertions in order, then a replacement's text
+            let mut inserted = vec![String::new(); text.len() + 1];
+            let mut replaced = vec![String::new(); text.len() + 1];
+            let mut kept = vec![true; text.len()];
+            for (start, end, new_text) in &spans {
+                if start == end {
+                    inserted[*start].push_str(new_text);
+                } else {
+                    replaced[*start].push_str(new_text);
+                    kept[*start..*end].iter_mut().for_each(|k| *k = false);
+                }
+            }
+            let mut expected = Vec::new();
+            for i in 0..=text.len() {
+                expected.extend_from_slice(inserted[i].as_bytes());
+                expected.extend_from_slice(replaced[i].as_bytes());
+                if i < text.len() && kept[i] {
+                    expected.push(text.as_bytes()[i]);
+                }
+            }
+
+            // The edits in a shuffled order, insertions keeping theirs
+            let position = |offset: usize| {
+                let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+                let line = text[..offset].matches('\n').count();
+                Position::new(line as u32, (offset - line_start) as u32)
+            };
+            let mut edits: Vec<(usize, TextEdit)> = spans
+                .iter()
+                .enumerate()
+                .map(|(i, (start, end, new_text))| {
+                    let range = Range::new(position(*start), position(*end));
+                    let key = if start == end { i } else { next(100) };
+                    (key, TextEdit::new(range, new_text.clone()))
+                })
+                .collect();
+            edits.sort_by_key(|(key, _)| *key);
+            let edits: Vec<TextEdit> = edits.into_iter().map(|(_, edit)| edit).collect();
+
+            assert_eq!(
+                apply_edits(&text, &edits).unwrap().into_bytes(),
+                expected,
+                "{:?} with {:?}",
+                text,
+                edits
+            );
+        }
+    }
+
+    #[test]
+    fn test_annotation_detail_is_one_elided_line() {
+        let mut symbol =
+            ElmSymbol::new("update".to_string(), SymbolKind::FUNCTION, Range::default());
+        symbol.signature =
+            Some("update :\n    Msg\n    -> Model\n    -> ( Model, Cmd Msg )".to_string());
+        assert_eq!(
+            symbol.annotation_detail(80).unwrap(),
+            "update : Msg -> Model -> ( Model, Cmd Msg )"
+        );
+        assert_eq!(symbol.annotation_detail(16).unwrap(), "update : Msg ->…");
+
+        symbol.kind = SymbolKind::ENUM;
+        assert_eq!(symbol.annotation_detail(80), None);
+    }
+}
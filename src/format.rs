@@ -0,0 +1,213 @@
+//! Built-in formatter used when elm-format is not installed.
+//!
+//! This is not elm-format and does not produce its canonical layout. It only
+//! sorts the imports, separates top-level declarations by two blank lines,
+//! indents declaration bodies by four spaces, puts single spaces around `=`
+//! and `->`, and trims trailing whitespace. Files that do not parse are left
+//! alone, and files with multi-line strings or `[glsl| ... |]` shaders keep
+//! their line contents as is.
+
+use crate::parser::ElmParser;
+
+/// Format `source`, or None if it does not parse
+pub fn format(source: &str) -> Option<String> {
+    let parser = ElmParser::new();
+    let tree = parser.parse(source)?;
+    if tree.root_node().has_error() {
+        return None;
+    }
+    let spaced = space_operators(&tree, source);
+
+    let tree = parser.parse(&spaced)?;
+    if tree.root_node().has_error() {
+        return None;
+    }
+    Some(layout(&tree, &spaced))
+}
+
+/// Single spaces around `=` and `->` that share a line with their neighbours
+fn space_operators(tree: &tree_sitter::Tree, source: &str) -> String {
+    let mut gaps: Vec<(usize, usize)> = Vec::new();
+    let mut stack = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        if matches!(node.kind(), "eq" | "arrow") {
+            if let Some(prev) = node.prev_sibling() {
+                gaps.push((prev.end_byte(), node.start_byte()));
+            }
+            if let Some(next) = node.next_sibling() {
+                gaps.push((node.end_byte(), next.start_byte()));
+            }
+            continue;
+        }
+        let mut cursor = node.walk();
+        stack.extend(node.children(&mut cursor));
+    }
+    gaps.sort();
+    gaps.dedup();
+
+    let mut result = String::with_capacity(source.len());
+    let mut copied = 0;
+    for (start, end) in gaps {
+        let gap = &source[start..end];
+        if start < copied || gap.contains('\n') || gap == " " {
+            continue;
+        }
+        result.push_str(&source[copied..start]);
+        result.push(' ');
+        copied = end;
+    }
+    result.push_str(&source[copied..]);
+    result
+}
+
+/// Header, sorted imports, then declarations two blank lines apart
+fn layout(tree: &tree_sitter::Tree, source: &str) -> String {
+    let root = tree.root_node();
+    let multiline_strings = source.contains("\"\"\"") || source.contains("[glsl|");
+    let mut cursor = root.walk();
+    let children: Vec<tree_sitter::Node> = root.named_children(&mut cursor).collect();
+
+    // The module line and its doc comment
+    let mut header_end = 0;
+    let mut rest = children.as_slice();
+    if let Some((first, tail)) = rest.split_first() {
+        if first.kind() == "module_declaration" {
+            header_end = first.end_byte();
+            rest = tail;
+            if let Some((doc, tail)) = rest.split_first() {
+                if doc.kind() == "block_comment" && source[doc.byte_range()].starts_with("{-|") {
+                    header_end = doc.end_byte();
+                    rest = tail;
+                }
+            }
+        }
+    }
+    let header = source[..header_end].trim_end();
+
+    let import_count = rest
+        .iter()
+        .position(|n| !matches!(n.kind(), "import_clause" | "line_comment" | "block_comment"))
+        .unwrap_or(rest.len());
+    let (imports, declarations) = rest.split_at(import_count);
+    // Comments directly above the first declaration belong to it
+    let attached = imports
+        .iter()
+        .rev()
+        .take_while(|n| n.kind() != "import_clause")
+        .count();
+    let (imports, comments) = imports.split_at(imports.len() - attached);
+
+    let mut sections = Vec::new();
+    if !header.is_empty() {
+        sections.push(header.to_string());
+    }
+    if !imports.is_empty() {
+        let mut lines: Vec<&str> = imports.iter().map(|n| &source[n.byte_range()]).collect();
+        // Comments between imports would lose their place, so keep those as written
+        if imports.iter().all(|n| n.kind() == "import_clause") {
+            lines.sort_by_key(|line| import_module(line));
+            lines.dedup();
+        }
+        sections.push(lines.join("\n"));
+    }
+
+    let mut groups: Vec<String> = Vec::new();
+    // Byte offset and index of the first declaration of the current group
+    let mut group: Option<(usize, usize)> = comments.first().map(|c| (c.start_byte(), 0));
+    for (i, node) in declarations.iter().enumerate() {
+        let (start, first) = *group.get_or_insert((node.start_byte(), i));
+        let continues = i + 1 < declarations.len()
+            // An annotation and comments stay with what follows them
+            && matches!(node.kind(), "type_annotation" | "line_comment" | "block_comment");
+        if continues {
+            continue;
+        }
+        groups.push(if multiline_strings {
+            source[start..node.end_byte()].to_string()
+        } else {
+            reindent_bodies(&declarations[first..=i], start, source)
+        });
+        group = None;
+    }
+
+    let mut result = sections.join("\n\n");
+    if !groups.is_empty() {
+        if !result.is_empty() {
+            result.push_str("\n\n\n");
+        }
+        result.push_str(&groups.join("\n\n\n"));
+    }
+    if !multiline_strings {
+        result = result
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+    result.push('\n');
+    result
+}
+
+fn import_module(line: &str) -> &str {
+    line.split_whitespace().nth(1).unwrap_or(line)
+}
+
+/// The text from `start` to the end of the last declaration in `nodes`,
+/// with each top-level declaration body moved to four spaces of indentation
+/// when it starts on its own line
+fn reindent_bodies(nodes: &[tree_sitter::Node], start: usize, source: &str) -> String {
+    let last = match nodes.last() {
+        Some(last) => *last,
+        None => return String::new(),
+    };
+    let text = &source[start..last.end_byte()];
+    let first_row = source[..start].matches('\n').count();
+    let mut lines: Vec<String> = text.split('\n').map(str::to_string).collect();
+
+    for node in nodes.iter().filter(|n| n.kind() == "value_declaration") {
+        let body = match node.child_by_field_name("body") {
+            Some(body) => body,
+            None => continue,
+        };
+        let eq_row = node
+            .child_by_field_name("functionDeclarationLeft")
+            .or_else(|| node.child_by_field_name("pattern"))
+            .map_or(node.start_position().row, |left| left.end_position().row);
+        let (body_row, column) = (body.start_position().row, body.start_position().column);
+        if body_row == eq_row || column == 4 {
+            continue;
+        }
+        let rows = (body_row - first_row)..=(body.end_position().row - first_row);
+        let indented = |line: &String| {
+            line.trim().is_empty() || line.len() - line.trim_start().len() >= column
+        };
+        if !lines[rows.clone()].iter().all(indented) {
+            continue;
+        }
+        for line in &mut lines[rows] {
+            if !line.trim().is_empty() {
+                *line = format!("    {}", &line[column..]);
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_sorts_imports_and_spaces_declarations() {
+        let source = "module Main exposing (main)\nimport Html\nimport Dict exposing (Dict)\n-- The entry point\nmain : Html.Html msg\nmain=\n  Html.text  \"hi\"   \nhelper : Int->Int\nhelper x =\n        x + 1\n";
+        assert_eq!(
+            format(source).unwrap(),
+            "module Main exposing (main)\n\nimport Dict exposing (Dict)\nimport Html\n\n\n-- The entry point\nmain : Html.Html msg\nmain =\n    Html.text  \"hi\"\n\n\nhelper : Int -> Int\nhelper x =\n    x + 1\n"
+        );
+    }
+
+    #[test]
+    fn test_format_leaves_unparsable_files_alone() {
+        assert_eq!(format("module Main exposing (..)\n\nmain =\n"), None);
+    }
+}
@@ -13,6 +13,7 @@ use tree_sitter::Node;
 
 use crate::binder::{bind_tree, SymbolLinks};
 use crate::disjoint_set::DisjointSet;
+use crate::logging;
 use crate::types::{
     FieldReference, MutableRecordType, RecordFieldReferenceTable, RecordType, Type, TypeVar,
 };
@@ -207,6 +208,7 @@ impl<'a> InferenceScope<'a> {
                     self.expression_types
                         .insert(node.id(), Type::Record(record_type));
                     tracing::debug!(
+                        target: logging::TYPES,
                         "propagate_alias_to_record: annotated record at node {} with alias {}",
                         node.id(),
                         alias_type.name
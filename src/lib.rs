@@ -1,10 +1,66 @@
+//! The engine of the Elm language server, usable without speaking LSP.
+//!
+//! The server in [`server`] is one frontend; CI bots, code-mod scripts or
+//! other editors can drive the same engine directly:
+//!
+//! - [`Workspace`] indexes one Elm project (an `elm.json` and its source
+//!   directories and packages) and answers navigation queries: definitions,
+//!   references, symbols, call paths, importers.
+//! - Refactorings are methods of [`Workspace`] too, like
+//!   [`Workspace::move_function`], [`Workspace::remove_variant`] or
+//!   [`Workspace::rename_file`]. They never write files: they return the
+//!   edits to make, keyed by file URI, for the caller to apply.
+//! - [`ElmParser`] parses a module with tree-sitter and extracts its
+//!   top-level symbols.
+//! - [`analysis`] holds the diagnostics computed from the syntax tree alone,
+//!   each with the fixes that resolve it.
+//! - [`Settings`] configures the engine as the server's settings do.
+//!
+//! Positions, ranges, URIs and edits are the LSP types, re-exported as
+//! [`lsp_types`] so callers use the same version as the engine.
+//!
+//! ```no_run
+//! use std::path::PathBuf;
+//!
+//! use elm_lsp::{Settings, Workspace};
+//!
+//! let mut workspace = Workspace::new(PathBuf::from("path/to/project"));
+//! workspace.settings = Settings::default();
+//! workspace.initialize()?;
+//! for reference in workspace.find_references("update", Some("Main")) {
+//!     println!("{} {:?}", reference.uri, reference.range.start);
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod analysis;
+pub mod bench;
 pub mod binder;
+pub mod concurrency;
+pub mod config;
 pub mod diagnostics;
 pub mod disjoint_set;
 pub mod document;
+pub mod format;
 pub mod inference;
+pub mod logging;
+pub mod matcher;
+pub mod on_type;
 pub mod parser;
+pub mod preview;
+pub mod queries;
+pub mod semantic_tokens;
 pub mod server;
+pub mod tools;
 pub mod type_checker;
 pub mod types;
 pub mod workspace;
+
+pub use tower_lsp::lsp_types;
+
+pub use config::Settings;
+pub use parser::ElmParser;
+pub use workspace::{
+    FileOperationResult, MoveResult, RefactorResult, RemoveVariantResult, SymbolReference,
+    Workspace, Workspaces,
+};
@@ -0,0 +1,143 @@
+//! Logging to stderr, with one target per feature and warnings forwarded to
+//! the client.
+//!
+//! Every log statement names the feature it belongs to, so a filter like
+//! `elm_lsp::refactor=debug` shows one feature in detail. The filter starts
+//! from `RUST_LOG` and can be replaced at runtime through the `logLevel`
+//! setting. Warnings and errors also go to the client as `window/logMessage`
+//! notifications, where editors show them in the server's output panel.
+
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tower_lsp::lsp_types::MessageType;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Layer, Registry};
+
+/// Indexing the project and its packages, and keeping the index current
+pub const INDEX: &str = "elm_lsp::index";
+/// Definitions, references and other lookups
+pub const NAVIGATION: &str = "elm_lsp::navigation";
+/// Renames, moves and the other edits spanning files
+pub const REFACTOR: &str = "elm_lsp::refactor";
+/// Compiler and built-in diagnostics
+pub const DIAGNOSTICS: &str = "elm_lsp::diagnostics";
+/// Completion
+pub const COMPLETION: &str = "elm_lsp::completion";
+/// Type inference
+pub const TYPES: &str = "elm_lsp::types";
+/// Formatting with elm-format or the built-in formatter
+pub const FORMAT: &str = "elm_lsp::format";
+
+type FilterHandle = reload::Handle<EnvFilter, Registry>;
+
+static FILTER: OnceLock<FilterHandle> = OnceLock::new();
+static FORWARD: Mutex<Option<UnboundedSender<(MessageType, String)>>> = Mutex::new(None);
+
+/// Log to stderr, filtered by `RUST_LOG` until a `logLevel` setting
+/// replaces it
+pub fn init() {
+    let (filter, handle) = reload::Layer::new(EnvFilter::from_default_env());
+    let installed = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(ClientForwarding)
+        .try_init();
+    if installed.is_ok() {
+        let _ = FILTER.set(handle);
+    }
+}
+
+/// Replace the filter with `directives`, like `info` or
+/// `warn,elm_lsp::index=debug`; an empty string goes back to `RUST_LOG`
+pub fn set_filter(directives: &str) -> anyhow::Result<()> {
+    let Some(handle) = FILTER.get() else {
+        return Ok(());
+    };
+    let filter = if directives.trim().is_empty() {
+        EnvFilter::from_default_env()
+    } else {
+        EnvFilter::try_new(directives)?
+    };
+    handle.reload(filter)?;
+    Ok(())
+}
+
+/// Receive the warnings and errors logged from now on, with their target,
+/// to pass them on to the client. Replaces an earlier receiver.
+pub fn forward_warnings() -> UnboundedReceiver<(MessageType, String)> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    if let Ok(mut forward) = FORWARD.lock() {
+        *forward = Some(sender);
+    }
+    receiver
+}
+
+/// Sends warnings and errors to the receiver of [`forward_warnings`]
+struct ClientForwarding;
+
+impl<S: Subscriber> Layer<S> for ClientForwarding {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        let kind = match *event.metadata().level() {
+            Level::ERROR => MessageType::ERROR,
+            Level::WARN => MessageType::WARNING,
+            _ => return,
+        };
+        let Ok(forward) = FORWARD.lock() else {
+            return;
+        };
+        let Some(sender) = forward.as_ref() else {
+            return;
+        };
+        let mut message = Message(String::new());
+        event.record(&mut message);
+        let target = event.metadata().target();
+        let _ = sender.send((kind, format!("[{}] {}", target, message.0)));
+    }
+}
+
+/// An event's message followed by its other fields
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let fields = std::mem::take(&mut self.0);
+            let _ = write!(self.0, "{:?}{}", value, fields);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forwards_warnings_with_their_target() {
+        init();
+        let mut receiver = forward_warnings();
+        set_filter("warn").unwrap();
+        tracing::info!(target: INDEX, "not forwarded");
+        tracing::warn!(target: INDEX, count = 2, "Failed to index {}", "Main.elm");
+        tracing::error!(target: REFACTOR, "Rename failed");
+        // Other tests log too, so only look for these
+        let received: Vec<(MessageType, String)> =
+            std::iter::from_fn(|| receiver.try_recv().ok()).collect();
+        assert!(received.contains(&(
+            MessageType::WARNING,
+            "[elm_lsp::index] Failed to index Main.elm count=2".to_string()
+        )));
+        assert!(received.contains(&(
+            MessageType::ERROR,
+            "[elm_lsp::refactor] Rename failed".to_string()
+        )));
+        assert!(!received.iter().any(|(_, m)| m.contains("not forwarded")));
+        assert!(set_filter("elm_lsp::index=nonsense=").is_err());
+    }
+}
@@ -1,22 +1,45 @@
+use std::path::Path;
+
 use anyhow::Result;
 use tower_lsp::{LspService, Server};
-use tracing_subscriber::EnvFilter;
 
 use elm_lsp::server::ElmLanguageServer;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .with_writer(std::io::stderr)
-        .init();
+    elm_lsp::logging::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let [flag, rest @ ..] = args.as_slice() {
+        if flag == "--bench-index" {
+            let project = rest.first().map_or(".", String::as_str);
+            return elm_lsp::bench::run(Path::new(project), &mut std::io::stdout());
+        }
+    }
 
-    tracing::info!("Starting Elm Language Server (Rust)");
+    tracing::info!(target: elm_lsp::logging::INDEX, "Starting Elm Language Server (Rust)");
 
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let (service, socket) = LspService::new(ElmLanguageServer::new);
+    let (service, socket) = LspService::build(ElmLanguageServer::new)
+        .custom_method("elm-lsp/previewRename", ElmLanguageServer::preview_rename)
+        .custom_method("elm-lsp/callPaths", ElmLanguageServer::call_paths)
+        .custom_method(
+            "elm-lsp/constructorUsages",
+            ElmLanguageServer::constructor_usages,
+        )
+        .custom_method(
+            "elm-lsp/declarationImporters",
+            ElmLanguageServer::declaration_importers,
+        )
+        .custom_method("elm-lsp/indexStats", ElmLanguageServer::index_stats)
+        .custom_method("elm-lsp/evergreenDrift", ElmLanguageServer::evergreen_drift)
+        .custom_method(
+            "elm-lsp/workspaceSymbols",
+            ElmLanguageServer::workspace_symbols,
+        )
+        .finish();
     Server::new(stdin, stdout, socket).serve(service).await;
 
     Ok(())
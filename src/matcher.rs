@@ -0,0 +1,122 @@
+//! Matching symbol names against a typed query, for workspace symbol
+//! search and completion ranking.
+//!
+//! Besides plain substrings, a query can abbreviate the words of a name:
+//! `uMbS`, `umbs` and `updModSave` all find `updateModelBeforeSave`. Words
+//! start at the beginning of the name, at upper case letters and after `_`.
+//! Each query character either continues the word the previous one matched
+//! in or starts a later word, so nothing has to be spelled out in full.
+
+use std::collections::HashMap;
+
+/// How well `name` matches `query`, lower being better: 0 for an exact
+/// match, 1 for a prefix, 2 at a word start (`userId` for `id`), 3 anywhere
+/// else and 4 for an abbreviation. Case is ignored.
+pub fn score(name: &str, query: &str) -> Option<u32> {
+    let lower = name.to_lowercase();
+    let query = query.to_lowercase();
+    if lower == query {
+        Some(0)
+    } else if lower.starts_with(&query) {
+        Some(1)
+    } else if name.char_indices().any(|(i, c)| {
+        // Lower-casing can change byte lengths, so `lower` has other offsets
+        (c.is_uppercase() || name[..i].ends_with('_'))
+            && name[i..].to_lowercase().starts_with(&query)
+    }) {
+        Some(2)
+    } else if lower.contains(&query) {
+        Some(3)
+    } else if abbreviates(name, &query) {
+        Some(4)
+    } else {
+        None
+    }
+}
+
+/// Whether `query` takes the first letters of some of the words of `name`,
+/// in order
+fn abbreviates(name: &str, query: &str) -> bool {
+    let chars: Vec<char> = name.chars().collect();
+    let word_starts: Vec<bool> = chars
+        .iter()
+        .enumerate()
+        .map(|(i, c)| i == 0 || c.is_uppercase() || chars[i - 1] == '_')
+        .collect();
+    let lower: Vec<char> = name.to_lowercase().chars().collect();
+    let query: Vec<char> = query.chars().collect();
+    // Lower-casing can change the length of non-ASCII names
+    if lower.len() != chars.len() || query.is_empty() {
+        return false;
+    }
+    let mut matcher = Abbreviation {
+        name: &lower,
+        word_starts: &word_starts,
+        query: &query,
+        known: HashMap::new(),
+    };
+    matcher.matches_from(0, 0, false)
+}
+
+/// The search for an abbreviation, remembering the positions already tried:
+/// names with many words starting alike would otherwise take exponential time
+struct Abbreviation<'a> {
+    name: &'a [char],
+    word_starts: &'a [bool],
+    query: &'a [char],
+    known: HashMap<(usize, usize, bool), bool>,
+}
+
+impl Abbreviation<'_> {
+    /// Whether the query from `matched` on matches the name from `at`, where
+    /// `continuing` says the previous character matched just before `at`
+    fn matches_from(&mut self, at: usize, matched: usize, continuing: bool) -> bool {
+        let Some(&first) = self.query.get(matched) else {
+            return true;
+        };
+        if let Some(&known) = self.known.get(&(at, matched, continuing)) {
+            return known;
+        }
+        let found = (continuing
+            && at < self.name.len()
+            && !self.word_starts[at]
+            && self.name[at] == first
+            && self.matches_from(at + 1, matched + 1, true))
+            || (at..self.name.len()).any(|i| {
+                self.word_starts[i]
+                    && self.name[i] == first
+                    && self.matches_from(i + 1, matched + 1, true)
+            });
+        self.known.insert((at, matched, continuing), found);
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score() {
+        assert_eq!(score("id", "Id"), Some(0));
+        assert_eq!(score("identity", "id"), Some(1));
+        assert_eq!(score("userId", "id"), Some(2));
+        assert_eq!(score("user_id", "id"), Some(2));
+        assert_eq!(score("valid", "id"), Some(3));
+        assert_eq!(score("updateModelBeforeSave", "uMbS"), Some(4));
+        assert_eq!(score("updateModelBeforeSave", "umbs"), Some(4));
+        assert_eq!(score("updateModelBeforeSave", "updModSave"), Some(4));
+        assert_eq!(score("updateModelBeforeSave", "mbs"), Some(4));
+        assert_eq!(score("update_model", "upmo"), Some(4));
+        assert_eq!(score("updateModelBeforeSave", "uMsB"), None);
+        assert_eq!(score("updateModelBeforeSave", "upx"), None);
+        // Letters inside a word do not start an abbreviation
+        assert_eq!(score("updateModel", "pm"), None);
+        // `İ` lower-cases to more bytes than it takes
+        assert_eq!(score("İA", "zz"), None);
+        assert_eq!(score("xİA", "a"), Some(2));
+        // Many words starting alike do not make the search exponential
+        let name = "a".to_string() + &"A".repeat(40) + "B";
+        assert_eq!(score(&name, &("a".repeat(30) + "c")), None);
+    }
+}
@@ -0,0 +1,170 @@
+//! Edits made as you type a newline.
+//!
+//! After a line ending in `let`, the new line is indented for the first
+//! binding and an `in` is added below it, unless the `let` already has one.
+//! After a line ending in `case x`, the missing `of` is added and the new
+//! line is indented for the first branch. The keyword must be a keyword in
+//! the syntax tree, so nothing happens when it is in a string or a comment.
+
+use tower_lsp::lsp_types::{Position, Range, TextEdit};
+
+use crate::parser::ElmParser;
+
+/// The edits for a newline typed just before `position`, which is on the
+/// new line
+pub fn newline_edits(source: &str, position: Position) -> Vec<TextEdit> {
+    let lines: Vec<&str> = source.split('\n').collect();
+    let line = position.line as usize;
+    let (Some(previous), Some(current)) = (
+        line.checked_sub(1).and_then(|l| lines.get(l)),
+        lines.get(line),
+    ) else {
+        return Vec::new();
+    };
+    let previous = previous.trim_end_matches('\r');
+    let trimmed = previous.trim_end();
+    let Some(keyword) = trimmed
+        .rsplit(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .next()
+    else {
+        return Vec::new();
+    };
+    let keyword_column = trimmed.len() - keyword.len();
+
+    if keyword == "let" {
+        if !is_keyword(source, line - 1, keyword_column, "let")
+            || has_in(&lines[line + 1..], keyword_column)
+        {
+            return Vec::new();
+        }
+        let indent = indent_edit(line, current, keyword_column + 4);
+        let in_line = format!("{}in", " ".repeat(keyword_column));
+        if line + 1 == lines.len() {
+            // Nothing follows, so the `in` goes with the new line's indentation
+            let end = Position::new(line as u32, current.len() as u32);
+            return vec![TextEdit {
+                range: Range::new(indent.range.start, end),
+                new_text: format!("{}\n{}", indent.new_text, in_line),
+            }];
+        }
+        let next = Position::new(line as u32 + 1, 0);
+        return vec![
+            indent,
+            TextEdit {
+                range: Range::new(next, next),
+                new_text: format!("{}\n", in_line),
+            },
+        ];
+    }
+
+    // `case` is the first keyword of the line's last expression
+    let Some(case_column) = previous.rfind("case ").filter(|&column| {
+        column == 0 || !previous[..column].ends_with(|c: char| c.is_alphanumeric() || c == '_')
+    }) else {
+        return Vec::new();
+    };
+    if keyword == "of"
+        || previous[case_column + 5..].trim().is_empty()
+        || !is_keyword(source, line - 1, case_column, "case")
+    {
+        return Vec::new();
+    }
+    let end = Position::new(line as u32 - 1, trimmed.len() as u32);
+    vec![
+        TextEdit {
+            range: Range::new(end, Position::new(line as u32 - 1, previous.len() as u32)),
+            new_text: " of".to_string(),
+        },
+        indent_edit(line, current, case_column + 4),
+    ]
+}
+
+/// Whether the syntax tree has the keyword `kind` at `line` and `column`,
+/// as opposed to the word in a string or comment
+fn is_keyword(source: &str, line: usize, column: usize, kind: &str) -> bool {
+    let Some(tree) = ElmParser::new().parse(source) else {
+        return false;
+    };
+    let point = tree_sitter::Point::new(line, column);
+    tree.root_node()
+        .descendant_for_point_range(point, point)
+        .is_some_and(|node| node.kind() == kind && node.start_position() == point)
+}
+
+/// Whether the lines after the new one already close a `let` at `column`
+/// with an `in`
+fn has_in(after: &[&str], column: usize) -> bool {
+    after
+        .iter()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.trim().is_empty())
+        .find(|line| line.len() - line.trim_start().len() <= column)
+        .is_some_and(|line| {
+            let rest = line.trim_start();
+            line.len() - rest.len() == column
+                && rest.starts_with("in")
+                && !rest[2..].starts_with(|c: char| c.is_alphanumeric() || c == '_')
+        })
+}
+
+/// Replace the indentation of the new line `line` with `width` spaces
+fn indent_edit(line: usize, current: &str, width: usize) -> TextEdit {
+    let indentation = current.len() - current.trim_start().len();
+    TextEdit {
+        range: Range::new(
+            Position::new(line as u32, 0),
+            Position::new(line as u32, indentation as u32),
+        ),
+        new_text: " ".repeat(width),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::apply_edits;
+
+    #[test]
+    fn test_let_gets_an_in() {
+        let source = "module M exposing (..)\n\n\nview model =\n    let\n\n    div [] []\n";
+        let edits = newline_edits(source, Position::new(5, 0));
+        assert_eq!(
+            apply_edits(source, &edits).unwrap(),
+            "module M exposing (..)\n\n\nview model =\n    let\n        \n    in\n    div [] []\n"
+        );
+
+        // Already closed
+        let source = "view model =\n    let\n    \n        x =\n            1\n    in\n    x\n";
+        assert!(newline_edits(source, Position::new(2, 4)).is_empty());
+
+        // At the end of the file
+        let source = "view model =\n    let\n";
+        let edits = newline_edits(source, Position::new(2, 0));
+        assert_eq!(
+            apply_edits(source, &edits).unwrap(),
+            "view model =\n    let\n        \n    in"
+        );
+    }
+
+    #[test]
+    fn test_case_gets_an_of() {
+        let source = "update msg model =\n    case msg\n    \n";
+        let edits = newline_edits(source, Position::new(2, 4));
+        assert_eq!(
+            apply_edits(source, &edits).unwrap(),
+            "update msg model =\n    case msg of\n        \n"
+        );
+        let source = "update msg model =\n    case msg of\n\n";
+        assert!(newline_edits(source, Position::new(2, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_not_in_strings_or_comments() {
+        let source = "view =\n    \"let\n\"\n";
+        assert!(newline_edits(source, Position::new(2, 0)).is_empty());
+        let source = "view =\n    -- then let\n\n    1\n";
+        assert!(newline_edits(source, Position::new(2, 0)).is_empty());
+        let source = "view =\n    -- case msg\n\n    1\n";
+        assert!(newline_edits(source, Position::new(2, 0)).is_empty());
+    }
+}
@@ -1,12 +1,20 @@
+//! Parsing Elm modules with tree-sitter.
+//!
+//! [`ElmParser`] turns source text into a syntax tree and the tree into the
+//! module's top-level symbols, with their annotations and doc comments.
+
+use std::collections::HashMap;
 use tower_lsp::lsp_types::*;
 use tree_sitter::{Language, Parser, Tree};
 
 use crate::document::{ElmSymbol, VariantInfo};
+use crate::queries;
 
 fn elm_language() -> Language {
     tree_sitter_elm::LANGUAGE.into()
 }
 
+/// Parser for Elm source, and extraction of the symbols a module declares
 pub struct ElmParser {
     _parser: Parser,
 }
@@ -27,107 +35,103 @@ impl ElmParser {
     }
 
     pub fn extract_symbols(&self, tree: &Tree, source: &str) -> Vec<ElmSymbol> {
-        let mut symbols = Vec::new();
-        let root = tree.root_node();
+        let mut annotations = Vec::new();
+        let mut declarations = Vec::new();
+        queries::for_each_match(&queries::SYMBOLS, tree.root_node(), source, |m| {
+            let Some(name) = m.get("name") else { return };
+            if let Some(node) = m.get("annotation") {
+                annotations.push((node, name));
+            } else if let Some(node) = m.get("value") {
+                declarations.push((SymbolKind::FUNCTION, node, name));
+            } else if let Some(node) = m.get("type") {
+                declarations.push((SymbolKind::ENUM, node, name));
+            } else if let Some(node) = m.get("type_alias") {
+                declarations.push((SymbolKind::STRUCT, node, name));
+            } else if let Some(node) = m.get("port") {
+                declarations.push((SymbolKind::INTERFACE, node, name));
+            }
+        });
 
-        // First pass: collect type annotations (they're siblings to value_declaration)
+        // Type annotations are siblings of their value_declaration, keyed by function name
         // Store (signature_text, name_range) where name_range is the range of the function name in the annotation
-        let mut type_annotations: std::collections::HashMap<String, (String, Range)> =
-            std::collections::HashMap::new();
-        let mut cursor = root.walk();
-        for child in root.children(&mut cursor) {
-            if child.kind() == "type_annotation" {
-                if let Some((name, sig, name_range)) = self.parse_type_annotation(child, source) {
-                    type_annotations.insert(name, (sig, name_range));
-                }
-            }
-        }
+        let type_annotations: HashMap<&str, (String, Range)> = annotations
+            .into_iter()
+            .map(|(node, name)| {
+                let sig = self.node_text(node, source).to_string();
+                (self.node_text(name, source), (sig, self.node_to_range(name)))
+            })
+            .collect();
 
-        // Second pass: extract all symbols
-        self.walk_node(root, source, &mut symbols, &type_annotations);
+        let mut symbols: Vec<ElmSymbol> = declarations
+            .into_iter()
+            .map(|(kind, node, name)| {
+                if kind == SymbolKind::FUNCTION {
+                    self.value_symbol(node, name, source, &type_annotations)
+                } else {
+                    self.declaration_symbol(kind, node, name, source)
+                }
+            })
+            .collect();
 
+        self.attach_comments(tree, source, &mut symbols);
         symbols
     }
 
-    fn parse_type_annotation(
-        &self,
-        node: tree_sitter::Node,
-        source: &str,
-    ) -> Option<(String, String, Range)> {
-        let mut cursor = node.walk();
-        for child in node.children(&mut cursor) {
-            if child.kind() == "lower_case_identifier" {
-                let name = self.node_text(child, source).to_string();
-                let sig = self.node_text(node, source).to_string();
-                let name_range = self.node_to_range(child);
-                return Some((name, sig, name_range));
+    /// Attach doc comments and `--` comments to the top-level declarations that follow them
+    fn attach_comments(&self, tree: &Tree, source: &str, symbols: &mut [ElmSymbol]) {
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        for child in root.children(&mut cursor) {
+            if !matches!(
+                child.kind(),
+                "type_annotation"
+                    | "value_declaration"
+                    | "type_declaration"
+                    | "type_alias_declaration"
+                    | "port_annotation"
+            ) {
+                continue;
             }
-        }
-        None
-    }
 
-    fn walk_node(
-        &self,
-        node: tree_sitter::Node,
-        source: &str,
-        symbols: &mut Vec<ElmSymbol>,
-        type_annotations: &std::collections::HashMap<String, (String, Range)>,
-    ) {
-        match node.kind() {
-            "value_declaration" => {
-                if let Some(symbol) = self.parse_value_declaration(node, source, type_annotations) {
-                    symbols.push(symbol);
-                }
-            }
-            "type_declaration" => {
-                if let Some(symbol) = self.parse_type_declaration(node, source) {
-                    symbols.push(symbol);
-                }
-            }
-            "type_alias_declaration" => {
-                if let Some(symbol) = self.parse_type_alias_declaration(node, source) {
-                    symbols.push(symbol);
-                }
-            }
-            "port_annotation" => {
-                if let Some(symbol) = self.parse_port_annotation(node, source) {
-                    symbols.push(symbol);
+            // A value's annotation starts the symbol, so its comments sit above the annotation
+            let start = self.node_to_range(child).start;
+            let Some(symbol) = symbols.iter_mut().find(|s| s.range.start == start) else {
+                continue;
+            };
+
+            let comments = comments_above(child, source);
+            let (Some(top), Some(bottom)) = (comments.last(), comments.first()) else {
+                continue;
+            };
+            symbol.comments_range = Some(Range::new(
+                self.node_to_range(*top).start,
+                self.node_to_range(*bottom).end,
+            ));
+
+            for comment in comments.iter().rev() {
+                let text = self.node_text(*comment, source);
+                if let Some(doc) = text.strip_prefix("{-|") {
+                    symbol.documentation = Some(doc.trim_end_matches("-}").trim().to_string());
+                } else if let Some(line) = text.strip_prefix("--") {
+                    symbol.leading_comments.push(line.trim().to_string());
                 }
             }
-            _ => {}
-        }
-
-        let mut cursor = node.walk();
-        for child in node.children(&mut cursor) {
-            self.walk_node(child, source, symbols, type_annotations);
         }
     }
 
-    fn parse_value_declaration(
+    fn value_symbol(
         &self,
         node: tree_sitter::Node,
+        name_node: tree_sitter::Node,
         source: &str,
-        type_annotations: &std::collections::HashMap<String, (String, Range)>,
-    ) -> Option<ElmSymbol> {
-        let mut name = None;
-        let mut name_range = None;
-
-        let mut cursor = node.walk();
-        for child in node.children(&mut cursor) {
-            if child.kind() == "function_declaration_left" {
-                if let Some(name_node) = child.child(0) {
-                    name = Some(self.node_text(name_node, source).to_string());
-                    name_range = Some(self.node_to_range(name_node));
-                }
-            }
-        }
-
-        let name = name?;
+        type_annotations: &HashMap<&str, (String, Range)>,
+    ) -> ElmSymbol {
+        let name = self.node_text(name_node, source);
         // Use the full node range so contains_position works for finding enclosing functions
         let mut full_range = self.node_to_range(node);
 
         // Look up the type annotation from the pre-collected map
-        let (signature, type_annotation_range) = match type_annotations.get(&name) {
+        let (signature, type_annotation_range) = match type_annotations.get(name) {
             Some((sig, range)) => (Some(sig.clone()), Some(*range)),
             None => (None, None),
         };
@@ -137,93 +141,49 @@ impl ElmParser {
             full_range.start = ta_range.start;
         }
 
-        let mut symbol = ElmSymbol::new(name, SymbolKind::FUNCTION, full_range);
+        let mut symbol = ElmSymbol::new(name.to_string(), SymbolKind::FUNCTION, full_range);
         symbol.signature = signature;
-        symbol.definition_range = name_range;
+        symbol.definition_range = Some(self.node_to_range(name_node));
         symbol.type_annotation_range = type_annotation_range;
-
-        Some(symbol)
-    }
-
-    fn parse_type_declaration(&self, node: tree_sitter::Node, source: &str) -> Option<ElmSymbol> {
-        let mut cursor = node.walk();
-        for child in node.children(&mut cursor) {
-            if child.kind() == "upper_case_identifier" {
-                let name = self.node_text(child, source).to_string();
-                let name_range = self.node_to_range(child);
-                let full_range = self.node_to_range(node);
-                let mut symbol = ElmSymbol::new(name, SymbolKind::ENUM, full_range);
-                // definition_range is just the identifier name (for renaming)
-                symbol.definition_range = Some(name_range);
-                symbol.signature = Some(self.node_text(node, source).to_string());
-
-                self.extract_type_constructors(node, source, &mut symbol);
-
-                return Some(symbol);
-            }
-        }
-        None
+        symbol
     }
 
-    fn extract_type_constructors(
+    /// Build the symbol for a type, type alias or port declaration
+    fn declaration_symbol(
         &self,
+        kind: SymbolKind,
         node: tree_sitter::Node,
+        name_node: tree_sitter::Node,
         source: &str,
-        parent_symbol: &mut ElmSymbol,
-    ) {
-        let mut cursor = node.walk();
-        for child in node.children(&mut cursor) {
-            if child.kind() == "union_variant" {
-                if let Some(name_node) = child.child(0) {
-                    let name = self.node_text(name_node, source).to_string();
-                    let range = self.node_to_range(name_node);
-                    let full_range = self.node_to_range(child);
-                    parent_symbol.variants.push(VariantInfo {
-                        name,
-                        range,
-                        full_range,
-                    });
-                }
-            }
+    ) -> ElmSymbol {
+        let name = self.node_text(name_node, source).to_string();
+        let mut symbol = ElmSymbol::new(name, kind, self.node_to_range(node));
+        // definition_range is just the identifier name (for renaming)
+        symbol.definition_range = Some(self.node_to_range(name_node));
+        symbol.signature = Some(self.node_text(node, source).to_string());
+
+        if kind == SymbolKind::ENUM {
+            self.extract_type_constructors(node, source, &mut symbol);
         }
+        symbol
     }
 
-    fn parse_type_alias_declaration(
+    fn extract_type_constructors(
         &self,
         node: tree_sitter::Node,
         source: &str,
-    ) -> Option<ElmSymbol> {
-        let mut cursor = node.walk();
-        for child in node.children(&mut cursor) {
-            if child.kind() == "upper_case_identifier" {
-                let name = self.node_text(child, source).to_string();
-                let name_range = self.node_to_range(child);
-                let full_range = self.node_to_range(node);
-                let mut symbol = ElmSymbol::new(name, SymbolKind::STRUCT, full_range);
-                // definition_range is just the identifier name (for renaming)
-                symbol.definition_range = Some(name_range);
-                symbol.signature = Some(self.node_text(node, source).to_string());
-                return Some(symbol);
-            }
-        }
-        None
-    }
-
-    fn parse_port_annotation(&self, node: tree_sitter::Node, source: &str) -> Option<ElmSymbol> {
+        parent_symbol: &mut ElmSymbol,
+    ) {
         let mut cursor = node.walk();
-        for child in node.children(&mut cursor) {
-            if child.kind() == "lower_case_identifier" {
-                let name = self.node_text(child, source).to_string();
-                let name_range = self.node_to_range(child);
-                let full_range = self.node_to_range(node);
-                let mut symbol = ElmSymbol::new(name, SymbolKind::INTERFACE, full_range);
-                // definition_range is just the identifier name (for renaming)
-                symbol.definition_range = Some(name_range);
-                symbol.signature = Some(self.node_text(node, source).to_string());
-                return Some(symbol);
+        for child in node.children_by_field_name("unionVariant", &mut cursor) {
+            if let Some(name_node) = child.child_by_field_name("name") {
+                parent_symbol.variants.push(VariantInfo {
+                    name: self.node_text(name_node, source).to_string(),
+                    range: self.node_to_range(name_node),
+                    full_range: self.node_to_range(child),
+                });
             }
         }
-        None
     }
 
     fn node_text<'a>(&self, node: tree_sitter::Node, source: &'a str) -> &'a str {
@@ -240,8 +200,145 @@ impl ElmParser {
     }
 }
 
+/// Comments attached to a declaration, nearest first.
+/// `--` and plain block comments must touch the line below them; a `{-|` doc
+/// comment may be separated by blank lines and ends the search.
+pub(crate) fn comments_above<'tree>(
+    node: tree_sitter::Node<'tree>,
+    source: &str,
+) -> Vec<tree_sitter::Node<'tree>> {
+    let mut comments = Vec::new();
+    let mut line = node.start_position().row;
+    let mut prev = node.prev_sibling();
+
+    while let Some(comment) = prev {
+        let adjacent = comment.end_position().row + 1 == line;
+        match comment.kind() {
+            "block_comment" if source[comment.byte_range()].starts_with("{-|") => {
+                // A doc comment right after the module header documents the module
+                let is_module_doc = comment
+                    .prev_sibling()
+                    .is_some_and(|p| p.kind() == "module_declaration");
+                if !is_module_doc {
+                    comments.push(comment);
+                }
+                break;
+            }
+            "line_comment" | "block_comment" if adjacent => {
+                comments.push(comment);
+                line = comment.start_position().row;
+            }
+            _ => break,
+        }
+        prev = comment.prev_sibling();
+    }
+    comments
+}
+
+/// The type annotation of the value `declaration`: written above it, maybe
+/// with comments in between, and naming the same value
+pub(crate) fn annotation_of<'tree>(
+    declaration: tree_sitter::Node<'tree>,
+    source: &str,
+) -> Option<tree_sitter::Node<'tree>> {
+    let name = declaration
+        .child_by_field_name("functionDeclarationLeft")?
+        .named_child(0)?;
+    let mut sibling = declaration.prev_named_sibling();
+    while let Some(node) = sibling {
+        match node.kind() {
+            "line_comment" | "block_comment" => sibling = node.prev_named_sibling(),
+            "type_annotation" => {
+                let annotated = node.child_by_field_name("name")?;
+                return (source[annotated.byte_range()] == source[name.byte_range()])
+                    .then_some(node);
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// The unqualified names under `node` whose kind is one of `kinds`, like
+/// `value_qid` for values or `record_base_identifier` for `{ model | ... }`,
+/// in source order
+pub(crate) fn unqualified_references<'tree>(
+    node: tree_sitter::Node<'tree>,
+    kinds: &[&str],
+) -> Vec<tree_sitter::Node<'tree>> {
+    let mut found = Vec::new();
+    collect_references(node, kinds, &mut found);
+    found
+}
+
+fn collect_references<'tree>(
+    node: tree_sitter::Node<'tree>,
+    kinds: &[&str],
+    found: &mut Vec<tree_sitter::Node<'tree>>,
+) {
+    if kinds.contains(&node.kind()) && node.named_child_count() == 1 {
+        found.push(node);
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_references(child, kinds, found);
+    }
+}
+
 impl Default for ElmParser {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols(source: &str) -> Vec<ElmSymbol> {
+        let parser = ElmParser::new();
+        let tree = parser.parse(source).unwrap();
+        parser.extract_symbols(&tree, source)
+    }
+
+    #[test]
+    fn test_doc_comments_attach_to_following_declaration() {
+        let source = r#"module A exposing (f, T)
+
+{-| Module docs
+-}
+
+{-| Doc for f
+-}
+-- note about f
+f : Int
+f =
+    1
+-- trailing after f
+
+-- comment for T
+type T
+    = A
+    | B
+"#;
+        let symbols = symbols(source);
+        let f = symbols.iter().find(|s| s.name == "f").unwrap();
+        assert_eq!(f.documentation.as_deref(), Some("Doc for f"));
+        assert_eq!(f.leading_comments, vec!["note about f"]);
+        assert_eq!(f.comments_range.unwrap().start, Position::new(5, 0));
+        assert_eq!(f.comments_range.unwrap().end, Position::new(7, 15));
+
+        let t = symbols.iter().find(|s| s.name == "T").unwrap();
+        assert_eq!(t.documentation, None);
+        assert_eq!(t.leading_comments, vec!["comment for T"]);
+    }
+
+    #[test]
+    fn test_module_doc_is_not_attached_to_first_declaration() {
+        let symbols = symbols("module A exposing (f)\n\n{-| Module docs -}\n\nf : Int\nf = 1\n");
+        let f = symbols.iter().find(|s| s.name == "f").unwrap();
+        assert_eq!(f.documentation, None);
+        assert_eq!(f.comments_range, None);
+    }
+}
@@ -0,0 +1,158 @@
+//! Summaries of workspace edits for confirmation dialogs.
+//!
+//! Backs the `elm-lsp/previewRename` request: instead of the edits themselves
+//! the client gets the files touched, how many edits each gets, and a few
+//! before/after lines per file. Nothing is applied.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tower_lsp::lsp_types::{Position, Range, TextDocumentPositionParams, TextEdit, Url};
+
+use crate::document::apply_edits;
+
+/// Changed lines shown per file
+const SAMPLES_PER_FILE: usize = 3;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewRenameParams {
+    #[serde(flatten)]
+    pub text_document_position: TextDocumentPositionParams,
+    pub new_name: String,
+    /// Overrides the `includeTests` setting for this request
+    pub include_tests: Option<bool>,
+    /// Overrides the `renameInDocs` setting for this request
+    pub rename_in_docs: Option<bool>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EditPreview {
+    pub file_count: usize,
+    pub edit_count: usize,
+    /// Files in path order
+    pub files: Vec<FilePreview>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FilePreview {
+    pub uri: Url,
+    pub edit_count: usize,
+    pub samples: Vec<LineChange>,
+}
+
+/// One changed line, before and after the edits on it
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LineChange {
+    /// 0-based line number
+    pub line: u32,
+    pub before: String,
+    pub after: String,
+}
+
+/// Summarize `changes`, reading each file's current text with `read`
+pub fn preview_edits(
+    changes: &HashMap<Url, Vec<TextEdit>>,
+    read: impl Fn(&Url) -> Option<String>,
+) -> EditPreview {
+    let mut files: Vec<FilePreview> = changes
+        .iter()
+        .filter(|(_, edits)| !edits.is_empty())
+        .map(|(uri, edits)| FilePreview {
+            uri: uri.clone(),
+            edit_count: edits.len(),
+            samples: read(uri)
+                .map(|text| sample_lines(&text, edits))
+                .unwrap_or_default(),
+        })
+        .collect();
+    files.sort_by(|a, b| a.uri.path().cmp(b.uri.path()));
+
+    EditPreview {
+        file_count: files.len(),
+        edit_count: files.iter().map(|f| f.edit_count).sum(),
+        files,
+    }
+}
+
+/// The first few lines touched by single-line edits, with the edits applied
+fn sample_lines(text: &str, edits: &[TextEdit]) -> Vec<LineChange> {
+    let mut by_line: BTreeMap<u32, Vec<&TextEdit>> = BTreeMap::new();
+    for edit in edits {
+        if edit.range.start.line == edit.range.end.line && !edit.new_text.contains('\n') {
+            by_line.entry(edit.range.start.line).or_default().push(edit);
+        }
+    }
+
+    by_line
+        .into_iter()
+        .filter_map(|(line, edits)| {
+            let before = text.lines().nth(line as usize)?;
+            // The line on its own, so the edits move to its first line
+            let edits: Vec<TextEdit> = edits
+                .into_iter()
+                .map(|edit| {
+                    let start = Position::new(0, edit.range.start.character);
+                    let end = Position::new(0, edit.range.end.character);
+                    TextEdit::new(Range::new(start, end), edit.new_text.clone())
+                })
+                .collect();
+            let after = apply_edits(before, &edits).ok()?;
+            Some(LineChange {
+                line,
+                before: before.to_string(),
+                after,
+            })
+        })
+        .take(SAMPLES_PER_FILE)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(line: u32, start: u32, end: u32, new_text: &str) -> TextEdit {
+        TextEdit {
+            range: Range::new(Position::new(line, start), Position::new(line, end)),
+            new_text: new_text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_preview_counts_and_samples() {
+        let a = Url::parse("file:///src/A.elm").unwrap();
+        let b = Url::parse("file:///src/B.elm").unwrap();
+        let mut changes = HashMap::new();
+        changes.insert(b.clone(), vec![edit(0, 0, 3, "bar")]);
+        changes.insert(
+            a.clone(),
+            vec![
+                edit(2, 0, 3, "bar"),
+                edit(3, 0, 3, "bar"),
+                edit(3, 6, 9, "bar"),
+            ],
+        );
+
+        let preview = preview_edits(&changes, |uri| {
+            (uri == &a).then(|| "module A exposing (..)\n\nfoo : Int\nfoo = foo\n".to_string())
+        });
+
+        assert_eq!(preview.file_count, 2);
+        assert_eq!(preview.edit_count, 4);
+        assert_eq!(preview.files[0].uri, a);
+        assert_eq!(
+            preview.files[0].samples[1],
+            LineChange {
+                line: 3,
+                before: "foo = foo".to_string(),
+                after: "bar = bar".to_string(),
+            }
+        );
+        // Unreadable files still report their edit count
+        assert_eq!(preview.files[1].edit_count, 1);
+        assert!(preview.files[1].samples.is_empty());
+    }
+}
@@ -0,0 +1,13 @@
+; Items of a single exposing list.
+
+(exposing_list
+  doubleDot: (double_dot) @all)
+
+(exposing_list
+  (exposed_value
+    (lower_case_identifier) @value))
+
+(exposing_list
+  (exposed_type
+    (upper_case_identifier) @type
+    (exposed_union_constructors)? @constructors))
@@ -0,0 +1,12 @@
+; Import clauses with their optional alias and exposing list.
+
+(import_clause
+  moduleName: (upper_case_qid) @module
+  asClause: (as_clause
+    name: (upper_case_identifier) @alias)?
+  exposing: (exposing_list)? @exposing)
+
+; The module's own exposing list.
+
+(module_declaration
+  exposing: (exposing_list) @module_exposing)
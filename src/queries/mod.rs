@@ -0,0 +1,71 @@
+//! Compiled tree-sitter queries used for extraction.
+//!
+//! Each query lives in a `.scm` file next to this module and is compiled once
+//! on first use. Supporting a new node kind means adding a pattern to the
+//! relevant file and handling its capture name at the call site.
+
+use std::sync::LazyLock;
+use tree_sitter::{Node, Query, QueryCapture, QueryCursor, StreamingIterator};
+
+fn compile(source: &str) -> Query {
+    Query::new(&tree_sitter_elm::LANGUAGE.into(), source).expect("Invalid Elm query")
+}
+
+/// Declarations (values, types, aliases, ports) and top-level type annotations
+pub static SYMBOLS: LazyLock<Query> = LazyLock::new(|| compile(include_str!("symbols.scm")));
+
+/// Qualified and unqualified identifiers that may reference a symbol
+pub static REFERENCES: LazyLock<Query> =
+    LazyLock::new(|| compile(include_str!("references.scm")));
+
+/// Import clauses and the module's own exposing list
+pub static IMPORTS: LazyLock<Query> = LazyLock::new(|| compile(include_str!("imports.scm")));
+
+/// Items of a single exposing list
+pub static EXPOSING: LazyLock<Query> = LazyLock::new(|| compile(include_str!("exposing.scm")));
+
+/// The captures of one query match, looked up by capture name
+pub struct Captures<'q, 'tree> {
+    query: &'q Query,
+    captures: &'q [QueryCapture<'tree>],
+}
+
+impl<'tree> Captures<'_, 'tree> {
+    /// Get the node captured under `name`, if the pattern captured it
+    pub fn get(&self, name: &str) -> Option<Node<'tree>> {
+        self.captures
+            .iter()
+            .find(|c| self.query.capture_names()[c.index as usize] == name)
+            .map(|c| c.node)
+    }
+}
+
+/// Run `query` over `node` and its descendants, calling `f` for each match in document order
+pub fn for_each_match<'tree>(
+    query: &Query,
+    node: Node<'tree>,
+    source: &str,
+    mut f: impl FnMut(&Captures<'_, 'tree>),
+) {
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.matches(query, node, source.as_bytes());
+    while let Some(m) = matches.next() {
+        f(&Captures {
+            query,
+            captures: m.captures,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queries_compile() {
+        LazyLock::force(&SYMBOLS);
+        LazyLock::force(&REFERENCES);
+        LazyLock::force(&IMPORTS);
+        LazyLock::force(&EXPOSING);
+    }
+}
@@ -0,0 +1,8 @@
+; Every identifier that may refer to a symbol. Whether a capture is a
+; declaration, a module prefix or a real reference is decided by the caller.
+
+(value_qid) @qualified
+(upper_case_qid) @qualified
+
+(lower_case_identifier) @identifier
+(upper_case_identifier) @identifier
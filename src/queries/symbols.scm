@@ -0,0 +1,20 @@
+; Top-level declarations and the type annotations attached to them.
+; Value declarations are matched at any depth so let-bound functions are included.
+
+(file
+  (type_annotation
+    name: (lower_case_identifier) @name) @annotation)
+
+(value_declaration
+  functionDeclarationLeft: (function_declaration_left
+    .
+    (lower_case_identifier) @name)) @value
+
+(type_declaration
+  name: (upper_case_identifier) @name) @type
+
+(type_alias_declaration
+  name: (upper_case_identifier) @name) @type_alias
+
+(port_annotation
+  name: (lower_case_identifier) @name) @port
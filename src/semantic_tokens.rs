@@ -0,0 +1,352 @@
+//! Semantic tokens: what each name, literal and comment in a module is.
+//!
+//! Tokens come from the parse tree alone, by where a name appears: the last
+//! segment of `Html.Attributes.class` is a value and the rest a module, the
+//! name after `type` is a type declaration, a pattern variable in a function
+//! head is a parameter. A range request only walks the nodes on the lines it
+//! asks for, and a delta request sends the tokens between the first and last
+//! change since the previous result, so large files do not ship every token
+//! on each edit.
+
+use tower_lsp::lsp_types::{
+    Range, SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensEdit,
+    SemanticTokensLegend,
+};
+use tree_sitter::{Node, Tree};
+
+const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::TYPE_PARAMETER,
+    SemanticTokenType::ENUM_MEMBER,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::OPERATOR,
+    SemanticTokenType::COMMENT,
+    SemanticTokenType::STRING,
+    SemanticTokenType::NUMBER,
+];
+
+const NAMESPACE: u32 = 0;
+const TYPE: u32 = 1;
+const TYPE_PARAMETER: u32 = 2;
+const ENUM_MEMBER: u32 = 3;
+const FUNCTION: u32 = 4;
+const PARAMETER: u32 = 5;
+const VARIABLE: u32 = 6;
+const PROPERTY: u32 = 7;
+const OPERATOR: u32 = 8;
+const COMMENT: u32 = 9;
+const STRING: u32 = 10;
+const NUMBER: u32 = 11;
+
+const TOKEN_MODIFIERS: &[SemanticTokenModifier] = &[
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::DOCUMENTATION,
+];
+
+const DECLARATION: u32 = 1;
+const DOCUMENTATION: u32 = 2;
+
+/// The token types and modifiers the server reports
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+/// A token at an absolute position, before encoding
+struct Token {
+    line: u32,
+    /// In UTF-16 code units, as positions are
+    start: u32,
+    length: u32,
+    token_type: u32,
+    modifiers: u32,
+}
+
+/// The encoded tokens of a module, or of the lines `range` spans
+pub fn tokens(tree: &Tree, source: &str, range: Option<Range>) -> Vec<SemanticToken> {
+    let lines = range.map_or((0, u32::MAX), |r| (r.start.line, r.end.line));
+    let mut found = Vec::new();
+    visit(tree.root_node(), source, lines, false, &mut found);
+    found.retain(|t| t.line >= lines.0 && t.line <= lines.1);
+    encode(&found)
+}
+
+/// Edits turning `previous` into `current`: one replacing everything
+/// between their common prefix and common suffix, or none when they match
+pub fn edits(previous: &[SemanticToken], current: &[SemanticToken]) -> Vec<SemanticTokensEdit> {
+    let prefix = previous
+        .iter()
+        .zip(current)
+        .take_while(|(a, b)| a == b)
+        .count();
+    if prefix == previous.len() && prefix == current.len() {
+        return Vec::new();
+    }
+    let suffix = previous[prefix..]
+        .iter()
+        .rev()
+        .zip(current[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    // Each token is five integers in the encoded data
+    vec![SemanticTokensEdit {
+        start: 5 * prefix as u32,
+        delete_count: 5 * (previous.len() - prefix - suffix) as u32,
+        data: Some(current[prefix..current.len() - suffix].to_vec()),
+    }]
+}
+
+fn visit(node: Node, source: &str, lines: (u32, u32), in_params: bool, out: &mut Vec<Token>) {
+    if (node.end_position().row as u32) < lines.0 || node.start_position().row as u32 > lines.1 {
+        return;
+    }
+    match node.kind() {
+        "line_comment" | "block_comment" => {
+            let documentation = source[node.byte_range()].starts_with("{-|");
+            let modifiers = if documentation { DOCUMENTATION } else { 0 };
+            return push_lines(node, source, COMMENT, modifiers, out);
+        }
+        "string_constant_expr" | "char_constant_expr" => {
+            return push_lines(node, source, STRING, 0, out);
+        }
+        "number_constant_expr" => return push(node, source, NUMBER, 0, out),
+        "operator_identifier" => return push(node, source, OPERATOR, 0, out),
+        "upper_case_qid" | "value_qid" => return qualified(node, source, out),
+        "lower_type_name" | "type_variable" => return push(node, source, TYPE_PARAMETER, 0, out),
+        "lower_case_identifier" | "upper_case_identifier" => {
+            if let Some((token_type, modifiers)) = identifier(node, in_params) {
+                push(node, source, token_type, modifiers, out);
+            }
+            return;
+        }
+        _ => {}
+    }
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        let in_params = match node.kind() {
+            "function_declaration_left" => true,
+            // The patterns of a lambda, not its body
+            "anonymous_function_expr" => child.kind().ends_with("pattern"),
+            _ => in_params,
+        };
+        visit(child, source, lines, in_params, out);
+    }
+}
+
+/// What a plain identifier is, from the node it belongs to
+fn identifier(node: Node, in_params: bool) -> Option<(u32, u32)> {
+    let parent = node.parent()?;
+    let is_first = parent.named_child(0) == Some(node);
+    Some(match parent.kind() {
+        "type_declaration" | "type_alias_declaration" => (TYPE, DECLARATION),
+        "union_variant" => (ENUM_MEMBER, DECLARATION),
+        "exposed_type" | "exposed_union_constructors" => (TYPE, 0),
+        "exposed_value" => (FUNCTION, 0),
+        "as_clause" => (NAMESPACE, DECLARATION),
+        "function_declaration_left" if is_first => (FUNCTION, DECLARATION),
+        "type_annotation" | "port_annotation" => (FUNCTION, DECLARATION),
+        "lower_pattern" if in_params => (PARAMETER, DECLARATION),
+        "lower_pattern" => (VARIABLE, DECLARATION),
+        "record_base_identifier" => (VARIABLE, 0),
+        "field_type" => (PROPERTY, DECLARATION),
+        "field" | "field_access_expr" | "field_accessor_function_expr" => (PROPERTY, 0),
+        _ => return None,
+    })
+}
+
+/// `Html.Attributes.class`: modules, then a value, type or constructor
+fn qualified(node: Node, source: &str, out: &mut Vec<Token>) {
+    let mut cursor = node.walk();
+    let segments: Vec<Node> = node
+        .named_children(&mut cursor)
+        .filter(|n| n.kind() != "dot")
+        .collect();
+    let parent = node.parent().map_or("", |p| p.kind());
+    let last = match parent {
+        // `module Html.Attributes`, `import Html.Attributes`
+        "module_declaration" | "import_clause" => NAMESPACE,
+        _ if node.kind() == "value_qid" => VARIABLE,
+        "value_expr" | "union_pattern" => ENUM_MEMBER,
+        _ => TYPE,
+    };
+    for (i, segment) in segments.iter().enumerate() {
+        let token_type = if i + 1 == segments.len() {
+            last
+        } else {
+            NAMESPACE
+        };
+        push(*segment, source, token_type, 0, out);
+    }
+}
+
+fn push(node: Node, source: &str, token_type: u32, modifiers: u32, out: &mut Vec<Token>) {
+    let start = node.start_position();
+    let line_start = node.start_byte() - start.column;
+    out.push(Token {
+        line: start.row as u32,
+        start: utf16_len(&source[line_start..node.start_byte()]),
+        length: utf16_len(&source[node.byte_range()]),
+        token_type,
+        modifiers,
+    });
+}
+
+/// One token per line of a node, since clients need not support tokens
+/// spanning lines
+fn push_lines(node: Node, source: &str, token_type: u32, modifiers: u32, out: &mut Vec<Token>) {
+    let start = node.start_position();
+    let mut column = utf16_len(&source[node.start_byte() - start.column..node.start_byte()]);
+    for (i, line) in source[node.byte_range()].split('\n').enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if !line.is_empty() {
+            out.push(Token {
+                line: (start.row + i) as u32,
+                start: column,
+                length: utf16_len(line),
+                token_type,
+                modifiers,
+            });
+        }
+        column = 0;
+    }
+}
+
+fn utf16_len(text: &str) -> u32 {
+    text.encode_utf16().count() as u32
+}
+
+/// Positions relative to the previous token, as the protocol sends them
+fn encode(tokens: &[Token]) -> Vec<SemanticToken> {
+    let mut previous = (0, 0);
+    tokens
+        .iter()
+        .map(|token| {
+            let delta_line = token.line - previous.0;
+            let delta_start = if delta_line == 0 {
+                token.start - previous.1
+            } else {
+                token.start
+            };
+            previous = (token.line, token.start);
+            SemanticToken {
+                delta_line,
+                delta_start,
+                length: token.length,
+                token_type: token.token_type,
+                token_modifiers_bitset: token.modifiers,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ElmParser;
+    use tower_lsp::lsp_types::Position;
+
+    /// Decode tokens into (line, start, text, type, modifiers)
+    fn decoded(source: &str, tokens: &[SemanticToken]) -> Vec<(u32, u32, String, u32, u32)> {
+        let lines: Vec<&str> = source.lines().collect();
+        let (mut line, mut start) = (0, 0);
+        tokens
+            .iter()
+            .map(|token| {
+                if token.delta_line > 0 {
+                    start = 0;
+                }
+                line += token.delta_line;
+                start += token.delta_start;
+                let text: String = lines[line as usize]
+                    .chars()
+                    .skip(start as usize)
+                    .take(token.length as usize)
+                    .collect();
+                (
+                    line,
+                    start,
+                    text,
+                    token.token_type,
+                    token.token_modifiers_bitset,
+                )
+            })
+            .collect()
+    }
+
+    fn of_type(found: &[(u32, u32, String, u32, u32)], token_type: u32) -> Vec<&str> {
+        found
+            .iter()
+            .filter(|t| t.3 == token_type)
+            .map(|t| t.2.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_tokens_classify_names() {
+        let source = "module Main exposing (main)\n\nimport Html.Attributes as A\n\n\n{-| The model -}\ntype alias Model a =\n    { count : a }\n\n\ntype Msg\n    = Inc Int\n\n\nupdate : Msg -> Model Int -> Model Int\nupdate (Inc n) model =\n    { model | count = model.count + n }\n\n\nmain =\n    A.class \"é\" |> always 1\n";
+        let tree = ElmParser::new().parse(source).unwrap();
+        let found = decoded(source, &tokens(&tree, source, None));
+
+        assert_eq!(
+            of_type(&found, NAMESPACE),
+            ["Main", "Html", "Attributes", "A", "A"]
+        );
+        assert_eq!(
+            of_type(&found, TYPE),
+            ["Model", "Msg", "Int", "Msg", "Model", "Int", "Model", "Int"]
+        );
+        assert_eq!(of_type(&found, ENUM_MEMBER), ["Inc", "Inc"]);
+        assert_eq!(of_type(&found, PARAMETER), ["n", "model"]);
+        assert_eq!(of_type(&found, PROPERTY), ["count", "count", "count"]);
+        assert_eq!(of_type(&found, TYPE_PARAMETER), ["a", "a"]);
+        assert_eq!(of_type(&found, STRING), ["\"é\""]);
+        assert_eq!(of_type(&found, OPERATOR), ["+", "|>"]);
+        assert_eq!(of_type(&found, NUMBER), ["1"]);
+        let comment = found.iter().find(|t| t.3 == COMMENT).unwrap();
+        assert_eq!(
+            (comment.2.as_str(), comment.4),
+            ("{-| The model -}", DOCUMENTATION)
+        );
+        let update = found.iter().find(|t| t.2 == "update").unwrap();
+        assert_eq!((update.3, update.4), (FUNCTION, DECLARATION));
+
+        // Columns count UTF-16 code units: `|>` follows the two-byte `é`
+        let pipe = found.iter().find(|t| t.2 == "|>").unwrap();
+        assert_eq!((pipe.0, pipe.1), (20, 16));
+    }
+
+    #[test]
+    fn test_range_tokens_cover_only_its_lines() {
+        let source =
+            "module Main exposing (main)\n\n\nmain =\n    \"a\n    b\"\n\n\nother =\n    2\n";
+        let tree = ElmParser::new().parse(source).unwrap();
+        let range = Range::new(Position::new(4, 0), Position::new(9, 0));
+        let found = decoded(source, &tokens(&tree, source, Some(range)));
+        let texts: Vec<&str> = found.iter().map(|t| t.2.as_str()).collect();
+        assert_eq!(texts, ["\"a", "    b\"", "other", "2"]);
+        assert_eq!(found[0].0, 4);
+    }
+
+    #[test]
+    fn test_edits_replace_only_the_changed_tokens() {
+        let parser = ElmParser::new();
+        let before = "module Main exposing (..)\n\n\na =\n    1\n\n\nb =\n    2\n";
+        let after = "module Main exposing (..)\n\n\na =\n    x 1\n\n\nb =\n    2\n";
+        let previous = tokens(&parser.parse(before).unwrap(), before, None);
+        let current = tokens(&parser.parse(after).unwrap(), after, None);
+
+        let found = edits(&previous, &current);
+        assert_eq!(found.len(), 1);
+        // `Main` and `a` are kept; `1` becomes `x 1`; `b` and `2` are kept
+        assert_eq!(found[0].start, 10);
+        assert_eq!(found[0].delete_count, 5);
+        assert_eq!(found[0].data.as_ref().unwrap().len(), 2);
+        assert!(edits(&current, &current).is_empty());
+    }
+}
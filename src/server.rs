@@ -1,14 +1,30 @@
+//! The language server: LSP requests and notifications, and the custom
+//! `elm.*` commands, answered from the [`Workspaces`] of the open folders.
+
 use dashmap::DashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::RwLock;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer};
 
+use crate::analysis;
+use crate::config::{DeclarationOrder, Settings, Severity};
 use crate::diagnostics::DiagnosticsProvider;
-use crate::document::{Document, VariantInfo};
+use crate::document::{node_range, read_source, Document, LineEnding, VariantInfo};
+use crate::logging;
+use crate::matcher;
+use crate::on_type;
 use crate::parser::ElmParser;
-use crate::workspace::{BranchConfig, Workspace};
+use crate::preview::{preview_edits, EditPreview, PreviewRenameParams};
+use crate::semantic_tokens;
+use crate::workspace::{
+    discover_projects, elm_json_symbols, BranchConfig, CallPaths, CallPathsParams,
+    ConstructorUsages, DeclarationImporters, EvergreenDrift, GlobalSymbol, ImportSpec, IndexStats,
+    RefactorResult, VariantReplacement, Workspace, Workspaces, DEFAULT_MAX_DEPTH,
+    DEFAULT_MAX_PATHS,
+};
 
 // Custom commands
 const CMD_MOVE_FUNCTION: &str = "elm.moveFunction";
@@ -26,13 +42,78 @@ const CMD_PREPARE_REMOVE_FIELD: &str = "elm.prepareRemoveField";
 const CMD_REMOVE_FIELD: &str = "elm.removeField";
 const CMD_PREPARE_ADD_VARIANT: &str = "elm.prepareAddVariant";
 const CMD_ADD_VARIANT: &str = "elm.addVariant";
+const CMD_GET_IMPORTERS: &str = "elm.getImporters";
+const CMD_FIND_REFERENCES: &str = "elm.findReferences";
+const CMD_REORDER: &str = "elm.reorder";
+const CMD_TUPLE_TO_RECORD: &str = "elm.tupleToRecord";
+const CMD_INTRODUCE_PARAMETER: &str = "elm.introduceParameter";
+const CMD_HOIST_CONSTANT: &str = "elm.hoistConstant";
+const CMD_EXTRACT_VIEW_HELPER: &str = "elm.extractViewHelper";
+const CMD_EXTRACT_VIEW_HELPER_WITH_MSG: &str = "elm.extractViewHelperWithMsg";
+const CMD_ADD_DEPENDENCY: &str = "elm.addDependency";
+const CMD_ORGANIZE_DECLARATIONS: &str = "elm.organizeDeclarations";
+
+/// Longest type annotation shown as a document symbol's detail
+const MAX_SYMBOL_DETAIL_WIDTH: usize = 100;
 
 pub struct ElmLanguageServer {
     client: Client,
     documents: DashMap<Url, Document>,
     parser: ElmParser,
-    workspace: RwLock<Option<Workspace>>,
+    workspaces: RwLock<Workspaces>,
     diagnostics_provider: RwLock<DiagnosticsProvider>,
+    /// Client can register file watchers for us (workspace/didChangeWatchedFiles)
+    can_watch_files: AtomicBool,
+    /// Client accepts LocationLink results for textDocument/definition
+    definition_link_support: AtomicBool,
+    /// Client accepts LocationLink results for textDocument/typeDefinition
+    type_definition_link_support: AtomicBool,
+    /// Client applies edits we send with workspace/applyEdit
+    apply_edit_support: AtomicBool,
+    /// Client accepts versioned `documentChanges` in workspace edits
+    document_changes_support: AtomicBool,
+    /// Client renames files as part of a workspace edit
+    rename_file_support: AtomicBool,
+    /// The user was told that formatting falls back to the built-in formatter
+    fallback_format_notified: AtomicBool,
+    /// The client started the server in restricted mode, which configuration
+    /// changes cannot lift
+    started_restricted: AtomicBool,
+    /// The last full semantic tokens sent for each document, for deltas
+    semantic_tokens: DashMap<Url, SemanticTokens>,
+    /// Source of semantic token result ids
+    semantic_tokens_id: AtomicU64,
+    /// Client pulls diagnostics with textDocument/diagnostic instead of
+    /// having them published
+    pull_diagnostics: AtomicBool,
+    /// Client accepts workspace/diagnostic/refresh requests
+    diagnostic_refresh_support: AtomicBool,
+    /// The last diagnostics pulled for each document, for unchanged reports
+    pulled_diagnostics: DashMap<Url, PulledDiagnostics>,
+    /// Source of diagnostic result ids
+    diagnostics_id: AtomicU64,
+    /// The compiler's diagnostics for each file as of its last save: the
+    /// compiler reads files from disk, so it runs on open and save only
+    compiled: DashMap<Url, Vec<Diagnostic>>,
+}
+
+/// Diagnostics reported to a pulling client, with the document version they
+/// were computed for
+#[derive(Clone)]
+struct PulledDiagnostics {
+    version: i32,
+    result_id: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// The answer to `elm-lsp/workspaceSymbols`
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceSymbols {
+    pub symbols: Vec<SymbolInformation>,
+    /// Matches were left out by `workspaceSymbolLimit`; a narrower query
+    /// brings them back
+    pub is_incomplete: bool,
 }
 
 impl ElmLanguageServer {
@@ -41,283 +122,1585 @@ impl ElmLanguageServer {
             client,
             documents: DashMap::new(),
             parser: ElmParser::new(),
-            workspace: RwLock::new(None),
+            workspaces: RwLock::new(Workspaces::new()),
             diagnostics_provider: RwLock::new(DiagnosticsProvider::new()),
+            can_watch_files: AtomicBool::new(false),
+            definition_link_support: AtomicBool::new(false),
+            type_definition_link_support: AtomicBool::new(false),
+            apply_edit_support: AtomicBool::new(false),
+            document_changes_support: AtomicBool::new(false),
+            rename_file_support: AtomicBool::new(false),
+            fallback_format_notified: AtomicBool::new(false),
+            started_restricted: AtomicBool::new(false),
+            semantic_tokens: DashMap::new(),
+            semantic_tokens_id: AtomicU64::new(0),
+            pull_diagnostics: AtomicBool::new(false),
+            diagnostic_refresh_support: AtomicBool::new(false),
+            pulled_diagnostics: DashMap::new(),
+            diagnostics_id: AtomicU64::new(0),
+            compiled: DashMap::new(),
         }
     }
 
-    async fn on_change(&self, uri: Url, text: String, version: i32) {
-        tracing::info!("on_change: uri={}", uri);
-        let doc = Document::new(uri.clone(), text.clone(), version);
+    /// Create and index the workspaces of a folder: one for the folder and
+    /// one for each project nested in it
+    fn open_folder(&self, path: PathBuf) {
+        for root in discover_projects(&path) {
+            self.open_project(&path, root);
+        }
+    }
 
-        if let Some(tree) = self.parser.parse(&text) {
-            let symbols = self.parser.extract_symbols(&tree, &text);
-            let mut doc = doc;
-            doc.symbols = symbols;
-            self.documents.insert(uri.clone(), doc);
+    fn open_project(&self, folder: &Path, path: PathBuf) {
+        tracing::info!(target: logging::INDEX, "Initializing workspace at {:?}", path);
 
-            // Update workspace index
-            if let Ok(mut ws) = self.workspace.write() {
-                if let Some(workspace) = ws.as_mut() {
-                    workspace.update_file(&uri, &text);
+        if let Ok(mut diag) = self.diagnostics_provider.write() {
+            diag.add_workspace_root(&path.to_string_lossy());
+        }
+
+        // Index outside the lock so requests for other folders are not blocked
+        let mut workspace = Workspace::new(path);
+        workspace.folder = folder.to_path_buf();
+        if let Ok(ws) = self.workspaces.read() {
+            workspace.settings = ws.settings.clone();
+        }
+        if let Err(e) = workspace.initialize() {
+            tracing::error!(target: logging::INDEX, "Failed to initialize workspace: {}", e);
+            return;
+        }
+        // Documents already open in the editor are indexed as they are there
+        for doc in self.documents.iter() {
+            if let Ok(path) = doc.uri.to_file_path() {
+                if path.starts_with(&workspace.root_path) {
+                    workspace.open_buffer(&doc.uri, &doc.text);
                 }
             }
-        } else {
-            tracing::warn!("Failed to parse document");
-            self.documents.insert(uri.clone(), doc);
         }
 
-        let diagnostics = self.get_diagnostics(&uri);
-        self.client
-            .publish_diagnostics(uri, diagnostics, None)
-            .await;
-    }
+        let module_count = workspace.modules.len();
+        let symbol_count: usize = workspace.symbols.values().map(|v| v.len()).sum();
+        tracing::info!(
+            target: logging::INDEX,
+            "Workspace initialized: {} modules, {} symbols",
+            module_count,
+            symbol_count
+        );
 
-    fn get_diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
-        if let Ok(provider) = self.diagnostics_provider.read() {
-            provider.get_diagnostics(uri)
-        } else {
-            Vec::new()
+        if let Ok(mut ws) = self.workspaces.write() {
+            ws.add(workspace);
         }
     }
 
-    /// Get the word at a position in the document
-    fn get_word_at_position(&self, uri: &Url, position: Position) -> Option<String> {
-        // Try from open document first
-        if let Some(doc) = self.documents.get(uri) {
-            if let Some(line) = doc.get_line(position.line) {
-                return self.extract_word_from_line(line, position.character as usize);
+    /// Dispose the workspace of a folder removed from the editor
+    fn close_folder(&self, path: &Path) {
+        tracing::info!(target: logging::INDEX, "Closing workspace at {:?}", path);
+        if let Ok(mut ws) = self.workspaces.write() {
+            if let Ok(mut diag) = self.diagnostics_provider.write() {
+                for workspace in ws.iter().filter(|w| w.folder == path) {
+                    diag.remove_workspace_root(&workspace.root_path.to_string_lossy());
+                }
             }
+            ws.remove(path);
         }
+        self.documents.retain(|uri, _| {
+            uri.to_file_path()
+                .map_or(true, |file| !file.starts_with(path))
+        });
+    }
 
-        // Fallback: read from disk if document not open
-        if let Ok(path) = uri.to_file_path() {
-            if let Ok(content) = std::fs::read_to_string(&path) {
-                if let Some(line) = content.lines().nth(position.line as usize) {
-                    return self.extract_word_from_line(line, position.character as usize);
+    /// Index packages installed since the last check and tell the user about them
+    async fn refresh_packages(&self) {
+        let added = {
+            let mut ws = match self.workspaces.write() {
+                Ok(ws) => ws,
+                Err(_) => return,
+            };
+            let mut added = Vec::new();
+            for workspace in ws.iter_mut() {
+                match workspace.refresh_packages() {
+                    Ok(names) => added.extend(names),
+                    Err(e) => {
+                        tracing::warn!(target: logging::INDEX, "Failed to refresh packages: {}", e)
+                    }
                 }
             }
-        }
+            added
+        };
 
-        None
+        if !added.is_empty() {
+            self.client
+                .log_message(
+                    MessageType::INFO,
+                    format!("Indexed new packages: {}", added.join(", ")),
+                )
+                .await;
+        }
     }
 
-    fn extract_word_from_line(&self, line: &str, col: usize) -> Option<String> {
-        self.extract_word_from_line_impl(line, col, true)
+    /// Publish the problems of each package's elm.json
+    async fn publish_elm_json_diagnostics(&self) {
+        let published: Vec<(Url, Vec<Diagnostic>)> = match self.workspaces.read() {
+            Ok(ws) => ws
+                .iter()
+                .filter_map(|workspace| {
+                    let uri = Url::from_file_path(workspace.root_path.join("elm.json")).ok()?;
+                    let mut diagnostics = workspace.elm_json_diagnostics();
+                    let severities = &ws.settings.diagnostic_severity;
+                    analysis::codes::apply_severities(&mut diagnostics, severities);
+                    Some((uri, diagnostics))
+                })
+                .collect(),
+            Err(_) => return,
+        };
+        for (uri, diagnostics) in published {
+            self.client
+                .publish_diagnostics(uri, diagnostics, None)
+                .await;
+        }
     }
 
-    /// Extract just the immediate word (no dots) from a line
-    fn extract_simple_word_from_line(&self, line: &str, col: usize) -> Option<String> {
-        self.extract_word_from_line_impl(line, col, false)
-    }
+    /// Ask the client to watch Elm sources, elm.json and the package cache
+    async fn register_file_watchers(&self) {
+        let package_dirs = match self.workspaces.read() {
+            Ok(ws) => ws
+                .iter()
+                .flat_map(|workspace| workspace.package_dirs())
+                .collect(),
+            Err(_) => Vec::new(),
+        };
 
-    fn extract_word_from_line_impl(
-        &self,
-        line: &str,
-        col: usize,
-        include_dots: bool,
-    ) -> Option<String> {
-        if col >= line.len() {
-            return None;
+        let mut watchers = vec![
+            FileSystemWatcher {
+                glob_pattern: GlobPattern::String("**/*.elm".to_string()),
+                kind: None,
+            },
+            FileSystemWatcher {
+                glob_pattern: GlobPattern::String("**/elm.json".to_string()),
+                kind: None,
+            },
+        ];
+        for base_uri in package_dirs
+            .into_iter()
+            .filter(|dir| dir.exists())
+            .filter_map(|dir| Url::from_directory_path(dir).ok())
+        {
+            watchers.push(FileSystemWatcher {
+                glob_pattern: GlobPattern::Relative(RelativePattern {
+                    base_uri: OneOf::Right(base_uri),
+                    pattern: "**/elm.json".to_string(),
+                }),
+                kind: Some(WatchKind::Create),
+            });
         }
 
-        // Find word boundaries
-        let chars: Vec<char> = line.chars().collect();
-        let mut start = col;
-        let mut end = col;
+        let options = DidChangeWatchedFilesRegistrationOptions { watchers };
+        let registration = Registration {
+            id: "elm-lsp-watched-files".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(options).ok(),
+        };
+        if let Err(e) = self.client.register_capability(vec![registration]).await {
+            tracing::warn!(target: logging::INDEX, "Failed to register file watchers: {}", e);
+        }
+    }
 
-        let is_word_char =
-            |c: char| -> bool { c.is_alphanumeric() || c == '_' || (include_dots && c == '.') };
+    async fn on_change(&self, uri: Url, text: String, version: i32) {
+        tracing::info!(target: logging::INDEX, "on_change: uri={}", uri);
+        let doc = Document::new(uri, text, version);
+        let (uri, text) = (doc.uri.clone(), doc.text.clone());
+        let pull = self.pull_diagnostics.load(Ordering::Relaxed);
+
+        let mut syntax_errors = Vec::new();
+        let mut lint_warnings = Vec::new();
+        let mut fixes = Vec::new();
+        if let Some(tree) = self.parser.parse(&text) {
+            if !pull {
+                syntax_errors = analysis::syntax_diagnostics(&tree, &text);
+                lint_warnings = self.lint_diagnostics(&uri, &tree, &text);
+            }
+            let symbols = self.parser.extract_symbols(&tree, &text);
+            let mut doc = doc;
+            doc.symbols = symbols;
+            self.documents.insert(uri.clone(), doc);
 
-        while start > 0 && is_word_char(chars[start - 1]) {
-            start -= 1;
+            // Update workspace index
+            if let Ok(mut ws) = self.workspaces.write() {
+                if let Some(workspace) = ws.for_uri_mut(&uri) {
+                    workspace.open_buffer(&uri, &text);
+                }
+            }
+            if !pull {
+                fixes = self.document_fixes(&uri, &tree, &text);
+            }
+        } else {
+            tracing::warn!(target: logging::INDEX, "Failed to parse document");
+            self.documents.insert(uri.clone(), doc);
         }
 
-        while end < chars.len() && is_word_char(chars[end]) {
-            end += 1;
+        // A pulling client asks for the diagnostics when it shows them
+        if pull {
+            self.pulled_diagnostics.remove(&uri);
+            return;
         }
 
-        if start < end {
-            Some(chars[start..end].iter().collect())
-        } else {
-            None
+        // Syntax errors are known right away, so show them before elm make runs
+        if !syntax_errors.is_empty() {
+            self.client
+                .publish_diagnostics(uri.clone(), syntax_errors.clone(), None)
+                .await;
+        }
+
+        let mut diagnostics = syntax_errors;
+        diagnostics.extend(lint_warnings);
+        diagnostics.extend(self.workspace_diagnostics(&uri));
+        analysis::attach_fixes(&mut diagnostics, fixes, version);
+        diagnostics.extend(self.compiler_diagnostics(&uri));
+        self.client
+            .publish_diagnostics(uri, diagnostics, None)
+            .await;
+    }
+
+    /// All the diagnostics of a document: syntax errors, lint warnings,
+    /// workspace checks and the compiler's errors
+    fn document_diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        let document = self
+            .documents
+            .get(uri)
+            .map(|doc| (doc.text.clone(), doc.version));
+        let mut diagnostics = Vec::new();
+        let mut fixes = Vec::new();
+        if let Some((text, tree)) = document
+            .as_ref()
+            .and_then(|(text, _)| Some((text, self.parser.parse(text)?)))
+        {
+            diagnostics = analysis::syntax_diagnostics(&tree, text);
+            diagnostics.extend(self.lint_diagnostics(uri, &tree, text));
+            fixes = self.document_fixes(uri, &tree, text);
+        }
+        diagnostics.extend(self.workspace_diagnostics(uri));
+        if let Some((_, version)) = document {
+            analysis::attach_fixes(&mut diagnostics, fixes, version);
         }
+        diagnostics.extend(self.compiler_diagnostics(uri));
+        diagnostics
     }
 
-    fn get_variant_at_position(
+    /// Every fix for the lint and workspace diagnostics of a document, to
+    /// carry in the diagnostics' `data`
+    fn document_fixes(
         &self,
         uri: &Url,
-        position: Position,
-    ) -> Option<(String, VariantInfo, usize, usize, Vec<String>)> {
-        if let Some(doc) = self.documents.get(uri) {
-            for symbol in &doc.symbols {
-                if symbol.kind == SymbolKind::ENUM {
-                    for (idx, variant) in symbol.variants.iter().enumerate() {
-                        if variant.range.start.line == position.line
-                            && position.character >= variant.range.start.character
-                            && position.character <= variant.range.end.character
-                        {
-                            let all_variants: Vec<String> =
-                                symbol.variants.iter().map(|v| v.name.clone()).collect();
-                            return Some((
-                                symbol.name.clone(),
-                                variant.clone(),
-                                idx,
-                                symbol.variants.len(),
-                                all_variants,
-                            ));
-                        }
-                    }
+        tree: &tree_sitter::Tree,
+        text: &str,
+    ) -> Vec<(Diagnostic, analysis::Fix)> {
+        let everything = Range::new(Position::new(0, 0), Position::new(u32::MAX, 0));
+        let in_document = |fix: analysis::QuickFix| {
+            let (title, changes) = (
+                fix.title,
+                std::collections::HashMap::from([(uri.clone(), fix.edits)]),
+            );
+            (fix.diagnostic, analysis::Fix { title, changes })
+        };
+        let mut fixes: Vec<_> = self
+            .lint_fixes(uri, tree, text, everything)
+            .into_iter()
+            .map(in_document)
+            .collect();
+        if let Ok(ws) = self.workspaces.read() {
+            if let Some(workspace) = ws.for_uri(uri) {
+                for fix in workspace.expose_fixes(uri, everything) {
+                    let (title, changes) = (fix.title, fix.changes);
+                    fixes.push((fix.diagnostic, analysis::Fix { title, changes }));
                 }
+                fixes.extend(
+                    workspace
+                        .docs_fixes(uri, everything)
+                        .into_iter()
+                        .chain(workspace.unused_import_fixes(uri, everything))
+                        .chain(workspace.module_path_fixes(uri, everything))
+                        .map(in_document),
+                );
             }
         }
-        None
+        for (_, fix) in &mut fixes {
+            self.match_line_endings(&mut fix.changes);
+        }
+        fixes
     }
 
-    /// Get the type (custom type or type alias) at a position
-    /// Returns (symbol_name, definition_range) if found
-    fn get_type_at_position(&self, uri: &Url, position: Position) -> Option<(String, Range)> {
-        if let Some(doc) = self.documents.get(uri) {
-            for symbol in &doc.symbols {
-                // ENUM = custom type, STRUCT = type alias
-                if symbol.kind == SymbolKind::ENUM || symbol.kind == SymbolKind::STRUCT {
-                    // Check if position is on the type name (definition_range)
-                    if let Some(def_range) = symbol.definition_range {
-                        if position.line == def_range.start.line
-                            && position.character >= def_range.start.character
-                            && position.character <= def_range.end.character
-                        {
-                            return Some((symbol.name.clone(), def_range));
-                        }
-                    }
+    /// Drop the pulled diagnostics of `uris`, or of every document, and ask
+    /// the client to pull again
+    async fn invalidate_pulled_diagnostics(&self, uris: Option<&[Url]>) {
+        if !self.pull_diagnostics.load(Ordering::Relaxed) {
+            return;
+        }
+        match uris {
+            Some(uris) => {
+                for uri in uris {
+                    self.pulled_diagnostics.remove(uri);
                 }
             }
+            None => self.pulled_diagnostics.clear(),
         }
-        None
-    }
-
-    /// Get the function at a position
-    /// Returns (symbol_name, definition_range) if found
-    fn get_function_at_position(&self, uri: &Url, position: Position) -> Option<(String, Range)> {
-        if let Some(doc) = self.documents.get(uri) {
-            for symbol in &doc.symbols {
-                if symbol.kind == SymbolKind::FUNCTION {
-                    // Check if position is on the function name (definition_range)
-                    if let Some(def_range) = symbol.definition_range {
-                        if position.line == def_range.start.line
-                            && position.character >= def_range.start.character
-                            && position.character <= def_range.end.character
-                        {
-                            return Some((symbol.name.clone(), def_range));
-                        }
-                    }
-
-                    // Also check if position is on the type annotation name
-                    if let Some(annot_range) = symbol.type_annotation_range {
-                        if position.line == annot_range.start.line
-                            && position.character >= annot_range.start.character
-                            && position.character <= annot_range.end.character
-                        {
-                            // Return the definition_range for the actual rename operation
-                            if let Some(def_range) = symbol.definition_range {
-                                return Some((symbol.name.clone(), def_range));
-                            }
-                        }
-                    }
-                }
+        if self.diagnostic_refresh_support.load(Ordering::Relaxed) {
+            if let Err(e) = self.client.workspace_diagnostic_refresh().await {
+                tracing::warn!(target: logging::DIAGNOSTICS, "Diagnostic refresh failed: {}", e);
             }
         }
-        None
     }
 
-    /// Classify what kind of symbol is at a position
-    /// Returns: "variant", "type", "function", "field", or "unknown"
-    fn get_symbol_kind_at_position(
+    /// Find references to the symbol at a position, dropping test usages
+    /// unless `include_tests` (or the setting) allows them
+    fn references_at(
         &self,
         uri: &Url,
         position: Position,
-        text: &str,
-    ) -> &'static str {
-        // Check for variant first (most specific)
-        if self.get_variant_at_position(uri, position).is_some() {
-            return "variant";
-        }
-
-        // Check for field
-        if let Ok(ws) = self.workspace.read() {
-            if let Some(workspace) = ws.as_ref() {
-                if workspace
-                    .get_field_at_position(uri, position, text)
-                    .is_some()
-                {
-                    return "field";
-                }
+        include_tests: Option<bool>,
+    ) -> Option<Vec<Location>> {
+        // A type variable only means something within its declaration
+        if let Ok(ws) = self.workspaces.read() {
+            if let Some(locations) = ws
+                .for_uri(uri)
+                .and_then(|workspace| workspace.type_variable_references(uri, position))
+            {
+                return Some(locations);
             }
         }
-
-        // Check for type
-        if self.get_type_at_position(uri, position).is_some() {
-            return "type";
-        }
-
-        // Check for function
-        if self.get_function_at_position(uri, position).is_some() {
-            return "function";
+        let mut locations = self.find_references_at(uri, position).ok().flatten()?;
+        if let Ok(ws) = self.workspaces.read() {
+            if let Some(workspace) = ws.for_uri(uri) {
+                workspace.filter_test_locations(&mut locations, include_tests);
+            }
         }
-
-        "unknown"
+        Some(locations)
     }
 
-    /// Rename a symbol by its name directly (without using position lookup)
-    fn rename_symbol_by_name(
-        &self,
-        uri: &Url,
-        name: &str,
-        new_name: &str,
-    ) -> Result<Option<WorkspaceEdit>> {
-        tracing::info!("Renaming {} to {}", name, new_name);
-
-        // Check for shadowing: does the new name already exist in the defining file?
-        // Read file content directly since document might not be opened
-        if let Ok(file_path) = uri.to_file_path() {
-            if let Ok(content) = std::fs::read_to_string(&file_path) {
-                // Look for the new name as a word boundary match (not substring)
-                let mut search_pos = 0;
-                while let Some(pos) = content[search_pos..].find(new_name) {
-                    let abs_pos = search_pos + pos;
-                    let before_ok = abs_pos == 0 || {
-                        let c = content.as_bytes()[abs_pos - 1] as char;
-                        !c.is_alphanumeric() && c != '_'
-                    };
-                    let after_ok = abs_pos + new_name.len() >= content.len() || {
-                        let c = content.as_bytes()[abs_pos + new_name.len()] as char;
-                        !c.is_alphanumeric() && c != '_'
-                    };
-                    if before_ok && after_ok {
-                        // Found a match - this would cause shadowing
-                        let line_num = content[..abs_pos].matches('\n').count() + 1;
-                        tracing::info!(
-                            "Shadowing detected: '{}' already exists in {} at line {}",
-                            new_name,
-                            file_path.display(),
-                            line_num
-                        );
-                        return Ok(None);
-                    }
-                    search_pos = abs_pos + 1;
-                }
+    fn find_references_at(&self, uri: &Url, position: Position) -> Result<Option<Vec<Location>>> {
+        // Get file content for type-aware field finding
+        let content = if let Some(doc) = self.documents.get(uri) {
+            doc.text.clone()
+        } else {
+            String::new()
+        };
+
+        // Try to classify the symbol at position for type-aware finding
+        let symbol = if let Ok(ws) = self.workspaces.read() {
+            if let Some(workspace) = ws.for_uri(uri) {
+                workspace.classify_definition_at_position(uri, position)
+            } else {
+                None
             }
-        }
+        } else {
+            None
+        };
 
-        let mut changes: std::collections::HashMap<Url, Vec<TextEdit>> =
-            std::collections::HashMap::new();
+        // Use type-aware reference finding if we can classify the symbol
+        if let Some(ref sym) = symbol {
+            tracing::info!(
+                target: logging::NAVIGATION,
+                "Finding references for {:?} {}",
+                sym.kind,
+                sym.name
+            );
 
-        // Get cross-file references from workspace
-        if let Ok(ws) = self.workspace.read() {
-            if let Some(workspace) = ws.as_ref() {
-                // Add definition location - prefer definition in the current file, skip Evergreen
-                let definition = workspace
-                    .get_symbols(name)
+            let refs = if let Ok(ws) = self.workspaces.read() {
+                if let Some(workspace) = ws.for_uri(uri) {
+                    match sym.kind {
+                        crate::binder::BoundSymbolKind::Function => {
+                            workspace.find_function_references_typed(sym)
+                        }
+                        crate::binder::BoundSymbolKind::Type
+                        | crate::binder::BoundSymbolKind::TypeAlias => {
+                            workspace.find_type_references_typed(sym)
+                        }
+                        crate::binder::BoundSymbolKind::UnionConstructor => {
+                            workspace.find_constructor_references_typed(sym)
+                        }
+                        crate::binder::BoundSymbolKind::FieldType => {
+                            workspace.find_field_references_typed(sym, &content)
+                        }
+                        crate::binder::BoundSymbolKind::Port => {
+                            workspace.find_port_references_typed(sym)
+                        }
+                        // Local variables: scope the search to their containing block
+                        crate::binder::BoundSymbolKind::FunctionParameter
+                        | crate::binder::BoundSymbolKind::CasePattern
+                        | crate::binder::BoundSymbolKind::AnonymousFunctionParameter => {
+                            workspace.find_local_references(sym, &content)
+                        }
+                        _ => workspace.find_references(&sym.name, sym.module_name.as_deref()),
+                    }
+                } else {
+                    vec![]
+                }
+            } else {
+                vec![]
+            };
+
+            if !refs.is_empty() {
+                let locations: Vec<Location> = refs
+                    .into_iter()
+                    .map(|r| Location {
+                        uri: r.uri,
+                        range: r.range,
+                    })
+                    .collect();
+                tracing::info!(target: logging::NAVIGATION, "Found {} references", locations.len());
+                return Ok(Some(locations));
+            }
+        }
+
+        // Fallback to text-based finding
+        let symbol_name = if let Some(doc) = self.documents.get(uri) {
+            doc.get_symbol_at_position(position).map(|s| s.name.clone())
+        } else {
+            None
+        };
+
+        let symbol_name = symbol_name.or_else(|| self.get_word_at_position(uri, position));
+
+        if let Some(name) = symbol_name {
+            tracing::info!(
+                target: logging::NAVIGATION,
+                "Finding references for (fallback): {}",
+                name
+            );
+
+            // Get cross-file references from workspace
+            if let Ok(ws) = self.workspaces.read() {
+                if let Some(workspace) = ws.for_uri(uri) {
+                    let refs = workspace.find_references(&name, None);
+                    if !refs.is_empty() {
+                        let locations: Vec<Location> = refs
+                            .into_iter()
+                            .map(|r| Location {
+                                uri: r.uri,
+                                range: r.range,
+                            })
+                            .collect();
+                        tracing::info!(
+                            target: logging::NAVIGATION,
+                            "Found {} references",
+                            locations.len()
+                        );
+                        return Ok(Some(locations));
+                    }
+                }
+            }
+
+            // Fallback to local references
+            if let Some(doc) = self.documents.get(uri) {
+                if let Some(symbol) = doc.symbols.iter().find(|s| s.name == name) {
+                    let mut locations: Vec<Location> = vec![Location {
+                        uri: uri.clone(),
+                        range: symbol.range,
+                    }];
+                    for range in &symbol.references {
+                        locations.push(Location {
+                            uri: uri.clone(),
+                            range: *range,
+                        });
+                    }
+                    return Ok(Some(locations));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Compute the rename edit for the symbol at a position, dropping edits
+    /// to test files unless `include_tests` (or the setting) allows them and
+    /// renaming in docs and strings if `rename_in_docs` (or the setting) asks
+    fn rename_at(
+        &self,
+        uri: &Url,
+        position: Position,
+        new_name: String,
+        include_tests: Option<bool>,
+        rename_in_docs: Option<bool>,
+    ) -> Result<Option<WorkspaceEdit>> {
+        if self.is_generated(uri) {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                "Cannot rename in generated code - change what generates it instead",
+            ));
+        }
+        // An import alias only changes in its own module
+        let alias_edits = match self.workspaces.read() {
+            Ok(ws) => match ws.for_uri(uri) {
+                Some(workspace) => workspace.rename_module_alias(uri, position, &new_name),
+                None => Ok(None),
+            },
+            Err(_) => Ok(None),
+        };
+        match alias_edits {
+            Ok(Some(edits)) => {
+                return Ok(Some(WorkspaceEdit {
+                    changes: Some([(uri.clone(), edits)].into_iter().collect()),
+                    ..Default::default()
+                }))
+            }
+            Ok(None) => {}
+            Err(e) => return Err(tower_lsp::jsonrpc::Error::invalid_params(e.to_string())),
+        }
+
+        // So does a type variable, within its declaration
+        let variable_edits = match self.workspaces.read() {
+            Ok(ws) => match ws.for_uri(uri) {
+                Some(workspace) => workspace.rename_type_variable(uri, position, &new_name),
+                None => Ok(None),
+            },
+            Err(_) => Ok(None),
+        };
+        match variable_edits {
+            Ok(Some(edits)) => {
+                return Ok(Some(WorkspaceEdit {
+                    changes: Some([(uri.clone(), edits)].into_iter().collect()),
+                    ..Default::default()
+                }))
+            }
+            Ok(None) => {}
+            Err(e) => return Err(tower_lsp::jsonrpc::Error::invalid_params(e.to_string())),
+        }
+
+        // The name as written at the cursor, without any module qualifier
+        let old_name = self
+            .get_word_at_position(uri, position)
+            .and_then(|word| word.rsplit('.').next().map(str::to_string));
+        let mut edit = self.rename_edit(uri, position, new_name.clone())?;
+        if let Some(changes) = edit.as_mut().and_then(|e| e.changes.as_mut()) {
+            if let Some(old_name) = old_name {
+                self.rename_in_docs(uri, changes, &old_name, &new_name, rename_in_docs);
+            }
+            self.filter_test_edits(uri, changes, include_tests);
+        }
+        Ok(edit)
+    }
+
+    /// `elm-lsp/previewRename`: summarize what a rename would change
+    /// (files, edit counts, sample lines) without applying anything
+    pub async fn preview_rename(&self, params: PreviewRenameParams) -> Result<Option<EditPreview>> {
+        let uri = &params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let edit = self.rename_at(
+            uri,
+            position,
+            params.new_name,
+            params.include_tests,
+            params.rename_in_docs,
+        )?;
+
+        let changes = match edit.and_then(|e| e.changes) {
+            Some(changes) => changes,
+            None => return Ok(None),
+        };
+        let preview = preview_edits(&changes, |uri| match self.documents.get(uri) {
+            Some(doc) => Some(doc.text.clone()),
+            None => read_source(&uri.to_file_path().ok()?).ok(),
+        });
+        tracing::info!(
+            target: logging::REFACTOR,
+            "Rename preview: {} edits in {} files",
+            preview.edit_count,
+            preview.file_count
+        );
+        Ok(Some(preview))
+    }
+
+    /// Counts of what each workspace has indexed, with its programs
+    pub async fn index_stats(&self) -> Result<Vec<IndexStats>> {
+        let ws = self
+            .workspaces
+            .read()
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        Ok(ws.iter().map(|workspace| workspace.index_stats()).collect())
+    }
+
+    /// `elm-lsp/workspaceSymbols`: workspace/symbol, telling whether
+    /// `workspaceSymbolLimit` left matches out
+    pub async fn workspace_symbols(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<WorkspaceSymbols> {
+        Ok(self.search_workspace_symbols(&params.query))
+    }
+
+    /// The symbols matching `query`, best first, up to the limit
+    fn search_workspace_symbols(&self, query: &str) -> WorkspaceSymbols {
+        let mut results = Vec::new();
+        let mut truncated = 0;
+
+        if let Ok(ws) = self.workspaces.read() {
+            let mut matches: Vec<_> = ws
+                .iter()
+                .flat_map(|workspace| workspace.search_symbols(query))
+                .collect();
+            // Best matches first, shorter names before longer ones
+            matches.sort_by(|(a_score, a), (b_score, b)| {
+                (a_score, a.name.len(), &a.name, &a.module_name).cmp(&(
+                    b_score,
+                    b.name.len(),
+                    &b.name,
+                    &b.module_name,
+                ))
+            });
+            let limit = ws.settings.workspace_symbol_limit;
+            if limit > 0 && matches.len() > limit {
+                truncated = matches.len() - limit;
+                matches.truncate(limit);
+            }
+            for (_, sym) in matches {
+                #[allow(deprecated)]
+                results.push(SymbolInformation {
+                    name: format!("{}.{}", sym.module_name, sym.name),
+                    kind: sym.kind,
+                    tags: None,
+                    deprecated: None,
+                    location: Location {
+                        uri: sym.definition_uri.clone(),
+                        range: sym.definition_range,
+                    },
+                    container_name: Some(sym.module_name.clone()),
+                });
+            }
+        }
+
+        if truncated > 0 {
+            tracing::info!(
+                target: logging::NAVIGATION,
+                "workspace/symbol: {:?} kept {} results, dropped {}",
+                query,
+                results.len(),
+                truncated
+            );
+        }
+        WorkspaceSymbols {
+            symbols: results,
+            is_incomplete: truncated > 0,
+        }
+    }
+
+    /// `elm-lsp/evergreenDrift`: for each Lamdera project, how `Types.elm`
+    /// differs from the newest Evergreen snapshot
+    pub async fn evergreen_drift(&self) -> Result<Vec<EvergreenDrift>> {
+        let ws = self
+            .workspaces
+            .read()
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        Ok(ws
+            .iter()
+            .filter_map(|workspace| workspace.evergreen_drift())
+            .collect())
+    }
+
+    /// `elm-lsp/callPaths`: every path from the entry points to the function
+    /// at the position, bounded by depth and count
+    pub async fn call_paths(&self, params: CallPathsParams) -> Result<Option<CallPaths>> {
+        let uri = &params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let max_depth = params.max_depth.unwrap_or(DEFAULT_MAX_DEPTH);
+        let max_paths = params.max_paths.unwrap_or(DEFAULT_MAX_PATHS);
+        let ws = self
+            .workspaces
+            .read()
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let paths = ws
+            .for_uri(uri)
+            .and_then(|workspace| workspace.call_paths(uri, position, max_depth, max_paths));
+        if let Some(paths) = &paths {
+            tracing::info!(
+                target: logging::NAVIGATION,
+                "Call paths to {}.{}: {}{}",
+                paths.function.module_name,
+                paths.function.function,
+                paths.paths.len(),
+                if paths.truncated { " (truncated)" } else { "" }
+            );
+        }
+        Ok(paths)
+    }
+
+    /// `elm-lsp/constructorUsages`: the usages of the custom type at the
+    /// position, grouped by constructor and by construction or pattern, with
+    /// the signatures mentioning the type
+    pub async fn constructor_usages(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<ConstructorUsages>> {
+        let uri = &params.text_document.uri;
+        let ws = self
+            .workspaces
+            .read()
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let usages = ws
+            .for_uri(uri)
+            .and_then(|workspace| workspace.constructor_usages(uri, params.position));
+        if let Some(usages) = &usages {
+            tracing::info!(
+                target: logging::NAVIGATION,
+                "Constructor usages of {}.{}: {} constructors, {} signatures",
+                usages.module_name,
+                usages.type_name,
+                usages.constructors.len(),
+                usages.signatures.len()
+            );
+        }
+        Ok(usages)
+    }
+
+    /// `elm-lsp/declarationImporters`: the modules importing the declaration
+    /// at the position, grouped by whether their import exposes it, aliases
+    /// its module or neither
+    pub async fn declaration_importers(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<DeclarationImporters>> {
+        let uri = &params.text_document.uri;
+        let ws = self
+            .workspaces
+            .read()
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let importers = ws
+            .for_uri(uri)
+            .and_then(|workspace| workspace.declaration_importers(uri, params.position));
+        if let Some(importers) = &importers {
+            tracing::info!(
+                target: logging::NAVIGATION,
+                "Importers of {}.{}: {} exposed, {} aliased, {} qualified",
+                importers.module_name,
+                importers.name,
+                importers.exposed.len(),
+                importers.aliased.len(),
+                importers.qualified.len()
+            );
+        }
+        Ok(importers)
+    }
+
+    /// Also rename `old_name` in doc comments and string literals of the
+    /// affected files, if requested or enabled in the settings
+    fn rename_in_docs(
+        &self,
+        uri: &Url,
+        changes: &mut std::collections::HashMap<Url, Vec<TextEdit>>,
+        old_name: &str,
+        new_name: &str,
+        rename_in_docs: Option<bool>,
+    ) {
+        if let Ok(ws) = self.workspaces.read() {
+            if let Some(workspace) = ws.for_uri(uri) {
+                workspace.rename_in_docs(changes, old_name, new_name, rename_in_docs);
+            }
+        }
+    }
+
+    /// Drop edits to generated files, and to test files unless tests are
+    /// included
+    fn filter_test_edits(
+        &self,
+        uri: &Url,
+        changes: &mut std::collections::HashMap<Url, Vec<TextEdit>>,
+        include_tests: Option<bool>,
+    ) {
+        if let Ok(ws) = self.workspaces.read() {
+            if let Some(workspace) = ws.for_uri(uri) {
+                workspace.filter_test_edits(changes, include_tests);
+            }
+        }
+    }
+
+    fn rename_edit(
+        &self,
+        uri: &Url,
+        position: Position,
+        new_name: String,
+    ) -> Result<Option<WorkspaceEdit>> {
+        // First check if this is a field rename
+        if let Some(doc) = self.documents.get(uri) {
+            if let Ok(ws) = self.workspaces.read() {
+                if let Some(workspace) = ws.for_uri(uri) {
+                    let field_result = workspace.get_field_at_position(uri, position, &doc.text);
+                    if let Some(field_info) = field_result {
+                        tracing::info!(
+                            target: logging::REFACTOR,
+                            "Renaming field {} in type alias {:?} to {}",
+                            field_info.name,
+                            field_info.definition.type_alias_name,
+                            new_name
+                        );
+
+                        let changes = workspace
+                            .rename_field_edits(&field_info, &new_name)
+                            .map_err(|e| {
+                                tower_lsp::jsonrpc::Error::invalid_params(e.to_string())
+                            })?;
+
+                        if !changes.is_empty() {
+                            tracing::info!(
+                                target: logging::REFACTOR,
+                                "Field rename affects {} files, {} edits",
+                                changes.len(),
+                                changes.values().map(|v| v.len()).sum::<usize>()
+                            );
+                            return Ok(Some(WorkspaceEdit {
+                                changes: Some(changes),
+                                ..Default::default()
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+        // Check if this is a variant rename
+        if let Some((_type_name, variant_info, _, _, _)) =
+            self.get_variant_at_position(uri, position)
+        {
+            // Validate that variant name starts with uppercase
+            if !new_name
+                .chars()
+                .next()
+                .map(|c| c.is_uppercase())
+                .unwrap_or(false)
+            {
+                return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                    "Variant names must start with an uppercase letter",
+                ));
+            }
+            tracing::info!(
+                target: logging::REFACTOR,
+                "Renaming variant {} to {}",
+                variant_info.name,
+                new_name
+            );
+            return self.rename_variant_by_range(
+                uri,
+                &variant_info.name,
+                variant_info.range,
+                &new_name,
+            );
+        }
+
+        // Fall back to symbol rename
+        let symbol_name = if let Some(doc) = self.documents.get(uri) {
+            doc.get_symbol_at_position(position).map(|s| s.name.clone())
+        } else {
+            None
+        };
+
+        let symbol_name = symbol_name.or_else(|| self.get_word_at_position(uri, position));
+
+        if let Some(name) = symbol_name {
+            // Check if this is a protected Lamdera type
+            if let Ok(ws) = self.workspaces.read() {
+                if let Some(workspace) = ws.for_uri(uri) {
+                    if workspace.is_protected_lamdera_type(&name) {
+                        tracing::info!(
+                            target: logging::REFACTOR,
+                            "Blocked rename of protected Lamdera type: {}",
+                            name
+                        );
+                        return Ok(None);
+                    }
+                }
+            }
+
+            return self.rename_symbol_by_name(uri, &name, &new_name);
+        }
+
+        Ok(None)
+    }
+
+    /// Re-check the modules that import the saved file, since a change to its
+    /// exports can break them. Bounded so saving a core module stays cheap.
+    async fn refresh_dependent_diagnostics(&self, uri: &Url) {
+        const MAX_DEPTH: usize = 3;
+        const MAX_FILES: usize = 20;
+
+        let dependents = match self.workspaces.read() {
+            Ok(ws) => ws
+                .for_uri(uri)
+                .map(|workspace| workspace.dependent_files(uri, MAX_DEPTH))
+                .unwrap_or_default(),
+            Err(_) => return,
+        };
+        for dependent in &dependents {
+            self.compiled.remove(dependent);
+        }
+        // A pulling client computes them again when it asks
+        if self.pull_diagnostics.load(Ordering::Relaxed) {
+            self.invalidate_pulled_diagnostics(Some(&dependents)).await;
+            return;
+        }
+
+        if dependents.len() > MAX_FILES {
+            tracing::info!(
+                target: logging::DIAGNOSTICS,
+                "Re-checking {} of {} dependents of {}",
+                MAX_FILES,
+                dependents.len(),
+                uri
+            );
+        }
+
+        for dependent in dependents.into_iter().take(MAX_FILES) {
+            let diagnostics = self.document_diagnostics(&dependent);
+            self.client
+                .publish_diagnostics(dependent, diagnostics, None)
+                .await;
+        }
+    }
+
+    /// Rewrite line breaks in edit text to match each target file's line endings
+    fn match_line_endings(&self, changes: &mut std::collections::HashMap<Url, Vec<TextEdit>>) {
+        for (uri, edits) in changes.iter_mut() {
+            let line_ending = match self.documents.get(uri) {
+                Some(doc) => doc.line_ending,
+                None => uri
+                    .to_file_path()
+                    .map(|path| LineEnding::of_file(&path))
+                    .unwrap_or_default(),
+            };
+            for edit in edits.iter_mut() {
+                edit.new_text = line_ending.apply(&edit.new_text).into_owned();
+            }
+        }
+    }
+
+    /// Serialize edits for a custom command response
+    fn changes_to_json(
+        &self,
+        mut changes: std::collections::HashMap<Url, Vec<TextEdit>>,
+    ) -> serde_json::Value {
+        self.match_line_endings(&mut changes);
+        serde_json::to_value(changes).unwrap_or_default()
+    }
+
+    /// The version of every open document, taken before a refactoring reads
+    /// them
+    fn document_versions(&self) -> std::collections::HashMap<Url, i32> {
+        self.documents
+            .iter()
+            .map(|doc| (doc.uri.clone(), doc.version))
+            .collect()
+    }
+
+    /// Finish a refactoring command's `response` with its edits.
+    ///
+    /// The refactoring fails instead when a document it edits changed (or
+    /// was opened) after `versions` were taken, since its edits were made for
+    /// the text before. Clients that support workspace/applyEdit get the edits
+    /// as one labeled edit, so they can undo it in one step, with the versions
+    /// the edits were made for so that they refuse it when a document changed
+    /// since; the response then says `"applied": true`. Other clients, like
+    /// the MCP wrapper, get the edits in `"changes"` to apply themselves. A
+    /// file the refactoring renames goes in the edit too, after the edits to
+    /// it, and clients that cannot rename files apply it all themselves.
+    async fn respond_with_edits(
+        &self,
+        label: &str,
+        mut changes: std::collections::HashMap<Url, Vec<TextEdit>>,
+        renamed: Option<(Url, Url)>,
+        versions: &std::collections::HashMap<Url, i32>,
+        mut response: serde_json::Value,
+    ) -> serde_json::Value {
+        let mut changed: Vec<String> = changes
+            .keys()
+            .filter(|uri| {
+                self.documents.get(*uri).map(|doc| doc.version) != versions.get(*uri).copied()
+            })
+            .map(|uri| {
+                uri.to_file_path()
+                    .map_or(uri.to_string(), |path| path.to_string_lossy().to_string())
+            })
+            .collect();
+        if !changed.is_empty() {
+            changed.sort();
+            tracing::info!(
+                target: logging::REFACTOR,
+                "{}: edited documents changed meanwhile: {:?}",
+                label,
+                changed
+            );
+            return serde_json::json!({
+                "success": false,
+                "error": format!(
+                    "{} changed while the refactoring was computed; run it again",
+                    changed.join(", ")
+                ),
+                "conflicts": changed
+            });
+        }
+
+        let by_client = self.apply_edit_support.load(Ordering::Relaxed)
+            && (renamed.is_none()
+                || self.document_changes_support.load(Ordering::Relaxed)
+                    && self.rename_file_support.load(Ordering::Relaxed));
+        if !by_client {
+            response["changes"] = self.changes_to_json(changes);
+            return response;
+        }
+
+        self.match_line_endings(&mut changes);
+        let edit = if self.document_changes_support.load(Ordering::Relaxed) {
+            let mut operations: Vec<DocumentChangeOperation> = changes
+                .into_iter()
+                .map(|(uri, edits)| {
+                    let version = versions.get(&uri).copied();
+                    DocumentChangeOperation::Edit(TextDocumentEdit {
+                        text_document: OptionalVersionedTextDocumentIdentifier { uri, version },
+                        edits: edits.into_iter().map(OneOf::Left).collect(),
+                    })
+                })
+                .collect();
+            if let Some((old_uri, new_uri)) = renamed {
+                operations.push(DocumentChangeOperation::Op(ResourceOp::Rename(
+                    RenameFile {
+                        old_uri,
+                        new_uri,
+                        options: None,
+                        annotation_id: None,
+                    },
+                )));
+            }
+            WorkspaceEdit {
+                document_changes: Some(DocumentChanges::Operations(operations)),
+                ..Default::default()
+            }
+        } else {
+            WorkspaceEdit {
+                changes: Some(changes),
+                ..Default::default()
+            }
+        };
+
+        let params = ApplyWorkspaceEditParams {
+            label: Some(label.to_string()),
+            edit,
+        };
+        match self
+            .client
+            .send_request::<request::ApplyWorkspaceEdit>(params)
+            .await
+        {
+            Ok(result) if result.applied => response["applied"] = true.into(),
+            Ok(result) => {
+                response["success"] = false.into();
+                response["error"] = result
+                    .failure_reason
+                    .unwrap_or_else(|| "The editor did not apply the edit".to_string())
+                    .into();
+            }
+            Err(e) => {
+                response["success"] = false.into();
+                response["error"] = e.to_string().into();
+            }
+        }
+        response
+    }
+
+    /// Warnings from the parse tree, with performance hints when enabled.
+    /// Generated code gets none: nobody cleans it up by hand.
+    fn lint_diagnostics(&self, uri: &Url, tree: &tree_sitter::Tree, text: &str) -> Vec<Diagnostic> {
+        if self.is_generated(uri) {
+            return Vec::new();
+        }
+        let mut diagnostics = analysis::lint_diagnostics(tree, text);
+        if self.performance_hints() {
+            diagnostics.extend(analysis::performance_diagnostics(tree, text));
+        }
+        analysis::locate_related(&mut diagnostics, uri);
+        analysis::drop_suppressed(text, &mut diagnostics);
+        analysis::codes::apply_severities(&mut diagnostics, &self.severities());
+        diagnostics
+    }
+
+    /// Fixes for the warnings of [`Self::lint_diagnostics`] in `range`
+    fn lint_fixes(
+        &self,
+        uri: &Url,
+        tree: &tree_sitter::Tree,
+        text: &str,
+        range: Range,
+    ) -> Vec<analysis::QuickFix> {
+        if self.is_generated(uri) {
+            return Vec::new();
+        }
+        let mut fixes = analysis::lint_fixes(tree, text, range);
+        if self.performance_hints() {
+            fixes.extend(analysis::performance_fixes(tree, text, range));
+        }
+        analysis::locate_related(fixes.iter_mut().map(|fix| &mut fix.diagnostic), uri);
+        let severities = self.severities();
+        fixes.retain_mut(|fix| {
+            !analysis::is_suppressed(text, &fix.diagnostic)
+                && analysis::codes::configure_severity(&mut fix.diagnostic, &severities)
+        });
+        fixes
+    }
+
+    /// The recursive functions of an open document
+    fn recursions(&self, uri: &Url) -> Vec<analysis::Recursion> {
+        let Some(doc) = self.documents.get(uri) else {
+            return Vec::new();
+        };
+        match self.parser.parse(&doc.text) {
+            Some(tree) => analysis::recursive_functions(&tree, &doc.text),
+            None => Vec::new(),
+        }
+    }
+
+    fn is_generated(&self, uri: &Url) -> bool {
+        self.workspaces
+            .read()
+            .ok()
+            .and_then(|ws| ws.for_uri(uri).map(|workspace| workspace.is_generated(uri)))
+            .unwrap_or(false)
+    }
+
+    /// The `diagnosticSeverity` setting
+    fn severities(&self) -> std::collections::BTreeMap<String, Severity> {
+        self.workspaces
+            .read()
+            .map(|ws| ws.settings.diagnostic_severity.clone())
+            .unwrap_or_default()
+    }
+
+    fn performance_hints(&self) -> bool {
+        self.workspaces
+            .read()
+            .map(|ws| ws.settings.performance_hints)
+            .unwrap_or(false)
+    }
+
+    /// Diagnostics found from the workspace index, without the compiler
+    fn workspace_diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        let mut diagnostics = match self.workspaces.read() {
+            Ok(ws) => ws
+                .for_uri(uri)
+                .map(|workspace| {
+                    let mut diagnostics = workspace.not_exposed_diagnostics(uri);
+                    diagnostics.extend(workspace.program_diagnostics(uri));
+                    diagnostics.extend(workspace.docs_diagnostics(uri));
+                    diagnostics.extend(workspace.package_diagnostics(uri));
+                    diagnostics.extend(workspace.unused_import_diagnostics(uri));
+                    diagnostics.extend(workspace.module_path_diagnostics(uri));
+                    diagnostics
+                })
+                .unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+        if let Some(doc) = self.documents.get(uri) {
+            analysis::drop_suppressed(&doc.text, &mut diagnostics);
+        }
+        analysis::codes::apply_severities(&mut diagnostics, &self.severities());
+        diagnostics
+    }
+
+    /// Apply the settings that are not per workspace: the log filter, the
+    /// compiler process limit and restricted mode
+    fn apply_server_settings(&self, settings: &Settings) {
+        apply_log_level(settings);
+        if let Ok(mut provider) = self.diagnostics_provider.write() {
+            provider.set_max_processes(settings.max_processes);
+            provider.set_restricted(settings.restricted);
+        }
+    }
+
+    /// True in restricted mode, where no external tool runs
+    fn is_restricted(&self) -> bool {
+        self.workspaces
+            .read()
+            .map(|ws| ws.settings.restricted)
+            .unwrap_or(true)
+    }
+
+    /// Run the compiler on a file, keeping its diagnostics until the next
+    /// save
+    fn compile(&self, uri: &Url) -> Vec<Diagnostic> {
+        let lamdera = self
+            .workspaces
+            .read()
+            .ok()
+            .and_then(|ws| {
+                ws.for_uri(uri)
+                    .map(|workspace| workspace.is_lamdera_project)
+            })
+            .unwrap_or(false);
+        let diagnostics = match self.diagnostics_provider.read() {
+            Ok(provider) => provider.get_diagnostics(uri, lamdera),
+            Err(_) => Vec::new(),
+        };
+        self.compiled.insert(uri.clone(), diagnostics.clone());
+        diagnostics
+    }
+
+    /// The compiler's diagnostics for a file as of its last save, compiling
+    /// it if it was not yet
+    fn compiler_diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        if let Some(diagnostics) = self.compiled.get(uri) {
+            return diagnostics.clone();
+        }
+        self.compile(uri)
+    }
+
+    /// Get the word at a position in the document
+    fn get_word_at_position(&self, uri: &Url, position: Position) -> Option<String> {
+        // Try from open document first
+        if let Some(doc) = self.documents.get(uri) {
+            if let Some(line) = doc.get_line(position.line) {
+                return self.extract_word_from_line(line, position.character as usize);
+            }
+        }
+
+        // Fallback: read from disk if document not open
+        if let Ok(path) = uri.to_file_path() {
+            if let Ok(content) = read_source(&path) {
+                if let Some(line) = content.lines().nth(position.line as usize) {
+                    return self.extract_word_from_line(line, position.character as usize);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn extract_word_from_line(&self, line: &str, col: usize) -> Option<String> {
+        self.extract_word_from_line_impl(line, col, true)
+    }
+
+    /// Extract just the immediate word (no dots) from a line
+    fn extract_simple_word_from_line(&self, line: &str, col: usize) -> Option<String> {
+        self.extract_word_from_line_impl(line, col, false)
+    }
+
+    fn extract_word_from_line_impl(
+        &self,
+        line: &str,
+        col: usize,
+        include_dots: bool,
+    ) -> Option<String> {
+        if col >= line.len() {
+            return None;
+        }
+
+        // Find word boundaries
+        let chars: Vec<char> = line.chars().collect();
+        let mut start = col;
+        let mut end = col;
+
+        let is_word_char =
+            |c: char| -> bool { c.is_alphanumeric() || c == '_' || (include_dots && c == '.') };
+
+        while start > 0 && is_word_char(chars[start - 1]) {
+            start -= 1;
+        }
+
+        while end < chars.len() && is_word_char(chars[end]) {
+            end += 1;
+        }
+
+        if start < end {
+            Some(chars[start..end].iter().collect())
+        } else {
+            None
+        }
+    }
+
+    fn get_variant_at_position(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> Option<(String, VariantInfo, usize, usize, Vec<String>)> {
+        if let Some(doc) = self.documents.get(uri) {
+            for symbol in &doc.symbols {
+                if symbol.kind == SymbolKind::ENUM {
+                    for (idx, variant) in symbol.variants.iter().enumerate() {
+                        if variant.range.start.line == position.line
+                            && position.character >= variant.range.start.character
+                            && position.character <= variant.range.end.character
+                        {
+                            let all_variants: Vec<String> =
+                                symbol.variants.iter().map(|v| v.name.clone()).collect();
+                            return Some((
+                                symbol.name.clone(),
+                                variant.clone(),
+                                idx,
+                                symbol.variants.len(),
+                                all_variants,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Get the type (custom type or type alias) at a position
+    /// Returns (symbol_name, definition_range) if found
+    fn get_type_at_position(&self, uri: &Url, position: Position) -> Option<(String, Range)> {
+        if let Some(doc) = self.documents.get(uri) {
+            for symbol in &doc.symbols {
+                // ENUM = custom type, STRUCT = type alias
+                if symbol.kind == SymbolKind::ENUM || symbol.kind == SymbolKind::STRUCT {
+                    // Check if position is on the type name (definition_range)
+                    if let Some(def_range) = symbol.definition_range {
+                        if position.line == def_range.start.line
+                            && position.character >= def_range.start.character
+                            && position.character <= def_range.end.character
+                        {
+                            return Some((symbol.name.clone(), def_range));
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Get the function at a position
+    /// Returns (symbol_name, definition_range) if found
+    fn get_function_at_position(&self, uri: &Url, position: Position) -> Option<(String, Range)> {
+        if let Some(doc) = self.documents.get(uri) {
+            for symbol in &doc.symbols {
+                if symbol.kind == SymbolKind::FUNCTION {
+                    // Check if position is on the function name (definition_range)
+                    if let Some(def_range) = symbol.definition_range {
+                        if position.line == def_range.start.line
+                            && position.character >= def_range.start.character
+                            && position.character <= def_range.end.character
+                        {
+                            return Some((symbol.name.clone(), def_range));
+                        }
+                    }
+
+                    // Also check if position is on the type annotation name
+                    if let Some(annot_range) = symbol.type_annotation_range {
+                        if position.line == annot_range.start.line
+                            && position.character >= annot_range.start.character
+                            && position.character <= annot_range.end.character
+                        {
+                            // Return the definition_range for the actual rename operation
+                            if let Some(def_range) = symbol.definition_range {
+                                return Some((symbol.name.clone(), def_range));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Classify what kind of symbol is at a position
+    /// Returns: "variant", "type", "function", "field", or "unknown"
+    fn get_symbol_kind_at_position(
+        &self,
+        uri: &Url,
+        position: Position,
+        text: &str,
+    ) -> &'static str {
+        // Check for variant first (most specific)
+        if self.get_variant_at_position(uri, position).is_some() {
+            return "variant";
+        }
+
+        // Check for field
+        if let Ok(ws) = self.workspaces.read() {
+            if let Some(workspace) = ws.for_uri(uri) {
+                if workspace
+                    .get_field_at_position(uri, position, text)
+                    .is_some()
+                {
+                    return "field";
+                }
+            }
+        }
+
+        // Check for type
+        if self.get_type_at_position(uri, position).is_some() {
+            return "type";
+        }
+
+        // Check for function
+        if self.get_function_at_position(uri, position).is_some() {
+            return "function";
+        }
+
+        "unknown"
+    }
+
+    /// Reorder the variants of the custom type, or the fields of the record
+    /// alias, whose name is at `position`. `order` None sorts alphabetically.
+    fn reorder_at(
+        &self,
+        uri: &Url,
+        position: Position,
+        order: Option<&[String]>,
+        update_call_sites: bool,
+    ) -> anyhow::Result<RefactorResult> {
+        let (name, _) = self
+            .get_type_at_position(uri, position)
+            .ok_or_else(|| anyhow::anyhow!("Position is not on a type name"))?;
+        let is_custom_type = self.is_custom_type(uri, &name);
+
+        let ws = self
+            .workspaces
+            .read()
+            .map_err(|_| anyhow::anyhow!("Could not acquire workspace lock"))?;
+        let workspace = ws
+            .for_uri(uri)
+            .ok_or_else(|| anyhow::anyhow!("Workspace not initialized"))?;
+        if is_custom_type {
+            workspace.reorder_variants(uri, &name, order)
+        } else {
+            workspace.reorder_fields(uri, &name, order, update_call_sites)
+        }
+    }
+
+    /// Turn the tuple type at `position` into the record alias `alias_name`
+    fn tuple_to_record_at(
+        &self,
+        uri: &Url,
+        position: Position,
+        alias_name: &str,
+        field_names: &[String],
+    ) -> anyhow::Result<RefactorResult> {
+        let ws = self
+            .workspaces
+            .read()
+            .map_err(|_| anyhow::anyhow!("Could not acquire workspace lock"))?;
+        let workspace = ws
+            .for_uri(uri)
+            .ok_or_else(|| anyhow::anyhow!("Workspace not initialized"))?;
+        workspace.tuple_to_record(uri, position, alias_name, field_names)
+    }
+
+    /// Run the extraction `command` (introduce parameter, hoist constant or
+    /// extract view helper) on the expression selected by `range`, naming
+    /// the result `name`
+    fn extract_at(
+        &self,
+        command: &str,
+        uri: &Url,
+        range: Range,
+        name: &str,
+    ) -> anyhow::Result<RefactorResult> {
+        let ws = self
+            .workspaces
+            .read()
+            .map_err(|_| anyhow::anyhow!("Could not acquire workspace lock"))?;
+        let workspace = ws
+            .for_uri(uri)
+            .ok_or_else(|| anyhow::anyhow!("Workspace not initialized"))?;
+        match command {
+            CMD_HOIST_CONSTANT => workspace.hoist_constant(uri, range, name),
+            CMD_EXTRACT_VIEW_HELPER => workspace.extract_view_helper(uri, range, name, false),
+            CMD_EXTRACT_VIEW_HELPER_WITH_MSG => {
+                workspace.extract_view_helper(uri, range, name, true)
+            }
+            _ => workspace.introduce_parameter(uri, range, name),
+        }
+    }
+
+    /// Reorder the declarations of the module at `uri` by `order`, or by the
+    /// `declarationOrder` setting
+    fn organize_at(
+        &self,
+        uri: &Url,
+        order: Option<DeclarationOrder>,
+    ) -> anyhow::Result<RefactorResult> {
+        let ws = self
+            .workspaces
+            .read()
+            .map_err(|_| anyhow::anyhow!("Could not acquire workspace lock"))?;
+        let workspace = ws
+            .for_uri(uri)
+            .ok_or_else(|| anyhow::anyhow!("Workspace not initialized"))?;
+        let order = order.unwrap_or(workspace.settings.declaration_order);
+        workspace.organize_declarations(uri, &order)
+    }
+
+    /// All semantic tokens of an open document, remembered for the next
+    /// delta request
+    fn full_semantic_tokens(&self, uri: &Url) -> Option<SemanticTokens> {
+        let doc = self.documents.get(uri)?;
+        let tree = self.parser.parse(&doc.text)?;
+        let id = self.semantic_tokens_id.fetch_add(1, Ordering::Relaxed);
+        let tokens = SemanticTokens {
+            result_id: Some(id.to_string()),
+            data: semantic_tokens::tokens(&tree, &doc.text, None),
+        };
+        self.semantic_tokens.insert(uri.clone(), tokens.clone());
+        Some(tokens)
+    }
+
+    /// Whether `name` is declared as a custom type (not an alias) in the open document
+    fn is_custom_type(&self, uri: &Url, name: &str) -> bool {
+        self.documents
+            .get(uri)
+            .and_then(|doc| doc.symbols.iter().find(|s| s.name == name).map(|s| s.kind))
+            == Some(SymbolKind::ENUM)
+    }
+
+    /// Refuse a rename whose new name clashes with a declaration, import or
+    /// local binding in one of the files it touches
+    fn check_rename_conflicts(
+        &self,
+        uri: &Url,
+        changes: &std::collections::HashMap<Url, Vec<TextEdit>>,
+        old_name: &str,
+        new_name: &str,
+    ) -> Result<()> {
+        if old_name == new_name {
+            return Ok(());
+        }
+        let conflicts = match self.workspaces.read() {
+            Ok(ws) => ws
+                .for_uri(uri)
+                .map(|workspace| workspace.rename_conflicts(changes, new_name))
+                .unwrap_or_default(),
+            Err(_) => return Ok(()),
+        };
+        match conflicts.first() {
+            Some(conflict) => {
+                tracing::info!(
+                    target: logging::REFACTOR,
+                    "Rename of {} to {} blocked by {} conflicts",
+                    old_name,
+                    new_name,
+                    conflicts.len()
+                );
+                Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                    "Cannot rename {} to {}: {} ({}:{})",
+                    old_name,
+                    new_name,
+                    conflict.message,
+                    conflict.uri.path(),
+                    conflict.range.start.line + 1
+                )))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Rename a symbol by its name directly (without using position lookup)
+    fn rename_symbol_by_name(
+        &self,
+        uri: &Url,
+        name: &str,
+        new_name: &str,
+    ) -> Result<Option<WorkspaceEdit>> {
+        tracing::info!(target: logging::REFACTOR, "Renaming {} to {}", name, new_name);
+
+        let mut changes: std::collections::HashMap<Url, Vec<TextEdit>> =
+            std::collections::HashMap::new();
+
+        // Get cross-file references from workspace
+        if let Ok(ws) = self.workspaces.read() {
+            if let Some(workspace) = ws.for_uri(uri) {
+                // Add definition location - prefer definition in the current file, skip Evergreen
+                let definition = workspace
+                    .get_symbols(name)
                     .into_iter()
                     .find(|s| {
                         &s.definition_uri == uri && !s.definition_uri.path().contains("/Evergreen/")
@@ -443,7 +1826,8 @@ impl ElmLanguageServer {
         }
 
         if !changes.is_empty() {
-            tracing::info!("Rename affects {} files", changes.len());
+            self.check_rename_conflicts(uri, &changes, name, new_name)?;
+            tracing::info!(target: logging::REFACTOR, "Rename affects {} files", changes.len());
             return Ok(Some(WorkspaceEdit {
                 changes: Some(changes),
                 ..Default::default()
@@ -463,6 +1847,7 @@ impl ElmLanguageServer {
         new_name: &str,
     ) -> Result<Option<WorkspaceEdit>> {
         tracing::info!(
+            target: logging::REFACTOR,
             "Renaming variant {} to {} (range: {:?})",
             variant_name,
             new_name,
@@ -490,9 +1875,9 @@ impl ElmLanguageServer {
         });
 
         // Get all references from workspace
-        if let Ok(ws) = self.workspace.read() {
-            if let Some(workspace) = ws.as_ref() {
-                let refs = workspace.find_references(variant_name, None);
+        if let Ok(ws) = self.workspaces.read() {
+            if let Some(workspace) = ws.for_uri(uri) {
+                let refs = workspace.find_constructor_references_at(uri, variant_name);
                 for r in refs {
                     // Skip Evergreen files
                     if r.uri.path().contains("/Evergreen/") {
@@ -519,7 +1904,12 @@ impl ElmLanguageServer {
         }
 
         if !changes.is_empty() {
-            tracing::info!("Variant rename affects {} files", changes.len());
+            self.check_rename_conflicts(uri, &changes, variant_name, new_name)?;
+            tracing::info!(
+                target: logging::REFACTOR,
+                "Variant rename affects {} files",
+                changes.len()
+            );
             return Ok(Some(WorkspaceEdit {
                 changes: Some(changes),
                 ..Default::default()
@@ -529,6 +1919,189 @@ impl ElmLanguageServer {
         Ok(None)
     }
 
+    /// A definition result for `target_selection` (the name) within `target`
+    /// (the whole declaration). Clients that accept links also get the
+    /// identifier at `position` as the origin, so exactly it is underlined.
+    fn definition_response(
+        &self,
+        uri: &Url,
+        position: Position,
+        target_uri: Url,
+        target: Range,
+        target_selection: Range,
+    ) -> GotoDefinitionResponse {
+        let links = self.definition_link_support.load(Ordering::Relaxed);
+        self.link_response(links, uri, position, target_uri, target, target_selection)
+    }
+
+    /// [`Self::definition_response`] for a client that accepts `links` or not
+    fn link_response(
+        &self,
+        links: bool,
+        uri: &Url,
+        position: Position,
+        target_uri: Url,
+        target: Range,
+        target_selection: Range,
+    ) -> GotoDefinitionResponse {
+        if !links {
+            return GotoDefinitionResponse::Scalar(Location {
+                uri: target_uri,
+                range: target_selection,
+            });
+        }
+        GotoDefinitionResponse::Link(vec![LocationLink {
+            origin_selection_range: self.identifier_range_at(uri, position),
+            target_uri,
+            target_range: target,
+            target_selection_range: target_selection,
+        }])
+    }
+
+    /// [`Self::definition_response`] for `symbol`, or for the original
+    /// declaration when `chain` holds the re-exports leading to it. Links
+    /// also list each re-exporting declaration, after the original.
+    fn reexport_definition_response(
+        &self,
+        uri: &Url,
+        position: Position,
+        symbol: GlobalSymbol,
+        chain: Vec<GlobalSymbol>,
+    ) -> GotoDefinitionResponse {
+        let Some((original, hops)) = chain.split_last() else {
+            return self.definition_response(
+                uri,
+                position,
+                symbol.definition_uri,
+                symbol.range,
+                symbol.definition_range,
+            );
+        };
+        let mut response = self.definition_response(
+            uri,
+            position,
+            original.definition_uri.clone(),
+            original.range,
+            original.definition_range,
+        );
+        if let GotoDefinitionResponse::Link(links) = &mut response {
+            let origin = links[0].origin_selection_range;
+            links.extend(
+                std::iter::once(&symbol)
+                    .chain(hops)
+                    .map(|hop| LocationLink {
+                        origin_selection_range: origin,
+                        target_uri: hop.definition_uri.clone(),
+                        target_range: hop.range,
+                        target_selection_range: hop.definition_range,
+                    }),
+            );
+        }
+        response
+    }
+
+    /// Range of the identifier (without module qualifier) at `position`
+    fn identifier_range_at(&self, uri: &Url, position: Position) -> Option<Range> {
+        self.documents.get(uri)?.identifier_range_at(position)
+    }
+
+    /// The type alias field declaring the field at `position`, when it is the
+    /// field of a `record.field` access or the name in a `{ field = .. }`
+    /// record literal or update. Returns the file, the `name : Type` range and
+    /// the name's range.
+    fn field_definition_at(&self, uri: &Url, position: Position) -> Option<(Url, Range, Range)> {
+        let content = self.documents.get(uri).map(|doc| doc.text.clone())?;
+        let ws = self.workspaces.read().ok()?;
+        let workspace = ws.for_uri(uri)?;
+        let tree = workspace.type_checker.get_tree(uri.as_str())?;
+        let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+        let node = Self::find_node_at_point(tree.root_node(), point)?;
+        if node.kind() != "lower_case_identifier" {
+            return None;
+        }
+        let parent = node.parent()?;
+        let is_field = match parent.kind() {
+            // Only the part after the dot, not the record
+            "field_access_expr" => {
+                !Self::node_contains(parent.child_by_field_name("target")?, node)
+            }
+            // Only the name, not an identifier in the value
+            "field" => parent.child(0).map(|c| c.id()) == Some(node.id()),
+            _ => false,
+        };
+        if !is_field {
+            return None;
+        }
+
+        let field_def =
+            workspace
+                .type_checker
+                .find_field_definition(uri.as_str(), node, &content)?;
+        let def_uri = Url::parse(&field_def.uri).ok()?;
+        let def_tree = workspace.type_checker.get_tree(&field_def.uri)?;
+        let field_node = Self::find_node_by_id(def_tree.root_node(), field_def.node_id)?;
+        let range = node_range(field_node);
+        // The whole `name : Type` is the target
+        let target = field_node
+            .parent()
+            .filter(|p| p.kind() == "field_type")
+            .map(node_range)
+            .unwrap_or(range);
+        tracing::info!(
+            target: logging::NAVIGATION,
+            "Found field definition in {} at {:?}",
+            field_def.uri,
+            range
+        );
+        Some((def_uri, target, range))
+    }
+
+    /// Format with the built-in formatter when elm-format is not installed.
+    /// Its output is not elm-format's, which the user is told once.
+    async fn fallback_format(&self, uri: &Url, path: &std::path::Path) -> Option<Vec<TextEdit>> {
+        let content = match self.documents.get(uri) {
+            Some(doc) => doc.text.clone(),
+            None => read_source(path).ok()?,
+        };
+        let formatted = crate::format::format(&content)?;
+        tracing::info!(
+            target: logging::FORMAT,
+            "elm-format not found, used the built-in formatter"
+        );
+        if !self.fallback_format_notified.swap(true, Ordering::Relaxed) {
+            self.client
+                .show_message(
+                    MessageType::WARNING,
+                    "elm-format is not installed: formatted with the built-in fallback, \
+                     which is not elm-format's canonical style",
+                )
+                .await;
+        }
+        Some(Self::whole_document_edit(&content, formatted))
+    }
+
+    /// One edit replacing `current` with `formatted`, or none if they are equal
+    fn whole_document_edit(current: &str, formatted: String) -> Vec<TextEdit> {
+        if formatted == current {
+            return vec![];
+        }
+        let line_count = current.lines().count() as u32;
+        let last_line_len = current.lines().last().map(|l| l.len()).unwrap_or(0) as u32;
+        vec![TextEdit {
+            range: Range {
+                start: Position {
+                    line: 0,
+                    character: 0,
+                },
+                end: Position {
+                    line: line_count,
+                    character: last_line_len,
+                },
+            },
+            new_text: formatted,
+        }]
+    }
+
     /// Find a node at a specific point in the tree
     fn find_node_at_point(
         node: tree_sitter::Node,
@@ -593,52 +2166,158 @@ impl ElmLanguageServer {
     }
 }
 
+/// Use the `logLevel` setting as the log filter
+fn apply_log_level(settings: &Settings) {
+    if let Err(e) = logging::set_filter(&settings.log_level) {
+        tracing::warn!(
+            target: logging::INDEX,
+            "Ignoring invalid logLevel {:?}: {}",
+            settings.log_level,
+            e
+        );
+    }
+}
+
+/// A full report of a document's pulled diagnostics
+fn full_diagnostic_report(
+    result_id: Option<String>,
+    items: Vec<Diagnostic>,
+) -> DocumentDiagnosticReportResult {
+    DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(
+        RelatedFullDocumentDiagnosticReport {
+            related_documents: None,
+            full_document_diagnostic_report: FullDocumentDiagnosticReport { result_id, items },
+        },
+    ))
+}
+
 #[tower_lsp::async_trait]
 impl LanguageServer for ElmLanguageServer {
     async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
-        tracing::info!("initialize: received request");
-
-        // Initialize workspace if we have a root
-        if let Some(root_uri) = params.root_uri {
-            if let Ok(path) = root_uri.to_file_path() {
-                tracing::info!("Initializing workspace at {:?}", path);
-
-                // Set diagnostics provider workspace root
-                if let Ok(mut diag) = self.diagnostics_provider.write() {
-                    diag.set_workspace_root(&path.to_string_lossy());
-                }
+        tracing::info!(target: logging::INDEX, "initialize: received request");
+
+        let can_watch_files = params
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|w| w.did_change_watched_files)
+            .and_then(|w| w.dynamic_registration)
+            .unwrap_or(false);
+        self.can_watch_files
+            .store(can_watch_files, Ordering::Relaxed);
+        let definition_link_support = params
+            .capabilities
+            .text_document
+            .as_ref()
+            .and_then(|t| t.definition.as_ref())
+            .and_then(|d| d.link_support)
+            .unwrap_or(false);
+        self.definition_link_support
+            .store(definition_link_support, Ordering::Relaxed);
+        let type_definition_link_support = params
+            .capabilities
+            .text_document
+            .as_ref()
+            .and_then(|t| t.type_definition.as_ref())
+            .and_then(|d| d.link_support)
+            .unwrap_or(false);
+        self.type_definition_link_support
+            .store(type_definition_link_support, Ordering::Relaxed);
+        let workspace_capabilities = params.capabilities.workspace.as_ref();
+        self.apply_edit_support.store(
+            workspace_capabilities
+                .and_then(|w| w.apply_edit)
+                .unwrap_or(false),
+            Ordering::Relaxed,
+        );
+        let workspace_edit = workspace_capabilities.and_then(|w| w.workspace_edit.as_ref());
+        self.document_changes_support.store(
+            workspace_edit
+                .and_then(|e| e.document_changes)
+                .unwrap_or(false),
+            Ordering::Relaxed,
+        );
+        self.rename_file_support.store(
+            workspace_edit
+                .and_then(|e| e.resource_operations.as_ref())
+                .is_some_and(|kinds| kinds.contains(&ResourceOperationKind::Rename)),
+            Ordering::Relaxed,
+        );
+        let pull_diagnostics = params
+            .capabilities
+            .text_document
+            .as_ref()
+            .is_some_and(|t| t.diagnostic.is_some());
+        self.pull_diagnostics
+            .store(pull_diagnostics, Ordering::Relaxed);
+        self.diagnostic_refresh_support.store(
+            workspace_capabilities
+                .and_then(|w| w.diagnostic.as_ref())
+                .and_then(|d| d.refresh_support)
+                .unwrap_or(false),
+            Ordering::Relaxed,
+        );
 
-                let mut workspace = Workspace::new(path);
-                if let Err(e) = workspace.initialize() {
-                    tracing::error!("Failed to initialize workspace: {}", e);
-                } else {
-                    let module_count = workspace.modules.len();
-                    let symbol_count: usize = workspace.symbols.values().map(|v| v.len()).sum();
-                    tracing::info!(
-                        "Workspace initialized: {} modules, {} symbols",
-                        module_count,
-                        symbol_count
-                    );
+        if let Some(options) = &params.initialization_options {
+            let settings = Settings::from_json(options);
+            self.started_restricted
+                .store(settings.restricted, Ordering::Relaxed);
+            self.apply_server_settings(&settings);
+            if let Ok(mut ws) = self.workspaces.write() {
+                ws.settings = settings;
+            }
+        }
 
-                    if let Ok(mut ws) = self.workspace.write() {
-                        *ws = Some(workspace);
-                    }
-                }
+        // One workspace per folder, falling back to the single root
+        let roots: Vec<Url> = match params.workspace_folders {
+            Some(folders) if !folders.is_empty() => folders.into_iter().map(|f| f.uri).collect(),
+            _ => params.root_uri.into_iter().collect(),
+        };
+        for root_uri in roots {
+            if let Ok(path) = root_uri.to_file_path() {
+                self.open_folder(path);
             }
         }
 
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::FULL),
+                        save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                        ..Default::default()
+                    },
                 )),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                        supported: Some(true),
+                        change_notifications: Some(OneOf::Left(true)),
+                    }),
+                    file_operations: None,
+                }),
                 definition_provider: Some(OneOf::Left(true)),
+                type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(true)),
                 references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
                 document_symbol_provider: Some(OneOf::Left(true)),
                 workspace_symbol_provider: Some(OneOf::Left(true)),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec![" ".to_string()]),
+                    retrigger_characters: None,
+                    work_done_progress_options: Default::default(),
+                }),
                 completion_provider: Some(CompletionOptions {
-                    trigger_characters: Some(vec![".".to_string()]),
+                    trigger_characters: Some(vec![
+                        ".".to_string(),
+                        "(".to_string(),
+                        ",".to_string(),
+                    ]),
                     ..Default::default()
                 }),
                 rename_provider: Some(OneOf::Right(RenameOptions {
@@ -646,7 +2325,30 @@ impl LanguageServer for ElmLanguageServer {
                     work_done_progress_options: Default::default(),
                 })),
                 code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        SemanticTokensOptions {
+                            legend: semantic_tokens::legend(),
+                            range: Some(true),
+                            full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+                            ..Default::default()
+                        },
+                    ),
+                ),
+                diagnostic_provider: pull_diagnostics.then(|| {
+                    DiagnosticServerCapabilities::Options(DiagnosticOptions {
+                        identifier: Some("elm".to_string()),
+                        // Saving a module can change the diagnostics of its importers
+                        inter_file_dependencies: true,
+                        workspace_diagnostics: false,
+                        work_done_progress_options: Default::default(),
+                    })
+                }),
                 document_formatting_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: "\n".to_string(),
+                    more_trigger_character: None,
+                }),
                 execute_command_provider: Some(ExecuteCommandOptions {
                     commands: vec![
                         CMD_MOVE_FUNCTION.to_string(),
@@ -660,6 +2362,16 @@ impl LanguageServer for ElmLanguageServer {
                         CMD_REMOVE_FIELD.to_string(),
                         CMD_PREPARE_ADD_VARIANT.to_string(),
                         CMD_ADD_VARIANT.to_string(),
+                        CMD_GET_IMPORTERS.to_string(),
+                        CMD_FIND_REFERENCES.to_string(),
+                        CMD_REORDER.to_string(),
+                        CMD_TUPLE_TO_RECORD.to_string(),
+                        CMD_INTRODUCE_PARAMETER.to_string(),
+                        CMD_HOIST_CONSTANT.to_string(),
+                        CMD_EXTRACT_VIEW_HELPER.to_string(),
+                        CMD_EXTRACT_VIEW_HELPER_WITH_MSG.to_string(),
+                        CMD_ADD_DEPENDENCY.to_string(),
+                        CMD_ORGANIZE_DECLARATIONS.to_string(),
                     ],
                     ..Default::default()
                 }),
@@ -673,15 +2385,26 @@ impl LanguageServer for ElmLanguageServer {
     }
 
     async fn initialized(&self, _: InitializedParams) {
-        tracing::info!("initialized: received notification");
+        tracing::info!(target: logging::INDEX, "initialized: received notification");
+
+        // Warnings and errors show up in the editor's output panel
+        let mut warnings = logging::forward_warnings();
+        let client = self.client.clone();
+        tokio::spawn(async move {
+            while let Some((kind, message)) = warnings.recv().await {
+                client.log_message(kind, message).await;
+            }
+        });
 
         // Log workspace status - get message first, then await
         let message = {
-            if let Ok(ws) = self.workspace.read() {
-                if let Some(workspace) = ws.as_ref() {
+            if let Ok(ws) = self.workspaces.read() {
+                if !ws.is_empty() {
+                    let modules: usize = ws.iter().map(|w| w.modules.len()).sum();
                     format!(
-                        "Elm LSP (Rust) initialized: {} modules indexed",
-                        workspace.modules.len()
+                        "Elm LSP (Rust) initialized: {} modules indexed in {} project(s)",
+                        modules,
+                        ws.len()
                     )
                 } else {
                     "Elm LSP (Rust) initialized (no workspace)".to_string()
@@ -692,6 +2415,11 @@ impl LanguageServer for ElmLanguageServer {
         };
 
         self.client.log_message(MessageType::INFO, message).await;
+
+        if self.can_watch_files.load(Ordering::Relaxed) {
+            self.register_file_watchers().await;
+        }
+        self.publish_elm_json_diagnostics().await;
     }
 
     async fn shutdown(&self) -> Result<()> {
@@ -699,10 +2427,15 @@ impl LanguageServer for ElmLanguageServer {
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        tracing::info!("did_open: uri={}", params.text_document.uri);
+        tracing::info!(target: logging::INDEX, "did_open: uri={}", params.text_document.uri);
         let uri = params.text_document.uri;
         let text = params.text_document.text;
         let version = params.text_document.version;
+        // Without file watchers this is our chance to notice `elm install`
+        if !self.can_watch_files.load(Ordering::Relaxed) {
+            self.refresh_packages().await;
+        }
+        self.compiled.remove(&uri);
         self.on_change(uri, text, version).await;
     }
 
@@ -714,34 +2447,125 @@ impl LanguageServer for ElmLanguageServer {
         }
     }
 
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        tracing::info!(target: logging::INDEX, "did_save: uri={}", params.text_document.uri);
+        let uri = params.text_document.uri;
+        // What the compiler reads from disk now matches the buffer
+        self.compiled.remove(&uri);
+        if self.pull_diagnostics.load(Ordering::Relaxed) {
+            self.pulled_diagnostics.remove(&uri);
+        } else {
+            let diagnostics = self.document_diagnostics(&uri);
+            self.client
+                .publish_diagnostics(uri.clone(), diagnostics, None)
+                .await;
+        }
+        self.refresh_dependent_diagnostics(&uri).await;
+    }
+
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
         self.documents.remove(&params.text_document.uri);
+        self.semantic_tokens.remove(&params.text_document.uri);
+        self.pulled_diagnostics.remove(&params.text_document.uri);
+        self.compiled.remove(&params.text_document.uri);
+        if let Ok(mut ws) = self.workspaces.write() {
+            if let Some(workspace) = ws.for_uri_mut(&params.text_document.uri) {
+                workspace.close_buffer(&params.text_document.uri);
+            }
+        }
+    }
+
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        for folder in params.event.removed {
+            if let Ok(path) = folder.uri.to_file_path() {
+                self.close_folder(&path);
+            }
+        }
+        for folder in params.event.added {
+            if let Ok(path) = folder.uri.to_file_path() {
+                self.open_folder(path);
+            }
+        }
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        let mut settings = Settings::from_json(&params.settings);
+        settings.restricted |= self.started_restricted.load(Ordering::Relaxed);
+        tracing::info!(target: logging::INDEX, "did_change_configuration: {:?}", settings);
+        self.apply_server_settings(&settings);
+        let mut reported_differently = false;
+        if let Ok(mut ws) = self.workspaces.write() {
+            reported_differently = ws.settings.diagnostic_severity != settings.diagnostic_severity
+                || ws.settings.performance_hints != settings.performance_hints;
+            for workspace in ws.iter_mut() {
+                workspace.apply_settings(settings.clone());
+            }
+            ws.settings = settings;
+        }
+        self.invalidate_pulled_diagnostics(None).await;
+
+        // Show the new severities right away rather than at the next edit
+        if reported_differently && !self.pull_diagnostics.load(Ordering::Relaxed) {
+            let open: Vec<Url> = self.documents.iter().map(|doc| doc.key().clone()).collect();
+            for uri in open {
+                let diagnostics = self.document_diagnostics(&uri);
+                self.client
+                    .publish_diagnostics(uri, diagnostics, None)
+                    .await;
+            }
+            self.publish_elm_json_diagnostics().await;
+        }
     }
 
     async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
-        tracing::info!("did_change_watched_files: {} changes", params.changes.len());
+        tracing::info!(
+            target: logging::INDEX,
+            "did_change_watched_files: {} changes",
+            params.changes.len()
+        );
+        let package_dirs = match self.workspaces.read() {
+            Ok(ws) => ws
+                .iter()
+                .flat_map(|workspace| workspace.package_dirs())
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        let mut packages_changed = false;
+
         for change in params.changes {
             let uri = change.uri;
+            if let Ok(path) = uri.to_file_path() {
+                let in_packages = package_dirs.iter().any(|dir| path.starts_with(dir));
+                if in_packages || path.file_name().is_some_and(|name| name == "elm.json") {
+                    packages_changed = true;
+                    continue;
+                }
+            }
             match change.typ {
                 FileChangeType::CREATED | FileChangeType::CHANGED => {
-                    tracing::info!("File changed/created: {}", uri);
-                    // Re-read and reindex the file
+                    tracing::info!(target: logging::INDEX, "File changed/created: {}", uri);
+                    // Re-read and reindex the file, unless it is open in the
+                    // editor, whose buffer wins
                     if let Ok(path) = uri.to_file_path() {
-                        if let Ok(content) = std::fs::read_to_string(&path) {
-                            // Update the document in the workspace
-                            if let Ok(mut ws) = self.workspace.write() {
-                                if let Some(workspace) = ws.as_mut() {
-                                    workspace.update_file(&uri, &content);
+                        if let Ok(mut ws) = self.workspaces.write() {
+                            if let Some(workspace) = ws.for_uri_mut(&uri) {
+                                if let Err(e) = workspace.index_file(&path) {
+                                    tracing::warn!(
+                                        target: logging::INDEX,
+                                        "Failed to reindex {}: {}",
+                                        uri,
+                                        e
+                                    );
                                 }
                             }
                         }
                     }
                 }
                 FileChangeType::DELETED => {
-                    tracing::info!("File deleted: {}", uri);
+                    tracing::info!(target: logging::INDEX, "File deleted: {}", uri);
                     self.documents.remove(&uri);
-                    if let Ok(mut ws) = self.workspace.write() {
-                        if let Some(workspace) = ws.as_mut() {
+                    if let Ok(mut ws) = self.workspaces.write() {
+                        if let Some(workspace) = ws.for_uri_mut(&uri) {
                             workspace.remove_file(&uri);
                         }
                     }
@@ -749,23 +2573,169 @@ impl LanguageServer for ElmLanguageServer {
                 _ => {}
             }
         }
+
+        if packages_changed {
+            self.refresh_packages().await;
+        }
+        // Modules listed in exposed-modules may have appeared or gone
+        self.publish_elm_json_diagnostics().await;
     }
 
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
         let uri = &params.text_document_position_params.text_document.uri;
         let position = params.text_document_position_params.position;
 
-        // First try local document
+        // In a GLSL block only the shader's inputs are known, not Elm names
+        if let Ok(ws) = self.workspaces.read() {
+            if let Some(workspace) = ws.for_uri(uri) {
+                if let Some((input, range)) = workspace.glsl_input_at(uri, position) {
+                    return Ok(Some(Hover {
+                        contents: HoverContents::Markup(MarkupContent {
+                            kind: MarkupKind::Markdown,
+                            value: format!("```glsl\n{}\n```", input.declaration()),
+                        }),
+                        range: Some(range),
+                    }));
+                }
+                if workspace.in_glsl_block(uri, position) {
+                    return Ok(None);
+                }
+            }
+        }
+
+        // Operators show their type, fixity and package
+        if let Ok(ws) = self.workspaces.read() {
+            if let Some(operator) = ws
+                .for_uri(uri)
+                .and_then(|workspace| workspace.operator_at(uri, position))
+            {
+                return Ok(Some(Hover {
+                    contents: HoverContents::Markup(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value: operator.hover_markdown(),
+                    }),
+                    range: None,
+                }));
+            }
+        }
+
+        // Int and Char literals show their value
+        if let Ok(ws) = self.workspaces.read() {
+            if let Some((value, range)) = ws
+                .for_uri(uri)
+                .and_then(|workspace| workspace.literal_hover(uri, position))
+            {
+                return Ok(Some(Hover {
+                    contents: HoverContents::Markup(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value,
+                    }),
+                    range: Some(range),
+                }));
+            }
+        }
+
+        // The base and `|` of a record update show the record's type alias
+        if let Ok(ws) = self.workspaces.read() {
+            if let Some((value, range)) = ws
+                .for_uri(uri)
+                .and_then(|workspace| workspace.record_update_hover(uri, position))
+            {
+                return Ok(Some(Hover {
+                    contents: HoverContents::Markup(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value,
+                    }),
+                    range: Some(range),
+                }));
+            }
+        }
+
+        // Module names, in imports or as qualifiers, show the module's docs
+        if let Ok(ws) = self.workspaces.read() {
+            if let Some((value, range)) = ws
+                .for_uri(uri)
+                .and_then(|workspace| workspace.module_hover(uri, position))
+            {
+                return Ok(Some(Hover {
+                    contents: HoverContents::Markup(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value,
+                    }),
+                    range: Some(range),
+                }));
+            }
+        }
+
+        // Then the local document
         if let Some(doc) = self.documents.get(uri) {
             if let Some(symbol) = doc.get_symbol_at_position(position) {
+                // Values without an annotation show their inferred type
+                let inferred = symbol
+                    .signature
+                    .is_none()
+                    .then(|| self.workspaces.read().ok())
+                    .flatten()
+                    .and_then(|ws| {
+                        ws.for_uri(uri)
+                            .and_then(|workspace| workspace.inferred_signature(uri, &symbol.name))
+                    });
+                let mut value = format!(
+                    "```elm\n{}\n```\n\n{}",
+                    symbol
+                        .signature
+                        .as_deref()
+                        .or(inferred.as_deref())
+                        .unwrap_or(&symbol.name),
+                    symbol.documentation.as_deref().unwrap_or("")
+                );
+                // `main` (or a Lamdera `app`) says what kind of program it is
+                let kind = self.workspaces.read().ok().and_then(|ws| {
+                    ws.for_uri(uri)
+                        .and_then(|workspace| workspace.program_kind(uri))
+                });
+                if let Some(kind) = kind.filter(|kind| kind.entry_point() == symbol.name) {
+                    value.push_str(&format!("\n\n*`{}` program*", kind.function()));
+                }
+                if let Some(tree) = self.parser.parse(&doc.text) {
+                    let recursion = analysis::recursive_functions(&tree, &doc.text)
+                        .into_iter()
+                        .find(|r| {
+                            r.name == symbol.name
+                                && symbol.range.start <= r.declaration.start
+                                && r.declaration.end <= symbol.range.end
+                        });
+                    if let Some(recursion) = recursion {
+                        let count = recursion.calls.len();
+                        value.push_str(&format!(
+                            "\n\n*Recursive: calls itself in {} place{}*",
+                            count,
+                            if count == 1 { "" } else { "s" }
+                        ));
+                    }
+                }
+                // Types of a Lamdera `Types.elm` show how they go over the wire
+                let wire = self.workspaces.read().ok().and_then(|ws| {
+                    ws.for_uri(uri)
+                        .and_then(|workspace| workspace.wire_encoding(uri, &doc.text, &symbol.name))
+                });
+                if let Some(wire) = wire {
+                    value.push_str("\n\n");
+                    value.push_str(&wire);
+                }
+                // Shaders list their attributes, uniforms and varyings
+                let shader = self.workspaces.read().ok().and_then(|ws| {
+                    ws.for_uri(uri)
+                        .and_then(|workspace| workspace.shader_inputs(uri, &symbol.name))
+                });
+                if let Some(shader) = shader {
+                    value.push_str("\n\n");
+                    value.push_str(&shader);
+                }
                 return Ok(Some(Hover {
                     contents: HoverContents::Markup(MarkupContent {
                         kind: MarkupKind::Markdown,
-                        value: format!(
-                            "```elm\n{}\n```\n\n{}",
-                            symbol.signature.as_deref().unwrap_or(&symbol.name),
-                            symbol.documentation.as_deref().unwrap_or("")
-                        ),
+                        value,
                     }),
                     range: Some(symbol.range),
                 }));
@@ -774,17 +2744,35 @@ impl LanguageServer for ElmLanguageServer {
 
         // Try workspace lookup
         if let Some(word) = self.get_word_at_position(uri, position) {
-            if let Ok(ws) = self.workspace.read() {
-                if let Some(workspace) = ws.as_ref() {
+            if let Ok(ws) = self.workspaces.read() {
+                if let Some(workspace) = ws.for_uri(uri) {
                     if let Some(symbol) = workspace.find_definition(&word) {
+                        let mut value = format!(
+                            "```elm\n{}\n```",
+                            workspace
+                                .hover_signature(symbol)
+                                .unwrap_or_else(|| symbol.name.clone())
+                        );
+                        if let Some(documentation) = workspace.symbol_documentation(symbol) {
+                            value.push_str("\n\n");
+                            value.push_str(&documentation);
+                        }
+                        value.push_str(&format!("\n\n*Defined in {}*", symbol.module_name));
+                        if let Some(shader) =
+                            workspace.shader_inputs(&symbol.definition_uri, &symbol.name)
+                        {
+                            value.push_str("\n\n");
+                            value.push_str(&shader);
+                        }
+                        // Which import brings the name into this file
+                        if let Some(provenance) = workspace.import_provenance(uri, &word) {
+                            value.push_str("\n\n");
+                            value.push_str(&provenance);
+                        }
                         return Ok(Some(Hover {
                             contents: HoverContents::Markup(MarkupContent {
                                 kind: MarkupKind::Markdown,
-                                value: format!(
-                                    "```elm\n{}\n```\n\n*Defined in {}*",
-                                    symbol.signature.as_deref().unwrap_or(&symbol.name),
-                                    symbol.module_name
-                                ),
+                                value,
                             }),
                             range: None,
                         }));
@@ -796,6 +2784,18 @@ impl LanguageServer for ElmLanguageServer {
         Ok(None)
     }
 
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        match self.workspaces.read() {
+            Ok(ws) => Ok(ws
+                .for_uri(uri)
+                .and_then(|workspace| workspace.signature_help(uri, position))),
+            Err(_) => Ok(None),
+        }
+    }
+
     async fn goto_definition(
         &self,
         params: GotoDefinitionParams,
@@ -803,101 +2803,62 @@ impl LanguageServer for ElmLanguageServer {
         let uri = &params.text_document_position_params.text_document.uri;
         let position = params.text_document_position_params.position;
 
-        // Get document content for type-aware lookups
-        let content = if let Some(doc) = self.documents.get(uri) {
-            doc.text.clone()
-        } else {
-            String::new()
-        };
-
-        // First, check if we're on a field access expression (record.field)
-        // This needs special handling to go to the field definition in the type alias
-        if let Ok(ws) = self.workspace.read() {
-            if let Some(workspace) = ws.as_ref() {
-                if let Some(tree) = workspace.type_checker.get_tree(uri.as_str()) {
-                    let point = tree_sitter::Point::new(
-                        position.line as usize,
-                        position.character as usize,
-                    );
-                    if let Some(node) = Self::find_node_at_point(tree.root_node(), point) {
-                        // Check if this is a field in a field_access_expr
-                        if let Some(parent) = node.parent() {
-                            if parent.kind() == "field_access_expr"
-                                && node.kind() == "lower_case_identifier"
-                            {
-                                // This is the field part of record.field
-                                // Check if we're clicking on the field (after the dot), not the record
-                                if let Some(target) = parent.child_by_field_name("target") {
-                                    // If node is NOT inside target, we're clicking on the field
-                                    if !Self::node_contains(target, node) {
-                                        tracing::info!("Field access detected, using type checker");
-                                        if let Some(field_def) = workspace
-                                            .type_checker
-                                            .find_field_definition(uri.as_str(), node, &content)
-                                        {
-                                            // Convert field definition URI to Url
-                                            if let Ok(def_uri) = Url::parse(&field_def.uri) {
-                                                // Find the actual range of the field in the type alias
-                                                if let Some(def_tree) =
-                                                    workspace.type_checker.get_tree(&field_def.uri)
-                                                {
-                                                    if workspace
-                                                        .type_checker
-                                                        .get_source(&field_def.uri)
-                                                        .is_some()
-                                                    {
-                                                        if let Some(field_node) =
-                                                            Self::find_node_by_id(
-                                                                def_tree.root_node(),
-                                                                field_def.node_id,
-                                                            )
-                                                        {
-                                                            let range = Range {
-                                                                start: Position::new(
-                                                                    field_node.start_position().row
-                                                                        as u32,
-                                                                    field_node
-                                                                        .start_position()
-                                                                        .column
-                                                                        as u32,
-                                                                ),
-                                                                end: Position::new(
-                                                                    field_node.end_position().row
-                                                                        as u32,
-                                                                    field_node.end_position().column
-                                                                        as u32,
-                                                                ),
-                                                            };
-                                                            tracing::info!("Found field definition in {} at {:?}", field_def.uri, range);
-                                                            return Ok(Some(
-                                                                GotoDefinitionResponse::Scalar(
-                                                                    Location {
-                                                                        uri: def_uri,
-                                                                        range,
-                                                                    },
-                                                                ),
-                                                            ));
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
+        // In a GLSL block, an input's name goes to its declaration
+        if let Ok(ws) = self.workspaces.read() {
+            if let Some(workspace) = ws.for_uri(uri) {
+                if let Some((input, range)) = workspace.glsl_input_at(uri, position) {
+                    if !self.definition_link_support.load(Ordering::Relaxed) {
+                        return Ok(Some(GotoDefinitionResponse::Scalar(Location::new(
+                            uri.clone(),
+                            input.range,
+                        ))));
                     }
+                    return Ok(Some(GotoDefinitionResponse::Link(vec![LocationLink {
+                        origin_selection_range: Some(range),
+                        target_uri: uri.clone(),
+                        target_range: input.range,
+                        target_selection_range: input.range,
+                    }])));
+                }
+                if workspace.in_glsl_block(uri, position) {
+                    return Ok(None);
                 }
             }
         }
 
+        // Fields (`record.field` or `{ field = .. }`) go to the field in the type alias
+        if let Some((def_uri, target, range)) = self.field_definition_at(uri, position) {
+            return Ok(Some(
+                self.definition_response(uri, position, def_uri, target, range),
+            ));
+        }
+
+        // On an import, the module name opens the module and an exposed item its declaration
+        let import_target = match self.workspaces.read() {
+            Ok(ws) => ws.for_uri(uri).and_then(|workspace| {
+                let target = workspace.import_target(uri, position)?;
+                let chain = workspace.reexport_chain(&target);
+                Some((target, chain))
+            }),
+            Err(_) => None,
+        };
+        if let Some((target, chain)) = import_target {
+            return Ok(Some(
+                self.reexport_definition_response(uri, position, target, chain),
+            ));
+        }
+
         // Try local document symbols (top-level declarations)
         if let Some(doc) = self.documents.get(uri) {
             if let Some(symbol) = doc.get_symbol_at_position(position) {
-                return Ok(Some(GotoDefinitionResponse::Scalar(Location {
-                    uri: uri.clone(),
-                    range: symbol.definition_range.unwrap_or(symbol.range),
-                })));
+                let name = symbol.definition_range.unwrap_or(symbol.range);
+                return Ok(Some(self.definition_response(
+                    uri,
+                    position,
+                    uri.clone(),
+                    symbol.range,
+                    name,
+                )));
             }
         }
 
@@ -908,15 +2869,26 @@ impl LanguageServer for ElmLanguageServer {
                 if let Some(word) =
                     self.extract_simple_word_from_line(line, position.character as usize)
                 {
-                    tracing::info!("Looking up definition for: {}", word);
-                    if let Ok(ws) = self.workspace.read() {
-                        if let Some(workspace) = ws.as_ref() {
+                    tracing::info!(
+                        target: logging::NAVIGATION,
+                        "Looking up definition for: {}",
+                        word
+                    );
+                    if let Ok(ws) = self.workspaces.read() {
+                        if let Some(workspace) = ws.for_uri(uri) {
                             if let Some(symbol) = workspace.find_definition(&word) {
-                                tracing::info!("Found definition in {}", symbol.module_name);
-                                return Ok(Some(GotoDefinitionResponse::Scalar(Location {
-                                    uri: symbol.definition_uri.clone(),
-                                    range: symbol.definition_range,
-                                })));
+                                tracing::info!(
+                                    target: logging::NAVIGATION,
+                                    "Found definition in {}",
+                                    symbol.module_name
+                                );
+                                let chain = workspace.reexport_chain(symbol);
+                                return Ok(Some(self.reexport_definition_response(
+                                    uri,
+                                    position,
+                                    symbol.clone(),
+                                    chain,
+                                )));
                             }
                         }
                     }
@@ -927,128 +2899,158 @@ impl LanguageServer for ElmLanguageServer {
         Ok(None)
     }
 
+    async fn goto_type_definition(
+        &self,
+        params: GotoTypeDefinitionParams,
+    ) -> Result<Option<GotoTypeDefinitionResponse>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let target = match self.workspaces.read() {
+            Ok(ws) => ws
+                .for_uri(uri)
+                .and_then(|workspace| workspace.type_definition_at(uri, position))
+                .cloned(),
+            Err(_) => None,
+        };
+        let links = self.type_definition_link_support.load(Ordering::Relaxed);
+        Ok(target.map(|symbol| {
+            self.link_response(
+                links,
+                uri,
+                position,
+                symbol.definition_uri,
+                symbol.range,
+                symbol.definition_range,
+            )
+        }))
+    }
+
     async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
         let uri = &params.text_document_position.text_document.uri;
         let position = params.text_document_position.position;
+        Ok(self.references_at(uri, position, None))
+    }
 
-        // Get file content for type-aware field finding
-        let content = if let Some(doc) = self.documents.get(uri) {
-            doc.text.clone()
-        } else {
-            String::new()
-        };
-
-        // Try to classify the symbol at position for type-aware finding
-        let symbol = if let Ok(ws) = self.workspace.read() {
-            if let Some(workspace) = ws.as_ref() {
-                workspace.classify_definition_at_position(uri, position)
-            } else {
-                None
-            }
-        } else {
-            None
-        };
-
-        // Use type-aware reference finding if we can classify the symbol
-        if let Some(ref sym) = symbol {
-            tracing::info!("Finding references for {:?} {}", sym.kind, sym.name);
-
-            let refs = if let Ok(ws) = self.workspace.read() {
-                if let Some(workspace) = ws.as_ref() {
-                    match sym.kind {
-                        crate::binder::BoundSymbolKind::Function => {
-                            workspace.find_function_references_typed(sym)
-                        }
-                        crate::binder::BoundSymbolKind::Type
-                        | crate::binder::BoundSymbolKind::TypeAlias => {
-                            workspace.find_type_references_typed(sym)
-                        }
-                        crate::binder::BoundSymbolKind::UnionConstructor => {
-                            workspace.find_constructor_references_typed(sym)
-                        }
-                        crate::binder::BoundSymbolKind::FieldType => {
-                            workspace.find_field_references_typed(sym, &content)
-                        }
-                        crate::binder::BoundSymbolKind::Port => {
-                            workspace.find_port_references_typed(sym)
-                        }
-                        // Local variables: scope the search to their containing block
-                        crate::binder::BoundSymbolKind::FunctionParameter
-                        | crate::binder::BoundSymbolKind::CasePattern
-                        | crate::binder::BoundSymbolKind::AnonymousFunctionParameter => {
-                            workspace.find_local_references(sym, &content)
-                        }
-                        _ => workspace.find_references(&sym.name, sym.module_name.as_deref()),
-                    }
-                } else {
-                    vec![]
-                }
-            } else {
-                vec![]
-            };
-
-            if !refs.is_empty() {
-                let locations: Vec<Location> = refs
-                    .into_iter()
-                    .map(|r| Location {
-                        uri: r.uri,
-                        range: r.range,
-                    })
-                    .collect();
-                tracing::info!("Found {} references", locations.len());
-                return Ok(Some(locations));
-            }
-        }
-
-        // Fallback to text-based finding
-        let symbol_name = if let Some(doc) = self.documents.get(uri) {
-            doc.get_symbol_at_position(position).map(|s| s.name.clone())
-        } else {
-            None
-        };
-
-        let symbol_name = symbol_name.or_else(|| self.get_word_at_position(uri, position));
-
-        if let Some(name) = symbol_name {
-            tracing::info!("Finding references for (fallback): {}", name);
-
-            // Get cross-file references from workspace
-            if let Ok(ws) = self.workspace.read() {
-                if let Some(workspace) = ws.as_ref() {
-                    let refs = workspace.find_references(&name, None);
-                    if !refs.is_empty() {
-                        let locations: Vec<Location> = refs
-                            .into_iter()
-                            .map(|r| Location {
-                                uri: r.uri,
-                                range: r.range,
-                            })
-                            .collect();
-                        tracing::info!("Found {} references", locations.len());
-                        return Ok(Some(locations));
-                    }
-                }
-            }
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let contains = |range: &Range| range.start <= position && position <= range.end;
 
-            // Fallback to local references
-            if let Some(doc) = self.documents.get(uri) {
-                if let Some(symbol) = doc.symbols.iter().find(|s| s.name == name) {
-                    let mut locations: Vec<Location> = vec![Location {
-                        uri: uri.clone(),
-                        range: symbol.range,
-                    }];
-                    for range in &symbol.references {
-                        locations.push(Location {
-                            uri: uri.clone(),
-                            range: *range,
-                        });
-                    }
-                    return Ok(Some(locations));
-                }
-            }
+        // In a recursive function, its name and the calls to itself
+        if let Some(recursion) = self
+            .recursions(uri)
+            .into_iter()
+            .find(|r| contains(&r.declaration) || r.calls.iter().any(contains))
+        {
+            let mut highlights = vec![DocumentHighlight {
+                range: recursion.declaration,
+                kind: Some(DocumentHighlightKind::WRITE),
+            }];
+            highlights.extend(recursion.calls.into_iter().map(|range| DocumentHighlight {
+                range,
+                kind: Some(DocumentHighlightKind::READ),
+            }));
+            return Ok(Some(highlights));
         }
 
-        Ok(None)
+        // Otherwise the references in this document
+        let highlights = self
+            .references_at(uri, position, Some(true))
+            .map(|locations| {
+                locations
+                    .into_iter()
+                    .filter(|location| location.uri == *uri)
+                    .map(|location| DocumentHighlight {
+                        range: location.range,
+                        kind: Some(DocumentHighlightKind::TEXT),
+                    })
+                    .collect()
+            });
+        Ok(highlights)
+    }
+
+    /// The function called or declared at the position, as the item whose
+    /// incoming and outgoing calls the client then asks for
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let ws = self
+            .workspaces
+            .read()
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        Ok(ws
+            .for_uri(uri)
+            .and_then(|workspace| workspace.prepare_call_hierarchy(uri, position))
+            .map(|item| vec![item]))
+    }
+
+    /// The functions calling the one of the item, across modules
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        let ws = self
+            .workspaces
+            .read()
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        Ok(ws
+            .for_uri(&params.item.uri)
+            .map(|workspace| workspace.incoming_calls(&params.item)))
+    }
+
+    /// The functions the one of the item calls, across modules
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        let ws = self
+            .workspaces
+            .read()
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        Ok(ws
+            .for_uri(&params.item.uri)
+            .map(|workspace| workspace.outgoing_calls(&params.item)))
+    }
+
+    /// A lens above every recursive function that lists its calls to itself
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let uri = &params.text_document.uri;
+        let lenses = self
+            .recursions(uri)
+            .into_iter()
+            .map(|recursion| {
+                let count = recursion.calls.len();
+                let locations: Vec<Location> = recursion
+                    .calls
+                    .iter()
+                    .map(|range| Location::new(uri.clone(), *range))
+                    .collect();
+                CodeLens {
+                    range: recursion.declaration,
+                    command: Some(Command {
+                        title: format!(
+                            "recursive: {} call{}",
+                            count,
+                            if count == 1 { "" } else { "s" }
+                        ),
+                        command: "editor.action.showReferences".to_string(),
+                        arguments: Some(vec![
+                            serde_json::json!(uri),
+                            serde_json::json!(recursion.declaration.start),
+                            serde_json::json!(locations),
+                        ]),
+                    }),
+                    data: None,
+                }
+            })
+            .collect();
+        Ok(Some(lenses))
     }
 
     async fn document_symbol(
@@ -1057,26 +3059,38 @@ impl LanguageServer for ElmLanguageServer {
     ) -> Result<Option<DocumentSymbolResponse>> {
         let uri = &params.text_document.uri;
 
+        if uri.path().ends_with("/elm.json") {
+            let content = match self.documents.get(uri) {
+                Some(doc) => doc.text.clone(),
+                None => match uri.to_file_path().map(std::fs::read_to_string) {
+                    Ok(Ok(content)) => content,
+                    _ => return Ok(None),
+                },
+            };
+            return Ok(Some(DocumentSymbolResponse::Nested(elm_json_symbols(
+                &content,
+            ))));
+        }
+
         if let Some(doc) = self.documents.get(uri) {
-            let symbols: Vec<SymbolInformation> = doc
+            let symbols: Vec<DocumentSymbol> = doc
                 .symbols
                 .iter()
                 .map(|s| {
                     #[allow(deprecated)]
-                    SymbolInformation {
+                    DocumentSymbol {
                         name: s.name.clone(),
+                        detail: s.annotation_detail(MAX_SYMBOL_DETAIL_WIDTH),
                         kind: s.kind,
                         tags: None,
                         deprecated: None,
-                        location: Location {
-                            uri: uri.clone(),
-                            range: s.range,
-                        },
-                        container_name: None,
+                        range: s.range,
+                        selection_range: s.definition_range.unwrap_or(s.range),
+                        children: None,
                     }
                 })
                 .collect();
-            return Ok(Some(DocumentSymbolResponse::Flat(symbols)));
+            return Ok(Some(DocumentSymbolResponse::Nested(symbols)));
         }
 
         Ok(None)
@@ -1086,36 +3100,14 @@ impl LanguageServer for ElmLanguageServer {
         &self,
         params: WorkspaceSymbolParams,
     ) -> Result<Option<Vec<SymbolInformation>>> {
-        let query = params.query.to_lowercase();
-        let mut results = Vec::new();
-
-        if let Ok(ws) = self.workspace.read() {
-            if let Some(workspace) = ws.as_ref() {
-                for (name, symbols) in &workspace.symbols {
-                    if name.to_lowercase().contains(&query) {
-                        for sym in symbols {
-                            #[allow(deprecated)]
-                            results.push(SymbolInformation {
-                                name: format!("{}.{}", sym.module_name, sym.name),
-                                kind: sym.kind,
-                                tags: None,
-                                deprecated: None,
-                                location: Location {
-                                    uri: sym.definition_uri.clone(),
-                                    range: sym.definition_range,
-                                },
-                                container_name: Some(sym.module_name.clone()),
-                            });
-                        }
-                    }
-                }
-            }
-        }
-
-        if results.is_empty() {
+        // workspace/symbol has no isIncomplete flag; clients query again as the
+        // user types, so a narrower query brings the dropped symbols back.
+        // Clients that want to know use elm-lsp/workspaceSymbols.
+        let found = self.search_workspace_symbols(&params.query);
+        if found.symbols.is_empty() {
             Ok(None)
         } else {
-            Ok(Some(results))
+            Ok(Some(found.symbols))
         }
     }
 
@@ -1123,19 +3115,52 @@ impl LanguageServer for ElmLanguageServer {
         use std::collections::HashSet;
 
         let uri = &params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
         let mut items = Vec::new();
         let mut seen_labels: HashSet<String> = HashSet::new();
 
+        // Only constructors and `_` can start a case branch pattern, only the
+        // record's fields can be set in a record update, an import can only
+        // expose what its module exposes, a module what it declares, only
+        // types and type variables go in a type, and after `Dict.` or
+        // `model.` only what the module exposes or the record's fields
+        if let Ok(ws) = self.workspaces.try_read() {
+            if let Some(context_items) = ws.for_uri(uri).and_then(|workspace| {
+                workspace
+                    .pattern_completions(uri, position)
+                    .or_else(|| workspace.record_update_completions(uri, position))
+                    .or_else(|| workspace.import_exposing_completions(uri, position))
+                    .or_else(|| workspace.module_exposing_completions(uri, position))
+                    .or_else(|| workspace.type_completions(uri, position))
+                    .or_else(|| workspace.qualified_value_completions(uri, position))
+                    .or_else(|| workspace.field_access_completions(uri, position))
+            }) {
+                return Ok(Some(CompletionResponse::Array(context_items)));
+            }
+        }
+
         // Limit to prevent timeout on large workspaces
         const MAX_COMPLETION_ITEMS: usize = 1000;
 
+        // Parameters, pattern variables and let bindings in scope come first
+        if let Ok(ws) = self.workspaces.try_read() {
+            if let Some(workspace) = ws.for_uri(uri) {
+                for item in workspace.local_completions(uri, position) {
+                    seen_labels.insert(item.label.clone());
+                    items.push(item);
+                }
+            }
+        }
+
         // Local symbols (prioritized)
         if let Some(doc) = self.documents.get(uri) {
             for s in doc.symbols.iter() {
                 if items.len() >= MAX_COMPLETION_ITEMS {
                     break;
                 }
-                seen_labels.insert(s.name.clone());
+                if !seen_labels.insert(s.name.clone()) {
+                    continue;
+                }
                 items.push(CompletionItem {
                     label: s.name.clone(),
                     kind: Some(match s.kind {
@@ -1153,16 +3178,28 @@ impl LanguageServer for ElmLanguageServer {
         }
 
         // Workspace symbols (non-blocking to avoid timeout while workspace is indexing)
-        if let Ok(ws) = self.workspace.try_read() {
-            if let Some(workspace) = ws.as_ref() {
+        if let Ok(ws) = self.workspaces.try_read() {
+            if let Some(workspace) = ws.for_uri(uri) {
                 'outer: for symbols in workspace.symbols.values() {
                     for sym in symbols {
                         if items.len() >= MAX_COMPLETION_ITEMS {
                             break 'outer;
                         }
+                        // A name clashing with one in scope goes in qualified
+                        let qualified =
+                            workspace.qualified_completion(uri, &sym.module_name, &sym.name);
+                        let key = qualified
+                            .as_ref()
+                            .map_or(&sym.name, |(insert_text, _)| insert_text);
                         // Use HashSet for O(1) duplicate check instead of O(n)
-                        if !seen_labels.contains(&sym.name) {
-                            seen_labels.insert(sym.name.clone());
+                        if !seen_labels.contains(key) {
+                            seen_labels.insert(key.clone());
+                            let (insert_text, additional_text_edits) = match qualified {
+                                Some((text, edits)) => {
+                                    (Some(text), (!edits.is_empty()).then_some(edits))
+                                }
+                                None => (None, None),
+                            };
                             items.push(CompletionItem {
                                 label: sym.name.clone(),
                                 kind: Some(match sym.kind {
@@ -1176,6 +3213,8 @@ impl LanguageServer for ElmLanguageServer {
                                     detail: Some(format!(" ({})", sym.module_name)),
                                     description: None,
                                 }),
+                                insert_text,
+                                additional_text_edits,
                                 ..Default::default()
                             });
                         }
@@ -1184,6 +3223,33 @@ impl LanguageServer for ElmLanguageServer {
             }
         }
 
+        // Rank by how well the word typed so far matches, abbreviations
+        // included, keeping local symbols first among equals
+        let typed: String = self
+            .documents
+            .get(uri)
+            .and_then(|doc| {
+                let line = doc.get_line(position.line)?;
+                let before: String = line.chars().take(position.character as usize).collect();
+                let start = before
+                    .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .map_or(0, |i| i + 1);
+                Some(before[start..].to_string())
+            })
+            .unwrap_or_default();
+        tracing::debug!(
+            target: logging::COMPLETION,
+            "completion: {} items for {:?}",
+            items.len(),
+            typed
+        );
+        if !typed.is_empty() {
+            for (i, item) in items.iter_mut().enumerate() {
+                let score = matcher::score(&item.label, &typed).unwrap_or(9);
+                item.sort_text = Some(format!("{}{:04}", score, i));
+            }
+        }
+
         if items.is_empty() {
             Ok(None)
         } else {
@@ -1197,11 +3263,31 @@ impl LanguageServer for ElmLanguageServer {
     ) -> Result<Option<PrepareRenameResponse>> {
         let uri = &params.text_document.uri;
         let position = params.position;
+        if self.is_generated(uri) {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                "Cannot rename in generated code - change what generates it instead",
+            ));
+        }
 
-        // First check if this is a field rename
+        // An import alias, in its `as` clause or as a qualifier, or a type
+        // variable
+        if let Ok(ws) = self.workspaces.read() {
+            if let Some((name, range)) = ws.for_uri(uri).and_then(|workspace| {
+                workspace
+                    .module_alias_at(uri, position)
+                    .or_else(|| workspace.type_variable_at(uri, position))
+            }) {
+                return Ok(Some(PrepareRenameResponse::RangeWithPlaceholder {
+                    range,
+                    placeholder: name,
+                }));
+            }
+        }
+
+        // Then check if this is a field rename
         if let Some(doc) = self.documents.get(uri) {
-            if let Ok(ws) = self.workspace.read() {
-                if let Some(workspace) = ws.as_ref() {
+            if let Ok(ws) = self.workspaces.read() {
+                if let Some(workspace) = ws.for_uri(uri) {
                     if let Some(field_info) =
                         workspace.get_field_at_position(uri, position, &doc.text)
                     {
@@ -1228,8 +3314,8 @@ impl LanguageServer for ElmLanguageServer {
         if let Some(doc) = self.documents.get(uri) {
             if let Some(symbol) = doc.get_symbol_at_position(position) {
                 // Check if this is a protected Lamdera type
-                if let Ok(ws) = self.workspace.read() {
-                    if let Some(workspace) = ws.as_ref() {
+                if let Ok(ws) = self.workspaces.read() {
+                    if let Some(workspace) = ws.for_uri(uri) {
                         if workspace.is_protected_lamdera_type(&symbol.name) {
                             return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
                                 "Cannot rename '{}' - this type is required by Lamdera",
@@ -1251,160 +3337,466 @@ impl LanguageServer for ElmLanguageServer {
     async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
         let uri = &params.text_document_position.text_document.uri;
         let position = params.text_document_position.position;
-        let new_name = params.new_name;
-
-        // First check if this is a field rename
-        if let Some(doc) = self.documents.get(uri) {
-            if let Ok(ws) = self.workspace.read() {
-                if let Some(workspace) = ws.as_ref() {
-                    let field_result = workspace.get_field_at_position(uri, position, &doc.text);
-                    if let Some(field_info) = field_result {
-                        tracing::info!(
-                            "Renaming field {} in type alias {:?} to {}",
-                            field_info.name,
-                            field_info.definition.type_alias_name,
-                            new_name
-                        );
-
-                        // Find all field references using type inference
-                        let refs = workspace
-                            .find_field_references(&field_info.name, &field_info.definition);
+        self.rename_at(uri, position, params.new_name, None, None)
+    }
 
-                        let mut changes: std::collections::HashMap<Url, Vec<TextEdit>> =
-                            std::collections::HashMap::new();
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = &params.text_document.uri;
+        let range = params.range;
+        let mut actions = Vec::new();
 
-                        for r in refs {
-                            // Note: For record pattern fields like `{ email }`, the bound variable
-                            // usages in the function scope are automatically included by
-                            // find_field_references via find_variable_usages_in_scope.
-                            changes.entry(r.uri).or_default().push(TextEdit {
-                                range: r.range,
-                                new_text: new_name.clone(),
-                            });
+        // Get word at start of range
+        if let Some(word) = self.get_word_at_position(uri, range.start) {
+            // Check if it's an undefined symbol that could be imported
+            if let Ok(ws) = self.workspaces.read() {
+                if let Some(workspace) = ws.for_uri(uri) {
+                    let symbols = workspace.get_symbols(&word);
+                    for sym in symbols {
+                        // Create "Add import" action
+                        let edits = workspace.import_edits(
+                            uri,
+                            &[ImportSpec::exposing(&sym.module_name, &[sym.name.as_str()])],
+                        );
+                        if edits.is_empty() {
+                            continue;
                         }
 
-                        if !changes.is_empty() {
-                            tracing::info!(
-                                "Field rename affects {} files, {} edits",
-                                changes.len(),
-                                changes.values().map(|v| v.len()).sum::<usize>()
-                            );
-                            return Ok(Some(WorkspaceEdit {
+                        let mut changes = std::collections::HashMap::new();
+                        changes.insert(uri.clone(), edits);
+                        self.match_line_endings(&mut changes);
+
+                        actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                            title: format!("Import {} from {}", sym.name, sym.module_name),
+                            kind: Some(CodeActionKind::QUICKFIX),
+                            edit: Some(WorkspaceEdit {
                                 changes: Some(changes),
                                 ..Default::default()
-                            }));
-                        }
+                            }),
+                            ..Default::default()
+                        }));
                     }
                 }
             }
         }
-        // Check if this is a variant rename
-        if let Some((_type_name, variant_info, _, _, _)) =
-            self.get_variant_at_position(uri, position)
-        {
-            // Validate that variant name starts with uppercase
-            if !new_name
-                .chars()
-                .next()
-                .map(|c| c.is_uppercase())
-                .unwrap_or(false)
-            {
-                return Err(tower_lsp::jsonrpc::Error::invalid_params(
-                    "Variant names must start with an uppercase letter",
-                ));
-            }
-            tracing::info!("Renaming variant {} to {}", variant_info.name, new_name);
-            return self.rename_variant_by_range(
-                uri,
-                &variant_info.name,
-                variant_info.range,
-                &new_name,
-            );
+
+        // Diagnostics that carry their fixes need nothing recomputed, as
+        // long as the document has not changed since
+        let (version, text) = match self.documents.get(uri) {
+            Some(doc) => (Some(doc.version), doc.text.clone()),
+            None => (None, String::new()),
+        };
+        // A new, empty module gets the header its path calls for
+        let header = match self.workspaces.read() {
+            Ok(ws) if version.is_some() => ws
+                .for_uri(uri)
+                .and_then(|workspace| workspace.module_header_template(uri, &text)),
+            _ => None,
+        };
+        if let Some(edit) = header {
+            let title = format!("Add module header `{}`", edit.new_text.trim_end());
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title,
+                kind: Some(CodeActionKind::QUICKFIX),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(std::collections::HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                is_preferred: Some(true),
+                ..Default::default()
+            }));
         }
 
-        // Fall back to symbol rename
-        let symbol_name = if let Some(doc) = self.documents.get(uri) {
-            doc.get_symbol_at_position(position).map(|s| s.name.clone())
-        } else {
-            None
+        let severities = self.severities();
+        let mut fixed: Vec<&Diagnostic> = Vec::new();
+        for diagnostic in &params.context.diagnostics {
+            let Some(data) =
+                analysis::FixData::of(diagnostic).filter(|data| Some(data.version) == version)
+            else {
+                continue;
+            };
+            for fix in data.fixes {
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: fix.title,
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(fix.changes),
+                        ..Default::default()
+                    }),
+                    is_preferred: Some(true),
+                    ..Default::default()
+                }));
+            }
+            fixed.push(diagnostic);
+        }
+        let is_fixed = |diagnostic: &Diagnostic| {
+            fixed.iter().any(|d| {
+                d.range == diagnostic.range
+                    && d.code == diagnostic.code
+                    && d.message == diagnostic.message
+            })
         };
 
-        let symbol_name = symbol_name.or_else(|| self.get_word_at_position(uri, position));
+        // An escape hatch for false positives: a directive hiding the
+        // diagnostic, for when fixing it is not the answer
+        for diagnostic in &params.context.diagnostics {
+            let Some(edit) = analysis::suppression_edit(&text, diagnostic) else {
+                continue;
+            };
+            let Some(NumberOrString::String(code)) = &diagnostic.code else {
+                continue;
+            };
+            let mut changes = std::collections::HashMap::new();
+            changes.insert(uri.clone(), vec![edit]);
+            self.match_line_endings(&mut changes);
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Suppress {} on this line", code),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(changes),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }));
+        }
 
-        if let Some(name) = symbol_name {
-            // Check if this is a protected Lamdera type
-            if let Ok(ws) = self.workspace.read() {
-                if let Some(workspace) = ws.as_ref() {
-                    if workspace.is_protected_lamdera_type(&name) {
-                        tracing::info!("Blocked rename of protected Lamdera type: {}", name);
-                        return Ok(None);
+        // Fixes for the warnings of lint_diagnostics
+        if let Some(doc) = self.documents.get(uri) {
+            if let Some(tree) = self.parser.parse(&doc.text) {
+                for fix in self.lint_fixes(uri, &tree, &doc.text, range) {
+                    if is_fixed(&fix.diagnostic) {
+                        continue;
                     }
+                    let mut changes = std::collections::HashMap::new();
+                    changes.insert(uri.clone(), fix.edits);
+                    self.match_line_endings(&mut changes);
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: fix.title,
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![fix.diagnostic]),
+                        edit: Some(WorkspaceEdit {
+                            changes: Some(changes),
+                            ..Default::default()
+                        }),
+                        is_preferred: Some(true),
+                        ..Default::default()
+                    }));
                 }
             }
-
-            return self.rename_symbol_by_name(uri, &name, &new_name);
         }
 
-        Ok(None)
-    }
-
-    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
-        let uri = &params.text_document.uri;
-        let range = params.range;
-        let mut actions = Vec::new();
-
-        // Get word at start of range
-        if let Some(word) = self.get_word_at_position(uri, range.start) {
-            // Check if it's an undefined symbol that could be imported
-            if let Ok(ws) = self.workspace.read() {
-                if let Some(workspace) = ws.as_ref() {
-                    let symbols = workspace.get_symbols(&word);
-                    for sym in symbols {
-                        // Create "Add import" action
-                        let import_line =
-                            format!("import {} exposing ({})\n", sym.module_name, sym.name);
-
-                        let edit = TextEdit {
-                            range: Range {
-                                start: Position {
-                                    line: 2,
-                                    character: 0,
-                                }, // After module declaration
-                                end: Position {
-                                    line: 2,
-                                    character: 0,
-                                },
-                            },
-                            new_text: import_line,
-                        };
-
-                        let mut changes = std::collections::HashMap::new();
-                        changes.insert(uri.clone(), vec![edit]);
+        // Expose what another module declares but this file cannot use yet
+        if let Ok(ws) = self.workspaces.read() {
+            if let Some(workspace) = ws.for_uri(uri) {
+                for mut fix in workspace.expose_fixes(uri, range) {
+                    if is_fixed(&fix.diagnostic)
+                        || analysis::is_suppressed(&text, &fix.diagnostic)
+                        || !analysis::codes::configure_severity(&mut fix.diagnostic, &severities)
+                    {
+                        continue;
+                    }
+                    let mut changes = fix.changes;
+                    self.match_line_endings(&mut changes);
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: fix.title,
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![fix.diagnostic]),
+                        edit: Some(WorkspaceEdit {
+                            changes: Some(changes),
+                            ..Default::default()
+                        }),
+                        is_preferred: Some(true),
+                        ..Default::default()
+                    }));
+                }
 
+                // Imports of modules no dependency has: offer the cached
+                // packages exposing them
+                for (module_name, packages) in workspace.missing_import_packages(uri, range) {
+                    for package in packages {
                         actions.push(CodeActionOrCommand::CodeAction(CodeAction {
-                            title: format!("Import {} from {}", sym.name, sym.module_name),
+                            title: format!("Add dependency {} for {}", package, module_name),
                             kind: Some(CodeActionKind::QUICKFIX),
-                            edit: Some(WorkspaceEdit {
-                                changes: Some(changes),
-                                ..Default::default()
+                            command: Some(Command {
+                                title: format!("Install {}", package),
+                                command: CMD_ADD_DEPENDENCY.to_string(),
+                                arguments: Some(vec![
+                                    serde_json::json!(uri.to_string()),
+                                    serde_json::json!(package),
+                                ]),
                             }),
                             ..Default::default()
                         }));
                     }
                 }
+
+                // Keep `@docs` in step with what a package module exposes,
+                // drop imports the module does not use and name the module
+                // after its path
+                let fixes = workspace
+                    .docs_fixes(uri, range)
+                    .into_iter()
+                    .chain(workspace.unused_import_fixes(uri, range))
+                    .chain(workspace.module_path_fixes(uri, range));
+                for mut fix in fixes {
+                    if is_fixed(&fix.diagnostic)
+                        || analysis::is_suppressed(&text, &fix.diagnostic)
+                        || !analysis::codes::configure_severity(&mut fix.diagnostic, &severities)
+                    {
+                        continue;
+                    }
+                    let mut changes = std::collections::HashMap::new();
+                    changes.insert(uri.clone(), fix.edits);
+                    self.match_line_endings(&mut changes);
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: fix.title,
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![fix.diagnostic]),
+                        edit: Some(WorkspaceEdit {
+                            changes: Some(changes),
+                            ..Default::default()
+                        }),
+                        is_preferred: Some(true),
+                        ..Default::default()
+                    }));
+                }
+
+                // Or move the file where its module name says it belongs
+                let mismatch = params.context.diagnostics.iter().find(|d| {
+                    matches!(&d.code, Some(NumberOrString::String(code))
+                        if code == analysis::codes::MODULE_NAME_MISMATCH.name)
+                });
+                if let (Some(diagnostic), Some(target)) =
+                    (mismatch, workspace.module_path_target(uri))
+                {
+                    let shown = target
+                        .strip_prefix(&workspace.root_path)
+                        .unwrap_or(&target)
+                        .to_string_lossy()
+                        .to_string();
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: format!("Move file to {}", shown),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        command: Some(Command {
+                            title: format!("Move file to {}", shown),
+                            command: CMD_MOVE_FILE.to_string(),
+                            arguments: Some(vec![
+                                serde_json::json!(uri.to_string()),
+                                serde_json::json!(target.to_string_lossy()),
+                            ]),
+                        }),
+                        ..Default::default()
+                    }));
+                }
+            }
+        }
+
+        // Check if cursor is on a function that could be exposed
+        if let Some(doc) = self.documents.get(uri) {
+            if let Some(symbol) = doc.get_symbol_at_position(range.start) {
+                if symbol.kind == SymbolKind::FUNCTION {
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: format!("Expose {}", symbol.name),
+                        kind: Some(CodeActionKind::REFACTOR),
+                        ..Default::default()
+                    }));
+                }
+            }
+        }
+
+        // Sort the variants or record fields of the type under the cursor.
+        // Only the declaration is checked here; the command updates the
+        // constructor calls across the workspace when it runs.
+        if let Some((name, _)) = self.get_type_at_position(uri, range.start) {
+            let unsorted = self
+                .reorder_at(uri, range.start, None, false)
+                .is_ok_and(|result| result.changes.is_some_and(|c| !c.is_empty()));
+            if unsorted {
+                let what = if self.is_custom_type(uri, &name) {
+                    "variants"
+                } else {
+                    "fields"
+                };
+                let title = format!("Sort {} of {} alphabetically", what, name);
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: title.clone(),
+                    kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                    command: Some(Command {
+                        title,
+                        command: CMD_REORDER.to_string(),
+                        arguments: Some(vec![
+                            serde_json::json!(uri.to_string()),
+                            serde_json::json!(range.start.line),
+                            serde_json::json!(range.start.character),
+                            serde_json::Value::Null,
+                            serde_json::json!(true),
+                        ]),
+                    }),
+                    ..Default::default()
+                }));
             }
         }
 
-        // Check if cursor is on a function that could be exposed
-        if let Some(doc) = self.documents.get(uri) {
-            if let Some(symbol) = doc.get_symbol_at_position(range.start) {
-                if symbol.kind == SymbolKind::FUNCTION {
-                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
-                        title: format!("Expose {}", symbol.name),
-                        kind: Some(CodeActionKind::REFACTOR),
-                        ..Default::default()
-                    }));
-                }
+        // On the module header, make the `@docs` lines match what is exposed
+        let docs_skeleton = match self.workspaces.read() {
+            Ok(ws) => ws
+                .for_uri(uri)
+                .and_then(|workspace| workspace.docs_skeleton(uri, range.start)),
+            Err(_) => None,
+        };
+        if let Some(edit) = docs_skeleton {
+            let mut changes = std::collections::HashMap::from([(uri.clone(), vec![edit])]);
+            self.match_line_endings(&mut changes);
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: "Generate @docs for exposed declarations".to_string(),
+                kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(changes),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }));
+        }
+
+        // Turn nested cases into one match on a pair, or back
+        let nested_cases = match self.workspaces.read() {
+            Ok(ws) => ws
+                .for_uri(uri)
+                .map(|workspace| {
+                    [
+                        (
+                            "Flatten nested case into a tuple match",
+                            workspace.flatten_case(uri, range.start),
+                        ),
+                        (
+                            "Split tuple case into nested cases",
+                            workspace.split_case(uri, range.start),
+                        ),
+                    ]
+                })
+                .into_iter()
+                .flatten()
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        for (title, edit) in nested_cases {
+            let Some(edit) = edit else { continue };
+            let mut changes = std::collections::HashMap::from([(uri.clone(), vec![edit])]);
+            self.match_line_endings(&mut changes);
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: title.to_string(),
+                kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(changes),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }));
+        }
+
+        // Spell a literal differently
+        let literal_rewrites: Vec<(String, TextEdit)> = match self.workspaces.read() {
+            Ok(ws) => ws
+                .for_uri(uri)
+                .map(|workspace| {
+                    let concat = workspace
+                        .string_concat(uri, range.start)
+                        .map(|edit| ("Convert to String.concat".to_string(), edit));
+                    workspace
+                        .convert_int_literal(uri, range.start)
+                        .into_iter()
+                        .chain(concat)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+        for (title, edit) in literal_rewrites {
+            let mut changes = std::collections::HashMap::from([(uri.clone(), vec![edit])]);
+            self.match_line_endings(&mut changes);
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title,
+                kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(changes),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }));
+        }
+
+        // Move a let binding out as far as it can go, or into its only use
+        let let_moves: Vec<(String, Vec<TextEdit>)> = match self.workspaces.read() {
+            Ok(ws) => ws
+                .for_uri(uri)
+                .map(|workspace| {
+                    workspace
+                        .float_binding(uri, range.start)
+                        .into_iter()
+                        .chain(workspace.sink_binding(uri, range.start))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+        for (title, edits) in let_moves {
+            let mut changes = std::collections::HashMap::from([(uri.clone(), edits)]);
+            self.match_line_endings(&mut changes);
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title,
+                kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(changes),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }));
+        }
+
+        // Pull a selected expression out into a parameter or a constant; the
+        // edits, which reach every caller, are computed when the command runs
+        let extractable = range.start != range.end
+            && match self.workspaces.read() {
+                Ok(ws) => ws
+                    .for_uri(uri)
+                    .is_some_and(|workspace| workspace.is_extractable(uri, range)),
+                Err(_) => false,
+            };
+        if extractable {
+            let extractions = [
+                (CMD_INTRODUCE_PARAMETER, "newParam", "Introduce parameter"),
+                (
+                    CMD_HOIST_CONSTANT,
+                    "newConstant",
+                    "Extract to top-level constant",
+                ),
+                (CMD_EXTRACT_VIEW_HELPER, "viewHelper", "Extract view helper"),
+                (
+                    CMD_EXTRACT_VIEW_HELPER_WITH_MSG,
+                    "viewHelper",
+                    "Extract view helper with its own Msg",
+                ),
+            ];
+            for (command, name, title) in extractions {
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: title.to_string(),
+                    kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+                    command: Some(Command {
+                        title: title.to_string(),
+                        command: command.to_string(),
+                        arguments: Some(vec![
+                            serde_json::json!(uri.to_string()),
+                            serde_json::json!(range.start.line),
+                            serde_json::json!(range.start.character),
+                            serde_json::json!(range.end.line),
+                            serde_json::json!(range.end.character),
+                            serde_json::json!(name),
+                        ]),
+                    }),
+                    ..Default::default()
+                }));
             }
         }
 
@@ -1419,7 +3811,8 @@ impl LanguageServer for ElmLanguageServer {
         &self,
         params: ExecuteCommandParams,
     ) -> Result<Option<serde_json::Value>> {
-        tracing::info!("execute_command: {:?}", params.command);
+        tracing::info!(target: logging::REFACTOR, "execute_command: {:?}", params.command);
+        let versions = self.document_versions();
 
         match params.command.as_str() {
             CMD_MOVE_FUNCTION => {
@@ -1438,6 +3831,7 @@ impl LanguageServer for ElmLanguageServer {
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
 
                 tracing::info!(
+                    target: logging::REFACTOR,
                     "Moving {} from {} to {}",
                     function_name,
                     source_uri,
@@ -1451,8 +3845,8 @@ impl LanguageServer for ElmLanguageServer {
 
                 // Execute the move - extract result before any awaits
                 let move_result = {
-                    if let Ok(ws) = self.workspace.read() {
-                        if let Some(workspace) = ws.as_ref() {
+                    if let Ok(ws) = self.workspaces.read() {
+                        if let Some(workspace) = ws.for_uri(&source_uri) {
                             workspace.move_function(&source_uri, &function_name, &target_path)
                         } else {
                             Err(anyhow::anyhow!("Workspace not initialized"))
@@ -1464,22 +3858,25 @@ impl LanguageServer for ElmLanguageServer {
 
                 match move_result {
                     Ok(result) => {
-                        // Convert to workspace edit and apply
-                        let edit = WorkspaceEdit {
-                            changes: Some(result.changes),
-                            ..Default::default()
-                        };
-
-                        // Apply the edit
-                        let _ = self.client.apply_edit(edit).await;
-
-                        Ok(Some(serde_json::json!({
+                        let label =
+                            format!("Move {} to {}", result.function_name, result.target_module);
+                        let response = serde_json::json!({
                             "success": true,
                             "sourceModule": result.source_module,
                             "targetModule": result.target_module,
                             "functionName": result.function_name,
                             "referencesUpdated": result.references_updated
-                        })))
+                        });
+                        Ok(Some(
+                            self.respond_with_edits(
+                                &label,
+                                result.changes,
+                                None,
+                                &versions,
+                                response,
+                            )
+                            .await,
+                        ))
                     }
                     Err(e) => Ok(Some(serde_json::json!({
                         "error": e.to_string()
@@ -1501,9 +3898,9 @@ impl LanguageServer for ElmLanguageServer {
                     tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
                 })?;
 
-                tracing::info!("Getting diagnostics for {}", uri);
+                tracing::info!(target: logging::DIAGNOSTICS, "Getting diagnostics for {}", uri);
 
-                let diagnostics = self.get_diagnostics(&uri);
+                let diagnostics = self.compile(&uri);
 
                 // Convert diagnostics to JSON-serializable format
                 let diagnostics_json: Vec<serde_json::Value> = diagnostics.iter().map(|d| {
@@ -1524,16 +3921,343 @@ impl LanguageServer for ElmLanguageServer {
                     })
                 }).collect();
 
-                Ok(Some(serde_json::json!({
-                    "uri": file_uri,
-                    "diagnostics": diagnostics_json
-                })))
+                Ok(Some(serde_json::json!({
+                    "uri": file_uri,
+                    "diagnostics": diagnostics_json
+                })))
+            }
+            CMD_PREPARE_REMOVE_VARIANT => {
+                // Expected arguments: [uri, line, character]
+                if params.arguments.len() != 3 {
+                    return Ok(Some(serde_json::json!({
+                        "error": "Expected 3 arguments: uri, line, character"
+                    })));
+                }
+
+                let uri_str: String = serde_json::from_value(params.arguments[0].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let line: u32 = serde_json::from_value(params.arguments[1].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let character: u32 = serde_json::from_value(params.arguments[2].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+
+                let uri = Url::parse(&uri_str).map_err(|e| {
+                    tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
+                })?;
+
+                let position = Position { line, character };
+
+                if let Some((type_name, variant, idx, total, all_variants)) =
+                    self.get_variant_at_position(&uri, position)
+                {
+                    // Get all usages
+                    let all_usages = if let Ok(ws) = self.workspaces.read() {
+                        if let Some(workspace) = ws.for_uri(&uri) {
+                            let module_name = workspace.get_module_name_from_uri(&uri);
+                            workspace.get_variant_usages(&uri, &variant.name, Some(&module_name))
+                        } else {
+                            Vec::new()
+                        }
+                    } else {
+                        Vec::new()
+                    };
+
+                    // Only constructor usages are truly blocking
+                    let blocking_usages: Vec<_> = all_usages
+                        .iter()
+                        .filter(|u| u.is_blocking)
+                        .cloned()
+                        .collect();
+
+                    // Pattern match usages can be auto-removed
+                    let pattern_usages: Vec<_> = all_usages
+                        .iter()
+                        .filter(|u| !u.is_blocking)
+                        .cloned()
+                        .collect();
+
+                    let blocking_count = blocking_usages.len();
+                    let pattern_count = pattern_usages.len();
+                    // Can always remove if more than 1 variant - constructor usages will be replaced with Debug.todo
+                    let can_remove = total > 1;
+
+                    // Other variants (excluding the one being removed)
+                    let other_variants: Vec<&String> = all_variants
+                        .iter()
+                        .filter(|v| *v != &variant.name)
+                        .collect();
+
+                    Ok(Some(serde_json::json!({
+                        "success": true,
+                        "typeName": type_name,
+                        "variantName": variant.name,
+                        "variantIndex": idx,
+                        "totalVariants": total,
+                        "otherVariants": other_variants,
+                        "blockingCount": blocking_count,
+                        "patternCount": pattern_count,
+                        "canRemove": can_remove,
+                        "blockingUsages": blocking_usages,
+                        "patternUsages": pattern_usages,
+                        "range": {
+                            "start": { "line": variant.range.start.line, "character": variant.range.start.character },
+                            "end": { "line": variant.range.end.line, "character": variant.range.end.character }
+                        }
+                    })))
+                } else {
+                    Ok(Some(serde_json::json!({
+                        "success": false,
+                        "message": "No variant found at this position"
+                    })))
+                }
+            }
+            CMD_REMOVE_VARIANT => {
+                // Expected arguments: [uri, line, character, replacement?]
+                if !(3..=4).contains(&params.arguments.len()) {
+                    return Ok(Some(serde_json::json!({
+                        "error": "Expected arguments: uri, line, character, optional replacement"
+                    })));
+                }
+
+                let uri_str: String = serde_json::from_value(params.arguments[0].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let line: u32 = serde_json::from_value(params.arguments[1].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let character: u32 = serde_json::from_value(params.arguments[2].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                // "DebugTodo" (default), {"Expression": "..."} or {"Variant": "Name"}
+                let replacement: VariantReplacement = match params.arguments.get(3) {
+                    Some(value) => serde_json::from_value(value.clone())
+                        .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?,
+                    None => VariantReplacement::default(),
+                };
+
+                let uri = Url::parse(&uri_str).map_err(|e| {
+                    tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
+                })?;
+
+                let position = Position { line, character };
+
+                if let Some((type_name, variant, idx, total, all_variants)) =
+                    self.get_variant_at_position(&uri, position)
+                {
+                    // Other variants (excluding the one being removed)
+                    let other_variants: Vec<&String> = all_variants
+                        .iter()
+                        .filter(|v| *v != &variant.name)
+                        .collect();
+
+                    // Execute removal
+                    let remove_result = {
+                        if let Ok(ws) = self.workspaces.read() {
+                            if let Some(workspace) = ws.for_uri(&uri) {
+                                workspace.remove_variant(
+                                    &uri,
+                                    &type_name,
+                                    &variant.name,
+                                    idx,
+                                    total,
+                                    &replacement,
+                                )
+                            } else {
+                                Err(anyhow::anyhow!("Workspace not initialized"))
+                            }
+                        } else {
+                            Err(anyhow::anyhow!("Could not acquire workspace lock"))
+                        }
+                    };
+
+                    match remove_result {
+                        Ok(result) => {
+                            if result.success {
+                                let label = format!("Remove variant {}", variant.name);
+                                let response = serde_json::json!({
+                                    "success": true,
+                                    "message": result.message,
+                                    "typeName": type_name,
+                                    "variantName": variant.name
+                                });
+                                Ok(Some(
+                                    self.respond_with_edits(
+                                        &label,
+                                        result.changes.unwrap_or_default(),
+                                        None,
+                                        &versions,
+                                        response,
+                                    )
+                                    .await,
+                                ))
+                            } else {
+                                Ok(Some(serde_json::json!({
+                                    "success": false,
+                                    "message": result.message,
+                                    "typeName": type_name,
+                                    "variantName": variant.name,
+                                    "otherVariants": other_variants,
+                                    "blockingUsages": result.blocking_usages
+                                })))
+                            }
+                        }
+                        Err(e) => Ok(Some(serde_json::json!({
+                            "success": false,
+                            "message": e.to_string()
+                        }))),
+                    }
+                } else {
+                    Ok(Some(serde_json::json!({
+                        "success": false,
+                        "message": "No variant found at this position"
+                    })))
+                }
+            }
+            CMD_RENAME_FILE => {
+                // Expected arguments: [file_uri, new_name]
+                // new_name is just the filename without path, e.g. "NewName.elm"
+                if params.arguments.len() != 2 {
+                    return Ok(Some(serde_json::json!({
+                        "error": "Expected 2 arguments: file_uri, new_name"
+                    })));
+                }
+
+                let file_uri: String = serde_json::from_value(params.arguments[0].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let new_name: String = serde_json::from_value(params.arguments[1].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+
+                let uri = Url::parse(&file_uri).map_err(|e| {
+                    tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
+                })?;
+
+                tracing::info!(
+                    target: logging::REFACTOR,
+                    "Renaming file {} to {}",
+                    file_uri,
+                    new_name
+                );
+
+                let rename_result = {
+                    if let Ok(ws) = self.workspaces.read() {
+                        if let Some(workspace) = ws.for_uri(&uri) {
+                            workspace.rename_file(&uri, &new_name)
+                        } else {
+                            Err(anyhow::anyhow!("Workspace not initialized"))
+                        }
+                    } else {
+                        Err(anyhow::anyhow!("Could not acquire workspace lock"))
+                    }
+                };
+
+                match rename_result {
+                    Ok(result) => {
+                        let label = format!(
+                            "Rename {} to {}",
+                            result.old_module_name, result.new_module_name
+                        );
+                        let renamed = Url::from_file_path(&result.new_path)
+                            .ok()
+                            .map(|new_uri| (uri.clone(), new_uri));
+                        let response = serde_json::json!({
+                            "success": true,
+                            "oldModuleName": result.old_module_name,
+                            "newModuleName": result.new_module_name,
+                            "oldPath": result.old_path,
+                            "newPath": result.new_path,
+                            "filesUpdated": result.files_updated
+                        });
+                        Ok(Some(
+                            self.respond_with_edits(
+                                &label,
+                                result.changes,
+                                renamed,
+                                &versions,
+                                response,
+                            )
+                            .await,
+                        ))
+                    }
+                    Err(e) => Ok(Some(serde_json::json!({
+                        "success": false,
+                        "error": e.to_string()
+                    }))),
+                }
+            }
+            CMD_MOVE_FILE => {
+                // Expected arguments: [file_uri, target_path]
+                // target_path is the full path where the file should be moved, e.g. "src/Utils/Helper.elm"
+                if params.arguments.len() != 2 {
+                    return Ok(Some(serde_json::json!({
+                        "error": "Expected 2 arguments: file_uri, target_path"
+                    })));
+                }
+
+                let file_uri: String = serde_json::from_value(params.arguments[0].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let target_path: String = serde_json::from_value(params.arguments[1].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+
+                let uri = Url::parse(&file_uri).map_err(|e| {
+                    tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
+                })?;
+
+                tracing::info!(
+                    target: logging::REFACTOR,
+                    "Moving file {} to {}",
+                    file_uri,
+                    target_path
+                );
+
+                let move_result = {
+                    if let Ok(ws) = self.workspaces.read() {
+                        if let Some(workspace) = ws.for_uri(&uri) {
+                            workspace.move_file(&uri, &target_path)
+                        } else {
+                            Err(anyhow::anyhow!("Workspace not initialized"))
+                        }
+                    } else {
+                        Err(anyhow::anyhow!("Could not acquire workspace lock"))
+                    }
+                };
+
+                match move_result {
+                    Ok(result) => {
+                        let label = format!(
+                            "Move {} to {}",
+                            result.old_module_name, result.new_module_name
+                        );
+                        let renamed = Url::from_file_path(&result.new_path)
+                            .ok()
+                            .map(|new_uri| (uri.clone(), new_uri));
+                        let response = serde_json::json!({
+                            "success": true,
+                            "oldModuleName": result.old_module_name,
+                            "newModuleName": result.new_module_name,
+                            "oldPath": result.old_path,
+                            "newPath": result.new_path,
+                            "filesUpdated": result.files_updated
+                        });
+                        Ok(Some(
+                            self.respond_with_edits(
+                                &label,
+                                result.changes,
+                                renamed,
+                                &versions,
+                                response,
+                            )
+                            .await,
+                        ))
+                    }
+                    Err(e) => Ok(Some(serde_json::json!({
+                        "success": false,
+                        "error": e.to_string()
+                    }))),
+                }
             }
-            CMD_PREPARE_REMOVE_VARIANT => {
-                // Expected arguments: [uri, line, character]
-                if params.arguments.len() != 3 {
+            CMD_RENAME_VARIANT => {
+                // Expected arguments: [uri, line, character, newName, includeTests?, renameInDocs?]
+                if !(4..=6).contains(&params.arguments.len()) {
                     return Ok(Some(serde_json::json!({
-                        "error": "Expected 3 arguments: uri, line, character"
+                        "success": false,
+                        "error": "Expected arguments: uri, line, character, newName, optional includeTests, optional renameInDocs"
                     })));
                 }
 
@@ -1543,82 +4267,121 @@ impl LanguageServer for ElmLanguageServer {
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
                 let character: u32 = serde_json::from_value(params.arguments[2].clone())
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let new_name: String = serde_json::from_value(params.arguments[3].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let include_tests: Option<bool> = match params.arguments.get(4) {
+                    Some(value) => serde_json::from_value(value.clone())
+                        .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?,
+                    None => None,
+                };
+                let rename_in_docs: Option<bool> = match params.arguments.get(5) {
+                    Some(value) => serde_json::from_value(value.clone())
+                        .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?,
+                    None => None,
+                };
 
                 let uri = Url::parse(&uri_str).map_err(|e| {
                     tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
                 })?;
 
+                // Validate that variant name starts with uppercase
+                if !new_name
+                    .chars()
+                    .next()
+                    .map(|c| c.is_uppercase())
+                    .unwrap_or(false)
+                {
+                    return Ok(Some(serde_json::json!({
+                        "success": false,
+                        "error": "Variant names must start with an uppercase letter"
+                    })));
+                }
+
                 let position = Position { line, character };
 
-                if let Some((type_name, variant, idx, total, all_variants)) =
+                // First, verify this is a variant
+                if let Some((type_name, variant, _idx, _total, _all_variants)) =
                     self.get_variant_at_position(&uri, position)
                 {
-                    // Get all usages
-                    let all_usages = if let Ok(ws) = self.workspace.read() {
-                        if let Some(workspace) = ws.as_ref() {
-                            let module_name = workspace.get_module_name_from_uri(&uri);
-                            workspace.get_variant_usages(&uri, &variant.name, Some(&module_name))
-                        } else {
-                            Vec::new()
+                    // This IS a variant - proceed with rename using the variant's definition range
+                    let old_name = variant.name.clone();
+                    let variant_range = variant.range;
+
+                    // Use rename_variant_by_range with the variant's definition range directly
+                    match self.rename_variant_by_range(&uri, &old_name, variant_range, &new_name) {
+                        Ok(Some(edit)) => {
+                            // Convert WorkspaceEdit to JSON
+                            if let Some(mut changes) = edit.changes {
+                                self.rename_in_docs(
+                                    &uri,
+                                    &mut changes,
+                                    &old_name,
+                                    &new_name,
+                                    rename_in_docs,
+                                );
+                                self.filter_test_edits(&uri, &mut changes, include_tests);
+                                let response = serde_json::json!({
+                                    "success": true,
+                                    "oldName": old_name,
+                                    "newName": new_name,
+                                    "typeName": type_name,
+                                    "symbolKind": "variant"
+                                });
+                                Ok(Some(
+                                    self.respond_with_edits(
+                                        &format!("Rename {} to {}", old_name, new_name),
+                                        changes,
+                                        None,
+                                        &versions,
+                                        response,
+                                    )
+                                    .await,
+                                ))
+                            } else {
+                                Ok(Some(serde_json::json!({
+                                    "success": true,
+                                    "oldName": old_name,
+                                    "newName": new_name,
+                                    "typeName": type_name,
+                                    "symbolKind": "variant",
+                                    "message": "No changes needed"
+                                })))
+                            }
                         }
+                        Ok(None) => Ok(Some(serde_json::json!({
+                            "success": false,
+                            "error": "Rename not possible for this variant"
+                        }))),
+                        Err(e) => Ok(Some(serde_json::json!({
+                            "success": false,
+                            "error": e.to_string()
+                        }))),
+                    }
+                } else {
+                    // Not a variant - detect what it actually is and return error
+                    let actual_kind = if let Some(doc) = self.documents.get(&uri) {
+                        self.get_symbol_kind_at_position(&uri, position, &doc.text)
                     } else {
-                        Vec::new()
+                        "unknown"
                     };
 
-                    // Only constructor usages are truly blocking
-                    let blocking_usages: Vec<_> = all_usages
-                        .iter()
-                        .filter(|u| u.is_blocking)
-                        .cloned()
-                        .collect();
-
-                    // Pattern match usages can be auto-removed
-                    let pattern_usages: Vec<_> = all_usages
-                        .iter()
-                        .filter(|u| !u.is_blocking)
-                        .cloned()
-                        .collect();
-
-                    let blocking_count = blocking_usages.len();
-                    let pattern_count = pattern_usages.len();
-                    // Can always remove if more than 1 variant - constructor usages will be replaced with Debug.todo
-                    let can_remove = total > 1;
-
-                    // Other variants (excluding the one being removed)
-                    let other_variants: Vec<&String> = all_variants
-                        .iter()
-                        .filter(|v| *v != &variant.name)
-                        .collect();
-
-                    Ok(Some(serde_json::json!({
-                        "success": true,
-                        "typeName": type_name,
-                        "variantName": variant.name,
-                        "variantIndex": idx,
-                        "totalVariants": total,
-                        "otherVariants": other_variants,
-                        "blockingCount": blocking_count,
-                        "patternCount": pattern_count,
-                        "canRemove": can_remove,
-                        "blockingUsages": blocking_usages,
-                        "patternUsages": pattern_usages,
-                        "range": {
-                            "start": { "line": variant.range.start.line, "character": variant.range.start.character },
-                            "end": { "line": variant.range.end.line, "character": variant.range.end.character }
-                        }
-                    })))
-                } else {
                     Ok(Some(serde_json::json!({
                         "success": false,
-                        "message": "No variant found at this position"
+                        "error": format!(
+                            "Position is not on a variant. Found '{}' instead. Use elm_rename_{} for this symbol.",
+                            actual_kind,
+                            if actual_kind == "unknown" { "..." } else { actual_kind }
+                        ),
+                        "actualKind": actual_kind
                     })))
                 }
             }
-            CMD_REMOVE_VARIANT => {
-                // Expected arguments: [uri, line, character]
-                if params.arguments.len() != 3 {
+            CMD_RENAME_TYPE => {
+                // Expected arguments: [uri, line, character, newName, includeTests?, renameInDocs?]
+                if !(4..=6).contains(&params.arguments.len()) {
                     return Ok(Some(serde_json::json!({
-                        "error": "Expected 3 arguments: uri, line, character"
+                        "success": false,
+                        "error": "Expected arguments: uri, line, character, newName, optional includeTests, optional renameInDocs"
                     })));
                 }
 
@@ -1628,6 +4391,18 @@ impl LanguageServer for ElmLanguageServer {
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
                 let character: u32 = serde_json::from_value(params.arguments[2].clone())
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let new_name: String = serde_json::from_value(params.arguments[3].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let include_tests: Option<bool> = match params.arguments.get(4) {
+                    Some(value) => serde_json::from_value(value.clone())
+                        .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?,
+                    None => None,
+                };
+                let rename_in_docs: Option<bool> = match params.arguments.get(5) {
+                    Some(value) => serde_json::from_value(value.clone())
+                        .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?,
+                    None => None,
+                };
 
                 let uri = Url::parse(&uri_str).map_err(|e| {
                     tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
@@ -1635,113 +4410,223 @@ impl LanguageServer for ElmLanguageServer {
 
                 let position = Position { line, character };
 
-                if let Some((type_name, variant, idx, total, all_variants)) =
-                    self.get_variant_at_position(&uri, position)
-                {
-                    // Other variants (excluding the one being removed)
-                    let other_variants: Vec<&String> = all_variants
-                        .iter()
-                        .filter(|v| *v != &variant.name)
-                        .collect();
+                // First, verify this is a type (custom type or type alias)
+                if let Some((type_name, _def_range)) = self.get_type_at_position(&uri, position) {
+                    // Check for protected Lamdera types
+                    if let Ok(ws) = self.workspaces.read() {
+                        if let Some(workspace) = ws.for_uri(&uri) {
+                            if workspace.is_protected_lamdera_type(&type_name) {
+                                return Ok(Some(serde_json::json!({
+                                    "success": false,
+                                    "error": format!("Cannot rename {} in a Lamdera project - this type is required by Lamdera", type_name)
+                                })));
+                            }
+                        }
+                    }
 
-                    // Execute removal
-                    let remove_result = {
-                        if let Ok(ws) = self.workspace.read() {
-                            if let Some(workspace) = ws.as_ref() {
-                                workspace.remove_variant(
+                    let old_name = type_name.clone();
+
+                    // Use rename_symbol_by_name with the type name directly
+                    match self.rename_symbol_by_name(&uri, &old_name, &new_name) {
+                        Ok(Some(edit)) => {
+                            if let Some(mut changes) = edit.changes {
+                                self.rename_in_docs(
                                     &uri,
-                                    &type_name,
-                                    &variant.name,
-                                    idx,
-                                    total,
-                                )
+                                    &mut changes,
+                                    &old_name,
+                                    &new_name,
+                                    rename_in_docs,
+                                );
+                                self.filter_test_edits(&uri, &mut changes, include_tests);
+                                let response = serde_json::json!({
+                                    "success": true,
+                                    "oldName": old_name,
+                                    "newName": new_name,
+                                    "symbolKind": "type"
+                                });
+                                Ok(Some(
+                                    self.respond_with_edits(
+                                        &format!("Rename {} to {}", old_name, new_name),
+                                        changes,
+                                        None,
+                                        &versions,
+                                        response,
+                                    )
+                                    .await,
+                                ))
                             } else {
-                                Err(anyhow::anyhow!("Workspace not initialized"))
+                                Ok(Some(serde_json::json!({
+                                    "success": true,
+                                    "oldName": old_name,
+                                    "newName": new_name,
+                                    "symbolKind": "type",
+                                    "message": "No changes needed"
+                                })))
                             }
-                        } else {
-                            Err(anyhow::anyhow!("Could not acquire workspace lock"))
                         }
+                        Ok(None) => Ok(Some(serde_json::json!({
+                            "success": false,
+                            "error": "Rename not possible for this type"
+                        }))),
+                        Err(e) => Ok(Some(serde_json::json!({
+                            "success": false,
+                            "error": e.to_string()
+                        }))),
+                    }
+                } else {
+                    // Not a type - detect what it actually is and return error
+                    let actual_kind = if let Some(doc) = self.documents.get(&uri) {
+                        self.get_symbol_kind_at_position(&uri, position, &doc.text)
+                    } else {
+                        "unknown"
                     };
 
-                    match remove_result {
-                        Ok(result) => {
-                            if result.success {
-                                // Return the changes for the caller to apply
-                                // (instead of trying to apply via workspace/applyEdit which may not be supported)
-                                let changes_json = if let Some(ref changes) = result.changes {
-                                    let mut changes_map = serde_json::Map::new();
-                                    for (uri, edits) in changes {
-                                        let edits_json: Vec<serde_json::Value> = edits.iter().map(|edit| {
-                                            serde_json::json!({
-                                                "range": {
-                                                    "start": { "line": edit.range.start.line, "character": edit.range.start.character },
-                                                    "end": { "line": edit.range.end.line, "character": edit.range.end.character }
-                                                },
-                                                "newText": edit.new_text
-                                            })
-                                        }).collect();
-                                        changes_map
-                                            .insert(uri.to_string(), serde_json::json!(edits_json));
-                                    }
-                                    Some(serde_json::Value::Object(changes_map))
-                                } else {
-                                    None
-                                };
+                    Ok(Some(serde_json::json!({
+                        "success": false,
+                        "error": format!(
+                            "Position is not on a type. Found '{}' instead. Use elm_rename_{} for this symbol.",
+                            actual_kind,
+                            if actual_kind == "unknown" { "..." } else { actual_kind }
+                        ),
+                        "actualKind": actual_kind
+                    })))
+                }
+            }
+            CMD_RENAME_FUNCTION => {
+                // Expected arguments: [uri, line, character, newName, includeTests?, renameInDocs?]
+                if !(4..=6).contains(&params.arguments.len()) {
+                    return Ok(Some(serde_json::json!({
+                        "success": false,
+                        "error": "Expected arguments: uri, line, character, newName, optional includeTests, optional renameInDocs"
+                    })));
+                }
+
+                let uri_str: String = serde_json::from_value(params.arguments[0].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let line: u32 = serde_json::from_value(params.arguments[1].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let character: u32 = serde_json::from_value(params.arguments[2].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let new_name: String = serde_json::from_value(params.arguments[3].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let include_tests: Option<bool> = match params.arguments.get(4) {
+                    Some(value) => serde_json::from_value(value.clone())
+                        .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?,
+                    None => None,
+                };
+                let rename_in_docs: Option<bool> = match params.arguments.get(5) {
+                    Some(value) => serde_json::from_value(value.clone())
+                        .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?,
+                    None => None,
+                };
+
+                let uri = Url::parse(&uri_str).map_err(|e| {
+                    tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
+                })?;
 
-                                Ok(Some(serde_json::json!({
+                let position = Position { line, character };
+
+                // First, verify this is a function
+                if let Some((func_name, _def_range)) = self.get_function_at_position(&uri, position)
+                {
+                    let old_name = func_name.clone();
+
+                    // Use rename_symbol_by_name with the function name directly
+                    // (conflict check is done inside rename_symbol_by_name)
+                    match self.rename_symbol_by_name(&uri, &old_name, &new_name) {
+                        Ok(Some(edit)) => {
+                            if let Some(mut changes) = edit.changes {
+                                self.rename_in_docs(
+                                    &uri,
+                                    &mut changes,
+                                    &old_name,
+                                    &new_name,
+                                    rename_in_docs,
+                                );
+                                self.filter_test_edits(&uri, &mut changes, include_tests);
+                                let response = serde_json::json!({
                                     "success": true,
-                                    "message": result.message,
-                                    "typeName": type_name,
-                                    "variantName": variant.name,
-                                    "changes": changes_json
-                                })))
+                                    "oldName": old_name,
+                                    "newName": new_name,
+                                    "symbolKind": "function"
+                                });
+                                Ok(Some(
+                                    self.respond_with_edits(
+                                        &format!("Rename {} to {}", old_name, new_name),
+                                        changes,
+                                        None,
+                                        &versions,
+                                        response,
+                                    )
+                                    .await,
+                                ))
                             } else {
                                 Ok(Some(serde_json::json!({
-                                    "success": false,
-                                    "message": result.message,
-                                    "typeName": type_name,
-                                    "variantName": variant.name,
-                                    "otherVariants": other_variants,
-                                    "blockingUsages": result.blocking_usages
+                                    "success": true,
+                                    "oldName": old_name,
+                                    "newName": new_name,
+                                    "symbolKind": "function",
+                                    "message": "No changes needed"
                                 })))
                             }
                         }
+                        Ok(None) => Ok(Some(serde_json::json!({
+                            "success": false,
+                            "error": "Rename not possible for this function"
+                        }))),
                         Err(e) => Ok(Some(serde_json::json!({
                             "success": false,
-                            "message": e.to_string()
+                            "error": e.to_string()
                         }))),
                     }
                 } else {
+                    // Not a function - detect what it actually is and return error
+                    let actual_kind = if let Some(doc) = self.documents.get(&uri) {
+                        self.get_symbol_kind_at_position(&uri, position, &doc.text)
+                    } else {
+                        "unknown"
+                    };
+
                     Ok(Some(serde_json::json!({
                         "success": false,
-                        "message": "No variant found at this position"
+                        "error": format!(
+                            "Position is not on a function. Found '{}' instead. Use elm_rename_{} for this symbol.",
+                            actual_kind,
+                            if actual_kind == "unknown" { "..." } else { actual_kind }
+                        ),
+                        "actualKind": actual_kind
                     })))
                 }
             }
-            CMD_RENAME_FILE => {
-                // Expected arguments: [file_uri, new_name]
-                // new_name is just the filename without path, e.g. "NewName.elm"
+            CMD_NOTIFY_FILE_RENAMED => {
+                // Expected arguments: [old_path, new_path]
+                // Updates workspace index after file rename/move
                 if params.arguments.len() != 2 {
                     return Ok(Some(serde_json::json!({
-                        "error": "Expected 2 arguments: file_uri, new_name"
+                        "success": false,
+                        "error": "Expected 2 arguments: old_path, new_path"
                     })));
                 }
 
-                let file_uri: String = serde_json::from_value(params.arguments[0].clone())
+                let old_path: String = serde_json::from_value(params.arguments[0].clone())
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
-                let new_name: String = serde_json::from_value(params.arguments[1].clone())
+                let new_path: String = serde_json::from_value(params.arguments[1].clone())
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
 
-                let uri = Url::parse(&file_uri).map_err(|e| {
-                    tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
-                })?;
-
-                tracing::info!("Renaming file {} to {}", file_uri, new_name);
+                tracing::info!(
+                    target: logging::REFACTOR,
+                    "Notifying file renamed: {} -> {}",
+                    old_path,
+                    new_path
+                );
 
-                let rename_result = {
-                    if let Ok(ws) = self.workspace.read() {
-                        if let Some(workspace) = ws.as_ref() {
-                            workspace.rename_file(&uri, &new_name)
+                let result = {
+                    if let Ok(mut ws) = self.workspaces.write() {
+                        if let Some(workspace) = ws.for_path_mut(std::path::Path::new(&new_path)) {
+                            workspace.notify_file_renamed(
+                                std::path::Path::new(&old_path),
+                                std::path::Path::new(&new_path),
+                            )
                         } else {
                             Err(anyhow::anyhow!("Workspace not initialized"))
                         }
@@ -1750,66 +4635,136 @@ impl LanguageServer for ElmLanguageServer {
                     }
                 };
 
-                match rename_result {
-                    Ok(result) => {
-                        // Convert changes to JSON
-                        let changes_json = {
-                            let mut changes_map = serde_json::Map::new();
-                            for (uri, edits) in &result.changes {
-                                let edits_json: Vec<serde_json::Value> = edits.iter().map(|edit| {
-                                    serde_json::json!({
-                                        "range": {
-                                            "start": { "line": edit.range.start.line, "character": edit.range.start.character },
-                                            "end": { "line": edit.range.end.line, "character": edit.range.end.character }
-                                        },
-                                        "newText": edit.new_text
-                                    })
-                                }).collect();
-                                changes_map.insert(uri.to_string(), serde_json::json!(edits_json));
-                            }
-                            serde_json::Value::Object(changes_map)
-                        };
-
-                        Ok(Some(serde_json::json!({
-                            "success": true,
-                            "oldModuleName": result.old_module_name,
-                            "newModuleName": result.new_module_name,
-                            "oldPath": result.old_path,
-                            "newPath": result.new_path,
-                            "filesUpdated": result.files_updated,
-                            "changes": changes_json
-                        })))
-                    }
+                match result {
+                    Ok(()) => Ok(Some(serde_json::json!({
+                        "success": true,
+                        "message": format!("Updated index: {} -> {}", old_path, new_path)
+                    }))),
                     Err(e) => Ok(Some(serde_json::json!({
                         "success": false,
                         "error": e.to_string()
                     }))),
                 }
             }
-            CMD_MOVE_FILE => {
-                // Expected arguments: [file_uri, target_path]
-                // target_path is the full path where the file should be moved, e.g. "src/Utils/Helper.elm"
+            CMD_FIND_REFERENCES => {
+                // Expected arguments: [uri, line, character, includeTests?]
+                if !(3..=4).contains(&params.arguments.len()) {
+                    return Ok(Some(serde_json::json!({
+                        "success": false,
+                        "error": "Expected arguments: uri, line, character, optional includeTests"
+                    })));
+                }
+
+                let uri_str: String = serde_json::from_value(params.arguments[0].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let line: u32 = serde_json::from_value(params.arguments[1].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let character: u32 = serde_json::from_value(params.arguments[2].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let include_tests: Option<bool> = match params.arguments.get(3) {
+                    Some(value) => serde_json::from_value(value.clone())
+                        .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?,
+                    None => None,
+                };
+
+                let uri = Url::parse(&uri_str).map_err(|e| {
+                    tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
+                })?;
+
+                let locations = self
+                    .references_at(&uri, Position { line, character }, include_tests)
+                    .unwrap_or_default();
+                Ok(Some(serde_json::json!({
+                    "success": true,
+                    "references": locations
+                })))
+            }
+            CMD_ADD_DEPENDENCY => {
+                // Expected arguments: [file_uri, package]
                 if params.arguments.len() != 2 {
                     return Ok(Some(serde_json::json!({
-                        "error": "Expected 2 arguments: file_uri, target_path"
+                        "success": false,
+                        "error": "Expected 2 arguments: file_uri, package"
                     })));
                 }
 
                 let file_uri: String = serde_json::from_value(params.arguments[0].clone())
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
-                let target_path: String = serde_json::from_value(params.arguments[1].clone())
+                let package: String = serde_json::from_value(params.arguments[1].clone())
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let uri = Url::parse(&file_uri).map_err(|e| {
+                    tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
+                })?;
+
+                tracing::info!(target: logging::INDEX, "Adding dependency {} for {}", package, uri);
+
+                // The install takes the paths it needs, so the tool runs
+                // without holding the workspaces lock
+                let install = match self.workspaces.read() {
+                    Ok(ws) => match ws.for_uri(&uri) {
+                        Some(workspace) => workspace.package_install(&package),
+                        None => Err(anyhow::anyhow!("Workspace not initialized")),
+                    },
+                    Err(_) => Err(anyhow::anyhow!("Could not acquire workspace lock")),
+                };
+                let result = match install {
+                    Ok(install) => tokio::task::spawn_blocking(move || install.run())
+                        .await
+                        .unwrap_or_else(|e| Err(anyhow::anyhow!("Install failed: {}", e))),
+                    Err(e) => Err(e),
+                };
+
+                match result {
+                    Ok(()) => {
+                        self.refresh_packages().await;
+                        Ok(Some(serde_json::json!({
+                            "success": true,
+                            "package": package
+                        })))
+                    }
+                    Err(e) => {
+                        self.client
+                            .show_message(MessageType::ERROR, e.to_string())
+                            .await;
+                        Ok(Some(serde_json::json!({
+                            "success": false,
+                            "error": e.to_string()
+                        })))
+                    }
+                }
+            }
+            CMD_GET_IMPORTERS => {
+                // Expected arguments: [file_uri, max_depth?]
+                if params.arguments.is_empty() || params.arguments.len() > 2 {
+                    return Ok(Some(serde_json::json!({
+                        "success": false,
+                        "error": "Expected arguments: file_uri, optional max_depth"
+                    })));
+                }
+
+                let file_uri: String = serde_json::from_value(params.arguments[0].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let max_depth: usize = match params.arguments.get(1) {
+                    Some(depth) => serde_json::from_value(depth.clone())
+                        .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?,
+                    None => usize::MAX,
+                };
 
                 let uri = Url::parse(&file_uri).map_err(|e| {
                     tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
                 })?;
 
-                tracing::info!("Moving file {} to {}", file_uri, target_path);
+                tracing::info!(
+                    target: logging::NAVIGATION,
+                    "Getting importers of {} (max depth {})",
+                    uri,
+                    max_depth
+                );
 
-                let move_result = {
-                    if let Ok(ws) = self.workspace.read() {
-                        if let Some(workspace) = ws.as_ref() {
-                            workspace.move_file(&uri, &target_path)
+                let result = {
+                    if let Ok(ws) = self.workspaces.read() {
+                        if let Some(workspace) = ws.for_uri(&uri) {
+                            workspace.importers_of(&uri, max_depth)
                         } else {
                             Err(anyhow::anyhow!("Workspace not initialized"))
                         }
@@ -1818,34 +4773,22 @@ impl LanguageServer for ElmLanguageServer {
                     }
                 };
 
-                match move_result {
-                    Ok(result) => {
-                        // Convert changes to JSON
-                        let changes_json = {
-                            let mut changes_map = serde_json::Map::new();
-                            for (uri, edits) in &result.changes {
-                                let edits_json: Vec<serde_json::Value> = edits.iter().map(|edit| {
-                                    serde_json::json!({
-                                        "range": {
-                                            "start": { "line": edit.range.start.line, "character": edit.range.start.character },
-                                            "end": { "line": edit.range.end.line, "character": edit.range.end.character }
-                                        },
-                                        "newText": edit.new_text
-                                    })
-                                }).collect();
-                                changes_map.insert(uri.to_string(), serde_json::json!(edits_json));
-                            }
-                            serde_json::Value::Object(changes_map)
-                        };
-
+                match result {
+                    Ok((module_name, importers)) => {
+                        let importers: Vec<serde_json::Value> = importers
+                            .into_iter()
+                            .map(|(importer, importer_uri)| {
+                                serde_json::json!({
+                                    "module": importer.module,
+                                    "depth": importer.depth,
+                                    "uri": importer_uri.to_string()
+                                })
+                            })
+                            .collect();
                         Ok(Some(serde_json::json!({
                             "success": true,
-                            "oldModuleName": result.old_module_name,
-                            "newModuleName": result.new_module_name,
-                            "oldPath": result.old_path,
-                            "newPath": result.new_path,
-                            "filesUpdated": result.files_updated,
-                            "changes": changes_json
+                            "module": module_name,
+                            "importers": importers
                         })))
                     }
                     Err(e) => Ok(Some(serde_json::json!({
@@ -1854,12 +4797,12 @@ impl LanguageServer for ElmLanguageServer {
                     }))),
                 }
             }
-            CMD_RENAME_VARIANT => {
-                // Expected arguments: [uri, line, character, newName]
-                if params.arguments.len() != 4 {
+            CMD_REORDER => {
+                // Expected arguments: [uri, line, character, order?, updateCallSites?]
+                if !(3..=5).contains(&params.arguments.len()) {
                     return Ok(Some(serde_json::json!({
                         "success": false,
-                        "error": "Expected 4 arguments: uri, line, character, newName"
+                        "error": "Expected arguments: uri, line, character, optional order, optional updateCallSites"
                     })));
                 }
 
@@ -1869,108 +4812,61 @@ impl LanguageServer for ElmLanguageServer {
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
                 let character: u32 = serde_json::from_value(params.arguments[2].clone())
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
-                let new_name: String = serde_json::from_value(params.arguments[3].clone())
-                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                // Omitted or null: sort alphabetically
+                let order: Option<Vec<String>> = match params.arguments.get(3) {
+                    Some(value) => serde_json::from_value(value.clone())
+                        .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?,
+                    None => None,
+                };
+                let update_call_sites: bool = match params.arguments.get(4) {
+                    Some(value) => serde_json::from_value(value.clone())
+                        .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?,
+                    None => true,
+                };
 
                 let uri = Url::parse(&uri_str).map_err(|e| {
                     tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
                 })?;
 
-                // Validate that variant name starts with uppercase
-                if !new_name
-                    .chars()
-                    .next()
-                    .map(|c| c.is_uppercase())
-                    .unwrap_or(false)
-                {
-                    return Ok(Some(serde_json::json!({
-                        "success": false,
-                        "error": "Variant names must start with an uppercase letter"
-                    })));
-                }
-
-                let position = Position { line, character };
-
-                // First, verify this is a variant
-                if let Some((type_name, variant, _idx, _total, _all_variants)) =
-                    self.get_variant_at_position(&uri, position)
-                {
-                    // This IS a variant - proceed with rename using the variant's definition range
-                    let old_name = variant.name.clone();
-                    let variant_range = variant.range;
-
-                    // Use rename_variant_by_range with the variant's definition range directly
-                    match self.rename_variant_by_range(&uri, &old_name, variant_range, &new_name) {
-                        Ok(Some(edit)) => {
-                            // Convert WorkspaceEdit to JSON
-                            if let Some(changes) = edit.changes {
-                                let mut changes_json = serde_json::Map::new();
-                                for (uri, edits) in changes {
-                                    let edits_json: Vec<serde_json::Value> = edits.iter().map(|edit| {
-                                        serde_json::json!({
-                                            "range": {
-                                                "start": { "line": edit.range.start.line, "character": edit.range.start.character },
-                                                "end": { "line": edit.range.end.line, "character": edit.range.end.character }
-                                            },
-                                            "newText": edit.new_text
-                                        })
-                                    }).collect();
-                                    changes_json
-                                        .insert(uri.to_string(), serde_json::json!(edits_json));
-                                }
-                                Ok(Some(serde_json::json!({
-                                    "success": true,
-                                    "oldName": old_name,
-                                    "newName": new_name,
-                                    "typeName": type_name,
-                                    "symbolKind": "variant",
-                                    "changes": serde_json::Value::Object(changes_json)
-                                })))
-                            } else {
-                                Ok(Some(serde_json::json!({
-                                    "success": true,
-                                    "oldName": old_name,
-                                    "newName": new_name,
-                                    "typeName": type_name,
-                                    "symbolKind": "variant",
-                                    "message": "No changes needed"
-                                })))
-                            }
-                        }
-                        Ok(None) => Ok(Some(serde_json::json!({
-                            "success": false,
-                            "error": "Rename not possible for this variant"
-                        }))),
-                        Err(e) => Ok(Some(serde_json::json!({
-                            "success": false,
-                            "error": e.to_string()
-                        }))),
+                match self.reorder_at(
+                    &uri,
+                    Position { line, character },
+                    order.as_deref(),
+                    update_call_sites,
+                ) {
+                    Ok(result) if result.success => {
+                        let response = serde_json::json!({
+                            "success": true,
+                            "message": result.message,
+                            "unchangedUsages": result.unchanged_usages
+                        });
+                        Ok(Some(
+                            self.respond_with_edits(
+                                "Reorder",
+                                result.changes.unwrap_or_default(),
+                                None,
+                                &versions,
+                                response,
+                            )
+                            .await,
+                        ))
                     }
-                } else {
-                    // Not a variant - detect what it actually is and return error
-                    let actual_kind = if let Some(doc) = self.documents.get(&uri) {
-                        self.get_symbol_kind_at_position(&uri, position, &doc.text)
-                    } else {
-                        "unknown"
-                    };
-
-                    Ok(Some(serde_json::json!({
+                    Ok(result) => Ok(Some(serde_json::json!({
                         "success": false,
-                        "error": format!(
-                            "Position is not on a variant. Found '{}' instead. Use elm_rename_{} for this symbol.",
-                            actual_kind,
-                            if actual_kind == "unknown" { "..." } else { actual_kind }
-                        ),
-                        "actualKind": actual_kind
-                    })))
+                        "error": result.message
+                    }))),
+                    Err(e) => Ok(Some(serde_json::json!({
+                        "success": false,
+                        "error": e.to_string()
+                    }))),
                 }
             }
-            CMD_RENAME_TYPE => {
-                // Expected arguments: [uri, line, character, newName]
-                if params.arguments.len() != 4 {
+            CMD_TUPLE_TO_RECORD => {
+                // Expected arguments: [uri, line, character, aliasName, fieldNames]
+                if params.arguments.len() != 5 {
                     return Ok(Some(serde_json::json!({
                         "success": false,
-                        "error": "Expected 4 arguments: uri, line, character, newName"
+                        "error": "Expected arguments: uri, line, character, aliasName, fieldNames"
                     })));
                 }
 
@@ -1980,223 +4876,156 @@ impl LanguageServer for ElmLanguageServer {
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
                 let character: u32 = serde_json::from_value(params.arguments[2].clone())
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
-                let new_name: String = serde_json::from_value(params.arguments[3].clone())
+                let alias_name: String = serde_json::from_value(params.arguments[3].clone())
+                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                let field_names: Vec<String> = serde_json::from_value(params.arguments[4].clone())
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
 
                 let uri = Url::parse(&uri_str).map_err(|e| {
                     tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
                 })?;
 
-                let position = Position { line, character };
-
-                // First, verify this is a type (custom type or type alias)
-                if let Some((type_name, _def_range)) = self.get_type_at_position(&uri, position) {
-                    // Check for protected Lamdera types
-                    if let Ok(ws) = self.workspace.read() {
-                        if let Some(workspace) = ws.as_ref() {
-                            if workspace.is_protected_lamdera_type(&type_name) {
-                                return Ok(Some(serde_json::json!({
-                                    "success": false,
-                                    "error": format!("Cannot rename {} in a Lamdera project - this type is required by Lamdera", type_name)
-                                })));
-                            }
-                        }
-                    }
-
-                    let old_name = type_name.clone();
+                let result = self.tuple_to_record_at(
+                    &uri,
+                    Position { line, character },
+                    &alias_name,
+                    &field_names,
+                );
 
-                    // Use rename_symbol_by_name with the type name directly
-                    match self.rename_symbol_by_name(&uri, &old_name, &new_name) {
-                        Ok(Some(edit)) => {
-                            if let Some(changes) = edit.changes {
-                                let mut changes_json = serde_json::Map::new();
-                                for (uri, edits) in changes {
-                                    let edits_json: Vec<serde_json::Value> = edits.iter().map(|edit| {
-                                        serde_json::json!({
-                                            "range": {
-                                                "start": { "line": edit.range.start.line, "character": edit.range.start.character },
-                                                "end": { "line": edit.range.end.line, "character": edit.range.end.character }
-                                            },
-                                            "newText": edit.new_text
-                                        })
-                                    }).collect();
-                                    changes_json
-                                        .insert(uri.to_string(), serde_json::json!(edits_json));
-                                }
-                                Ok(Some(serde_json::json!({
-                                    "success": true,
-                                    "oldName": old_name,
-                                    "newName": new_name,
-                                    "symbolKind": "type",
-                                    "changes": serde_json::Value::Object(changes_json)
-                                })))
-                            } else {
-                                Ok(Some(serde_json::json!({
-                                    "success": true,
-                                    "oldName": old_name,
-                                    "newName": new_name,
-                                    "symbolKind": "type",
-                                    "message": "No changes needed"
-                                })))
-                            }
-                        }
-                        Ok(None) => Ok(Some(serde_json::json!({
-                            "success": false,
-                            "error": "Rename not possible for this type"
-                        }))),
-                        Err(e) => Ok(Some(serde_json::json!({
-                            "success": false,
-                            "error": e.to_string()
-                        }))),
+                match result {
+                    Ok(result) if result.success => {
+                        let response = serde_json::json!({
+                            "success": true,
+                            "message": result.message,
+                            "unchangedUsages": result.unchanged_usages
+                        });
+                        Ok(Some(
+                            self.respond_with_edits(
+                                "Convert tuple to record",
+                                result.changes.unwrap_or_default(),
+                                None,
+                                &versions,
+                                response,
+                            )
+                            .await,
+                        ))
                     }
-                } else {
-                    // Not a type - detect what it actually is and return error
-                    let actual_kind = if let Some(doc) = self.documents.get(&uri) {
-                        self.get_symbol_kind_at_position(&uri, position, &doc.text)
-                    } else {
-                        "unknown"
-                    };
-
-                    Ok(Some(serde_json::json!({
+                    Ok(result) => Ok(Some(serde_json::json!({
                         "success": false,
-                        "error": format!(
-                            "Position is not on a type. Found '{}' instead. Use elm_rename_{} for this symbol.",
-                            actual_kind,
-                            if actual_kind == "unknown" { "..." } else { actual_kind }
-                        ),
-                        "actualKind": actual_kind
-                    })))
+                        "error": result.message
+                    }))),
+                    Err(e) => Ok(Some(serde_json::json!({
+                        "success": false,
+                        "error": e.to_string()
+                    }))),
                 }
             }
-            CMD_RENAME_FUNCTION => {
-                // Expected arguments: [uri, line, character, newName]
-                if params.arguments.len() != 4 {
+            CMD_INTRODUCE_PARAMETER
+            | CMD_HOIST_CONSTANT
+            | CMD_EXTRACT_VIEW_HELPER
+            | CMD_EXTRACT_VIEW_HELPER_WITH_MSG => {
+                // Expected arguments: [uri, startLine, startCharacter, endLine, endCharacter, name]
+                if params.arguments.len() != 6 {
                     return Ok(Some(serde_json::json!({
                         "success": false,
-                        "error": "Expected 4 arguments: uri, line, character, newName"
+                        "error": "Expected arguments: uri, startLine, startCharacter, endLine, endCharacter, name"
                     })));
                 }
 
                 let uri_str: String = serde_json::from_value(params.arguments[0].clone())
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
-                let line: u32 = serde_json::from_value(params.arguments[1].clone())
-                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
-                let character: u32 = serde_json::from_value(params.arguments[2].clone())
-                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
-                let new_name: String = serde_json::from_value(params.arguments[3].clone())
+                let mut bounds = [0u32; 4];
+                for (i, bound) in bounds.iter_mut().enumerate() {
+                    *bound = serde_json::from_value(params.arguments[i + 1].clone())
+                        .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                }
+                let name: String = serde_json::from_value(params.arguments[5].clone())
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
 
                 let uri = Url::parse(&uri_str).map_err(|e| {
                     tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
                 })?;
+                let range = Range::new(
+                    Position::new(bounds[0], bounds[1]),
+                    Position::new(bounds[2], bounds[3]),
+                );
 
-                let position = Position { line, character };
-
-                // First, verify this is a function
-                if let Some((func_name, _def_range)) = self.get_function_at_position(&uri, position)
-                {
-                    let old_name = func_name.clone();
-
-                    // Use rename_symbol_by_name with the function name directly
-                    // (shadowing check is done inside rename_symbol_by_name)
-                    match self.rename_symbol_by_name(&uri, &old_name, &new_name) {
-                        Ok(Some(edit)) => {
-                            if let Some(changes) = edit.changes {
-                                let mut changes_json = serde_json::Map::new();
-                                for (uri, edits) in changes {
-                                    let edits_json: Vec<serde_json::Value> = edits.iter().map(|edit| {
-                                        serde_json::json!({
-                                            "range": {
-                                                "start": { "line": edit.range.start.line, "character": edit.range.start.character },
-                                                "end": { "line": edit.range.end.line, "character": edit.range.end.character }
-                                            },
-                                            "newText": edit.new_text
-                                        })
-                                    }).collect();
-                                    changes_json
-                                        .insert(uri.to_string(), serde_json::json!(edits_json));
-                                }
-                                Ok(Some(serde_json::json!({
-                                    "success": true,
-                                    "oldName": old_name,
-                                    "newName": new_name,
-                                    "symbolKind": "function",
-                                    "changes": serde_json::Value::Object(changes_json)
-                                })))
-                            } else {
-                                Ok(Some(serde_json::json!({
-                                    "success": true,
-                                    "oldName": old_name,
-                                    "newName": new_name,
-                                    "symbolKind": "function",
-                                    "message": "No changes needed"
-                                })))
-                            }
-                        }
-                        Ok(None) => Ok(Some(serde_json::json!({
-                            "success": false,
-                            "error": "Rename not possible for this function"
-                        }))),
-                        Err(e) => Ok(Some(serde_json::json!({
-                            "success": false,
-                            "error": e.to_string()
-                        }))),
+                match self.extract_at(params.command.as_str(), &uri, range, &name) {
+                    Ok(result) if result.success => {
+                        let response = serde_json::json!({
+                            "success": true,
+                            "message": result.message,
+                            "unchangedUsages": result.unchanged_usages
+                        });
+                        Ok(Some(
+                            self.respond_with_edits(
+                                match params.command.as_str() {
+                                    CMD_HOIST_CONSTANT => "Hoist constant",
+                                    CMD_EXTRACT_VIEW_HELPER | CMD_EXTRACT_VIEW_HELPER_WITH_MSG => {
+                                        "Extract view helper"
+                                    }
+                                    _ => "Introduce parameter",
+                                },
+                                result.changes.unwrap_or_default(),
+                                None,
+                                &versions,
+                                response,
+                            )
+                            .await,
+                        ))
                     }
-                } else {
-                    // Not a function - detect what it actually is and return error
-                    let actual_kind = if let Some(doc) = self.documents.get(&uri) {
-                        self.get_symbol_kind_at_position(&uri, position, &doc.text)
-                    } else {
-                        "unknown"
-                    };
-
-                    Ok(Some(serde_json::json!({
+                    Ok(result) => Ok(Some(serde_json::json!({
                         "success": false,
-                        "error": format!(
-                            "Position is not on a function. Found '{}' instead. Use elm_rename_{} for this symbol.",
-                            actual_kind,
-                            if actual_kind == "unknown" { "..." } else { actual_kind }
-                        ),
-                        "actualKind": actual_kind
-                    })))
+                        "error": result.message
+                    }))),
+                    Err(e) => Ok(Some(serde_json::json!({
+                        "success": false,
+                        "error": e.to_string()
+                    }))),
                 }
             }
-            CMD_NOTIFY_FILE_RENAMED => {
-                // Expected arguments: [old_path, new_path]
-                // Updates workspace index after file rename/move
-                if params.arguments.len() != 2 {
+            CMD_ORGANIZE_DECLARATIONS => {
+                // Expected arguments: [uri, order?]
+                if !(1..=2).contains(&params.arguments.len()) {
                     return Ok(Some(serde_json::json!({
                         "success": false,
-                        "error": "Expected 2 arguments: old_path, new_path"
+                        "error": "Expected arguments: uri, optional order"
                     })));
                 }
 
-                let old_path: String = serde_json::from_value(params.arguments[0].clone())
-                    .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
-                let new_path: String = serde_json::from_value(params.arguments[1].clone())
+                let uri_str: String = serde_json::from_value(params.arguments[0].clone())
                     .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+                // Omitted or null: the declarationOrder setting
+                let order: Option<DeclarationOrder> = match params.arguments.get(1) {
+                    Some(value) => serde_json::from_value(value.clone())
+                        .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?,
+                    None => None,
+                };
 
-                tracing::info!("Notifying file renamed: {} -> {}", old_path, new_path);
+                let uri = Url::parse(&uri_str).map_err(|e| {
+                    tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
+                })?;
 
-                let result = {
-                    if let Ok(mut ws) = self.workspace.write() {
-                        if let Some(workspace) = ws.as_mut() {
-                            workspace.notify_file_renamed(
-                                std::path::Path::new(&old_path),
-                                std::path::Path::new(&new_path),
+                match self.organize_at(&uri, order) {
+                    Ok(result) if result.success => {
+                        let response = serde_json::json!({
+                            "success": true,
+                            "message": result.message
+                        });
+                        Ok(Some(
+                            self.respond_with_edits(
+                                "Organize declarations",
+                                result.changes.unwrap_or_default(),
+                                None,
+                                &versions,
+                                response,
                             )
-                        } else {
-                            Err(anyhow::anyhow!("Workspace not initialized"))
-                        }
-                    } else {
-                        Err(anyhow::anyhow!("Could not acquire workspace lock"))
+                            .await,
+                        ))
                     }
-                };
-
-                match result {
-                    Ok(()) => Ok(Some(serde_json::json!({
-                        "success": true,
-                        "message": format!("Updated index: {} -> {}", old_path, new_path)
+                    Ok(result) => Ok(Some(serde_json::json!({
+                        "success": false,
+                        "error": result.message
                     }))),
                     Err(e) => Ok(Some(serde_json::json!({
                         "success": false,
@@ -2222,11 +5051,16 @@ impl LanguageServer for ElmLanguageServer {
                     tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid URI: {}", e))
                 })?;
 
-                tracing::info!("Generating ERD for type {} in {}", type_name, uri);
+                tracing::info!(
+                    target: logging::NAVIGATION,
+                    "Generating ERD for type {} in {}",
+                    type_name,
+                    uri
+                );
 
                 let erd_result = {
-                    if let Ok(ws) = self.workspace.read() {
-                        if let Some(workspace) = ws.as_ref() {
+                    if let Ok(ws) = self.workspaces.read() {
+                        if let Some(workspace) = ws.for_uri(&uri) {
                             workspace.generate_erd(&type_name, &uri)
                         } else {
                             Err("Workspace not initialized".to_string())
@@ -2273,8 +5107,8 @@ impl LanguageServer for ElmLanguageServer {
                 })?;
 
                 let result = {
-                    if let Ok(ws) = self.workspace.read() {
-                        if let Some(workspace) = ws.as_ref() {
+                    if let Ok(ws) = self.workspaces.read() {
+                        if let Some(workspace) = ws.for_uri(&uri) {
                             workspace.prepare_remove_field(&uri, line, character)
                         } else {
                             None
@@ -2360,8 +5194,8 @@ impl LanguageServer for ElmLanguageServer {
 
                 // First get the field info
                 let field_info = {
-                    if let Ok(ws) = self.workspace.read() {
-                        if let Some(workspace) = ws.as_ref() {
+                    if let Ok(ws) = self.workspaces.read() {
+                        if let Some(workspace) = ws.for_uri(&uri) {
                             workspace.prepare_remove_field(&uri, line, character)
                         } else {
                             None
@@ -2377,8 +5211,8 @@ impl LanguageServer for ElmLanguageServer {
 
                     // Execute removal
                     let remove_result = {
-                        if let Ok(ws) = self.workspace.read() {
-                            if let Some(workspace) = ws.as_ref() {
+                        if let Ok(ws) = self.workspaces.read() {
+                            if let Some(workspace) = ws.for_uri(&uri) {
                                 workspace.remove_field(
                                     &uri,
                                     &type_name,
@@ -2396,35 +5230,23 @@ impl LanguageServer for ElmLanguageServer {
                     match remove_result {
                         Ok(result) => {
                             if result.success {
-                                // Return the changes for the caller to apply
-                                let changes_json = if let Some(ref changes) = result.changes {
-                                    let mut changes_map = serde_json::Map::new();
-                                    for (uri, edits) in changes {
-                                        let edits_json: Vec<serde_json::Value> = edits.iter().map(|edit| {
-                                            serde_json::json!({
-                                                "range": {
-                                                    "start": { "line": edit.range.start.line, "character": edit.range.start.character },
-                                                    "end": { "line": edit.range.end.line, "character": edit.range.end.character }
-                                                },
-                                                "newText": edit.new_text
-                                            })
-                                        }).collect();
-                                        changes_map
-                                            .insert(uri.to_string(), serde_json::json!(edits_json));
-                                    }
-                                    Some(serde_json::Value::Object(changes_map))
-                                } else {
-                                    None
-                                };
-
-                                Ok(Some(serde_json::json!({
+                                let response = serde_json::json!({
                                     "success": true,
                                     "typeName": type_name,
                                     "fieldName": field_name,
                                     "otherFields": other_fields,
-                                    "message": result.message,
-                                    "changes": changes_json
-                                })))
+                                    "message": result.message
+                                });
+                                Ok(Some(
+                                    self.respond_with_edits(
+                                        &format!("Remove field {}", field_name),
+                                        result.changes.unwrap_or_default(),
+                                        None,
+                                        &versions,
+                                        response,
+                                    )
+                                    .await,
+                                ))
                             } else {
                                 Ok(Some(serde_json::json!({
                                     "success": false,
@@ -2470,8 +5292,8 @@ impl LanguageServer for ElmLanguageServer {
                 })?;
 
                 let result = {
-                    if let Ok(ws) = self.workspace.read() {
-                        if let Some(workspace) = ws.as_ref() {
+                    if let Ok(ws) = self.workspaces.read() {
+                        if let Some(workspace) = ws.for_uri(&uri) {
                             workspace.prepare_add_variant(&uri, &type_name, &new_variant_name)
                         } else {
                             crate::workspace::PrepareAddVariantResult::error(
@@ -2543,8 +5365,8 @@ impl LanguageServer for ElmLanguageServer {
                 })?;
 
                 let result = {
-                    if let Ok(ws) = self.workspace.read() {
-                        if let Some(workspace) = ws.as_ref() {
+                    if let Ok(ws) = self.workspaces.read() {
+                        if let Some(workspace) = ws.for_uri(&uri) {
                             workspace.add_variant(
                                 &uri,
                                 &type_name,
@@ -2563,34 +5385,22 @@ impl LanguageServer for ElmLanguageServer {
                 match result {
                     Ok(add_result) => {
                         if add_result.success {
-                            // Return the changes for the caller to apply
-                            let changes_json = if let Some(ref changes) = add_result.changes {
-                                let mut changes_map = serde_json::Map::new();
-                                for (uri, edits) in changes {
-                                    let edits_json: Vec<serde_json::Value> = edits.iter().map(|edit| {
-                                        serde_json::json!({
-                                            "range": {
-                                                "start": { "line": edit.range.start.line, "character": edit.range.start.character },
-                                                "end": { "line": edit.range.end.line, "character": edit.range.end.character }
-                                            },
-                                            "newText": edit.new_text
-                                        })
-                                    }).collect();
-                                    changes_map
-                                        .insert(uri.to_string(), serde_json::json!(edits_json));
-                                }
-                                Some(serde_json::Value::Object(changes_map))
-                            } else {
-                                None
-                            };
-
-                            Ok(Some(serde_json::json!({
+                            let response = serde_json::json!({
                                 "success": true,
                                 "message": add_result.message,
                                 "typeName": type_name,
-                                "variantName": new_variant_name,
-                                "changes": changes_json
-                            })))
+                                "variantName": new_variant_name
+                            });
+                            Ok(Some(
+                                self.respond_with_edits(
+                                    &format!("Add variant {}", new_variant_name),
+                                    add_result.changes.unwrap_or_default(),
+                                    None,
+                                    &versions,
+                                    response,
+                                )
+                                .await,
+                            ))
                         } else {
                             // Include prepare_info if available (for branch count mismatch errors)
                             let mut response = serde_json::json!({
@@ -2629,24 +5439,136 @@ impl LanguageServer for ElmLanguageServer {
         }
     }
 
-    async fn formatting(
+    async fn diagnostic(
         &self,
-        params: DocumentFormattingParams,
-    ) -> Result<Option<Vec<TextEdit>>> {
+        params: DocumentDiagnosticParams,
+    ) -> Result<DocumentDiagnosticReportResult> {
+        let uri = params.text_document.uri;
+        let Some(version) = self.documents.get(&uri).map(|doc| doc.version) else {
+            return Ok(full_diagnostic_report(None, Vec::new()));
+        };
+        let cached = self
+            .pulled_diagnostics
+            .get(&uri)
+            .filter(|pulled| pulled.version == version)
+            .map(|pulled| pulled.clone());
+        let pulled = match cached {
+            Some(pulled) => pulled,
+            None => {
+                let id = self.diagnostics_id.fetch_add(1, Ordering::Relaxed);
+                let pulled = PulledDiagnostics {
+                    version,
+                    result_id: id.to_string(),
+                    diagnostics: self.document_diagnostics(&uri),
+                };
+                self.pulled_diagnostics.insert(uri, pulled.clone());
+                pulled
+            }
+        };
+
+        if params.previous_result_id.as_deref() == Some(pulled.result_id.as_str()) {
+            return Ok(DocumentDiagnosticReportResult::Report(
+                DocumentDiagnosticReport::Unchanged(RelatedUnchangedDocumentDiagnosticReport {
+                    related_documents: None,
+                    unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport {
+                        result_id: pulled.result_id,
+                    },
+                }),
+            ));
+        }
+        Ok(full_diagnostic_report(
+            Some(pulled.result_id),
+            pulled.diagnostics,
+        ))
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        Ok(self
+            .full_semantic_tokens(&params.text_document.uri)
+            .map(SemanticTokensResult::Tokens))
+    }
+
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> Result<Option<SemanticTokensFullDeltaResult>> {
+        let uri = &params.text_document.uri;
+        let previous = self
+            .semantic_tokens
+            .get(uri)
+            .filter(|tokens| tokens.result_id.as_ref() == Some(&params.previous_result_id))
+            .map(|tokens| tokens.data.clone());
+        let Some(tokens) = self.full_semantic_tokens(uri) else {
+            return Ok(None);
+        };
+        // Without the result the client refers to, send everything
+        Ok(Some(match previous {
+            Some(previous) => SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+                result_id: tokens.result_id,
+                edits: semantic_tokens::edits(&previous, &tokens.data),
+            }),
+            None => SemanticTokensFullDeltaResult::Tokens(tokens),
+        }))
+    }
+
+    async fn semantic_tokens_range(
+        &self,
+        params: SemanticTokensRangeParams,
+    ) -> Result<Option<SemanticTokensRangeResult>> {
+        let Some(doc) = self.documents.get(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        let Some(tree) = self.parser.parse(&doc.text) else {
+            return Ok(None);
+        };
+        Ok(Some(SemanticTokensRangeResult::Tokens(SemanticTokens {
+            result_id: None,
+            data: semantic_tokens::tokens(&tree, &doc.text, Some(params.range)),
+        })))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
         let uri = &params.text_document.uri;
-        tracing::info!("formatting: uri={}", uri);
+        tracing::info!(target: logging::FORMAT, "formatting: uri={}", uri);
 
         // Get the file path
         let file_path = match uri.to_file_path() {
             Ok(path) => path,
             Err(_) => {
-                tracing::error!("Could not convert URI to file path: {}", uri);
+                tracing::error!(
+                    target: logging::FORMAT,
+                    "Could not convert URI to file path: {}",
+                    uri
+                );
                 return Ok(None);
             }
         };
 
-        // Run elm-format on the file
-        let output = match std::process::Command::new("elm-format")
+        // Restricted mode runs no tools from the project: the built-in formatter only
+        if self.is_restricted() {
+            let content = match self.documents.get(uri) {
+                Some(doc) => doc.text.clone(),
+                None => match read_source(&file_path) {
+                    Ok(c) => c,
+                    Err(_) => return Ok(None),
+                },
+            };
+            return Ok(crate::format::format(&content)
+                .map(|formatted| Self::whole_document_edit(&content, formatted)));
+        }
+
+        // Run elm-format on the file, the project's own when it has one
+        let elm_format = match self.workspaces.read() {
+            Ok(ws) => ws
+                .for_uri(uri)
+                .map(|workspace| workspace.tool_path("elm-format")),
+            Err(_) => None,
+        };
+        let elm_format = elm_format.unwrap_or_else(|| PathBuf::from("elm-format"));
+        let output = match std::process::Command::new(&elm_format)
             .args(["--stdin", "--elm-version=0.19"])
             .stdin(std::process::Stdio::piped())
             .stdout(std::process::Stdio::piped())
@@ -2658,10 +5580,10 @@ impl LanguageServer for ElmLanguageServer {
                 let content = if let Some(doc) = self.documents.get(uri) {
                     doc.text.clone()
                 } else {
-                    match std::fs::read_to_string(&file_path) {
+                    match read_source(&file_path) {
                         Ok(c) => c,
                         Err(e) => {
-                            tracing::error!("Could not read file: {}", e);
+                            tracing::error!(target: logging::FORMAT, "Could not read file: {}", e);
                             return Ok(None);
                         }
                     }
@@ -2671,7 +5593,11 @@ impl LanguageServer for ElmLanguageServer {
                 if let Some(mut stdin) = child.stdin.take() {
                     use std::io::Write;
                     if let Err(e) = stdin.write_all(content.as_bytes()) {
-                        tracing::error!("Could not write to elm-format stdin: {}", e);
+                        tracing::error!(
+                            target: logging::FORMAT,
+                            "Could not write to elm-format stdin: {}",
+                            e
+                        );
                         return Ok(None);
                     }
                 }
@@ -2679,27 +5605,34 @@ impl LanguageServer for ElmLanguageServer {
                 match child.wait_with_output() {
                     Ok(out) => out,
                     Err(e) => {
-                        tracing::error!("elm-format failed: {}", e);
+                        tracing::error!(target: logging::FORMAT, "elm-format failed: {}", e);
                         return Ok(None);
                     }
                 }
             }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(self.fallback_format(uri, &file_path).await);
+            }
             Err(e) => {
-                tracing::error!("Could not spawn elm-format: {}", e);
+                tracing::error!(target: logging::FORMAT, "Could not spawn elm-format: {}", e);
                 return Ok(None);
             }
         };
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            tracing::warn!("elm-format exited with error: {}", stderr);
+            tracing::warn!(target: logging::FORMAT, "elm-format exited with error: {}", stderr);
             return Ok(None);
         }
 
         let formatted = match String::from_utf8(output.stdout) {
             Ok(s) => s,
             Err(e) => {
-                tracing::error!("elm-format output was not valid UTF-8: {}", e);
+                tracing::error!(
+                    target: logging::FORMAT,
+                    "elm-format output was not valid UTF-8: {}",
+                    e
+                );
                 return Ok(None);
             }
         };
@@ -2708,33 +5641,36 @@ impl LanguageServer for ElmLanguageServer {
         let current_content = if let Some(doc) = self.documents.get(uri) {
             doc.text.clone()
         } else {
-            match std::fs::read_to_string(&file_path) {
+            match read_source(&file_path) {
                 Ok(c) => c,
                 Err(_) => return Ok(None),
             }
         };
 
-        // If content is the same, no edits needed
-        if formatted == current_content {
-            return Ok(Some(vec![]));
-        }
-
-        // Return a single edit replacing the entire document
-        let line_count = current_content.lines().count() as u32;
-        let last_line_len = current_content.lines().last().map(|l| l.len()).unwrap_or(0) as u32;
+        Ok(Some(Self::whole_document_edit(&current_content, formatted)))
+    }
 
-        Ok(Some(vec![TextEdit {
-            range: Range {
-                start: Position {
-                    line: 0,
-                    character: 0,
-                },
-                end: Position {
-                    line: line_count,
-                    character: last_line_len,
-                },
-            },
-            new_text: formatted,
-        }]))
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let uri = &params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        if params.ch != "\n" {
+            return Ok(None);
+        }
+        let Some(doc) = self.documents.get(uri) else {
+            return Ok(None);
+        };
+        // `in` for a new `let`, `of` for a `case` missing it
+        let edits = on_type::newline_edits(&doc.text, position);
+        tracing::debug!(
+            target: logging::FORMAT,
+            "on-type formatting at {}:{}: {} edits",
+            position.line,
+            position.character,
+            edits.len()
+        );
+        Ok((!edits.is_empty()).then_some(edits))
     }
 }
@@ -0,0 +1,107 @@
+//! Finding the command-line tools a project uses.
+//!
+//! Projects often pin elm, elm-format, elm-json, elm-test or elm-review with
+//! elm-tooling (`elm-tooling.json`) or install them with npm, instead of
+//! relying on what is on the PATH. A tool is looked up in `node_modules/.bin`
+//! of the project or a parent directory, then in elm-tooling's download
+//! directory for the version `elm-tooling.json` pins, and otherwise run by
+//! name from the PATH.
+
+use std::path::{Path, PathBuf};
+
+/// The tools the server may run or report
+pub const TOOLS: &[&str] = &[
+    "elm",
+    "lamdera",
+    "elm-format",
+    "elm-json",
+    "elm-test",
+    "elm-review",
+];
+
+/// The Elm home directory: `ELM_HOME`, or `~/.elm`
+pub fn elm_home() -> PathBuf {
+    if let Ok(elm_home) = std::env::var("ELM_HOME") {
+        PathBuf::from(elm_home)
+    } else if let Some(home) = dirs::home_dir() {
+        home.join(".elm")
+    } else {
+        PathBuf::from(".elm")
+    }
+}
+
+/// The program to run for `name` in the project at `project_root`
+pub fn find_tool(project_root: &Path, elm_home: &Path, name: &str) -> PathBuf {
+    for dir in project_root.ancestors() {
+        let installed = dir.join("node_modules").join(".bin").join(name);
+        if installed.is_file() {
+            return installed;
+        }
+        if let Some(version) = pinned_version(&dir.join("elm-tooling.json"), name) {
+            let downloaded = elm_home
+                .join("elm-tooling")
+                .join(name)
+                .join(version)
+                .join(name);
+            if downloaded.is_file() {
+                return downloaded;
+            }
+        }
+    }
+    PathBuf::from(name)
+}
+
+/// The version of `name` under `tools` in an elm-tooling.json
+fn pinned_version(elm_tooling: &Path, name: &str) -> Option<String> {
+    let content = std::fs::read_to_string(elm_tooling).ok()?;
+    let json: serde_json::Value = serde_json::from_str(&content).ok()?;
+    json.get("tools")?.get(name)?.as_str().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_tool_prefers_project_installs() {
+        let repo = TempDir::new().unwrap();
+        let elm_home = TempDir::new().unwrap();
+        let project = repo.path().join("apps/web");
+        fs::create_dir_all(&project).unwrap();
+
+        // elm-tooling.json at the repository root pins elm-format
+        fs::write(
+            repo.path().join("elm-tooling.json"),
+            r#"{ "tools": { "elm": "0.19.1", "elm-format": "0.8.7" } }"#,
+        )
+        .unwrap();
+        let downloaded = elm_home.path().join("elm-tooling/elm-format/0.8.7");
+        fs::create_dir_all(&downloaded).unwrap();
+        fs::write(downloaded.join("elm-format"), "").unwrap();
+        assert_eq!(
+            find_tool(&project, elm_home.path(), "elm-format"),
+            downloaded.join("elm-format")
+        );
+
+        // npm's node_modules/.bin comes first
+        let bin = repo.path().join("node_modules/.bin");
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(bin.join("elm-format"), "").unwrap();
+        assert_eq!(
+            find_tool(&project, elm_home.path(), "elm-format"),
+            bin.join("elm-format")
+        );
+
+        // Pinned but not downloaded, or not pinned: from the PATH
+        assert_eq!(
+            find_tool(&project, elm_home.path(), "elm"),
+            PathBuf::from("elm")
+        );
+        assert_eq!(
+            find_tool(&project, elm_home.path(), "elm-review"),
+            PathBuf::from("elm-review")
+        );
+    }
+}
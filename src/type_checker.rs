@@ -9,6 +9,7 @@ use tree_sitter::{Node, Tree};
 
 use crate::binder::{bind_tree, SymbolLinks};
 use crate::inference::{infer_file, InferenceResult, InferenceScope};
+use crate::logging;
 use crate::types::Type;
 
 /// Result of finding a definition
@@ -38,6 +39,12 @@ pub struct TargetTypeAlias {
     pub module: String,
 }
 
+/// The symbols and types of one file, see [`TypeChecker::analyze`]
+pub struct FileAnalysis {
+    symbol_links: SymbolLinks,
+    inference: InferenceResult,
+}
+
 /// Type checker that resolves definitions using type inference
 pub struct TypeChecker {
     /// Cached inference results per file
@@ -62,19 +69,28 @@ impl TypeChecker {
 
     /// Index a file for type checking
     pub fn index_file(&mut self, uri: &str, source: &str, tree: Tree) {
-        // Store source and tree
+        let analysis = Self::analyze(uri, source, &tree);
+        self.insert_analysis(uri, source, tree, analysis);
+    }
+
+    /// Bind and infer the types of a file, without touching the caches, so
+    /// files can be analyzed on several threads
+    pub fn analyze(uri: &str, source: &str, tree: &Tree) -> FileAnalysis {
+        FileAnalysis {
+            symbol_links: bind_tree(source, tree),
+            inference: infer_file(source, tree, uri),
+        }
+    }
+
+    /// Store the result of [`Self::analyze`] for `uri`
+    pub fn insert_analysis(&mut self, uri: &str, source: &str, tree: Tree, analysis: FileAnalysis) {
         self.source_cache
             .insert(uri.to_string(), source.to_string());
-        self.tree_cache.insert(uri.to_string(), tree.clone());
-
-        // Bind symbols
-        let symbol_links = bind_tree(source, &tree);
+        self.tree_cache.insert(uri.to_string(), tree);
         self.symbol_links_cache
-            .insert(uri.to_string(), symbol_links);
-
-        // Run type inference
-        let result = infer_file(source, &tree, uri);
-        self.inference_cache.insert(uri.to_string(), result);
+            .insert(uri.to_string(), analysis.symbol_links);
+        self.inference_cache
+            .insert(uri.to_string(), analysis.inference);
     }
 
     /// Get the type of an expression at a given node
@@ -106,6 +122,58 @@ impl TypeChecker {
         self.find_field_definition_impl(uri, node, source, Some(target))
     }
 
+    /// The type alias `ty` names and its fields in declaration order. `ty` is
+    /// an aliased record, or a union type named after the alias, as inference
+    /// leaves annotations like `Model` unexpanded.
+    pub fn record_alias_fields(&self, ty: &Type) -> Option<(TargetTypeAlias, Vec<String>)> {
+        let (module, type_name) = match ty {
+            Type::Record(r) => {
+                let alias = r.alias.as_ref()?;
+                (alias.module.as_str(), alias.name.as_str())
+            }
+            Type::Union(u) => (u.module.as_str(), u.name.as_str()),
+            _ => return None,
+        };
+
+        for (uri, tree) in &self.tree_cache {
+            let source = match self.source_cache.get(uri) {
+                Some(s) => s,
+                None => continue,
+            };
+            let file_module = self.get_module_name(uri, source);
+            if !module.is_empty() && file_module != module {
+                continue;
+            }
+
+            let root = tree.root_node();
+            let mut cursor = root.walk();
+            for child in root.children(&mut cursor) {
+                if child.kind() != "type_alias_declaration"
+                    || self.get_type_alias_name(&child, source).as_deref() != Some(type_name)
+                {
+                    continue;
+                }
+                let record = child.child_by_field_name("typeExpression").and_then(|t| {
+                    t.named_children(&mut t.walk())
+                        .find(|n| n.kind() == "record_type")
+                })?;
+                let fields = record
+                    .named_children(&mut record.walk())
+                    .filter(|n| n.kind() == "field_type")
+                    .filter_map(|n| n.child_by_field_name("name"))
+                    .filter_map(|n| n.utf8_text(source.as_bytes()).ok())
+                    .map(str::to_string)
+                    .collect();
+                let target = TargetTypeAlias {
+                    name: type_name.to_string(),
+                    module: file_module,
+                };
+                return Some((target, fields));
+            }
+        }
+        None
+    }
+
     fn find_field_definition_impl(
         &self,
         uri: &str,
@@ -332,6 +400,7 @@ impl TypeChecker {
                     }
                 };
                 tracing::debug!(
+                    target: logging::TYPES,
                     "find_field_definition(field): record_expr kind = {:?}",
                     record_expr.kind()
                 );
@@ -344,6 +413,7 @@ impl TypeChecker {
                         let base_text = base.utf8_text(source.as_bytes()).unwrap_or("?");
                         let base_type = self.infer_type_of_node(uri, base, source);
                         tracing::debug!(
+                            target: logging::TYPES,
                             "find_field_definition(field): base type = {:?}",
                             base_type
                         );
@@ -418,11 +488,13 @@ impl TypeChecker {
                             // BUT for single-field with a target, use stricter candidate counting
                             let min_fields = if target_alias.is_some() { 1 } else { 2 };
                             if combined_fields.len() >= min_fields {
-                                if combined_fields.len() == 1 && target_alias.is_some() {
+                                if let Some(target) =
+                                    target_alias.filter(|_| combined_fields.len() == 1)
+                                {
                                     // For single-field with target: count all candidates first
                                     // Only accept if target is a candidate AND there are few alternatives
-                                    let (target_matches, other_count) = self
-                                        .count_field_candidates(field_name, target_alias.unwrap());
+                                    let (target_matches, other_count) =
+                                        self.count_field_candidates(field_name, target);
                                     let base_is_lambda_param =
                                         Self::is_lambda_parameter(record_expr, base_text, source);
 
@@ -435,7 +507,7 @@ impl TypeChecker {
                                     if should_accept {
                                         // Return definition for the target type
                                         return self.find_field_in_type_alias_by_name(
-                                            &target_alias.unwrap().name,
+                                            &target.name,
                                             field_name,
                                             uri,
                                             target_alias,
@@ -463,6 +535,7 @@ impl TypeChecker {
                     // Try to get cached type from inference
                     let record_type = self.get_type(uri, record_expr.id());
                     tracing::debug!(
+                        target: logging::TYPES,
                         "find_field_definition(field): cached type = {:?}",
                         record_type
                     );
@@ -481,6 +554,7 @@ impl TypeChecker {
                     // Fallback: structural matching - collect fields from record_expr and match against type aliases
                     let record_fields = self.collect_record_expr_fields(record_expr, source);
                     tracing::debug!(
+                        target: logging::TYPES,
                         "find_field_definition(field): collected fields = {:?}",
                         record_fields
                     );
@@ -491,6 +565,7 @@ impl TypeChecker {
                         let result =
                             self.find_type_alias_by_fields(&record_fields, field_name, uri, None);
                         tracing::debug!(
+                            target: logging::TYPES,
                             "find_field_definition(field): structural match result = {:?}",
                             result.as_ref().map(|d| &d.type_alias_name)
                         );
@@ -604,7 +679,8 @@ impl TypeChecker {
 
     /// Infer the type of a specific node
     fn infer_type_of_node(&self, uri: &str, node: Node, source: &str) -> Option<Type> {
-        tracing::info!(
+        tracing::debug!(
+            target: logging::TYPES,
             "infer_type_of_node: node kind={}, id={}",
             node.kind(),
             node.id()
@@ -612,7 +688,7 @@ impl TypeChecker {
 
         // First check cache
         if let Some(ty) = self.get_type(uri, node.id()) {
-            tracing::info!("infer_type_of_node: cache hit {:?}", ty);
+            tracing::debug!(target: logging::TYPES, "infer_type_of_node: cache hit {:?}", ty);
             return Some(ty);
         }
 
@@ -624,22 +700,34 @@ impl TypeChecker {
         // Find the containing value_declaration to establish parameter bindings
         // This is needed for field access like user.name where user is a function parameter
         if let Some(containing_decl) = Self::find_containing_value_declaration(node) {
-            tracing::info!("infer_type_of_node: found containing decl, running inference");
+            tracing::debug!(
+                target: logging::TYPES,
+                "infer_type_of_node: found containing decl, running inference"
+            );
             // Run inference on the whole declaration first to bind parameters
             scope.infer(containing_decl);
             // Now get the type of the specific node from the expression_types cache
             if let Some(ty) = scope.get_expr_type(node.id()) {
-                tracing::info!("infer_type_of_node: got type from decl inference: {:?}", ty);
+                tracing::debug!(
+                    target: logging::TYPES,
+                    "infer_type_of_node: got type from decl inference: {:?}",
+                    ty
+                );
                 return Some(ty);
             }
-            tracing::info!(
+            tracing::debug!(
+                target: logging::TYPES,
                 "infer_type_of_node: node {} not in expression_types after decl inference",
                 node.id()
             );
         }
 
         let ty = scope.infer(node);
-        tracing::info!("infer_type_of_node: direct infer result: {:?}", ty);
+        tracing::debug!(
+            target: logging::TYPES,
+            "infer_type_of_node: direct infer result: {:?}",
+            ty
+        );
         Some(ty)
     }
 
@@ -992,6 +1080,7 @@ impl TypeChecker {
     }
 
     /// Extract inner type from a collection type like Cache a, List a, SeqDict k v, etc.
+    #[allow(clippy::only_used_in_recursion)]
     fn extract_inner_type_from_collection(&self, ty: &Type, param_index: usize) -> Option<String> {
         match ty {
             Type::Union(union_type) => {
@@ -1690,6 +1779,7 @@ impl TypeChecker {
     }
 
     /// Check if a type expression has a field with the given name and type
+    #[allow(clippy::too_many_arguments)]
     fn check_field_type_match(
         &self,
         node: Node,
@@ -1780,6 +1870,7 @@ impl TypeChecker {
         fields
     }
 
+    #[allow(clippy::only_used_in_recursion)]
     fn collect_field_accesses_recursive(
         &self,
         variable_name: &str,
@@ -2054,6 +2145,7 @@ impl TypeChecker {
         target: Option<&TargetTypeAlias>,
     ) -> Option<FieldDefinition> {
         tracing::debug!(
+            target: logging::TYPES,
             "find_type_alias_by_fields: looking for {:?} with target field {}, target={:?}",
             record_fields,
             target_field,
@@ -2316,6 +2408,44 @@ type alias User =
         assert_eq!(def.type_alias_name, Some("User".to_string()));
     }
 
+    #[test]
+    fn test_find_field_definition_from_record_literal() {
+        let source = r#"
+module Test exposing (..)
+
+type alias User =
+    { name : String
+    , email : String
+    }
+
+type alias Team =
+    { name : String
+    , size : Int
+    }
+
+ann : User
+ann =
+    { name = "Ann", email = "ann@example.com" }
+"#;
+        let tree = parse(source);
+        let mut checker = TypeChecker::new();
+        checker.index_file("test.elm", source, tree.clone());
+
+        // `name` in the literal, not in either alias
+        let offset = source.find("{ name = \"Ann\"").unwrap() + 2;
+        let field = tree
+            .root_node()
+            .descendant_for_byte_range(offset, offset)
+            .unwrap();
+        assert_eq!(field.parent().map(|p| p.kind()), Some("field"));
+
+        let def = checker
+            .find_field_definition("test.elm", field, source)
+            .expect("literal field should resolve");
+        assert_eq!(def.name, "name");
+        assert_eq!(def.type_alias_name, Some("User".to_string()));
+    }
+
     fn find_field_node<'a>(node: Node<'a>, source: &str, field_name: &str) -> Option<Node<'a>> {
         if node.kind() == "lower_case_identifier" {
             if let Ok(text) = node.utf8_text(source.as_bytes()) {
@@ -362,6 +362,215 @@ impl MutableRecordType {
     }
 }
 
+/// Where a type is printed, to know when it needs parentheses
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Precedence {
+    Top,
+    /// Left of an arrow
+    FunctionParam,
+    /// Argument of a type constructor
+    Argument,
+}
+
+impl Type {
+    /// Whether the type can be written down: nothing in it is unknown
+    pub fn is_known(&self) -> bool {
+        match self {
+            Type::Var(_) | Type::Unit(_) => true,
+            Type::Function(f) => f.params.iter().all(Type::is_known) && f.ret.is_known(),
+            Type::Tuple(t) => t.types.iter().all(Type::is_known),
+            Type::Union(u) => u.params.iter().all(Type::is_known),
+            Type::Record(r) => {
+                r.fields.values().all(Type::is_known)
+                    && r.base_type.as_deref().is_none_or(Type::is_known)
+            }
+            Type::MutableRecord(r) => {
+                r.fields.values().all(Type::is_known)
+                    && r.base_type.as_deref().is_none_or(Type::is_known)
+            }
+            Type::InProgressBinding | Type::Unknown => false,
+        }
+    }
+
+    /// The same type with inference variables (`t12`) renamed to unused
+    /// letters, in order of appearance, so it reads like a written annotation
+    pub fn with_readable_vars(&self) -> Type {
+        let mut taken = Vec::new();
+        self.collect_var_names(&mut taken);
+        let mut renames: HashMap<String, String> = HashMap::new();
+        let mut letters = (b'a'..=b'z').map(|c| (c as char).to_string());
+        self.rename_vars(&mut |name| {
+            let generated = name
+                .strip_prefix('t')
+                .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+            if !generated {
+                return name.to_string();
+            }
+            renames
+                .entry(name.to_string())
+                .or_insert_with(|| {
+                    letters
+                        .find(|l| !taken.contains(l))
+                        .unwrap_or_else(|| name.to_string())
+                })
+                .clone()
+        })
+    }
+
+    fn collect_var_names(&self, names: &mut Vec<String>) {
+        match self {
+            Type::Var(v) => names.push(v.name.clone()),
+            Type::Function(f) => {
+                f.params.iter().for_each(|p| p.collect_var_names(names));
+                f.ret.collect_var_names(names);
+            }
+            Type::Tuple(t) => t.types.iter().for_each(|t| t.collect_var_names(names)),
+            Type::Union(u) => u.params.iter().for_each(|p| p.collect_var_names(names)),
+            Type::Record(r) => {
+                r.fields.values().for_each(|t| t.collect_var_names(names));
+                if let Some(base) = &r.base_type {
+                    base.collect_var_names(names);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn rename_vars(&self, rename: &mut impl FnMut(&str) -> String) -> Type {
+        match self {
+            Type::Var(v) => Type::Var(TypeVar {
+                name: rename(&v.name),
+                ..v.clone()
+            }),
+            Type::Function(f) => Type::Function(FunctionType {
+                params: f.params.iter().map(|p| p.rename_vars(rename)).collect(),
+                ret: Box::new(f.ret.rename_vars(rename)),
+                alias: f.alias.clone(),
+            }),
+            Type::Tuple(t) => Type::Tuple(TupleType {
+                types: t.types.iter().map(|t| t.rename_vars(rename)).collect(),
+                alias: t.alias.clone(),
+            }),
+            Type::Union(u) => Type::Union(UnionType {
+                params: u.params.iter().map(|p| p.rename_vars(rename)).collect(),
+                ..u.clone()
+            }),
+            Type::Record(r) => {
+                let mut names: Vec<&String> = r.fields.keys().collect();
+                names.sort();
+                let fields = names
+                    .into_iter()
+                    .map(|name| (name.clone(), r.fields[name].rename_vars(rename)))
+                    .collect();
+                Type::Record(RecordType {
+                    fields,
+                    base_type: r
+                        .base_type
+                        .as_ref()
+                        .map(|b| Box::new(b.rename_vars(rename))),
+                    ..r.clone()
+                })
+            }
+            other => other.clone(),
+        }
+    }
+
+    fn write(&self, f: &mut std::fmt::Formatter<'_>, precedence: Precedence) -> std::fmt::Result {
+        if let Some(alias) = self.alias().filter(|_| !self.is_var()) {
+            return write_applied(f, &alias.name, &alias.parameters, precedence);
+        }
+        match self {
+            Type::Var(v) => write!(f, "{}", v.name),
+            Type::Function(function) => {
+                if precedence > Precedence::Top {
+                    write!(f, "(")?;
+                }
+                for param in &function.params {
+                    param.write(f, Precedence::FunctionParam)?;
+                    write!(f, " -> ")?;
+                }
+                function.ret.write(f, Precedence::Top)?;
+                if precedence > Precedence::Top {
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            Type::Tuple(tuple) => {
+                write!(f, "( ")?;
+                for (i, ty) in tuple.types.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    ty.write(f, Precedence::Top)?;
+                }
+                write!(f, " )")
+            }
+            Type::Union(union) => write_applied(f, &union.name, &union.params, precedence),
+            Type::Record(record) => write_record(f, &record.fields, record.base_type.as_deref()),
+            Type::MutableRecord(record) => {
+                write_record(f, &record.fields, record.base_type.as_deref())
+            }
+            Type::Unit(_) => write!(f, "()"),
+            Type::InProgressBinding | Type::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+/// Elm syntax for the type, e.g. `List (Maybe a) -> Int`. Names are unqualified.
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write(f, Precedence::Top)
+    }
+}
+
+fn write_applied(
+    f: &mut std::fmt::Formatter<'_>,
+    name: &str,
+    params: &[Type],
+    precedence: Precedence,
+) -> std::fmt::Result {
+    if params.is_empty() {
+        return write!(f, "{}", name);
+    }
+    if precedence == Precedence::Argument {
+        write!(f, "(")?;
+    }
+    write!(f, "{}", name)?;
+    for param in params {
+        write!(f, " ")?;
+        param.write(f, Precedence::Argument)?;
+    }
+    if precedence == Precedence::Argument {
+        write!(f, ")")?;
+    }
+    Ok(())
+}
+
+fn write_record(
+    f: &mut std::fmt::Formatter<'_>,
+    fields: &HashMap<String, Type>,
+    base: Option<&Type>,
+) -> std::fmt::Result {
+    if fields.is_empty() && base.is_none() {
+        return write!(f, "{{}}");
+    }
+    write!(f, "{{ ")?;
+    if let Some(base) = base {
+        base.write(f, Precedence::Top)?;
+        write!(f, " | ")?;
+    }
+    let mut names: Vec<&String> = fields.keys().collect();
+    names.sort();
+    for (i, name) in names.into_iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{} : ", name)?;
+        fields[name].write(f, Precedence::Top)?;
+    }
+    write!(f, " }}")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -427,4 +636,27 @@ mod tests {
         let refs = table.get("name");
         assert_eq!(refs.len(), 1); // Second add was ignored
     }
+
+    #[test]
+    fn test_display_parenthesizes_nested_types() {
+        let ty = Type::function(
+            vec![
+                Type::function(vec![Type::var("a")], Type::bool()),
+                Type::list(Type::maybe(Type::var("a"))),
+            ],
+            Type::tuple(vec![Type::int(), Type::string()]),
+        );
+        assert_eq!(
+            ty.to_string(),
+            "(a -> Bool) -> List (Maybe a) -> ( Int, String )"
+        );
+        assert!(ty.is_known());
+        assert_eq!(
+            Type::function(vec![Type::var("t7"), Type::var("a")], Type::var("t7"))
+                .with_readable_vars()
+                .to_string(),
+            "b -> a -> b"
+        );
+        assert!(!Type::list(Type::unknown()).is_known());
+    }
 }
@@ -0,0 +1,202 @@
+//! Call hierarchy: who calls a function, and what it calls.
+//!
+//! Both directions come from the reference index, like the call paths: a
+//! reference to a function from inside another top-level function is a
+//! call. Only workspace functions take part, and Evergreen snapshots and
+//! other generated modules are left out, as nobody calls into them.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use tower_lsp::lsp_types::*;
+use tree_sitter::Point;
+
+use crate::binder::BoundSymbolKind;
+
+use super::{ElmModule, ExposingInfo, Workspace};
+
+/// A function, by module name and name
+type Function = (String, String);
+
+impl Workspace {
+    /// The function a call hierarchy starts from: the one referred to at
+    /// `position`, or else the top-level function around it
+    pub fn prepare_call_hierarchy(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> Option<CallHierarchyItem> {
+        let module = self.find_module_by_path(&uri.to_file_path().ok()?)?;
+        if excluded(module) {
+            return None;
+        }
+        let referred = self.referred_function(uri, module, position);
+        let (module_name, name) = match referred {
+            Some(function) => function,
+            None => {
+                let (name, module_name) = self.find_enclosing_function(uri, position)?;
+                (module_name, name)
+            }
+        };
+        self.call_hierarchy_item(&module_name, &name)
+    }
+
+    /// The functions calling the one of `item`, each with its calls
+    pub fn incoming_calls(&self, item: &CallHierarchyItem) -> Vec<CallHierarchyIncomingCall> {
+        let Some(target) = self.item_function(item) else {
+            return Vec::new();
+        };
+        let mut calls: BTreeMap<Function, Vec<Range>> = BTreeMap::new();
+        for (caller, callee, range) in self.calls() {
+            if callee == target {
+                calls.entry(caller).or_default().push(range);
+            }
+        }
+        calls
+            .into_iter()
+            .filter_map(|((module_name, name), from_ranges)| {
+                Some(CallHierarchyIncomingCall {
+                    from: self.call_hierarchy_item(&module_name, &name)?,
+                    from_ranges,
+                })
+            })
+            .collect()
+    }
+
+    /// The functions the one of `item` calls, each with its calls
+    pub fn outgoing_calls(&self, item: &CallHierarchyItem) -> Vec<CallHierarchyOutgoingCall> {
+        let Some(source) = self.item_function(item) else {
+            return Vec::new();
+        };
+        let mut calls: BTreeMap<Function, Vec<Range>> = BTreeMap::new();
+        for (caller, callee, range) in self.calls() {
+            if caller == source {
+                calls.entry(callee).or_default().push(range);
+            }
+        }
+        let mut outgoing: Vec<CallHierarchyOutgoingCall> = calls
+            .into_iter()
+            .filter_map(|((module_name, name), mut from_ranges)| {
+                from_ranges.sort_by_key(|range| range.start);
+                Some(CallHierarchyOutgoingCall {
+                    to: self.call_hierarchy_item(&module_name, &name)?,
+                    from_ranges,
+                })
+            })
+            .collect();
+        outgoing.sort_by_key(|call| call.from_ranges[0].start);
+        outgoing
+    }
+
+    /// Every call between workspace functions: the calling function, the
+    /// function called, and where
+    fn calls(&self) -> Vec<(Function, Function, Range)> {
+        let by_path: HashMap<&PathBuf, &ElmModule> = self
+            .modules
+            .values()
+            .filter(|module| !excluded(module))
+            .map(|module| (&module.path, module))
+            .collect();
+        let mut calls = Vec::new();
+        for (key, references) in &self.references {
+            for reference in references {
+                if reference.is_definition || reference.kind != Some(BoundSymbolKind::Function) {
+                    continue;
+                }
+                let Some(module) = reference
+                    .uri
+                    .to_file_path()
+                    .ok()
+                    .and_then(|path| by_path.get(&path).copied())
+                else {
+                    continue;
+                };
+                let Some(callee) = self.call_target(module, key) else {
+                    continue;
+                };
+                let Some((name, module_name)) =
+                    self.find_enclosing_function(&reference.uri, reference.range.start)
+                else {
+                    continue;
+                };
+                calls.push(((module_name, name), callee, reference.range));
+            }
+        }
+        calls
+    }
+
+    /// The workspace function `key`, a name from the reference index, is
+    /// in code of `module`: qualified with its module, declared in
+    /// `module`, or brought in by an `exposing (..)`
+    fn call_target(&self, module: &ElmModule, key: &str) -> Option<Function> {
+        let is_function = |module_name: &str, name: &str| {
+            self.modules.get(module_name).is_some_and(|m| {
+                !excluded(m)
+                    && m.symbols
+                        .iter()
+                        .any(|s| s.name == name && s.kind == SymbolKind::FUNCTION)
+            })
+        };
+        if let Some((module_name, name)) = key.rsplit_once('.') {
+            return is_function(module_name, name)
+                .then(|| (module_name.to_string(), name.to_string()));
+        }
+        if is_function(&module.module_name, key) {
+            return Some((module.module_name.clone(), key.to_string()));
+        }
+        module
+            .imports
+            .iter()
+            .find(|import| {
+                matches!(import.exposing, ExposingInfo::All)
+                    && is_function(&import.module_name, key)
+            })
+            .map(|import| (import.module_name.clone(), key.to_string()))
+    }
+
+    /// The workspace function the name at `position` refers to
+    fn referred_function(
+        &self,
+        uri: &Url,
+        module: &ElmModule,
+        position: Position,
+    ) -> Option<Function> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let point = Point::new(position.line as usize, position.character as usize);
+        let node = tree.root_node().descendant_for_point_range(point, point)?;
+        let qid = node.parent().filter(|n| n.kind() == "value_qid")?;
+        let text = &source[qid.byte_range()];
+        let key = self.resolve_reference(text, &module.imports);
+        self.call_target(module, &key)
+    }
+
+    /// The function named by `item`, from its file and name
+    fn item_function(&self, item: &CallHierarchyItem) -> Option<Function> {
+        let module = self.find_module_by_path(&item.uri.to_file_path().ok()?)?;
+        Some((module.module_name.clone(), item.name.clone()))
+    }
+
+    fn call_hierarchy_item(&self, module_name: &str, name: &str) -> Option<CallHierarchyItem> {
+        let module = self.modules.get(module_name)?;
+        let symbol = module
+            .symbols
+            .iter()
+            .find(|s| s.name == name && s.kind == SymbolKind::FUNCTION)?;
+        Some(CallHierarchyItem {
+            name: name.to_string(),
+            kind: SymbolKind::FUNCTION,
+            tags: None,
+            detail: Some(module_name.to_string()),
+            uri: Url::from_file_path(&module.path).ok()?,
+            range: symbol.range,
+            selection_range: symbol.definition_range.unwrap_or(symbol.range),
+            data: None,
+        })
+    }
+}
+
+/// Evergreen snapshots and other generated modules
+fn excluded(module: &ElmModule) -> bool {
+    module.generated || module.path.to_string_lossy().contains("/Evergreen/")
+}
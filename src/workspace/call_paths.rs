@@ -0,0 +1,187 @@
+//! Every route from the entry points to a function.
+//!
+//! Starting from a function, the callers of each function are followed up
+//! to an entry point (`main`, or `app` in a Lamdera `Frontend`/`Backend`),
+//! keeping every distinct path instead of the first one found. Cycles are
+//! cut, and the search stops at a depth and a number of paths, so auditing a
+//! widely used helper stays cheap.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::*;
+
+use super::{CallChainEntry, Workspace};
+use crate::binder::BoundSymbolKind;
+
+/// Parameters of `elm-lsp/callPaths`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallPathsParams {
+    #[serde(flatten)]
+    pub text_document_position: TextDocumentPositionParams,
+    /// Longest path followed, in calls (default 10)
+    pub max_depth: Option<usize>,
+    /// Most paths returned (default 50)
+    pub max_paths: Option<usize>,
+}
+
+/// The paths from a function's callers up to the entry points
+#[derive(Debug, Serialize)]
+pub struct CallPaths {
+    /// The function the paths lead to
+    pub function: CallChainEntry,
+    /// Each path starts at a direct caller and ends at an entry point, or at
+    /// a function nothing calls. Paths reaching an entry point come first.
+    pub paths: Vec<Vec<CallChainEntry>>,
+    /// Whether the depth or path limit cut the search short
+    pub truncated: bool,
+}
+
+pub const DEFAULT_MAX_DEPTH: usize = 10;
+pub const DEFAULT_MAX_PATHS: usize = 50;
+
+/// State of one search: the limits and the callers found so far
+struct Search {
+    max_depth: usize,
+    max_paths: usize,
+    callers: HashMap<(String, String), Vec<CallChainEntry>>,
+    paths: Vec<Vec<CallChainEntry>>,
+    truncated: bool,
+}
+
+impl Workspace {
+    /// The paths from the entry points to the function at `position`
+    pub fn call_paths(
+        &self,
+        uri: &Url,
+        position: Position,
+        max_depth: usize,
+        max_paths: usize,
+    ) -> Option<CallPaths> {
+        let (name, module_name) = self.find_enclosing_function(uri, position)?;
+        let function = self.chain_entry(&module_name, &name)?;
+        let mut search = Search {
+            max_depth,
+            max_paths,
+            callers: HashMap::new(),
+            paths: Vec::new(),
+            truncated: false,
+        };
+        if !function.is_entry_point {
+            self.follow_callers(&function, &mut vec![function.clone()], &mut search);
+        }
+        search
+            .paths
+            .sort_by_key(|path| !path.last().is_some_and(|entry| entry.is_entry_point));
+        Some(CallPaths {
+            function,
+            paths: search.paths,
+            truncated: search.truncated,
+        })
+    }
+
+    /// One path from the function at `position` to an entry point, if there
+    /// is one, starting with that function
+    pub fn call_chain(&self, uri: &Url, position: Position) -> Vec<CallChainEntry> {
+        let paths = self.call_paths(uri, position, DEFAULT_MAX_DEPTH, DEFAULT_MAX_PATHS);
+        let Some(paths) = paths else {
+            return Vec::new();
+        };
+        let mut chain = vec![paths.function];
+        chain.extend(paths.paths.into_iter().next().unwrap_or_default());
+        chain
+    }
+
+    /// Extend `path`, which ends at `function`, with each of its callers
+    fn follow_callers(
+        &self,
+        function: &CallChainEntry,
+        path: &mut Vec<CallChainEntry>,
+        search: &mut Search,
+    ) {
+        if search.paths.len() >= search.max_paths {
+            search.truncated = true;
+            return;
+        }
+        let callers: Vec<CallChainEntry> = self
+            .callers(function, search)
+            .into_iter()
+            .filter(|caller| {
+                !path
+                    .iter()
+                    .any(|p| p.module_name == caller.module_name && p.function == caller.function)
+            })
+            .collect();
+        let reached_end = callers.is_empty() || function.is_entry_point;
+        if reached_end || path.len() > search.max_depth {
+            search.truncated |= !reached_end;
+            if path.len() > 1 {
+                search.paths.push(path[1..].to_vec());
+            }
+            return;
+        }
+        for caller in callers {
+            path.push(caller.clone());
+            self.follow_callers(&caller, path, search);
+            path.pop();
+        }
+    }
+
+    /// The distinct top-level functions that refer to `function`
+    fn callers(&self, function: &CallChainEntry, search: &mut Search) -> Vec<CallChainEntry> {
+        let key = (function.module_name.clone(), function.function.clone());
+        if let Some(callers) = search.callers.get(&key) {
+            return callers.clone();
+        }
+
+        let mut callers: Vec<CallChainEntry> = Vec::new();
+        for reference in self.find_references(&function.function, Some(&function.module_name)) {
+            if reference.is_definition || reference.kind != Some(BoundSymbolKind::Function) {
+                continue;
+            }
+            let Some((name, module_name)) =
+                self.find_enclosing_function(&reference.uri, reference.range.start)
+            else {
+                continue;
+            };
+            let same = |e: &CallChainEntry| e.module_name == module_name && e.function == name;
+            if same(function) || callers.iter().any(same) || !self.sees(&module_name, function) {
+                continue;
+            }
+            callers.extend(self.chain_entry(&module_name, &name));
+        }
+        callers.sort_by(|a, b| (&a.module_name, a.line).cmp(&(&b.module_name, b.line)));
+        search.callers.insert(key, callers.clone());
+        callers
+    }
+
+    /// Whether code in `module_name` can refer to `function` at all: it is
+    /// declared there, or its module is imported
+    fn sees(&self, module_name: &str, function: &CallChainEntry) -> bool {
+        module_name == function.module_name
+            || self.modules.get(module_name).is_some_and(|module| {
+                module
+                    .imports
+                    .iter()
+                    .any(|import| import.module_name == function.module_name)
+            })
+    }
+
+    fn chain_entry(&self, module_name: &str, name: &str) -> Option<CallChainEntry> {
+        let module = self.modules.get(module_name)?;
+        let symbol = module
+            .symbols
+            .iter()
+            .find(|s| s.name == name && s.kind == SymbolKind::FUNCTION)?;
+        let is_entry_point =
+            name == "main" || (name == "app" && matches!(module_name, "Frontend" | "Backend"));
+        Some(CallChainEntry {
+            function: name.to_string(),
+            file: module.path.to_string_lossy().to_string(),
+            module_name: module_name.to_string(),
+            line: symbol.range.start.line,
+            is_entry_point,
+        })
+    }
+}
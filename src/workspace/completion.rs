@@ -0,0 +1,1205 @@
+//! Context-specific completions.
+//!
+//! Most completions offer every symbol in scope. Some positions only accept a
+//! few kinds of names, and these are recognized here from the text around the
+//! cursor, which is often incomplete and does not parse cleanly.
+//!
+//! In a `case` branch pattern, only constructors and `_` can appear. When the
+//! scrutinee's type is known, its constructors are offered; otherwise, and in
+//! nested patterns, every constructor in scope is.
+//!
+//! In a record update `{ model | `, only the fields of `model`'s type alias
+//! that the update does not set yet are offered.
+//!
+//! In `import Dict exposing (`, the values and types the imported module
+//! exposes are offered, with `Type(..)` for custom types whose constructors
+//! it exposes. In the module header's `exposing (`, the file's own
+//! declarations that are not exposed yet are.
+//!
+//! In a type, after the `:` of an annotation or the `=` of a type alias or
+//! custom type, the type variables the declaration uses are offered, then
+//! the types in scope and the types of imported modules, qualified. After a
+//! qualifier like `Dict.`, only that module's types are. Record field names
+//! and constructor names being declared get nothing.
+//!
+//! After a qualifier like `Dict.` or an import alias, what that module
+//! exposes is offered; after `model.`, the fields of `model`'s type alias.
+//! Everywhere else, the parameters, pattern variables and let bindings in
+//! scope come before the other symbols.
+//!
+//! A name that would clash with one the file declares or another
+//! import exposes is inserted qualified, `Dict.map` rather than `map`, and
+//! its module imported when it is not yet.
+
+use tower_lsp::lsp_types::*;
+
+use super::{ElmModule, ExposingInfo, ImportSpec, Workspace};
+use crate::binder::{bind_tree, BoundSymbol, BoundSymbolKind};
+use crate::types::Type;
+
+/// Constructors every module can use without an import: `(constructor, type)`
+const DEFAULT_CONSTRUCTORS: &[(&str, &str)] = &[
+    ("Just", "Maybe"),
+    ("Nothing", "Maybe"),
+    ("Ok", "Result"),
+    ("Err", "Result"),
+    ("True", "Bool"),
+    ("False", "Bool"),
+];
+
+/// Types every module can use without an import: `(type, module)`
+const DEFAULT_TYPES: &[(&str, &str)] = &[
+    ("Int", "Basics"),
+    ("Float", "Basics"),
+    ("Bool", "Basics"),
+    ("Order", "Basics"),
+    ("Never", "Basics"),
+    ("String", "String"),
+    ("Char", "Char"),
+    ("List", "List"),
+    ("Maybe", "Maybe"),
+    ("Result", "Result"),
+    ("Program", "Platform"),
+    ("Cmd", "Platform.Cmd"),
+    ("Sub", "Platform.Sub"),
+];
+
+/// A type being written in a declaration's type
+struct TypeSlot {
+    /// The module qualifier typed so far, `Dict` in `Dict.Di`
+    qualifier: Option<String>,
+    /// Type variables the declaration uses elsewhere
+    variables: Vec<String>,
+}
+
+/// A record update the cursor is at a field name in
+struct RecordUpdate {
+    /// The record being updated, `model` in `{ model | count = 1 }`
+    base: String,
+    /// Fields set by the other assignments of the update
+    set_fields: Vec<String>,
+}
+
+/// Name, kind and signature of a declaration
+type SymbolSummary<'a> = (&'a str, SymbolKind, Option<&'a String>);
+
+/// A value or type a module exposes
+struct ModuleExport {
+    name: String,
+    kind: SymbolKind,
+    detail: Option<String>,
+    /// Whether a custom type's constructors are exposed too
+    constructors: bool,
+}
+
+/// The exposing list the cursor is in, of an import or the module header
+struct ExposingList {
+    /// The imported module, or the file's own module
+    module_name: String,
+    /// Whether this is the module header's list
+    header: bool,
+    /// Items already in the list
+    listed: Vec<String>,
+}
+
+/// The `case` a branch pattern being typed belongs to
+struct CasePattern {
+    /// Line and column of the `case` keyword
+    case_position: Position,
+    /// The cursor is inside a constructor's arguments, not at the top of the pattern
+    nested: bool,
+}
+
+impl Workspace {
+    /// Completions for a `case` branch pattern at `position`, or None when the
+    /// cursor is not in one
+    pub fn pattern_completions(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> Option<Vec<CompletionItem>> {
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let pattern = case_pattern_at(source, position)?;
+        let module = self.find_module_by_path(&uri.to_file_path().ok()?)?;
+
+        let scrutinee = (!pattern.nested)
+            .then(|| self.scrutinee_type(uri, pattern.case_position))
+            .flatten()
+            .map(|(qualifier, name)| (self.type_module(module, &qualifier, &name), name));
+        let constructors = match &scrutinee {
+            Some((type_module, type_name)) => self
+                .constructors_of(type_module, type_name)
+                .into_iter()
+                .filter_map(|name| {
+                    let label = self.constructor_label(module, type_module, type_name, &name)?;
+                    Some((label, type_name.clone()))
+                })
+                .collect(),
+            None => self.constructors_in_scope(module),
+        };
+
+        let mut items: Vec<CompletionItem> = constructors
+            .into_iter()
+            .map(|(label, type_name)| CompletionItem {
+                label,
+                kind: Some(CompletionItemKind::ENUM_MEMBER),
+                detail: Some(type_name),
+                ..Default::default()
+            })
+            .collect();
+        items.push(CompletionItem {
+            label: "_".to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some("Matches anything".to_string()),
+            ..Default::default()
+        });
+        Some(items)
+    }
+
+    /// Completions for a field name in a record update at `position`, or None
+    /// when the cursor is not at one or the record's type alias is unknown
+    pub fn record_update_completions(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> Option<Vec<CompletionItem>> {
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let offset = position_offset(source, position)?;
+        let update = record_update_at(source, offset)?;
+        let (alias, fields) = self.base_record_fields(uri, offset, &update.base)?;
+
+        let items = fields
+            .into_iter()
+            .filter(|field| !update.set_fields.contains(field))
+            .map(|field| CompletionItem {
+                label: field,
+                kind: Some(CompletionItemKind::FIELD),
+                detail: Some(alias.name.clone()),
+                ..Default::default()
+            })
+            .collect();
+        Some(items)
+    }
+
+    /// The type alias of the variable `base` at `offset`, and its fields. The
+    /// variable is a parameter or other pattern of the enclosing declaration;
+    /// when inference has no type for it (the update being typed often does
+    /// not parse), the parameter's type is read from the annotation.
+    pub(super) fn base_record_fields(
+        &self,
+        uri: &Url,
+        offset: usize,
+        base: &str,
+    ) -> Option<(crate::type_checker::TargetTypeAlias, Vec<String>)> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        // The cursor often sits after the end of the (unfinished) declaration
+        let anchor = source[..offset].trim_end().len().saturating_sub(1);
+        let node = tree.root_node().descendant_for_byte_range(anchor, anchor)?;
+        let declaration = std::iter::successors(Some(node), |n| n.parent()).find(|n| {
+            n.named_children(&mut n.walk())
+                .any(|c| c.kind() == "function_declaration_left")
+        })?;
+
+        let mut patterns = Vec::new();
+        let mut stack = vec![declaration];
+        while let Some(n) = stack.pop() {
+            if n.kind() == "lower_pattern" && &source[n.byte_range()] == base {
+                patterns.push(n);
+            }
+            stack.extend(n.named_children(&mut n.walk()));
+        }
+        let inferred = patterns
+            .iter()
+            .filter_map(|p| self.type_checker.get_type(uri.as_str(), p.id()))
+            .find_map(|ty| self.type_checker.record_alias_fields(&ty));
+        if inferred.is_some() {
+            return inferred;
+        }
+
+        // `update : Msg -> Model -> Model` gives `model` in `update msg model` its type
+        let left = declaration
+            .named_children(&mut declaration.walk())
+            .find(|c| c.kind() == "function_declaration_left")?;
+        let name_node = left.named_child(0)?;
+        let index = left
+            .children_by_field_name("pattern", &mut left.walk())
+            .position(|p| &source[p.byte_range()] == base)?;
+        let annotation = declaration
+            .prev_named_sibling()
+            .filter(|n| n.kind() == "type_annotation")
+            .filter(|n| {
+                n.child_by_field_name("name")
+                    .is_some_and(|name| source[name.byte_range()] == source[name_node.byte_range()])
+            })?;
+        let type_expression = annotation.child_by_field_name("typeExpression")?;
+        let part = type_expression
+            .children_by_field_name("part", &mut type_expression.walk())
+            .nth(index)?;
+        if part.kind() != "type_ref" || part.named_child_count() != 1 {
+            return None;
+        }
+        let written = &source[part.byte_range()];
+        let (qualifier, type_name) = written.rsplit_once('.').unwrap_or(("", written));
+        let module = self.find_module_by_path(&uri.to_file_path().ok()?)?;
+        let type_module = module
+            .imports
+            .iter()
+            .find(|import| import.alias.as_deref() == Some(qualifier))
+            .map_or(qualifier, |import| import.module_name.as_str());
+        self.type_checker
+            .record_alias_fields(&Type::union(type_module, type_name, Vec::new()))
+    }
+
+    /// Completions for the exposing list of an import at `position`: what the
+    /// imported module exposes, minus what the list already has
+    pub fn import_exposing_completions(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> Option<Vec<CompletionItem>> {
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let import = exposing_list_at(source, position_offset(source, position)?)
+            .filter(|list| !list.header)?;
+
+        let mut items = Vec::new();
+        let mut push = |label: String, kind: CompletionItemKind, detail: Option<String>| {
+            if !import.listed.contains(&label) {
+                items.push(CompletionItem {
+                    label,
+                    kind: Some(kind),
+                    detail,
+                    ..Default::default()
+                });
+            }
+        };
+        for export in self.module_exports(&import.module_name)? {
+            let completion_kind = match export.kind {
+                SymbolKind::ENUM => CompletionItemKind::ENUM,
+                SymbolKind::STRUCT => CompletionItemKind::STRUCT,
+                SymbolKind::INTERFACE => CompletionItemKind::INTERFACE,
+                _ => CompletionItemKind::FUNCTION,
+            };
+            if export.constructors {
+                let label = format!("{}(..)", export.name);
+                push(label, completion_kind, export.detail.clone());
+            }
+            push(export.name, completion_kind, export.detail);
+        }
+        for operator in self.operators.values().flatten() {
+            if operator.module_name == import.module_name {
+                let detail = operator.signature.clone();
+                push(
+                    format!("({})", operator.operator),
+                    CompletionItemKind::OPERATOR,
+                    detail,
+                );
+            }
+        }
+        Some(items)
+    }
+
+    /// Completions for the module header's exposing list at `position`: the
+    /// file's declarations that it does not expose yet
+    pub fn module_exposing_completions(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> Option<Vec<CompletionItem>> {
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let header = exposing_list_at(source, position_offset(source, position)?)
+            .filter(|list| list.header)?;
+        if header.listed.iter().any(|item| item == "..") {
+            return Some(Vec::new());
+        }
+        let module = self.find_module_by_path(&uri.to_file_path().ok()?)?;
+
+        let listed = |name: &str| {
+            header
+                .listed
+                .iter()
+                .any(|item| item.trim_end_matches("(..)").trim_end() == name)
+        };
+        let mut items = Vec::new();
+        for symbol in module.symbols.iter().filter(|s| !listed(&s.name)) {
+            let kind = match symbol.kind {
+                SymbolKind::ENUM => CompletionItemKind::ENUM,
+                SymbolKind::STRUCT => CompletionItemKind::STRUCT,
+                SymbolKind::INTERFACE => CompletionItemKind::INTERFACE,
+                _ => CompletionItemKind::FUNCTION,
+            };
+            // Types keep their full text as the signature; show only the first line
+            let detail = match symbol.kind {
+                SymbolKind::FUNCTION | SymbolKind::INTERFACE => symbol.signature.clone(),
+                _ => symbol
+                    .signature
+                    .as_deref()
+                    .and_then(|s| s.lines().next())
+                    .map(str::to_string),
+            };
+            if symbol.kind == SymbolKind::ENUM {
+                items.push(CompletionItem {
+                    label: format!("{}(..)", symbol.name),
+                    kind: Some(kind),
+                    detail: detail.clone(),
+                    ..Default::default()
+                });
+            }
+            items.push(CompletionItem {
+                label: symbol.name.clone(),
+                kind: Some(kind),
+                detail,
+                ..Default::default()
+            });
+        }
+        Some(items)
+    }
+
+    /// Completions for a type in an annotation, type alias or custom type at
+    /// `position`, or None when the cursor is not at one
+    pub fn type_completions(&self, uri: &Url, position: Position) -> Option<Vec<CompletionItem>> {
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let slot = type_slot_at(source, position_offset(source, position)?)?;
+        let module = self.find_module_by_path(&uri.to_file_path().ok()?)?;
+
+        let mut items: Vec<CompletionItem> = Vec::new();
+        let mut push = |label: String, kind: SymbolKind, detail: Option<String>| {
+            if items.iter().all(|item| item.label != label) {
+                items.push(CompletionItem {
+                    label,
+                    kind: Some(match kind {
+                        SymbolKind::TYPE_PARAMETER => CompletionItemKind::TYPE_PARAMETER,
+                        SymbolKind::STRUCT => CompletionItemKind::STRUCT,
+                        _ => CompletionItemKind::ENUM,
+                    }),
+                    detail,
+                    ..Default::default()
+                });
+            }
+        };
+        let types = |module_name: &str| {
+            self.module_exports(module_name)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|export| matches!(export.kind, SymbolKind::ENUM | SymbolKind::STRUCT))
+        };
+
+        if let Some(qualifier) = &slot.qualifier {
+            let module_name = module
+                .imports
+                .iter()
+                .find(|import| import.alias.as_ref().unwrap_or(&import.module_name) == qualifier)
+                .map_or(qualifier.as_str(), |import| import.module_name.as_str());
+            for export in types(module_name) {
+                push(export.name, export.kind, export.detail);
+            }
+            return Some(items);
+        }
+
+        for variable in slot.variables {
+            push(variable, SymbolKind::TYPE_PARAMETER, None);
+        }
+        for symbol in &module.symbols {
+            if matches!(symbol.kind, SymbolKind::ENUM | SymbolKind::STRUCT) {
+                // Types keep their full text as the signature; show only the first line
+                let detail = symbol
+                    .signature
+                    .as_deref()
+                    .and_then(|s| s.lines().next())
+                    .map(str::to_string);
+                push(symbol.name.clone(), symbol.kind, detail);
+            }
+        }
+        for (name, module_name) in DEFAULT_TYPES {
+            push(
+                name.to_string(),
+                SymbolKind::ENUM,
+                Some(module_name.to_string()),
+            );
+        }
+        for import in &module.imports {
+            let qualifier = import.alias.as_ref().unwrap_or(&import.module_name);
+            for export in types(&import.module_name) {
+                let exposed = match &import.exposing {
+                    ExposingInfo::All => true,
+                    ExposingInfo::Explicit(listed) => listed
+                        .iter()
+                        .any(|i| i.trim_end_matches("(..)").trim_end() == export.name),
+                };
+                if exposed {
+                    push(export.name.clone(), export.kind, export.detail.clone());
+                }
+                let label = format!("{}.{}", qualifier, export.name);
+                push(label, export.kind, export.detail);
+            }
+        }
+        Some(items)
+    }
+
+    /// Completions after a qualifier, `Dict.` or an import alias like `D.`:
+    /// the values, types and exposed constructors of the module it names.
+    /// None when the cursor is not after a qualifier of an import.
+    pub fn qualified_value_completions(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> Option<Vec<CompletionItem>> {
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let (qualifier, _) = dotted_word_at(source, position_offset(source, position)?)?;
+        if !qualifier.starts_with(char::is_uppercase) {
+            return None;
+        }
+        let module = self.find_module_by_path(&uri.to_file_path().ok()?)?;
+        let import = module
+            .imports
+            .iter()
+            .find(|import| import.alias.as_ref().unwrap_or(&import.module_name) == qualifier)?;
+
+        let mut items = Vec::new();
+        for export in self.module_exports(&import.module_name)? {
+            if export.constructors {
+                for constructor in self.constructors_of(&import.module_name, &export.name) {
+                    items.push(CompletionItem {
+                        label: constructor,
+                        kind: Some(CompletionItemKind::ENUM_MEMBER),
+                        detail: Some(export.name.clone()),
+                        ..Default::default()
+                    });
+                }
+            }
+            items.push(CompletionItem {
+                label: export.name,
+                kind: Some(match export.kind {
+                    SymbolKind::ENUM => CompletionItemKind::ENUM,
+                    SymbolKind::STRUCT => CompletionItemKind::STRUCT,
+                    _ => CompletionItemKind::FUNCTION,
+                }),
+                detail: export.detail,
+                ..Default::default()
+            });
+        }
+        Some(items)
+    }
+
+    /// Completions after `record.`: the fields of the type alias of the
+    /// variable `record`. None when the cursor is not after a variable and
+    /// a dot, or its type is unknown.
+    pub fn field_access_completions(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> Option<Vec<CompletionItem>> {
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let offset = position_offset(source, position)?;
+        let (base, _) = dotted_word_at(source, offset)?;
+        if !base.starts_with(char::is_lowercase) || base.contains('.') {
+            return None;
+        }
+        let (alias, fields) = self.base_record_fields(uri, offset, base)?;
+        let items = fields
+            .into_iter()
+            .map(|field| CompletionItem {
+                label: field,
+                kind: Some(CompletionItemKind::FIELD),
+                detail: Some(alias.name.clone()),
+                ..Default::default()
+            })
+            .collect();
+        Some(items)
+    }
+
+    /// The parameters, pattern variables and let bindings in scope at
+    /// `position`, innermost first, with their inferred type when known
+    pub fn local_completions(&self, uri: &Url, position: Position) -> Vec<CompletionItem> {
+        let (Some(tree), Some(source)) = (
+            self.type_checker.get_tree(uri.as_str()),
+            self.type_checker.get_source(uri.as_str()),
+        ) else {
+            return Vec::new();
+        };
+        let Some(offset) = position_offset(source, position) else {
+            return Vec::new();
+        };
+        // The cursor often sits after the end of the (unfinished) expression
+        let anchor = source[..offset].trim_end().len().saturating_sub(1);
+        let Some(node) = tree.root_node().descendant_for_byte_range(anchor, anchor) else {
+            return Vec::new();
+        };
+
+        let links = bind_tree(source, tree);
+        let mut items: Vec<CompletionItem> = Vec::new();
+        // The file's own container holds the top-level declarations, which
+        // the document symbols offer already
+        for scope in
+            std::iter::successors(Some(node), |n| n.parent()).filter(|n| n.kind() != "file")
+        {
+            let Some(symbols) = links.get_container(scope.id()) else {
+                continue;
+            };
+            let mut bound: Vec<&BoundSymbol> = symbols
+                .values()
+                .flatten()
+                .filter(|symbol| {
+                    matches!(
+                        symbol.kind,
+                        BoundSymbolKind::Function
+                            | BoundSymbolKind::FunctionParameter
+                            | BoundSymbolKind::CasePattern
+                            | BoundSymbolKind::AnonymousFunctionParameter
+                    )
+                })
+                .collect();
+            bound.sort_by(|a, b| a.name.cmp(&b.name));
+            for symbol in bound {
+                if items.iter().any(|item| item.label == symbol.name) {
+                    continue;
+                }
+                let detail = self
+                    .type_checker
+                    .get_type(uri.as_str(), symbol.node_id)
+                    .map(|ty| ty.to_string());
+                items.push(CompletionItem {
+                    label: symbol.name.clone(),
+                    kind: Some(CompletionItemKind::VARIABLE),
+                    detail,
+                    ..Default::default()
+                });
+            }
+        }
+        items
+    }
+
+    /// How accepting `name` of `module_name` inserts it in the module at
+    /// `uri` without making it ambiguous: qualified, by the alias or name
+    /// of its import, when the file declares a `name` or another import
+    /// exposes one, with an edit adding the import when there is none.
+    /// None when the plain name is fine.
+    pub fn qualified_completion(
+        &self,
+        uri: &Url,
+        module_name: &str,
+        name: &str,
+    ) -> Option<(String, Vec<TextEdit>)> {
+        let module = self.find_module_by_path(&uri.to_file_path().ok()?)?;
+        if module.module_name == module_name {
+            return None;
+        }
+        let declared_here = module
+            .symbols
+            .iter()
+            .any(|s| s.name == name || s.variants.iter().any(|v| v.name == name));
+        let exposed_elsewhere = module.imports.iter().any(|import| {
+            import.module_name != module_name
+                && match &import.exposing {
+                    ExposingInfo::All => self.module_defines(&import.module_name, name),
+                    ExposingInfo::Explicit(items) => items.iter().any(|item| {
+                        item == name
+                            || item.strip_suffix("(..)").is_some_and(|type_name| {
+                                self.exposes_variant(import, type_name, name)
+                            })
+                    }),
+                }
+        });
+        // Every module imports `Basics exposing (..)` without saying so
+        let in_basics = module_name != "Basics" && self.module_defines("Basics", name);
+        if !declared_here && !exposed_elsewhere && !in_basics {
+            return None;
+        }
+
+        match module.imports.iter().find(|i| i.module_name == module_name) {
+            Some(import) => {
+                let qualifier = import.alias.as_deref().unwrap_or(module_name);
+                Some((format!("{}.{}", qualifier, name), Vec::new()))
+            }
+            None => Some((
+                format!("{}.{}", module_name, name),
+                self.import_edits(uri, &[ImportSpec::module(module_name)]),
+            )),
+        }
+    }
+
+    /// What a workspace or package module exposes
+    fn module_exports(&self, module_name: &str) -> Option<Vec<ModuleExport>> {
+        let (symbols, exposing): (Vec<SymbolSummary>, &ExposingInfo) =
+            match self.modules.get(module_name) {
+                Some(module) => (
+                    module
+                        .symbols
+                        .iter()
+                        .map(|s| (s.name.as_str(), s.kind, s.signature.as_ref()))
+                        .collect(),
+                    &module.exposing,
+                ),
+                None => {
+                    let module = self.external_modules.get(module_name)?;
+                    let symbols = self
+                        .external_symbols
+                        .iter()
+                        .flat_map(|(key, symbols)| symbols.iter().map(move |s| (key, s)))
+                        .filter(|(key, s)| {
+                            s.module_name == module_name
+                                && **key == format!("{}.{}", module_name, s.name)
+                        })
+                        .map(|(_, s)| (s.name.as_str(), s.kind, s.signature.as_ref()))
+                        .collect();
+                    (symbols, &module.exposing)
+                }
+            };
+
+        let exports = symbols
+            .into_iter()
+            .filter_map(|(name, kind, signature)| {
+                let (exposed, constructors) = match exposing {
+                    ExposingInfo::All => (true, true),
+                    ExposingInfo::Explicit(items) => {
+                        let open = items.iter().any(|i| *i == format!("{}(..)", name));
+                        (open || items.iter().any(|i| i == name), open)
+                    }
+                };
+                // Declarations keep their full text as the signature; show only the first line
+                let detail = match kind {
+                    SymbolKind::FUNCTION | SymbolKind::INTERFACE => signature.cloned(),
+                    _ => signature.and_then(|s| s.lines().next()).map(str::to_string),
+                };
+                exposed.then(|| ModuleExport {
+                    name: name.to_string(),
+                    kind,
+                    detail,
+                    constructors: constructors && kind == SymbolKind::ENUM,
+                })
+            })
+            .collect();
+        Some(exports)
+    }
+
+    /// Module and name of the union type matched by the `case` at `position`
+    fn scrutinee_type(&self, uri: &Url, position: Position) -> Option<(String, String)> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+        let node = tree.root_node().descendant_for_point_range(point, point)?;
+        let case = std::iter::successors(Some(node), |n| n.parent())
+            .find(|n| n.kind() == "case_of_expr")?;
+        let expr = case.child_by_field_name("expr")?;
+        match self.type_checker.get_type(uri.as_str(), expr.id())? {
+            Type::Union(union) => Some((union.module, union.name)),
+            _ => None,
+        }
+    }
+
+    /// The module declaring the type `name`, written with `qualifier` (empty,
+    /// an alias or a module name) in `module`
+    fn type_module(&self, module: &ElmModule, qualifier: &str, name: &str) -> String {
+        if !qualifier.is_empty() {
+            return module
+                .imports
+                .iter()
+                .find(|import| import.alias.as_deref() == Some(qualifier))
+                .map_or(qualifier.to_string(), |import| import.module_name.clone());
+        }
+        let declares = |m: &ElmModule| {
+            m.symbols
+                .iter()
+                .any(|s| s.kind == SymbolKind::ENUM && s.name == name)
+        };
+        if declares(module) {
+            return module.module_name.clone();
+        }
+        module
+            .imports
+            .iter()
+            .find(|import| {
+                let exposed = match &import.exposing {
+                    ExposingInfo::All => true,
+                    ExposingInfo::Explicit(items) => {
+                        items.iter().any(|i| i.trim_end_matches("(..)") == name)
+                    }
+                };
+                exposed && self.modules.get(&import.module_name).is_some_and(declares)
+            })
+            .map_or(name.to_string(), |import| import.module_name.clone())
+    }
+
+    /// Constructor names of a custom type, from the workspace or a package
+    fn constructors_of(&self, module_name: &str, type_name: &str) -> Vec<String> {
+        let is_type = |kind: SymbolKind, name: &str| kind == SymbolKind::ENUM && name == type_name;
+        if let Some(module) = self.modules.get(module_name) {
+            return module
+                .symbols
+                .iter()
+                .find(|s| is_type(s.kind, &s.name))
+                .map(|s| s.variants.iter().map(|v| v.name.clone()).collect())
+                .unwrap_or_default();
+        }
+
+        // Package types keep their declaration text as the signature
+        let declaration = self
+            .external_symbols
+            .get(&format!("{}.{}", module_name, type_name))
+            .and_then(|symbols| symbols.iter().find(|s| is_type(s.kind, &s.name)))
+            .and_then(|s| s.signature.as_deref());
+        let from_package = declaration
+            .and_then(|text| {
+                let tree = self.parser.parse(text)?;
+                let symbols = self.parser.extract_symbols(&tree, text);
+                let symbol = symbols.into_iter().find(|s| is_type(s.kind, &s.name))?;
+                Some(
+                    symbol
+                        .variants
+                        .into_iter()
+                        .map(|v| v.name)
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .unwrap_or_default();
+        if !from_package.is_empty() {
+            return from_package;
+        }
+        DEFAULT_CONSTRUCTORS
+            .iter()
+            .filter(|(_, owner)| *owner == type_name)
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    /// How `module` refers to a constructor of `type_module`, or None if it
+    /// cannot (the module is not imported)
+    fn constructor_label(
+        &self,
+        module: &ElmModule,
+        type_module: &str,
+        type_name: &str,
+        name: &str,
+    ) -> Option<String> {
+        if type_module == module.module_name
+            || DEFAULT_CONSTRUCTORS
+                .iter()
+                .any(|(n, t)| *n == name && *t == type_name)
+        {
+            return Some(name.to_string());
+        }
+        let import = module
+            .imports
+            .iter()
+            .find(|import| import.module_name == type_module)?;
+        let exposed = match &import.exposing {
+            ExposingInfo::All => true,
+            ExposingInfo::Explicit(items) => {
+                items.iter().any(|i| *i == format!("{}(..)", type_name))
+            }
+        };
+        if exposed {
+            Some(name.to_string())
+        } else {
+            let qualifier = import.alias.as_deref().unwrap_or(&import.module_name);
+            Some(format!("{}.{}", qualifier, name))
+        }
+    }
+
+    /// Every constructor `module` can use: its own, imported ones and the defaults
+    fn constructors_in_scope(&self, module: &ElmModule) -> Vec<(String, String)> {
+        let mut constructors: Vec<(String, String)> = DEFAULT_CONSTRUCTORS
+            .iter()
+            .map(|(name, owner)| (name.to_string(), owner.to_string()))
+            .collect();
+        let modules = std::iter::once(module).chain(
+            module
+                .imports
+                .iter()
+                .filter_map(|import| self.modules.get(&import.module_name)),
+        );
+        for source in modules {
+            for symbol in source.symbols.iter().filter(|s| s.kind == SymbolKind::ENUM) {
+                for variant in &symbol.variants {
+                    if let Some(label) = self.constructor_label(
+                        module,
+                        &source.module_name,
+                        &symbol.name,
+                        &variant.name,
+                    ) {
+                        constructors.push((label, symbol.name.clone()));
+                    }
+                }
+            }
+        }
+        constructors
+    }
+}
+
+/// Recognize a `case` branch pattern at `position` from the surrounding lines:
+/// the text before the cursor has no `->` yet, and going up, the lines at the
+/// same indentation are earlier branches until a less indented `case ... of`
+fn case_pattern_at(source: &str, position: Position) -> Option<CasePattern> {
+    let lines: Vec<&str> = source.lines().collect();
+    let line = lines.get(position.line as usize)?;
+    let prefix = line.get(..position.character as usize)?;
+    if prefix.contains("->") || prefix.contains('=') {
+        return None;
+    }
+    let indent = |text: &str| text.len() - text.trim_start().len();
+    let current = indent(line);
+    if current == 0 {
+        return None;
+    }
+
+    for (row, above) in lines[..position.line as usize].iter().enumerate().rev() {
+        if above.trim().is_empty() {
+            continue;
+        }
+        let depth = indent(above);
+        if depth > current {
+            continue;
+        }
+        if depth == current {
+            if above.contains("->") {
+                continue;
+            }
+            return None;
+        }
+        let trimmed = above.trim();
+        let column = above.find("case ")?;
+        if !trimmed.ends_with(" of") {
+            return None;
+        }
+        let typed = prefix.trim_start();
+        return Some(CasePattern {
+            case_position: Position::new(row as u32, column as u32),
+            nested: typed.contains(' ') || typed.contains('('),
+        });
+    }
+    None
+}
+
+/// Recognize the cursor at a type: in an annotation (a let's included) or a
+/// port after its `:`, or in a type alias or custom type after its `=`. The
+/// declaration starts on the cursor's line or the closest less indented line
+/// above, going out until a top-level line. Record field names and the names
+/// of the constructors a custom type declares are not types.
+fn type_slot_at(source: &str, offset: usize) -> Option<TypeSlot> {
+    let before = source.get(..offset)?;
+    let word_start = before
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+        .map_or(0, |i| i + 1);
+    let word = &before[word_start..];
+    let qualifier = match word.rsplit_once('.') {
+        Some((qualifier, _)) if qualifier.starts_with(|c: char| c.is_uppercase()) => {
+            Some(qualifier.to_string())
+        }
+        Some(_) => return None,
+        None => None,
+    };
+
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let current = indent(&source[line_start..]);
+    let mut header = None;
+    if is_declaration_start(before[line_start..].trim_start()) {
+        header = Some(line_start);
+    } else {
+        let mut outer = current;
+        let mut end = line_start;
+        while end > 0 && outer > 0 {
+            let start = source[..end - 1].rfind('\n').map_or(0, |i| i + 1);
+            let line = &source[start..end - 1];
+            end = start;
+            if line.trim().is_empty() || indent(line) >= outer {
+                continue;
+            }
+            if is_declaration_start(line.trim_start()) {
+                header = Some(start);
+                break;
+            }
+            outer = indent(line);
+        }
+    }
+    let header = header?;
+    let header_indent = indent(&source[header..]);
+    let declaration = source[header..].trim_start();
+    let declaration_start = source.len() - declaration.len();
+    let is_type_declaration = declaration.starts_with("type ");
+    // Where the type starts
+    let body = declaration_start
+        + if is_type_declaration {
+            declaration.find('=')?
+        } else {
+            declaration.find(':')?
+        };
+    if body >= word_start {
+        return None;
+    }
+
+    // The innermost bracket open around the cursor, and the separator
+    // closest to the cursor at its level
+    let mut depth = 0;
+    let mut open = None;
+    let mut separator = None;
+    for c in source[body + 1..word_start].chars().rev() {
+        match c {
+            ')' | ']' | '}' => depth += 1,
+            '(' | '[' | '{' if depth > 0 => depth -= 1,
+            '(' | '[' | '{' => {
+                open = Some(c);
+                break;
+            }
+            ',' | ':' | '|' if depth == 0 => {
+                separator.get_or_insert(c);
+            }
+            _ => {}
+        }
+    }
+    let previous = source[..word_start].trim_end().chars().last();
+    match open {
+        Some('{') if separator != Some(':') => return None,
+        None if is_type_declaration && matches!(previous, Some('=' | '|')) => return None,
+        _ => {}
+    }
+
+    // The whole declaration: up to the next line that is not more indented
+    let mut end = source.len();
+    let mut at = declaration_start;
+    while let Some(newline) = source[at..].find('\n') {
+        let next = at + newline + 1;
+        let line = source[next..].lines().next().unwrap_or("");
+        if !line.trim().is_empty() && indent(line) <= header_indent {
+            end = next;
+            break;
+        }
+        at = next;
+    }
+    let variables = if is_type_declaration {
+        // The parameters of `type alias Pair a b =`
+        declaration[..body - declaration_start]
+            .split_whitespace()
+            .skip_while(|w| matches!(*w, "type" | "alias"))
+            .skip(1)
+            .map(str::to_string)
+            .collect()
+    } else {
+        type_variables(source, body + 1, end, word_start)
+    };
+    Some(TypeSlot {
+        qualifier,
+        variables,
+    })
+}
+
+/// Whether `line` starts an annotation, a port or a type declaration
+fn is_declaration_start(line: &str) -> bool {
+    if line.starts_with("type ") {
+        return true;
+    }
+    let line = line.strip_prefix("port ").unwrap_or(line);
+    let name_end = line
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(line.len());
+    line.starts_with(|c: char| c.is_lowercase()) && line[name_end..].trim_start().starts_with(':')
+}
+
+/// The type variables in `source[start..end]`, apart from the word at
+/// `skip`: lower case names that are not record fields
+fn type_variables(source: &str, start: usize, end: usize, skip: usize) -> Vec<String> {
+    let text = &source[start..end];
+    let mut variables: Vec<String> = Vec::new();
+    let mut rest = text;
+    while let Some(word_at) = rest.find(|c: char| c.is_alphanumeric() || c == '_') {
+        let word_end = rest[word_at..]
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map_or(rest.len(), |i| word_at + i);
+        let word = &rest[word_at..word_end];
+        let position = start + (text.len() - rest.len()) + word_at;
+        let qualified = rest[..word_at].ends_with('.');
+        let field = rest[word_end..].trim_start().starts_with(':');
+        if word.starts_with(|c: char| c.is_lowercase())
+            && !qualified
+            && !field
+            && position != skip
+            && !variables.iter().any(|v| v == word)
+        {
+            variables.push(word.to_string());
+        }
+        rest = &rest[word_end..];
+    }
+    variables
+}
+
+/// The word being typed at `offset` after a dot, split at its last dot:
+/// `("Json.Decode", "fi")` for `Json.Decode.fi`, `("model", "")` for
+/// `model.`
+fn dotted_word_at(source: &str, offset: usize) -> Option<(&str, &str)> {
+    let before = source.get(..offset)?;
+    let start = before
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+        .map_or(0, |i| i + 1);
+    let (qualifier, partial) = before[start..].rsplit_once('.')?;
+    (!qualifier.is_empty() && !qualifier.ends_with('.')).then_some((qualifier, partial))
+}
+
+/// Byte offset of `position` in `source`
+fn position_offset(source: &str, position: Position) -> Option<usize> {
+    let line_start: usize = source
+        .split_inclusive('\n')
+        .take(position.line as usize)
+        .map(str::len)
+        .sum();
+    let offset = line_start + position.character as usize;
+    (offset <= source.len()).then_some(offset)
+}
+
+/// Recognize the cursor at a field name of a record update `{ base | .. }`:
+/// the innermost open brace has a `|` after the base, and the text since the
+/// last `|` or `,` is at most a partial field name
+fn record_update_at(source: &str, offset: usize) -> Option<RecordUpdate> {
+    let before = source.get(..offset)?;
+    let bytes = source.as_bytes();
+    // `|` on its own, not part of `|>`, `<|` or `||`
+    let is_bar = |i: usize| {
+        let operator = |j: Option<usize>| {
+            j.and_then(|j| bytes.get(j))
+                .is_some_and(|b| b"|<>".contains(b))
+        };
+        !operator(i.checked_sub(1)) && !operator(Some(i + 1))
+    };
+
+    let mut depth = 0;
+    let mut segment_start = None;
+    let mut bar = None;
+    let mut open = None;
+    for (i, c) in before.char_indices().rev() {
+        match c {
+            ')' | ']' | '}' => depth += 1,
+            '(' | '[' | '{' if depth > 0 => depth -= 1,
+            '{' => {
+                open = Some(i);
+                break;
+            }
+            '(' | '[' => return None,
+            ',' if depth == 0 => {
+                segment_start.get_or_insert(i + 1);
+            }
+            '|' if depth == 0 && is_bar(i) => {
+                segment_start.get_or_insert(i + 1);
+                bar = Some(i);
+            }
+            _ => {}
+        }
+    }
+    let (open, bar) = (open?, bar?);
+    let base = source[open + 1..bar].trim();
+    let is_lower_name = |text: &str| {
+        text.chars().next().is_some_and(|c| c.is_lowercase())
+            && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+    };
+    let typed = before[segment_start?..].trim_start();
+    if !is_lower_name(base) || !(typed.is_empty() || is_lower_name(typed)) {
+        return None;
+    }
+
+    // The assignments up to the closing brace, except the one being typed
+    let mut set_fields = Vec::new();
+    let mut depth = 0;
+    let mut start = bar + 1;
+    for (i, c) in source[bar + 1..]
+        .char_indices()
+        .map(|(i, c)| (i + bar + 1, c))
+    {
+        let end = match c {
+            '(' | '[' | '{' => {
+                depth += 1;
+                false
+            }
+            '}' if depth == 0 => true,
+            ')' | ']' | '}' => {
+                depth -= 1;
+                false
+            }
+            ',' => depth == 0,
+            // An unclosed update ends where the next declaration starts
+            '\n' if bytes.get(i + 1).is_some_and(|b| !b.is_ascii_whitespace()) => break,
+            _ => false,
+        };
+        if end {
+            if !(start..=i).contains(&offset) {
+                if let Some((name, _)) = source[start..i].split_once('=') {
+                    set_fields.push(name.trim().to_string());
+                }
+            }
+            if c == '}' {
+                break;
+            }
+            start = i + 1;
+        }
+    }
+
+    Some(RecordUpdate {
+        base: base.to_string(),
+        set_fields,
+    })
+}
+
+/// Recognize the cursor in an exposing list: the innermost open parenthesis
+/// follows `import Name [as Alias] exposing` or `[port] module Name exposing`
+fn exposing_list_at(source: &str, offset: usize) -> Option<ExposingList> {
+    let before = source.get(..offset)?;
+    let mut depth = 0;
+    let mut open = None;
+    for (i, c) in before.char_indices().rev() {
+        match c {
+            ')' => depth += 1,
+            '(' if depth > 0 => depth -= 1,
+            '(' => {
+                open = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let open = open?;
+    // Declarations start at the beginning of a line
+    let statement_start = before[..open]
+        .rmatch_indices('\n')
+        .map(|(i, _)| i + 1)
+        .find(|&i| before[i..].starts_with(|c: char| !c.is_whitespace()))
+        .unwrap_or(0);
+    let words: Vec<&str> = before[statement_start..open].split_whitespace().collect();
+    let (module_name, header) = match words.as_slice() {
+        ["import", name, "exposing"] | ["import", name, "as", _, "exposing"] => (name, false),
+        ["module", name, "exposing"] | ["port", "module", name, "exposing"] => (name, true),
+        _ => return None,
+    };
+
+    // The whole list: up to the closing parenthesis, or the next line that
+    // is not indented when the list is not closed yet
+    let bytes = source.as_bytes();
+    let mut depth = 0;
+    let mut end = source.len();
+    for (i, c) in source[open + 1..]
+        .char_indices()
+        .map(|(i, c)| (i + open + 1, c))
+    {
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            ')' => {
+                end = i;
+                break;
+            }
+            '\n' if bytes.get(i + 1).is_some_and(|b| !b.is_ascii_whitespace()) => {
+                end = i;
+                break;
+            }
+            _ => {}
+        }
+    }
+    let list = &source[open + 1..end];
+    let typed = before[open + 1..].rsplit(',').next().unwrap_or("").trim();
+    let listed = list
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty() && *item != typed)
+        .map(str::to_string)
+        .collect();
+    Some(ExposingList {
+        module_name: module_name.to_string(),
+        header,
+        listed,
+    })
+}
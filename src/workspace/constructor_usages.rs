@@ -0,0 +1,234 @@
+//! How a custom type is used across the workspace, constructor by
+//! constructor.
+//!
+//! Before reshaping a domain type it helps to see where each constructor
+//! builds a value and where it is matched on, and which annotations and
+//! other types mention the type. The usages come from the same constructor
+//! and type references as rename and variant removal, classified by where
+//! they sit in the syntax tree.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use tower_lsp::lsp_types::*;
+use tree_sitter::Tree;
+
+use super::{DefinitionSymbol, ElmModule, ExposingInfo, SymbolReference, Workspace};
+use crate::binder::BoundSymbolKind;
+
+/// The usages of a custom type, for `elm-lsp/constructorUsages`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConstructorUsages {
+    pub type_name: String,
+    pub module_name: String,
+    /// Each constructor, in declaration order
+    pub constructors: Vec<ConstructorUsage>,
+    /// Annotations and type declarations mentioning the type
+    pub signatures: Vec<Location>,
+}
+
+/// Where one constructor is used
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConstructorUsage {
+    pub name: String,
+    /// Expressions building a value with it
+    pub constructions: Vec<Location>,
+    /// Patterns matching it
+    pub patterns: Vec<Location>,
+}
+
+/// Where a reference sits
+#[derive(PartialEq)]
+enum UsageKind {
+    Construction,
+    Pattern,
+    Signature,
+}
+
+impl Workspace {
+    /// The usages of the custom type at `position`: its name or one of its
+    /// constructors, where declared or used
+    pub fn constructor_usages(&self, uri: &Url, position: Position) -> Option<ConstructorUsages> {
+        let (module_name, type_name) = self.custom_type_at(uri, position)?;
+        let module = self.modules.get(&module_name)?;
+        let declaration = module
+            .symbols
+            .iter()
+            .find(|s| s.kind == SymbolKind::ENUM && s.name == type_name)?;
+        let defining_uri = Url::from_file_path(&module.path).ok()?;
+        let mut trees = HashMap::new();
+
+        let constructors = declaration
+            .variants
+            .iter()
+            .map(|variant| {
+                let references = self.find_constructor_references_typed(&DefinitionSymbol {
+                    name: variant.name.clone(),
+                    kind: BoundSymbolKind::UnionConstructor,
+                    uri: defining_uri.clone(),
+                    range: variant.range,
+                    type_context: Some(type_name.clone()),
+                    module_name: Some(module_name.clone()),
+                    scope_range: None,
+                });
+                let mut usage = ConstructorUsage {
+                    name: variant.name.clone(),
+                    constructions: Vec::new(),
+                    patterns: Vec::new(),
+                };
+                for (kind, location) in self.classify_references(references, &mut trees) {
+                    match kind {
+                        UsageKind::Construction => usage.constructions.push(location),
+                        UsageKind::Pattern => usage.patterns.push(location),
+                        UsageKind::Signature => {}
+                    }
+                }
+                usage
+            })
+            .collect();
+
+        let type_references = self
+            .find_type_references_typed(&DefinitionSymbol {
+                name: type_name.clone(),
+                kind: BoundSymbolKind::Type,
+                uri: defining_uri.clone(),
+                range: declaration.range,
+                type_context: None,
+                module_name: Some(module_name.clone()),
+                scope_range: None,
+            })
+            .into_iter()
+            .filter(|r| {
+                r.uri == defining_uri
+                    || self
+                        .get_module_at_uri(&r.uri)
+                        .is_some_and(|m| m.imports.iter().any(|i| i.module_name == module_name))
+            })
+            .collect();
+        let signatures = self
+            .classify_references(type_references, &mut trees)
+            .into_iter()
+            .filter(|(kind, _)| *kind == UsageKind::Signature)
+            .map(|(_, location)| location)
+            .collect();
+
+        Some(ConstructorUsages {
+            type_name,
+            module_name,
+            constructors,
+            signatures,
+        })
+    }
+
+    /// Module and name of the custom type named at `position`, or owning the
+    /// constructor named there, whether declared or used
+    fn custom_type_at(&self, uri: &Url, position: Position) -> Option<(String, String)> {
+        let module = self.get_module_at_uri(uri)?;
+        if let Some(symbol) = self.classify_definition_at_position(uri, position) {
+            match symbol.kind {
+                BoundSymbolKind::Type => return Some((module.module_name.clone(), symbol.name)),
+                BoundSymbolKind::UnionConstructor => {
+                    return Some((module.module_name.clone(), symbol.type_context?))
+                }
+                _ => {}
+            }
+        }
+
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+        let node = tree.root_node().descendant_for_point_range(point, point)?;
+        if node.kind() != "upper_case_identifier" {
+            return None;
+        }
+        let name = &source[node.byte_range()];
+        // `Q.Name` names a type or constructor of the module imported as `Q`
+        let qualified = node
+            .parent()
+            .filter(|p| matches!(p.kind(), "upper_case_qid" | "value_qid"))
+            .map(|p| &source[p.byte_range()])
+            .and_then(|text| text.rsplit_once('.'))
+            .map(|(qualifier, _)| qualifier);
+        let candidates: Vec<&ElmModule> = match qualified {
+            Some(qualifier) => module
+                .imports
+                .iter()
+                .filter(|i| i.alias.as_ref().unwrap_or(&i.module_name) == qualifier)
+                .filter_map(|i| self.modules.get(&i.module_name))
+                .collect(),
+            None => std::iter::once(module)
+                .chain(
+                    module
+                        .imports
+                        .iter()
+                        .filter(|i| match &i.exposing {
+                            ExposingInfo::All => true,
+                            ExposingInfo::Explicit(items) => !items.is_empty(),
+                        })
+                        .filter_map(|i| self.modules.get(&i.module_name)),
+                )
+                .collect(),
+        };
+        candidates.into_iter().find_map(|candidate| {
+            candidate
+                .symbols
+                .iter()
+                .filter(|s| s.kind == SymbolKind::ENUM)
+                .find(|s| s.name == name || s.variants.iter().any(|v| v.name == name))
+                .map(|s| (candidate.module_name.clone(), s.name.clone()))
+        })
+    }
+
+    /// Where each reference sits, leaving out declarations, Evergreen
+    /// snapshots and mentions in imports or strings
+    fn classify_references(
+        &self,
+        references: Vec<SymbolReference>,
+        trees: &mut HashMap<Url, Option<Tree>>,
+    ) -> Vec<(UsageKind, Location)> {
+        let mut classified = Vec::new();
+        for r in references {
+            if r.is_definition || r.uri.path().contains("/Evergreen/") {
+                continue;
+            }
+            let tree = trees.entry(r.uri.clone()).or_insert_with(|| {
+                let content = self.read_file_content(&r.uri)?;
+                self.parser.parse(&content)
+            });
+            let Some(tree) = tree else {
+                continue;
+            };
+            let point = tree_sitter::Point::new(
+                r.range.start.line as usize,
+                r.range.start.character as usize,
+            );
+            let Some(node) = tree.root_node().descendant_for_point_range(point, point) else {
+                continue;
+            };
+            let kind = std::iter::successors(Some(node), |n| n.parent()).find_map(|n| {
+                match n.kind() {
+                    "union_pattern" | "pattern" => Some(Some(UsageKind::Pattern)),
+                    "type_ref" | "type_annotation" | "port_annotation" | "type_expression" => {
+                        Some(Some(UsageKind::Signature))
+                    }
+                    "value_expr" => Some(Some(UsageKind::Construction)),
+                    // The declaration itself, imports, strings and comments
+                    "union_variant"
+                    | "type_declaration"
+                    | "import_clause"
+                    | "module_declaration"
+                    | "string_constant_expr"
+                    | "line_comment"
+                    | "block_comment" => Some(None),
+                    _ => None,
+                }
+            });
+            if let Some(Some(kind)) = kind {
+                classified.push((kind, Location::new(r.uri, r.range)));
+            }
+        }
+        classified
+    }
+}
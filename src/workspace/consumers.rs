@@ -0,0 +1,171 @@
+//! The modules importing a declaration, by how they import it.
+//!
+//! For an "API consumers" view of a module: each module whose import can
+//! reach the declaration goes in one group, the first that applies of
+//! exposed (`import Utils exposing (format)`, or `(..)`), aliased (`import
+//! Utils as U`) and qualified (`import Utils`), with its uses of the
+//! declaration. Evergreen snapshots are left out.
+
+use serde::Serialize;
+use tower_lsp::lsp_types::*;
+
+use crate::document::node_range;
+
+use super::{ElmModule, ImportInfo, Workspace};
+
+/// The importers of a declaration, for `elm-lsp/declarationImporters`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeclarationImporters {
+    pub name: String,
+    pub module_name: String,
+    /// Whether its module exposes it; when not, no importer can use it
+    pub exposed_by_module: bool,
+    /// Imports exposing it, so it is used unqualified
+    pub exposed: Vec<ImportingModule>,
+    /// Imports with an alias, so it is used as `U.format`
+    pub aliased: Vec<ImportingModule>,
+    /// Plain imports, so it is used as `Utils.format`
+    pub qualified: Vec<ImportingModule>,
+}
+
+/// A module importing the declaration's module
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportingModule {
+    pub module_name: String,
+    pub uri: Url,
+    /// The import clause
+    pub import_range: Range,
+    pub alias: Option<String>,
+    /// Where the module refers to the declaration
+    pub references: Vec<Range>,
+}
+
+impl Workspace {
+    /// The importers of the declaration named at `position`, in the module
+    /// declaring it
+    pub fn declaration_importers(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> Option<DeclarationImporters> {
+        let module = self.find_module_by_path(&uri.to_file_path().ok()?)?;
+        let name = declaration_at(module, position)?;
+        let mut importers = DeclarationImporters {
+            name: name.clone(),
+            module_name: module.module_name.clone(),
+            exposed_by_module: module_exposes(module, &name),
+            exposed: Vec::new(),
+            aliased: Vec::new(),
+            qualified: Vec::new(),
+        };
+        if !importers.exposed_by_module {
+            return Some(importers);
+        }
+
+        let mut consumers: Vec<&ElmModule> = self
+            .modules
+            .values()
+            .filter(|m| !m.path.to_string_lossy().contains("/Evergreen/"))
+            .collect();
+        consumers.sort_by(|a, b| a.module_name.cmp(&b.module_name));
+        for consumer in consumers {
+            let Some(import) = consumer
+                .imports
+                .iter()
+                .find(|import| import.module_name == module.module_name)
+            else {
+                continue;
+            };
+            let exposed = import.exposing.exposes(&name, |type_name| {
+                self.exposes_variant(import, type_name, &name)
+            });
+            let Some(importing) = self.importing_module(consumer, import, &name, exposed) else {
+                continue;
+            };
+            let group = if exposed {
+                &mut importers.exposed
+            } else if import.alias.is_some() {
+                &mut importers.aliased
+            } else {
+                &mut importers.qualified
+            };
+            group.push(importing);
+        }
+        Some(importers)
+    }
+
+    /// `consumer`, with the range of its `import` and its references to
+    /// `name`
+    fn importing_module(
+        &self,
+        consumer: &ElmModule,
+        import: &ImportInfo,
+        name: &str,
+        exposed: bool,
+    ) -> Option<ImportingModule> {
+        let uri = Url::from_file_path(&consumer.path).ok()?;
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        let clause = root.named_children(&mut cursor).find(|n| {
+            n.kind() == "import_clause"
+                && n.child_by_field_name("moduleName")
+                    .is_some_and(|m| source[m.byte_range()] == import.module_name)
+        })?;
+
+        // Unqualified references count when the import brings the name in
+        // and the module does not declare its own
+        let qualified = format!("{}.{}", import.module_name, name);
+        let unqualified = exposed && !consumer.symbols.iter().any(|symbol| symbol.name == name);
+        let mut references: Vec<Range> = self
+            .references
+            .iter()
+            .filter(|(key, _)| **key == qualified || (unqualified && key.as_str() == name))
+            .flat_map(|(_, references)| references)
+            .filter(|reference| !reference.is_definition && reference.uri == uri)
+            .map(|reference| reference.range)
+            .collect();
+        references.sort_by_key(|range| range.start);
+        references.dedup();
+
+        Some(ImportingModule {
+            module_name: consumer.module_name.clone(),
+            uri,
+            import_range: node_range(clause),
+            alias: import.alias.clone(),
+            references,
+        })
+    }
+}
+
+/// Whether `module` exposes `name`, a declaration or a variant
+fn module_exposes(module: &ElmModule, name: &str) -> bool {
+    module.exposing.exposes(name, |type_name| {
+        module
+            .symbols
+            .iter()
+            .filter(|symbol| symbol.name == type_name)
+            .any(|symbol| symbol.variants.iter().any(|v| v.name == name))
+    })
+}
+
+/// The declaration of `module` whose name is at `position`: in its
+/// declaration or annotation, or a variant
+fn declaration_at(module: &ElmModule, position: Position) -> Option<String> {
+    let contains = |range: &Range| range.start <= position && position <= range.end;
+    module.symbols.iter().find_map(|symbol| {
+        if symbol.definition_range.as_ref().is_some_and(contains)
+            || symbol.type_annotation_range.as_ref().is_some_and(contains)
+        {
+            return Some(symbol.name.clone());
+        }
+        symbol
+            .variants
+            .iter()
+            .find(|variant| contains(&variant.range))
+            .map(|variant| variant.name.clone())
+    })
+}
@@ -0,0 +1,109 @@
+//! Where top-level declarations start and end, from the syntax tree.
+//!
+//! A value declaration takes the lines of its body, of the annotation above
+//! it and of the comments attached to those, whatever the layout: comments
+//! between annotation and body, nested `let` blocks or a body followed
+//! directly by the next declaration do not change where it ends.
+
+use tower_lsp::lsp_types::*;
+use tree_sitter::{Node, Tree};
+
+use crate::parser::comments_above;
+
+use super::module_docs::module_doc_comment;
+
+const DECLARATION_KINDS: [&str; 5] = [
+    "value_declaration",
+    "type_annotation",
+    "type_declaration",
+    "type_alias_declaration",
+    "port_annotation",
+];
+
+/// The top-level value declaration named `name`
+pub(super) fn value_declaration<'a>(tree: &'a Tree, source: &str, name: &str) -> Option<Node<'a>> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let found = root
+        .named_children(&mut cursor)
+        .find(|n| n.kind() == "value_declaration" && declared_name(*n, source) == Some(name));
+    found
+}
+
+/// The lines of `declaration` with its annotation and comments, as a range
+/// from the start of its first line to the end of its last
+pub(super) fn declaration_extent(declaration: Node, source: &str) -> Range {
+    let first = declaration_start(declaration, source);
+    Range::new(
+        Position::new(first.start_position().row as u32, 0),
+        Position::new(
+            declaration.end_position().row as u32,
+            declaration.end_position().column as u32,
+        ),
+    )
+}
+
+/// The node the lines of `declaration` start at: its first attached comment,
+/// its annotation, or the declaration itself
+pub(super) fn declaration_start<'a>(declaration: Node<'a>, source: &str) -> Node<'a> {
+    let mut first = declaration;
+    let mut sibling = declaration.prev_named_sibling();
+    // Comments may sit between an annotation and its body
+    while let Some(node) = sibling {
+        match node.kind() {
+            "line_comment" | "block_comment" => sibling = node.prev_named_sibling(),
+            "type_annotation" => {
+                let annotated = node
+                    .child_by_field_name("name")
+                    .map(|n| &source[n.byte_range()]);
+                if annotated.is_some() && annotated == declared_name(declaration, source) {
+                    first = node;
+                }
+                break;
+            }
+            _ => break,
+        }
+    }
+    comments_above(first, source)
+        .last()
+        .copied()
+        .unwrap_or(first)
+}
+
+/// The line of the first top-level declaration, above its annotation and
+/// comments
+pub(super) fn first_declaration_line(tree: &Tree, source: &str) -> Option<u32> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let first = root
+        .named_children(&mut cursor)
+        .find(|n| DECLARATION_KINDS.contains(&n.kind()))?;
+    let start = comments_above(first, source)
+        .last()
+        .copied()
+        .unwrap_or(first);
+    Some(start.start_position().row as u32)
+}
+
+/// The line after the imports, or after the module header and its doc
+/// comment when there are no imports
+pub(super) fn after_imports_line(tree: &Tree, source: &str) -> u32 {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let last_import = root
+        .named_children(&mut cursor)
+        .filter(|n| n.kind() == "import_clause")
+        .last();
+    last_import
+        .or_else(|| module_doc_comment(tree, source))
+        .or_else(|| root.child_by_field_name("moduleDeclaration"))
+        .map_or(0, |n| n.end_position().row as u32 + 1)
+}
+
+/// The name a value declaration declares: `update` for `update msg model =`
+pub(super) fn declared_name<'a>(declaration: Node, source: &'a str) -> Option<&'a str> {
+    let name = declaration
+        .child_by_field_name("functionDeclarationLeft")?
+        .named_child(0)?;
+    Some(&source[name.byte_range()])
+}
@@ -0,0 +1,179 @@
+//! Adding a package dependency for an import of an unknown module.
+//!
+//! The packages offered are those in the local package cache exposing the
+//! module. Installing never edits elm.json directly: `elm-json install`
+//! solves the constraints when it is available, otherwise the compiler's
+//! own `install` does, so indirect dependencies stay consistent.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use tower_lsp::lsp_types::*;
+
+use crate::document::node_range;
+use crate::logging;
+
+use super::{parse_exposed_modules, Workspace};
+
+impl Workspace {
+    /// Imports in `range` of modules neither the workspace nor its packages
+    /// have, with the cached packages exposing each of them. None in
+    /// restricted mode, which cannot install them.
+    pub fn missing_import_packages(&self, uri: &Url, range: Range) -> Vec<(String, Vec<String>)> {
+        if self.settings.restricted {
+            return Vec::new();
+        }
+        let (Some(tree), Some(source)) = (
+            self.type_checker.get_tree(uri.as_str()),
+            self.type_checker.get_source(uri.as_str()),
+        ) else {
+            return Vec::new();
+        };
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        root.named_children(&mut cursor)
+            .filter(|n| n.kind() == "import_clause")
+            .filter(|import| {
+                let found = node_range(*import);
+                found.start <= range.end && range.start <= found.end
+            })
+            .filter_map(|import| {
+                let name = import.child_by_field_name("moduleName")?;
+                let module_name = &source[name.byte_range()];
+                if self.modules.contains_key(module_name)
+                    || self.external_modules.contains_key(module_name)
+                {
+                    return None;
+                }
+                let packages = self.packages_exposing(module_name);
+                (!packages.is_empty()).then(|| (module_name.to_string(), packages))
+            })
+            .collect()
+    }
+
+    /// Packages in the local caches that expose `module_name`, by name
+    fn packages_exposing(&self, module_name: &str) -> Vec<String> {
+        let mut packages = Vec::new();
+        for dir in self.package_dirs() {
+            let Ok(authors) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for author in authors.flatten() {
+                let Ok(names) = std::fs::read_dir(author.path()) else {
+                    continue;
+                };
+                for name in names.flatten() {
+                    let package = format!(
+                        "{}/{}",
+                        author.file_name().to_string_lossy(),
+                        name.file_name().to_string_lossy()
+                    );
+                    if packages.contains(&package) {
+                        continue;
+                    }
+                    let exposes = std::fs::read_dir(name.path())
+                        .into_iter()
+                        .flatten()
+                        .flatten()
+                        .filter_map(|version| {
+                            let content =
+                                std::fs::read_to_string(version.path().join("elm.json")).ok()?;
+                            serde_json::from_str::<serde_json::Value>(&content).ok()
+                        })
+                        .filter_map(|json| parse_exposed_modules(&json))
+                        .any(|modules| modules.iter().any(|m| m == module_name));
+                    if exposes {
+                        packages.push(package);
+                    }
+                }
+            }
+        }
+        packages.sort();
+        packages
+    }
+
+    /// How to add `package` to the project's dependencies, to run without
+    /// holding the workspace; an error in restricted mode
+    pub fn package_install(&self, package: &str) -> anyhow::Result<PackageInstall> {
+        if self.settings.restricted {
+            return Err(anyhow::anyhow!(
+                "Cannot install {} in restricted mode",
+                package
+            ));
+        }
+        let compiler = if self.is_lamdera_project {
+            "lamdera"
+        } else {
+            "elm"
+        };
+        Ok(PackageInstall {
+            package: package.to_string(),
+            root_path: self.root_path.clone(),
+            elm_json: self.tool_path("elm-json"),
+            compiler: self.tool_path(compiler),
+        })
+    }
+}
+
+/// An install of a package into a project, with the tools it runs
+pub struct PackageInstall {
+    package: String,
+    root_path: PathBuf,
+    elm_json: PathBuf,
+    compiler: PathBuf,
+}
+
+impl PackageInstall {
+    /// Add the package with `elm-json install`, or the compiler's `install`
+    /// when elm-json is not available. Blocks until the tool exits.
+    pub fn run(&self) -> anyhow::Result<()> {
+        let package = self.package.as_str();
+        let child = match Command::new(&self.elm_json)
+            .args(["install", "--yes", package])
+            .current_dir(&self.root_path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(_) => {
+                // `elm install` asks for confirmation before changing elm.json
+                tracing::info!(
+                    target: logging::INDEX,
+                    "elm-json not found, installing {} with {}",
+                    package,
+                    self.compiler.display()
+                );
+                let mut child = Command::new(&self.compiler)
+                    .args(["install", package])
+                    .current_dir(&self.root_path)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(b"y\n")?;
+                }
+                child
+            }
+        };
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            anyhow::bail!(
+                "Could not install {}: {}",
+                package,
+                if stderr.trim().is_empty() {
+                    stdout.trim()
+                } else {
+                    stderr.trim()
+                }
+            );
+        }
+        tracing::info!(target: logging::INDEX, "Installed {}", package);
+        Ok(())
+    }
+}
@@ -444,6 +444,7 @@ impl Workspace {
     }
 
     /// Process a field type for ERD relationships
+    #[allow(clippy::too_many_arguments)]
     fn process_erd_field(
         &self,
         parent_type: &str,
@@ -0,0 +1,321 @@
+//! How a Lamdera project's `Types.elm` has drifted from its newest Evergreen
+//! snapshot.
+//!
+//! `lamdera deploy` needs a migration for every type that changed since the
+//! last deployed version, which `src/Evergreen/V<n>/Types.elm` records. The
+//! report lists the types added, removed and changed since the newest
+//! snapshot, and for a changed record or custom type the fields or variants
+//! that differ. Snapshots qualify every type (`Evergreen.V3.Page.Route`)
+//! while `Types.elm` usually imports them, so types are compared without
+//! their module qualifiers.
+//!
+//! Hovering a type of `Types.elm` also shows how Lamdera's wire format
+//! encodes it: records field by field in alphabetical order, and custom
+//! types as the alphabetical index of their variant followed by its
+//! arguments. The order in the source does not matter, but adding or
+//! renaming a field or variant can move the others.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tower_lsp::lsp_types::Url;
+use tree_sitter::Node;
+
+use super::{normalize_type, Workspace};
+
+/// The changes to `Types.elm` since the newest Evergreen snapshot
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EvergreenDrift {
+    pub root: String,
+    /// The snapshot compared against, like `V12`; None before the first deploy
+    pub snapshot: Option<String>,
+    /// Types declared in `Types.elm` but not in the snapshot
+    pub added: Vec<String>,
+    /// Types declared in the snapshot but not in `Types.elm`
+    pub removed: Vec<String>,
+    pub changed: Vec<TypeDrift>,
+}
+
+/// How one type differs from its snapshot
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeDrift {
+    pub name: String,
+    /// Fields of a record alias, or variants of a custom type
+    pub added: Vec<Member>,
+    pub removed: Vec<Member>,
+    /// Members whose type changed, as they are now
+    pub changed: Vec<Member>,
+    /// The whole definition, when it is not a record or custom type on both
+    /// sides, as in the snapshot and now
+    pub definition: Option<(String, String)>,
+}
+
+/// A record field with its type, or a variant with its arguments
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Member {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_text: String,
+}
+
+/// A type declaration, reduced to what a migration cares about
+#[derive(Debug)]
+enum Definition {
+    Custom(Vec<Member>),
+    Record(Vec<Member>),
+    Alias(String),
+}
+
+/// Members are compared in the wire encoding's alphabetical order, so
+/// reordering fields or variants in the source is no drift
+impl PartialEq for Definition {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Definition::Custom(a), Definition::Custom(b))
+            | (Definition::Record(a), Definition::Record(b)) => alphabetical(a) == alphabetical(b),
+            (Definition::Alias(a), Definition::Alias(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Workspace {
+    /// The drift of `Types.elm` from the newest snapshot, for Lamdera projects
+    pub fn evergreen_drift(&self) -> Option<EvergreenDrift> {
+        if !self.is_lamdera_project {
+            return None;
+        }
+        let types_path = self
+            .modules
+            .get("Types")
+            .map(|m| m.path.clone())
+            .unwrap_or_else(|| self.root_path.join("src/Types.elm"));
+        let current = self.type_definitions(&self.source(&types_path).ok()?)?;
+        let (snapshot, previous) = match self.newest_snapshot() {
+            Some((version, path)) => (
+                Some(version),
+                self.type_definitions(&self.source(&path).ok()?)?,
+            ),
+            None => (None, BTreeMap::new()),
+        };
+
+        let mut drift = EvergreenDrift {
+            root: self.root_path.to_string_lossy().to_string(),
+            snapshot,
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        };
+        for (name, definition) in &current {
+            match previous.get(name) {
+                None => drift.added.push(name.clone()),
+                Some(old) if old != definition => {
+                    drift.changed.push(type_drift(name, old, definition));
+                }
+                Some(_) => {}
+            }
+        }
+        drift.removed = previous
+            .keys()
+            .filter(|name| !current.contains_key(*name))
+            .cloned()
+            .collect();
+        Some(drift)
+    }
+
+    /// The highest `src/Evergreen/V<n>/Types.elm`, with its version
+    fn newest_snapshot(&self) -> Option<(String, PathBuf)> {
+        let evergreen = self.root_path.join("src/Evergreen");
+        std::fs::read_dir(evergreen)
+            .ok()?
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let version: u32 = name.strip_prefix('V')?.parse().ok()?;
+                let types = entry.path().join("Types.elm");
+                types.is_file().then_some((version, name, types))
+            })
+            .max_by_key(|(version, _, _)| *version)
+            .map(|(_, name, types)| (name, types))
+    }
+
+    /// How Lamdera encodes the type `name` declared in `source`, the text of
+    /// the module at `uri`, as markdown for its hover; None outside the
+    /// `Types` module of a Lamdera project
+    pub fn wire_encoding(&self, uri: &Url, source: &str, name: &str) -> Option<String> {
+        let path = uri.to_file_path().ok()?;
+        if !self.is_lamdera_project || self.find_module_by_path(&path)?.module_name != "Types" {
+            return None;
+        }
+        let definitions = self.type_definitions(source)?;
+        let mut markdown = String::from("**Lamdera wire encoding**\n\n");
+        match definitions.get(name)? {
+            Definition::Custom(variants) => {
+                markdown.push_str("| Tag | Variant | Arguments |\n|---|---|---|\n");
+                for (tag, variant) in alphabetical(variants).iter().enumerate() {
+                    markdown.push_str(&format!(
+                        "| {} | `{}` | {} |\n",
+                        tag,
+                        variant.name,
+                        code(&variant.type_text)
+                    ));
+                }
+                markdown.push_str(
+                    "\nThe tag is the variant's alphabetical position: \
+                     adding or renaming a variant can change the tags of the others.",
+                );
+            }
+            Definition::Record(fields) => {
+                markdown.push_str("| # | Field | Type |\n|---|---|---|\n");
+                for (index, field) in alphabetical(fields).iter().enumerate() {
+                    markdown.push_str(&format!(
+                        "| {} | `{}` | {} |\n",
+                        index,
+                        field.name,
+                        code(&field.type_text)
+                    ));
+                }
+                markdown.push_str(
+                    "\nFields are encoded in alphabetical order, whatever their order here.",
+                );
+            }
+            Definition::Alias(text) => {
+                markdown.push_str(&format!("Encoded as {}.", code(text)));
+            }
+        }
+        Some(markdown)
+    }
+
+    fn type_definitions(&self, source: &str) -> Option<BTreeMap<String, Definition>> {
+        let tree = self.parser.parse(source)?;
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        let definitions = root
+            .named_children(&mut cursor)
+            .filter_map(|declaration| {
+                let name = declaration.child_by_field_name("name")?;
+                let name = source[name.byte_range()].to_string();
+                let definition = match declaration.kind() {
+                    "type_declaration" => {
+                        let mut cursor = declaration.walk();
+                        let variants = declaration
+                            .children_by_field_name("unionVariant", &mut cursor)
+                            .filter_map(|variant| {
+                                let name = variant.child_by_field_name("name")?;
+                                let arguments = source[name.end_byte()..variant.end_byte()].trim();
+                                Some(member(&source[name.byte_range()], arguments))
+                            })
+                            .collect();
+                        Definition::Custom(variants)
+                    }
+                    "type_alias_declaration" => {
+                        let expression = declaration.child_by_field_name("typeExpression")?;
+                        match record_fields(expression, source) {
+                            Some(fields) => Definition::Record(fields),
+                            None => {
+                                Definition::Alias(normalize_type(&source[expression.byte_range()]))
+                            }
+                        }
+                    }
+                    _ => return None,
+                };
+                Some((name, definition))
+            })
+            .collect();
+        Some(definitions)
+    }
+}
+
+/// The fields of `{ a : Int, b : String }`; None for other types
+fn record_fields(expression: Node, source: &str) -> Option<Vec<Member>> {
+    let record = expression
+        .named_child(0)
+        .filter(|n| n.kind() == "record_type")?;
+    let mut cursor = record.walk();
+    let fields = record
+        .children_by_field_name("fieldType", &mut cursor)
+        .filter_map(|field| {
+            let name = field.child_by_field_name("name")?;
+            let type_expression = field.child_by_field_name("typeExpression")?;
+            Some(member(
+                &source[name.byte_range()],
+                &source[type_expression.byte_range()],
+            ))
+        })
+        .collect();
+    Some(fields)
+}
+
+fn alphabetical(members: &[Member]) -> Vec<&Member> {
+    let mut sorted: Vec<&Member> = members.iter().collect();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name));
+    sorted
+}
+
+fn code(text: &str) -> String {
+    if text.is_empty() {
+        String::new()
+    } else {
+        format!("`{}`", text)
+    }
+}
+
+fn member(name: &str, type_text: &str) -> Member {
+    Member {
+        name: name.to_string(),
+        type_text: normalize_type(type_text),
+    }
+}
+
+fn type_drift(name: &str, old: &Definition, new: &Definition) -> TypeDrift {
+    let mut drift = TypeDrift {
+        name: name.to_string(),
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+        definition: None,
+    };
+    match (old, new) {
+        (Definition::Record(old), Definition::Record(new))
+        | (Definition::Custom(old), Definition::Custom(new)) => {
+            for member in new {
+                match old.iter().find(|m| m.name == member.name) {
+                    None => drift.added.push(member.clone()),
+                    Some(previous) if previous.type_text != member.type_text => {
+                        drift.changed.push(member.clone());
+                    }
+                    Some(_) => {}
+                }
+            }
+            drift.removed = old
+                .iter()
+                .filter(|m| !new.iter().any(|n| n.name == m.name))
+                .cloned()
+                .collect();
+        }
+        _ => drift.definition = Some((describe(old), describe(new))),
+    }
+    drift
+}
+
+fn describe(definition: &Definition) -> String {
+    let members = |members: &[Member], separator: &str, join: &str| {
+        members
+            .iter()
+            .map(|m| {
+                format!("{}{}{}", m.name, separator, m.type_text)
+                    .trim()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join(join)
+    };
+    match definition {
+        Definition::Custom(variants) => members(variants, " ", " | "),
+        Definition::Record(fields) => format!("{{ {} }}", members(fields, " : ", ", ")),
+        Definition::Alias(text) => text.clone(),
+    }
+}
@@ -0,0 +1,270 @@
+//! References to declarations another module does not expose.
+//!
+//! `B.helper`, `B.Clicked` or `import B exposing (helper)` only compile when
+//! `B` exposes what they name. For modules in the workspace this is known
+//! from the index, so it is reported as soon as the file changes, with a fix
+//! that adds the missing item to `B`'s exposing list.
+
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::*;
+
+use crate::analysis::codes;
+use crate::document::node_range;
+
+use super::{ExposingInfo, ImportInfo, Workspace};
+
+/// A reference to something its module declares but does not expose
+struct NotExposed {
+    range: Range,
+    module_name: String,
+    /// The name as written, without its qualifier
+    name: String,
+    /// What the module has to expose: the name, or `Type(..)` for a constructor
+    item: String,
+    /// Where the module declares it
+    declaration: Option<Location>,
+}
+
+/// A quickfix that exposes a declaration from the module declaring it
+pub struct ExposeFix {
+    pub title: String,
+    pub diagnostic: Diagnostic,
+    pub changes: HashMap<Url, Vec<TextEdit>>,
+}
+
+impl Workspace {
+    /// Diagnostics for references to declarations their module does not expose
+    pub fn not_exposed_diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        self.not_exposed_references(uri)
+            .iter()
+            .map(diagnostic)
+            .collect()
+    }
+
+    /// Fixes for the diagnostics of [`Self::not_exposed_diagnostics`] in `range`
+    pub fn expose_fixes(&self, uri: &Url, range: Range) -> Vec<ExposeFix> {
+        self.not_exposed_references(uri)
+            .into_iter()
+            .filter(|r| r.range.start <= range.end && range.start <= r.range.end)
+            .filter_map(|reference| {
+                let (target_uri, edit) = self.expose_edit(&reference)?;
+                Some(ExposeFix {
+                    title: format!("Expose {} from {}", reference.item, reference.module_name),
+                    diagnostic: diagnostic(&reference),
+                    changes: HashMap::from([(target_uri, vec![edit])]),
+                })
+            })
+            .collect()
+    }
+
+    fn not_exposed_references(&self, uri: &Url) -> Vec<NotExposed> {
+        let (Some(tree), Some(source)) = (
+            self.type_checker.get_tree(uri.as_str()),
+            self.type_checker.get_source(uri.as_str()),
+        ) else {
+            return Vec::new();
+        };
+        let Some(module) = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.find_module_by_path(&path))
+        else {
+            return Vec::new();
+        };
+
+        let mut found = Vec::new();
+        let mut stack = vec![tree.root_node()];
+        while let Some(node) = stack.pop() {
+            match node.kind() {
+                "module_declaration" => continue,
+                "import_clause" => {
+                    found.extend(self.import_items_not_exposed(node, source));
+                    continue;
+                }
+                "value_qid" | "upper_case_qid" => {
+                    found.extend(self.qualified_not_exposed(node, source, &module.imports));
+                    continue;
+                }
+                _ => {}
+            }
+            let mut cursor = node.walk();
+            stack.extend(node.named_children(&mut cursor));
+        }
+        found.sort_by_key(|r| r.range.start);
+        found
+    }
+
+    /// `B.helper`, `B.Model` or `B.Clicked`, with `B` an import or its alias
+    fn qualified_not_exposed(
+        &self,
+        node: tree_sitter::Node,
+        source: &str,
+        imports: &[ImportInfo],
+    ) -> Option<NotExposed> {
+        let (qualifier, name) = source[node.byte_range()].rsplit_once('.')?;
+        let import = imports.iter().find(|import| match &import.alias {
+            Some(alias) => alias == qualifier,
+            None => import.module_name == qualifier,
+        })?;
+        let in_type = node.parent().is_some_and(|p| p.kind() == "type_ref");
+        let item = self.item_for(&import.module_name, name, !in_type)?;
+        self.unless_exposed(&import.module_name, name, item, node_range(node))
+    }
+
+    /// Items of `import B exposing (...)` that `B` does not expose
+    fn import_items_not_exposed(&self, import: tree_sitter::Node, source: &str) -> Vec<NotExposed> {
+        let Some(module_name) = import
+            .child_by_field_name("moduleName")
+            .map(|n| &source[n.byte_range()])
+        else {
+            return Vec::new();
+        };
+        let Some(exposing) = import.child_by_field_name("exposing") else {
+            return Vec::new();
+        };
+        let mut cursor = exposing.walk();
+        exposing
+            .named_children(&mut cursor)
+            .filter_map(|item| {
+                let name_node = item.named_child(0)?;
+                let name = &source[name_node.byte_range()];
+                let wanted = match item.kind() {
+                    "exposed_value" => name.to_string(),
+                    "exposed_type" if item.named_child_count() > 1 => format!("{}(..)", name),
+                    "exposed_type" => name.to_string(),
+                    _ => return None,
+                };
+                self.declares(module_name, name)?;
+                self.unless_exposed(module_name, name, wanted, node_range(item))
+            })
+            .collect()
+    }
+
+    /// What `module_name` has to expose for `name` to be usable: the name
+    /// itself, or `Type(..)` for a constructor. None if it declares no such thing.
+    fn item_for(&self, module_name: &str, name: &str, in_value: bool) -> Option<String> {
+        let module = self.modules.get(module_name)?;
+        if in_value {
+            let owner = module
+                .symbols
+                .iter()
+                .find(|s| s.variants.iter().any(|v| v.name == name));
+            if let Some(owner) = owner {
+                return Some(format!("{}(..)", owner.name));
+            }
+        }
+        self.declares(module_name, name)
+    }
+
+    fn declares(&self, module_name: &str, name: &str) -> Option<String> {
+        let module = self.modules.get(module_name)?;
+        module
+            .symbols
+            .iter()
+            .any(|s| s.name == name)
+            .then(|| name.to_string())
+    }
+
+    fn unless_exposed(
+        &self,
+        module_name: &str,
+        name: &str,
+        item: String,
+        range: Range,
+    ) -> Option<NotExposed> {
+        let module = self.modules.get(module_name)?;
+        let exposed = match &module.exposing {
+            ExposingInfo::All => true,
+            ExposingInfo::Explicit(items) => items.iter().any(|i| {
+                *i == item || item.strip_suffix("(..)").is_none() && *i == format!("{}(..)", item)
+            }),
+        };
+        if exposed {
+            return None;
+        }
+        let key = item.strip_suffix("(..)").unwrap_or(&item);
+        let declaration = module
+            .symbols
+            .iter()
+            .find(|s| s.name == name)
+            .or_else(|| module.symbols.iter().find(|s| s.name == key))
+            .zip(Url::from_file_path(&module.path).ok())
+            .map(|(symbol, uri)| Location {
+                uri,
+                range: symbol.definition_range.unwrap_or(symbol.range),
+            });
+        Some(NotExposed {
+            range,
+            module_name: module_name.to_string(),
+            name: name.to_string(),
+            item,
+            declaration,
+        })
+    }
+
+    /// The edit adding the reference's item to its module's exposing list.
+    /// A type exposed without its constructors gets `(..)` added in place.
+    fn expose_edit(&self, reference: &NotExposed) -> Option<(Url, TextEdit)> {
+        let module = self.modules.get(&reference.module_name)?;
+        let uri = Url::from_file_path(&module.path).ok()?;
+        let (tree, source) = match (
+            self.type_checker.get_tree(uri.as_str()),
+            self.type_checker.get_source(uri.as_str()),
+        ) {
+            (Some(tree), Some(source)) => (tree.clone(), source.to_string()),
+            _ => {
+                let source = self.source(&module.path).ok()?;
+                (self.parser.parse(&source)?, source)
+            }
+        };
+
+        let exposing = tree
+            .root_node()
+            .child_by_field_name("moduleDeclaration")?
+            .child_by_field_name("exposing")?;
+        if let Some(type_name) = reference.item.strip_suffix("(..)") {
+            let mut cursor = exposing.walk();
+            let listed = exposing
+                .named_children(&mut cursor)
+                .find(|n| n.kind() == "exposed_type" && &source[n.byte_range()] == type_name);
+            if let Some(listed) = listed {
+                return Some((
+                    uri,
+                    TextEdit {
+                        range: node_range(listed),
+                        new_text: reference.item.clone(),
+                    },
+                ));
+            }
+        }
+        let edit = super::tuple_to_record::expose_edit(&tree, &source, &reference.item)?;
+        Some((uri, edit))
+    }
+}
+
+fn diagnostic(reference: &NotExposed) -> Diagnostic {
+    let message = match reference.item.strip_suffix("(..)") {
+        None => format!(
+            "`{}` does not expose `{}`",
+            reference.module_name, reference.name
+        ),
+        Some(type_name) if type_name == reference.name => format!(
+            "`{}` does not expose the constructors of `{}`",
+            reference.module_name, type_name
+        ),
+        Some(type_name) => format!(
+            "`{}` does not expose `{}`, a constructor of `{}`",
+            reference.module_name, reference.name, type_name
+        ),
+    };
+    Diagnostic {
+        related_information: reference.declaration.clone().map(|location| {
+            vec![DiagnosticRelatedInformation {
+                location,
+                message: format!("`{}` declared here", reference.name),
+            }]
+        }),
+        ..codes::NOT_EXPOSED.diagnostic(reference.range, DiagnosticSeverity::ERROR, message)
+    }
+}
@@ -0,0 +1,483 @@
+//! Pulling a selected expression out of a function.
+//!
+//! "Introduce parameter" turns the expression into a new first parameter of
+//! the enclosing top-level function. Putting it first keeps partial
+//! applications and pipelines valid: every use of the function, called or
+//! not, just gets one more leading argument.
+//!
+//! "Hoist to constant" moves a closed expression (one that uses no local
+//! variables) to a new top-level constant, and replaces every structurally
+//! identical copy of it in the module. Elm does not allow shadowing, so equal
+//! text refers to the same things wherever it appears.
+
+use std::collections::HashMap;
+use tower_lsp::lsp_types::*;
+
+use crate::binder::BoundSymbolKind;
+use crate::document::node_range;
+
+use super::declarations::declaration_start;
+use super::{RefactorResult, Workspace};
+
+impl Workspace {
+    /// Whether `range` selects an expression of a top-level declaration, as
+    /// the extractions need, without computing any of their edits
+    pub fn is_extractable(&self, uri: &Url, range: Range) -> bool {
+        self.type_checker
+            .get_tree(uri.as_str())
+            .and_then(|tree| selected_expression(tree, range))
+            .and_then(|expr| {
+                std::iter::successors(Some(expr), |n| n.parent())
+                    .find(|n| n.parent().is_some_and(|p| p.kind() == "file"))
+            })
+            .is_some_and(|declaration| declaration.kind() == "value_declaration")
+    }
+
+    /// Replace the expression selected by `range` with a new first parameter
+    /// `param_name` of the enclosing function, and pass the expression at
+    /// every use of the function. Uses outside the function get
+    /// `Debug.todo` instead when the expression refers to local variables
+    /// (or, in other modules, to unqualified names), and are listed for review.
+    pub fn introduce_parameter(
+        &self,
+        uri: &Url,
+        range: Range,
+        param_name: &str,
+    ) -> anyhow::Result<RefactorResult> {
+        let (tree, source) = match (
+            self.type_checker.get_tree(uri.as_str()),
+            self.type_checker.get_source(uri.as_str()),
+        ) {
+            (Some(tree), Some(source)) => (tree, source),
+            _ => return Err(anyhow::anyhow!("{} is not indexed", uri)),
+        };
+
+        let expr = match selected_expression(tree, range) {
+            Some(expr) => expr,
+            None => return Ok(RefactorResult::error("Selection is not an expression")),
+        };
+        let declaration = std::iter::successors(Some(expr), |n| n.parent())
+            .find(|n| n.parent().is_some_and(|p| p.kind() == "file"))
+            .filter(|n| n.kind() == "value_declaration");
+        let left = declaration.and_then(|d| d.child_by_field_name("functionDeclarationLeft"));
+        let (declaration, left) = match (declaration, left) {
+            (Some(declaration), Some(left)) => (declaration, left),
+            _ => {
+                return Ok(RefactorResult::error(
+                    "Selection is not inside a top-level function",
+                ))
+            }
+        };
+        let name_node = match left.child(0) {
+            Some(name_node) => name_node,
+            None => return Ok(RefactorResult::error("Could not find the function name")),
+        };
+        let function_name = &source[name_node.byte_range()];
+
+        if !is_lower_identifier(param_name) {
+            return Ok(RefactorResult::error(&format!(
+                "'{}' is not a valid parameter name",
+                param_name
+            )));
+        }
+        let module_name = self.get_module_name_from_uri(uri);
+        let declared = self
+            .get_module(&module_name)
+            .is_some_and(|m| m.symbols.iter().any(|s| s.name == param_name));
+        let locals = bound_names(declaration, source, None);
+        if declared || locals.iter().any(|l| l == param_name) {
+            return Ok(RefactorResult::error(&format!(
+                "'{}' is already defined in {}",
+                param_name, function_name
+            )));
+        }
+
+        // What the callers pass
+        let outer_locals = bound_names(declaration, source, Some(expr));
+        let free_names = free_value_names(expr, source);
+        let uses_locals = free_names.iter().any(|n| outer_locals.contains(n));
+        let uses_unqualified = !free_names.is_empty();
+        let expr_text = &source[expr.byte_range()];
+        let argument = if needs_parentheses(expr, expr_text) {
+            format!("({})", expr_text)
+        } else {
+            expr_text.to_string()
+        };
+        let placeholder = format!("(Debug.todo \"{}\")", param_name);
+
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        let mut unchanged = Vec::new();
+        let edits = changes.entry(uri.clone()).or_default();
+        edits.push(TextEdit {
+            range: node_range(expr),
+            new_text: param_name.to_string(),
+        });
+        let after_name = node_range(name_node).end;
+        edits.push(TextEdit {
+            range: Range::new(after_name, after_name),
+            new_text: format!(" {}", param_name),
+        });
+
+        // The annotation gets the inferred type, if there is one
+        let annotation = declaration
+            .prev_named_sibling()
+            .filter(|n| n.kind() == "type_annotation")
+            .filter(|n| {
+                n.child_by_field_name("name")
+                    .is_some_and(|name| &source[name.byte_range()] == function_name)
+            })
+            .and_then(|n| n.child_by_field_name("typeExpression"));
+        if let Some(annotation) = annotation {
+            let ty = self
+                .type_checker
+                .get_type(uri.as_str(), expr.id())
+                .filter(|ty| ty.is_known())
+                .map(|ty| ty.with_readable_vars());
+            match ty {
+                Some(ty) => {
+                    let start = node_range(annotation).start;
+                    let ty = if ty.is_function() {
+                        format!("({})", ty)
+                    } else {
+                        ty.to_string()
+                    };
+                    edits.push(TextEdit {
+                        range: Range::new(start, start),
+                        new_text: format!("{} -> ", ty),
+                    });
+                }
+                None => unchanged.push(Location::new(uri.clone(), node_range(annotation))),
+            }
+        }
+
+        // Every use of the function passes the new argument
+        let expr_range = node_range(expr);
+        let declaration_range = node_range(declaration);
+        let references = self
+            .find_module_aware_references(function_name, &module_name, uri)
+            .into_iter()
+            .filter(|r| matches!(r.kind, Some(BoundSymbolKind::Function) | None));
+        let mut call_sites = 0;
+        for reference in references {
+            let same_file = &reference.uri == uri;
+            if same_file && contains(expr_range, reference.range.start) {
+                continue;
+            }
+            let file_tree = self.type_checker.get_tree(reference.uri.as_str());
+            let usage = file_tree.and_then(|t| {
+                let point = tree_sitter::Point::new(
+                    reference.range.start.line as usize,
+                    reference.range.start.character as usize,
+                );
+                let node = t.root_node().descendant_for_point_range(point, point)?;
+                std::iter::successors(Some(node), |n| n.parent())
+                    .take(3)
+                    .find(|n| n.kind() == "value_expr")
+                    .map(|usage| (node_range(usage), is_call_target(usage)))
+            });
+            // Exposing lists and the declaration's own name need no change
+            let (usage_range, called) = match usage {
+                Some(usage) => usage,
+                None => continue,
+            };
+
+            let recursive = same_file && contains(declaration_range, usage_range.start);
+            let passed = if recursive {
+                param_name.to_string()
+            } else if uses_locals || (!same_file && uses_unqualified) {
+                unchanged.push(Location::new(reference.uri.clone(), usage_range));
+                placeholder.clone()
+            } else {
+                argument.clone()
+            };
+
+            let edits = changes.entry(reference.uri.clone()).or_default();
+            if called {
+                edits.push(TextEdit {
+                    range: Range::new(usage_range.end, usage_range.end),
+                    new_text: format!(" {}", passed),
+                });
+            } else {
+                edits.push(TextEdit {
+                    range: Range::new(usage_range.start, usage_range.start),
+                    new_text: "(".to_string(),
+                });
+                edits.push(TextEdit {
+                    range: Range::new(usage_range.end, usage_range.end),
+                    new_text: format!(" {})", passed),
+                });
+            }
+            call_sites += 1;
+        }
+
+        let mut message = format!(
+            "Added parameter '{}' to {}, updated {} use(s)",
+            param_name, function_name, call_sites
+        );
+        if !unchanged.is_empty() {
+            message.push_str(&format!(", {} need manual review", unchanged.len()));
+        }
+        Ok(RefactorResult::success(&message, changes, unchanged))
+    }
+
+    /// Move the closed expression selected by `range` to a new top-level
+    /// constant `name` declared above the enclosing declaration, annotated
+    /// with its inferred type when known, and replace every identical
+    /// expression in the module with it
+    pub fn hoist_constant(
+        &self,
+        uri: &Url,
+        range: Range,
+        name: &str,
+    ) -> anyhow::Result<RefactorResult> {
+        let (tree, source) = match (
+            self.type_checker.get_tree(uri.as_str()),
+            self.type_checker.get_source(uri.as_str()),
+        ) {
+            (Some(tree), Some(source)) => (tree, source),
+            _ => return Err(anyhow::anyhow!("{} is not indexed", uri)),
+        };
+
+        let expr = match selected_expression(tree, range) {
+            Some(expr) => expr,
+            None => return Ok(RefactorResult::error("Selection is not an expression")),
+        };
+        let declaration = std::iter::successors(Some(expr), |n| n.parent())
+            .find(|n| n.parent().is_some_and(|p| p.kind() == "file"))
+            .filter(|n| n.kind() == "value_declaration");
+        let declaration = match declaration {
+            Some(declaration) => declaration,
+            None => {
+                return Ok(RefactorResult::error(
+                    "Selection is not inside a top-level declaration",
+                ))
+            }
+        };
+
+        if !is_lower_identifier(name) {
+            return Ok(RefactorResult::error(&format!(
+                "'{}' is not a valid constant name",
+                name
+            )));
+        }
+        let module_name = self.get_module_name_from_uri(uri);
+        let declared = self
+            .get_module(&module_name)
+            .is_some_and(|m| m.symbols.iter().any(|s| s.name == name));
+        if declared
+            || bound_names(tree.root_node(), source, None)
+                .iter()
+                .any(|n| n == name)
+        {
+            return Ok(RefactorResult::error(&format!(
+                "'{}' is already defined in {}",
+                name, module_name
+            )));
+        }
+
+        let locals = bound_names(declaration, source, Some(expr));
+        let mut used: Vec<String> = free_value_names(expr, source)
+            .into_iter()
+            .filter(|n| locals.contains(n))
+            .collect();
+        if !used.is_empty() {
+            used.sort();
+            used.dedup();
+            return Ok(RefactorResult::error(&format!(
+                "The expression uses local variable(s): {}",
+                used.join(", ")
+            )));
+        }
+
+        // The constant goes above the declaration, its annotation and comments
+        let anchor = declaration_start(declaration, source);
+        let at = Position::new(anchor.start_position().row as u32, 0);
+
+        let annotation = self
+            .type_checker
+            .get_type(uri.as_str(), expr.id())
+            .filter(|ty| ty.is_known())
+            .map(|ty| format!("{} : {}\n", name, ty.with_readable_vars()))
+            .unwrap_or_default();
+        // The constant does not need the selection's own parentheses
+        let inner = Some(expr)
+            .filter(|e| e.kind() == "parenthesized_expr")
+            .and_then(|e| e.child_by_field_name("expression"))
+            .unwrap_or(expr);
+        let body = indented_body(&source[inner.byte_range()], inner.start_position().column);
+
+        let mut edits = vec![TextEdit {
+            range: Range::new(at, at),
+            new_text: format!("{}{} =\n    {}\n\n\n", annotation, name, body),
+        }];
+        let key = structure(expr, source);
+        let mut occurrences = Vec::new();
+        collect_identical(
+            tree.root_node(),
+            source,
+            expr.kind(),
+            &key,
+            &mut occurrences,
+        );
+        for occurrence in &occurrences {
+            edits.push(TextEdit {
+                range: node_range(*occurrence),
+                new_text: name.to_string(),
+            });
+        }
+
+        let mut changes = HashMap::new();
+        changes.insert(uri.clone(), edits);
+        let message = format!(
+            "Extracted '{}', replacing {} occurrence(s)",
+            name,
+            occurrences.len()
+        );
+        Ok(RefactorResult::success(&message, changes, Vec::new()))
+    }
+}
+
+/// The tokens of `node`, ignoring layout and comments
+fn structure(node: tree_sitter::Node, source: &str) -> String {
+    let mut tokens = Vec::new();
+    let mut stack = vec![node];
+    while let Some(current) = stack.pop() {
+        if matches!(current.kind(), "line_comment" | "block_comment") {
+            continue;
+        }
+        if current.child_count() == 0 {
+            tokens.push(&source[current.byte_range()]);
+            continue;
+        }
+        let mut cursor = current.walk();
+        let children: Vec<_> = current.children(&mut cursor).collect();
+        stack.extend(children.into_iter().rev());
+    }
+    tokens.join(" ")
+}
+
+/// Outermost nodes below `node` of `kind` whose tokens are `key`
+fn collect_identical<'tree>(
+    node: tree_sitter::Node<'tree>,
+    source: &str,
+    kind: &str,
+    key: &str,
+    found: &mut Vec<tree_sitter::Node<'tree>>,
+) {
+    if node.kind() == kind && structure(node, source) == key {
+        found.push(node);
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_identical(child, source, kind, key, found);
+    }
+}
+
+/// The text of an expression that started at `column`, reindented as the
+/// body of a top-level declaration
+pub(super) fn indented_body(text: &str, column: usize) -> String {
+    text.split('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 {
+                return line.to_string();
+            }
+            let indent = line.len() - line.trim_start().len();
+            format!("    {}", &line[indent.min(column)..])
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The outermost expression node spanning exactly `range`
+pub(super) fn selected_expression(
+    tree: &tree_sitter::Tree,
+    range: Range,
+) -> Option<tree_sitter::Node<'_>> {
+    let start = tree_sitter::Point::new(range.start.line as usize, range.start.character as usize);
+    let end = tree_sitter::Point::new(range.end.line as usize, range.end.character as usize);
+    let node = tree.root_node().descendant_for_point_range(start, end)?;
+    std::iter::successors(Some(node), |n| n.parent())
+        .take_while(|n| n.start_position() == start && n.end_position() == end)
+        .filter(|n| n.kind().ends_with("_expr"))
+        .last()
+}
+
+pub(super) fn is_lower_identifier(name: &str) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "if", "then", "else", "case", "of", "let", "in", "type", "module", "where", "import",
+        "exposing", "as", "port",
+    ];
+    name.starts_with(|c: char| c.is_lowercase())
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&name)
+}
+
+/// Names bound by patterns and let-declared functions within `node`,
+/// leaving out anything inside `skip`
+pub(super) fn bound_names(
+    node: tree_sitter::Node,
+    source: &str,
+    skip: Option<tree_sitter::Node>,
+) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut stack = vec![node];
+    while let Some(current) = stack.pop() {
+        if skip.is_some_and(|s| s.id() == current.id()) {
+            continue;
+        }
+        let binding = match current.kind() {
+            "lower_pattern" => Some(current),
+            "function_declaration_left" if current.parent() != Some(node) => current.child(0),
+            _ => None,
+        };
+        if let Some(binding) = binding {
+            names.push(source[binding.byte_range()].to_string());
+        }
+        let mut cursor = current.walk();
+        stack.extend(current.children(&mut cursor));
+    }
+    names
+}
+
+/// Unqualified values `expr` refers to, other than ones it binds itself
+fn free_value_names(expr: tree_sitter::Node, source: &str) -> Vec<String> {
+    let inner = bound_names(expr, source, None);
+    let mut names = Vec::new();
+    let mut stack = vec![expr];
+    while let Some(current) = stack.pop() {
+        if matches!(current.kind(), "value_expr" | "record_base_identifier") {
+            let text = &source[current.byte_range()];
+            if !text.contains('.') && !inner.iter().any(|n| n == text) {
+                names.push(text.to_string());
+            }
+            continue;
+        }
+        let mut cursor = current.walk();
+        stack.extend(current.children(&mut cursor));
+    }
+    names
+}
+
+/// Whether the expression must be wrapped to be passed as an argument
+fn needs_parentheses(expr: tree_sitter::Node, text: &str) -> bool {
+    !matches!(
+        expr.kind(),
+        "parenthesized_expr" | "tuple_expr" | "list_expr" | "record_expr" | "string_constant_expr"
+    ) && text.contains(char::is_whitespace)
+}
+
+/// Whether `usage` is the function of a call, as opposed to passed around
+fn is_call_target(usage: tree_sitter::Node) -> bool {
+    usage
+        .parent()
+        .filter(|p| p.kind() == "function_call_expr")
+        .and_then(|p| p.child_by_field_name("target"))
+        .is_some_and(|t| t.id() == usage.id())
+}
+
+fn contains(range: Range, position: Position) -> bool {
+    let key = |p: Position| (p.line, p.character);
+    key(range.start) <= key(position) && key(position) < key(range.end)
+}
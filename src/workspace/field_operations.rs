@@ -5,8 +5,11 @@
 
 use std::collections::HashMap;
 use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
 
-use crate::binder::BoundSymbolKind;
+use crate::binder::{bind_tree, BoundSymbolKind, SymbolLinks};
+use crate::logging;
+use crate::parser::unqualified_references;
 use crate::type_checker::{FieldDefinition, TargetTypeAlias};
 
 use super::{FieldInfo, FieldUsage, FieldUsageType, RemoveFieldResult, SymbolReference, Workspace};
@@ -34,7 +37,7 @@ impl Workspace {
                 Err(_) => continue,
             };
 
-            let content = match std::fs::read_to_string(&path) {
+            let content = match self.source(&path) {
                 Ok(c) => c,
                 Err(_) => continue,
             };
@@ -479,7 +482,7 @@ impl Workspace {
     ) -> Option<(String, String, Vec<String>, Vec<FieldUsage>)> {
         // Find the field at this position
         let path = uri.to_file_path().ok()?;
-        let content = std::fs::read_to_string(&path).ok()?;
+        let content = self.source(&path).ok()?;
 
         let tree = self.parser.parse(&content)?;
         let point = tree_sitter::Point {
@@ -589,7 +592,8 @@ impl Workspace {
         let path = uri
             .to_file_path()
             .map_err(|_| anyhow::anyhow!("Invalid URI"))?;
-        let content = std::fs::read_to_string(&path)?;
+        self.ensure_not_generated(&path)?;
+        let content = self.source(&path)?;
 
         let tree = self
             .parser
@@ -661,9 +665,7 @@ impl Workspace {
                         let usage_path = Url::parse(&usage.uri)
                             .ok()
                             .and_then(|u| u.to_file_path().ok());
-                        let usage_content = usage_path
-                            .as_ref()
-                            .and_then(|p| std::fs::read_to_string(p).ok());
+                        let usage_content = usage_path.as_ref().and_then(|p| self.source(p).ok());
 
                         if let Some(ref c) = usage_content {
                             let line = c.lines().nth(range.start.line as usize).unwrap_or("");
@@ -932,6 +934,7 @@ impl Workspace {
 
             // Find all field usages in this file
             let usages = self.find_field_usages_in_tree(tree, content, field_name);
+            let mut links = None;
 
             for (node_id, range) in usages {
                 // Skip the definition itself (already added)
@@ -955,7 +958,8 @@ impl Workspace {
                             .find_field_definition(file_uri.as_str(), node, content)
                     };
 
-                    tracing::info!(
+                    tracing::debug!(
+                        target: logging::NAVIGATION,
                         "find_field_references: checking {} in {}, ref_def={:?}",
                         field_name,
                         file_uri.path(),
@@ -969,7 +973,10 @@ impl Workspace {
                         if ref_def.type_alias_name == definition.type_alias_name
                             && ref_def.module_name == definition.module_name
                         {
-                            tracing::info!("find_field_references: MATCH - adding reference");
+                            tracing::debug!(
+                                target: logging::NAVIGATION,
+                                "find_field_references: MATCH - adding reference"
+                            );
                             // Determine the kind based on parent node
                             let is_record_pattern =
                                 node.parent().map(|p| p.kind()) == Some("record_pattern");
@@ -986,39 +993,23 @@ impl Workspace {
                                 type_context: definition.type_alias_name.clone(),
                             });
 
-                            // For record pattern fields, also find all variable usages
-                            // of this name within the enclosing function scope.
-                            // BUT ONLY if there are no OTHER bindings for the same name
-                            // (case patterns, let bindings, lambda params, etc.)
+                            // A punned pattern field like `{ userId }` also binds
+                            // a variable, whose uses take the new name too
                             if is_record_pattern {
-                                if let Some(scope_node) = Self::find_enclosing_scope(node) {
-                                    // Check if the scope has other bindings that would shadow this variable
-                                    let has_other_bindings = Self::scope_has_other_bindings(
-                                        scope_node,
-                                        content,
-                                        field_name,
-                                        node.id(),
-                                    );
-
-                                    if !has_other_bindings {
-                                        let var_usages = self.find_variable_usages_in_scope(
-                                            scope_node, content, field_name,
-                                            node, // Exclude the pattern field itself
-                                        );
-                                        for (var_range, _) in var_usages {
-                                            references.push(SymbolReference {
-                                                uri: file_uri.clone(),
-                                                range: var_range,
-                                                is_definition: false,
-                                                kind: Some(BoundSymbolKind::FunctionParameter), // Treated as local variable
-                                                type_context: definition.type_alias_name.clone(),
-                                            });
-                                        }
-                                    }
+                                let links = links.get_or_insert_with(|| bind_tree(content, tree));
+                                for var_range in pattern_variable_usages(node, content, links) {
+                                    references.push(SymbolReference {
+                                        uri: file_uri.clone(),
+                                        range: var_range,
+                                        is_definition: false,
+                                        kind: Some(BoundSymbolKind::FunctionParameter), // Treated as local variable
+                                        type_context: definition.type_alias_name.clone(),
+                                    });
                                 }
                             }
                         } else {
-                            tracing::info!(
+                            tracing::debug!(
+                                target: logging::NAVIGATION,
                                 "find_field_references: NO MATCH - expected {:?}/{:?}, got {:?}/{:?}",
                                 definition.type_alias_name, definition.module_name,
                                 ref_def.type_alias_name, ref_def.module_name
@@ -1033,6 +1024,60 @@ impl Workspace {
         references
     }
 
+    /// The edits renaming the field of `field` to `new_name`, together with
+    /// the variables its punned patterns bind. Fails when one of those
+    /// variables cannot take the new name without clashing with another.
+    pub fn rename_field_edits(
+        &self,
+        field: &FieldInfo,
+        new_name: &str,
+    ) -> anyhow::Result<HashMap<Url, Vec<TextEdit>>> {
+        let references = self.find_field_references(&field.name, &field.definition);
+        for r in &references {
+            if r.kind != Some(BoundSymbolKind::RecordPatternField) {
+                continue;
+            }
+            let (Some(tree), Some(source)) = (
+                self.type_checker.get_tree(r.uri.as_str()),
+                self.type_checker.get_source(r.uri.as_str()),
+            ) else {
+                continue;
+            };
+            let point = tree_sitter::Point::new(
+                r.range.start.line as usize,
+                r.range.start.character as usize,
+            );
+            let pattern = tree
+                .root_node()
+                .descendant_for_point_range(point, point)
+                .and_then(|n| {
+                    std::iter::successors(Some(n), |n| n.parent())
+                        .find(|n| n.kind() == "lower_pattern")
+                });
+            let Some(pattern) = pattern else {
+                continue;
+            };
+            if punned_rename_clashes(pattern, source, &bind_tree(source, tree), new_name) {
+                anyhow::bail!(
+                    "`{}` is already used where `{{ {} }}` is matched at {}:{}, so the variable it binds cannot be renamed",
+                    new_name,
+                    field.name,
+                    r.uri.path().rsplit('/').next().unwrap_or_default(),
+                    r.range.start.line + 1
+                );
+            }
+        }
+
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        for r in references {
+            changes.entry(r.uri).or_default().push(TextEdit {
+                range: r.range,
+                new_text: new_name.to_string(),
+            });
+        }
+        Ok(changes)
+    }
+
     /// Find all field usages in a tree that match the given field name
     fn find_field_usages_in_tree(
         &self,
@@ -1105,3 +1150,76 @@ impl Workspace {
         }
     }
 }
+
+/// The scope where the punned pattern field `field` binds its variable
+fn binding_scope<'a>(field: Node<'a>, name: &str, links: &SymbolLinks) -> Option<Node<'a>> {
+    std::iter::successors(field.parent(), |n| n.parent()).find(|n| {
+        links
+            .get_all_symbols(n.id(), name)
+            .iter()
+            .any(|symbol| symbol.node_id == field.id())
+    })
+}
+
+/// The unqualified references to `name` under `node`
+fn references_to<'a>(node: Node<'a>, source: &str, name: &str) -> Vec<Node<'a>> {
+    unqualified_references(node, &["value_qid", "record_base_identifier"])
+        .into_iter()
+        .filter(|reference| &source[reference.byte_range()] == name)
+        .collect()
+}
+
+/// The uses of the variable the punned pattern field `field` binds, as the
+/// binder resolves them, so variables shadowing it are left alone
+fn pattern_variable_usages(field: Node, source: &str, links: &SymbolLinks) -> Vec<Range> {
+    let name = &source[field.byte_range()];
+    let Some(scope) = binding_scope(field, name, links) else {
+        return Vec::new();
+    };
+    references_to(scope, source, name)
+        .into_iter()
+        .filter(|reference| {
+            std::iter::successors(Some(*reference), |n| n.parent())
+                .map(|n| links.get_all_symbols(n.id(), name))
+                .find(|symbols| !symbols.is_empty())
+                .is_some_and(|symbols| symbols.iter().any(|s| s.node_id == field.id()))
+        })
+        .map(|reference| Range {
+            start: Position::new(
+                reference.start_position().row as u32,
+                reference.start_position().column as u32,
+            ),
+            end: Position::new(
+                reference.end_position().row as u32,
+                reference.end_position().column as u32,
+            ),
+        })
+        .collect()
+}
+
+/// Whether the variable of the punned pattern field `field` cannot be named
+/// `new_name`: Elm allows no shadowing, so the name must not be bound
+/// around or within its scope, nor already referred to within it
+fn punned_rename_clashes(field: Node, source: &str, links: &SymbolLinks, new_name: &str) -> bool {
+    let name = &source[field.byte_range()];
+    let Some(scope) = binding_scope(field, name, links) else {
+        return false;
+    };
+    let binds = |n: Node| {
+        links
+            .get_container(n.id())
+            .is_some_and(|symbols| symbols.contains_key(new_name))
+    };
+    if std::iter::successors(Some(scope), |n| n.parent()).any(binds) {
+        return true;
+    }
+    let mut stack = vec![scope];
+    while let Some(node) = stack.pop() {
+        if binds(node) {
+            return true;
+        }
+        let mut cursor = node.walk();
+        stack.extend(node.named_children(&mut cursor));
+    }
+    !references_to(scope, source, new_name).is_empty()
+}
@@ -7,6 +7,9 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use tower_lsp::lsp_types::*;
 
+use crate::document::node_range;
+use crate::logging;
+
 use super::{FileOperationResult, Workspace, LAMDERA_PROTECTED_FILES};
 
 /// Check if a file is a protected Lamdera file (must be at root of src/)
@@ -25,11 +28,44 @@ fn is_lamdera_protected_file(path: &Path) -> bool {
 }
 
 impl Workspace {
+    /// The header a new, empty file at `uri` starts with, `module Pages.Home
+    /// exposing (..)`, named after its path so that it matches. None when
+    /// the file has content or its path is no module of a source directory.
+    pub fn module_header_template(&self, uri: &Url, content: &str) -> Option<TextEdit> {
+        if !content.trim().is_empty() {
+            return None;
+        }
+        let path = uri.to_file_path().ok()?;
+        if path.extension().and_then(|e| e.to_str()) != Some("elm")
+            || !self
+                .source_dirs
+                .iter()
+                .chain(&self.test_dirs)
+                .any(|dir| path.starts_with(dir))
+        {
+            return None;
+        }
+        let module_name = self.path_to_module_name(&path);
+        let valid = module_name.split('.').all(|segment| {
+            segment.starts_with(|c: char| c.is_ascii_uppercase())
+                && segment.chars().all(|c| c.is_alphanumeric() || c == '_')
+        });
+        if !valid {
+            return None;
+        }
+        let end = Position::new(content.lines().count() as u32, 0);
+        Some(TextEdit {
+            range: Range::new(Position::new(0, 0), end),
+            new_text: format!("module {} exposing (..)\n\n\n", module_name),
+        })
+    }
+
     /// Rename a file and update its module declaration + all imports
     pub fn rename_file(&self, uri: &Url, new_name: &str) -> anyhow::Result<FileOperationResult> {
         let old_path = uri
             .to_file_path()
             .map_err(|_| anyhow::anyhow!("Invalid file URI"))?;
+        self.ensure_not_generated(&old_path)?;
 
         // Block renaming protected Lamdera files (only at root of src/)
         if self.is_lamdera_project && is_lamdera_protected_file(&old_path) {
@@ -47,7 +83,7 @@ impl Workspace {
         }
 
         // Get old module name from file content
-        let content = std::fs::read_to_string(&old_path)?;
+        let content = self.source(&old_path)?;
         let old_module_name = extract_module_name_from_content(&content)
             .ok_or_else(|| anyhow::anyhow!("Could not extract module name from file"))?;
 
@@ -104,6 +140,7 @@ impl Workspace {
         let old_path = uri
             .to_file_path()
             .map_err(|_| anyhow::anyhow!("Invalid file URI"))?;
+        self.ensure_not_generated(&old_path)?;
 
         // Block moving protected Lamdera files (only at root of src/)
         if self.is_lamdera_project && is_lamdera_protected_file(&old_path) {
@@ -121,7 +158,7 @@ impl Workspace {
         }
 
         // Get old module name from file content
-        let content = std::fs::read_to_string(&old_path)?;
+        let content = self.source(&old_path)?;
         let old_module_name = extract_module_name_from_content(&content)
             .ok_or_else(|| anyhow::anyhow!("Could not extract module name from file"))?;
 
@@ -134,6 +171,7 @@ impl Workspace {
         } else {
             self.root_path.join(target_path)
         };
+        self.ensure_not_generated(&new_path)?;
 
         // Collect all edits
         let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
@@ -161,7 +199,10 @@ impl Workspace {
         })
     }
 
-    /// Update all imports of old_module to new_module across the workspace
+    /// Update all references to old_module across the workspace: the module
+    /// name in `import` lines (aliases and exposing lists are kept) and every
+    /// `OldModule.x` qualifier in expressions, patterns and type annotations.
+    /// Returns the number of files updated.
     fn update_imports_for_rename(
         &self,
         old_module: &str,
@@ -169,7 +210,6 @@ impl Workspace {
         skip_uri: &Url,
         changes: &mut HashMap<Url, Vec<TextEdit>>,
     ) -> anyhow::Result<usize> {
-        let import_pattern = format!("import {}", old_module);
         let mut files_updated = 0;
 
         for module in self.modules.values() {
@@ -186,78 +226,33 @@ impl Workspace {
                 continue;
             }
 
-            let content = std::fs::read_to_string(&module.path)?;
-
-            // Find all import statements for the old module
-            for (line_num, line) in content.lines().enumerate() {
-                let trimmed = line.trim();
-                if trimmed.starts_with(&import_pattern) {
-                    // Check it's not a prefix match (e.g., "import Foo" shouldn't match "import FooBar")
-                    let after_import = &trimmed[import_pattern.len()..];
-                    if after_import.is_empty()
-                        || after_import.starts_with(' ')
-                        || after_import.starts_with('\n')
-                        || after_import.starts_with('\t')
-                    {
-                        let line_start = line.find("import").unwrap_or(0);
-                        let old_end = line_start + "import ".len() + old_module.len();
-
-                        changes.entry(file_uri.clone()).or_default().push(TextEdit {
-                            range: Range {
-                                start: Position {
-                                    line: line_num as u32,
-                                    character: (line_start + "import ".len()) as u32,
-                                },
-                                end: Position {
-                                    line: line_num as u32,
-                                    character: old_end as u32,
-                                },
-                            },
-                            new_text: new_module.to_string(),
-                        });
-
-                        files_updated += 1;
-                    }
-                }
-
-                // Also check for qualified references like "OldModule.function"
-                // This handles cases where the module is used with qualification
-                let qualified_pattern = format!("{}.", old_module);
-                if trimmed.contains(&qualified_pattern)
-                    && !trimmed.starts_with("import ")
-                    && !trimmed.starts_with("module ")
-                {
-                    // Find all occurrences in the line
-                    let mut search_start = 0;
-                    while let Some(pos) = line[search_start..].find(&qualified_pattern) {
-                        let actual_pos = search_start + pos;
-
-                        // Make sure it's not part of a larger identifier
-                        let before_ok = actual_pos == 0
-                            || !line
-                                .chars()
-                                .nth(actual_pos - 1)
-                                .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.');
-
-                        if before_ok {
-                            changes.entry(file_uri.clone()).or_default().push(TextEdit {
-                                range: Range {
-                                    start: Position {
-                                        line: line_num as u32,
-                                        character: actual_pos as u32,
-                                    },
-                                    end: Position {
-                                        line: line_num as u32,
-                                        character: (actual_pos + old_module.len()) as u32,
-                                    },
-                                },
-                                new_text: new_module.to_string(),
-                            });
-                        }
-
-                        search_start = actual_pos + qualified_pattern.len();
-                    }
-                }
+            let import = match module.imports.iter().find(|i| i.module_name == old_module) {
+                Some(import) => import,
+                None => continue,
+            };
+
+            let content = self.source(&module.path)?;
+            let tree = match self.parser.parse(&content) {
+                Some(tree) => tree,
+                None => continue,
+            };
+
+            // With `import Old as Alias`, usages are qualified by the alias
+            // and stay as they are
+            let rewrite_qualifiers = import.alias.is_none();
+            let mut edits = Vec::new();
+            collect_module_name_edits(
+                tree.root_node(),
+                &content,
+                old_module,
+                new_module,
+                rewrite_qualifiers,
+                &mut edits,
+            );
+
+            if !edits.is_empty() {
+                files_updated += 1;
+                changes.entry(file_uri).or_default().extend(edits);
             }
         }
 
@@ -265,6 +260,64 @@ impl Workspace {
     }
 }
 
+/// Edits replacing `old_module` in import clauses and in qualified names below `node`
+fn collect_module_name_edits(
+    node: tree_sitter::Node,
+    source: &str,
+    old_module: &str,
+    new_module: &str,
+    rewrite_qualifiers: bool,
+    edits: &mut Vec<TextEdit>,
+) {
+    match node.kind() {
+        "module_declaration" => {}
+        "import_clause" => {
+            if let Some(name) = node.child_by_field_name("moduleName") {
+                if &source[name.byte_range()] == old_module {
+                    edits.push(TextEdit {
+                        range: node_range(name),
+                        new_text: new_module.to_string(),
+                    });
+                }
+            }
+        }
+        "value_qid" | "upper_case_qid" => {
+            if !rewrite_qualifiers {
+                return;
+            }
+            let mut cursor = node.walk();
+            let segments: Vec<tree_sitter::Node> = node
+                .children(&mut cursor)
+                .filter(|c| c.kind() != "dot")
+                .collect();
+            // Everything before the last segment is the module qualifier
+            if let [first, .., _] = segments.as_slice() {
+                let last_qualifier = segments[segments.len() - 2];
+                let qualifier = &source[first.start_byte()..last_qualifier.end_byte()];
+                if qualifier == old_module {
+                    edits.push(TextEdit {
+                        range: Range::new(node_range(*first).start, node_range(last_qualifier).end),
+                        new_text: new_module.to_string(),
+                    });
+                }
+            }
+        }
+        _ => {
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                collect_module_name_edits(
+                    child,
+                    source,
+                    old_module,
+                    new_module,
+                    rewrite_qualifiers,
+                    edits,
+                );
+            }
+        }
+    }
+}
+
 /// Extract module name from file content using simple string parsing
 pub(crate) fn extract_module_name_from_content(content: &str) -> Option<String> {
     for line in content.lines() {
@@ -325,6 +378,7 @@ fn path_string_to_module_name(root_path: &Path, path_str: &str) -> String {
     let path = Path::new(path_str);
 
     tracing::debug!(
+        target: logging::REFACTOR,
         "path_string_to_module_name: path_str={}, root_path={}",
         path_str,
         root_path.display()
@@ -334,10 +388,14 @@ fn path_string_to_module_name(root_path: &Path, path_str: &str) -> String {
     let relative_path = if path.is_absolute() {
         // Try to strip workspace root
         if let Ok(rel) = path.strip_prefix(root_path) {
-            tracing::debug!("  Stripped prefix, relative={}", rel.display());
+            tracing::debug!(
+                target: logging::REFACTOR,
+                "  Stripped prefix, relative={}",
+                rel.display()
+            );
             rel.to_path_buf()
         } else {
-            tracing::debug!("  Could not strip prefix");
+            tracing::debug!(target: logging::REFACTOR, "  Could not strip prefix");
             // Fallback: just use the path as-is
             path.to_path_buf()
         }
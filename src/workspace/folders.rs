@@ -0,0 +1,111 @@
+//! One `Workspace` per Elm project in the workspace folders open in the
+//! editor: the folder itself, and every subdirectory with its own elm.json,
+//! as in a monorepo. Files belong to the deepest project containing them.
+
+use std::path::{Path, PathBuf};
+use tower_lsp::lsp_types::Url;
+use walkdir::WalkDir;
+
+use super::Workspace;
+use crate::config::Settings;
+
+/// Directories never searched for nested projects
+const SKIPPED_DIRS: &[&str] = &["elm-stuff", "node_modules"];
+
+/// The Elm projects of a workspace folder: the folder itself, then each
+/// subdirectory holding an elm.json
+pub fn discover_projects(folder: &Path) -> Vec<PathBuf> {
+    let mut projects = vec![folder.to_path_buf()];
+    let nested = WalkDir::new(folder)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            !(entry.file_type().is_dir()
+                && (name.starts_with('.') || SKIPPED_DIRS.contains(&name.as_ref())))
+        })
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && entry.file_name() == "elm.json")
+        .filter_map(|entry| entry.path().parent().map(Path::to_path_buf))
+        .filter(|dir| dir != folder);
+    projects.extend(nested);
+    projects
+}
+
+/// The workspaces of all open folders, looked up by the file a request is about
+#[derive(Default)]
+pub struct Workspaces {
+    folders: Vec<Workspace>,
+    /// Settings given to workspaces of newly opened folders
+    pub settings: Settings,
+}
+
+impl Workspaces {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a project's workspace, replacing any previous one for the same root
+    pub fn add(&mut self, workspace: Workspace) {
+        self.folders.retain(|w| w.root_path != workspace.root_path);
+        self.folders.push(workspace);
+    }
+
+    /// Dispose the workspaces of the folder `folder`, returning whether there
+    /// were any
+    pub fn remove(&mut self, folder: &Path) -> bool {
+        let before = self.folders.len();
+        self.folders.retain(|w| w.folder != folder);
+        self.folders.len() != before
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.folders.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.folders.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Workspace> {
+        self.folders.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Workspace> {
+        self.folders.iter_mut()
+    }
+
+    /// The workspace owning `uri`: the folder with the deepest root containing
+    /// the file, or the first folder for files outside every root
+    pub fn for_uri(&self, uri: &Url) -> Option<&Workspace> {
+        let path = uri.to_file_path().ok();
+        self.index_for(path.as_deref()).map(|i| &self.folders[i])
+    }
+
+    pub fn for_uri_mut(&mut self, uri: &Url) -> Option<&mut Workspace> {
+        let path = uri.to_file_path().ok();
+        self.index_for(path.as_deref())
+            .map(|i| &mut self.folders[i])
+    }
+
+    pub fn for_path_mut(&mut self, path: &Path) -> Option<&mut Workspace> {
+        self.index_for(Some(path)).map(|i| &mut self.folders[i])
+    }
+
+    fn index_for(&self, path: Option<&Path>) -> Option<usize> {
+        let owner = path.and_then(|path| {
+            self.folders
+                .iter()
+                .enumerate()
+                .filter(|(_, w)| path.starts_with(&w.root_path))
+                .max_by_key(|(_, w)| w.root_path.components().count())
+                .map(|(i, _)| i)
+        });
+        owner.or(if self.folders.is_empty() {
+            None
+        } else {
+            Some(0)
+        })
+    }
+}
@@ -0,0 +1,159 @@
+//! `[glsl| ... |]` shader blocks.
+//!
+//! The parser keeps a block as one opaque expression, so nothing in it is
+//! taken for Elm: hover and go to definition inside it only know the
+//! shader's inputs, the `attribute`, `uniform` and `varying` declarations.
+//! Hovering one of their names shows its declaration, and go to definition
+//! jumps to it. Hovering the Elm value a shader is bound to, like
+//! `vertexShader` in `WebGL.entity vertexShader ...`, lists those inputs.
+
+use tower_lsp::lsp_types::*;
+use tree_sitter::{Node, Point, Tree};
+
+use crate::document::offset_to_position;
+
+use super::declarations::value_declaration;
+use super::Workspace;
+
+const BLOCK_START: &str = "[glsl|";
+const BLOCK_END: &str = "|]";
+
+/// An input declared in a shader, e.g. `uniform mat4 perspective;`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlslInput {
+    /// `attribute`, `uniform` or `varying`
+    pub qualifier: String,
+    pub glsl_type: String,
+    pub name: String,
+    /// The name, where declared
+    pub range: Range,
+}
+
+impl GlslInput {
+    /// The declaration, as written in GLSL
+    pub fn declaration(&self) -> String {
+        format!("{} {} {};", self.qualifier, self.glsl_type, self.name)
+    }
+}
+
+impl Workspace {
+    /// Whether `position` is inside a GLSL block of the module at `uri`
+    pub fn in_glsl_block(&self, uri: &Url, position: Position) -> bool {
+        self.type_checker
+            .get_tree(uri.as_str())
+            .is_some_and(|tree| glsl_block_at(tree, position).is_some())
+    }
+
+    /// The input of a GLSL block that the name at `position` refers to, and
+    /// the range of that name
+    pub fn glsl_input_at(&self, uri: &Url, position: Position) -> Option<(GlslInput, Range)> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let block = glsl_block_at(tree, position)?;
+        let (word, range) = word_at(source, position)?;
+        let input = glsl_inputs(block, source)
+            .into_iter()
+            .find(|input| input.name == word)?;
+        Some((input, range))
+    }
+
+    /// The inputs of the shader the top-level value `name` of the module at
+    /// `uri` is bound to, as a GLSL code block of Markdown
+    pub fn shader_inputs(&self, uri: &Url, name: &str) -> Option<String> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let body = value_declaration(tree, source, name)?.child_by_field_name("body")?;
+        if body.kind() != "glsl_code_expr" {
+            return None;
+        }
+        let inputs = glsl_inputs(body, source);
+        if inputs.is_empty() {
+            return None;
+        }
+        let declarations: Vec<String> = inputs.iter().map(GlslInput::declaration).collect();
+        Some(format!(
+            "*Shader inputs*\n\n```glsl\n{}\n```",
+            declarations.join("\n")
+        ))
+    }
+}
+
+/// The GLSL block around `position`
+fn glsl_block_at(tree: &Tree, position: Position) -> Option<Node<'_>> {
+    let point = Point::new(position.line as usize, position.character as usize);
+    let mut node = tree.root_node().descendant_for_point_range(point, point)?;
+    loop {
+        if node.kind() == "glsl_code_expr" {
+            return Some(node);
+        }
+        node = node.parent()?;
+    }
+}
+
+/// The inputs `block` declares, in order
+fn glsl_inputs(block: Node, source: &str) -> Vec<GlslInput> {
+    let text = &source[block.byte_range()];
+    let Some(code) = text
+        .strip_prefix(BLOCK_START)
+        .map(|code| code.strip_suffix(BLOCK_END).unwrap_or(code))
+    else {
+        return Vec::new();
+    };
+    let code_start = block.start_byte() + BLOCK_START.len();
+
+    let mut inputs = Vec::new();
+    let mut line_start = code_start;
+    for line in code.split_inclusive('\n') {
+        let statement = line.split("//").next().unwrap_or(line);
+        let statement = statement.split(';').next().unwrap_or(statement);
+        // Precision qualifiers sit between the storage qualifier and the type
+        let words: Vec<&str> = statement
+            .split_whitespace()
+            .filter(|word| !matches!(*word, "lowp" | "mediump" | "highp"))
+            .collect();
+        if let [qualifier @ ("attribute" | "uniform" | "varying"), glsl_type, name] =
+            words.as_slice()
+        {
+            let name = name.split('[').next().unwrap_or(name);
+            if let Some(column) = statement.rfind(name) {
+                let start = line_start + column;
+                inputs.push(GlslInput {
+                    qualifier: qualifier.to_string(),
+                    glsl_type: glsl_type.to_string(),
+                    name: name.to_string(),
+                    range: Range::new(
+                        offset_to_position(source, start),
+                        offset_to_position(source, start + name.len()),
+                    ),
+                });
+            }
+        }
+        line_start += line.len();
+    }
+    inputs
+}
+
+/// The GLSL identifier at `position` and its range
+fn word_at(source: &str, position: Position) -> Option<(&str, Range)> {
+    let line = source.lines().nth(position.line as usize)?;
+    let column = (position.character as usize).min(line.len());
+    if !line.is_char_boundary(column) {
+        return None;
+    }
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let start = line[..column]
+        .rfind(|c: char| !is_word(c))
+        .map_or(0, |i| i + 1);
+    let end = line[column..]
+        .find(|c: char| !is_word(c))
+        .map_or(line.len(), |i| column + i);
+    (start < end).then(|| {
+        (
+            &line[start..end],
+            Range::new(
+                Position::new(position.line, start as u32),
+                Position::new(position.line, end as u32),
+            ),
+        )
+    })
+}
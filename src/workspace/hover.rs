@@ -0,0 +1,50 @@
+//! What hovering a declared name shows: its annotation, or `name : Type`
+//! with the type inferred for a value without one, and its `{-| ... -}` doc
+//! comment, for workspace and package declarations alike.
+
+use tower_lsp::lsp_types::Url;
+
+use super::declarations::value_declaration;
+use super::{GlobalSymbol, Workspace};
+
+impl Workspace {
+    /// The annotation of `symbol`, or its inferred type when it has none
+    pub fn hover_signature(&self, symbol: &GlobalSymbol) -> Option<String> {
+        symbol
+            .signature
+            .clone()
+            .or_else(|| self.inferred_signature(&symbol.definition_uri, &symbol.name))
+    }
+
+    /// `name : Type` for the top-level value `name` of the module at `uri`,
+    /// with the type the checker infers. None when part of it is unknown.
+    pub fn inferred_signature(&self, uri: &Url, name: &str) -> Option<String> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let declaration = value_declaration(tree, source, name)?;
+        let ty = self.type_checker.get_type(uri.as_str(), declaration.id())?;
+        ty.is_known()
+            .then(|| format!("{} : {}", name, ty.with_readable_vars()))
+    }
+
+    /// The doc comment above the declaration of `symbol`. Package modules
+    /// are not indexed with their comments, so their file is parsed.
+    pub fn symbol_documentation(&self, symbol: &GlobalSymbol) -> Option<String> {
+        if let Some(module) = self.modules.get(&symbol.module_name) {
+            return module
+                .symbols
+                .iter()
+                .find(|s| s.name == symbol.name)?
+                .documentation
+                .clone();
+        }
+        let path = symbol.definition_uri.to_file_path().ok()?;
+        let source = self.source(&path).ok()?;
+        let tree = self.parser.parse(&source)?;
+        self.parser
+            .extract_symbols(&tree, &source)
+            .into_iter()
+            .find(|s| s.name == symbol.name)?
+            .documentation
+    }
+}
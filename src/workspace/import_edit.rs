@@ -0,0 +1,278 @@
+//! Adding imports, shared by every feature that needs one.
+//!
+//! The edit is built from the syntax tree rather than from lines, so doc
+//! comments and files without blank lines do not throw it off. A module that
+//! is already imported gets the missing names added to its exposing list; a
+//! new import goes where it sorts among the others, or after the module
+//! header and its doc comment when there are none.
+
+use tower_lsp::lsp_types::*;
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::item_key;
+use crate::document::node_range;
+
+use super::module_docs::module_doc_comment;
+use super::Workspace;
+
+/// An import a feature needs: the module, its alias and the names it must
+/// expose
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportSpec {
+    pub module_name: String,
+    pub alias: Option<String>,
+    pub exposing: Vec<String>,
+}
+
+impl ImportSpec {
+    /// `import Module`
+    pub fn module(module_name: &str) -> Self {
+        Self {
+            module_name: module_name.to_string(),
+            alias: None,
+            exposing: Vec::new(),
+        }
+    }
+
+    /// `import Module exposing (items)`
+    pub fn exposing(module_name: &str, items: &[&str]) -> Self {
+        Self {
+            module_name: module_name.to_string(),
+            alias: None,
+            exposing: items.iter().map(|item| item.to_string()).collect(),
+        }
+    }
+
+    /// An import written as text, with or without the `import` keyword:
+    /// `Html.Attributes as A exposing (class, Msg(..))`
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let text = text.strip_prefix("import ").unwrap_or(text);
+        let (head, exposing) = match text.split_once(" exposing ") {
+            Some((head, list)) => {
+                let list = list.trim().strip_prefix('(')?.strip_suffix(')')?;
+                let items = split_items(list);
+                (head, items)
+            }
+            None => (text, Vec::new()),
+        };
+        let mut words = head.split_whitespace();
+        let module_name = words.next()?.to_string();
+        let alias = match (words.next(), words.next()) {
+            (Some("as"), Some(alias)) => Some(alias.to_string()),
+            (None, _) => None,
+            _ => return None,
+        };
+        Some(Self {
+            module_name,
+            alias,
+            exposing,
+        })
+    }
+
+    /// The import as a line of code, without the line break
+    pub fn line(&self) -> String {
+        let mut line = format!("import {}", self.module_name);
+        if let Some(alias) = &self.alias {
+            line.push_str(&format!(" as {}", alias));
+        }
+        if !self.exposing.is_empty() {
+            line.push_str(&format!(" exposing ({})", self.exposing.join(", ")));
+        }
+        line
+    }
+}
+
+impl Workspace {
+    /// The edits making the file at `uri` import what `specs` ask for
+    pub fn import_edits(&self, uri: &Url, specs: &[ImportSpec]) -> Vec<TextEdit> {
+        let Some(source) = self.read_file_content(uri) else {
+            return Vec::new();
+        };
+        match self.parser.parse(&source) {
+            Some(tree) => import_edits(&tree, &source, specs),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// The edits making `source` import what `specs` ask for. Specs for the same
+/// module are merged, and new imports going to the same place share one edit.
+pub fn import_edits(tree: &Tree, source: &str, specs: &[ImportSpec]) -> Vec<TextEdit> {
+    let mut merged: Vec<ImportSpec> = Vec::new();
+    for spec in specs {
+        let Some(same) = merged
+            .iter_mut()
+            .find(|m| m.module_name == spec.module_name && m.alias == spec.alias)
+        else {
+            merged.push(spec.clone());
+            continue;
+        };
+        for item in &spec.exposing {
+            match same
+                .exposing
+                .iter()
+                .position(|e| item_key(e) == item_key(item))
+            {
+                Some(i) if item.ends_with("(..)") => same.exposing[i] = item.clone(),
+                Some(_) => {}
+                None => same.exposing.push(item.clone()),
+            }
+        }
+    }
+    merged.sort_by(|a, b| a.module_name.cmp(&b.module_name));
+
+    let mut edits: Vec<TextEdit> = Vec::new();
+    for edit in merged
+        .iter()
+        .filter_map(|spec| import_edit(tree, source, spec))
+    {
+        match edits.last_mut() {
+            Some(last) if last.range == edit.range && edit.range.start == edit.range.end => {
+                last.new_text
+                    .push_str(edit.new_text.trim_start_matches('\n'));
+            }
+            _ => edits.push(edit),
+        }
+    }
+    edits
+}
+
+/// The edit making `source` import what `spec` asks for, or None when it
+/// already does
+pub fn import_edit(tree: &Tree, source: &str, spec: &ImportSpec) -> Option<TextEdit> {
+    let root = tree.root_node();
+    let header = root.child_by_field_name("moduleDeclaration");
+    let own_name = header
+        .and_then(|h| h.child_by_field_name("name"))
+        .map(|n| &source[n.byte_range()]);
+    if own_name == Some(spec.module_name.as_str()) {
+        return None;
+    }
+
+    let mut cursor = root.walk();
+    let imports: Vec<Node> = root
+        .named_children(&mut cursor)
+        .filter(|n| n.kind() == "import_clause")
+        .collect();
+    let existing = imports.iter().find(|import| {
+        module_name(**import, source) == Some(spec.module_name.as_str())
+            && spec
+                .alias
+                .as_ref()
+                .is_none_or(|alias| alias_of(**import, source) == Some(alias.as_str()))
+    });
+    if let Some(import) = existing {
+        return extend_exposing(*import, source, &spec.exposing);
+    }
+
+    let line = spec.line();
+    // Before the first import that sorts after it, or after the last one
+    if let Some(next) = imports
+        .iter()
+        .find(|import| module_name(**import, source) > Some(spec.module_name.as_str()))
+    {
+        let at = Position::new(next.start_position().row as u32, 0);
+        return Some(insert(at, format!("{}\n", line)));
+    }
+    if let Some(last) = imports.last() {
+        let at = Position::new(last.end_position().row as u32 + 1, 0);
+        return Some(insert(at, format!("{}\n", line)));
+    }
+
+    // The first import: after the header and the module's doc comment
+    let above = module_doc_comment(tree, source).or(header)?;
+    let at = Position::new(above.end_position().row as u32 + 1, 0);
+    Some(insert(at, format!("\n{}\n", line)))
+}
+
+/// The edit adding `items` to an existing import's exposing list
+fn extend_exposing(import: Node, source: &str, items: &[String]) -> Option<TextEdit> {
+    let Some(exposing) = import.child_by_field_name("exposing") else {
+        if items.is_empty() {
+            return None;
+        }
+        return Some(insert(
+            node_range(import).end,
+            format!(" exposing ({})", items.join(", ")),
+        ));
+    };
+    if exposing.child_by_field_name("doubleDot").is_some() {
+        return None;
+    }
+
+    let mut cursor = exposing.walk();
+    let mut exposed: Vec<String> = exposing
+        .named_children(&mut cursor)
+        .filter(|n| {
+            matches!(
+                n.kind(),
+                "exposed_value" | "exposed_type" | "exposed_operator"
+            )
+        })
+        .map(|n| source[n.byte_range()].split_whitespace().collect())
+        .collect();
+    let mut changed = false;
+    for item in items {
+        match exposed.iter().position(|e| item_key(e) == item_key(item)) {
+            // `Msg` becomes `Msg(..)` when the constructors are needed
+            Some(i) if item.ends_with("(..)") && !exposed[i].ends_with("(..)") => {
+                exposed[i] = item.clone();
+                changed = true;
+            }
+            Some(_) => {}
+            None => {
+                exposed.push(item.clone());
+                changed = true;
+            }
+        }
+    }
+    changed.then(|| TextEdit {
+        range: node_range(exposing),
+        new_text: format!("exposing ({})", exposed.join(", ")),
+    })
+}
+
+fn module_name<'a>(import: Node, source: &'a str) -> Option<&'a str> {
+    import
+        .child_by_field_name("moduleName")
+        .map(|n| &source[n.byte_range()])
+}
+
+fn alias_of<'a>(import: Node, source: &'a str) -> Option<&'a str> {
+    import
+        .child_by_field_name("asClause")
+        .and_then(|a| a.child_by_field_name("name"))
+        .map(|n| &source[n.byte_range()])
+}
+
+/// The items of an exposing list, keeping `Msg(..)` and `(+)` whole
+fn split_items(list: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for c in list.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        if !c.is_whitespace() {
+            current.push(c);
+        }
+    }
+    items.push(current);
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+fn insert(at: Position, new_text: String) -> TextEdit {
+    TextEdit {
+        range: Range::new(at, at),
+        new_text,
+    }
+}
@@ -0,0 +1,113 @@
+//! Reverse import graph: which modules import a given module.
+//!
+//! Kept up to date by `Workspace::update_file` and `retract_file`, so a change
+//! to one module only touches that module's edges. Used to re-check dependents
+//! on save and, through `elm.getImporters`, for impact analysis.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// A module that imports the queried module, directly (depth 1) or transitively
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Importer {
+    pub module: String,
+    pub depth: usize,
+}
+
+/// Edges from each module to the modules it imports, and the reverse
+#[derive(Debug, Default)]
+pub struct ImportGraph {
+    imports: HashMap<String, HashSet<String>>,
+    importers: HashMap<String, HashSet<String>>,
+}
+
+impl ImportGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the imports of `module`, replacing any previous ones
+    pub fn set_imports(&mut self, module: &str, imported: impl IntoIterator<Item = String>) {
+        self.remove_module(module);
+        let imported: HashSet<String> = imported.into_iter().collect();
+        for target in &imported {
+            self.importers
+                .entry(target.clone())
+                .or_default()
+                .insert(module.to_string());
+        }
+        self.imports.insert(module.to_string(), imported);
+    }
+
+    /// Forget the imports of `module`. Edges from its importers stay, so they
+    /// reconnect if the module comes back.
+    pub fn remove_module(&mut self, module: &str) {
+        for target in self.imports.remove(module).unwrap_or_default() {
+            if let Some(importers) = self.importers.get_mut(&target) {
+                importers.remove(module);
+                if importers.is_empty() {
+                    self.importers.remove(&target);
+                }
+            }
+        }
+    }
+
+    /// Modules that import `module` directly
+    pub fn direct_importers(&self, module: &str) -> Vec<String> {
+        let mut importers: Vec<String> = self
+            .importers
+            .get(module)
+            .map(|set| set.iter().cloned().collect())
+            .unwrap_or_default();
+        importers.sort();
+        importers
+    }
+
+    /// Modules that `module` imports directly
+    pub fn imports_of(&self, module: &str) -> Vec<String> {
+        let mut imports: Vec<String> = self
+            .imports
+            .get(module)
+            .map(|set| set.iter().cloned().collect())
+            .unwrap_or_default();
+        imports.sort();
+        imports
+    }
+
+    /// Modules that import `module` directly or through at most `max_depth`
+    /// hops, nearest first. The module itself is not included.
+    pub fn transitive_importers(&self, module: &str, max_depth: usize) -> Vec<String> {
+        self.importers(module, max_depth)
+            .into_iter()
+            .map(|importer| importer.module)
+            .collect()
+    }
+
+    /// Like `transitive_importers`, with the number of hops to each importer
+    pub fn importers(&self, module: &str, max_depth: usize) -> Vec<Importer> {
+        let mut seen: HashSet<&str> = HashSet::from([module]);
+        let mut queue: VecDeque<(&str, usize)> = VecDeque::from([(module, 0)]);
+        let mut result = Vec::new();
+
+        while let Some((current, depth)) = queue.pop_front() {
+            if depth == max_depth {
+                continue;
+            }
+            let mut next: Vec<&str> = self
+                .importers
+                .get(current)
+                .map(|set| set.iter().map(|s| s.as_str()).collect())
+                .unwrap_or_default();
+            next.sort();
+            for importer in next {
+                if seen.insert(importer) {
+                    result.push(Importer {
+                        module: importer.to_string(),
+                        depth: depth + 1,
+                    });
+                    queue.push_back((importer, depth + 1));
+                }
+            }
+        }
+        result
+    }
+}
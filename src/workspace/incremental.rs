@@ -0,0 +1,111 @@
+//! Incremental recomputation of per-file derived data.
+//!
+//! Every indexed file is an input identified by its URI. For each input we
+//! remember the hash of the content its derived data (symbols, imports,
+//! exposing, references, type info) was computed from, the revision at which
+//! that happened, and the index keys the file contributed. Re-submitting
+//! unchanged content is a no-op, and a changed file only retracts and
+//! recomputes its own contributions instead of sweeping every index.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use tower_lsp::lsp_types::Url;
+
+/// Bookkeeping for one indexed file
+#[derive(Debug, Clone)]
+pub struct FileInput {
+    /// Revision at which the derived data was last computed
+    pub revision: u64,
+    /// Hash of the content the derived data was computed from
+    pub content_hash: u64,
+    /// Module name the file declared when it was indexed
+    pub module_name: String,
+    /// Keys this file added to `Workspace::symbols`
+    pub symbol_keys: Vec<String>,
+    /// Keys this file added to `Workspace::references`
+    pub reference_keys: Vec<String>,
+}
+
+/// Tracks which files are up to date and what they contributed to the indexes
+#[derive(Debug, Default)]
+pub struct IncrementalIndex {
+    revision: u64,
+    files: HashMap<Url, FileInput>,
+}
+
+impl IncrementalIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hash file content for change detection
+    pub fn hash_content(content: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Current global revision (bumped every time a file is recomputed or removed)
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// True if the derived data for `uri` was computed from content with this hash
+    pub fn is_fresh(&self, uri: &Url, content_hash: u64) -> bool {
+        self.files
+            .get(uri)
+            .is_some_and(|input| input.content_hash == content_hash)
+    }
+
+    /// Get the recorded input for a file
+    pub fn get(&self, uri: &Url) -> Option<&FileInput> {
+        self.files.get(uri)
+    }
+
+    /// Record freshly computed derived data for a file, returning its new revision
+    pub fn record(
+        &mut self,
+        uri: Url,
+        content_hash: u64,
+        module_name: String,
+        symbol_keys: Vec<String>,
+        reference_keys: Vec<String>,
+    ) -> u64 {
+        self.revision += 1;
+        self.files.insert(
+            uri,
+            FileInput {
+                revision: self.revision,
+                content_hash,
+                module_name,
+                symbol_keys,
+                reference_keys,
+            },
+        );
+        self.revision
+    }
+
+    /// Forget a file, returning what it contributed so the caller can retract it
+    pub fn retract(&mut self, uri: &Url) -> Option<FileInput> {
+        let removed = self.files.remove(uri);
+        if removed.is_some() {
+            self.revision += 1;
+        }
+        removed
+    }
+
+    /// Files whose derived data was recomputed after the given revision
+    pub fn changed_since(&self, revision: u64) -> impl Iterator<Item = &Url> {
+        self.files
+            .iter()
+            .filter(move |(_, input)| input.revision > revision)
+            .map(|(uri, _)| uri)
+    }
+
+    /// Drop all recorded inputs (used when the workspace is re-initialized)
+    pub fn clear(&mut self) {
+        self.files.clear();
+        self.revision += 1;
+    }
+}
@@ -0,0 +1,327 @@
+//! Moving a let binding to where it belongs: out to an outer `let` or the
+//! top level when it uses nothing bound in between, or into the one branch
+//! that uses it.
+//!
+//! The binder tells which scope each name a binding refers to comes from.
+//! A binding can leave every scope it takes nothing from, so it floats past
+//! parameters, lambdas and case branches until one of them binds a name it
+//! uses. Sinking goes the other way: when only one `case` or `if` branch of
+//! the `in` expression uses the binding, it moves into that branch and is
+//! no longer computed on the others. It never sinks into a lambda or a
+//! function, where it would be computed on every call.
+
+use std::collections::HashSet;
+
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use super::nested_case::{line_indent, reindent, spaces};
+use super::Workspace;
+use crate::analysis::binding_removal;
+use crate::binder::{bind_tree, SymbolLinks};
+use crate::document::node_range;
+use crate::parser::{annotation_of, unqualified_references};
+
+/// A named let binding and the `let` declaring it
+struct Binding<'a> {
+    name: String,
+    declaration: Node<'a>,
+    annotation: Option<Node<'a>>,
+    let_in: Node<'a>,
+    body: Node<'a>,
+}
+
+impl Workspace {
+    /// The title and edits moving the let binding named at `position` as
+    /// far out as the names it uses allow
+    pub fn float_binding(&self, uri: &Url, position: Position) -> Option<(String, Vec<TextEdit>)> {
+        let source = self.read_file_content(uri)?;
+        let tree = self.parser.parse(&source)?;
+        let binding = binding_at(tree.root_node(), &source, position)?;
+        let links = bind_tree(&source, &tree);
+        let scopes = dependencies(&binding, &source, &links);
+        if scopes.contains(&binding.let_in.id()) {
+            return None;
+        }
+
+        // The outermost `let` or the file reachable without leaving a scope
+        // the binding uses
+        let mut target = None;
+        for scope in std::iter::successors(binding.let_in.parent(), |n| n.parent()) {
+            if matches!(scope.kind(), "let_in_expr" | "file") {
+                target = Some(scope);
+            }
+            if scope.kind() == "file" || scopes.contains(&scope.id()) {
+                break;
+            }
+        }
+        let target = target?;
+        if is_bound_elsewhere(target, &binding, &links) {
+            return None;
+        }
+
+        let removal = TextEdit {
+            range: binding_removal(binding.let_in, binding.declaration, binding.body, &source),
+            new_text: String::new(),
+        };
+        // The declaration or `in` expression of the target holding the binding
+        let holder = std::iter::successors(Some(binding.let_in), |n| n.parent())
+            .find(|n| n.parent().is_some_and(|p| p.id() == target.id()))?;
+        let (title, insertion) = if target.kind() == "file" {
+            let end = node_range(holder).end;
+            (
+                format!("Move `{}` to the top level", binding.name),
+                TextEdit {
+                    range: Range::new(end, end),
+                    new_text: format!("\n\n\n{}", binding_text(&binding, &source, 0)),
+                },
+            )
+        } else if target.child_by_field_name("body") == Some(holder) {
+            let mut cursor = target.walk();
+            let last = target
+                .children_by_field_name("valueDeclaration", &mut cursor)
+                .last()?;
+            let column = last.start_position().column;
+            let end = node_range(last).end;
+            (
+                format!("Move `{}` out to the enclosing let", binding.name),
+                TextEdit {
+                    range: Range::new(end, end),
+                    new_text: format!(
+                        "\n\n{}{}",
+                        spaces(column),
+                        binding_text(&binding, &source, column)
+                    ),
+                },
+            )
+        } else {
+            let first = annotation_of(holder, &source).unwrap_or(holder);
+            let column = first.start_position().column;
+            let start = node_range(first).start;
+            (
+                format!("Move `{}` out to the enclosing let", binding.name),
+                TextEdit {
+                    range: Range::new(start, start),
+                    new_text: format!(
+                        "{}\n\n{}",
+                        binding_text(&binding, &source, column),
+                        spaces(column)
+                    ),
+                },
+            )
+        };
+        Some((title, vec![removal, insertion]))
+    }
+
+    /// The title and edits moving the let binding named at `position` into
+    /// the only `case` or `if` branch of the `in` expression that uses it
+    pub fn sink_binding(&self, uri: &Url, position: Position) -> Option<(String, Vec<TextEdit>)> {
+        let source = self.read_file_content(uri)?;
+        let tree = self.parser.parse(&source)?;
+        let binding = binding_at(tree.root_node(), &source, position)?;
+        let links = bind_tree(&source, &tree);
+
+        let mut uses = Vec::new();
+        collect_uses(binding.let_in, &binding, &source, &links, &mut uses);
+        let within = |outer: Node, inner: &Node| {
+            inner.start_byte() >= outer.start_byte() && inner.end_byte() <= outer.end_byte()
+        };
+        if uses.is_empty() || uses.iter().any(|u| !within(binding.body, u)) {
+            return None;
+        }
+
+        // The innermost branch holding every use, below any function
+        let slot = std::iter::successors(Some(uses[0]), |n| n.parent())
+            .take_while(|n| n.id() != binding.body.id())
+            .filter(|n| is_branch(*n))
+            .find(|n| uses.iter().all(|u| within(*n, u)))?;
+        let crosses_function = std::iter::successors(Some(slot), |n| n.parent())
+            .take_while(|n| n.id() != binding.body.id())
+            .any(|n| matches!(n.kind(), "anonymous_function_expr" | "value_declaration"));
+        if crosses_function || line_indent(&source, slot) != slot.start_position().column {
+            return None;
+        }
+
+        let removal = TextEdit {
+            range: binding_removal(binding.let_in, binding.declaration, binding.body, &source),
+            new_text: String::new(),
+        };
+        let column = slot.start_position().column;
+        let mut cursor = slot.walk();
+        let first = slot
+            .named_children(&mut cursor)
+            .find(|n| matches!(n.kind(), "value_declaration" | "type_annotation"));
+        let insertion = match first {
+            Some(first) if slot.kind() == "let_in_expr" => {
+                let column = first.start_position().column;
+                let start = node_range(first).start;
+                TextEdit {
+                    range: Range::new(start, start),
+                    new_text: format!(
+                        "{}\n\n{}",
+                        binding_text(&binding, &source, column),
+                        spaces(column)
+                    ),
+                }
+            }
+            _ => TextEdit {
+                range: node_range(slot),
+                new_text: format!(
+                    "let\n{}{}\n{}in\n{}{}",
+                    spaces(column + 4),
+                    binding_text(&binding, &source, column + 4),
+                    spaces(column),
+                    spaces(column),
+                    &source[slot.byte_range()]
+                ),
+            },
+        };
+        let title = format!("Move `{}` into the branch that uses it", binding.name);
+        Some((title, vec![removal, insertion]))
+    }
+}
+
+/// The named let binding whose name, or annotation name, is at `position`
+fn binding_at<'a>(root: Node<'a>, source: &str, position: Position) -> Option<Binding<'a>> {
+    let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+    let node = root.descendant_for_point_range(point, point)?;
+    if node.kind() != "lower_case_identifier" {
+        return None;
+    }
+    let parent = node.parent()?;
+    let declaration = match parent.kind() {
+        "function_declaration_left" if parent.named_child(0) == Some(node) => parent.parent()?,
+        "type_annotation" => parent.next_named_sibling()?,
+        _ => return None,
+    };
+    let let_in = declaration.parent()?;
+    if declaration.kind() != "value_declaration" || let_in.kind() != "let_in_expr" {
+        return None;
+    }
+    let name = declaration
+        .child_by_field_name("functionDeclarationLeft")?
+        .named_child(0)?;
+    let name = source[name.byte_range()].to_string();
+    if source[node.byte_range()] != name {
+        return None;
+    }
+    Some(Binding {
+        name,
+        annotation: annotation_of(declaration, source),
+        declaration,
+        body: let_in.child_by_field_name("body")?,
+        let_in,
+    })
+}
+
+/// The binding with its annotation, and any comments between them,
+/// starting at `column`
+fn binding_text(binding: &Binding, source: &str, column: usize) -> String {
+    let mut node = binding.annotation.unwrap_or(binding.declaration);
+    let mut parts = vec![reindent(node, source, column)];
+    while node.id() != binding.declaration.id() {
+        let Some(next) = node.next_named_sibling() else {
+            break;
+        };
+        parts.push(reindent(next, source, column));
+        node = next;
+    }
+    parts.join(&format!("\n{}", spaces(column)))
+}
+
+/// The scope declaring the unqualified name `reference` refers to
+fn scope_of<'a>(reference: Node<'a>, name: &str, links: &SymbolLinks) -> Option<Node<'a>> {
+    std::iter::successors(Some(reference), |n| n.parent()).find(|n| {
+        links
+            .get_container(n.id())
+            .is_some_and(|symbols| symbols.contains_key(name))
+    })
+}
+
+/// The unqualified names `node` refers to
+fn references<'a, 's>(node: Node<'a>, source: &'s str) -> Vec<(Node<'a>, &'s str)> {
+    unqualified_references(node, &["value_qid", "record_base_identifier"])
+        .into_iter()
+        .map(|reference| (reference, &source[reference.byte_range()]))
+        .collect()
+}
+
+/// The scopes outside the binding declaring names it uses, apart from its
+/// own name
+fn dependencies(binding: &Binding, source: &str, links: &SymbolLinks) -> HashSet<usize> {
+    let declaration = binding.declaration;
+    references(declaration, source)
+        .into_iter()
+        .filter(|(_, name)| *name != binding.name)
+        .filter_map(|(reference, name)| scope_of(reference, name, links))
+        .filter(|scope| {
+            scope.start_byte() < declaration.start_byte()
+                || scope.end_byte() > declaration.end_byte()
+        })
+        .map(|scope| scope.id())
+        .collect()
+}
+
+/// The uses of the binding within its `let`, outside its own declaration
+fn collect_uses<'a>(
+    let_in: Node<'a>,
+    binding: &Binding,
+    source: &str,
+    links: &SymbolLinks,
+    found: &mut Vec<Node<'a>>,
+) {
+    found.extend(
+        references(let_in, source)
+            .into_iter()
+            .filter(|(reference, name)| {
+                *name == binding.name
+                    && scope_of(*reference, name, links).is_some_and(|s| s.id() == let_in.id())
+                    && !(reference.start_byte() >= binding.declaration.start_byte()
+                        && reference.end_byte() <= binding.declaration.end_byte())
+            })
+            .map(|(reference, _)| reference),
+    );
+}
+
+/// Whether another binding under `target` has the binding's name, which
+/// would clash once it moves there
+fn is_bound_elsewhere(target: Node, binding: &Binding, links: &SymbolLinks) -> bool {
+    let own = binding
+        .declaration
+        .child_by_field_name("functionDeclarationLeft")
+        .map(|left| left.id());
+    let mut stack = vec![target];
+    while let Some(node) = stack.pop() {
+        let mut cursor = node.walk();
+        stack.extend(node.named_children(&mut cursor));
+        let Some(symbols) = links.get_container(node.id()) else {
+            continue;
+        };
+        let mut others = symbols.get(&binding.name).into_iter().flatten();
+        if others.any(|symbol| Some(symbol.node_id) != own) {
+            return true;
+        }
+    }
+    false
+}
+
+/// A `case` branch or an `if` branch, as opposed to a condition
+fn is_branch(node: Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    match parent.kind() {
+        "case_of_branch" => parent.child_by_field_name("expr") == Some(node),
+        "if_else_expr" => {
+            let mut cursor = parent.walk();
+            let parts: Vec<Node> = parent
+                .children_by_field_name("exprList", &mut cursor)
+                .collect();
+            parts
+                .iter()
+                .position(|part| *part == node)
+                .is_some_and(|i| i % 2 == 1 || i + 1 == parts.len())
+        }
+        _ => false,
+    }
+}
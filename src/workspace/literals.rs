@@ -0,0 +1,182 @@
+//! Int, Char and String literals: hovers telling what they stand for, and
+//! rewrites between equivalent spellings.
+//!
+//! Int literals show their value in the other base and can switch between
+//! decimal and `0x` hexadecimal. Hexadecimal is written the way elm-format
+//! writes it, upper case and padded to 2, 4, 8 or 16 digits. Char literals
+//! show their code point. A chain of `++` with a string literal in it can
+//! become a `String.concat` of a list.
+
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::document::node_range;
+
+use super::nested_case::{line_indent, reindent, spaces};
+use super::Workspace;
+
+impl Workspace {
+    /// The value of the Int or Char literal at `position`, in markdown
+    pub fn literal_hover(&self, uri: &Url, position: Position) -> Option<(String, Range)> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let literal = literal_at(tree.root_node(), position)?;
+        let text = &source[literal.byte_range()];
+        let value = match literal.kind() {
+            "number_constant_expr" => {
+                let value = int_value(text)?;
+                if text.starts_with("0x") {
+                    format!("`{}` = `{}`", text, value)
+                } else {
+                    format!("`{}` = `{}`", text, hexadecimal(value))
+                }
+            }
+            _ => {
+                let code = char_code(text)?;
+                format!("`Char.toCode {}` = `{}` (U+{:04X})", text, code, code)
+            }
+        };
+        Some((value, node_range(literal)))
+    }
+
+    /// The title and edit writing the Int literal at `position` in the other
+    /// base
+    pub fn convert_int_literal(&self, uri: &Url, position: Position) -> Option<(String, TextEdit)> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let literal = literal_at(tree.root_node(), position)?;
+        let text = &source[literal.byte_range()];
+        let value = int_value(text)?;
+        let (title, new_text) = if text.starts_with("0x") {
+            ("Convert to decimal", value.to_string())
+        } else {
+            ("Convert to hexadecimal", hexadecimal(value))
+        };
+        Some((
+            format!("{} `{}`", title, new_text),
+            TextEdit {
+                range: node_range(literal),
+                new_text,
+            },
+        ))
+    }
+
+    /// An edit turning the `++` chain at `position` that joins strings into
+    /// `String.concat [ ... ]`
+    pub fn string_concat(&self, uri: &Url, position: Position) -> Option<TextEdit> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+        let node = tree.root_node().descendant_for_point_range(point, point)?;
+        // The outermost chain, as `++` chains nest when parenthesized
+        let chain = std::iter::successors(Some(node), |n| n.parent())
+            .filter(|n| concat_parts(*n, source).is_some())
+            .last()?;
+        let parts = concat_parts(chain, source)?;
+
+        let texts: Vec<String> = parts
+            .iter()
+            .map(|part| {
+                let inner = match part.kind() {
+                    "parenthesized_expr" => part.child_by_field_name("expression"),
+                    _ => None,
+                };
+                let part = inner.filter(|inner| !inner.has_error()).unwrap_or(*part);
+                reindent(part, source, line_indent(source, chain) + 6)
+            })
+            .collect();
+        let new_text = if chain.start_position().row == chain.end_position().row {
+            format!("String.concat [ {} ]", texts.join(", "))
+        } else {
+            let indent = spaces(line_indent(source, chain) + 4);
+            format!(
+                "String.concat\n{}[ {}\n{}]",
+                indent,
+                texts.join(&format!("\n{}, ", indent)),
+                indent
+            )
+        };
+        Some(TextEdit {
+            range: node_range(chain),
+            new_text,
+        })
+    }
+}
+
+/// The Int or Char literal at `position`
+fn literal_at(root: Node, position: Position) -> Option<Node> {
+    let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+    let node = root.descendant_for_point_range(point, point)?;
+    std::iter::successors(Some(node), |n| n.parent())
+        .take(2)
+        .find(|n| matches!(n.kind(), "number_constant_expr" | "char_constant_expr"))
+}
+
+/// The operands of `node` when it only joins values with `++` and one of
+/// them is a string literal
+fn concat_parts<'a>(node: Node<'a>, source: &str) -> Option<Vec<Node<'a>>> {
+    if node.kind() != "bin_op_expr" || node.has_error() {
+        return None;
+    }
+    let mut cursor = node.walk();
+    let mut parts = Vec::new();
+    for part in node.named_children(&mut cursor) {
+        match part.kind() {
+            "operator" if &source[part.byte_range()] == "++" => {}
+            "operator" => return None,
+            // Comments have no place in the list
+            "line_comment" | "block_comment" => return None,
+            _ => parts.push(part),
+        }
+    }
+    let has_string = parts
+        .iter()
+        .any(|part| part.kind() == "string_constant_expr");
+    (parts.len() > 1 && has_string).then_some(parts)
+}
+
+/// The value of an Int literal, decimal or `0x` hexadecimal
+fn int_value(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x") {
+        Some(digits) => u64::from_str_radix(digits, 16).ok(),
+        None if text.chars().all(|c| c.is_ascii_digit()) => text.parse().ok(),
+        // Floats
+        None => None,
+    }
+}
+
+/// `value` in hexadecimal as elm-format writes it
+fn hexadecimal(value: u64) -> String {
+    let digits = format!("{:X}", value).len();
+    let width = [2, 4, 8, 16]
+        .into_iter()
+        .find(|w| *w >= digits)
+        .unwrap_or(16);
+    format!("0x{:0width$X}", value, width = width)
+}
+
+/// The code point of a Char literal like `'a'`, `'\n'` or `'\u{1F600}'`
+fn char_code(text: &str) -> Option<u32> {
+    let inner = text.strip_prefix('\'')?.strip_suffix('\'')?;
+    let code = match inner.strip_prefix('\\') {
+        None => {
+            let mut chars = inner.chars();
+            let c = chars.next()?;
+            if chars.next().is_some() {
+                return None;
+            }
+            c as u32
+        }
+        Some("n") => 10,
+        Some("r") => 13,
+        Some("t") => 9,
+        Some("\"") => 34,
+        Some("'") => 39,
+        Some("\\") => 92,
+        Some(escape) => {
+            let hex = escape.strip_prefix("u{")?.strip_suffix('}')?;
+            u32::from_str_radix(hex, 16).ok()?
+        }
+    };
+    Some(code)
+}
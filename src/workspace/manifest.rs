@@ -0,0 +1,209 @@
+//! Outline of elm.json for the document symbols request.
+//!
+//! serde_json checks the manifest but keeps no positions, so a small scanner
+//! walks the validated text again to find where each key and value sits.
+
+use tower_lsp::lsp_types::*;
+
+/// Symbols for an elm.json: its fields, with source directories, exposed
+/// modules and dependencies nested under their keys. Empty if the manifest
+/// is not valid JSON.
+pub fn elm_json_symbols(content: &str) -> Vec<DocumentSymbol> {
+    if serde_json::from_str::<serde_json::Value>(content).is_err() {
+        return Vec::new();
+    }
+    let bytes = content.as_bytes();
+    let start = skip_whitespace(bytes, 0);
+    if bytes.get(start) != Some(&b'{') {
+        return Vec::new();
+    }
+    object_symbols(content, start, None)
+}
+
+/// The section of the manifest a value belongs to, which picks its symbol kind
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    SourceDirectories,
+    ExposedModules,
+    Dependencies,
+}
+
+impl Section {
+    fn of(key: &str) -> Option<Self> {
+        match key {
+            "source-directories" => Some(Self::SourceDirectories),
+            "exposed-modules" => Some(Self::ExposedModules),
+            "dependencies" | "test-dependencies" => Some(Self::Dependencies),
+            _ => None,
+        }
+    }
+
+    fn leaf_kind(section: Option<Self>) -> SymbolKind {
+        match section {
+            Some(Self::SourceDirectories) => SymbolKind::FILE,
+            Some(Self::ExposedModules) => SymbolKind::MODULE,
+            Some(Self::Dependencies) => SymbolKind::PACKAGE,
+            None => SymbolKind::PROPERTY,
+        }
+    }
+}
+
+fn object_symbols(content: &str, open: usize, section: Option<Section>) -> Vec<DocumentSymbol> {
+    let bytes = content.as_bytes();
+    let mut symbols = Vec::new();
+    let mut i = open + 1;
+    loop {
+        i = skip_whitespace(bytes, i);
+        if bytes.get(i) != Some(&b'"') {
+            return symbols;
+        }
+        let key_start = i;
+        let key_end = string_end(bytes, key_start);
+        let key: String = serde_json::from_str(&content[key_start..key_end]).unwrap_or_default();
+        i = skip_whitespace(bytes, key_end);
+        if bytes.get(i) != Some(&b':') {
+            return symbols;
+        }
+        let value_start = skip_whitespace(bytes, i + 1);
+        let value_end = value_end(bytes, value_start);
+        let section = section.or_else(|| Section::of(&key));
+
+        let (kind, detail, children) = match bytes.get(value_start) {
+            Some(b'{') => (
+                SymbolKind::NAMESPACE,
+                None,
+                Some(object_symbols(content, value_start, section)),
+            ),
+            Some(b'[') => (
+                SymbolKind::ARRAY,
+                None,
+                Some(array_symbols(content, value_start, section)),
+            ),
+            _ => (
+                Section::leaf_kind(section),
+                Some(scalar(&content[value_start..value_end])),
+                None,
+            ),
+        };
+        #[allow(deprecated)]
+        symbols.push(DocumentSymbol {
+            name: key,
+            detail,
+            kind,
+            tags: None,
+            deprecated: None,
+            range: Range::new(position(content, key_start), position(content, value_end)),
+            selection_range: Range::new(position(content, key_start), position(content, key_end)),
+            children,
+        });
+
+        i = skip_whitespace(bytes, value_end);
+        if bytes.get(i) == Some(&b',') {
+            i += 1;
+        }
+    }
+}
+
+/// The strings of a list, such as source directories or exposed modules
+fn array_symbols(content: &str, open: usize, section: Option<Section>) -> Vec<DocumentSymbol> {
+    let bytes = content.as_bytes();
+    let mut symbols = Vec::new();
+    let mut i = open + 1;
+    loop {
+        i = skip_whitespace(bytes, i);
+        match bytes.get(i) {
+            None | Some(b']') => return symbols,
+            Some(b',') => i += 1,
+            Some(b'"') => {
+                let end = string_end(bytes, i);
+                let range = Range::new(position(content, i), position(content, end));
+                #[allow(deprecated)]
+                symbols.push(DocumentSymbol {
+                    name: scalar(&content[i..end]),
+                    detail: None,
+                    kind: Section::leaf_kind(section),
+                    tags: None,
+                    deprecated: None,
+                    range,
+                    selection_range: range,
+                    children: None,
+                });
+                i = end;
+            }
+            Some(_) => i = value_end(bytes, i),
+        }
+    }
+}
+
+/// A scalar value as shown in the outline, strings without their quotes
+fn scalar(text: &str) -> String {
+    serde_json::from_str::<String>(text).unwrap_or_else(|_| text.to_string())
+}
+
+fn skip_whitespace(bytes: &[u8], mut i: usize) -> usize {
+    while bytes.get(i).is_some_and(u8::is_ascii_whitespace) {
+        i += 1;
+    }
+    i
+}
+
+/// Offset just past the string starting at `start`
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    while let Some(&byte) = bytes.get(i) {
+        match byte {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Offset just past the value starting at `start`
+fn value_end(bytes: &[u8], start: usize) -> usize {
+    match bytes.get(start) {
+        Some(b'"') => string_end(bytes, start),
+        Some(b'{' | b'[') => {
+            let mut depth = 0;
+            let mut i = start;
+            while let Some(&byte) = bytes.get(i) {
+                match byte {
+                    b'"' => {
+                        i = string_end(bytes, i);
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return i + 1;
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+            bytes.len()
+        }
+        _ => {
+            let mut i = start;
+            while bytes
+                .get(i)
+                .is_some_and(|b| !matches!(b, b',' | b'}' | b']') && !b.is_ascii_whitespace())
+            {
+                i += 1;
+            }
+            i
+        }
+    }
+}
+
+fn position(content: &str, offset: usize) -> Position {
+    let before = &content[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
@@ -1,21 +1,87 @@
-use std::collections::HashMap;
+//! The index of an Elm project and the operations on it.
+//!
+//! A [`Workspace`] covers one `elm.json`: its modules, their symbols and
+//! references, and the packages it depends on. Queries and refactorings live
+//! in the submodules, each as methods of [`Workspace`]; refactorings return
+//! the edits to make rather than writing files.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use tower_lsp::lsp_types::*;
-use walkdir::WalkDir;
 
 use crate::binder::BoundSymbolKind;
-use crate::document::ElmSymbol;
+use crate::concurrency;
+use crate::config::Settings;
+use crate::document::{read_source, ElmSymbol};
+use crate::logging;
+use crate::matcher;
 use crate::parser::ElmParser;
-use crate::type_checker::TypeChecker;
-
+use crate::queries;
+use crate::type_checker::{FileAnalysis, TypeChecker};
+
+mod call_hierarchy;
+mod call_paths;
+mod completion;
+mod constructor_usages;
+mod consumers;
+mod declarations;
+mod dependencies;
 mod erd;
+mod evergreen;
+mod exposure;
+mod extract;
 mod field_operations;
 mod file_operations;
+mod folders;
+mod glsl;
+mod hover;
+mod import_edit;
+mod import_graph;
+mod incremental;
+mod let_floating;
+mod literals;
+mod manifest;
+mod module_alias;
+mod module_docs;
+mod module_path;
 mod move_function;
+mod nested_case;
+mod operators;
+mod organize;
+mod package;
+mod package_cache;
+mod program;
+mod provenance;
+mod record_update;
+mod rename_operations;
+mod reorder;
+mod signature_help;
+#[cfg(test)]
+mod snapshot_tests;
+mod tuple_to_record;
+mod type_definition;
+mod type_variable;
 mod types;
+mod unused_imports;
 mod variant_operations;
+mod view_helper;
+mod walk;
 
+pub use call_paths::{CallPaths, CallPathsParams, DEFAULT_MAX_DEPTH, DEFAULT_MAX_PATHS};
+pub use constructor_usages::{ConstructorUsage, ConstructorUsages};
+pub use consumers::{DeclarationImporters, ImportingModule};
 pub use erd::*;
+pub use evergreen::{EvergreenDrift, Member, TypeDrift};
+pub use exposure::ExposeFix;
+pub use folders::{discover_projects, Workspaces};
+pub use glsl::GlslInput;
+pub use import_edit::{import_edit, import_edits, ImportSpec};
+pub use import_graph::{ImportGraph, Importer};
+pub use incremental::{FileInput, IncrementalIndex};
+pub use manifest::elm_json_symbols;
+pub use operators::OperatorInfo;
+pub use program::{IndexStats, ProgramKind};
 pub use types::*;
 
 /// Represents an Elm module with its symbols and metadata
@@ -26,6 +92,13 @@ pub struct ElmModule {
     pub symbols: Vec<ElmSymbol>,
     pub imports: Vec<ImportInfo>,
     pub exposing: ExposingInfo,
+    /// Only basic navigation is indexed (see [`Settings::is_light_file`])
+    pub light: bool,
+    /// Generated code (see [`Settings::is_generated`]): navigation only, and
+    /// never changed by refactors
+    pub generated: bool,
+    /// The program its `main` (or Lamdera `app`) defines
+    pub program: Option<ProgramKind>,
 }
 
 #[derive(Debug, Clone)]
@@ -35,12 +108,29 @@ pub struct ImportInfo {
     pub exposing: ExposingInfo,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ExposingInfo {
     All,
     Explicit(Vec<String>),
 }
 
+impl ExposingInfo {
+    /// Whether the list exposes `name`. A `Type(..)` entry exposes the type
+    /// and its constructors; only the exposing module knows those, so
+    /// `is_variant_of(type_name)` tells whether `name` is one of them.
+    pub fn exposes(&self, name: &str, is_variant_of: impl Fn(&str) -> bool) -> bool {
+        match self {
+            ExposingInfo::All => true,
+            ExposingInfo::Explicit(items) => {
+                items.iter().any(|item| match item.strip_suffix("(..)") {
+                    Some(type_name) => type_name == name || is_variant_of(type_name),
+                    None => item == name,
+                })
+            }
+        }
+    }
+}
+
 /// Cross-file symbol reference
 #[derive(Debug, Clone)]
 pub struct SymbolReference {
@@ -68,13 +158,16 @@ pub struct DefinitionSymbol {
 }
 
 /// Global symbol entry in the index
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GlobalSymbol {
     pub name: String,
     pub module_name: String,
     pub kind: SymbolKind,
     pub definition_uri: Url,
+    /// The declaration's name
     pub definition_range: Range,
+    /// The whole declaration, including its annotation
+    pub range: Range,
     pub signature: Option<String>,
 }
 
@@ -91,6 +184,13 @@ const LAMDERA_PROTECTED_TYPES: &[&str] = &[
     "BackendModel",
 ];
 
+/// What a package module exposes
+#[derive(Debug, Clone)]
+pub struct ExternalModule {
+    pub package: String,
+    pub exposing: ExposingInfo,
+}
+
 /// Represents an external package dependency
 #[derive(Debug, Clone)]
 pub struct ExternalPackage {
@@ -102,33 +202,266 @@ pub struct ExternalPackage {
 /// The workspace index - tracks all symbols across all files
 pub struct Workspace {
     pub root_path: PathBuf,
+    /// The editor's workspace folder this project was found in, which is
+    /// `root_path` unless the project is nested in it
+    pub folder: PathBuf,
     pub source_dirs: Vec<PathBuf>,
+    /// elm-test directories (`tests/`), indexed alongside the sources
+    pub test_dirs: Vec<PathBuf>,
     pub modules: HashMap<String, ElmModule>,
     pub symbols: HashMap<String, Vec<GlobalSymbol>>,
     pub references: HashMap<String, Vec<SymbolReference>>,
     pub parser: ElmParser,
     pub type_checker: TypeChecker,
     pub is_lamdera_project: bool,
+    /// The `exposed-modules` of a package's elm.json; None for applications
+    pub exposed_modules: Option<Vec<String>>,
     /// External packages (from ~/.elm or elm-stuff)
     pub external_packages: Vec<ExternalPackage>,
     /// Symbols from external packages (indexed separately)
     pub external_symbols: HashMap<String, Vec<GlobalSymbol>>,
+    /// Modules of external packages, by module name
+    pub external_modules: HashMap<String, ExternalModule>,
+    /// Infix operators declared by external packages, by operator
+    pub operators: HashMap<String, Vec<OperatorInfo>>,
+    /// Elm home directory holding downloaded packages (~/.elm or ELM_HOME)
+    pub elm_home: PathBuf,
+    /// Compiler version from elm.json or the installed binary (e.g. "0.19.1")
+    pub compiler_version: Option<String>,
+    /// Modification time of elm.json when dependencies were last read
+    elm_json_modified: Option<std::time::SystemTime>,
+    /// Dependencies listed in elm.json that are not downloaded yet
+    missing_packages: usize,
+    /// Per-file inputs and contributions, used to recompute only what changed
+    pub inputs: IncrementalIndex,
+    /// User settings controlling which files get full analysis
+    pub settings: Settings,
+    /// Which modules import which, for finding the dependents of a change
+    pub import_graph: ImportGraph,
+    /// Text of the files open in the editor, which wins over what is on disk
+    buffers: HashMap<PathBuf, String>,
+}
+
+/// Parse an exact compiler version such as "0.19.1" (also the prefix of
+/// "0.19.1-1.3.2"). Version ranges like "0.19.0 <= v < 0.20.0" are rejected.
+fn parse_version(text: &str) -> Option<String> {
+    if text.contains('<') {
+        return None;
+    }
+    let version = text.split(['-', ' ']).next()?;
+    let parts: Vec<&str> = version.split('.').collect();
+    let exact = parts.len() == 3
+        && parts
+            .iter()
+            .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()));
+    exact.then(|| version.to_string())
+}
+
+/// Type text with single spaces and without module qualifiers, so that
+/// `Evergreen.V3.Page.Route` and `Route` compare equal
+fn normalize_type(text: &str) -> String {
+    let spaced = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut result = String::new();
+    let mut word = String::new();
+    for c in spaced.chars().chain(std::iter::once(' ')) {
+        if c.is_alphanumeric() || c == '_' || c == '.' {
+            word.push(c);
+            continue;
+        }
+        let unqualified = match word.rsplit_once('.') {
+            Some((_, name)) if word.starts_with(char::is_uppercase) => name,
+            _ => &word,
+        };
+        result.push_str(unqualified);
+        word.clear();
+        result.push(c);
+    }
+    result.trim_end().to_string()
+}
+
+/// The `exposed-modules` of a package's elm.json: a list, or lists under
+/// category headings
+fn parse_exposed_modules(elm_json: &serde_json::Value) -> Option<Vec<String>> {
+    if elm_json.get("type")?.as_str()? != "package" {
+        return None;
+    }
+    let names = |list: &serde_json::Value| -> Vec<String> {
+        list.as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|name| name.as_str().map(str::to_string))
+            .collect()
+    };
+    Some(match elm_json.get("exposed-modules") {
+        Some(serde_json::Value::Object(categories)) => {
+            categories.values().flat_map(names).collect()
+        }
+        Some(list) => names(list),
+        None => Vec::new(),
+    })
 }
 
 impl Workspace {
     pub fn new(root_path: PathBuf) -> Self {
         Self {
+            folder: root_path.clone(),
             root_path,
             source_dirs: Vec::new(),
+            test_dirs: Vec::new(),
             modules: HashMap::new(),
             symbols: HashMap::new(),
             references: HashMap::new(),
             parser: ElmParser::new(),
             type_checker: TypeChecker::new(),
             is_lamdera_project: false,
+            exposed_modules: None,
             external_packages: Vec::new(),
             external_symbols: HashMap::new(),
+            external_modules: HashMap::new(),
+            operators: HashMap::new(),
+            elm_home: crate::tools::elm_home(),
+            compiler_version: None,
+            elm_json_modified: None,
+            missing_packages: 0,
+            inputs: IncrementalIndex::new(),
+            settings: Settings::default(),
+            import_graph: ImportGraph::new(),
+            buffers: HashMap::new(),
+        }
+    }
+
+    /// Apply new settings, re-indexing files whose analysis level changed
+    pub fn apply_settings(&mut self, settings: Settings) {
+        if settings == self.settings {
+            return;
+        }
+        self.settings = settings;
+
+        let changed: Vec<PathBuf> = self
+            .modules
+            .values()
+            .filter(|module| {
+                let size = std::fs::metadata(&module.path).map_or(0, |m| m.len() as usize);
+                self.is_light_file(&module.path, size) != module.light
+                    || self.is_generated_path(&module.path) != module.generated
+            })
+            .map(|module| module.path.clone())
+            .collect();
+
+        for path in changed {
+            if let Ok(uri) = Url::from_file_path(&path) {
+                self.retract_file(&uri, &path);
+            }
+            if let Err(e) = self.index_file(&path) {
+                tracing::warn!(target: logging::INDEX, "Failed to re-index {:?}: {}", path, e);
+            }
+        }
+    }
+
+    /// Files of the modules that import the module defined in `uri`, directly
+    /// or through at most `max_depth` imports, nearest first
+    pub fn dependent_files(&self, uri: &Url, max_depth: usize) -> Vec<Url> {
+        let module_name = match self.inputs.get(uri) {
+            Some(input) => &input.module_name,
+            None => return Vec::new(),
+        };
+        self.import_graph
+            .transitive_importers(module_name, max_depth)
+            .iter()
+            .filter_map(|name| self.modules.get(name))
+            .filter_map(|module| Url::from_file_path(&module.path).ok())
+            .collect()
+    }
+
+    /// The module defined in `uri` and the modules importing it, up to
+    /// `max_depth` imports away, with the file of each importer
+    pub fn importers_of(
+        &self,
+        uri: &Url,
+        max_depth: usize,
+    ) -> anyhow::Result<(String, Vec<(Importer, Url)>)> {
+        let module_name = self
+            .inputs
+            .get(uri)
+            .map(|input| input.module_name.clone())
+            .ok_or_else(|| anyhow::anyhow!("File is not indexed: {}", uri))?;
+        let importers = self
+            .import_graph
+            .importers(&module_name, max_depth)
+            .into_iter()
+            .filter_map(|importer| {
+                let path = &self.modules.get(&importer.module)?.path;
+                let importer_uri = Url::from_file_path(path).ok()?;
+                Some((importer, importer_uri))
+            })
+            .collect();
+        Ok((module_name, importers))
+    }
+
+    /// Whether a file belongs to the elm-test suite
+    pub fn is_test_file(&self, uri: &Url) -> bool {
+        uri.to_file_path()
+            .is_ok_and(|path| self.test_dirs.iter().any(|dir| path.starts_with(dir)))
+    }
+
+    /// Whether results should include test usages: the per-request choice if
+    /// given, else the `includeTests` setting
+    pub fn include_tests(&self, requested: Option<bool>) -> bool {
+        requested.unwrap_or(self.settings.include_tests)
+    }
+
+    /// Drop edits to generated files, and to test files unless tests are
+    /// included
+    pub fn filter_test_edits(
+        &self,
+        changes: &mut HashMap<Url, Vec<TextEdit>>,
+        include_tests: Option<bool>,
+    ) {
+        changes.retain(|uri, _| !self.is_generated(uri));
+        if !self.include_tests(include_tests) {
+            changes.retain(|uri, _| !self.is_test_file(uri));
+        }
+    }
+
+    /// Drop locations in test files unless tests are included
+    pub fn filter_test_locations(
+        &self,
+        locations: &mut Vec<Location>,
+        include_tests: Option<bool>,
+    ) {
+        if !self.include_tests(include_tests) {
+            locations.retain(|location| !self.is_test_file(&location.uri));
+        }
+    }
+
+    /// Whether a file is too large or excluded, and so skips heavy analyses
+    fn is_light_file(&self, path: &Path, size: usize) -> bool {
+        let relative = path.strip_prefix(&self.root_path).unwrap_or(path);
+        self.settings.is_light_file(relative, size)
+    }
+
+    /// Whether the file at `uri` is generated code
+    pub fn is_generated(&self, uri: &Url) -> bool {
+        uri.to_file_path()
+            .is_ok_and(|path| self.is_generated_path(&path))
+    }
+
+    fn is_generated_path(&self, path: &Path) -> bool {
+        let relative = path.strip_prefix(&self.root_path).unwrap_or(path);
+        self.settings.is_generated(relative)
+    }
+
+    /// Fail when a refactor would change generated code, which the next
+    /// build of its generator overwrites
+    pub(super) fn ensure_not_generated(&self, path: &Path) -> anyhow::Result<()> {
+        if self.is_generated_path(path) {
+            let relative = path.strip_prefix(&self.root_path).unwrap_or(path);
+            anyhow::bail!(
+                "{} is generated code - change what generates it instead",
+                relative.display()
+            );
         }
+        Ok(())
     }
 
     /// Check if a symbol name is a protected Lamdera type that cannot be renamed
@@ -157,10 +490,11 @@ impl Workspace {
         }
     }
 
-    /// Iterate over non-Evergreen modules with their URIs
+    /// Iterate over modules that are not generated (like Evergreen
+    /// snapshots) with their URIs
     fn iter_non_evergreen_modules(&self) -> impl Iterator<Item = (&ElmModule, Url)> {
         self.modules.values().filter_map(|module| {
-            if module.path.to_string_lossy().contains("/Evergreen/") {
+            if module.generated || module.path.to_string_lossy().contains("/Evergreen/") {
                 return None;
             }
             Url::from_file_path(&module.path)
@@ -189,6 +523,7 @@ impl Workspace {
         let elm_json_path = self.root_path.join("elm.json");
         if elm_json_path.exists() {
             let content = std::fs::read_to_string(&elm_json_path)?;
+            self.elm_json_modified = Self::modified_time(&elm_json_path);
             self.parse_elm_json(&content)?;
         } else {
             // Default to src/ if no elm.json
@@ -198,6 +533,14 @@ impl Workspace {
             }
         }
 
+        let tests_dir = self.root_path.join("tests");
+        if tests_dir.is_dir()
+            && !self.source_dirs.contains(&tests_dir)
+            && !self.test_dirs.contains(&tests_dir)
+        {
+            self.test_dirs.push(tests_dir);
+        }
+
         // Index all .elm files
         self.index_all_files()?;
 
@@ -213,8 +556,9 @@ impl Workspace {
         // Detect Lamdera project by checking for lamdera/* dependencies
         self.is_lamdera_project = self.detect_lamdera_project(&json);
         if self.is_lamdera_project {
-            tracing::info!("Detected Lamdera project");
+            tracing::info!(target: logging::INDEX, "Detected Lamdera project");
         }
+        self.exposed_modules = parse_exposed_modules(&json);
 
         // Handle both application and package elm.json formats
         if let Some(source_dirs) = json.get("source-directories") {
@@ -239,6 +583,7 @@ impl Workspace {
         }
 
         // Parse dependencies for external package support
+        self.compiler_version = self.detect_compiler_version(&json);
         self.parse_dependencies(&json);
 
         Ok(())
@@ -246,56 +591,210 @@ impl Workspace {
 
     /// Parse dependencies from elm.json and locate package sources
     fn parse_dependencies(&mut self, json: &serde_json::Value) {
-        let elm_home = Self::get_elm_home();
+        self.missing_packages = 0;
 
         if let Some(deps) = json.get("dependencies") {
             // Parse direct dependencies
             if let Some(direct) = deps.get("direct") {
-                self.collect_packages(direct, &elm_home);
+                self.collect_packages(direct);
             }
             // Parse indirect dependencies
             if let Some(indirect) = deps.get("indirect") {
-                self.collect_packages(indirect, &elm_home);
+                self.collect_packages(indirect);
             }
         }
 
-        tracing::info!("Found {} external packages", self.external_packages.len());
+        tracing::info!(
+            target: logging::INDEX,
+            "Found {} external packages ({} not downloaded)",
+            self.external_packages.len(),
+            self.missing_packages
+        );
     }
 
     /// Collect packages from a dependencies object
-    fn collect_packages(&mut self, deps: &serde_json::Value, elm_home: &Path) {
+    fn collect_packages(&mut self, deps: &serde_json::Value) {
+        let package_dirs = self.package_dirs();
         if let Some(obj) = deps.as_object() {
             for (name, version) in obj {
                 if let Some(version_str) = version.as_str() {
-                    // Try to find package in elm home
-                    let package_path = elm_home
-                        .join("0.19.1")
-                        .join("packages")
-                        .join(name.replace('/', std::path::MAIN_SEPARATOR_STR))
-                        .join(version_str)
-                        .join("src");
-
-                    if package_path.exists() {
+                    // Use the first package cache that has this package
+                    let package_path = package_dirs
+                        .iter()
+                        .map(|dir| {
+                            dir.join(name.replace('/', std::path::MAIN_SEPARATOR_STR))
+                                .join(version_str)
+                                .join("src")
+                        })
+                        .find(|path| path.exists());
+
+                    if let Some(package_path) = package_path {
                         self.external_packages.push(ExternalPackage {
                             name: name.clone(),
                             version: version_str.to_string(),
                             path: package_path,
                         });
+                    } else {
+                        self.missing_packages += 1;
                     }
                 }
             }
         }
     }
 
-    /// Get the Elm home directory (~/.elm or ELM_HOME)
-    fn get_elm_home() -> PathBuf {
-        if let Ok(elm_home) = std::env::var("ELM_HOME") {
-            PathBuf::from(elm_home)
-        } else if let Some(home) = dirs::home_dir() {
-            home.join(".elm")
+    /// The program to run for the tool `name` in this project, see
+    /// [`crate::tools::find_tool`]
+    pub fn tool_path(&self, name: &str) -> PathBuf {
+        crate::tools::find_tool(&self.root_path, &self.elm_home, name)
+    }
+
+    /// Directory where the compiler downloads package sources
+    pub fn packages_dir(&self) -> PathBuf {
+        let version = self.compiler_version.as_deref().unwrap_or("0.19.1");
+        self.elm_home.join(version).join("packages")
+    }
+
+    /// Package caches to search, most specific first: the detected compiler
+    /// version, then the known 0.19 layouts, then Lamdera's own cache
+    pub fn package_dirs(&self) -> Vec<PathBuf> {
+        let mut homes = vec![self.elm_home.clone()];
+        if self.is_lamdera_project {
+            if let Some(home) = dirs::home_dir() {
+                homes.push(home.join(".lamdera"));
+            }
+        }
+
+        let mut dirs = vec![self.packages_dir()];
+        for home in &homes {
+            for version in ["0.19.1", "0.19.0"] {
+                let dir = home.join(version).join("packages");
+                if !dirs.contains(&dir) {
+                    dirs.push(dir);
+                }
+            }
+        }
+        dirs
+    }
+
+    /// Detect the compiler version: an application's elm.json pins it exactly,
+    /// otherwise ask the installed compiler. None if neither is available.
+    fn detect_compiler_version(&self, elm_json: &serde_json::Value) -> Option<String> {
+        if let Some(version) = elm_json
+            .get("elm-version")
+            .and_then(|v| v.as_str())
+            .and_then(parse_version)
+        {
+            return Some(version);
+        }
+
+        // Restricted mode runs no tools from an untrusted project
+        if self.settings.restricted {
+            return None;
+        }
+        let binary = if self.is_lamdera_project {
+            "lamdera"
         } else {
-            PathBuf::from(".elm")
+            "elm"
+        };
+        let output = std::process::Command::new(self.tool_path(binary))
+            .arg("--version")
+            .output()
+            .ok()?;
+        let version = parse_version(String::from_utf8_lossy(&output.stdout).trim());
+        if version.is_none() {
+            tracing::warn!(
+                target: logging::INDEX,
+                "Could not detect the {} compiler version",
+                binary
+            );
+        }
+        version
+    }
+
+    fn modified_time(path: &Path) -> Option<std::time::SystemTime> {
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Pick up packages installed since the last check (e.g. by `elm install`).
+    /// Re-reads elm.json when it changed or when some dependencies were not
+    /// downloaded yet; indexes new packages and drops removed ones.
+    /// Returns the names of newly indexed packages.
+    pub fn refresh_packages(&mut self) -> anyhow::Result<Vec<String>> {
+        let elm_json_path = self.root_path.join("elm.json");
+        let modified = Self::modified_time(&elm_json_path);
+        if modified.is_none() || (modified == self.elm_json_modified && self.missing_packages == 0)
+        {
+            return Ok(Vec::new());
+        }
+
+        let content = std::fs::read_to_string(&elm_json_path)?;
+        let json: serde_json::Value = serde_json::from_str(&content)?;
+        self.elm_json_modified = modified;
+        self.is_lamdera_project = self.detect_lamdera_project(&json);
+        self.exposed_modules = parse_exposed_modules(&json);
+        self.compiler_version = self.detect_compiler_version(&json);
+
+        let previous = std::mem::take(&mut self.external_packages);
+        self.parse_dependencies(&json);
+
+        for package in &previous {
+            if !self
+                .external_packages
+                .iter()
+                .any(|p| p.path == package.path)
+            {
+                tracing::info!(
+                    target: logging::INDEX,
+                    "Dropping package {} {}",
+                    package.name,
+                    package.version
+                );
+                self.retract_external_package(package);
+            }
+        }
+
+        let added: Vec<ExternalPackage> = self
+            .external_packages
+            .iter()
+            .filter(|p| !previous.iter().any(|old| old.path == p.path))
+            .cloned()
+            .collect();
+        for package in &added {
+            tracing::info!(
+                target: logging::INDEX,
+                "Indexing new package {} {}",
+                package.name,
+                package.version
+            );
+            if let Err(e) = self.index_external_package(package) {
+                tracing::warn!(
+                    target: logging::INDEX,
+                    "Failed to index package {}: {}",
+                    package.name,
+                    e
+                );
+            }
         }
+
+        Ok(added.into_iter().map(|p| p.name).collect())
+    }
+
+    /// Remove the symbols an external package contributed
+    fn retract_external_package(&mut self, package: &ExternalPackage) {
+        self.external_symbols.retain(|_, symbols| {
+            symbols.retain(|s| {
+                s.definition_uri
+                    .to_file_path()
+                    .map_or(true, |path| !path.starts_with(&package.path))
+            });
+            !symbols.is_empty()
+        });
+        self.external_modules
+            .retain(|_, module| module.package != package.name);
+        self.operators.retain(|_, operators| {
+            operators.retain(|op| op.package != package.name);
+            !operators.is_empty()
+        });
     }
 
     /// Index external packages for go-to-definition support
@@ -304,70 +803,58 @@ impl Workspace {
 
         for package in &packages {
             if let Err(e) = self.index_external_package(package) {
-                tracing::warn!("Failed to index package {}: {}", package.name, e);
+                tracing::warn!(
+                    target: logging::INDEX,
+                    "Failed to index package {}: {}",
+                    package.name,
+                    e
+                );
             }
         }
 
-        tracing::info!("Indexed {} external symbols", self.external_symbols.len());
+        tracing::info!(
+            target: logging::INDEX,
+            "Indexed {} external symbols",
+            self.external_symbols.len()
+        );
         Ok(())
     }
 
-    /// Index a single external package
+    /// Index a single external package, reusing the index other projects
+    /// built for it
     fn index_external_package(&mut self, package: &ExternalPackage) -> anyhow::Result<()> {
-        for entry in WalkDir::new(&package.path)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            let path = entry.path();
-            if path.extension().is_some_and(|ext| ext == "elm") {
-                self.index_external_file(path, &package.name)?;
-            }
-        }
-        Ok(())
-    }
-
-    /// Index a single external file (only extracts symbols, no references)
-    fn index_external_file(&mut self, path: &Path, _package_name: &str) -> anyhow::Result<()> {
-        let content = std::fs::read_to_string(path)?;
-        let uri = Url::from_file_path(path).map_err(|_| anyhow::anyhow!("Invalid path"))?;
-
-        if let Some(tree) = self.parser.parse(&content) {
-            let symbols = self.parser.extract_symbols(&tree, &content);
-            let module_name = self
-                .extract_module_name(&tree, &content)
-                .unwrap_or_else(|| {
-                    path.file_stem()
-                        .and_then(|s| s.to_str())
-                        .unwrap_or("Unknown")
-                        .to_string()
-                });
-
-            // Add symbols to external index (not the main symbols index)
-            for symbol in &symbols {
-                let global_symbol = GlobalSymbol {
-                    name: symbol.name.clone(),
-                    module_name: module_name.clone(),
-                    kind: symbol.kind,
-                    definition_uri: uri.clone(),
-                    definition_range: symbol.definition_range.unwrap_or(symbol.range),
-                    signature: symbol.signature.clone(),
-                };
-
+        let index = self.package_index(package)?;
+        for module in &index.modules {
+            for symbol in &module.symbols {
                 // Index by unqualified name
                 self.external_symbols
                     .entry(symbol.name.clone())
                     .or_default()
-                    .push(global_symbol.clone());
+                    .push(symbol.clone());
 
                 // Index by qualified name
-                let qualified_name = format!("{}.{}", module_name, symbol.name);
+                let qualified_name = format!("{}.{}", module.name, symbol.name);
                 self.external_symbols
                     .entry(qualified_name)
                     .or_default()
-                    .push(global_symbol);
+                    .push(symbol.clone());
             }
-        }
 
+            self.external_modules.insert(
+                module.name.clone(),
+                ExternalModule {
+                    package: package.name.clone(),
+                    exposing: module.exposing.clone(),
+                },
+            );
+
+            for operator in &module.operators {
+                self.operators
+                    .entry(operator.operator.clone())
+                    .or_default()
+                    .push(operator.clone());
+            }
+        }
         Ok(())
     }
 
@@ -401,181 +888,221 @@ impl Workspace {
     /// Index all .elm files in the workspace
     pub fn index_all_files(&mut self) -> anyhow::Result<()> {
         let mut files_to_index = Vec::new();
-        let is_lamdera = self.is_lamdera_project;
 
-        for source_dir in &self.source_dirs {
-            for entry in WalkDir::new(source_dir).into_iter().filter_map(|e| e.ok()) {
-                let path = entry.path();
+        // Generated code, like Lamdera's Evergreen snapshots, is indexed for
+        // navigation only
+        for source_dir in self.source_dirs.iter().chain(&self.test_dirs) {
+            files_to_index.extend(walk::elm_files(source_dir, &self.root_path));
+        }
 
-                // Skip Evergreen directory in Lamdera projects
-                if is_lamdera && self.is_evergreen_path(path) {
-                    continue;
-                }
+        let threads = self.settings.index_thread_count();
+        tracing::info!(
+            target: logging::INDEX,
+            "Indexing {} Elm files on {} thread(s)",
+            files_to_index.len(),
+            threads
+        );
 
-                if path.extension().is_some_and(|ext| ext == "elm") {
-                    files_to_index.push(path.to_path_buf());
+        // Reading is quick; parsing and type inference run on several threads
+        let mut jobs = Vec::new();
+        for path in files_to_index {
+            let content = match self.source(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    tracing::warn!(target: logging::INDEX, "Failed to index {:?}: {}", path, e);
+                    continue;
                 }
+            };
+            let Ok(uri) = Url::from_file_path(&path) else {
+                continue;
+            };
+            let content_hash = IncrementalIndex::hash_content(&content);
+            if self.inputs.is_fresh(&uri, content_hash) {
+                continue;
             }
+            let light = self.is_light_file(&path, content.len());
+            jobs.push((path, uri, content, content_hash, light));
         }
-
-        tracing::info!("Indexing {} Elm files", files_to_index.len());
-
-        for path in files_to_index {
-            if let Err(e) = self.index_file(&path) {
-                tracing::warn!("Failed to index {:?}: {}", path, e);
+        let parsed = concurrency::map_in_parallel(jobs, threads, |job| {
+            let (path, uri, content, content_hash, light) = job;
+            let tree = ElmParser::new().parse(&content);
+            let analysis = tree
+                .as_ref()
+                .filter(|_| !light)
+                .map(|tree| TypeChecker::analyze(uri.as_str(), &content, tree));
+            (path, uri, content, content_hash, tree, analysis)
+        });
+        for (path, uri, content, content_hash, tree, analysis) in parsed {
+            match tree {
+                Some(tree) => self.index_tree(&uri, path, &content, content_hash, tree, analysis),
+                None => tracing::warn!(target: logging::INDEX, "Failed to parse {:?}", path),
             }
         }
 
-        // Build reference index after all files are parsed
-        self.build_reference_index();
-
         Ok(())
     }
 
-    /// Check if a path is in the Evergreen directory
-    fn is_evergreen_path(&self, path: &Path) -> bool {
-        path.components().any(|c| {
-            if let std::path::Component::Normal(name) = c {
-                name == "Evergreen"
-            } else {
-                false
-            }
-        })
-    }
-
-    /// Index a single file
+    /// Index a single file, from the editor's buffer when it is open
     pub fn index_file(&mut self, path: &Path) -> anyhow::Result<()> {
-        let content = std::fs::read_to_string(path)?;
+        let content = self.source(path)?;
         let uri = Url::from_file_path(path).map_err(|_| anyhow::anyhow!("Invalid path"))?;
+        self.update_file(&uri, &content);
+        Ok(())
+    }
 
-        if let Some(tree) = self.parser.parse(&content) {
-            let symbols = self.parser.extract_symbols(&tree, &content);
-            let module_name = self
-                .extract_module_name(&tree, &content)
-                .unwrap_or_else(|| self.path_to_module_name(path));
-            let imports = self.extract_imports(&tree, &content);
-            let exposing = self.extract_exposing(&tree, &content);
-
-            // Index for type checking
-            self.type_checker
-                .index_file(uri.as_str(), &content, tree.clone());
-
-            // Index references from this file
-            self.find_references_in_tree(&tree, &content, &uri, &module_name, &imports);
-
-            // Add symbols to global index
-            for symbol in &symbols {
-                let qualified_name = format!("{}.{}", module_name, symbol.name);
-
-                let global_symbol = GlobalSymbol {
-                    name: symbol.name.clone(),
-                    module_name: module_name.clone(),
-                    kind: symbol.kind,
-                    definition_uri: uri.clone(),
-                    definition_range: symbol.definition_range.unwrap_or(symbol.range),
-                    signature: symbol.signature.clone(),
-                };
-
-                self.symbols
-                    .entry(symbol.name.clone())
-                    .or_default()
-                    .push(global_symbol.clone());
+    /// Index the text of a file open in the editor, and read it instead of
+    /// the file until [`Self::close_buffer`]
+    pub fn open_buffer(&mut self, uri: &Url, text: &str) {
+        if let Ok(path) = uri.to_file_path() {
+            self.buffers.insert(path, text.to_string());
+        }
+        self.update_file(uri, text);
+    }
 
-                // Also index by qualified name
-                self.symbols
-                    .entry(qualified_name)
-                    .or_default()
-                    .push(global_symbol);
+    /// Go back to the file on disk once the editor closes it, dropping
+    /// unsaved changes from the index
+    pub fn close_buffer(&mut self, uri: &Url) {
+        let Ok(path) = uri.to_file_path() else {
+            return;
+        };
+        if self.buffers.remove(&path).is_some() && path.is_file() {
+            if let Err(e) = self.index_file(&path) {
+                tracing::warn!(
+                    target: logging::INDEX,
+                    "Failed to re-index {}: {}",
+                    path.display(),
+                    e
+                );
             }
-
-            let module = ElmModule {
-                path: path.to_path_buf(),
-                module_name: module_name.clone(),
-                symbols,
-                imports,
-                exposing,
-            };
-
-            self.modules.insert(module_name, module);
         }
+    }
 
-        Ok(())
+    /// The text of a source file: the editor's buffer when it is open, and
+    /// otherwise the file on disk
+    pub fn source(&self, path: &Path) -> std::io::Result<String> {
+        match self.buffers.get(path) {
+            Some(text) => Ok(text.clone()),
+            None => read_source(path),
+        }
     }
 
     /// Update a file in the index (called on didChange)
+    /// Does nothing if the content is identical to what the index was built from.
     pub fn update_file(&mut self, uri: &Url, content: &str) {
         let path = match uri.to_file_path() {
             Ok(p) => p,
             Err(_) => return,
         };
 
-        // Remove old symbols for this file
-        let old_module_name = self
-            .modules
-            .iter()
-            .find(|(_, m)| m.path == path)
-            .map(|(name, _)| name.clone());
+        let content_hash = IncrementalIndex::hash_content(content);
+        if self.inputs.is_fresh(uri, content_hash) {
+            return;
+        }
 
-        if let Some(module_name) = old_module_name {
-            self.modules.remove(&module_name);
-            // Clean up symbols from this module
-            for symbols in self.symbols.values_mut() {
-                symbols.retain(|s| s.module_name != module_name);
+        let tree = match self.parser.parse(content) {
+            Some(tree) => tree,
+            None => {
+                self.retract_file(uri, &path);
+                return;
             }
-        }
+        };
+        self.index_tree(uri, path, content, content_hash, tree, None);
+    }
 
-        // Invalidate type checker cache for this file
-        self.type_checker.invalidate_file(uri.as_str());
+    /// Index the parse tree of a file, replacing what the file contributed
+    /// before. `analysis` is the type checker's, when already done.
+    fn index_tree(
+        &mut self,
+        uri: &Url,
+        path: PathBuf,
+        content: &str,
+        content_hash: u64,
+        tree: tree_sitter::Tree,
+        analysis: Option<FileAnalysis>,
+    ) {
+        self.retract_file(uri, &path);
 
-        // Remove old references from this file
-        for refs in self.references.values_mut() {
-            refs.retain(|r| r.uri != *uri);
-        }
-        // Remove empty entries
-        self.references.retain(|_, refs| !refs.is_empty());
-
-        // Re-index the file
-        if let Some(tree) = self.parser.parse(content) {
-            let symbols = self.parser.extract_symbols(&tree, content);
-            let module_name = self
-                .extract_module_name(&tree, content)
-                .unwrap_or_else(|| self.path_to_module_name(&path));
-            let imports = self.extract_imports(&tree, content);
-            let exposing = self.extract_exposing(&tree, content);
-
-            // Re-index for type checking
-            self.type_checker
-                .index_file(uri.as_str(), content, tree.clone());
-
-            // Re-index references for this file
-            self.find_references_in_tree(&tree, content, uri, &module_name, &imports);
-
-            for symbol in &symbols {
-                let global_symbol = GlobalSymbol {
-                    name: symbol.name.clone(),
-                    module_name: module_name.clone(),
-                    kind: symbol.kind,
-                    definition_uri: uri.clone(),
-                    definition_range: symbol.definition_range.unwrap_or(symbol.range),
-                    signature: symbol.signature.clone(),
-                };
+        let symbols = self.parser.extract_symbols(&tree, content);
+        let module_name = self
+            .extract_module_name(&tree, content)
+            .unwrap_or_else(|| self.path_to_module_name(&path));
+        let imports = self.extract_imports(&tree, content);
+        let exposing = self.extract_exposing(&tree, content);
+        let program = self.detect_program(&tree, content, &module_name, &imports);
+
+        // Large and generated files keep symbols and imports for navigation,
+        // but skip reference indexing and type checking
+        let light = self.is_light_file(&path, content.len());
+        let generated = self.is_generated_path(&path);
+        let mut reference_keys: HashSet<String> = HashSet::new();
+        if light {
+            tracing::debug!(target: logging::INDEX, "Indexing {:?} for navigation only", path);
+        } else {
+            for (key, reference) in self.find_references_in_tree(&tree, content, uri, &imports) {
+                self.references
+                    .entry(key.clone())
+                    .or_default()
+                    .push(reference);
+                reference_keys.insert(key);
+            }
+
+            match analysis {
+                Some(analysis) => {
+                    self.type_checker
+                        .insert_analysis(uri.as_str(), content, tree, analysis)
+                }
+                None => self.type_checker.index_file(uri.as_str(), content, tree),
+            }
+        }
 
+        // Add symbols to global index, by unqualified and qualified name
+        let mut symbol_keys: HashSet<String> = HashSet::new();
+        for symbol in &symbols {
+            let global_symbol = GlobalSymbol {
+                name: symbol.name.clone(),
+                module_name: module_name.clone(),
+                kind: symbol.kind,
+                definition_uri: uri.clone(),
+                definition_range: symbol.definition_range.unwrap_or(symbol.range),
+                range: symbol.range,
+                signature: symbol.signature.clone(),
+            };
+
+            let qualified_name = format!("{}.{}", module_name, symbol.name);
+            for key in [symbol.name.clone(), qualified_name] {
                 self.symbols
-                    .entry(symbol.name.clone())
+                    .entry(key.clone())
                     .or_default()
-                    .push(global_symbol);
+                    .push(global_symbol.clone());
+                symbol_keys.insert(key);
             }
+        }
 
-            let module = ElmModule {
+        self.import_graph.set_imports(
+            &module_name,
+            imports.iter().map(|import| import.module_name.clone()),
+        );
+        self.modules.insert(
+            module_name.clone(),
+            ElmModule {
                 path,
                 module_name: module_name.clone(),
                 symbols,
                 imports,
                 exposing,
-            };
+                light,
+                generated,
+                program,
+            },
+        );
 
-            self.modules.insert(module_name, module);
-        }
+        self.inputs.record(
+            uri.clone(),
+            content_hash,
+            module_name,
+            symbol_keys.into_iter().collect(),
+            reference_keys.into_iter().collect(),
+        );
     }
 
     /// Remove a file from the index
@@ -584,30 +1111,41 @@ impl Workspace {
             Ok(p) => p,
             Err(_) => return,
         };
+        self.retract_file(uri, &path);
+    }
 
-        // Find and remove the module by path
-        let module_name = self
-            .modules
-            .iter()
-            .find(|(_, m)| m.path == path)
-            .map(|(name, _)| name.clone());
+    /// Retract everything a file contributed to the indexes.
+    /// Uses the keys recorded in `inputs` so only the affected entries are touched.
+    fn retract_file(&mut self, uri: &Url, path: &Path) {
+        self.type_checker.invalidate_file(uri.as_str());
 
-        if let Some(module_name) = module_name {
-            self.modules.remove(&module_name);
-            // Clean up symbols from this module
-            for symbols in self.symbols.values_mut() {
-                symbols.retain(|s| s.module_name != module_name);
-            }
+        let input = match self.inputs.retract(uri) {
+            Some(input) => input,
+            None => return,
+        };
+
+        if self.modules.get(&input.module_name).is_some_and(|m| m.path == path) {
+            self.modules.remove(&input.module_name);
+            self.import_graph.remove_module(&input.module_name);
         }
 
-        // Invalidate type checker cache
-        self.type_checker.invalidate_file(uri.as_str());
+        for key in &input.symbol_keys {
+            if let Some(symbols) = self.symbols.get_mut(key) {
+                symbols.retain(|s| s.definition_uri != *uri);
+                if symbols.is_empty() {
+                    self.symbols.remove(key);
+                }
+            }
+        }
 
-        // Remove references from this file
-        for refs in self.references.values_mut() {
-            refs.retain(|r| r.uri != *uri);
+        for key in &input.reference_keys {
+            if let Some(refs) = self.references.get_mut(key) {
+                refs.retain(|r| r.uri != *uri);
+                if refs.is_empty() {
+                    self.references.remove(key);
+                }
+            }
         }
-        self.references.retain(|_, refs| !refs.is_empty());
     }
 
     /// Notify the workspace that a file was renamed/moved
@@ -643,121 +1181,64 @@ impl Workspace {
 
     fn extract_imports(&self, tree: &tree_sitter::Tree, source: &str) -> Vec<ImportInfo> {
         let mut imports = Vec::new();
-        let root = tree.root_node();
-        let mut cursor = root.walk();
-
-        for child in root.children(&mut cursor) {
-            if child.kind() == "import_clause" {
-                let mut module_name = None;
-                let mut alias = None;
-                let mut exposing = ExposingInfo::Explicit(Vec::new());
-
-                let mut inner_cursor = child.walk();
-                for inner_child in child.children(&mut inner_cursor) {
-                    match inner_child.kind() {
-                        "upper_case_qid" => {
-                            module_name = Some(source[inner_child.byte_range()].to_string());
-                        }
-                        "as_clause" => {
-                            let mut as_cursor = inner_child.walk();
-                            for as_child in inner_child.children(&mut as_cursor) {
-                                if as_child.kind() == "upper_case_identifier" {
-                                    alias = Some(source[as_child.byte_range()].to_string());
-                                }
-                            }
-                        }
-                        "exposing_list" => {
-                            exposing = self.parse_exposing_list(inner_child, source);
-                        }
-                        _ => {}
-                    }
-                }
-
-                if let Some(name) = module_name {
-                    imports.push(ImportInfo {
-                        module_name: name,
-                        alias,
-                        exposing,
-                    });
-                }
-            }
-        }
-
-        imports
-    }
+        queries::for_each_match(&queries::IMPORTS, tree.root_node(), source, |m| {
+            let Some(module) = m.get("module") else { return };
+            imports.push(ImportInfo {
+                module_name: source[module.byte_range()].to_string(),
+                alias: m.get("alias").map(|n| source[n.byte_range()].to_string()),
+                exposing: m
+                    .get("exposing")
+                    .map(|n| self.parse_exposing_list(n, source))
+                    .unwrap_or_else(|| ExposingInfo::Explicit(Vec::new())),
+            });
+        });
+        imports
+    }
 
     fn extract_exposing(&self, tree: &tree_sitter::Tree, source: &str) -> ExposingInfo {
-        let root = tree.root_node();
-        let mut cursor = root.walk();
-
-        for child in root.children(&mut cursor) {
-            if child.kind() == "module_declaration" {
-                let mut inner_cursor = child.walk();
-                for inner_child in child.children(&mut inner_cursor) {
-                    if inner_child.kind() == "exposing_list" {
-                        return self.parse_exposing_list(inner_child, source);
-                    }
-                }
+        let mut exposing = None;
+        queries::for_each_match(&queries::IMPORTS, tree.root_node(), source, |m| {
+            if let Some(list) = m.get("module_exposing") {
+                exposing.get_or_insert_with(|| self.parse_exposing_list(list, source));
             }
-        }
-
-        ExposingInfo::Explicit(Vec::new())
+        });
+        exposing.unwrap_or_else(|| ExposingInfo::Explicit(Vec::new()))
     }
 
     fn parse_exposing_list(&self, node: tree_sitter::Node, source: &str) -> ExposingInfo {
-        let mut cursor = node.walk();
+        let mut exposes_all = false;
         let mut exposed = Vec::new();
 
-        for child in node.children(&mut cursor) {
-            match child.kind() {
-                "double_dot" => return ExposingInfo::All,
-                "exposed_value" => {
-                    let mut inner_cursor = child.walk();
-                    for inner_child in child.children(&mut inner_cursor) {
-                        if inner_child.kind() == "lower_case_identifier" {
-                            exposed.push(source[inner_child.byte_range()].to_string());
-                        }
-                    }
-                }
-                "exposed_type" => {
-                    // Capture the full exposed type including (..) if present
-                    // e.g., "EventType(..)" should be stored as "EventType(..)"
-                    let mut type_name = String::new();
-                    let mut has_all_constructors = false;
-                    let mut inner_cursor = child.walk();
-                    for inner_child in child.children(&mut inner_cursor) {
-                        match inner_child.kind() {
-                            "upper_case_identifier" => {
-                                type_name = source[inner_child.byte_range()].to_string();
-                            }
-                            "exposed_union_constructors" => {
-                                // Check if it's (..) meaning all constructors
-                                let text = &source[inner_child.byte_range()];
-                                if text.contains("..") {
-                                    has_all_constructors = true;
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
-                    if !type_name.is_empty() {
-                        if has_all_constructors {
-                            exposed.push(format!("{}(..)", type_name));
-                        } else {
-                            exposed.push(type_name);
-                        }
-                    }
+        queries::for_each_match(&queries::EXPOSING, node, source, |m| {
+            if m.get("all").is_some() {
+                exposes_all = true;
+            } else if let Some(value) = m.get("value") {
+                exposed.push(source[value.byte_range()].to_string());
+            } else if let Some(type_name) = m.get("type") {
+                // Capture the full exposed type including (..) if present
+                // e.g., "EventType(..)" should be stored as "EventType(..)"
+                let type_name = &source[type_name.byte_range()];
+                let has_all_constructors = m
+                    .get("constructors")
+                    .is_some_and(|c| source[c.byte_range()].contains(".."));
+                if has_all_constructors {
+                    exposed.push(format!("{}(..)", type_name));
+                } else {
+                    exposed.push(type_name.to_string());
                 }
-                _ => {}
             }
-        }
+        });
 
-        ExposingInfo::Explicit(exposed)
+        if exposes_all {
+            ExposingInfo::All
+        } else {
+            ExposingInfo::Explicit(exposed)
+        }
     }
 
     fn path_to_module_name(&self, path: &Path) -> String {
         // Convert path like src/Pages/Home.elm to Pages.Home
-        for source_dir in &self.source_dirs {
+        for source_dir in self.source_dirs.iter().chain(&self.test_dirs) {
             if let Ok(relative) = path.strip_prefix(source_dir) {
                 let module_name = relative
                     .with_extension("")
@@ -771,151 +1252,63 @@ impl Workspace {
             .unwrap_or_else(|| "Unknown".to_string())
     }
 
-    /// Build the reference index by scanning all files for symbol usages
-    fn build_reference_index(&mut self) {
-        // Collect module info first to avoid borrow issues
-        let module_info: Vec<_> = self
-            .modules
-            .iter()
-            .map(|(name, m)| (name.clone(), m.path.clone(), m.imports.clone()))
-            .collect();
-
-        for (module_name, path, imports) in module_info {
-            let content = match std::fs::read_to_string(&path) {
-                Ok(c) => c,
-                Err(_) => continue,
-            };
-
-            let uri = match Url::from_file_path(&path) {
-                Ok(u) => u,
-                Err(_) => continue,
-            };
-
-            if let Some(tree) = self.parser.parse(&content) {
-                self.find_references_in_tree(&tree, &content, &uri, &module_name, &imports);
-            }
-        }
-    }
-
+    /// Collect every symbol reference in a file, keyed by its resolved name
     fn find_references_in_tree(
-        &mut self,
+        &self,
         tree: &tree_sitter::Tree,
         source: &str,
         uri: &Url,
-        _current_module: &str,
         imports: &[ImportInfo],
-    ) {
-        let root = tree.root_node();
-        self.walk_for_references(root, source, uri, imports);
-    }
+    ) -> Vec<(String, SymbolReference)> {
+        let mut found = Vec::new();
+        let mut push = |text: &str, range: Range, kind: Option<BoundSymbolKind>| {
+            let resolved_name = self.resolve_reference(text, imports);
+            found.push((
+                resolved_name,
+                SymbolReference {
+                    uri: uri.clone(),
+                    range,
+                    is_definition: false,
+                    kind,
+                    type_context: None,
+                },
+            ));
+        };
 
-    fn walk_for_references(
-        &mut self,
-        node: tree_sitter::Node,
-        source: &str,
-        uri: &Url,
-        imports: &[ImportInfo],
-    ) {
-        match node.kind() {
-            "value_qid" | "upper_case_qid" => {
-                let is_in_import = self.is_module_name_in_import(node);
-
-                if !is_in_import {
-                    let text = &source[node.byte_range()];
-                    let kind = self.classify_reference_kind(node, text);
-
-                    if text.contains('.') {
-                        let symbol_name = text.rsplit('.').next().unwrap_or(text);
-                        let symbol_start_col = node.end_position().column - symbol_name.len();
-
-                        let range = Range {
-                            start: Position::new(
-                                node.end_position().row as u32,
-                                symbol_start_col as u32,
-                            ),
-                            end: Position::new(
-                                node.end_position().row as u32,
-                                node.end_position().column as u32,
-                            ),
-                        };
-
-                        let resolved_name = self.resolve_reference(text, imports);
-
-                        self.references
-                            .entry(resolved_name)
-                            .or_default()
-                            .push(SymbolReference {
-                                uri: uri.clone(),
-                                range,
-                                is_definition: false,
-                                kind,
-                                type_context: None,
-                            });
-                    } else {
-                        let range = Range {
-                            start: Position::new(
-                                node.start_position().row as u32,
-                                node.start_position().column as u32,
-                            ),
-                            end: Position::new(
-                                node.end_position().row as u32,
-                                node.end_position().column as u32,
-                            ),
-                        };
-
-                        let resolved_name = self.resolve_reference(text, imports);
-
-                        self.references
-                            .entry(resolved_name)
-                            .or_default()
-                            .push(SymbolReference {
-                                uri: uri.clone(),
-                                range,
-                                is_definition: false,
-                                kind,
-                                type_context: None,
-                            });
-                    }
+        queries::for_each_match(&queries::REFERENCES, tree.root_node(), source, |m| {
+            if let Some(node) = m.get("qualified") {
+                if self.is_module_name_in_import(node) {
+                    return;
                 }
-            }
-            "lower_case_identifier" | "upper_case_identifier" => {
                 let text = &source[node.byte_range()];
-                let in_decl = self.is_in_declaration_context(node);
+                let kind = self.classify_reference_kind(node, text);
 
-                if !in_decl {
-                    let kind = self.classify_reference_kind(node, text);
-                    let range = Range {
+                // Only the last segment of a qualified name is the symbol itself
+                let range = if text.contains('.') {
+                    let symbol_name = text.rsplit('.').next().unwrap_or(text);
+                    let end = node.end_position();
+                    Range {
                         start: Position::new(
-                            node.start_position().row as u32,
-                            node.start_position().column as u32,
+                            end.row as u32,
+                            (end.column - symbol_name.len()) as u32,
                         ),
-                        end: Position::new(
-                            node.end_position().row as u32,
-                            node.end_position().column as u32,
-                        ),
-                    };
-
-                    let resolved_name = self.resolve_reference(text, imports);
-
-                    self.references
-                        .entry(resolved_name)
-                        .or_default()
-                        .push(SymbolReference {
-                            uri: uri.clone(),
-                            range,
-                            is_definition: false,
-                            kind,
-                            type_context: None,
-                        });
+                        end: Position::new(end.row as u32, end.column as u32),
+                    }
+                } else {
+                    self.node_to_lsp_range(node)
+                };
+                push(text, range, kind);
+            } else if let Some(node) = m.get("identifier") {
+                if self.is_in_declaration_context(node) {
+                    return;
                 }
+                let text = &source[node.byte_range()];
+                let kind = self.classify_reference_kind(node, text);
+                push(text, self.node_to_lsp_range(node), kind);
             }
-            _ => {}
-        }
+        });
 
-        let mut cursor = node.walk();
-        for child in node.children(&mut cursor) {
-            self.walk_for_references(child, source, uri, imports);
-        }
+        found
     }
 
     fn classify_reference_kind(
@@ -923,7 +1316,9 @@ impl Workspace {
         node: tree_sitter::Node,
         text: &str,
     ) -> Option<BoundSymbolKind> {
-        let is_uppercase = text.chars().next().is_some_and(|c| c.is_uppercase());
+        // `Module.value` is a value: only the last segment decides
+        let base_name = text.rsplit('.').next().unwrap_or(text);
+        let is_uppercase = base_name.chars().next().is_some_and(|c| c.is_uppercase());
 
         let mut current = node;
         while let Some(parent) = current.parent() {
@@ -962,10 +1357,8 @@ impl Workspace {
                 "field_type" => {
                     return Some(BoundSymbolKind::FieldType);
                 }
-                "field" => {
-                    if current == node {
-                        return Some(BoundSymbolKind::FieldType);
-                    }
+                "field" if current == node => {
+                    return Some(BoundSymbolKind::FieldType);
                 }
                 "port_annotation" => {
                     return Some(BoundSymbolKind::Port);
@@ -1202,13 +1595,87 @@ impl Workspace {
         &self,
         symbol: &DefinitionSymbol,
     ) -> Vec<SymbolReference> {
-        let all_refs = self.find_references(&symbol.name, symbol.module_name.as_deref());
+        let all_refs = match (&symbol.module_name, &symbol.type_context) {
+            (Some(module_name), Some(type_name)) => self.find_module_aware_constructor_references(
+                &symbol.name,
+                type_name,
+                module_name,
+                &symbol.uri,
+            ),
+            _ => self.find_references(&symbol.name, symbol.module_name.as_deref()),
+        };
         all_refs
             .into_iter()
             .filter(|r| matches!(r.kind, Some(BoundSymbolKind::UnionConstructor) | None))
             .collect()
     }
 
+    /// Find references to the constructor `name` declared in the file at `uri`,
+    /// in expressions and patterns, qualified or not, across the workspace
+    pub fn find_constructor_references_at(&self, uri: &Url, name: &str) -> Vec<SymbolReference> {
+        let module = self.get_module_at_uri(uri);
+        let type_name = module.and_then(|m| {
+            m.symbols
+                .iter()
+                .find(|s| s.variants.iter().any(|v| v.name == name))
+                .map(|s| s.name.clone())
+        });
+        self.find_constructor_references_typed(&DefinitionSymbol {
+            name: name.to_string(),
+            kind: BoundSymbolKind::UnionConstructor,
+            uri: uri.clone(),
+            range: Range::default(),
+            type_context: type_name,
+            module_name: module.map(|m| m.module_name.clone()),
+            scope_range: None,
+        })
+    }
+
+    /// Constructor references that resolve to `defining_module`: qualified
+    /// usages (aliases included), unqualified usages in the defining file, and
+    /// unqualified usages in files importing it with `(..)` or `TypeName(..)`
+    fn find_module_aware_constructor_references(
+        &self,
+        name: &str,
+        type_name: &str,
+        defining_module: &str,
+        defining_uri: &Url,
+    ) -> Vec<SymbolReference> {
+        let mut results = Vec::new();
+
+        if let Some(refs) = self
+            .references
+            .get(&format!("{}.{}", defining_module, name))
+        {
+            results.extend(refs.iter().cloned());
+        }
+
+        let exposing_type = format!("{}(..)", type_name);
+        if let Some(refs) = self.references.get(name) {
+            for r in refs {
+                if &r.uri == defining_uri {
+                    results.push(r.clone());
+                    continue;
+                }
+                let exposed = self.get_module_at_uri(&r.uri).is_some_and(|module| {
+                    module.imports.iter().any(|imp| {
+                        imp.module_name == defining_module
+                            && match &imp.exposing {
+                                ExposingInfo::All => true,
+                                ExposingInfo::Explicit(names) => names.contains(&exposing_type),
+                            }
+                    })
+                });
+                if exposed {
+                    results.push(r.clone());
+                }
+            }
+        }
+
+        Self::deduplicate_references(&mut results);
+        results
+    }
+
     /// Find references to a record field using the DefinitionSymbol
     /// This uses the existing type-aware field reference finder
     pub fn find_field_references_typed(
@@ -1469,6 +1936,7 @@ impl Workspace {
         let base_name = Self::extract_base_name(symbol_name);
 
         tracing::debug!(
+            target: logging::NAVIGATION,
             "find_module_aware_references: symbol={}, defining_module={}, defining_uri={}",
             base_name,
             defining_module,
@@ -1480,6 +1948,7 @@ impl Workspace {
         if let Some(refs) = self.references.get(&qualified_key) {
             for r in refs {
                 tracing::debug!(
+                    target: logging::NAVIGATION,
                     "  Including qualified ref (key={}): {} {:?}",
                     qualified_key,
                     r.uri.as_str(),
@@ -1496,6 +1965,7 @@ impl Workspace {
                 // Always include refs from the defining file
                 if &r.uri == defining_uri {
                     tracing::debug!(
+                        target: logging::NAVIGATION,
                         "  Including unqualified ref from definition file: {:?}",
                         r.range
                     );
@@ -1528,6 +1998,7 @@ impl Workspace {
 
                         if symbol_is_exposed {
                             tracing::debug!(
+                                target: logging::NAVIGATION,
                                 "  Including exposed unqualified ref from {}: {:?}",
                                 r.uri.as_str(),
                                 r.range
@@ -1535,6 +2006,7 @@ impl Workspace {
                             results.push(r.clone());
                         } else {
                             tracing::debug!(
+                                target: logging::NAVIGATION,
                                 "  Excluding unqualified ref from {} (not exposed from {}): {:?}",
                                 r.uri.as_str(),
                                 defining_module,
@@ -1592,6 +2064,87 @@ impl Workspace {
         None
     }
 
+    /// What an `import` at `position` points to: the module's file for the
+    /// module name, or the declaration of an item in its exposing list.
+    /// Package modules are found as well as workspace ones.
+    pub fn import_target(&self, uri: &Url, position: Position) -> Option<GlobalSymbol> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+        let node = tree.root_node().descendant_for_point_range(point, point)?;
+        let import = std::iter::successors(Some(node), |n| n.parent())
+            .find(|n| n.kind() == "import_clause")?;
+        let module_node = import.child_by_field_name("moduleName")?;
+        let module_name = &source[module_node.byte_range()];
+
+        let item = std::iter::successors(Some(node), |n| n.parent())
+            .take_while(|n| n.id() != import.id())
+            .find(|n| {
+                matches!(
+                    n.kind(),
+                    "exposed_value" | "exposed_type" | "exposed_operator"
+                )
+            });
+        let item = match item {
+            Some(item) => item,
+            None if module_node.byte_range().contains(&node.start_byte()) => {
+                let start = Range::default();
+                return Some(GlobalSymbol {
+                    name: module_name.to_string(),
+                    module_name: module_name.to_string(),
+                    kind: SymbolKind::MODULE,
+                    definition_uri: self.module_uri(module_name)?,
+                    definition_range: start,
+                    range: start,
+                    signature: None,
+                });
+            }
+            None => return None,
+        };
+        // `Type(..)` names the type; operators are written `(+)`
+        let name = source[item.byte_range()]
+            .trim_end_matches("(..)")
+            .trim_start_matches('(')
+            .trim_end_matches(')');
+
+        self.declared_symbol(module_name, name)
+    }
+
+    /// The top-level declaration `name` of the workspace or package module
+    /// `module_name`
+    fn declared_symbol(&self, module_name: &str, name: &str) -> Option<GlobalSymbol> {
+        if let Some(module) = self.modules.get(module_name) {
+            let symbol = module.symbols.iter().find(|s| s.name == name)?;
+            return Some(GlobalSymbol {
+                name: symbol.name.clone(),
+                module_name: module_name.to_string(),
+                kind: symbol.kind,
+                definition_uri: Url::from_file_path(&module.path).ok()?,
+                definition_range: symbol.definition_range.unwrap_or(symbol.range),
+                range: symbol.range,
+                signature: symbol.signature.clone(),
+            });
+        }
+        self.external_symbols
+            .get(name)?
+            .iter()
+            .find(|s| s.module_name == module_name)
+            .cloned()
+    }
+
+    /// File of the module `module_name`, in the workspace or a package
+    fn module_uri(&self, module_name: &str) -> Option<Url> {
+        if let Some(module) = self.modules.get(module_name) {
+            return Url::from_file_path(&module.path).ok();
+        }
+        let relative = format!("{}.elm", module_name.replace('.', "/"));
+        self.external_packages
+            .iter()
+            .flat_map(|p| [p.path.join("src").join(&relative), p.path.join(&relative)])
+            .find(|path| path.is_file())
+            .and_then(|path| Url::from_file_path(path).ok())
+    }
+
     /// Get all symbols matching a name (searches both qualified and unqualified)
     pub fn get_symbols(&self, name: &str) -> Vec<&GlobalSymbol> {
         let mut results = Vec::new();
@@ -1613,6 +2166,27 @@ impl Workspace {
         results
     }
 
+    /// Symbols matching a workspace/symbol query, each with its
+    /// [`matcher::score`] (lower is better). Matches on the qualified name
+    /// count as substring matches.
+    pub fn search_symbols(&self, query: &str) -> Vec<(u32, &GlobalSymbol)> {
+        let query = query.to_lowercase();
+        self.symbols
+            .iter()
+            .filter(|(key, _)| !key.contains('.'))
+            .flat_map(|(_, symbols)| symbols.iter())
+            .filter_map(|sym| {
+                let score = matcher::score(&sym.name, &query).or_else(|| {
+                    format!("{}.{}", sym.module_name, sym.name)
+                        .to_lowercase()
+                        .contains(&query)
+                        .then_some(3)
+                })?;
+                Some((score, sym))
+            })
+            .collect()
+    }
+
     /// Get module by name
     pub fn get_module(&self, name: &str) -> Option<&ElmModule> {
         self.modules.get(name)
@@ -1654,7 +2228,7 @@ impl Workspace {
     /// Read file content from a URI
     fn read_file_content(&self, uri: &Url) -> Option<String> {
         let path = uri.to_file_path().ok()?;
-        std::fs::read_to_string(&path).ok()
+        self.source(&path).ok()
     }
 
     /// Find a node at a specific point in the tree
@@ -1711,280 +2285,6 @@ impl Workspace {
         None
     }
 
-    /// Find the enclosing function scope for a node (value_declaration or let_in_expr)
-    fn find_enclosing_scope(node: tree_sitter::Node) -> Option<tree_sitter::Node> {
-        let mut current = Some(node);
-        while let Some(n) = current {
-            match n.kind() {
-                "value_declaration" | "let_in_expr" => return Some(n),
-                _ => current = n.parent(),
-            }
-        }
-        None
-    }
-
-    /// Find all variable usages within a scope that match the given name
-    /// Returns a list of (range, node_id) for each usage
-    fn find_variable_usages_in_scope(
-        &self,
-        scope_node: tree_sitter::Node,
-        source: &str,
-        var_name: &str,
-        exclude_node: tree_sitter::Node,
-    ) -> Vec<(Range, usize)> {
-        let mut usages = Vec::new();
-        self.walk_for_variable_usages(scope_node, source, var_name, exclude_node.id(), &mut usages);
-        usages
-    }
-
-    /// Recursively walk the tree to find variable usages
-    fn walk_for_variable_usages(
-        &self,
-        node: tree_sitter::Node,
-        source: &str,
-        var_name: &str,
-        exclude_id: usize,
-        usages: &mut Vec<(Range, usize)>,
-    ) {
-        // Skip the excluded node (the pattern field itself)
-        if node.id() == exclude_id {
-            return;
-        }
-
-        // Check if this is a value_expr that matches our variable name
-        if node.kind() == "value_expr" || node.kind() == "lower_case_identifier" {
-            if let Ok(text) = node.utf8_text(source.as_bytes()) {
-                if text == var_name {
-                    // Check that this is a variable usage, not a field name
-                    // Field names would have parent of field_type, field_access_expr, field, etc.
-                    let parent_kind = node.parent().map(|p| p.kind());
-                    let is_field_context = matches!(
-                        parent_kind,
-                        Some("field_type")
-                            | Some("field_access_expr")
-                            | Some("field_accessor_function_expr")
-                            | Some("record_pattern")
-                    );
-                    // Also check if this is the field name in a record field (not the value)
-                    let is_field_name_in_field = if parent_kind == Some("field") {
-                        // In a `field` node, the first child is the field name
-                        node.parent()
-                            .and_then(|p| p.child(0))
-                            .map(|c| c.id() == node.id())
-                            .unwrap_or(false)
-                    } else {
-                        false
-                    };
-
-                    // Also check if this is the function being called in a function_call_expr
-                    // e.g., `emailInput userConfig ...` where emailInput is a top-level function
-                    // In this case, the identifier is NOT a variable usage of the pattern-bound name
-                    let is_function_call_target = if let Some(parent) = node.parent() {
-                        if parent.kind() == "function_call_expr" {
-                            // Check if this node is the first child (the function being called)
-                            parent
-                                .child(0)
-                                .map(|c| c.id() == node.id())
-                                .unwrap_or(false)
-                        } else if parent.kind() == "value_expr" {
-                            // value_expr might wrap the identifier, check grandparent
-                            if let Some(grandparent) = parent.parent() {
-                                if grandparent.kind() == "function_call_expr" {
-                                    grandparent
-                                        .child(0)
-                                        .map(|c| c.id() == parent.id())
-                                        .unwrap_or(false)
-                                } else {
-                                    false
-                                }
-                            } else {
-                                false
-                            }
-                        } else if parent.kind() == "value_qid" {
-                            // value_qid wraps the identifier, check grandparent (value_expr) and great-grandparent (function_call_expr)
-                            // Structure: function_call_expr → value_expr → value_qid → lower_case_identifier
-                            if let Some(grandparent) = parent.parent() {
-                                if grandparent.kind() == "value_expr" {
-                                    if let Some(great_grandparent) = grandparent.parent() {
-                                        if great_grandparent.kind() == "function_call_expr" {
-                                            great_grandparent
-                                                .child(0)
-                                                .map(|c| c.id() == grandparent.id())
-                                                .unwrap_or(false)
-                                        } else {
-                                            false
-                                        }
-                                    } else {
-                                        false
-                                    }
-                                } else {
-                                    false
-                                }
-                            } else {
-                                false
-                            }
-                        } else {
-                            false
-                        }
-                    } else {
-                        false
-                    };
-
-                    if !is_field_context && !is_field_name_in_field && !is_function_call_target {
-                        let range = Range {
-                            start: Position::new(
-                                node.start_position().row as u32,
-                                node.start_position().column as u32,
-                            ),
-                            end: Position::new(
-                                node.end_position().row as u32,
-                                node.end_position().column as u32,
-                            ),
-                        };
-                        usages.push((range, node.id()));
-                    }
-                }
-            }
-        }
-
-        // Recurse into children
-        let mut cursor = node.walk();
-        for child in node.children(&mut cursor) {
-            self.walk_for_variable_usages(child, source, var_name, exclude_id, usages);
-        }
-    }
-
-    /// Check if a scope contains other bindings for the given variable name
-    /// (case patterns, function parameters, let bindings, lambda parameters, etc.)
-    /// This is used to avoid renaming variable usages that are bound by something OTHER than
-    /// the record pattern we're processing.
-    fn scope_has_other_bindings(
-        scope_node: tree_sitter::Node,
-        source: &str,
-        var_name: &str,
-        exclude_node_id: usize,
-    ) -> bool {
-        Self::walk_for_other_bindings(scope_node, source, var_name, exclude_node_id)
-    }
-
-    /// Recursively check for variable bindings in patterns
-    fn walk_for_other_bindings(
-        node: tree_sitter::Node,
-        source: &str,
-        var_name: &str,
-        exclude_node_id: usize,
-    ) -> bool {
-        // Skip the node we're renaming (the record pattern field itself)
-        if node.id() == exclude_node_id {
-            return false;
-        }
-
-        let node_kind = node.kind();
-
-        // Check for bindings in various pattern types
-        let is_binding = match node_kind {
-            // Case pattern bindings: `Just x -> ...`
-            "pattern" | "union_pattern" | "cons_pattern" => {
-                // Look for lower_case_identifier children that match
-                let mut cursor = node.walk();
-                for child in node.children(&mut cursor) {
-                    if child.kind() == "lower_case_identifier" && child.id() != exclude_node_id {
-                        if let Ok(text) = child.utf8_text(source.as_bytes()) {
-                            if text == var_name {
-                                return true;
-                            }
-                        }
-                    }
-                }
-                false
-            }
-            // Let bindings: `let x = ... in ...`
-            "value_declaration" if node.parent().map(|p| p.kind()) == Some("let_in_expr") => {
-                // Check the function name
-                if let Some(decl) = node.child_by_field_name("functionDeclarationLeft") {
-                    if let Some(name_node) = decl.child(0) {
-                        if name_node.id() != exclude_node_id {
-                            if let Ok(text) = name_node.utf8_text(source.as_bytes()) {
-                                if text == var_name {
-                                    return true;
-                                }
-                            }
-                        }
-                    }
-                }
-                false
-            }
-            // Function parameters (excluding the record pattern itself)
-            "function_declaration_left" => {
-                // Check all patterns in the function declaration
-                let mut cursor = node.walk();
-                for child in node.children(&mut cursor) {
-                    if child.kind() == "lower_pattern" && child.id() != exclude_node_id {
-                        if let Ok(text) = child.utf8_text(source.as_bytes()) {
-                            if text == var_name {
-                                return true;
-                            }
-                        }
-                    }
-                }
-                false
-            }
-            // Lambda parameters: `\x -> ...`
-            "anonymous_function_expr" => {
-                // Check the patterns before the arrow
-                let mut cursor = node.walk();
-                for child in node.children(&mut cursor) {
-                    if (child.kind() == "pattern" || child.kind() == "lower_pattern")
-                        && child.id() != exclude_node_id
-                    {
-                        if let Ok(text) = child.utf8_text(source.as_bytes()) {
-                            if text == var_name {
-                                return true;
-                            }
-                        }
-                    }
-                }
-                false
-            }
-            // Lower case identifier in a pattern context (catch-all)
-            "lower_pattern" | "lower_case_identifier" => {
-                // Check if this is in a pattern context (case, lambda, etc.)
-                let parent_kind = node.parent().map(|p| p.kind());
-                let is_pattern_context = matches!(
-                    parent_kind,
-                    Some("pattern")
-                        | Some("union_pattern")
-                        | Some("cons_pattern")
-                        | Some("case_of_branch")
-                        | Some("anonymous_function_expr")
-                );
-                if is_pattern_context && node.id() != exclude_node_id {
-                    if let Ok(text) = node.utf8_text(source.as_bytes()) {
-                        if text == var_name {
-                            return true;
-                        }
-                    }
-                }
-                false
-            }
-            _ => false,
-        };
-
-        if is_binding {
-            return true;
-        }
-
-        // Recurse into children
-        let mut cursor = node.walk();
-        for child in node.children(&mut cursor) {
-            if Self::walk_for_other_bindings(child, source, var_name, exclude_node_id) {
-                return true;
-            }
-        }
-
-        false
-    }
-
     /// Classify the definition at a given position
     /// Returns a DefinitionSymbol if the position is on a valid definition
     pub fn classify_definition_at_position(
@@ -2264,6 +2564,11 @@ impl Workspace {
                     return None;
                 }
 
+                // An item of the module's own exposing list or of an import's
+                "exposed_value" | "exposed_type" | "exposed_operator" => {
+                    return self.classify_exposed_item(current, source, module_name);
+                }
+
                 "file" => return None,
 
                 _ => {}
@@ -2273,6 +2578,42 @@ impl Workspace {
         }
     }
 
+    /// The declaration an exposing list item names: in this module for the
+    /// module header, in the imported module for an import
+    fn classify_exposed_item(
+        &self,
+        item: tree_sitter::Node,
+        source: &str,
+        module_name: Option<String>,
+    ) -> Option<DefinitionSymbol> {
+        let text = self.node_text(source, item);
+        let name = text
+            .trim_end_matches("(..)")
+            .trim_start_matches('(')
+            .trim_end_matches(')');
+        let owner = match self.find_ancestor_of_kind(item, "import_clause") {
+            Some(import) => self.node_text(source, import.child_by_field_name("moduleName")?),
+            None => module_name?,
+        };
+        let module = self.modules.get(&owner)?;
+        let symbol = module.symbols.iter().find(|s| s.name == name)?;
+        let kind = match symbol.kind {
+            SymbolKind::ENUM => BoundSymbolKind::Type,
+            SymbolKind::STRUCT => BoundSymbolKind::TypeAlias,
+            SymbolKind::INTERFACE => BoundSymbolKind::Port,
+            _ => BoundSymbolKind::Function,
+        };
+        Some(DefinitionSymbol {
+            name: name.to_string(),
+            kind,
+            uri: Url::from_file_path(&module.path).ok()?,
+            range: symbol.definition_range.unwrap_or(symbol.range),
+            type_context: None,
+            module_name: Some(owner),
+            scope_range: None,
+        })
+    }
+
     fn find_ancestor_of_kind<'a>(
         &self,
         node: tree_sitter::Node<'a>,
@@ -2554,6 +2895,48 @@ value = help
         drop(temp_dir);
     }
 
+    #[test]
+    fn test_rename_file_rewrites_qualified_usages() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        fs::write(
+            src_dir.join("Helper.elm"),
+            "module Helper exposing (Id(..), help)\n\ntype Id = Id Int\n\nhelp = 42\n",
+        )
+        .unwrap();
+        fs::write(
+            src_dir.join("Main.elm"),
+            "module Main exposing (..)\n\nimport Helper\n\nid : Helper.Id\nid = Helper.Id Helper.help\n\nlabel = \"Helper.help\"\n",
+        )
+        .unwrap();
+        fs::write(
+            src_dir.join("Page.elm"),
+            "module Page exposing (..)\n\nimport Helper as H\n    exposing (help)\n\nx = H.help\n",
+        )
+        .unwrap();
+        workspace.initialize().unwrap();
+
+        let helper_uri = Url::from_file_path(src_dir.join("Helper.elm")).unwrap();
+        let result = workspace.rename_file(&helper_uri, "Util.elm").unwrap();
+        assert_eq!(result.files_updated, 2);
+
+        let main_uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let mut main_edits: Vec<(u32, u32)> = result.changes[&main_uri]
+            .iter()
+            .map(|e| (e.range.start.line, e.range.start.character))
+            .collect();
+        main_edits.sort();
+        // The import, the annotation and both qualified values, but not the string
+        assert_eq!(main_edits, vec![(2, 7), (4, 5), (5, 5), (5, 15)]);
+
+        // The aliased import keeps its alias, so `H.help` is untouched
+        let page_uri = Url::from_file_path(src_dir.join("Page.elm")).unwrap();
+        let page_edits = &result.changes[&page_uri];
+        assert_eq!(page_edits.len(), 1);
+        assert_eq!(page_edits[0].range.start, Position::new(2, 7));
+        assert_eq!(page_edits[0].new_text, "Util");
+    }
+
     #[test]
     fn test_find_module_declaration_range() {
         let content = "module MyModule exposing (..)\n\nvalue = 42";
@@ -2565,6 +2948,542 @@ value = help
         assert_eq!(range.start.character, 0);
     }
 
+    #[test]
+    fn test_index_file_with_bom_and_crlf() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+
+        let content = "\u{FEFF}module Win exposing (value)\r\n\r\nvalue : Int\r\nvalue = 1\r\n";
+        fs::write(src_dir.join("Win.elm"), content).unwrap();
+        workspace.initialize().unwrap();
+
+        let module = workspace.modules.get("Win").unwrap();
+        let value = module.symbols.iter().find(|s| s.name == "value").unwrap();
+        assert_eq!(value.range.start, Position::new(2, 0));
+        assert_eq!(value.range.end, Position::new(3, 9));
+
+        // The exposing entry on the BOM line is not shifted by the BOM bytes
+        let uri = Url::from_file_path(src_dir.join("Win.elm")).unwrap();
+        let refs = workspace.find_references("value", Some("Win"));
+        assert!(refs
+            .iter()
+            .any(|r| r.uri == uri && r.range.start == Position::new(0, 21)));
+
+        drop(temp_dir);
+    }
+
+    #[test]
+    fn test_excluded_file_is_indexed_for_navigation_only() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let generated_dir = temp_dir.path().join("src/Generated");
+        fs::create_dir_all(&generated_dir).unwrap();
+
+        let content = "module Generated.Api exposing (get)\n\nget : Int\nget = helper\n\nhelper : Int\nhelper = 1\n";
+        fs::write(generated_dir.join("Api.elm"), content).unwrap();
+        workspace.initialize().unwrap();
+
+        let module = workspace.modules.get("Generated.Api").unwrap();
+        assert!(module.light);
+        assert!(module.symbols.iter().any(|s| s.name == "get"));
+        assert!(workspace.symbols.contains_key("Generated.Api.get"));
+        assert!(workspace
+            .find_references("helper", Some("Generated.Api"))
+            .is_empty());
+
+        // Clearing the exclude list re-indexes the file with full analysis
+        workspace.apply_settings(Settings {
+            exclude: Vec::new(),
+            ..Settings::default()
+        });
+        assert!(!workspace.modules.get("Generated.Api").unwrap().light);
+        assert!(!workspace
+            .find_references("helper", Some("Generated.Api"))
+            .is_empty());
+
+        drop(temp_dir);
+    }
+
+    #[test]
+    fn test_refresh_packages_picks_up_installed_package() {
+        let temp_dir = TempDir::new().unwrap();
+        let elm_home = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        let elm_json = r#"{
+            "source-directories": ["src"],
+            "dependencies": { "direct": { "elm/json": "1.1.3" }, "indirect": {} }
+        }"#;
+        fs::write(temp_dir.path().join("elm.json"), elm_json).unwrap();
+
+        let mut workspace = Workspace::new(temp_dir.path().to_path_buf());
+        workspace.elm_home = elm_home.path().to_path_buf();
+        workspace.initialize().unwrap();
+        assert!(workspace.external_packages.is_empty());
+        assert!(workspace.refresh_packages().unwrap().is_empty());
+
+        // `elm install` downloads the package into ELM_HOME
+        let package_src = workspace.packages_dir().join("elm/json/1.1.3/src/Json");
+        fs::create_dir_all(&package_src).unwrap();
+        fs::write(
+            package_src.join("Decode.elm"),
+            "module Json.Decode exposing (int)\n\nint : Decoder Int\nint = int\n",
+        )
+        .unwrap();
+
+        assert_eq!(workspace.refresh_packages().unwrap(), vec!["elm/json"]);
+        assert!(workspace.external_symbols.contains_key("Json.Decode.int"));
+
+        // Nothing changed since, so nothing is re-indexed
+        assert!(workspace.refresh_packages().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_missing_imports_offer_cached_packages() {
+        let temp_dir = TempDir::new().unwrap();
+        let elm_home = TempDir::new().unwrap();
+        let src_dir = temp_dir.path().join("src");
+        fs::create_dir_all(&src_dir).unwrap();
+        let elm_json = r#"{ "source-directories": ["src"], "elm-version": "0.19.1" }"#;
+        fs::write(temp_dir.path().join("elm.json"), elm_json).unwrap();
+        let main =
+            "module Main exposing (..)\n\nimport Json.Decode.Pipeline\nimport Missing.Everywhere\n";
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+
+        // A package another project downloaded, in two versions
+        let packages = elm_home.path().join("0.19.1/packages");
+        for version in ["1.0.0", "1.0.1"] {
+            let dir = packages
+                .join("NoRedInk/elm-json-decode-pipeline")
+                .join(version);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(
+                dir.join("elm.json"),
+                r#"{ "type": "package", "exposed-modules": ["Json.Decode.Pipeline"] }"#,
+            )
+            .unwrap();
+        }
+
+        let mut workspace = Workspace::new(temp_dir.path().to_path_buf());
+        workspace.elm_home = elm_home.path().to_path_buf();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let everything = Range::new(Position::new(0, 0), Position::new(10, 0));
+        assert_eq!(
+            workspace.missing_import_packages(&uri, everything),
+            vec![(
+                "Json.Decode.Pipeline".to_string(),
+                vec!["NoRedInk/elm-json-decode-pipeline".to_string()]
+            )]
+        );
+
+        // Installing goes through the project's elm-json
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let bin = temp_dir.path().join("node_modules/.bin");
+            fs::create_dir_all(&bin).unwrap();
+            fs::write(bin.join("elm-json"), "#!/bin/sh\necho \"$@\" > installed\n").unwrap();
+            fs::set_permissions(bin.join("elm-json"), fs::Permissions::from_mode(0o755)).unwrap();
+            workspace
+                .package_install("NoRedInk/elm-json-decode-pipeline")
+                .unwrap()
+                .run()
+                .unwrap();
+            let args = fs::read_to_string(temp_dir.path().join("installed")).unwrap();
+            assert_eq!(
+                args.trim(),
+                "install --yes NoRedInk/elm-json-decode-pipeline"
+            );
+
+            // Restricted mode offers nothing and runs nothing
+            fs::remove_file(temp_dir.path().join("installed")).unwrap();
+            workspace.settings.restricted = true;
+            assert!(workspace
+                .missing_import_packages(&uri, everything)
+                .is_empty());
+            assert!(workspace
+                .package_install("NoRedInk/elm-json-decode-pipeline")
+                .is_err());
+            assert!(!temp_dir.path().join("installed").exists());
+        }
+    }
+
+    #[test]
+    fn test_packages_found_in_elm_0_19_0_layout() {
+        let temp_dir = TempDir::new().unwrap();
+        let elm_home = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        let elm_json = r#"{
+            "source-directories": ["src"],
+            "elm-version": "0.19.0",
+            "dependencies": { "direct": { "elm/core": "1.0.2" }, "indirect": {} }
+        }"#;
+        fs::write(temp_dir.path().join("elm.json"), elm_json).unwrap();
+        let package_src = elm_home.path().join("0.19.0/packages/elm/core/1.0.2/src");
+        fs::create_dir_all(&package_src).unwrap();
+        fs::write(
+            package_src.join("Basics.elm"),
+            "module Basics exposing (identity)\n\nidentity : a -> a\nidentity x = x\n",
+        )
+        .unwrap();
+
+        let mut workspace = Workspace::new(temp_dir.path().to_path_buf());
+        workspace.elm_home = elm_home.path().to_path_buf();
+        workspace.initialize().unwrap();
+
+        assert_eq!(workspace.compiler_version.as_deref(), Some("0.19.0"));
+        assert_eq!(workspace.external_packages.len(), 1);
+        assert!(workspace.external_symbols.contains_key("Basics.identity"));
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("0.19.1"), Some("0.19.1".to_string()));
+        assert_eq!(parse_version("0.19.1-1.3.2"), Some("0.19.1".to_string()));
+        assert_eq!(parse_version("0.19.0 <= v < 0.20.0"), None);
+        assert_eq!(parse_version(""), None);
+    }
+
+    #[test]
+    fn test_index_skips_build_artifacts_and_gitignored_files() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let module = |name: &str| format!("module {} exposing (x)\n\nx = 1\n", name);
+
+        fs::write(temp_dir.path().join(".gitignore"), "/src/Gen/\n*.tmp.elm\n").unwrap();
+        fs::write(src_dir.join("Main.elm"), module("Main")).unwrap();
+        fs::write(src_dir.join("Scratch.tmp.elm"), module("Scratch")).unwrap();
+        for dir in ["Gen", "elm-stuff", "node_modules", "Keep"] {
+            fs::create_dir_all(src_dir.join(dir)).unwrap();
+            let name = format!("{}.A", dir.replace('-', ""));
+            fs::write(src_dir.join(dir).join("A.elm"), module(&name)).unwrap();
+        }
+        // A nested .gitignore can re-include what a parent rule excluded
+        fs::write(src_dir.join("Keep/.gitignore"), "!*.tmp.elm\n").unwrap();
+        fs::write(src_dir.join("Keep/B.tmp.elm"), module("Keep.B")).unwrap();
+
+        workspace.initialize().unwrap();
+
+        let mut modules: Vec<&str> = workspace.modules.keys().map(|k| k.as_str()).collect();
+        modules.sort();
+        assert_eq!(modules, vec!["Keep.A", "Keep.B", "Main"]);
+    }
+
+    #[test]
+    fn test_workspaces_route_uris_to_owning_folder() {
+        let (first_dir, first) = create_test_workspace();
+        let (second_dir, second) = create_test_workspace();
+        let mut workspaces = Workspaces::new();
+        workspaces.add(first);
+        workspaces.add(second);
+
+        let uri = Url::from_file_path(second_dir.path().join("src/Main.elm")).unwrap();
+        let owner = workspaces.for_uri(&uri).unwrap();
+        assert_eq!(owner.root_path, second_dir.path());
+
+        // Removing a folder disposes only its workspace
+        assert!(workspaces.remove(second_dir.path()));
+        assert_eq!(workspaces.len(), 1);
+        let owner = workspaces.for_uri(&uri).unwrap();
+        assert_eq!(owner.root_path, first_dir.path());
+    }
+
+    #[test]
+    fn test_nested_projects_are_discovered_and_routed() {
+        let (temp_dir, root) = create_test_workspace();
+        let elm_json = r#"{ "source-directories": ["src"] }"#;
+        for project in ["apps/admin", "packages/ui", "node_modules/dep", ".cache/x"] {
+            let dir = temp_dir.path().join(project);
+            fs::create_dir_all(dir.join("src")).unwrap();
+            fs::write(dir.join("elm.json"), elm_json).unwrap();
+        }
+        fs::write(
+            temp_dir.path().join("apps/admin/src/Admin.elm"),
+            "module Admin exposing (x)\n\n\nx =\n    1\n",
+        )
+        .unwrap();
+
+        let projects = discover_projects(temp_dir.path());
+        let relative: Vec<_> = projects
+            .iter()
+            .map(|p| p.strip_prefix(temp_dir.path()).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            relative,
+            [
+                PathBuf::new(),
+                PathBuf::from("apps/admin"),
+                PathBuf::from("packages/ui")
+            ]
+        );
+
+        let mut workspaces = Workspaces::new();
+        workspaces.add(root);
+        for project in &projects[1..] {
+            let mut workspace = Workspace::new(project.clone());
+            workspace.folder = temp_dir.path().to_path_buf();
+            workspace.initialize().unwrap();
+            workspaces.add(workspace);
+        }
+
+        // Each file goes to the deepest project, with its own index
+        let admin = Url::from_file_path(temp_dir.path().join("apps/admin/src/Admin.elm")).unwrap();
+        let owner = workspaces.for_uri(&admin).unwrap();
+        assert_eq!(owner.root_path, projects[1]);
+        assert!(owner.modules.contains_key("Admin"));
+        let main = Url::from_file_path(temp_dir.path().join("src/Main.elm")).unwrap();
+        assert_eq!(workspaces.for_uri(&main).unwrap().root_path, projects[0]);
+
+        // Closing the folder disposes every project found in it
+        assert!(workspaces.remove(temp_dir.path()));
+        assert!(workspaces.is_empty());
+    }
+
+    #[test]
+    fn test_dependent_files_follow_imports_up_to_depth() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let files = [
+            ("Types", ""),
+            ("Api", "import Types\n"),
+            ("Page", "import Api\n"),
+            ("Main", "import Page\n"),
+            ("Other", ""),
+        ];
+        for (name, imports) in files {
+            let content = format!("module {} exposing (..)\n\n{}\nx = 1\n", name, imports);
+            fs::write(src_dir.join(format!("{}.elm", name)), content).unwrap();
+        }
+        workspace.initialize().unwrap();
+
+        let uri = |name: &str| Url::from_file_path(src_dir.join(format!("{}.elm", name))).unwrap();
+        assert_eq!(
+            workspace.dependent_files(&uri("Types"), 2),
+            vec![uri("Api"), uri("Page")]
+        );
+        assert_eq!(workspace.dependent_files(&uri("Types"), 3).len(), 3);
+
+        // Dropping the import removes the edge
+        workspace.update_file(&uri("Page"), "module Page exposing (..)\n\nx = 1\n");
+        assert_eq!(
+            workspace.dependent_files(&uri("Types"), 3),
+            vec![uri("Api")]
+        );
+    }
+
+    #[test]
+    fn test_importers_of_reports_depth() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        for (name, imports) in [
+            ("Types", ""),
+            ("Api", "import Types\n"),
+            ("Main", "import Api\nimport Types\n"),
+        ] {
+            let content = format!("module {} exposing (..)\n\n{}\nx = 1\n", name, imports);
+            fs::write(src_dir.join(format!("{}.elm", name)), content).unwrap();
+        }
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Types.elm")).unwrap();
+        let (module_name, importers) = workspace.importers_of(&uri, usize::MAX).unwrap();
+        assert_eq!(module_name, "Types");
+        let importers: Vec<(&str, usize)> = importers
+            .iter()
+            .map(|(importer, _)| (importer.module.as_str(), importer.depth))
+            .collect();
+        // Main imports Types directly, so it is not reported again at depth 2
+        assert_eq!(importers, vec![("Api", 1), ("Main", 1)]);
+        assert_eq!(
+            workspace.import_graph.imports_of("Main"),
+            vec!["Api", "Types"]
+        );
+    }
+
+    #[test]
+    fn test_test_usages_follow_include_tests() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let tests_dir = temp_dir.path().join("tests");
+        fs::create_dir_all(&tests_dir).unwrap();
+        fs::write(
+            temp_dir.path().join("src/Api.elm"),
+            "module Api exposing (fetch)\n\nfetch = 1\n",
+        )
+        .unwrap();
+        fs::write(
+            tests_dir.join("ApiTest.elm"),
+            "module ApiTest exposing (suite)\n\nimport Api\n\nsuite = Api.fetch\n",
+        )
+        .unwrap();
+        workspace.initialize().unwrap();
+
+        let test_uri = Url::from_file_path(tests_dir.join("ApiTest.elm")).unwrap();
+        assert!(workspace.is_test_file(&test_uri));
+        let locations: Vec<Location> = workspace
+            .find_references("fetch", Some("Api"))
+            .into_iter()
+            .map(|r| Location::new(r.uri, r.range))
+            .collect();
+        assert!(locations.iter().any(|l| l.uri == test_uri));
+
+        let mut filtered = locations.clone();
+        workspace.filter_test_locations(&mut filtered, Some(false));
+        assert!(!filtered.iter().any(|l| l.uri == test_uri));
+        assert!(!filtered.is_empty());
+
+        // The setting applies when the request does not say
+        workspace.settings.include_tests = false;
+        let mut filtered = locations.clone();
+        workspace.filter_test_locations(&mut filtered, None);
+        assert!(!filtered.iter().any(|l| l.uri == test_uri));
+        let mut unfiltered = locations.clone();
+        workspace.filter_test_locations(&mut unfiltered, Some(true));
+        assert_eq!(unfiltered.len(), locations.len());
+    }
+
+    #[test]
+    fn test_rename_conflicts_cover_declarations_imports_and_locals() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        fs::write(
+            src_dir.join("Util.elm"),
+            "module Util exposing (helper)\n\nhelper = 1\n",
+        )
+        .unwrap();
+        fs::write(
+            src_dir.join("Main.elm"),
+            "module Main exposing (..)\n\nimport Util exposing (helper)\n\nfoo = 1\n\nother = 2\n\nbar count =\n    foo + count\n",
+        )
+        .unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let edit = |line, start, end| TextEdit {
+            range: Range::new(Position::new(line, start), Position::new(line, end)),
+            new_text: String::new(),
+        };
+        let changes = HashMap::from([(uri.clone(), vec![edit(4, 0, 3), edit(9, 4, 7)])]);
+
+        let conflicts = workspace.rename_conflicts(&changes, "other");
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].range.start.line, 6);
+
+        let conflicts = workspace.rename_conflicts(&changes, "helper");
+        assert!(conflicts[0].message.contains("imported from Util"));
+
+        // `count` is a parameter of `bar`, which uses `foo`
+        let conflicts = workspace.rename_conflicts(&changes, "count");
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].range.start, Position::new(8, 4));
+
+        assert!(workspace.rename_conflicts(&changes, "total").is_empty());
+    }
+
+    #[test]
+    fn test_rename_in_docs_is_opt_in() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        fs::write(
+            src_dir.join("Api.elm"),
+            "module Api exposing (fetch)\n\n{-| Use fetch, not fetchAll.\n-}\nfetch = \"fetch\"\n",
+        )
+        .unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Api.elm")).unwrap();
+        let definition = TextEdit {
+            range: Range::new(Position::new(4, 0), Position::new(4, 5)),
+            new_text: "load".to_string(),
+        };
+        let mut changes = HashMap::from([(uri.clone(), vec![definition])]);
+
+        workspace.rename_in_docs(&mut changes, "fetch", "load", None);
+        assert_eq!(changes[&uri].len(), 1);
+
+        workspace.rename_in_docs(&mut changes, "fetch", "load", Some(true));
+        let mut starts: Vec<Position> = changes[&uri].iter().map(|e| e.range.start).collect();
+        starts.sort_by_key(|p| (p.line, p.character));
+        // `fetchAll` is left alone
+        assert_eq!(
+            starts,
+            vec![
+                Position::new(2, 8),
+                Position::new(4, 0),
+                Position::new(4, 9)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_constructor_references_follow_imports() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        for (name, content) in [
+            ("Types", "module Types exposing (Msg(..))\n\ntype Msg = Click | Hover\n"),
+            (
+                "Main",
+                "module Main exposing (..)\n\nimport Types exposing (Msg(..))\n\nupdate msg =\n    case msg of\n        Click -> Hover\n        _ -> msg\n",
+            ),
+            ("Page", "module Page exposing (..)\n\nimport Types as T\n\nx = T.Click\n"),
+            ("Other", "module Other exposing (..)\n\ntype Local = Click\n\ny = Click\n"),
+        ] {
+            fs::write(src_dir.join(format!("{}.elm", name)), content).unwrap();
+        }
+        workspace.initialize().unwrap();
+
+        let types_uri = Url::from_file_path(src_dir.join("Types.elm")).unwrap();
+        let mut found: Vec<(String, u32, u32)> = workspace
+            .find_constructor_references_at(&types_uri, "Click")
+            .into_iter()
+            .map(|r| {
+                let file = r.uri.path().rsplit('/').next().unwrap().to_string();
+                (file, r.range.start.line, r.range.start.character)
+            })
+            .collect();
+        found.sort();
+        // The case pattern and the aliased qualified call, but not Other's own `Click`
+        assert_eq!(
+            found,
+            vec![
+                ("Main.elm".to_string(), 6, 8),
+                ("Page.elm".to_string(), 4, 6)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_file_recomputes_only_changed_content() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+
+        let content = "module Counter exposing (count)\n\ncount : Int\ncount = 1\n";
+        fs::write(src_dir.join("Counter.elm"), content).unwrap();
+        let uri = Url::from_file_path(src_dir.join("Counter.elm")).unwrap();
+
+        workspace.update_file(&uri, content);
+        let revision = workspace.inputs.revision();
+
+        // Same content: nothing is recomputed
+        workspace.update_file(&uri, content);
+        assert_eq!(workspace.inputs.revision(), revision);
+        assert_eq!(workspace.symbols.get("count").map(|s| s.len()), Some(1));
+
+        // Renamed declaration: old contributions are retracted
+        let changed = "module Counter exposing (total)\n\ntotal : Int\ntotal = 1\n";
+        workspace.update_file(&uri, changed);
+        assert!(workspace.inputs.revision() > revision);
+        assert!(!workspace.symbols.contains_key("count"));
+        assert!(!workspace.symbols.contains_key("Counter.count"));
+        assert_eq!(workspace.symbols.get("Counter.total").map(|s| s.len()), Some(1));
+        assert_eq!(workspace.inputs.changed_since(revision).count(), 1);
+
+        workspace.remove_file(&uri);
+        assert!(workspace.get_module("Counter").is_none());
+        assert!(!workspace.symbols.contains_key("total"));
+        assert!(workspace.references.values().flatten().all(|r| r.uri != uri));
+
+        drop(temp_dir);
+    }
+
     #[test]
     fn test_rename_file_rejects_invalid_extension() {
         let (temp_dir, mut workspace) = create_test_workspace();
@@ -2582,4 +3501,2596 @@ value = help
 
         drop(temp_dir);
     }
+
+    /// Apply LSP edits to a string, failing the test when they overlap
+    pub(super) fn apply_edits(content: &str, edits: &[TextEdit]) -> String {
+        crate::document::apply_edits(content, edits).unwrap()
+    }
+
+    #[test]
+    fn test_remove_variant_keeps_neighbouring_comments() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+
+        let content = r#"module Colors exposing (..)
+
+
+type Color
+    = Red
+    -- Green is deprecated
+    | Green
+    -- Blue is the default
+    | Blue
+
+
+toString : Color -> String
+toString color =
+    case color of
+        Red ->
+            "red"
+
+        -- the green case
+        Green ->
+            "green"
+
+        Blue ->
+            "blue"
+"#;
+        fs::write(src_dir.join("Colors.elm"), content).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Colors.elm")).unwrap();
+        let result = workspace
+            .remove_variant(&uri, "Color", "Green", 1, 3, &VariantReplacement::DebugTodo)
+            .unwrap();
+        assert!(result.success, "{}", result.message);
+
+        let edits = &result.changes.unwrap()[&uri];
+        let updated = apply_edits(content, edits);
+        assert_eq!(
+            updated,
+            r#"module Colors exposing (..)
+
+
+type Color
+    = Red
+    -- Blue is the default
+    | Blue
+
+
+toString : Color -> String
+toString color =
+    case color of
+        Red ->
+            "red"
+
+        Blue ->
+            "blue"
+"#
+        );
+
+        drop(temp_dir);
+    }
+
+    #[test]
+    fn test_remove_variant_maps_usages_to_target_variant() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+
+        let content = r#"module Auth exposing (..)
+
+
+type Msg
+    = Login String
+    | LegacyLogin String
+    | Logout
+
+
+handle msg =
+    case msg of
+        Login name ->
+            name
+
+        LegacyLogin name ->
+            name
+
+        Logout ->
+            ""
+
+
+legacyOnly msg =
+    case msg of
+        LegacyLogin name ->
+            name
+
+        _ ->
+            ""
+
+
+make =
+    LegacyLogin "x"
+"#;
+        fs::write(src_dir.join("Auth.elm"), content).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Auth.elm")).unwrap();
+        let replacement = VariantReplacement::Variant("Login".to_string());
+        let result = workspace
+            .remove_variant(&uri, "Msg", "LegacyLogin", 1, 3, &replacement)
+            .unwrap();
+        assert!(result.success, "{}", result.message);
+
+        let updated = apply_edits(content, &result.changes.unwrap()[&uri]);
+        assert_eq!(
+            updated,
+            r#"module Auth exposing (..)
+
+
+type Msg
+    = Login String
+    | Logout
+
+
+handle msg =
+    case msg of
+        Login name ->
+            name
+
+        Logout ->
+            ""
+
+
+legacyOnly msg =
+    case msg of
+        Login name ->
+            name
+
+        _ ->
+            ""
+
+
+make =
+    Login "x"
+"#
+        );
+
+        // The target must take the same arguments
+        let result = workspace
+            .remove_variant(
+                &uri,
+                "Msg",
+                "LegacyLogin",
+                1,
+                3,
+                &VariantReplacement::Variant("Logout".to_string()),
+            )
+            .unwrap();
+        assert!(!result.success);
+    }
+
+    #[test]
+    fn test_reorder_variants_keeps_separators() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let content =
+            "module Color exposing (..)\n\n\ntype Color\n    = Red\n    | Blue Int\n    | Green\n";
+        fs::write(src_dir.join("Color.elm"), content).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Color.elm")).unwrap();
+        let result = workspace.reorder_variants(&uri, "Color", None).unwrap();
+        assert!(result.success, "{}", result.message);
+        assert_eq!(
+            apply_edits(content, &result.changes.unwrap()[&uri]),
+            "module Color exposing (..)\n\n\ntype Color\n    = Blue Int\n    | Green\n    | Red\n"
+        );
+
+        let order = vec!["Red".to_string(), "Red".to_string(), "Blue".to_string()];
+        let result = workspace
+            .reorder_variants(&uri, "Color", Some(&order))
+            .unwrap();
+        assert!(!result.success);
+    }
+
+    #[test]
+    fn test_reorder_fields_updates_constructor_calls() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let user = "module User exposing (..)\n\n\ntype alias User =\n    { name : String\n    , age : Int\n    }\n";
+        let main = "module Main exposing (..)\n\nimport User exposing (User)\n\n\nann =\n    User \"Ann\" 42\n\n\nmake =\n    User\n";
+        fs::write(src_dir.join("User.elm"), user).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("User.elm")).unwrap();
+        let main_uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let order = vec!["age".to_string(), "name".to_string()];
+        let result = workspace
+            .reorder_fields(&uri, "User", Some(&order), true)
+            .unwrap();
+        assert!(result.success, "{}", result.message);
+
+        let changes = result.changes.unwrap();
+        assert_eq!(
+            apply_edits(user, &changes[&uri]),
+            "module User exposing (..)\n\n\ntype alias User =\n    { age : Int\n    , name : String\n    }\n"
+        );
+        assert_eq!(
+            apply_edits(main, &changes[&main_uri]),
+            "module Main exposing (..)\n\nimport User exposing (User)\n\n\nann =\n    User 42 \"Ann\"\n\n\nmake =\n    User\n"
+        );
+        // A bare `User` cannot be reordered and is reported instead
+        assert_eq!(result.unchanged_usages.len(), 1);
+        assert_eq!(result.unchanged_usages[0].range.start.line, 10);
+    }
+
+    #[test]
+    fn test_tuple_to_record_rewrites_annotations_and_call_sites() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let point = "module Point exposing (origin, swap)\n\n\norigin : ( Int, Int )\norigin =\n    ( 0, 0 )\n\n\nswap : ( Int, Int ) -> ( Int, Int )\nswap ( a, b ) =\n    ( b, a )\n";
+        let main = "module Main exposing (..)\n\nimport Point\n\n\nflipped : Int\nflipped =\n    let\n        ( x, _ ) =\n            Point.swap ( 1, 2 )\n    in\n    x\n";
+        fs::write(src_dir.join("Point.elm"), point).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Point.elm")).unwrap();
+        let main_uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let fields = vec!["x".to_string(), "y".to_string()];
+        let result = workspace
+            .tuple_to_record(&uri, Position::new(3, 10), "Point", &fields)
+            .unwrap();
+        assert!(result.success, "{}", result.message);
+        assert!(
+            result.unchanged_usages.is_empty(),
+            "{:?}",
+            result.unchanged_usages
+        );
+
+        let changes = result.changes.unwrap();
+        assert_eq!(
+            apply_edits(point, &changes[&uri]),
+            "module Point exposing (origin, swap, Point)\n\n\ntype alias Point =\n    { x : Int\n    , y : Int\n    }\n\n\norigin : Point\norigin =\n    { x = 0, y = 0 }\n\n\nswap : Point -> Point\nswap { x, y } =\n    { x = y, y = x }\n"
+        );
+        assert_eq!(
+            apply_edits(main, &changes[&main_uri]),
+            "module Main exposing (..)\n\nimport Point\n\n\nflipped : Int\nflipped =\n    let\n        { x } =\n            Point.swap { x = 1, y = 2 }\n    in\n    x\n"
+        );
+
+        let result = workspace
+            .tuple_to_record(&uri, Position::new(3, 10), "Point", &fields[..1])
+            .unwrap();
+        assert!(!result.success);
+    }
+
+    #[test]
+    fn test_introduce_parameter_updates_every_use() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let greet = "module Greet exposing (greet, greetAll)\n\n\ngreet : String -> String\ngreet name =\n    \"Hello, \" ++ name\n\n\ngreetAll : List String -> List String\ngreetAll names =\n    List.map greet names\n";
+        let main =
+            "module Main exposing (..)\n\nimport Greet\n\n\nann =\n    Greet.greet \"Ann\"\n";
+        fs::write(src_dir.join("Greet.elm"), greet).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Greet.elm")).unwrap();
+        let main_uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let selection = Range::new(Position::new(5, 4), Position::new(5, 13));
+        assert!(workspace.is_extractable(&uri, selection));
+        // Half of `"Hello, "` is not an expression
+        assert!(
+            !workspace.is_extractable(&uri, Range::new(Position::new(5, 4), Position::new(5, 9)))
+        );
+        let result = workspace
+            .introduce_parameter(&uri, selection, "greeting")
+            .unwrap();
+        assert!(result.success, "{}", result.message);
+        assert!(result.unchanged_usages.is_empty());
+
+        let changes = result.changes.unwrap();
+        assert_eq!(
+            apply_edits(greet, &changes[&uri]),
+            "module Greet exposing (greet, greetAll)\n\n\ngreet : String -> String -> String\ngreet greeting name =\n    greeting ++ name\n\n\ngreetAll : List String -> List String\ngreetAll names =\n    List.map (greet \"Hello, \") names\n"
+        );
+        assert_eq!(
+            apply_edits(main, &changes[&main_uri]),
+            "module Main exposing (..)\n\nimport Greet\n\n\nann =\n    Greet.greet \"Hello, \" \"Ann\"\n"
+        );
+
+        // A selection using a local variable cannot be passed by the callers
+        let selection = Range::new(Position::new(5, 17), Position::new(5, 21));
+        let result = workspace
+            .introduce_parameter(&uri, selection, "who")
+            .unwrap();
+        assert!(result.success, "{}", result.message);
+        assert_eq!(result.unchanged_usages.len(), 2);
+
+        let result = workspace
+            .introduce_parameter(&uri, selection, "greetAll")
+            .unwrap();
+        assert!(!result.success);
+    }
+
+    #[test]
+    fn test_hoist_constant_replaces_identical_expressions() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let content = "module Prices exposing (..)\n\n\nnet : Float -> Float\nnet price =\n    price * (1 + 0.2)\n\n\ngross : Float -> Float\ngross price =\n    price / (1 +   0.2)\n";
+        fs::write(src_dir.join("Prices.elm"), content).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Prices.elm")).unwrap();
+        let selection = Range::new(Position::new(5, 12), Position::new(5, 21));
+        let result = workspace.hoist_constant(&uri, selection, "vat").unwrap();
+        assert!(result.success, "{}", result.message);
+        assert_eq!(
+            apply_edits(content, &result.changes.unwrap()[&uri]),
+            "module Prices exposing (..)\n\n\nvat : Float\nvat =\n    1 + 0.2\n\n\nnet : Float -> Float\nnet price =\n    price * vat\n\n\ngross : Float -> Float\ngross price =\n    price / vat\n"
+        );
+
+        // `price` is a parameter, so `price * (1 + 0.2)` is not closed
+        let selection = Range::new(Position::new(5, 4), Position::new(5, 21));
+        let result = workspace.hoist_constant(&uri, selection, "vat").unwrap();
+        assert!(!result.success);
+    }
+
+    #[test]
+    fn test_import_target_opens_module_and_exposed_items() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        fs::create_dir_all(src_dir.join("Data")).unwrap();
+        let user = "module Data.User exposing (User, name)\n\n\ntype alias User =\n    { name : String }\n\n\nname : User -> String\nname user =\n    user.name\n";
+        let main = "module Main exposing (..)\n\nimport Data.User exposing (User, name)\n\n\nmain =\n    name\n";
+        fs::write(src_dir.join("Data/User.elm"), user).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+
+        let user_uri = Url::from_file_path(src_dir.join("Data/User.elm")).unwrap();
+        let main_uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+
+        let module = workspace
+            .import_target(&main_uri, Position::new(2, 12))
+            .unwrap();
+        assert_eq!(module.definition_uri, user_uri);
+        assert_eq!(module.definition_range.start, Position::new(0, 0));
+
+        let alias = workspace
+            .import_target(&main_uri, Position::new(2, 28))
+            .unwrap();
+        assert_eq!(alias.definition_range.start, Position::new(3, 11));
+
+        let function = workspace
+            .import_target(&main_uri, Position::new(2, 34))
+            .unwrap();
+        assert_eq!(function.definition_uri, user_uri);
+        assert_eq!(function.definition_range.start, Position::new(8, 0));
+
+        // Outside of imports there is nothing to resolve here
+        assert!(workspace
+            .import_target(&main_uri, Position::new(6, 4))
+            .is_none());
+    }
+
+    #[test]
+    fn test_references_from_exposing_list() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let page = "module Page exposing (Msg(..), view)\n\n\ntype Msg\n    = Clicked\n\n\nview : Msg -> String\nview msg =\n    \"page\"\n";
+        let main = "module Main exposing (..)\n\nimport Page exposing (Msg(..))\n\n\nmain : String\nmain =\n    Page.view Clicked\n\n\nupdate : Msg -> Msg\nupdate msg =\n    msg\n";
+        fs::write(src_dir.join("Page.elm"), page).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+
+        let page_uri = Url::from_file_path(src_dir.join("Page.elm")).unwrap();
+        let main_uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+
+        // `view` in `module Page exposing (Msg(..), view)`
+        let symbol = workspace
+            .classify_definition_at_position(&page_uri, Position::new(0, 31))
+            .unwrap();
+        assert_eq!(symbol.kind, BoundSymbolKind::Function);
+        let refs = workspace.find_function_references_typed(&symbol);
+        assert!(refs
+            .iter()
+            .any(|r| r.uri == main_uri && r.range.start.line == 7));
+
+        // `Msg` in `import Page exposing (Msg(..))` resolves to Page's type
+        let symbol = workspace
+            .classify_definition_at_position(&main_uri, Position::new(2, 23))
+            .unwrap();
+        assert_eq!(symbol.kind, BoundSymbolKind::Type);
+        assert_eq!(symbol.uri, page_uri);
+        let refs = workspace.find_type_references_typed(&symbol);
+        assert!(refs
+            .iter()
+            .any(|r| r.uri == main_uri && r.range.start.line == 10));
+    }
+
+    #[test]
+    fn test_search_symbols_ranks_matches() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let user = "module User exposing (..)\n\n\nid : Int\nid =\n    1\n\n\nidentity : a -> a\nidentity a =\n    a\n\n\nuserId : Int\nuserId =\n    2\n\n\nvalid : Bool\nvalid =\n    True\n";
+        fs::write(src_dir.join("User.elm"), user).unwrap();
+        workspace.initialize().unwrap();
+
+        let mut matches = workspace.search_symbols("Id");
+        matches.sort_by_key(|(score, sym)| (*score, sym.name.clone()));
+        let ranked: Vec<(u32, &str)> = matches
+            .iter()
+            .map(|(score, sym)| (*score, sym.name.as_str()))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![(0, "id"), (1, "identity"), (2, "userId"), (3, "valid")]
+        );
+
+        // Abbreviations match the words of a name
+        let matches = workspace.search_symbols("uI");
+        assert_eq!(matches.len(), 1);
+        assert_eq!((matches[0].0, matches[0].1.name.as_str()), (4, "userId"));
+
+        // Qualified queries match through the module name
+        let matches = workspace.search_symbols("User.val");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].1.name, "valid");
+    }
+
+    #[test]
+    fn test_operator_hover_from_package() {
+        let temp_dir = TempDir::new().unwrap();
+        let elm_home = TempDir::new().unwrap();
+        let src_dir = temp_dir.path().join("src");
+        fs::create_dir_all(&src_dir).unwrap();
+        let elm_json = r#"{
+            "source-directories": ["src"],
+            "dependencies": { "direct": { "elm/url": "1.0.0" }, "indirect": {} }
+        }"#;
+        fs::write(temp_dir.path().join("elm.json"), elm_json).unwrap();
+        let package_src = elm_home
+            .path()
+            .join("0.19.1/packages/elm/url/1.0.0/src/Url");
+        fs::create_dir_all(&package_src).unwrap();
+        let parser = "module Url.Parser exposing (Parser, (</>), slash)\n\n\ninfix right 7 (</>) = slash\n\n\n{-| Parse a path with multiple segments.\n-}\nslash : Parser a b -> Parser b c -> Parser a c\nslash first second =\n    first\n";
+        fs::write(package_src.join("Parser.elm"), parser).unwrap();
+        let main = "module Main exposing (..)\n\nimport Url.Parser exposing ((</>))\n\n\nroute =\n    a </> b\n";
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+
+        let mut workspace = Workspace::new(temp_dir.path().to_path_buf());
+        workspace.elm_home = elm_home.path().to_path_buf();
+        workspace.initialize().unwrap();
+        let main_uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+
+        let operator = workspace
+            .operator_at(&main_uri, Position::new(6, 7))
+            .unwrap();
+        assert_eq!(operator.associativity, "right");
+        assert_eq!(operator.precedence, 7);
+        let hover = operator.hover_markdown();
+        assert!(hover.contains("(</>) : Parser a b -> Parser b c -> Parser a c"));
+        assert!(hover.contains("infix right 7 (</>) = slash"));
+        assert!(hover.contains("*Defined in Url.Parser (elm/url)*"));
+        assert!(hover.contains("Parse a path with multiple segments."));
+
+        // The exposing list names the same operator; `route` is not one
+        assert!(workspace
+            .operator_at(&main_uri, Position::new(2, 29))
+            .is_some());
+        assert!(workspace
+            .operator_at(&main_uri, Position::new(5, 2))
+            .is_none());
+    }
+
+    #[test]
+    fn test_signature_help_in_pipelines() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let main = "module Main exposing (..)\n\n\nscale : Int -> Int -> Int\nscale factor n =\n    factor * n\n\n\nsucceed : a -> Parser a\nsucceed a =\n    Debug.todo \"parser\"\n\n\ntype alias Point =\n    { x : Int, y : Int }\n\n\npoint : Int -> Int -> Point\npoint x y =\n    { x = x, y = y }\n\n\ndirect =\n    scale 2 3\n\n\npiped =\n    3 |> scale 2\n\n\nbackwards =\n    scale 2 <| 3\n\n\nparser =\n    succeed point |. spaces |= int |. comma |= int\n";
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+
+        let active = |line: u32, character: u32| {
+            workspace
+                .signature_help(&uri, Position::new(line, character))
+                .map(|help| (help.signatures[0].label.clone(), help.active_parameter))
+        };
+        let scale = "scale : Int -> Int -> Int".to_string();
+
+        // `scale 2 3`: on the name, then after each argument
+        assert_eq!(active(23, 8), Some((scale.clone(), Some(0))));
+        assert_eq!(active(23, 11), Some((scale.clone(), Some(0))));
+        assert_eq!(active(23, 12), Some((scale.clone(), Some(1))));
+        // `3 |> scale 2`: the pipe supplies the last parameter
+        assert_eq!(active(27, 16), Some((scale.clone(), Some(0))));
+        // `scale 2 <| 3`: the right side is the second parameter
+        assert_eq!(active(31, 16), Some((scale.clone(), Some(1))));
+        // Each `|=` feeds the next parameter of `point`
+        let point = Some("point : Int -> Int -> Point".to_string());
+        assert_eq!(
+            active(35, 34).map(|(l, a)| (Some(l), a)),
+            Some((point.clone(), Some(0)))
+        );
+        assert_eq!(
+            active(35, 50).map(|(l, a)| (Some(l), a)),
+            Some((point, Some(1)))
+        );
+        // Outside any call
+        assert_eq!(active(0, 5), None);
+    }
+
+    #[test]
+    fn test_signature_help_after_non_ascii_names() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let main = "module Main exposing (..)\n\n\nscale : Int -> Int -> Int\nscale factor n =\n    factor * n\n\n\ncafé =\n    2\n\n\ndirect =\n    scale café \n\n\n-- naïve \n";
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+
+        // The last character before the space is `é`, two bytes long
+        let help = workspace
+            .signature_help(&uri, Position::new(13, 16))
+            .unwrap();
+        assert_eq!(help.signatures[0].label, "scale : Int -> Int -> Int");
+        assert_eq!(help.active_parameter, Some(1));
+        assert!(workspace
+            .signature_help(&uri, Position::new(16, 10))
+            .is_none());
+    }
+
+    #[test]
+    fn test_pattern_completions_offer_constructors() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let page = "module Page exposing (Tab(..))\n\n\ntype Tab\n    = Home\n    | Settings\n";
+        let main = "module Main exposing (..)\n\nimport Page\n\n\ntype Msg\n    = Clicked\n    | Typed String\n\n\nupdate : Msg -> Int -> Int\nupdate msg model =\n    case msg of\n        Clicked ->\n            model\n\n        T\n\n\ncheck : Maybe Msg -> Bool\ncheck m =\n    case m of\n        Just (C\n";
+        fs::write(src_dir.join("Page.elm"), page).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let labels = |line: u32, character: u32| {
+            workspace
+                .pattern_completions(&uri, Position::new(line, character))
+                .map(|items| items.into_iter().map(|i| i.label).collect::<Vec<_>>())
+        };
+
+        // A new branch of `case msg of`: the constructors of Msg and `_`
+        assert_eq!(
+            labels(16, 9),
+            Some(vec![
+                "Clicked".to_string(),
+                "Typed".to_string(),
+                "_".to_string()
+            ])
+        );
+        // Nested in a constructor's arguments: every constructor in scope,
+        // qualified when the type is not exposed
+        let nested = labels(22, 15).unwrap();
+        for label in ["Just", "Clicked", "Page.Home", "_"] {
+            assert!(nested.contains(&label.to_string()), "{label} in {nested:?}");
+        }
+        // Not a pattern: a branch body, or a declaration body
+        assert_eq!(labels(14, 17), None);
+        assert_eq!(labels(12, 8), None);
+    }
+
+    #[test]
+    fn test_record_update_completions_list_unset_fields() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let main = "module Main exposing (..)\n\n\ntype alias Model =\n    { count : Int, name : String, open : Bool }\n\n\nupdate : Int -> Model -> Model\nupdate n model =\n    { model | count = n,  }\n\n\ntoggle : Model -> Model\ntoggle model =\n    { model | o\n";
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let labels = |line: u32, character: u32| {
+            workspace
+                .record_update_completions(&uri, Position::new(line, character))
+                .map(|items| items.into_iter().map(|i| i.label).collect::<Vec<_>>())
+        };
+
+        // `count` is already set
+        assert_eq!(
+            labels(9, 25),
+            Some(vec!["name".to_string(), "open".to_string()])
+        );
+        // Unclosed update, typed from the annotation
+        assert_eq!(
+            labels(14, 15),
+            Some(vec![
+                "count".to_string(),
+                "name".to_string(),
+                "open".to_string()
+            ])
+        );
+        // In a field's value, not at a field name
+        assert_eq!(labels(9, 23), None);
+    }
+
+    #[test]
+    fn test_import_exposing_completions() {
+        let temp_dir = TempDir::new().unwrap();
+        let elm_home = TempDir::new().unwrap();
+        let src_dir = temp_dir.path().join("src");
+        fs::create_dir_all(&src_dir).unwrap();
+        let elm_json = r#"{
+            "source-directories": ["src"],
+            "dependencies": { "direct": { "elm/core": "1.0.5" }, "indirect": {} }
+        }"#;
+        fs::write(temp_dir.path().join("elm.json"), elm_json).unwrap();
+        let package_src = elm_home.path().join("0.19.1/packages/elm/core/1.0.5/src");
+        fs::create_dir_all(&package_src).unwrap();
+        let dict = "module Dict exposing (Dict, empty, insert)\n\n\ntype Dict k v\n    = Empty\n\n\nempty : Dict k v\nempty =\n    Empty\n\n\ninsert : k -> v -> Dict k v -> Dict k v\ninsert k v d =\n    d\n\n\nhelper =\n    1\n";
+        fs::write(package_src.join("Dict.elm"), dict).unwrap();
+        let page = "module Page exposing (Msg(..), view)\n\n\ntype Msg\n    = Clicked\n\n\nview : Msg -> String\nview msg =\n    \"page\"\n\n\nhidden =\n    1\n";
+        let main =
+            "module Main exposing (..)\n\nimport Dict exposing (empty, )\nimport Page exposing (\n";
+        fs::write(src_dir.join("Page.elm"), page).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+
+        let mut workspace = Workspace::new(temp_dir.path().to_path_buf());
+        workspace.elm_home = elm_home.path().to_path_buf();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let labels = |line: u32, character: u32| {
+            workspace
+                .import_exposing_completions(&uri, Position::new(line, character))
+                .map(|items| {
+                    let mut labels: Vec<String> = items.into_iter().map(|i| i.label).collect();
+                    labels.sort();
+                    labels
+                })
+        };
+
+        // A package module, without what is listed already
+        assert_eq!(
+            labels(2, 29),
+            Some(vec!["Dict".to_string(), "insert".to_string()])
+        );
+        // A workspace module, with the constructors of an open custom type
+        assert_eq!(
+            labels(3, 22),
+            Some(vec![
+                "Msg".to_string(),
+                "Msg(..)".to_string(),
+                "view".to_string()
+            ])
+        );
+        assert_eq!(labels(2, 10), None);
+    }
+
+    #[test]
+    fn test_not_exposed_references_and_fixes() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let helper = "module Helper exposing (Msg, visible)\n\n\ntype Msg\n    = Clicked\n\n\nvisible =\n    1\n\n\nhelper =\n    2\n";
+        let main = "module Main exposing (..)\n\nimport Helper as H exposing (helper)\n\n\nmain =\n    ( H.visible, H.helper, H.Clicked )\n";
+        fs::write(src_dir.join("Helper.elm"), helper).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let diagnostics = workspace.not_exposed_diagnostics(&uri);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "`Helper` does not expose `helper`",
+                "`Helper` does not expose `helper`",
+                "`Helper` does not expose `Clicked`, a constructor of `Msg`",
+            ]
+        );
+        assert_eq!(diagnostics[1].range.start, Position::new(6, 17));
+
+        let helper_uri = Url::from_file_path(src_dir.join("Helper.elm")).unwrap();
+        let fixes = workspace.expose_fixes(&uri, diagnostics[1].range);
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(fixes[0].title, "Expose helper from Helper");
+        assert_eq!(fixes[0].changes[&helper_uri][0].new_text, ", helper");
+
+        // A type exposed without its constructors gets `(..)` in place
+        let fixes = workspace.expose_fixes(&uri, diagnostics[2].range);
+        let edit = &fixes[0].changes[&helper_uri][0];
+        assert_eq!(fixes[0].title, "Expose Msg(..) from Helper");
+        assert_eq!(edit.new_text, "Msg(..)");
+        assert_eq!(edit.range.start, Position::new(0, 24));
+    }
+
+    #[test]
+    fn test_call_paths_enumerates_every_route() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let helper = "module Helper exposing (format)\n\n\nformat x =\n    x\n";
+        let view = "module View exposing (view)\n\nimport Helper\n\n\nview x =\n    card (Helper.format x)\n\n\ncard x =\n    Helper.format x\n";
+        let main = "module Main exposing (main)\n\nimport Helper\nimport View\n\n\nmain =\n    View.view 1\n\n\nunused =\n    Helper.format 2\n";
+        fs::write(src_dir.join("Helper.elm"), helper).unwrap();
+        fs::write(src_dir.join("View.elm"), view).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Helper.elm")).unwrap();
+        let result = workspace
+            .call_paths(&uri, Position::new(3, 0), 10, 50)
+            .unwrap();
+        let paths: Vec<Vec<String>> = result
+            .paths
+            .iter()
+            .map(|path| {
+                path.iter()
+                    .map(|e| format!("{}.{}", e.module_name, e.function))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                vec!["View.view", "Main.main"],
+                vec!["View.card", "View.view", "Main.main"],
+                vec!["Main.unused"],
+            ]
+        );
+        assert!(result.paths[0][1].is_entry_point);
+        assert!(!result.paths[2][0].is_entry_point);
+        assert!(!result.truncated);
+
+        // Limits cut the search and say so
+        let limited = workspace
+            .call_paths(&uri, Position::new(3, 0), 10, 1)
+            .unwrap();
+        assert_eq!(limited.paths.len(), 1);
+        assert!(limited.truncated);
+
+        let chain = workspace.call_chain(&uri, Position::new(3, 0));
+        let names: Vec<&str> = chain.iter().map(|e| e.function.as_str()).collect();
+        assert_eq!(names, ["format", "view", "main"]);
+    }
+
+    #[test]
+    fn test_program_kind_and_navigation_checks() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let app = "module App exposing (main)\n\nimport Browser\nimport Browser.Navigation as Nav\n\n\nmain =\n    Browser.application config\n\n\ngo key =\n    Nav.pushUrl key \"/\"\n";
+        let widget = "module Widget exposing (main)\n\nimport Browser exposing (element)\nimport Browser.Navigation as Nav\n\n\nmain =\n    element <| config\n\n\ngo key =\n    Cmd.batch [ Nav.pushUrl key \"/\", Nav.load \"/\" ]\n";
+        let helper = "module Helper exposing (x)\n\n\nx =\n    1\n";
+        fs::write(src_dir.join("App.elm"), app).unwrap();
+        fs::write(src_dir.join("Widget.elm"), widget).unwrap();
+        fs::write(src_dir.join("Helper.elm"), helper).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = |name: &str| Url::from_file_path(src_dir.join(name)).unwrap();
+        assert_eq!(
+            workspace.program_kind(&uri("App.elm")),
+            Some(ProgramKind::Application)
+        );
+        assert_eq!(
+            workspace.program_kind(&uri("Widget.elm")),
+            Some(ProgramKind::Element)
+        );
+        assert_eq!(workspace.program_kind(&uri("Helper.elm")), None);
+
+        let stats = workspace.index_stats();
+        assert_eq!(stats.modules, 3);
+        assert_eq!(stats.programs.len(), 2);
+
+        // Only the element program is warned about `pushUrl`, and not `load`
+        assert!(workspace.program_diagnostics(&uri("App.elm")).is_empty());
+        let diagnostics = workspace.program_diagnostics(&uri("Widget.elm"));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(11, 16));
+    }
+
+    #[test]
+    fn test_module_hover_shows_module_docs() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let strings = "module Utils.Strings exposing (shout, whisper)\n\n{-| Helpers for text.\n\n# Volume\n@docs shout, whisper\n-}\n\n\nshout s =\n    s\n\n\nwhisper s =\n    s\n";
+        let main = "module Main exposing (main)\n\nimport Utils.Strings as S\n\n\nmain =\n    S.shout \"hi\"\n";
+        fs::create_dir_all(src_dir.join("Utils")).unwrap();
+        fs::write(src_dir.join("Utils/Strings.elm"), strings).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let expected = "```elm\nmodule Utils.Strings\n```\n\nHelpers for text.\n\n# Volume\n`shout`, `whisper`";
+
+        // The module name of an import
+        let (value, range) = workspace.module_hover(&uri, Position::new(2, 10)).unwrap();
+        assert_eq!(value, expected);
+        assert_eq!(range.start, Position::new(2, 7));
+
+        // An alias used as a qualifier
+        let (value, range) = workspace.module_hover(&uri, Position::new(6, 4)).unwrap();
+        assert_eq!(value, expected);
+        assert_eq!(range, Range::new(Position::new(6, 4), Position::new(6, 5)));
+
+        // The function after the qualifier is not a module
+        assert!(workspace.module_hover(&uri, Position::new(6, 7)).is_none());
+    }
+
+    #[test]
+    fn test_import_provenance() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let date = "module Utils.Date exposing (format)\n\n\nformat d =\n    d\n";
+        let types =
+            "module Types exposing (..)\n\n\ntype Msg\n    = Clicked\n\n\ntype Color\n    = Red\n";
+        let main = "module Main exposing (main)\n\nimport Types exposing (..)\nimport Utils.Date as Date exposing (format)\n\n\nmain =\n    ( Date.format 1, format 2, Clicked, Red )\n";
+        fs::create_dir_all(src_dir.join("Utils")).unwrap();
+        fs::write(src_dir.join("Utils/Date.elm"), date).unwrap();
+        fs::write(src_dir.join("Types.elm"), types).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        assert_eq!(
+            workspace.import_provenance(&uri, "Date.format").as_deref(),
+            Some("*Imported from `Utils.Date` (aliased as `Date`)*")
+        );
+        assert_eq!(
+            workspace.import_provenance(&uri, "format").as_deref(),
+            Some("*Exposed via `import Utils.Date exposing (format)`*")
+        );
+        assert_eq!(
+            workspace.import_provenance(&uri, "Clicked").as_deref(),
+            Some("*Exposed via `import Types exposing (..)`*")
+        );
+        // Declared in Main itself
+        assert_eq!(workspace.import_provenance(&uri, "main"), None);
+    }
+
+    #[test]
+    fn test_hover_signature_and_documentation() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let utils = "module Utils exposing (first, format, title)\n\n\n{-| Format a count for display -}\nformat : Int -> String\nformat n =\n    String.fromInt n\n\n\ntitle =\n    \"Home\"\n\n\nfirst a b =\n    a\n";
+        fs::write(src_dir.join("Utils.elm"), utils).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Utils.elm")).unwrap();
+        let format = workspace.declared_symbol("Utils", "format").unwrap();
+        assert_eq!(
+            workspace.hover_signature(&format).as_deref(),
+            Some("format : Int -> String")
+        );
+        assert_eq!(
+            workspace.symbol_documentation(&format).as_deref(),
+            Some("Format a count for display")
+        );
+
+        // Without an annotation, the inferred type
+        let title = workspace.declared_symbol("Utils", "title").unwrap();
+        assert_eq!(
+            workspace.hover_signature(&title).as_deref(),
+            Some("title : String")
+        );
+        assert_eq!(workspace.symbol_documentation(&title), None);
+        assert_eq!(
+            workspace.inferred_signature(&uri, "first").as_deref(),
+            Some("first : a -> b -> a")
+        );
+    }
+
+    #[test]
+    fn test_module_name_mismatching_path() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        fs::create_dir_all(src_dir.join("Pages")).unwrap();
+        fs::write(
+            src_dir.join("Pages/Home.elm"),
+            "module Home exposing (view)\n\n\nview =\n    1\n",
+        )
+        .unwrap();
+        fs::write(
+            src_dir.join("Settings.elm"),
+            "module Settings exposing (view)\n\n\nview =\n    2\n",
+        )
+        .unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Pages/Home.elm")).unwrap();
+        let diagnostics = workspace.module_path_diagnostics(&uri);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "The module is named `Home`, but its path makes it `Pages.Home`"
+        );
+        let fixes = workspace.module_path_fixes(&uri, diagnostics[0].range);
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(fixes[0].edits[0].new_text, "Pages.Home");
+        assert_eq!(
+            workspace.module_path_target(&uri),
+            Some(src_dir.join("Home.elm"))
+        );
+
+        let settings = Url::from_file_path(src_dir.join("Settings.elm")).unwrap();
+        assert!(workspace.module_path_diagnostics(&settings).is_empty());
+        assert_eq!(workspace.module_path_target(&settings), None);
+    }
+
+    #[test]
+    fn test_call_hierarchy_across_modules() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let utils = "module Utils exposing (double, triple)\n\n\ndouble n =\n    n * 2\n\n\ntriple n =\n    n * 3\n";
+        let main = "module Main exposing (main)\n\nimport Utils as U exposing (double)\n\n\nmain =\n    twice (double 1)\n\n\ntwice n =\n    U.triple (double n)\n";
+        let snapshot = "module Evergreen.V1.Main exposing (..)\n\nimport Utils\n\n\nold =\n    Utils.double 1\n";
+        fs::create_dir_all(src_dir.join("Evergreen/V1")).unwrap();
+        fs::write(src_dir.join("Utils.elm"), utils).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        fs::write(src_dir.join("Evergreen/V1/Main.elm"), snapshot).unwrap();
+        workspace.initialize().unwrap();
+
+        // On the call of `double` in `main`
+        let main_uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let double = workspace
+            .prepare_call_hierarchy(&main_uri, Position::new(6, 12))
+            .unwrap();
+        assert_eq!(double.name, "double");
+        assert_eq!(double.detail.as_deref(), Some("Utils"));
+
+        let incoming = workspace.incoming_calls(&double);
+        let callers: Vec<_> = incoming
+            .iter()
+            .map(|call| (call.from.name.as_str(), call.from_ranges.len()))
+            .collect();
+        assert_eq!(callers, vec![("main", 1), ("twice", 1)]);
+
+        // On the declaration of `twice`
+        let twice = workspace
+            .prepare_call_hierarchy(&main_uri, Position::new(9, 1))
+            .unwrap();
+        let outgoing = workspace.outgoing_calls(&twice);
+        let callees: Vec<_> = outgoing.iter().map(|call| call.to.name.as_str()).collect();
+        assert_eq!(callees, vec!["triple", "double"]);
+    }
+
+    #[test]
+    fn test_declaration_importers_by_import_style() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let utils = "module Utils exposing (format, helper)\n\n\nformat n =\n    n\n\n\nhelper =\n    1\n\n\nhidden =\n    2\n";
+        let page = "module Page exposing (view)\n\nimport Utils exposing (format)\n\n\nview =\n    format (format 1)\n";
+        let settings =
+            "module Settings exposing (view)\n\nimport Utils as U\n\n\nview =\n    U.format 2\n";
+        let admin = "module Admin exposing (view)\n\nimport Utils\n\n\nview =\n    Utils.helper\n";
+        fs::write(src_dir.join("Utils.elm"), utils).unwrap();
+        fs::write(src_dir.join("Page.elm"), page).unwrap();
+        fs::write(src_dir.join("Settings.elm"), settings).unwrap();
+        fs::write(src_dir.join("Admin.elm"), admin).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Utils.elm")).unwrap();
+        let importers = workspace
+            .declaration_importers(&uri, Position::new(3, 1))
+            .unwrap();
+        assert_eq!(importers.name, "format");
+        assert!(importers.exposed_by_module);
+        let modules = |group: &[ImportingModule]| -> Vec<(String, usize)> {
+            group
+                .iter()
+                .map(|m| (m.module_name.clone(), m.references.len()))
+                .collect()
+        };
+        assert_eq!(modules(&importers.exposed), vec![("Page".to_string(), 2)]);
+        assert_eq!(
+            modules(&importers.aliased),
+            vec![("Settings".to_string(), 1)]
+        );
+        assert_eq!(
+            modules(&importers.qualified),
+            vec![("Admin".to_string(), 0)]
+        );
+        assert_eq!(importers.aliased[0].alias.as_deref(), Some("U"));
+
+        // Not exposed: nobody can import it
+        let hidden = workspace
+            .declaration_importers(&uri, Position::new(11, 1))
+            .unwrap();
+        assert!(!hidden.exposed_by_module);
+        assert!(hidden.qualified.is_empty());
+    }
+
+    #[test]
+    fn test_glsl_shader_inputs() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let shaders = "module Shaders exposing (vertexShader)\n\n\nvertexShader =\n    [glsl|\n        attribute vec3 position;\n        uniform mediump mat4 perspective; // camera\n        varying vec3 vcolor;\n        void main () {\n            gl_Position = perspective * vec4(position, 1.0);\n        }\n    |]\n";
+        fs::write(src_dir.join("Shaders.elm"), shaders).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Shaders.elm")).unwrap();
+        assert_eq!(
+            workspace.shader_inputs(&uri, "vertexShader").as_deref(),
+            Some("*Shader inputs*\n\n```glsl\nattribute vec3 position;\nuniform mat4 perspective;\nvarying vec3 vcolor;\n```")
+        );
+
+        // `perspective` in `main` is the uniform declared above
+        let (input, range) = workspace.glsl_input_at(&uri, Position::new(9, 28)).unwrap();
+        assert_eq!(input.qualifier, "uniform");
+        assert_eq!(
+            input.range,
+            Range::new(Position::new(6, 29), Position::new(6, 40))
+        );
+        assert_eq!(range.start, Position::new(9, 26));
+
+        // Other names in the block are not taken for Elm
+        assert!(workspace
+            .glsl_input_at(&uri, Position::new(9, 15))
+            .is_none());
+        assert!(workspace.in_glsl_block(&uri, Position::new(9, 15)));
+        assert!(!workspace.in_glsl_block(&uri, Position::new(3, 2)));
+    }
+
+    #[test]
+    fn test_reexport_chain_reaches_original_declaration() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let internal = "module Internal exposing (Color, red)\n\n\ntype Color\n    = Color Int\n\n\nred : Color\nred =\n    Color 0\n";
+        let colors = "module Colors exposing (Color, red)\n\nimport Internal\n\n\ntype alias Color =\n    Internal.Color\n\n\nred : Color\nred =\n    Internal.red\n";
+        let ui = "module Ui exposing (red)\n\nimport Colors as C\n\n\nred =\n    C.red\n";
+        fs::write(src_dir.join("Internal.elm"), internal).unwrap();
+        fs::write(src_dir.join("Colors.elm"), colors).unwrap();
+        fs::write(src_dir.join("Ui.elm"), ui).unwrap();
+        workspace.initialize().unwrap();
+
+        // Ui.red -> Colors.red -> Internal.red
+        let ui_red = workspace.declared_symbol("Ui", "red").unwrap();
+        let chain = workspace.reexport_chain(&ui_red);
+        let hops: Vec<_> = chain.iter().map(|s| s.module_name.as_str()).collect();
+        assert_eq!(hops, ["Colors", "Internal"]);
+        assert_eq!(chain[1].definition_range.start, Position::new(8, 0));
+
+        let alias = workspace.declared_symbol("Colors", "Color").unwrap();
+        let chain = workspace.reexport_chain(&alias);
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain[0].module_name, "Internal");
+
+        // The original declaration re-exports nothing
+        let original = workspace.declared_symbol("Internal", "red").unwrap();
+        assert!(workspace.reexport_chain(&original).is_empty());
+    }
+
+    #[test]
+    fn test_unused_imports_and_exposed_names() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let date = "module Date exposing (Date, format, parse)\n\n\ntype Date\n    = Date Int\n\n\nformat d =\n    d\n\n\nparse s =\n    s\n";
+        let types = "module Types exposing (..)\n\n\ntype Msg\n    = Clicked\n";
+        let colors = "module Colors exposing (red)\n\n\nred =\n    1\n";
+        let main = "module Main exposing (main)\n\nimport Colors\nimport Date exposing (Date, format, parse)\nimport Types exposing (..)\n\n\nmain : Date -> Msg\nmain d =\n    always Clicked (format d)\n";
+        fs::write(src_dir.join("Date.elm"), date).unwrap();
+        fs::write(src_dir.join("Types.elm"), types).unwrap();
+        fs::write(src_dir.join("Colors.elm"), colors).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let everything = Range::new(Position::new(0, 0), Position::new(u32::MAX, 0));
+        let fixes = workspace.unused_import_fixes(&uri, everything);
+        let messages: Vec<_> = fixes
+            .iter()
+            .map(|f| f.diagnostic.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "`Colors` is imported but never used",
+                "`parse` is exposed by the import of `Date` but never used",
+            ]
+        );
+        assert_eq!(
+            apply_edits(main, &fixes[0].edits),
+            main.replace("import Colors\n", "")
+        );
+        assert_eq!(
+            apply_edits(main, &fixes[1].edits),
+            main.replace("format, parse)", "format)")
+        );
+    }
+
+    #[test]
+    fn test_qualified_completion_on_name_collision() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let dates = "module Dates exposing (format)\n\n\nformat d =\n    d\n";
+        let money =
+            "module Money exposing (format, parse)\n\n\nformat m =\n    m\n\n\nparse m =\n    m\n";
+        let numbers = "module Numbers exposing (format)\n\n\nformat n =\n    n\n";
+        let main = "module Main exposing (main)\n\nimport Dates exposing (format)\nimport Money as M\n\n\nmain =\n    format 1\n";
+        fs::write(src_dir.join("Dates.elm"), dates).unwrap();
+        fs::write(src_dir.join("Money.elm"), money).unwrap();
+        fs::write(src_dir.join("Numbers.elm"), numbers).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        // `format` is already exposed by Dates: the alias qualifies Money's
+        let (text, edits) = workspace
+            .qualified_completion(&uri, "Money", "format")
+            .unwrap();
+        assert_eq!(text, "M.format");
+        assert!(edits.is_empty());
+
+        // A module not imported yet is qualified by its name and imported
+        let (text, edits) = workspace
+            .qualified_completion(&uri, "Numbers", "format")
+            .unwrap();
+        assert_eq!(text, "Numbers.format");
+        assert!(apply_edits(main, &edits).contains("import Numbers\n"));
+
+        // No clash, or the name already in scope, keeps the plain name
+        assert!(workspace
+            .qualified_completion(&uri, "Money", "parse")
+            .is_none());
+        assert!(workspace
+            .qualified_completion(&uri, "Dates", "format")
+            .is_none());
+    }
+
+    #[test]
+    fn test_qualified_and_local_completions() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let shape = "module Shape exposing (Shape(..), area)\n\n\ntype Shape\n    = Circle Float\n\n\narea shape =\n    1\n";
+        let main = "module Main exposing (view)\n\nimport Shape as S\n\n\nview count =\n    let\n        double =\n            count * 2\n    in\n    double\n\n\nshapes =\n    S.\n";
+        fs::write(src_dir.join("Shape.elm"), shape).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let labels = |items: Vec<CompletionItem>| -> Vec<String> {
+            items.into_iter().map(|item| item.label).collect()
+        };
+
+        // After the alias, what Shape exposes, constructors included
+        let items = workspace
+            .qualified_value_completions(&uri, Position::new(14, 6))
+            .unwrap();
+        let mut qualified = labels(items);
+        qualified.sort();
+        assert_eq!(qualified, ["Circle", "Shape", "area"]);
+
+        // In `view`'s body, the let binding and the parameter
+        let local = labels(workspace.local_completions(&uri, Position::new(10, 10)));
+        assert_eq!(local, ["double", "count"]);
+        assert!(workspace
+            .qualified_value_completions(&uri, Position::new(10, 10))
+            .is_none());
+    }
+
+    #[test]
+    fn test_module_header_template_for_empty_file() {
+        let (temp_dir, workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let uri = Url::from_file_path(src_dir.join("Pages/Home.elm")).unwrap();
+
+        let edit = workspace.module_header_template(&uri, "\n").unwrap();
+        assert_eq!(edit.new_text, "module Pages.Home exposing (..)\n\n\n");
+        assert_eq!(edit.range.end, Position::new(1, 0));
+
+        // Files with content, or outside the source directories, get none
+        assert!(workspace
+            .module_header_template(&uri, "module Home exposing (..)\n")
+            .is_none());
+        let outside = Url::from_file_path(temp_dir.path().join("scripts/Gen.elm")).unwrap();
+        assert!(workspace.module_header_template(&outside, "").is_none());
+    }
+
+    #[test]
+    fn test_docs_skeleton_covers_exposed_names() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let bare = "module Bare exposing (..)\n\nimport Html\n\n\ntype Shape\n    = Circle\n\n\narea s =\n    1\n";
+        let documented = "module Documented exposing (Shape(..), area, perimeter)\n\n{-| Shapes and their sizes.\n\n# Sizes\n@docs area, volume\n-}\n\n\ntype Shape\n    = Circle\n\n\narea s =\n    1\n\n\nperimeter s =\n    1\n";
+        fs::write(src_dir.join("Bare.elm"), bare).unwrap();
+        fs::write(src_dir.join("Documented.elm"), documented).unwrap();
+        workspace.initialize().unwrap();
+        let uri = |name: &str| Url::from_file_path(src_dir.join(name)).unwrap();
+
+        // A new doc comment after the header
+        let edit = workspace
+            .docs_skeleton(&uri("Bare.elm"), Position::new(0, 3))
+            .unwrap();
+        assert_eq!(edit.range.start, Position::new(0, 25));
+        assert_eq!(
+            edit.new_text,
+            "\n\n{-|\n\n# Types\n\n@docs Shape\n\n\n# Functions\n\n@docs area\n\n-}"
+        );
+
+        // The prose stays, `volume` is no longer exposed, `Shape` and
+        // `perimeter` are missing
+        let edit = workspace
+            .docs_skeleton(&uri("Documented.elm"), Position::new(0, 3))
+            .unwrap();
+        assert_eq!(edit.range.start, Position::new(2, 0));
+        assert_eq!(
+            edit.new_text,
+            "{-| Shapes and their sizes.\n\n# Sizes\n@docs area\n\n\n# Types\n\n@docs Shape\n\n\n# Functions\n\n@docs perimeter\n\n-}"
+        );
+
+        // Only offered on the header
+        assert!(workspace
+            .docs_skeleton(&uri("Bare.elm"), Position::new(9, 0))
+            .is_none());
+    }
+
+    #[test]
+    fn test_docs_drift_in_package_modules() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let elm_json = r#"{ "type": "package", "exposed-modules": { "Geometry": ["Shapes"] } }"#;
+        fs::write(temp_dir.path().join("elm.json"), elm_json).unwrap();
+        let shapes = "module Shapes exposing (Shape, area)\n\n{-| Shapes.\n\n@docs Shape, volume\n-}\n\n\ntype Shape\n    = Circle\n\n\narea s =\n    1\n";
+        let internal = "module Internal exposing (helper)\n\n\nhelper =\n    1\n";
+        fs::write(src_dir.join("Shapes.elm"), shapes).unwrap();
+        fs::write(src_dir.join("Internal.elm"), internal).unwrap();
+        workspace.initialize().unwrap();
+        assert_eq!(workspace.exposed_modules, Some(vec!["Shapes".to_string()]));
+
+        let uri = Url::from_file_path(src_dir.join("Shapes.elm")).unwrap();
+        let diagnostics = workspace.docs_diagnostics(&uri);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "`area` is exposed but not in any `@docs` line",
+                "`volume` is in `@docs` but the module does not expose it",
+            ]
+        );
+        assert_eq!(diagnostics[0].range.start, Position::new(0, 31));
+        assert_eq!(
+            diagnostics[1].range,
+            Range::new(Position::new(4, 13), Position::new(4, 19))
+        );
+
+        let fixes = workspace.docs_fixes(&uri, diagnostics[0].range);
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(
+            fixes[0].edits[0].new_text,
+            "{-| Shapes.\n\n@docs Shape, volume\n\n\n# Functions\n\n@docs area\n\n-}"
+        );
+        let fixes = workspace.docs_fixes(&uri, diagnostics[1].range);
+        assert_eq!(fixes[0].title, "Remove volume from @docs");
+        assert_eq!(
+            fixes[0].edits[0].new_text,
+            "{-| Shapes.\n\n@docs Shape\n\n-}"
+        );
+
+        // Modules the package does not expose are not checked
+        let internal = Url::from_file_path(src_dir.join("Internal.elm")).unwrap();
+        assert!(workspace.docs_diagnostics(&internal).is_empty());
+    }
+
+    #[test]
+    fn test_module_exposing_completions() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let page = "module Page exposing (view, Msg, )\n\nimport Html exposing (div)\n\n\ntype Msg\n    = Clicked\n\n\ntype alias Model =\n    { count : Int }\n\n\nview : Model -> String\nview model =\n    \"page\"\n\n\nupdate : Msg -> Model -> Model\nupdate msg model =\n    model\n\n\ntype Color\n    = Red\n";
+        fs::write(src_dir.join("Page.elm"), page).unwrap();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src_dir.join("Page.elm")).unwrap();
+
+        let items = workspace
+            .module_exposing_completions(&uri, Position::new(0, 32))
+            .unwrap();
+        let mut labels: Vec<&str> = items.iter().map(|i| i.label.as_str()).collect();
+        labels.sort();
+        // `Msg` is listed, so neither form of it is offered again
+        assert_eq!(labels, ["Color", "Color(..)", "Model", "update"]);
+        let update = items.iter().find(|i| i.label == "update").unwrap();
+        assert_eq!(
+            update.detail.as_deref(),
+            Some("update : Msg -> Model -> Model")
+        );
+
+        // An import's list is not the header's
+        assert!(workspace
+            .module_exposing_completions(&uri, Position::new(2, 22))
+            .is_none());
+    }
+
+    #[test]
+    fn test_package_validation() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let elm_json = "{\n    \"type\": \"package\",\n    \"exposed-modules\": [\n        \"Shapes\",\n        \"Missing\"\n    ]\n}\n";
+        fs::write(temp_dir.path().join("elm.json"), elm_json).unwrap();
+        let shapes = "module Shapes exposing (Shape, area, circle)\n\n{-| Shapes.\n\n@docs Shape, area, circle\n-}\n\nimport Internal.Geometry as Geometry exposing (Point)\n\n\n{-| A shape. -}\ntype Shape\n    = Circle Point\n\n\n{-| The area. -}\narea : Shape -> Geometry.Unit\narea s =\n    1\n\n\ncircle : Point -> Shape\ncircle p =\n    Circle p\n\n\nhidden : Point -> Point\nhidden p =\n    p\n";
+        let geometry = "module Internal.Geometry exposing (Point, Unit)\n\n\ntype alias Point =\n    ( Float, Float )\n\n\ntype alias Unit =\n    Float\n";
+        fs::create_dir_all(src_dir.join("Internal")).unwrap();
+        fs::write(src_dir.join("Shapes.elm"), shapes).unwrap();
+        fs::write(src_dir.join("Internal/Geometry.elm"), geometry).unwrap();
+        workspace.initialize().unwrap();
+
+        // A listed module without a file
+        let diagnostics = workspace.elm_json_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(4, 8), Position::new(4, 17))
+        );
+
+        // `circle` has no docs and `Unit` and `Point` are internal, but
+        // `hidden` is not exposed
+        let uri = Url::from_file_path(src_dir.join("Shapes.elm")).unwrap();
+        let diagnostics = workspace.package_diagnostics(&uri);
+        let found: Vec<(u32, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.range.start.line, d.message.split('`').nth(1).unwrap()))
+            .collect();
+        assert_eq!(found, [(16, "Unit"), (21, "Point"), (22, "circle")]);
+
+        // Internal modules are not checked
+        let internal = Url::from_file_path(src_dir.join("Internal/Geometry.elm")).unwrap();
+        assert!(workspace.package_diagnostics(&internal).is_empty());
+    }
+
+    #[test]
+    fn test_generated_code_is_left_out_of_refactors() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let snapshot_dir = src_dir.join("Evergreen/V1");
+        fs::create_dir_all(&snapshot_dir).unwrap();
+        fs::write(
+            snapshot_dir.join("Types.elm"),
+            "module Evergreen.V1.Types exposing (..)\n\n\ntype alias Model =\n    { count : Int }\n",
+        )
+        .unwrap();
+        fs::write(
+            src_dir.join("Types.elm"),
+            "module Types exposing (..)\n\n\ntype alias Model =\n    { count : Int }\n",
+        )
+        .unwrap();
+        workspace.initialize().unwrap();
+
+        // Indexed for navigation
+        let module = workspace.modules.get("Evergreen.V1.Types").unwrap();
+        assert!(module.generated && module.light);
+        assert!(workspace.symbols.contains_key("Evergreen.V1.Types.Model"));
+        assert!(!workspace.modules.get("Types").unwrap().generated);
+
+        // Never moved, renamed or edited
+        let snapshot = Url::from_file_path(snapshot_dir.join("Types.elm")).unwrap();
+        let types = Url::from_file_path(src_dir.join("Types.elm")).unwrap();
+        assert!(workspace.is_generated(&snapshot));
+        let error = workspace.rename_file(&snapshot, "Old").unwrap_err();
+        assert!(error.to_string().contains("is generated code"));
+        assert!(workspace
+            .move_file(&types, "src/Evergreen/V1/Moved.elm")
+            .is_err());
+        assert!(workspace
+            .reorder_fields(&snapshot, "Model", None, false)
+            .is_err());
+        assert!(workspace.reorder_variants(&snapshot, "Msg", None).is_err());
+        let fields = ["a".to_string(), "b".to_string()];
+        assert!(workspace
+            .tuple_to_record(&snapshot, Position::new(0, 0), "Pair", &fields)
+            .is_err());
+        let mut changes = HashMap::new();
+        changes.insert(snapshot.clone(), Vec::new());
+        changes.insert(types.clone(), Vec::new());
+        workspace.filter_test_edits(&mut changes, None);
+        assert_eq!(changes.keys().collect::<Vec<_>>(), [&types]);
+    }
+
+    #[test]
+    fn test_organize_declarations() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let content = "module Main exposing (main)\n\n{-| The app.\n-}\n\nimport Html\n\n\n-- VIEW\n\n\nview : Model -> Html.Html Msg\nview model =\n    Html.text \"\"\n\n\nhelper =\n    1\n\n\n{-| Entry point -}\nmain =\n    Html.text \"\"\n\n\nupdate msg model =\n    model\n\n\ntype alias Model =\n    Int\n\n\ntype Msg\n    = NoOp\n";
+        fs::write(src_dir.join("Main.elm"), content).unwrap();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+
+        let result = workspace
+            .organize_declarations(&uri, &crate::config::DeclarationOrder::default())
+            .unwrap();
+        assert!(result.success);
+        let edits = &result.changes.unwrap()[&uri];
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].range.start, Position::new(8, 0));
+        assert_eq!(
+            edits[0].new_text,
+            "{-| Entry point -}\nmain =\n    Html.text \"\"\n\n\ntype alias Model =\n    Int\n\n\ntype Msg\n    = NoOp\n\n\nupdate msg model =\n    model\n\n\n-- VIEW\n\n\nview : Model -> Html.Html Msg\nview model =\n    Html.text \"\"\n\n\nhelper =\n    1"
+        );
+
+        // Without rules, nothing moves
+        let order = crate::config::DeclarationOrder {
+            exposed_first: false,
+            types_first: false,
+            group_update_view: false,
+        };
+        let result = workspace.organize_declarations(&uri, &order).unwrap();
+        assert!(result.changes.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_evergreen_drift_against_newest_snapshot() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let elm_json = r#"{ "source-directories": ["src"], "dependencies": { "direct": { "lamdera/core": "1.0.0" } } }"#;
+        fs::write(temp_dir.path().join("elm.json"), elm_json).unwrap();
+        for (version, types) in [
+            ("V1", "module Evergreen.V1.Types exposing (..)\n\n\ntype Old\n    = Old\n"),
+            ("V2", "module Evergreen.V2.Types exposing (..)\n\nimport Evergreen.V2.Page\n\n\ntype alias FrontendModel =\n    { page : Evergreen.V2.Page.Page\n    , count : Int\n    , name : String\n    }\n\n\ntype ToBackend\n    = Save Int\n    | Reset\n\n\ntype alias Id =\n    Int\n\n\ntype Gone\n    = Gone\n"),
+        ] {
+            let dir = src_dir.join("Evergreen").join(version);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("Types.elm"), types).unwrap();
+        }
+        let types = "module Types exposing (..)\n\nimport Page exposing (Page)\n\n\ntype alias FrontendModel =\n    { page : Page\n    , count : Float\n    , email : String\n    }\n\n\ntype ToBackend\n    = Save Int\n    | Reset\n\n\ntype alias Id =\n    { value : Int }\n\n\ntype BackendMsg\n    = NoOp\n";
+        fs::write(src_dir.join("Types.elm"), types).unwrap();
+        workspace.initialize().unwrap();
+
+        let drift = workspace.evergreen_drift().unwrap();
+        assert_eq!(drift.snapshot.as_deref(), Some("V2"));
+        assert_eq!(drift.added, ["BackendMsg"]);
+        assert_eq!(drift.removed, ["Gone"]);
+        let changed: Vec<&str> = drift.changed.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(changed, ["FrontendModel", "Id"]);
+
+        // `page` only differs by its qualifier
+        let model = &drift.changed[0];
+        let names = |members: &[Member]| -> Vec<String> {
+            members
+                .iter()
+                .map(|m| format!("{} : {}", m.name, m.type_text))
+                .collect()
+        };
+        assert_eq!(names(&model.added), ["email : String"]);
+        assert_eq!(names(&model.removed), ["name : String"]);
+        assert_eq!(names(&model.changed), ["count : Float"]);
+        assert_eq!(
+            drift.changed[1].definition,
+            Some(("Int".to_string(), "{ value : Int }".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_evergreen_drift_ignores_reordered_members() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let elm_json = r#"{ "source-directories": ["src"], "dependencies": { "direct": { "lamdera/core": "1.0.0" } } }"#;
+        fs::write(temp_dir.path().join("elm.json"), elm_json).unwrap();
+        let snapshot = "module Evergreen.V1.Types exposing (..)\n\n\ntype alias FrontendModel =\n    { page : Int\n    , count : Int\n    }\n\n\ntype ToBackend\n    = Save Int\n    | Reset\n";
+        let dir = src_dir.join("Evergreen").join("V1");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Types.elm"), snapshot).unwrap();
+        // The same fields and variants, declared in another order
+        let types = "module Types exposing (..)\n\n\ntype alias FrontendModel =\n    { count : Int\n    , page : Int\n    }\n\n\ntype ToBackend\n    = Reset\n    | Save Int\n";
+        fs::write(src_dir.join("Types.elm"), types).unwrap();
+        workspace.initialize().unwrap();
+
+        let drift = workspace.evergreen_drift().unwrap();
+        assert!(drift.added.is_empty());
+        assert!(drift.removed.is_empty());
+        assert!(drift.changed.is_empty());
+    }
+
+    #[test]
+    fn test_lamdera_wire_encoding_of_types() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let elm_json = r#"{ "source-directories": ["src"], "dependencies": { "direct": { "lamdera/core": "1.0.0" } } }"#;
+        fs::write(temp_dir.path().join("elm.json"), elm_json).unwrap();
+        let types = "module Types exposing (..)\n\n\ntype alias FrontendModel =\n    { page : Int\n    , count : Maybe Int\n    }\n\n\ntype ToBackend\n    = Save Int String\n    | Reset\n";
+        fs::write(src_dir.join("Types.elm"), types).unwrap();
+        fs::write(src_dir.join("Main.elm"), types.replace("Types", "Main")).unwrap();
+        workspace.initialize().unwrap();
+        let uri = |name: &str| Url::from_file_path(src_dir.join(name)).unwrap();
+
+        let record = workspace
+            .wire_encoding(&uri("Types.elm"), types, "FrontendModel")
+            .unwrap();
+        assert!(record.contains("| 0 | `count` | `Maybe Int` |\n| 1 | `page` | `Int` |"));
+        let custom = workspace
+            .wire_encoding(&uri("Types.elm"), types, "ToBackend")
+            .unwrap();
+        assert!(custom.contains("| 0 | `Reset` |  |\n| 1 | `Save` | `Int String` |"));
+
+        assert!(workspace
+            .wire_encoding(&uri("Main.elm"), types, "ToBackend")
+            .is_none());
+    }
+
+    #[test]
+    fn test_open_buffers_win_over_disk() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        fs::write(
+            src_dir.join("Helper.elm"),
+            "module Helper exposing (helper)\n\n\nhelper =\n    1\n",
+        )
+        .unwrap();
+        fs::write(
+            src_dir.join("Main.elm"),
+            "module Main exposing (main)\n\n\nmain =\n    2\n",
+        )
+        .unwrap();
+        workspace.initialize().unwrap();
+
+        // Unsaved: Main now uses the helper
+        let main_uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let unsaved =
+            "module Main exposing (main)\n\nimport Helper\n\n\nmain =\n    Helper.helper\n";
+        workspace.open_buffer(&main_uri, unsaved);
+        let users = |workspace: &Workspace| {
+            workspace
+                .find_references("helper", Some("Helper"))
+                .iter()
+                .filter(|r| r.uri == main_uri)
+                .count()
+        };
+        assert_eq!(users(&workspace), 1);
+
+        // Refactors edit the buffer's text, not the file's
+        let helper_uri = Url::from_file_path(src_dir.join("Helper.elm")).unwrap();
+        let result = workspace.rename_file(&helper_uri, "Util.elm").unwrap();
+        let main_edits = &result.changes[&main_uri];
+        assert!(main_edits.iter().any(|e| e.new_text.contains("Util")));
+        workspace.initialize().unwrap();
+        assert_eq!(users(&workspace), 1);
+
+        workspace.close_buffer(&main_uri);
+        assert_eq!(users(&workspace), 0);
+    }
+
+    #[test]
+    fn test_rename_module_alias_in_its_module() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        fs::create_dir_all(src_dir.join("Long")).unwrap();
+        fs::write(
+            src_dir.join("Long/Name.elm"),
+            "module Long.Name exposing (Thing(..), get)\n\n\ntype Thing\n    = Wrap Int\n\n\nget x =\n    x\n",
+        )
+        .unwrap();
+        let main = "module Main exposing (main)\n\nimport Dict\nimport Long.Name as M\n\n\nmain : M.Thing -> Int\nmain (M.Wrap x) =\n    M.get x\n";
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+
+        // On a qualifier as well as on the `as` clause
+        let (name, range) = workspace
+            .module_alias_at(&uri, Position::new(8, 4))
+            .unwrap();
+        assert_eq!(name, "M");
+        assert_eq!(range, Range::new(Position::new(8, 4), Position::new(8, 5)));
+        let edits = workspace
+            .rename_module_alias(&uri, Position::new(3, 20), "Name")
+            .unwrap()
+            .unwrap();
+        let mut lines: Vec<u32> = edits.iter().map(|e| e.range.start.line).collect();
+        lines.sort();
+        assert_eq!(lines, [3, 6, 7, 8]);
+        assert!(edits.iter().all(|e| e.new_text == "Name"));
+
+        // Not an alias: the module name, a constructor
+        assert!(workspace
+            .module_alias_at(&uri, Position::new(3, 12))
+            .is_none());
+        assert!(workspace
+            .module_alias_at(&uri, Position::new(7, 9))
+            .is_none());
+
+        assert!(workspace
+            .rename_module_alias(&uri, Position::new(3, 20), "Dict")
+            .is_err());
+        assert!(workspace
+            .rename_module_alias(&uri, Position::new(3, 20), "lower")
+            .is_err());
+    }
+
+    #[test]
+    fn test_flatten_and_split_nested_case() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src = temp_dir.path().join("src");
+        let nested = r#"module Main exposing (..)
+
+
+view model =
+    case model.page of
+        Home ->
+            case model.user of
+                Just user ->
+                    greet
+                        user
+
+                Nothing ->
+                    login
+
+        About ->
+            about
+"#;
+        let flat = r#"module Main exposing (..)
+
+
+view model =
+    case ( model.page, model.user ) of
+        ( Home, Just user ) ->
+            greet
+                user
+
+        ( Home, Nothing ) ->
+            login
+
+        ( About, _ ) ->
+            about
+"#;
+        fs::write(src.join("Main.elm"), nested).unwrap();
+        fs::write(
+            src.join("Bound.elm"),
+            "module Bound exposing (..)\n\n\nf x =\n    case x of\n        Just y ->\n            case y of\n                _ ->\n                    1\n\n        Nothing ->\n            0\n",
+        )
+        .unwrap();
+        workspace.initialize().unwrap();
+
+        let uri = Url::from_file_path(src.join("Main.elm")).unwrap();
+        let apply = |source: &str, edit: TextEdit| apply_edits(source, &[edit]);
+
+        // From the outer or the nested case
+        let edit = workspace.flatten_case(&uri, Position::new(4, 6)).unwrap();
+        assert_eq!(apply(nested, edit), flat);
+        let edit = workspace.flatten_case(&uri, Position::new(6, 14)).unwrap();
+        assert_eq!(apply(nested, edit), flat);
+        assert!(workspace.split_case(&uri, Position::new(4, 6)).is_none());
+        // Not on a case header
+        assert!(workspace.flatten_case(&uri, Position::new(8, 20)).is_none());
+
+        workspace.open_buffer(&uri, flat);
+        let edit = workspace.split_case(&uri, Position::new(4, 6)).unwrap();
+        assert_eq!(apply(flat, edit), nested);
+        assert!(workspace.flatten_case(&uri, Position::new(4, 6)).is_none());
+
+        // `y` is bound by the outer pattern, so it cannot move out
+        let bound = Url::from_file_path(src.join("Bound.elm")).unwrap();
+        assert!(workspace
+            .flatten_case(&bound, Position::new(4, 6))
+            .is_none());
+    }
+
+    #[test]
+    fn test_type_definition_of_values() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let content = "module Main exposing (..)\n\n\ntype Page\n    = Home\n    | About\n\n\ncurrent : Page\ncurrent =\n    Home\n\n\ntitle =\n    current\n";
+        fs::write(src_dir.join("Main.elm"), content).unwrap();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+
+        let type_of = |line, character| {
+            workspace
+                .type_definition_at(&uri, Position::new(line, character))
+                .map(|symbol| format!("{}.{}", symbol.module_name, symbol.name))
+        };
+        // A reference, and the name of a declaration
+        assert_eq!(type_of(14, 6).as_deref(), Some("Main.Page"));
+        assert_eq!(type_of(9, 2).as_deref(), Some("Main.Page"));
+        // Nothing on the `=`
+        assert_eq!(type_of(13, 6), None);
+    }
+
+    #[test]
+    fn test_float_binding_keeps_a_commented_annotation() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src = temp_dir.path().join("src");
+        let view = "module Main exposing (..)\n\n\nview model =\n    let\n        title : String\n        -- shown in the header\n        title =\n            \"Home\"\n    in\n    text title\n";
+        fs::write(src.join("Main.elm"), view).unwrap();
+        workspace.initialize().unwrap();
+        let main = Url::from_file_path(src.join("Main.elm")).unwrap();
+
+        let (_, edits) = workspace.float_binding(&main, Position::new(7, 8)).unwrap();
+        assert_eq!(
+            apply_edits(view, &edits),
+            "module Main exposing (..)\n\n\nview model =\n    text title\n\n\ntitle : String\n-- shown in the header\ntitle =\n    \"Home\"\n"
+        );
+    }
+
+    #[test]
+    fn test_float_and_sink_let_bindings() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src = temp_dir.path().join("src");
+        let view = r#"module Main exposing (..)
+
+
+view model =
+    let
+        title =
+            "Home"
+
+        count =
+            model.count
+    in
+    case model.page of
+        Home ->
+            text title
+
+        About ->
+            text (String.fromInt count)
+"#;
+        let update = r#"module Update exposing (..)
+
+
+update msg model =
+    let
+        helper x =
+            let
+                base =
+                    model.offset
+            in
+            x + base
+    in
+    helper 1
+"#;
+        fs::write(src.join("Main.elm"), view).unwrap();
+        fs::write(src.join("Update.elm"), update).unwrap();
+        workspace.initialize().unwrap();
+        let main = Url::from_file_path(src.join("Main.elm")).unwrap();
+        let update_uri = Url::from_file_path(src.join("Update.elm")).unwrap();
+
+        // `title` uses nothing local, so it can go to the top level
+        let (title, edits) = workspace.float_binding(&main, Position::new(5, 8)).unwrap();
+        assert_eq!(title, "Move `title` to the top level");
+        assert_eq!(
+            apply_edits(view, &edits),
+            r#"module Main exposing (..)
+
+
+view model =
+    let
+        count =
+            model.count
+    in
+    case model.page of
+        Home ->
+            text title
+
+        About ->
+            text (String.fromInt count)
+
+
+title =
+    "Home"
+"#
+        );
+        // `count` needs `model`
+        assert!(workspace
+            .float_binding(&main, Position::new(8, 8))
+            .is_none());
+
+        // Only the `Home` branch uses `title`
+        let (title, edits) = workspace.sink_binding(&main, Position::new(5, 8)).unwrap();
+        assert_eq!(title, "Move `title` into the branch that uses it");
+        assert_eq!(
+            apply_edits(view, &edits),
+            r#"module Main exposing (..)
+
+
+view model =
+    let
+        count =
+            model.count
+    in
+    case model.page of
+        Home ->
+            let
+                title =
+                    "Home"
+            in
+            text title
+
+        About ->
+            text (String.fromInt count)
+"#
+        );
+
+        // `base` needs `model` but not `x`, so it joins `helper` and the
+        // inner `let` goes away
+        let (title, edits) = workspace
+            .float_binding(&update_uri, Position::new(7, 16))
+            .unwrap();
+        assert_eq!(title, "Move `base` out to the enclosing let");
+        assert_eq!(
+            apply_edits(update, &edits),
+            r#"module Update exposing (..)
+
+
+update msg model =
+    let
+        base =
+            model.offset
+
+        helper x =
+            x + base
+    in
+    helper 1
+"#
+        );
+        // Used in a function, not a branch
+        assert!(workspace
+            .sink_binding(&update_uri, Position::new(7, 16))
+            .is_none());
+    }
+
+    #[test]
+    fn test_literal_hovers_and_rewrites() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src = temp_dir.path().join("src");
+        let content = r#"module Main exposing (..)
+
+
+mask =
+    255
+
+
+color =
+    0x0100
+
+
+newline =
+    '\n'
+
+
+greeting name =
+    "Hello, " ++ name ++ "!"
+
+
+sum =
+    [ 1 ] ++ [ 2 ]
+"#;
+        fs::write(src.join("Main.elm"), content).unwrap();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src.join("Main.elm")).unwrap();
+
+        let (hover, _) = workspace.literal_hover(&uri, Position::new(4, 5)).unwrap();
+        assert_eq!(hover, "`255` = `0xFF`");
+        let (hover, _) = workspace.literal_hover(&uri, Position::new(8, 6)).unwrap();
+        assert_eq!(hover, "`0x0100` = `256`");
+        let (hover, _) = workspace.literal_hover(&uri, Position::new(12, 5)).unwrap();
+        assert_eq!(hover, "`Char.toCode '\\n'` = `10` (U+000A)");
+
+        let (title, edit) = workspace
+            .convert_int_literal(&uri, Position::new(4, 5))
+            .unwrap();
+        assert_eq!(title, "Convert to hexadecimal `0xFF`");
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(4, 4), Position::new(4, 7))
+        );
+        let (title, _) = workspace
+            .convert_int_literal(&uri, Position::new(8, 6))
+            .unwrap();
+        assert_eq!(title, "Convert to decimal `256`");
+
+        let edit = workspace
+            .string_concat(&uri, Position::new(16, 19))
+            .unwrap();
+        assert_eq!(edit.new_text, r#"String.concat [ "Hello, ", name, "!" ]"#);
+        // Lists are joined with `++` too
+        assert!(workspace
+            .string_concat(&uri, Position::new(20, 10))
+            .is_none());
+    }
+
+    #[test]
+    fn test_package_index_is_shared_between_projects() {
+        let elm_home = TempDir::new().unwrap();
+        let package_src = elm_home
+            .path()
+            .join("0.19.1/packages/author/shared/1.0.0/src");
+        fs::create_dir_all(&package_src).unwrap();
+        fs::write(
+            package_src.join("Shared.elm"),
+            "module Shared exposing (helper)\n\n\nhelper =\n    1\n",
+        )
+        .unwrap();
+        let open_project = || {
+            let temp_dir = TempDir::new().unwrap();
+            fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+            let elm_json = r#"{
+                "source-directories": ["src"],
+                "dependencies": { "direct": { "author/shared": "1.0.0" }, "indirect": {} }
+            }"#;
+            fs::write(temp_dir.path().join("elm.json"), elm_json).unwrap();
+            let mut workspace = Workspace::new(temp_dir.path().to_path_buf());
+            workspace.elm_home = elm_home.path().to_path_buf();
+            workspace.initialize().unwrap();
+            (temp_dir, workspace)
+        };
+
+        let (_first_dir, first) = open_project();
+        assert!(first.external_symbols.contains_key("Shared.helper"));
+        let stored = elm_home
+            .path()
+            .join("elm-lsp/packages/author/shared/1.0.0.json");
+        assert!(stored.is_file());
+
+        // Published packages do not change, so the second project takes the
+        // index as it is instead of parsing the package again
+        fs::write(
+            package_src.join("Shared.elm"),
+            "module Shared exposing (other)\n\n\nother =\n    1\n",
+        )
+        .unwrap();
+        let (_second_dir, second) = open_project();
+        assert!(second.external_symbols.contains_key("Shared.helper"));
+        assert!(!second.external_symbols.contains_key("Shared.other"));
+    }
+
+    #[test]
+    fn test_rename_field_renames_punned_variables() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src = temp_dir.path().join("src");
+        let main = r#"module Main exposing (..)
+
+
+type alias User =
+    { userId : Int, name : String }
+
+
+label : User -> String
+label { userId, name } =
+    case name of
+        "" ->
+            String.fromInt userId
+
+        other ->
+            other
+
+
+ids : List User -> List Int
+ids users =
+    List.map (\{ userId, name } -> userId + String.length name) users
+
+
+first : Maybe User -> List User -> Int
+first maybeUser others =
+    case maybeUser of
+        Just { userId, name } ->
+            userId + String.length name
+
+        Nothing ->
+            let
+                userId =
+                    List.length others
+            in
+            userId
+"#;
+        fs::write(src.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src.join("Main.elm")).unwrap();
+
+        let field = workspace
+            .get_field_at_position(&uri, Position::new(4, 6), main)
+            .unwrap();
+        let changes = workspace.rename_field_edits(&field, "accountId").unwrap();
+        let renamed = apply_edits(main, &changes[&uri]);
+        assert!(renamed.contains("{ accountId : Int, name : String }"));
+        assert!(renamed.contains("label { accountId, name } ="));
+        assert!(renamed.contains("String.fromInt accountId"));
+        assert!(renamed.contains("(\\{ accountId, name } -> accountId + String.length name)"));
+        assert!(renamed.contains("Just { accountId, name } ->\n            accountId +"));
+        // The `userId` bound in the other branch is another variable
+        assert!(renamed.contains("userId =\n                    List.length others\n            in\n            userId\n"));
+
+        // `name` is bound next to `userId`, and `others` within `first`
+        assert!(workspace.rename_field_edits(&field, "name").is_err());
+        assert!(workspace.rename_field_edits(&field, "others").is_err());
+        // `first` is a top-level value, and `other` a variable in `label`
+        assert!(workspace.rename_field_edits(&field, "first").is_err());
+        assert!(workspace.rename_field_edits(&field, "other").is_err());
+    }
+
+    #[test]
+    fn test_type_completions() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let dict = "module Dict exposing (Dict, empty)\n\n\ntype Dict k v\n    = Empty\n\n\nempty : Dict k v\nempty =\n    Empty\n";
+        let html = "module Html exposing (Html, Attribute, text)\n\n\ntype Html msg\n    = Node\n\n\ntype Attribute msg\n    = Attr\n\n\ntext : String -> Html msg\ntext s =\n    Node\n";
+        let page = "module Page exposing (Page, Route(..))\n\n\ntype alias Page =\n    { title : String }\n\n\ntype Route\n    = Home\n";
+        let main = r#"module Main exposing (..)
+
+import Dict exposing (Dict)
+import Html as H exposing (Html)
+import Page
+
+
+type alias Model a =
+    { count : In
+    , items : List a
+    }
+
+
+type Msg
+    = Got Di
+
+
+view : Model comparable -> msg -> H.
+view model msg =
+    H.text ""
+
+
+update : Msg -> Model x -> Ma
+update msg model =
+    model
+"#;
+        fs::write(src_dir.join("Dict.elm"), dict).unwrap();
+        fs::write(src_dir.join("Html.elm"), html).unwrap();
+        fs::write(src_dir.join("Page.elm"), page).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let labels = |line: u32, character: u32| {
+            workspace
+                .type_completions(&uri, Position::new(line, character))
+                .map(|items| {
+                    let mut labels: Vec<String> = items.into_iter().map(|i| i.label).collect();
+                    labels.sort();
+                    labels
+                })
+        };
+
+        // A field's type in a type alias with a parameter
+        let field = labels(8, 16).unwrap();
+        for expected in [
+            "a",
+            "Int",
+            "Model",
+            "Msg",
+            "Dict",
+            "Html",
+            "H.Html",
+            "Page.Page",
+            "Page.Route",
+        ] {
+            assert!(
+                field.contains(&expected.to_string()),
+                "{} missing",
+                expected
+            );
+        }
+        assert!(!field.contains(&"Route".to_string()));
+        assert!(!field.contains(&"view".to_string()));
+        // A constructor's argument, but not its name
+        assert!(labels(14, 12).unwrap().contains(&"Dict".to_string()));
+        assert_eq!(labels(14, 9), None);
+        // After a qualifier, only that module's types
+        assert_eq!(
+            labels(17, 36),
+            Some(vec!["Attribute".to_string(), "Html".to_string()])
+        );
+        // The annotation's own type variables
+        let annotation = labels(22, 29).unwrap();
+        assert!(annotation.contains(&"x".to_string()));
+        assert!(annotation.contains(&"Maybe".to_string()));
+        assert!(!annotation.contains(&"comparable".to_string()));
+        // Field names and expressions are not types
+        assert_eq!(labels(8, 10), None);
+        assert_eq!(labels(19, 9), None);
+        assert_eq!(labels(23, 10), None);
+    }
+
+    #[test]
+    fn test_record_update_hover() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let main = r#"module Main exposing (..)
+
+
+type Page
+    = Home
+
+
+type alias Model =
+    { page : Page
+    , count : Int
+    }
+
+
+goHome : Model -> Model
+goHome model =
+    { model | page = Home, count = 0 }
+
+
+bump record =
+    { record | count = record.count + 1 }
+"#;
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+
+        let (value, range) = workspace
+            .record_update_hover(&uri, Position::new(15, 7))
+            .unwrap();
+        assert_eq!(
+            value,
+            "```elm\ntype alias Model =\n    { page : Page\n    , count : Int\n    }\n```\n\nUpdates a `Model` from `Main`, setting `page`, `count`"
+        );
+        assert_eq!(
+            range,
+            Range::new(Position::new(15, 6), Position::new(15, 11))
+        );
+        // The `|` shows the same
+        let (bar, range) = workspace
+            .record_update_hover(&uri, Position::new(15, 12))
+            .unwrap();
+        assert_eq!(bar, value);
+        assert_eq!(
+            range,
+            Range::new(Position::new(15, 12), Position::new(15, 13))
+        );
+
+        // Without an alias, the inferred record type
+        let (value, _) = workspace
+            .record_update_hover(&uri, Position::new(19, 7))
+            .unwrap();
+        assert!(value.contains("count : "), "{}", value);
+        assert!(value.ends_with("Updates a record, setting `count`"));
+
+        // Fields and values are not the base
+        assert!(workspace
+            .record_update_hover(&uri, Position::new(15, 15))
+            .is_none());
+        assert!(workspace
+            .record_update_hover(&uri, Position::new(15, 22))
+            .is_none());
+    }
+
+    #[test]
+    fn test_constructor_usages() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let shape = r#"module Shape exposing (Shape(..), area, unit)
+
+
+type Shape
+    = Circle Float
+    | Square Float
+
+
+unit : Shape
+unit =
+    Circle 1
+
+
+area : Shape -> Float
+area shape =
+    case shape of
+        Circle r ->
+            r * r
+
+        Square side ->
+            side * side
+"#;
+        let main = r#"module Main exposing (..)
+
+import Shape exposing (Shape(..))
+
+
+type alias Model =
+    { shape : Shape }
+
+
+grow : Model -> Model
+grow model =
+    case model.shape of
+        Square side ->
+            { model | shape = Square (side * 2) }
+
+        other ->
+            { model | shape = other }
+"#;
+        fs::write(src_dir.join("Shape.elm"), shape).unwrap();
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+        let main_uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let shape_uri = Url::from_file_path(src_dir.join("Shape.elm")).unwrap();
+        let places = |locations: &[Location]| {
+            let mut places: Vec<(String, u32, u32)> = locations
+                .iter()
+                .map(|l| {
+                    let file = l.uri.path().rsplit('/').next().unwrap().to_string();
+                    (file, l.range.start.line, l.range.start.character)
+                })
+                .collect();
+            places.sort();
+            places
+        };
+        let at = |file: &str, line: u32, character: u32| (file.to_string(), line, character);
+
+        // From a constructor used in another module
+        let usages = workspace
+            .constructor_usages(&main_uri, Position::new(12, 8))
+            .unwrap();
+        assert_eq!(
+            (usages.module_name.as_str(), usages.type_name.as_str()),
+            ("Shape", "Shape")
+        );
+        let names: Vec<&str> = usages
+            .constructors
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, ["Circle", "Square"]);
+        let circle = &usages.constructors[0];
+        assert_eq!(places(&circle.constructions), [at("Shape.elm", 10, 4)]);
+        assert_eq!(places(&circle.patterns), [at("Shape.elm", 16, 8)]);
+        let square = &usages.constructors[1];
+        assert_eq!(places(&square.constructions), [at("Main.elm", 13, 30)]);
+        assert_eq!(
+            places(&square.patterns),
+            [at("Main.elm", 12, 8), at("Shape.elm", 19, 8)]
+        );
+        assert_eq!(
+            places(&usages.signatures),
+            [
+                at("Main.elm", 6, 14),
+                at("Shape.elm", 8, 7),
+                at("Shape.elm", 13, 7)
+            ]
+        );
+
+        // The declaration's name gives the same
+        let from_declaration = workspace
+            .constructor_usages(&shape_uri, Position::new(3, 6))
+            .unwrap();
+        assert_eq!(
+            places(&from_declaration.signatures),
+            places(&usages.signatures)
+        );
+        // Not on a custom type
+        assert!(workspace
+            .constructor_usages(&main_uri, Position::new(10, 2))
+            .is_none());
+    }
+
+    #[test]
+    fn test_elm_json_symbols() {
+        let content = r#"{
+    "type": "package",
+    "name": "author/shapes",
+    "exposed-modules": {
+        "Shapes": [
+            "Shapes",
+            "Shapes.Circle"
+        ]
+    },
+    "dependencies": {
+        "elm/core": "1.0.0 <= v < 2.0.0"
+    }
+}
+"#;
+        let symbols = elm_json_symbols(content);
+        let outline: Vec<(&str, SymbolKind, Option<&str>)> = symbols
+            .iter()
+            .map(|s| (s.name.as_str(), s.kind, s.detail.as_deref()))
+            .collect();
+        assert_eq!(
+            outline,
+            [
+                ("type", SymbolKind::PROPERTY, Some("package")),
+                ("name", SymbolKind::PROPERTY, Some("author/shapes")),
+                ("exposed-modules", SymbolKind::NAMESPACE, None),
+                ("dependencies", SymbolKind::NAMESPACE, None),
+            ]
+        );
+
+        let category = &symbols[2].children.as_ref().unwrap()[0];
+        assert_eq!(category.name, "Shapes");
+        let modules: Vec<(&str, SymbolKind, u32)> = category
+            .children
+            .iter()
+            .flatten()
+            .map(|s| (s.name.as_str(), s.kind, s.range.start.line))
+            .collect();
+        assert_eq!(
+            modules,
+            [
+                ("Shapes", SymbolKind::MODULE, 5),
+                ("Shapes.Circle", SymbolKind::MODULE, 6)
+            ]
+        );
+        assert_eq!(symbols[2].range.start, Position::new(3, 4));
+        assert_eq!(symbols[2].range.end, Position::new(8, 5));
+
+        let package = &symbols[3].children.as_ref().unwrap()[0];
+        assert_eq!(package.name, "elm/core");
+        assert_eq!(package.kind, SymbolKind::PACKAGE);
+        assert_eq!(package.detail.as_deref(), Some("1.0.0 <= v < 2.0.0"));
+
+        assert!(elm_json_symbols("{ \"type\": ").is_empty());
+    }
+
+    #[test]
+    fn test_extract_view_helper() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let html = "module Html exposing (Html, Attribute, div, text, map, onClick)\n\n\ntype Node msg\n    = Node (List msg)\n\n\ntype alias Html msg =\n    Node msg\n\n\ntype Attribute msg\n    = Attribute msg\n\n\ndiv : List (Attribute msg) -> List (Html msg) -> Html msg\ndiv attributes children =\n    Node []\n\n\ntext : String -> Html msg\ntext s =\n    Node []\n\n\nmap : (a -> msg) -> Html a -> Html msg\nmap f node =\n    Node []\n\n\nonClick : msg -> Attribute msg\nonClick msg =\n    Attribute msg\n";
+        let content = r#"module Main exposing (..)
+
+import Html as H exposing (Html)
+
+
+type alias Model =
+    { name : String }
+
+
+type Msg
+    = Rename String
+    | Reset
+
+
+view : Model -> Html Msg
+view model =
+    H.div []
+        [ H.div [ H.onClick Reset ] [ H.text model.name ]
+        , H.text "footer"
+        ]
+
+
+viewForm : Model -> Html Msg
+viewForm model =
+    H.div []
+        [ H.div [] [ viewRename model ]
+        ]
+
+
+viewRename : Model -> Html Msg
+viewRename model =
+    H.div [ H.onClick (Rename model.name) ] []
+"#;
+        fs::write(src_dir.join("Html.elm"), html).unwrap();
+        fs::write(src_dir.join("Main.elm"), content).unwrap();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let extract = |range: Range, name: &str, own_msg: bool| {
+            let result = workspace
+                .extract_view_helper(&uri, range, name, own_msg)
+                .unwrap();
+            assert!(result.success, "{}", result.message);
+            apply_edits(content, &result.changes.unwrap()[&uri])
+        };
+
+        // Nothing in it produces messages, so it works for any msg
+        let name = Range::new(Position::new(17, 38), Position::new(17, 55));
+        let extracted = extract(name, "viewName", false);
+        assert!(extracted.contains("[ viewName model ]"), "{}", extracted);
+        assert!(
+            extracted.contains("        ]\n\n\nviewName : Model -> Html msg\nviewName model =\n    H.text model.name\n\n\nviewForm"),
+            "{}",
+            extracted
+        );
+
+        // `Reset` is a message, so the helper keeps the page's Msg
+        let button = Range::new(Position::new(17, 10), Position::new(17, 57));
+        let extracted = extract(button, "viewReset", false);
+        assert!(
+            extracted.contains("        [ viewReset model\n        , H.text \"footer\""),
+            "{}",
+            extracted
+        );
+        assert!(
+            extracted.contains("viewReset : Model -> Html Msg\nviewReset model =\n    H.div [ H.onClick Reset ] [ H.text model.name ]\n"),
+            "{}",
+            extracted
+        );
+
+        // With its own Msg, the call is mapped back to the page's messages
+        let extracted = extract(button, "viewReset", true);
+        assert!(
+            extracted.contains("        [ H.map fromResetMsg (viewReset model)\n"),
+            "{}",
+            extracted
+        );
+        assert!(
+            extracted.contains(
+                "viewReset : Model -> Html ResetMsg\nviewReset model =\n    H.div [ H.onClick ResetReset ] [ H.text model.name ]\n\n\ntype ResetMsg\n    = ResetReset\n\n\nfromResetMsg : ResetMsg -> Msg\nfromResetMsg msg =\n    case msg of\n        ResetReset ->\n            Reset\n"
+            ),
+            "{}",
+            extracted
+        );
+
+        // `viewRename` builds messages of the page itself
+        let rename = Range::new(Position::new(25, 10), Position::new(25, 39));
+        let result = workspace
+            .extract_view_helper(&uri, rename, "viewField", true)
+            .unwrap();
+        assert!(!result.success, "{}", result.message);
+        assert!(
+            workspace
+                .extract_view_helper(&uri, rename, "viewField", false)
+                .unwrap()
+                .success
+        );
+
+        // Not view code
+        let field = Range::new(Position::new(17, 45), Position::new(17, 55));
+        assert!(
+            !workspace
+                .extract_view_helper(&uri, field, "viewField", false)
+                .unwrap()
+                .success
+        );
+    }
+
+    #[test]
+    fn test_type_variable_references_stay_in_declaration() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+        let main = "module Main exposing (..)\n\n\ntype Tree a\n    = Leaf a\n    | Node (Tree a) (Tree a)\n\n\nmap : (a -> b) -> Tree a -> Tree b\nmap f tree =\n    let\n        go : Tree a -> Tree b\n        go t =\n            map f t\n    in\n    go tree\n\n\nfirst : List a -> Maybe a\nfirst list =\n    List.head list\n";
+        fs::write(src_dir.join("Main.elm"), main).unwrap();
+        workspace.initialize().unwrap();
+        let uri = Url::from_file_path(src_dir.join("Main.elm")).unwrap();
+        let lines = |position: Position| {
+            let mut lines: Vec<u32> = workspace
+                .type_variable_references(&uri, position)
+                .unwrap()
+                .iter()
+                .map(|location| location.range.start.line)
+                .collect();
+            lines.sort();
+            lines
+        };
+
+        // The function's annotation shares its variables with the let annotation
+        assert_eq!(lines(Position::new(8, 7)), [8, 8, 11]);
+        assert_eq!(lines(Position::new(11, 18)), [8, 8, 11]);
+        assert_eq!(lines(Position::new(3, 10)), [3, 4, 5, 5]);
+        assert_eq!(
+            workspace.type_variable_at(&uri, Position::new(18, 13)),
+            Some((
+                "a".to_string(),
+                Range::new(Position::new(18, 13), Position::new(18, 14))
+            ))
+        );
+        assert!(workspace
+            .type_variable_at(&uri, Position::new(8, 0))
+            .is_none());
+
+        let edits = workspace
+            .rename_type_variable(&uri, Position::new(18, 13), "item")
+            .unwrap()
+            .unwrap();
+        assert_eq!(edits.len(), 2);
+        assert!(edits.iter().all(|e| e.range.start.line == 18));
+        assert!(workspace
+            .rename_type_variable(&uri, Position::new(8, 7), "b")
+            .is_err());
+        assert!(workspace
+            .rename_type_variable(&uri, Position::new(8, 7), "Item")
+            .is_err());
+    }
+
+    #[test]
+    fn test_import_edits_place_and_merge_imports() {
+        let parser = ElmParser::new();
+        let edited = |source: &str, specs: &[ImportSpec]| {
+            let tree = parser.parse(source).unwrap();
+            apply_edits(source, &import_edits(&tree, source, specs))
+        };
+
+        // No imports yet, and no blank line after the doc comment
+        let documented = "module Main exposing (main)\n{-| The app -}\nmain =\n    text \"\"\n";
+        assert_eq!(
+            edited(documented, &[ImportSpec::exposing("Html", &["text"])]),
+            "module Main exposing (main)\n{-| The app -}\n\nimport Html exposing (text)\nmain =\n    text \"\"\n"
+        );
+
+        let imported = "module Main exposing (main)\n\nimport Browser\nimport Html exposing (div)\nimport Task\n\n\nmain =\n    div [] []\n";
+        assert_eq!(
+            edited(
+                imported,
+                &[
+                    ImportSpec::exposing("Html", &["text"]),
+                    ImportSpec::parse("import Dict exposing (Dict)").unwrap(),
+                    ImportSpec::module("Json.Decode"),
+                    ImportSpec::module("Browser"),
+                ]
+            ),
+            "module Main exposing (main)\n\nimport Browser\nimport Dict exposing (Dict)\nimport Html exposing (div, text)\nimport Json.Decode\nimport Task\n\n\nmain =\n    div [] []\n"
+        );
+
+        // Importing the module itself or something already exposed is a no-op
+        let tree = parser.parse(imported).unwrap();
+        assert!(import_edits(
+            &tree,
+            imported,
+            &[
+                ImportSpec::module("Main"),
+                ImportSpec::exposing("Html", &["div"])
+            ]
+        )
+        .is_empty());
+        assert_eq!(
+            ImportSpec::parse("Html.Attributes as A exposing (class, Msg(..))"),
+            Some(ImportSpec {
+                module_name: "Html.Attributes".to_string(),
+                alias: Some("A".to_string()),
+                exposing: vec!["class".to_string(), "Msg(..)".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn test_move_function_carries_doc_comment() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+
+        let source = r#"module Source exposing (helper, other)
+
+
+other : Int
+other =
+    1
+
+
+{-| Doubles a number.
+-}
+helper : Int -> Int
+helper x =
+    x * 2
+"#;
+        let target = r#"module Target exposing (existing)
+
+import Dict
+
+
+{-| Already here.
+-}
+existing : Int
+existing =
+    0
+"#;
+        fs::write(src_dir.join("Source.elm"), source).unwrap();
+        fs::write(src_dir.join("Target.elm"), target).unwrap();
+        workspace.initialize().unwrap();
+
+        let source_uri = Url::from_file_path(src_dir.join("Source.elm")).unwrap();
+        let target_uri = Url::from_file_path(src_dir.join("Target.elm")).unwrap();
+        let result = workspace
+            .move_function(&source_uri, "helper", &src_dir.join("Target.elm"))
+            .unwrap();
+
+        let updated_source = apply_edits(source, &result.changes[&source_uri]);
+        assert!(!updated_source.contains("Doubles a number"));
+
+        let updated_target = apply_edits(target, &result.changes[&target_uri]);
+        assert!(updated_target.contains(
+            "{-| Doubles a number.\n-}\nhelper : Int -> Int\nhelper x =\n    x * 2\n\n\n{-| Already here.\n-}\nexisting : Int"
+        ));
+
+        drop(temp_dir);
+    }
+
+    #[test]
+    fn test_move_function_takes_whole_declaration_from_tree() {
+        let (temp_dir, mut workspace) = create_test_workspace();
+        let src_dir = temp_dir.path().join("src");
+
+        // A comment between annotation and body, a let block whose last line
+        // is less indented than its bindings, and the next declaration right below
+        let source = "module Source exposing (helper, other)\n\n\n-- Doubles a number.\nhelper : Int -> Int\n-- twice\nhelper x =\n    let\n        y =\n            x * 2\n    in\n    y\nother : Int\nother =\n    1\n";
+        let target = "module Target exposing (existing)\nimport Dict\n";
+        fs::write(src_dir.join("Source.elm"), source).unwrap();
+        fs::write(src_dir.join("Target.elm"), target).unwrap();
+        workspace.initialize().unwrap();
+
+        let source_uri = Url::from_file_path(src_dir.join("Source.elm")).unwrap();
+        let target_uri = Url::from_file_path(src_dir.join("Target.elm")).unwrap();
+        let result = workspace
+            .move_function(&source_uri, "helper", &src_dir.join("Target.elm"))
+            .unwrap();
+
+        let updated_source = apply_edits(source, &result.changes[&source_uri]);
+        assert!(!updated_source.contains("twice"));
+        assert!(!updated_source.contains("Doubles"));
+        assert!(updated_source.contains("other : Int\nother =\n    1\n"));
+
+        let updated_target = apply_edits(target, &result.changes[&target_uri]);
+        assert!(updated_target.contains(
+            "import Dict\n\n\n-- Doubles a number.\nhelper : Int -> Int\n-- twice\nhelper x =\n    let\n        y =\n            x * 2\n    in\n    y\n"
+        ));
+
+        drop(temp_dir);
+    }
 }
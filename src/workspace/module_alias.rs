@@ -0,0 +1,141 @@
+//! Renaming the alias of an import, like `M` in
+//! `import Long.Module.Name as M`.
+//!
+//! The alias only exists in the module that declares it, so the rename
+//! rewrites the `as` clause and the qualifier of every `M.name` there and
+//! leaves the aliased module alone. Imports sharing the alias are renamed
+//! together, since their qualified names cannot be told apart.
+
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::document::node_range;
+
+use super::Workspace;
+
+/// The occurrences of an alias in one module
+struct AliasUses {
+    name: String,
+    /// The occurrence at the position asked about
+    at: Range,
+    /// The `as` clauses declaring it and the qualifiers using it
+    ranges: Vec<Range>,
+}
+
+impl Workspace {
+    /// The import alias at `position`, in an `as` clause or as a qualifier,
+    /// and its range there
+    pub fn module_alias_at(&self, uri: &Url, position: Position) -> Option<(String, Range)> {
+        let uses = self.alias_uses(uri, position)?;
+        Some((uses.name, uses.at))
+    }
+
+    /// Rename the import alias at `position` in its module; Ok(None) when
+    /// there is no alias there
+    pub fn rename_module_alias(
+        &self,
+        uri: &Url,
+        position: Position,
+        new_name: &str,
+    ) -> anyhow::Result<Option<Vec<TextEdit>>> {
+        let Some(uses) = self.alias_uses(uri, position) else {
+            return Ok(None);
+        };
+        let mut chars = new_name.chars();
+        let valid = chars.next().is_some_and(|c| c.is_ascii_uppercase())
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            anyhow::bail!("'{}' is not a valid module alias", new_name);
+        }
+        if new_name != uses.name {
+            let module = self.get_module_at_uri(uri);
+            let taken = module
+                .into_iter()
+                .flat_map(|m| &m.imports)
+                .any(|import| import.alias.as_deref().unwrap_or(&import.module_name) == new_name);
+            if taken {
+                anyhow::bail!(
+                    "Another import is already referred to as '{}' in this module",
+                    new_name
+                );
+            }
+        }
+        let edits = uses
+            .ranges
+            .into_iter()
+            .map(|range| TextEdit {
+                range,
+                new_text: new_name.to_string(),
+            })
+            .collect();
+        Ok(Some(edits))
+    }
+
+    fn alias_uses(&self, uri: &Url, position: Position) -> Option<AliasUses> {
+        let source = self.read_file_content(uri)?;
+        let tree = self.parser.parse(&source)?;
+        let root = tree.root_node();
+        let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+        let node = root.descendant_for_point_range(point, point)?;
+        if node.kind() != "upper_case_identifier" {
+            return None;
+        }
+        let name = &source[node.byte_range()];
+
+        let mut cursor = root.walk();
+        let declarations: Vec<Node> = root
+            .named_children(&mut cursor)
+            .filter(|n| n.kind() == "import_clause")
+            .filter_map(|import| {
+                import
+                    .child_by_field_name("asClause")?
+                    .child_by_field_name("name")
+            })
+            .filter(|alias| &source[alias.byte_range()] == name)
+            .collect();
+        if declarations.is_empty() {
+            return None;
+        }
+        let mut qualifiers = Vec::new();
+        collect_qualifiers(root, &source, name, &mut qualifiers);
+        if !declarations.contains(&node) && !qualifiers.contains(&node) {
+            return None;
+        }
+
+        Some(AliasUses {
+            name: name.to_string(),
+            at: node_range(node),
+            ranges: declarations
+                .iter()
+                .chain(&qualifiers)
+                .map(|n| node_range(*n))
+                .collect(),
+        })
+    }
+}
+
+/// The qualifiers that are just `alias`, as in `alias.name` or `alias.Type`
+fn collect_qualifiers<'a>(node: Node<'a>, source: &str, alias: &str, found: &mut Vec<Node<'a>>) {
+    match node.kind() {
+        // The imported module's own name is not a use of the alias
+        "import_clause" | "module_declaration" => return,
+        "upper_case_qid" | "value_qid" => {
+            let mut cursor = node.walk();
+            let segments: Vec<Node> = node
+                .named_children(&mut cursor)
+                .filter(|n| n.kind() != "dot")
+                .collect();
+            if let [qualifier, _] = segments.as_slice() {
+                if &source[qualifier.byte_range()] == alias {
+                    found.push(*qualifier);
+                }
+            }
+            return;
+        }
+        _ => {}
+    }
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_qualifiers(child, source, alias, found);
+    }
+}
@@ -0,0 +1,387 @@
+//! Module documentation: the `{-| ... -}` comment right after the header.
+//!
+//! Hovering a module name, in an import or as the qualifier of `Html.div`,
+//! shows that comment, with each `@docs` line turned into a list of the
+//! names it documents. On the module header, a code action makes the `@docs`
+//! lines cover exactly what the module exposes, for publishing a package.
+//!
+//! `elm publish` refuses a package whose exposed modules document something
+//! they do not expose, or expose something they do not document. In the
+//! exposed modules of a package that is reported as you type, with fixes
+//! adding or removing the `@docs` entry.
+
+use tower_lsp::lsp_types::*;
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::codes;
+use crate::analysis::QuickFix;
+use crate::document::node_range;
+
+use super::Workspace;
+
+impl Workspace {
+    /// Markdown for the module named at `position`, in an import or as a
+    /// qualifier, and the range of that name
+    pub fn module_hover(&self, uri: &Url, position: Position) -> Option<(String, Range)> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+        let node = tree.root_node().descendant_for_point_range(point, point)?;
+        let (module_name, range) = self.module_named_at(uri, node, source)?;
+
+        let path = self.module_uri(&module_name)?.to_file_path().ok()?;
+        let module_source = self.source(&path).ok()?;
+        let module_tree = self.parser.parse(&module_source)?;
+        let mut value = format!("```elm\nmodule {}\n```", module_name);
+        if let Some(package) = self.external_modules.get(&module_name) {
+            value.push_str(&format!("\n\n*{}*", package.package));
+        }
+        if let Some(doc) = module_doc(&module_tree, &module_source) {
+            value.push_str("\n\n");
+            value.push_str(&render_docs(&doc));
+        }
+        Some((value, range))
+    }
+
+    /// An edit making the module doc comment's `@docs` lines cover what the
+    /// module at `uri` exposes, when `position` is on its header. Creates
+    /// the doc comment when there is none.
+    pub fn docs_skeleton(&self, uri: &Url, position: Position) -> Option<TextEdit> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let header = tree.root_node().child_by_field_name("moduleDeclaration")?;
+        let header_range = node_range(header);
+        if position.line < header_range.start.line || position.line > header_range.end.line {
+            return None;
+        }
+
+        let (types, values) = exposed_names(tree, source);
+        docs_edit(tree, source, &types, &values)
+    }
+
+    /// Exposed names missing from `@docs` and documented names that are not
+    /// exposed, in the exposed modules of a package
+    pub fn docs_diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        self.docs_drift(uri)
+            .into_iter()
+            .map(|fix| fix.diagnostic)
+            .collect()
+    }
+
+    /// Fixes for the diagnostics of [`Self::docs_diagnostics`] in `range`
+    pub fn docs_fixes(&self, uri: &Url, range: Range) -> Vec<QuickFix> {
+        self.docs_drift(uri)
+            .into_iter()
+            .filter(|fix| {
+                let found = fix.diagnostic.range;
+                found.start <= range.end && range.start <= found.end
+            })
+            .collect()
+    }
+
+    fn docs_drift(&self, uri: &Url) -> Vec<QuickFix> {
+        let Some(exposed_modules) = &self.exposed_modules else {
+            return Vec::new();
+        };
+        let module_name = self.get_module_name_from_uri(uri);
+        if !exposed_modules.contains(&module_name) {
+            return Vec::new();
+        }
+        let (Some(tree), Some(source)) = (
+            self.type_checker.get_tree(uri.as_str()),
+            self.type_checker.get_source(uri.as_str()),
+        ) else {
+            return Vec::new();
+        };
+
+        let (types, values) = exposed_names(tree, source);
+        let entries = module_doc_comment(tree, source)
+            .map(|comment| docs_entries(comment, source))
+            .unwrap_or_default();
+        let documented: Vec<String> = entries.iter().map(|(name, _)| name.clone()).collect();
+
+        let mut fixes = Vec::new();
+        for name in types.iter().chain(&values) {
+            if documented.contains(name) {
+                continue;
+            }
+            // Add just this name, keeping every other entry as it is
+            let is_type = types.contains(name);
+            let added = |wanted: bool| if wanted { vec![name.clone()] } else { vec![] };
+            let mut with_name = documented.clone();
+            with_name.extend(added(!is_type));
+            let Some(edit) = docs_edit(tree, source, &added(is_type), &with_name) else {
+                continue;
+            };
+            fixes.push(QuickFix {
+                title: format!("Add {} to @docs", name),
+                diagnostic: codes::MISSING_FROM_DOCS.diagnostic(
+                    exposed_item_range(tree, source, name),
+                    DiagnosticSeverity::WARNING,
+                    format!("`{}` is exposed but not in any `@docs` line", name),
+                ),
+                edits: vec![edit],
+            });
+        }
+        for (name, range) in &entries {
+            if types.contains(name) || values.contains(name) {
+                continue;
+            }
+            let without: Vec<String> = documented.iter().filter(|d| *d != name).cloned().collect();
+            let Some(edit) = docs_edit(tree, source, &[], &without) else {
+                continue;
+            };
+            fixes.push(QuickFix {
+                title: format!("Remove {} from @docs", name),
+                diagnostic: codes::DOCS_NOT_EXPOSED.diagnostic(
+                    *range,
+                    DiagnosticSeverity::WARNING,
+                    format!("`{}` is in `@docs` but the module does not expose it", name),
+                ),
+                edits: vec![edit],
+            });
+        }
+        fixes.sort_by_key(|fix| fix.diagnostic.range.start);
+        fixes
+    }
+
+    /// The module an import names, or a qualifier resolved through the
+    /// imports of the module at `uri`
+    fn module_named_at(&self, uri: &Url, node: Node, source: &str) -> Option<(String, Range)> {
+        let parent = node.parent()?;
+        if let Some(import) =
+            std::iter::successors(Some(node), |n| n.parent()).find(|n| n.kind() == "import_clause")
+        {
+            let module_node = import.child_by_field_name("moduleName")?;
+            if !module_node.byte_range().contains(&node.start_byte()) {
+                return None;
+            }
+            return Some((
+                source[module_node.byte_range()].to_string(),
+                node_range(module_node),
+            ));
+        }
+
+        // A qualifier is every segment of a qid but the last
+        if !matches!(parent.kind(), "value_qid" | "upper_case_qid")
+            || node.kind() != "upper_case_identifier"
+            || parent
+                .named_child(parent.named_child_count().checked_sub(1)?)?
+                .id()
+                == node.id()
+        {
+            return None;
+        }
+        let (qualifier, _) = source[parent.byte_range()].rsplit_once('.')?;
+        let module = self.find_module_by_path(&uri.to_file_path().ok()?)?;
+        let module_name = module
+            .imports
+            .iter()
+            .find(|import| match &import.alias {
+                Some(alias) => alias == qualifier,
+                None => import.module_name == qualifier,
+            })
+            .map(|import| import.module_name.clone())?;
+        let start = node_range(parent).start;
+        let end = Position::new(start.line, start.character + qualifier.len() as u32);
+        Some((module_name, Range::new(start, end)))
+    }
+}
+
+/// The `{-| ... -}` comment right after the module header
+pub(super) fn module_doc_comment<'a>(tree: &'a Tree, source: &str) -> Option<Node<'a>> {
+    let header = tree.root_node().child_by_field_name("moduleDeclaration")?;
+    header.next_sibling().filter(|comment| {
+        comment.kind() == "block_comment" && source[comment.byte_range()].starts_with("{-|")
+    })
+}
+
+/// The text of a module's doc comment, without `{-|` and `-}`
+fn module_doc(tree: &Tree, source: &str) -> Option<String> {
+    let comment = module_doc_comment(tree, source)?;
+    let text = &source[comment.byte_range()];
+    let doc = text.strip_prefix("{-|")?.strip_suffix("-}")?;
+    Some(doc.trim().to_string())
+}
+
+/// What a module exposes, in order: types first, then values
+pub(super) fn exposed_names(tree: &Tree, source: &str) -> (Vec<String>, Vec<String>) {
+    let root = tree.root_node();
+    let mut types = Vec::new();
+    let mut values = Vec::new();
+    let Some(exposing) = root
+        .child_by_field_name("moduleDeclaration")
+        .and_then(|m| m.child_by_field_name("exposing"))
+    else {
+        return (types, values);
+    };
+
+    let mut cursor = exposing.walk();
+    let items: Vec<Node> = exposing.named_children(&mut cursor).collect();
+    if items.iter().any(|item| item.kind() == "double_dot") {
+        let mut cursor = root.walk();
+        for declaration in root.named_children(&mut cursor) {
+            let name = match declaration.kind() {
+                "type_declaration" | "type_alias_declaration" | "port_annotation" => {
+                    declaration.child_by_field_name("name")
+                }
+                "value_declaration" => declaration
+                    .child_by_field_name("functionDeclarationLeft")
+                    .and_then(|left| left.named_child(0)),
+                _ => None,
+            };
+            let Some(name) = name.map(|n| source[n.byte_range()].to_string()) else {
+                continue;
+            };
+            match declaration.kind() {
+                "type_declaration" | "type_alias_declaration" => types.push(name),
+                _ => values.push(name),
+            }
+        }
+        return (types, values);
+    }
+
+    for item in items {
+        let text: String = source[item.byte_range()].split_whitespace().collect();
+        match item.kind() {
+            "exposed_type" => types.push(text.trim_end_matches("(..)").to_string()),
+            "exposed_value" | "exposed_operator" => values.push(text),
+            _ => {}
+        }
+    }
+    (types, values)
+}
+
+/// `doc` with `@docs` lines naming exactly `types` and `values`: names no
+/// longer exposed are dropped and missing ones added in new sections, while
+/// the prose is kept
+fn updated_docs(doc: &str, types: &[String], values: &[String]) -> String {
+    let exposed = |name: &String| types.contains(name) || values.contains(name);
+    let mut lines: Vec<String> = Vec::new();
+    for line in doc.lines() {
+        if line.trim_start().starts_with("@docs") {
+            let kept: Vec<String> = docs_names(line).into_iter().filter(exposed).collect();
+            if !kept.is_empty() {
+                lines.push(format!("@docs {}", kept.join(", ")));
+            }
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+    let mut text = lines.join("\n").trim_end().to_string();
+
+    let documented = docs_names(doc);
+    for (heading, names) in [("Types", types), ("Functions", values)] {
+        let missing: Vec<&str> = names
+            .iter()
+            .filter(|name| !documented.contains(name))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            text.push_str(&format!(
+                "\n\n\n# {}\n\n@docs {}",
+                heading,
+                missing.join(", ")
+            ));
+        }
+    }
+    text
+}
+
+/// Each name of the `@docs` lines of `comment`, with its range
+fn docs_entries(comment: Node, source: &str) -> Vec<(String, Range)> {
+    let start = comment.start_position();
+    let mut entries = Vec::new();
+    for (index, line) in source[comment.byte_range()].lines().enumerate() {
+        if !line.trim_start().starts_with("@docs") {
+            continue;
+        }
+        let row = (start.row + index) as u32;
+        let first_column = if index == 0 { start.column } else { 0 };
+        let mut offset = line.find("@docs").unwrap_or(0) + "@docs".len();
+        for part in line[offset..].split(',') {
+            let name = part.trim();
+            if let Some(at) = part.find(name).filter(|_| !name.is_empty()) {
+                let column = (first_column + offset + at) as u32;
+                entries.push((
+                    name.to_string(),
+                    Range::new(
+                        Position::new(row, column),
+                        Position::new(row, column + name.len() as u32),
+                    ),
+                ));
+            }
+            offset += part.len() + 1;
+        }
+    }
+    entries
+}
+
+/// Where the module header exposes `name`: its item, or `(..)`
+fn exposed_item_range(tree: &Tree, source: &str, name: &str) -> Range {
+    let header = tree.root_node().child_by_field_name("moduleDeclaration");
+    let Some(exposing) = header.and_then(|h| h.child_by_field_name("exposing")) else {
+        return header.map(node_range).unwrap_or_default();
+    };
+    let mut cursor = exposing.walk();
+    let item = exposing.named_children(&mut cursor).find(|item| {
+        let text: String = source[item.byte_range()].split_whitespace().collect();
+        item.kind() == "double_dot" || text.trim_end_matches("(..)") == name
+    });
+    node_range(item.unwrap_or(exposing))
+}
+
+/// An edit to the module doc comment giving it `@docs` lines for exactly
+/// `types` and `values`, or creating the comment when there is none
+fn docs_edit(tree: &Tree, source: &str, types: &[String], values: &[String]) -> Option<TextEdit> {
+    let header = tree.root_node().child_by_field_name("moduleDeclaration")?;
+    let (range, doc) = match module_doc_comment(tree, source) {
+        Some(comment) => (node_range(comment), module_doc(tree, source)?),
+        None => {
+            let end = node_range(header).end;
+            (Range::new(end, end), String::new())
+        }
+    };
+    let updated = updated_docs(&doc, types, values);
+    if updated == doc {
+        return None;
+    }
+    // Without prose the comment starts with the first section
+    let sections = updated.trim_start();
+    let text = if sections.starts_with('#') || sections.starts_with("@docs") {
+        format!("{{-|\n\n{}\n\n-}}", sections)
+    } else {
+        format!("{{-| {}\n\n-}}", sections)
+    };
+    let new_text = if range.start == range.end {
+        format!("\n\n{}", text)
+    } else {
+        text
+    };
+    Some(TextEdit { range, new_text })
+}
+
+/// The names listed by `@docs` lines, in order
+fn docs_names(doc: &str) -> Vec<String> {
+    doc.lines()
+        .filter_map(|line| line.trim_start().strip_prefix("@docs"))
+        .flat_map(|names| names.split(','))
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// `@docs a, b` becomes `` `a`, `b` ``
+fn render_docs(doc: &str) -> String {
+    doc.lines()
+        .map(|line| match line.trim_start().strip_prefix("@docs") {
+            Some(_) => docs_names(line)
+                .iter()
+                .map(|name| format!("`{}`", name))
+                .collect::<Vec<_>>()
+                .join(", "),
+            None => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,121 @@
+//! A module header naming another module than the file's path does.
+//!
+//! `src/Pages/Home.elm` must declare `module Pages.Home`, or nothing can
+//! import it. The mismatch is reported on the module name, with a fix
+//! rewriting the declaration; the server also offers to move the file where
+//! its declared name says it belongs, through `elm.moveFile`.
+
+use std::path::{Path, PathBuf};
+
+use tower_lsp::lsp_types::*;
+
+use crate::analysis::codes;
+use crate::analysis::QuickFix;
+use crate::document::node_range;
+
+use super::Workspace;
+
+/// The module name a file declares, where, and the one its path calls for
+struct Mismatch {
+    range: Range,
+    declared: String,
+    expected: String,
+}
+
+impl Workspace {
+    /// Report a module name that does not match the file's path
+    pub fn module_path_diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        self.module_path_mismatch(uri)
+            .map(|mismatch| mismatch_diagnostic(&mismatch))
+            .into_iter()
+            .collect()
+    }
+
+    /// The fix for the diagnostic of [`Self::module_path_diagnostics`] in
+    /// `range`: the declaration rewritten to the name the path calls for,
+    /// unless the path names no module, like `src/pages/home.elm`
+    pub fn module_path_fixes(&self, uri: &Url, range: Range) -> Vec<QuickFix> {
+        let Some(mismatch) = self.module_path_mismatch(uri) else {
+            return Vec::new();
+        };
+        let found = mismatch.range;
+        if found.end < range.start || range.end < found.start || !is_module_name(&mismatch.expected)
+        {
+            return Vec::new();
+        }
+        vec![QuickFix {
+            title: format!("Rename module to {}", mismatch.expected),
+            diagnostic: mismatch_diagnostic(&mismatch),
+            edits: vec![TextEdit {
+                range: mismatch.range,
+                new_text: mismatch.expected,
+            }],
+        }]
+    }
+
+    /// Where the file at `uri` belongs for the module name it declares, in
+    /// its source directory. None when the name matches the path, or when
+    /// another file is there already.
+    pub fn module_path_target(&self, uri: &Url) -> Option<PathBuf> {
+        let mismatch = self.module_path_mismatch(uri)?;
+        let path = uri.to_file_path().ok()?;
+        let dir = self.source_dir_of(&path)?;
+        let target = mismatch
+            .declared
+            .split('.')
+            .fold(dir.to_path_buf(), |target, segment| target.join(segment))
+            .with_extension("elm");
+        (!target.exists()).then_some(target)
+    }
+
+    fn module_path_mismatch(&self, uri: &Url) -> Option<Mismatch> {
+        let path = uri.to_file_path().ok()?;
+        self.source_dir_of(&path)?;
+        // Generated modules are not for editing
+        if self.find_module_by_path(&path).is_some_and(|m| m.generated) {
+            return None;
+        }
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let name = tree
+            .root_node()
+            .child_by_field_name("moduleDeclaration")?
+            .child_by_field_name("name")?;
+        let declared = &source[name.byte_range()];
+        let expected = self.path_to_module_name(&path);
+        (declared != expected).then(|| Mismatch {
+            range: node_range(name),
+            declared: declared.to_string(),
+            expected,
+        })
+    }
+
+    /// The source or test directory `path` is in
+    fn source_dir_of(&self, path: &Path) -> Option<&Path> {
+        self.source_dirs
+            .iter()
+            .chain(&self.test_dirs)
+            .find(|dir| path.starts_with(dir))
+            .map(PathBuf::as_path)
+    }
+}
+
+fn mismatch_diagnostic(mismatch: &Mismatch) -> Diagnostic {
+    codes::MODULE_NAME_MISMATCH.diagnostic(
+        mismatch.range,
+        DiagnosticSeverity::ERROR,
+        format!(
+            "The module is named `{}`, but its path makes it `{}`",
+            mismatch.declared, mismatch.expected
+        ),
+    )
+}
+
+/// Whether `name` is a valid module name: capitalized segments of letters,
+/// digits and underscores
+fn is_module_name(name: &str) -> bool {
+    name.split('.').all(|segment| {
+        segment.starts_with(|c: char| c.is_ascii_uppercase())
+            && segment.chars().all(|c| c.is_alphanumeric() || c == '_')
+    })
+}
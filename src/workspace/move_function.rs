@@ -7,6 +7,10 @@ use std::collections::HashMap;
 use std::path::Path;
 use tower_lsp::lsp_types::*;
 
+use super::declarations::{
+    after_imports_line, declaration_extent, first_declaration_line, value_declaration,
+};
+use super::import_edit::ImportSpec;
 use super::{MoveResult, Workspace, LAMDERA_PROTECTED_TYPES};
 
 impl Workspace {
@@ -64,6 +68,8 @@ impl Workspace {
         let source_path = source_uri
             .to_file_path()
             .map_err(|_| anyhow::anyhow!("Invalid source URI"))?;
+        self.ensure_not_generated(&source_path)?;
+        self.ensure_not_generated(target_path)?;
 
         // Find source module
         let source_module = self
@@ -89,31 +95,44 @@ impl Workspace {
         }
 
         // Find the function in source module
-        let function = source_module
+        if !source_module
             .symbols
             .iter()
-            .find(|s| s.name == function_name && s.kind == SymbolKind::FUNCTION)
-            .ok_or_else(|| anyhow::anyhow!("Function not found in source module"))?;
+            .any(|s| s.name == function_name && s.kind == SymbolKind::FUNCTION)
+        {
+            return Err(anyhow::anyhow!("Function not found in source module"));
+        }
 
-        // Read source file content
-        let source_content = std::fs::read_to_string(&source_path)?;
+        // Read and parse source file content
+        let source_content = self.source(&source_path)?;
         let source_lines: Vec<&str> = source_content.lines().collect();
+        let source_tree = self
+            .parser
+            .parse(&source_content)
+            .ok_or_else(|| anyhow::anyhow!("Could not parse {}", source_path.display()))?;
 
-        // Extract function definition (type signature + body)
-        let (func_start_line, func_end_line) = find_function_bounds(
-            &source_content,
-            function_name,
-            function.range.start.line as usize,
-        );
+        // The declaration with its annotation and attached comments, from the syntax tree
+        let declaration = value_declaration(&source_tree, &source_content, function_name)
+            .ok_or_else(|| anyhow::anyhow!("Function not found in source module"))?;
+        let extent = declaration_extent(declaration, &source_content);
+        let func_start_line = extent.start.line as usize;
+        let func_end_line = extent.end.line as usize;
 
         // Get the function text (including type signature if present)
         let function_text: String = source_lines[func_start_line..=func_end_line].join("\n");
 
-        // Read target file content
-        let target_content = std::fs::read_to_string(target_path)?;
+        // Read and parse target file content
+        let target_content = self.source(target_path)?;
+        let target_tree = self
+            .parser
+            .parse(&target_content)
+            .ok_or_else(|| anyhow::anyhow!("Could not parse {}", target_path.display()))?;
 
-        // Find insertion point in target (after imports, before first definition)
-        let target_insert_line = find_insertion_point(&target_content);
+        // Insert before the first declaration and the comments attached to it,
+        // or after the imports if the target has no declarations yet
+        let first_declaration_line = first_declaration_line(&target_tree, &target_content);
+        let target_insert_line = first_declaration_line
+            .unwrap_or_else(|| after_imports_line(&target_tree, &target_content));
 
         // Create target URI
         let target_uri =
@@ -143,24 +162,10 @@ impl Workspace {
         });
 
         // 2. Add import for the moved function in source file (so existing local usages still work)
-        let import_text = format!(
-            "import {} exposing ({})\n",
-            target_module_name, function_name
-        );
-        let source_import_line = find_import_insertion_point(&source_content);
-        source_edits.push(TextEdit {
-            range: Range {
-                start: Position {
-                    line: source_import_line as u32,
-                    character: 0,
-                },
-                end: Position {
-                    line: source_import_line as u32,
-                    character: 0,
-                },
-            },
-            new_text: import_text,
-        });
+        source_edits.extend(self.import_edits(
+            source_uri,
+            &[ImportSpec::exposing(&target_module_name, &[function_name])],
+        ));
 
         // 2b. Remove function from source file's exposing list
         if let Some(unexpose_edit) = create_unexpose_edit(&source_content, function_name) {
@@ -168,15 +173,19 @@ impl Workspace {
         }
 
         // 3. Add function to target file
-        let target_text = format!("\n\n{}\n", function_text);
+        let target_text = if first_declaration_line.is_some() {
+            format!("{}\n\n\n", function_text)
+        } else {
+            format!("\n\n{}\n", function_text)
+        };
         target_edits.push(TextEdit {
             range: Range {
                 start: Position {
-                    line: target_insert_line as u32,
+                    line: target_insert_line,
                     character: 0,
                 },
                 end: Position {
-                    line: target_insert_line as u32,
+                    line: target_insert_line,
                     character: 0,
                 },
             },
@@ -189,8 +198,7 @@ impl Workspace {
         }
 
         // 4b. Add import for source module types/functions used in the moved function
-        let target_module_ref = self.find_module_by_path(target_path);
-        let source_symbols: Vec<String> = source_module
+        let source_symbols: Vec<&str> = source_module
             .symbols
             .iter()
             .filter(|s| {
@@ -198,41 +206,15 @@ impl Workspace {
                 // Skip the function we're moving itself
                 s.name != function_name && is_symbol_used_in_text(&function_text, &s.name)
             })
-            .map(|s| s.name.clone())
+            .map(|s| s.name.as_str())
             .collect();
 
         if !source_symbols.is_empty() {
-            // Check if target already imports source module
-            let already_imports_source = target_module_ref
-                .map(|tm| {
-                    tm.imports
-                        .iter()
-                        .any(|i| i.module_name == source_module_name)
-                })
-                .unwrap_or(false);
-
-            if !already_imports_source {
-                // Add import for the source module with the needed symbols
-                let import_line = find_import_insertion_point(&target_content);
-                let import_text = format!(
-                    "import {} exposing ({})\n",
-                    source_module_name,
-                    source_symbols.join(", ")
-                );
-                target_edits.push(TextEdit {
-                    range: Range {
-                        start: Position {
-                            line: import_line as u32,
-                            character: 0,
-                        },
-                        end: Position {
-                            line: import_line as u32,
-                            character: 0,
-                        },
-                    },
-                    new_text: import_text,
-                });
-            }
+            // Merges with an existing import of the source module
+            target_edits.extend(self.import_edits(
+                &target_uri,
+                &[ImportSpec::exposing(&source_module_name, &source_symbols)],
+            ));
         }
 
         // 5. Update references in other files to use qualified name
@@ -267,28 +249,13 @@ impl Workspace {
                         });
                 } else {
                     // Need to add import and potentially qualify the reference
-                    let ref_content = std::fs::read_to_string(&ref_path)?;
-                    let import_line = find_import_insertion_point(&ref_content);
-
                     reference_edits
                         .entry(r.uri.clone())
                         .or_default()
-                        .push(TextEdit {
-                            range: Range {
-                                start: Position {
-                                    line: import_line as u32,
-                                    character: 0,
-                                },
-                                end: Position {
-                                    line: import_line as u32,
-                                    character: 0,
-                                },
-                            },
-                            new_text: format!(
-                                "import {} exposing ({})\n",
-                                target_module_name, function_name
-                            ),
-                        });
+                        .extend(self.import_edits(
+                            &r.uri,
+                            &[ImportSpec::exposing(&target_module_name, &[function_name])],
+                        ));
                 }
             }
         }
@@ -311,112 +278,6 @@ impl Workspace {
     }
 }
 
-/// Find the start and end lines of a function definition
-fn find_function_bounds(content: &str, name: &str, approx_line: usize) -> (usize, usize) {
-    let lines: Vec<&str> = content.lines().collect();
-    let mut start_line = approx_line;
-    let mut end_line = approx_line;
-
-    // Look backwards for type signature
-    if start_line > 0 {
-        for i in (0..start_line).rev() {
-            let line = lines[i].trim();
-            if line.is_empty() {
-                break;
-            }
-            // Check if this is a type signature for our function
-            if line.starts_with(&format!("{} :", name)) {
-                start_line = i;
-                break;
-            }
-            // If we hit another definition, stop
-            if line.contains(" =") && !line.starts_with(&format!("{} ", name)) {
-                break;
-            }
-        }
-    }
-
-    // Look forwards for end of function
-    let mut indent_level = None;
-    for i in approx_line..lines.len() {
-        let line = lines[i];
-
-        if line.is_empty() {
-            // Empty line might be end of function
-            if i > approx_line {
-                // Check if next non-empty line is a new definition
-                for next_line in lines[(i + 1)..].iter().map(|l| l.trim()) {
-                    if next_line.is_empty() {
-                        continue;
-                    }
-                    // If next non-empty line is a top-level definition, we're done
-                    if !next_line.starts_with(' ') && !next_line.starts_with('\t') {
-                        end_line = i - 1;
-                        return (start_line, end_line);
-                    }
-                    break;
-                }
-            }
-            continue;
-        }
-
-        // Track indentation to find end of function
-        let trimmed = line.trim_start();
-        let current_indent = line.len() - trimmed.len();
-
-        if indent_level.is_none() && !line.is_empty() && line.contains('=') {
-            // Found the function definition line, track its indent
-            indent_level = Some(current_indent);
-        }
-
-        if let Some(base_indent) = indent_level {
-            // If we hit a line with same or less indentation that's not empty
-            // and it looks like a new definition, stop
-            if current_indent <= base_indent && i > approx_line {
-                let trimmed = line.trim();
-                let is_elm_keyword = trimmed.starts_with("else")
-                    || trimmed.starts_with("then")
-                    || trimmed.starts_with("in ")
-                    || trimmed == "in"
-                    || trimmed.starts_with("of")
-                    || trimmed.starts_with("let")
-                    || trimmed.starts_with("case")
-                    || trimmed.starts_with("if ")
-                    || trimmed.starts_with("->")
-                    || trimmed.starts_with("|")
-                    || trimmed.starts_with(",")
-                    || trimmed.starts_with("}")
-                    || trimmed.starts_with("]")
-                    || trimmed.starts_with(")");
-                if !is_elm_keyword
-                    && (trimmed
-                        .chars()
-                        .next()
-                        .map(|c| c.is_lowercase())
-                        .unwrap_or(false)
-                        || trimmed.starts_with("type ")
-                        || trimmed.starts_with("port "))
-                {
-                    end_line = i - 1;
-                    while end_line > start_line && lines[end_line].trim().is_empty() {
-                        end_line -= 1;
-                    }
-                    return (start_line, end_line);
-                }
-            }
-        }
-
-        end_line = i;
-    }
-
-    // Trim trailing empty lines
-    while end_line > start_line && lines[end_line].trim().is_empty() {
-        end_line -= 1;
-    }
-
-    (start_line, end_line)
-}
-
 /// Check if a symbol name is used in a piece of text (as a word boundary)
 fn is_symbol_used_in_text(text: &str, symbol_name: &str) -> bool {
     // Use word boundary matching to avoid false positives
@@ -429,54 +290,6 @@ fn is_symbol_used_in_text(text: &str, symbol_name: &str) -> bool {
     false
 }
 
-/// Find where to insert a new function in a file (after imports)
-fn find_insertion_point(content: &str) -> usize {
-    let lines: Vec<&str> = content.lines().collect();
-    let mut last_import_line = 0;
-
-    for (i, line) in lines.iter().enumerate() {
-        let trimmed = line.trim();
-        if trimmed.starts_with("import ") {
-            last_import_line = i;
-        } else if trimmed.starts_with("type ")
-            || trimmed.starts_with("port ")
-            || (trimmed
-                .chars()
-                .next()
-                .map(|c| c.is_lowercase())
-                .unwrap_or(false)
-                && trimmed.contains('='))
-        {
-            // Found first definition after imports
-            return i;
-        }
-    }
-
-    // Return line after last import
-    last_import_line + 2
-}
-
-/// Find where to insert a new import
-fn find_import_insertion_point(content: &str) -> usize {
-    let lines: Vec<&str> = content.lines().collect();
-
-    for (i, line) in lines.iter().enumerate() {
-        let trimmed = line.trim();
-        if trimmed.starts_with("import ") {
-            return i; // Insert before first import
-        }
-    }
-
-    // If no imports, insert after module declaration
-    for (i, line) in lines.iter().enumerate() {
-        if line.trim().starts_with("module ") {
-            return i + 2; // Skip module line and empty line
-        }
-    }
-
-    2 // Default to line 3
-}
-
 /// Create an edit to remove a function from the module's exposing list
 fn create_unexpose_edit(content: &str, function_name: &str) -> Option<TextEdit> {
     let lines: Vec<&str> = content.lines().collect();
@@ -0,0 +1,333 @@
+//! Flattening nested `case` expressions into one match on a tuple, and
+//! splitting such a match back into nested cases.
+//!
+//! ```elm
+//! case x of            case ( x, y ) of
+//!     A ->                 ( A, B ) ->
+//!         case y of            one
+//!             B ->
+//!                 one      ( C, _ ) ->
+//!                              two
+//!     C ->
+//!         two
+//! ```
+//!
+//! Both scrutinees have to be plain values or field accesses, so evaluating
+//! them together changes nothing. Branch bodies are kept as written and only
+//! re-indented. Cases with comments between their branches are left alone,
+//! as there is no good place to keep them.
+
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::document::node_range;
+
+use super::Workspace;
+
+/// One branch of the flat match: its two patterns and its body
+struct Row<'a> {
+    first: String,
+    second: String,
+    body: Node<'a>,
+}
+
+impl Workspace {
+    /// An edit turning the `case` at `position`, whose branches match the
+    /// same value again in nested cases, into one match on a tuple
+    pub fn flatten_case(&self, uri: &Url, position: Position) -> Option<TextEdit> {
+        let source = self.read_file_content(uri)?;
+        let tree = self.parser.parse(&source)?;
+        let case = case_at(tree.root_node(), position)?;
+        // The position may be on a nested case as well as on the outer one
+        let outer = std::iter::successors(Some(case), |n| n.parent())
+            .filter(|n| n.kind() == "case_of_expr")
+            .take(2)
+            .find(|n| flat_rows(*n, &source).is_some())?;
+        let (x, y, rows) = flat_rows(outer, &source)?;
+        let scrutinee = format!("( {}, {} )", x, y);
+        Some(rewrite(outer, &source, &scrutinee, &rows))
+    }
+
+    /// An edit turning the match on a pair at `position` into a `case` on
+    /// the first value with nested cases on the second
+    pub fn split_case(&self, uri: &Url, position: Position) -> Option<TextEdit> {
+        let source = self.read_file_content(uri)?;
+        let tree = self.parser.parse(&source)?;
+        let case = case_at(tree.root_node(), position)?;
+        if has_comments(case) {
+            return None;
+        }
+        let scrutinee = case.child_by_field_name("expr")?;
+        let [x, y] = tuple_parts(scrutinee, "tuple_expr")?;
+        if !is_simple(x) || !is_simple(y) {
+            return None;
+        }
+
+        let mut cursor = case.walk();
+        let mut groups: Vec<(String, Vec<Row>)> = Vec::new();
+        let mut heads = Vec::new();
+        for branch in case.children_by_field_name("branch", &mut cursor) {
+            if has_comments(branch) {
+                return None;
+            }
+            let pattern = branch.child_by_field_name("pattern")?;
+            let [first, second] = tuple_parts(unwrap_pattern(pattern), "tuple_pattern")?;
+            let row = Row {
+                first: pattern_text(first, &source),
+                second: pattern_text(second, &source),
+                body: branch.child_by_field_name("expr")?,
+            };
+            match groups.last_mut() {
+                Some((text, rows)) if *text == row.first => rows.push(row),
+                _ => {
+                    // Each group becomes one outer branch, so the first
+                    // patterns must not overlap: no catch-alls and no head
+                    // seen twice
+                    let head = pattern_head(unwrap_pattern(first), &source)?;
+                    if heads.contains(&head) {
+                        return None;
+                    }
+                    heads.push(head);
+                    groups.push((row.first.clone(), vec![row]));
+                }
+            }
+        }
+
+        let indent = line_indent(&source, case);
+        let mut text = format!("case {} of", &source[x.byte_range()]);
+        for (i, (head, rows)) in groups.iter().enumerate() {
+            if i > 0 {
+                text.push('\n');
+            }
+            text.push_str(&format!("\n{}{} ->\n", spaces(indent + 4), head));
+            if let [row] = rows.as_slice() {
+                if row.second == "_" {
+                    text.push_str(&spaces(indent + 8));
+                    text.push_str(&reindent(row.body, &source, indent + 8));
+                    continue;
+                }
+            }
+            text.push_str(&format!(
+                "{}case {} of",
+                spaces(indent + 8),
+                &source[y.byte_range()]
+            ));
+            for (j, row) in rows.iter().enumerate() {
+                if j > 0 {
+                    text.push('\n');
+                }
+                text.push_str(&format!(
+                    "\n{}{} ->\n{}{}",
+                    spaces(indent + 12),
+                    row.second,
+                    spaces(indent + 16),
+                    reindent(row.body, &source, indent + 16)
+                ));
+            }
+        }
+        Some(TextEdit {
+            range: node_range(case),
+            new_text: text,
+        })
+    }
+}
+
+/// The `case` whose `case ... of` header contains `position`
+fn case_at(root: Node, position: Position) -> Option<Node> {
+    let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+    let node = root.descendant_for_point_range(point, point)?;
+    std::iter::successors(Some(node), |n| n.parent())
+        .filter(|n| n.kind() == "case_of_expr")
+        .find(|case| {
+            let mut cursor = case.walk();
+            let of = case.children(&mut cursor).find(|c| c.kind() == "of");
+            of.is_some_and(|of| point <= of.end_position())
+        })
+}
+
+/// The scrutinees and rows of `case`, when its branches match one value
+/// again in nested cases
+fn flat_rows<'a>(case: Node<'a>, source: &'a str) -> Option<(&'a str, &'a str, Vec<Row<'a>>)> {
+    let x = case.child_by_field_name("expr")?;
+    if !is_simple(x) || has_comments(case) {
+        return None;
+    }
+    let mut y: Option<Node> = None;
+    let mut bound = Vec::new();
+    let mut rows = Vec::new();
+    let mut cursor = case.walk();
+    for branch in case.children_by_field_name("branch", &mut cursor) {
+        let pattern = branch.child_by_field_name("pattern")?;
+        let body = branch.child_by_field_name("expr")?;
+        if has_comments(branch) {
+            return None;
+        }
+        collect_identifiers(pattern, source, &mut bound);
+        let first = pattern_text(pattern, source);
+        let inner = Some(body)
+            .filter(|body| body.kind() == "case_of_expr")
+            .and_then(|body| Some((body, body.child_by_field_name("expr")?)))
+            .filter(|(_, scrutinee)| is_simple(*scrutinee));
+        let Some((inner, scrutinee)) = inner else {
+            rows.push(Row {
+                first,
+                second: "_".to_string(),
+                body,
+            });
+            continue;
+        };
+        match y {
+            Some(y) if source[y.byte_range()] != source[scrutinee.byte_range()] => return None,
+            _ => y = Some(scrutinee),
+        }
+        if has_comments(inner) {
+            return None;
+        }
+        let mut inner_cursor = inner.walk();
+        for inner_branch in inner.children_by_field_name("branch", &mut inner_cursor) {
+            if has_comments(inner_branch) {
+                return None;
+            }
+            rows.push(Row {
+                first: first.clone(),
+                second: pattern_text(inner_branch.child_by_field_name("pattern")?, source),
+                body: inner_branch.child_by_field_name("expr")?,
+            });
+        }
+    }
+    // The second value moves out of the branches, away from the names
+    // their patterns bind
+    let y = y?;
+    let mut used = Vec::new();
+    collect_identifiers(y, source, &mut used);
+    if used.iter().any(|name| bound.contains(name)) {
+        return None;
+    }
+    Some((&source[x.byte_range()], &source[y.byte_range()], rows))
+}
+
+/// The flat match replacing `case`
+fn rewrite(case: Node, source: &str, scrutinee: &str, rows: &[Row]) -> TextEdit {
+    let indent = line_indent(source, case);
+    let mut text = format!("case {} of", scrutinee);
+    for (i, row) in rows.iter().enumerate() {
+        if i > 0 {
+            text.push('\n');
+        }
+        text.push_str(&format!(
+            "\n{}( {}, {} ) ->\n{}{}",
+            spaces(indent + 4),
+            row.first,
+            row.second,
+            spaces(indent + 8),
+            reindent(row.body, source, indent + 8)
+        ));
+    }
+    TextEdit {
+        range: node_range(case),
+        new_text: text,
+    }
+}
+
+/// What a first pattern matches on: a constructor, a literal or a list
+/// shape. None for patterns matching anything.
+fn pattern_head(pattern: Node, source: &str) -> Option<String> {
+    match pattern.kind() {
+        "union_pattern" => {
+            let constructor = pattern.child_by_field_name("constructor")?;
+            Some(source[constructor.byte_range()].to_string())
+        }
+        // `_`, names, records, tuples, `()` and `p as name`
+        "anything_pattern" | "lower_pattern" | "record_pattern" | "tuple_pattern" | "unit_expr"
+        | "pattern" => None,
+        _ => Some(pattern_text(pattern, source)),
+    }
+}
+
+/// The pattern inside `pattern` wrappers and parentheses
+fn unwrap_pattern(mut pattern: Node) -> Node {
+    while pattern.kind() == "pattern" && pattern.named_child_count() == 1 {
+        match pattern.named_child(0) {
+            Some(inner) => pattern = inner,
+            None => break,
+        }
+    }
+    pattern
+}
+
+/// The two parts of a pair of `kind`
+fn tuple_parts<'a>(node: Node<'a>, kind: &str) -> Option<[Node<'a>; 2]> {
+    if node.kind() != kind {
+        return None;
+    }
+    let mut cursor = node.walk();
+    let parts: Vec<Node> = node.named_children(&mut cursor).collect();
+    match parts.as_slice() {
+        [first, second] => Some([*first, *second]),
+        _ => None,
+    }
+}
+
+/// Plain values like `model` or `model.page`, which are cheap and safe to
+/// evaluate in another place
+fn is_simple(node: Node) -> bool {
+    matches!(node.kind(), "value_expr" | "field_access_expr")
+}
+
+fn has_comments(node: Node) -> bool {
+    let mut cursor = node.walk();
+    let found = node
+        .children(&mut cursor)
+        .any(|child| matches!(child.kind(), "line_comment" | "block_comment"));
+    found
+}
+
+fn collect_identifiers(node: Node, source: &str, found: &mut Vec<String>) {
+    if node.kind() == "lower_case_identifier" {
+        found.push(source[node.byte_range()].to_string());
+    }
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_identifiers(child, source, found);
+    }
+}
+
+/// A pattern on one line
+fn pattern_text(pattern: Node, source: &str) -> String {
+    source[pattern.byte_range()]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The indentation of the line `node` starts on
+pub(super) fn line_indent(source: &str, node: Node) -> usize {
+    let line_start = source[..node.start_byte()].rfind('\n').map_or(0, |i| i + 1);
+    source[line_start..]
+        .chars()
+        .take_while(|c| *c == ' ')
+        .count()
+}
+
+pub(super) fn spaces(count: usize) -> String {
+    " ".repeat(count)
+}
+
+/// The text of `body` with its lines moved to start at `column`
+pub(super) fn reindent(body: Node, source: &str, column: usize) -> String {
+    let from = body.start_position().column;
+    let text = &source[body.byte_range()];
+    let mut lines = text.split('\n');
+    let mut result = lines.next().unwrap_or_default().to_string();
+    for line in lines {
+        result.push('\n');
+        if line.trim().is_empty() {
+            continue;
+        }
+        let indent = line.chars().take_while(|c| *c == ' ').count();
+        let shifted = (indent + column).saturating_sub(from);
+        result.push_str(&spaces(shifted));
+        result.push_str(&line[indent..]);
+    }
+    result
+}
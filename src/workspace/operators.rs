@@ -0,0 +1,129 @@
+//! Infix operators declared by packages.
+//!
+//! Since Elm 0.19 only packages (elm/core, elm/url, elm/parser, ...) may
+//! declare operators, with `infix left 0 (|>) = apR`. The index keeps each
+//! declaration's fixity together with the signature and docs of the function
+//! it names, for hover.
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::*;
+
+use crate::document::node_range;
+use crate::document::ElmSymbol;
+
+use super::Workspace;
+
+/// An `infix` declaration from a package module
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperatorInfo {
+    /// The operator, e.g. `|>`
+    pub operator: String,
+    /// `left`, `right` or `non`
+    pub associativity: String,
+    pub precedence: u32,
+    /// The function the operator stands for, e.g. `apR`
+    pub function: String,
+    pub module_name: String,
+    pub package: String,
+    pub definition_uri: Url,
+    pub definition_range: Range,
+    /// The function's annotation, e.g. `apR : a -> (a -> b) -> b`
+    pub signature: Option<String>,
+    pub documentation: Option<String>,
+}
+
+impl OperatorInfo {
+    /// Markdown for hover: the operator's type, its fixity and where it comes from
+    pub fn hover_markdown(&self) -> String {
+        let mut code = match self.signature.as_deref().and_then(|s| s.split_once(':')) {
+            Some((_, tipe)) => format!("({}) :{}\n", self.operator, tipe),
+            None => String::new(),
+        };
+        code.push_str(&format!(
+            "infix {} {} ({}) = {}",
+            self.associativity, self.precedence, self.operator, self.function
+        ));
+        let mut value = format!(
+            "```elm\n{}\n```\n\n*Defined in {} ({})*",
+            code, self.module_name, self.package
+        );
+        if let Some(docs) = &self.documentation {
+            value.push_str("\n\n");
+            value.push_str(docs);
+        }
+        value
+    }
+}
+
+/// The operators declared at the top level of a module
+pub(super) fn infix_declarations(
+    tree: &tree_sitter::Tree,
+    source: &str,
+    symbols: &[ElmSymbol],
+    module_name: &str,
+    package: &str,
+    uri: &Url,
+) -> Vec<OperatorInfo> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    root.named_children(&mut cursor)
+        .filter(|n| n.kind() == "infix_declaration")
+        .filter_map(|node| {
+            let text = |field: &str| {
+                node.child_by_field_name(field)
+                    .map(|n| source[n.byte_range()].to_string())
+            };
+            let operator_node = node.child_by_field_name("operator")?;
+            let function_node = node.named_child(node.named_child_count().checked_sub(1)?)?;
+            let function = source[function_node.byte_range()].to_string();
+            let symbol = symbols.iter().find(|s| s.name == function);
+            Some(OperatorInfo {
+                operator: source[operator_node.byte_range()].to_string(),
+                associativity: text("associativity")?,
+                precedence: text("precedence")?.parse().ok()?,
+                function,
+                module_name: module_name.to_string(),
+                package: package.to_string(),
+                definition_uri: uri.clone(),
+                definition_range: node_range(operator_node),
+                signature: symbol.and_then(|s| s.signature.clone()),
+                documentation: symbol.and_then(|s| s.documentation.clone()),
+            })
+        })
+        .collect()
+}
+
+impl Workspace {
+    /// The operator under the cursor, in an expression, `(op)` or an exposing
+    /// list. When several packages declare the same operator, the one from a
+    /// module the file imports wins.
+    pub fn operator_at(&self, uri: &Url, position: Position) -> Option<&OperatorInfo> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+        let node = tree
+            .root_node()
+            .named_descendant_for_point_range(point, point)?;
+        if node.kind() != "operator_identifier" {
+            return None;
+        }
+        let candidates = self.operators.get(&source[node.byte_range()])?;
+
+        let module = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.find_module_by_path(&path));
+        let imported = |op: &&OperatorInfo| {
+            module.is_some_and(|module| {
+                module
+                    .imports
+                    .iter()
+                    .any(|import| import.module_name == op.module_name)
+            })
+        };
+        candidates
+            .iter()
+            .find(imported)
+            .or_else(|| candidates.first())
+    }
+}
@@ -0,0 +1,165 @@
+//! Reordering a module's top-level declarations.
+//!
+//! Every declaration moves as one block with what precedes it: its doc
+//! comment, its type annotation and any other comment above it. The blocks
+//! are sorted by the rules of a [`DeclarationOrder`], keeping their original
+//! order where the rules do not tell them apart, and written back separated
+//! by two blank lines as elm-format does.
+
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::config::DeclarationOrder;
+use crate::document::offset_to_position;
+
+use super::module_docs::exposed_names;
+use super::{RefactorResult, Workspace};
+
+/// The Elm architecture's functions, in the order they are grouped
+const ARCHITECTURE: &[&str] = &[
+    "main",
+    "app",
+    "init",
+    "update",
+    "updateFromFrontend",
+    "updateFromBackend",
+    "subscriptions",
+    "view",
+];
+
+/// A declaration with the comments and annotation above it
+struct Block {
+    name: String,
+    /// 0 for types and type aliases, 1 for ports, 2 for values
+    kind: usize,
+    start: usize,
+    end: usize,
+}
+
+impl Workspace {
+    /// Reorder the top-level declarations of the module at `uri` as `order`
+    /// says
+    pub fn organize_declarations(
+        &self,
+        uri: &Url,
+        order: &DeclarationOrder,
+    ) -> anyhow::Result<RefactorResult> {
+        let path = uri
+            .to_file_path()
+            .map_err(|_| anyhow::anyhow!("Invalid URI"))?;
+        self.ensure_not_generated(&path)?;
+        let content = self
+            .read_file_content(uri)
+            .ok_or_else(|| anyhow::anyhow!("Could not read {}", uri))?;
+        let tree = self
+            .parser
+            .parse(&content)
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse source"))?;
+        if tree.root_node().has_error() {
+            return Ok(RefactorResult::error(
+                "The module has syntax errors; fix them before organizing it",
+            ));
+        }
+
+        let blocks = blocks(tree.root_node(), &content);
+        let (Some(first), Some(last)) = (blocks.first(), blocks.last()) else {
+            return Ok(RefactorResult::success(
+                "The module has no declarations to organize",
+                HashMap::new(),
+                Vec::new(),
+            ));
+        };
+        let (types, values) = exposed_names(&tree, &content);
+        let exposed = |name: &str| types.iter().chain(&values).any(|n| n == name);
+
+        let mut sorted: Vec<&Block> = blocks.iter().collect();
+        sorted.sort_by_key(|block| {
+            let architecture = ARCHITECTURE
+                .iter()
+                .position(|name| *name == block.name && block.kind == 2)
+                .unwrap_or(ARCHITECTURE.len());
+            (
+                order.exposed_first && !exposed(&block.name),
+                if order.types_first { block.kind } else { 0 },
+                if order.group_update_view {
+                    architecture
+                } else {
+                    0
+                },
+            )
+        });
+
+        let mut changes = HashMap::new();
+        if sorted.iter().zip(&blocks).any(|(a, b)| a.start != b.start) {
+            let new_text = sorted
+                .iter()
+                .map(|block| &content[block.start..block.end])
+                .collect::<Vec<_>>()
+                .join("\n\n\n");
+            let range = Range::new(
+                offset_to_position(&content, first.start),
+                offset_to_position(&content, last.end),
+            );
+            changes.insert(uri.clone(), vec![TextEdit { range, new_text }]);
+        }
+        let message = if changes.is_empty() {
+            "Declarations are already in order".to_string()
+        } else {
+            format!("Organized {} declarations", blocks.len())
+        };
+        Ok(RefactorResult::success(&message, changes, Vec::new()))
+    }
+}
+
+/// The declarations after the header, the module doc comment and the
+/// imports, each starting where the text after the previous one starts
+fn blocks(root: Node, content: &str) -> Vec<Block> {
+    let mut cursor = root.walk();
+    let children: Vec<Node> = root.named_children(&mut cursor).collect();
+    let mut previous_end = children
+        .iter()
+        .filter(|n| {
+            matches!(n.kind(), "module_declaration" | "import_clause")
+                || is_module_doc(**n, content)
+        })
+        .map(|n| n.end_byte())
+        .max()
+        .unwrap_or(0);
+
+    let header_end = previous_end;
+    let mut blocks = Vec::new();
+    for node in children.iter().filter(|n| n.end_byte() > header_end) {
+        let (name, kind) = match node.kind() {
+            "type_declaration" | "type_alias_declaration" => (node.child_by_field_name("name"), 0),
+            "port_annotation" => (node.child_by_field_name("name"), 1),
+            "value_declaration" => (
+                node.child_by_field_name("functionDeclarationLeft")
+                    .and_then(|left| left.named_child(0)),
+                2,
+            ),
+            "infix_declaration" => (None, 2),
+            // Comments and annotations belong to the next declaration
+            _ => continue,
+        };
+        let start = previous_end + content[previous_end..].len()
+            - content[previous_end..].trim_start().len();
+        blocks.push(Block {
+            name: name.map_or(String::new(), |n| content[n.byte_range()].to_string()),
+            kind,
+            start,
+            end: node.end_byte(),
+        });
+        previous_end = node.end_byte();
+    }
+    blocks
+}
+
+fn is_module_doc(node: Node, content: &str) -> bool {
+    node.kind() == "block_comment"
+        && content[node.byte_range()].starts_with("{-|")
+        && node
+            .prev_named_sibling()
+            .is_some_and(|n| n.kind() == "module_declaration")
+}
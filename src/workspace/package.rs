@@ -0,0 +1,152 @@
+//! Checks for package projects, which `elm publish` would otherwise report.
+//!
+//! Every module in elm.json's `exposed-modules` must exist. An exposed
+//! module's public signatures must not use types from modules the package
+//! keeps internal, since users could not name them. And every exposed
+//! declaration needs a doc comment.
+
+use tower_lsp::lsp_types::*;
+
+use crate::analysis::codes;
+use crate::document::{node_range, offset_to_position};
+
+use super::module_docs::exposed_names;
+use super::Workspace;
+
+impl Workspace {
+    /// Entries of a package's `exposed-modules` without a module in the
+    /// source directories, as diagnostics for elm.json
+    pub fn elm_json_diagnostics(&self) -> Vec<Diagnostic> {
+        let Some(exposed_modules) = &self.exposed_modules else {
+            return Vec::new();
+        };
+        let Ok(content) = std::fs::read_to_string(self.root_path.join("elm.json")) else {
+            return Vec::new();
+        };
+        let list_start = content.find("\"exposed-modules\"").unwrap_or(0);
+
+        exposed_modules
+            .iter()
+            .filter(|name| !self.modules.contains_key(*name))
+            .filter_map(|name| {
+                let quoted = format!("\"{}\"", name);
+                let start = list_start + content[list_start..].find(&quoted)?;
+                Some(codes::MISSING_EXPOSED_MODULE.diagnostic(
+                    Range::new(
+                        offset_to_position(&content, start),
+                        offset_to_position(&content, start + quoted.len()),
+                    ),
+                    DiagnosticSeverity::ERROR,
+                    format!(
+                        "`{}` is in exposed-modules but there is no such module in the source directories",
+                        name
+                    ),
+                ))
+            })
+            .collect()
+    }
+
+    /// Problems of an exposed module of a package: exposed declarations
+    /// without docs, and internal types in exposed signatures
+    pub fn package_diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        let Some(exposed_modules) = &self.exposed_modules else {
+            return Vec::new();
+        };
+        let Some(module) = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.find_module_by_path(&path))
+            .filter(|module| exposed_modules.contains(&module.module_name))
+        else {
+            return Vec::new();
+        };
+        let (Some(tree), Some(source)) = (
+            self.type_checker.get_tree(uri.as_str()),
+            self.type_checker.get_source(uri.as_str()),
+        ) else {
+            return Vec::new();
+        };
+        let (types, values) = exposed_names(tree, source);
+        let exposed = |name: &str| types.iter().chain(&values).any(|n| n == name);
+
+        let mut diagnostics = Vec::new();
+        for symbol in module.symbols.iter().filter(|s| exposed(&s.name)) {
+            if symbol.documentation.is_none() {
+                diagnostics.push(codes::MISSING_DOC_COMMENT.diagnostic(
+                    symbol.definition_range.unwrap_or(symbol.range),
+                    DiagnosticSeverity::WARNING,
+                    format!(
+                        "`{}` is exposed but has no doc comment, which `elm publish` requires",
+                        symbol.name
+                    ),
+                ));
+            }
+        }
+
+        // Types named in the annotations of exposed values
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        for annotation in root
+            .named_children(&mut cursor)
+            .filter(|n| n.kind() == "type_annotation")
+        {
+            let is_exposed = annotation
+                .child_by_field_name("name")
+                .is_some_and(|name| exposed(&source[name.byte_range()]));
+            let Some(type_expression) = annotation
+                .child_by_field_name("typeExpression")
+                .filter(|_| is_exposed)
+            else {
+                continue;
+            };
+            let mut stack = vec![type_expression];
+            while let Some(node) = stack.pop() {
+                let mut children = node.walk();
+                stack.extend(node.named_children(&mut children));
+                if node.kind() != "upper_case_qid"
+                    || node.parent().is_none_or(|p| p.kind() != "type_ref")
+                {
+                    continue;
+                }
+                let name = &source[node.byte_range()];
+                let resolved = self.resolve_reference(name, &module.imports);
+                let Some((type_module, type_name)) = resolved.rsplit_once('.') else {
+                    continue;
+                };
+                if type_module != module.module_name
+                    && self.modules.contains_key(type_module)
+                    && !exposed_modules.iter().any(|m| m == type_module)
+                {
+                    let mut diagnostic = codes::INTERNAL_TYPE_EXPOSED.diagnostic(
+                        node_range(node),
+                        DiagnosticSeverity::WARNING,
+                        format!(
+                            "`{}` comes from `{}`, which the package does not expose, so users cannot refer to it",
+                            type_name, type_module
+                        ),
+                    );
+                    diagnostic.related_information =
+                        self.type_location(type_module, type_name).map(|location| {
+                            vec![DiagnosticRelatedInformation {
+                                location,
+                                message: format!("`{}` defined here", type_name),
+                            }]
+                        });
+                    diagnostics.push(diagnostic);
+                }
+            }
+        }
+        diagnostics.sort_by_key(|d| d.range.start);
+        diagnostics
+    }
+
+    /// Where `module_name` declares the type `type_name`
+    fn type_location(&self, module_name: &str, type_name: &str) -> Option<Location> {
+        let module = self.modules.get(module_name)?;
+        let symbol = module.symbols.iter().find(|s| s.name == type_name)?;
+        Some(Location {
+            uri: Url::from_file_path(&module.path).ok()?,
+            range: symbol.definition_range.unwrap_or(symbol.range),
+        })
+    }
+}
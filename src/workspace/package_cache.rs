@@ -0,0 +1,200 @@
+//! Package indexes shared between projects.
+//!
+//! A published package never changes, so what `elm/core 1.0.5` declares is
+//! the same for every project. Its index is built once, kept in memory for
+//! the other projects the server opens, and written to
+//! `ELM_HOME/elm-lsp/packages/<author>/<name>/<version>.json` for later
+//! sessions. An index is only reused for the same package directory, which
+//! its locations point into, and when the same server version wrote it.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::Url;
+use walkdir::WalkDir;
+
+use super::operators::{self, OperatorInfo};
+use super::{ExposingInfo, ExternalPackage, GlobalSymbol, Workspace};
+use crate::document::read_source;
+use crate::logging;
+
+/// What a package declares, module by module
+#[derive(Serialize, Deserialize)]
+pub(super) struct PackageIndex {
+    /// The version of the server that built the index
+    server_version: String,
+    /// The package's `src` directory
+    path: PathBuf,
+    pub modules: Vec<PackageModule>,
+}
+
+/// The declarations of one package module
+#[derive(Serialize, Deserialize)]
+pub(super) struct PackageModule {
+    pub name: String,
+    pub symbols: Vec<GlobalSymbol>,
+    pub exposing: ExposingInfo,
+    pub operators: Vec<OperatorInfo>,
+}
+
+/// Indexes by package name and version
+type SharedIndexes = HashMap<(String, String), Arc<PackageIndex>>;
+
+/// Indexes built or loaded by any project of this server
+static SHARED: LazyLock<Mutex<SharedIndexes>> = LazyLock::new(Default::default);
+
+impl PackageIndex {
+    fn fits(&self, package: &ExternalPackage) -> bool {
+        self.path == package.path && self.server_version == env!("CARGO_PKG_VERSION")
+    }
+}
+
+impl Workspace {
+    /// The index of `package`, shared with the other projects using it and
+    /// only built when none of them, in this session or an earlier one, has
+    pub(super) fn package_index(
+        &self,
+        package: &ExternalPackage,
+    ) -> anyhow::Result<Arc<PackageIndex>> {
+        let key = (package.name.clone(), package.version.clone());
+        let shared = SHARED
+            .lock()
+            .ok()
+            .and_then(|shared| shared.get(&key).cloned())
+            .filter(|index| index.fits(package));
+        if let Some(index) = shared {
+            return Ok(index);
+        }
+
+        let file = self.package_index_file(package);
+        let stored = std::fs::read_to_string(&file)
+            .ok()
+            .and_then(|json| serde_json::from_str::<PackageIndex>(&json).ok())
+            .filter(|index| index.fits(package));
+        let index = match stored {
+            Some(index) => {
+                tracing::debug!(
+                    target: logging::INDEX,
+                    "Loaded the index of {} {} from {}",
+                    package.name,
+                    package.version,
+                    file.display()
+                );
+                index
+            }
+            None => {
+                let index = self.build_package_index(package)?;
+                // Restricted mode writes no files of its own
+                if !self.settings.restricted {
+                    if let Err(e) = write_index(&file, &index) {
+                        tracing::debug!(
+                            target: logging::INDEX,
+                            "Could not store the index of {} {}: {}",
+                            package.name,
+                            package.version,
+                            e
+                        );
+                    }
+                }
+                index
+            }
+        };
+
+        let index = Arc::new(index);
+        if let Ok(mut shared) = SHARED.lock() {
+            shared.insert(key, index.clone());
+        }
+        Ok(index)
+    }
+
+    /// Where the index of `package` is stored between sessions
+    fn package_index_file(&self, package: &ExternalPackage) -> PathBuf {
+        self.elm_home
+            .join("elm-lsp")
+            .join("packages")
+            .join(package.name.replace('/', std::path::MAIN_SEPARATOR_STR))
+            .join(format!("{}.json", package.version))
+    }
+
+    /// Parse the modules of `package`
+    fn build_package_index(&self, package: &ExternalPackage) -> anyhow::Result<PackageIndex> {
+        let mut modules = Vec::new();
+        for entry in WalkDir::new(&package.path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "elm") {
+                modules.extend(self.package_module(path, &package.name)?);
+            }
+        }
+        Ok(PackageIndex {
+            server_version: env!("CARGO_PKG_VERSION").to_string(),
+            path: package.path.clone(),
+            modules,
+        })
+    }
+
+    /// The declarations of the package module at `path`; only symbols, no
+    /// references
+    fn package_module(
+        &self,
+        path: &Path,
+        package_name: &str,
+    ) -> anyhow::Result<Option<PackageModule>> {
+        let content = read_source(path)?;
+        let uri = Url::from_file_path(path).map_err(|_| anyhow::anyhow!("Invalid path"))?;
+        let Some(tree) = self.parser.parse(&content) else {
+            return Ok(None);
+        };
+
+        let symbols = self.parser.extract_symbols(&tree, &content);
+        let module_name = self
+            .extract_module_name(&tree, &content)
+            .unwrap_or_else(|| {
+                path.file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("Unknown")
+                    .to_string()
+            });
+        let operators = operators::infix_declarations(
+            &tree,
+            &content,
+            &symbols,
+            &module_name,
+            package_name,
+            &uri,
+        );
+        Ok(Some(PackageModule {
+            symbols: symbols
+                .iter()
+                .map(|symbol| GlobalSymbol {
+                    name: symbol.name.clone(),
+                    module_name: module_name.clone(),
+                    kind: symbol.kind,
+                    definition_uri: uri.clone(),
+                    definition_range: symbol.definition_range.unwrap_or(symbol.range),
+                    range: symbol.range,
+                    signature: symbol.signature.clone(),
+                })
+                .collect(),
+            exposing: self.extract_exposing(&tree, &content),
+            operators,
+            name: module_name,
+        }))
+    }
+}
+
+/// Write `index` to `file`, through a temporary file so that another server
+/// never reads half of it
+fn write_index(file: &Path, index: &PackageIndex) -> anyhow::Result<()> {
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let partial = file.with_extension(format!("json.{}", std::process::id()));
+    std::fs::write(&partial, serde_json::to_string(index)?)?;
+    std::fs::rename(&partial, file)?;
+    Ok(())
+}
@@ -0,0 +1,210 @@
+//! What kind of program a module defines.
+//!
+//! `main = Browser.element { ... }` makes a module an element program,
+//! `app = Lamdera.frontend { ... }` a Lamdera frontend, and so on. The kind
+//! is shown when hovering `main`, listed in the index stats, and decides
+//! which checks apply: only applications own the URL, so navigation that
+//! needs a `Browser.Navigation.Key` is flagged everywhere else.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use tower_lsp::lsp_types::*;
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::codes;
+use crate::document::node_range;
+
+use super::declarations::declared_name;
+use super::{ImportInfo, Workspace};
+
+/// How a module's `main` (or a Lamdera `app`) runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProgramKind {
+    Sandbox,
+    Element,
+    Document,
+    Application,
+    Worker,
+    LamderaFrontend,
+    LamderaBackend,
+}
+
+impl ProgramKind {
+    /// The kind of program built by `function`, a fully qualified name
+    fn from_function(function: &str) -> Option<Self> {
+        Some(match function {
+            "Browser.sandbox" => Self::Sandbox,
+            "Browser.element" => Self::Element,
+            "Browser.document" => Self::Document,
+            "Browser.application" => Self::Application,
+            "Platform.worker" => Self::Worker,
+            "Lamdera.frontend" => Self::LamderaFrontend,
+            "Lamdera.backend" => Self::LamderaBackend,
+            _ => return None,
+        })
+    }
+
+    /// The function building this kind of program
+    pub fn function(self) -> &'static str {
+        match self {
+            Self::Sandbox => "Browser.sandbox",
+            Self::Element => "Browser.element",
+            Self::Document => "Browser.document",
+            Self::Application => "Browser.application",
+            Self::Worker => "Platform.worker",
+            Self::LamderaFrontend => "Lamdera.frontend",
+            Self::LamderaBackend => "Lamdera.backend",
+        }
+    }
+
+    /// The declaration defining the program
+    pub fn entry_point(self) -> &'static str {
+        match self {
+            Self::LamderaFrontend | Self::LamderaBackend => "app",
+            _ => "main",
+        }
+    }
+
+    /// Whether the program controls the URL and gets a `Browser.Navigation.Key`
+    pub fn handles_urls(self) -> bool {
+        matches!(self, Self::Application | Self::LamderaFrontend)
+    }
+}
+
+/// Counts of what a workspace has indexed
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexStats {
+    pub root: String,
+    pub modules: usize,
+    /// Modules indexed for navigation only
+    pub light_modules: usize,
+    pub symbols: usize,
+    pub external_modules: usize,
+    /// The kind of program each module with a `main` (or Lamdera `app`) defines
+    pub programs: BTreeMap<String, ProgramKind>,
+    /// The program run for each tool, found as described in [`crate::tools`]
+    pub tools: BTreeMap<String, String>,
+}
+
+/// `Browser.Navigation` functions that take a `Key`
+const NAVIGATION_WITH_KEY: &[&str] = &["pushUrl", "replaceUrl", "back", "forward"];
+
+impl Workspace {
+    pub fn index_stats(&self) -> IndexStats {
+        IndexStats {
+            root: self.root_path.to_string_lossy().to_string(),
+            modules: self.modules.len(),
+            light_modules: self.modules.values().filter(|m| m.light).count(),
+            symbols: self.modules.values().map(|m| m.symbols.len()).sum(),
+            external_modules: self.external_modules.len(),
+            programs: self
+                .modules
+                .values()
+                .filter_map(|m| Some((m.module_name.clone(), m.program?)))
+                .collect(),
+            tools: crate::tools::TOOLS
+                .iter()
+                .map(|name| {
+                    let path = self.tool_path(name);
+                    (name.to_string(), path.to_string_lossy().to_string())
+                })
+                .collect(),
+        }
+    }
+
+    /// The kind of program the module at `uri` defines, if any
+    pub fn program_kind(&self, uri: &Url) -> Option<ProgramKind> {
+        let path = uri.to_file_path().ok()?;
+        self.find_module_by_path(&path)?.program
+    }
+
+    /// Classify the program of a module from its `main`, or from `app` in a
+    /// Lamdera `Frontend` or `Backend`
+    pub(super) fn detect_program(
+        &self,
+        tree: &Tree,
+        source: &str,
+        module_name: &str,
+        imports: &[ImportInfo],
+    ) -> Option<ProgramKind> {
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        let entry_point = root.named_children(&mut cursor).find(|n| {
+            n.kind() == "value_declaration"
+                && declared_name(*n, source).is_some_and(|name| {
+                    name == "main"
+                        || (name == "app" && matches!(module_name, "Frontend" | "Backend"))
+                })
+        })?;
+        let target = program_call_target(entry_point.child_by_field_name("body")?)?;
+        let name = &source[target.byte_range()];
+        ProgramKind::from_function(&self.resolve_reference(name, imports))
+    }
+
+    /// Warnings for checks that depend on the kind of program
+    pub fn program_diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        let Some(kind) = self.program_kind(uri) else {
+            return Vec::new();
+        };
+        if kind.handles_urls() {
+            return Vec::new();
+        }
+        let (Some(tree), Some(source), Some(module)) = (
+            self.type_checker.get_tree(uri.as_str()),
+            self.type_checker.get_source(uri.as_str()),
+            uri.to_file_path()
+                .ok()
+                .and_then(|path| self.find_module_by_path(&path)),
+        ) else {
+            return Vec::new();
+        };
+
+        let mut diagnostics = Vec::new();
+        let mut stack = vec![tree.root_node()];
+        while let Some(node) = stack.pop() {
+            if node.kind() == "value_qid" {
+                let name = &source[node.byte_range()];
+                let resolved = self.resolve_reference(name, &module.imports);
+                if let Some(function) = resolved
+                    .strip_prefix("Browser.Navigation.")
+                    .filter(|f| NAVIGATION_WITH_KEY.contains(f))
+                {
+                    diagnostics.push(codes::NAVIGATION_WITHOUT_KEY.diagnostic(
+                        node_range(node),
+                        DiagnosticSeverity::WARNING,
+                        format!(
+                            "`{}` needs a `Browser.Navigation.Key`, which only `Browser.application` provides, but this module's program is `{}`",
+                            function,
+                            kind.function()
+                        ),
+                    ));
+                }
+                continue;
+            }
+            let mut cursor = node.walk();
+            stack.extend(node.named_children(&mut cursor));
+        }
+        diagnostics.sort_by_key(|d| d.range.start);
+        diagnostics
+    }
+}
+
+/// The function called by `Browser.element { ... }` or
+/// `Browser.element <| { ... }`
+fn program_call_target(body: Node) -> Option<Node> {
+    let body = std::iter::successors(Some(body), |n| n.named_child(0))
+        .find(|n| n.kind() != "parenthesized_expr")?;
+    let target = match body.kind() {
+        "function_call_expr" => body.child_by_field_name("target")?,
+        "bin_op_expr" => body.named_child(0)?,
+        _ => return None,
+    };
+    let mut cursor = target.walk();
+    let qid = target
+        .named_children(&mut cursor)
+        .find(|n| n.kind() == "value_qid");
+    qid.or_else(|| (target.kind() == "value_qid").then_some(target))
+}
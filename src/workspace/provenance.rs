@@ -0,0 +1,193 @@
+//! Where a name used in a module comes from: the import that brings it in.
+//!
+//! Hover shows it as a line like "imported from `Utils.Date` (aliased as
+//! `Date`)" or "exposed via `import Types exposing (..)`", so the import
+//! block does not have to be searched by hand.
+//!
+//! Elm modules only expose their own declarations, so an umbrella module
+//! re-exports with a declaration that is nothing but a name from another
+//! module: `foo = Internal.foo` or `type alias Foo = Internal.Foo`. Go to
+//! definition follows those to the original declaration.
+
+use tower_lsp::lsp_types::Url;
+use tree_sitter::{Node, Point};
+
+use crate::document::ElmSymbol;
+
+use super::declarations::value_declaration;
+use super::{ExposingInfo, GlobalSymbol, ImportInfo, Workspace};
+
+/// Re-exports followed before giving up, against cycles
+const MAX_HOPS: usize = 8;
+
+impl Workspace {
+    /// The import of the module at `uri` that `name` (`format` or
+    /// `Date.format`) comes through, as a line of Markdown. None for names
+    /// the module declares itself or that no import brings in.
+    pub fn import_provenance(&self, uri: &Url, name: &str) -> Option<String> {
+        let module = self.find_module_by_path(&uri.to_file_path().ok()?)?;
+        if !name.contains('.') && module.symbols.iter().any(|symbol| declares(symbol, name)) {
+            return None;
+        }
+        let (import, item) = self.providing_import(&module.imports, name)?;
+        Some(match (item, &import.alias) {
+            (Some(item), _) => format!(
+                "*Exposed via `import {} exposing ({})`*",
+                import.module_name, item
+            ),
+            (None, _) if !name.contains('.') => format!(
+                "*Exposed via `import {} exposing (..)`*",
+                import.module_name
+            ),
+            (None, Some(alias)) => format!(
+                "*Imported from `{}` (aliased as `{}`)*",
+                import.module_name, alias
+            ),
+            (None, None) => format!("*Imported from `{}`*", import.module_name),
+        })
+    }
+
+    /// The declarations `symbol` re-exports, one hop after the other, the
+    /// original declaration last. Empty when `symbol` is the original.
+    pub fn reexport_chain(&self, symbol: &GlobalSymbol) -> Vec<GlobalSymbol> {
+        let mut chain: Vec<GlobalSymbol> = Vec::new();
+        let mut current = symbol.clone();
+        while chain.len() < MAX_HOPS {
+            let Some(next) = self.reexported(&current) else {
+                break;
+            };
+            let seen =
+                |s: &GlobalSymbol| s.definition_uri == next.definition_uri && s.name == next.name;
+            if seen(symbol) || chain.iter().any(seen) {
+                break;
+            }
+            chain.push(next.clone());
+            current = next;
+        }
+        chain
+    }
+
+    /// The declaration `symbol` re-exports, when its body is only a name
+    /// from an import
+    fn reexported(&self, symbol: &GlobalSymbol) -> Option<GlobalSymbol> {
+        let path = symbol.definition_uri.to_file_path().ok()?;
+        let source = self.source(&path).ok()?;
+        let tree = self.parser.parse(&source)?;
+        let root = tree.root_node();
+        let start = symbol.definition_range.start;
+        let point = Point::new(start.line as usize, start.character as usize);
+        let mut cursor = root.walk();
+        let declaration = root.named_children(&mut cursor).find(|n| {
+            matches!(n.kind(), "value_declaration" | "type_alias_declaration")
+                && n.start_position() <= point
+                && point < n.end_position()
+        })?;
+        let target = aliased_name(declaration, &source)?;
+        // `foo = bar` with a local `bar` is no re-export
+        if !target.contains('.') && value_declaration(&tree, &source, target).is_some() {
+            return None;
+        }
+
+        let imports = self.extract_imports(&tree, &source);
+        let (import, _) = self.providing_import(&imports, target)?;
+        let name = target.rsplit('.').next()?;
+        self.declared_symbol(&import.module_name, name)
+    }
+
+    /// The import of `imports` that `name` comes through, with the item of
+    /// its exposing list naming it. A qualified name comes through the
+    /// import of its qualifier; for an unqualified one an explicit exposing
+    /// list wins over `exposing (..)`, which only counts when the module
+    /// defines the name.
+    fn providing_import<'a>(
+        &self,
+        imports: &'a [ImportInfo],
+        name: &str,
+    ) -> Option<(&'a ImportInfo, Option<&'a str>)> {
+        if let Some((qualifier, _)) = name.rsplit_once('.') {
+            let import = imports.iter().find(|import| match &import.alias {
+                Some(alias) => alias == qualifier,
+                None => import.module_name == qualifier,
+            })?;
+            return Some((import, None));
+        }
+
+        let explicit = imports.iter().find_map(|import| {
+            let ExposingInfo::Explicit(items) = &import.exposing else {
+                return None;
+            };
+            let item = items.iter().find(|item| {
+                *item == name
+                    || (item.ends_with("(..)")
+                        && self.exposes_variant(import, &item[..item.len() - 4], name))
+            })?;
+            Some((import, Some(item.as_str())))
+        });
+        explicit.or_else(|| {
+            imports
+                .iter()
+                .find(|import| {
+                    matches!(import.exposing, ExposingInfo::All)
+                        && self.module_defines(&import.module_name, name)
+                })
+                .map(|import| (import, None))
+        })
+    }
+
+    /// Whether `Type(..)` in the exposing list of `import` brings `name` in:
+    /// the type itself or one of its variants
+    pub(super) fn exposes_variant(&self, import: &ImportInfo, type_name: &str, name: &str) -> bool {
+        if type_name == name {
+            return true;
+        }
+        match self.modules.get(&import.module_name) {
+            Some(module) => module
+                .symbols
+                .iter()
+                .filter(|symbol| symbol.name == type_name)
+                .any(|symbol| symbol.variants.iter().any(|v| v.name == name)),
+            None => self.module_defines(&import.module_name, name),
+        }
+    }
+
+    /// Whether the workspace or package module `module_name` declares `name`
+    pub(super) fn module_defines(&self, module_name: &str, name: &str) -> bool {
+        match self.modules.get(module_name) {
+            Some(module) => module.symbols.iter().any(|symbol| declares(symbol, name)),
+            None => self
+                .external_symbols
+                .get(name)
+                .is_some_and(|symbols| symbols.iter().any(|s| s.module_name == module_name)),
+        }
+    }
+}
+
+/// Whether `symbol` is `name` or a type with a variant `name`
+fn declares(symbol: &ElmSymbol, name: &str) -> bool {
+    symbol.name == name || symbol.variants.iter().any(|variant| variant.name == name)
+}
+
+/// The name a declaration is nothing but: the body of `foo = Internal.foo`,
+/// without parameters, or the type of `type alias Foo = Internal.Foo`,
+/// without arguments
+fn aliased_name<'a>(declaration: Node, source: &'a str) -> Option<&'a str> {
+    let name = if declaration.kind() == "value_declaration" {
+        let left = declaration.child_by_field_name("functionDeclarationLeft")?;
+        if left.named_child_count() != 1 {
+            return None;
+        }
+        let body = declaration.child_by_field_name("body")?;
+        (body.kind() == "value_expr").then_some(body)?
+    } else {
+        let expression = declaration.child_by_field_name("typeExpression")?;
+        let reference = expression.named_child(0)?;
+        if expression.named_child_count() != 1
+            || reference.kind() != "type_ref"
+            || reference.named_child_count() != 1
+        {
+            return None;
+        }
+        reference
+    };
+    Some(source[name.byte_range()].trim())
+}
@@ -0,0 +1,91 @@
+//! Hovers for record updates.
+//!
+//! Hovering the base or the `|` of `{ model | page = Home }` shows the type
+//! alias the update resolves to, the same [`TargetTypeAlias`] field renames
+//! and record update completions use, with its declaration and the fields
+//! the update sets. When the record has no alias, its inferred type is shown.
+
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use super::Workspace;
+use crate::document::node_range;
+use crate::type_checker::TargetTypeAlias;
+
+impl Workspace {
+    /// Markdown for the record update whose base or `|` is at `position`,
+    /// and the range of that base or `|`
+    pub fn record_update_hover(&self, uri: &Url, position: Position) -> Option<(String, Range)> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+        let node = tree.root_node().descendant_for_point_range(point, point)?;
+        let (update, hovered) = update_at(node, source)?;
+        let base = update.child_by_field_name("baseRecord")?;
+        let base_name = &source[base.byte_range()];
+
+        let mut cursor = update.walk();
+        let set: Vec<String> = update
+            .children_by_field_name("field", &mut cursor)
+            .filter_map(|field| field.child_by_field_name("name"))
+            .map(|name| format!("`{}`", &source[name.byte_range()]))
+            .collect();
+        let sets = if set.is_empty() {
+            String::new()
+        } else {
+            format!(", setting {}", set.join(", "))
+        };
+
+        let ty = self.type_checker.get_type(uri.as_str(), base.id());
+        let alias = ty
+            .as_ref()
+            .and_then(|ty| self.type_checker.record_alias_fields(ty))
+            .or_else(|| self.base_record_fields(uri, base.end_byte(), base_name))
+            .map(|(alias, _)| alias);
+        let value = match (alias, ty) {
+            (Some(alias), _) => format!(
+                "```elm\n{}\n```\n\nUpdates a `{}` from `{}`{}",
+                self.alias_declaration(&alias)
+                    .unwrap_or_else(|| format!("type alias {}", alias.name)),
+                alias.name,
+                alias.module,
+                sets
+            ),
+            (None, Some(ty)) => format!("```elm\n{}\n```\n\nUpdates a record{}", ty, sets),
+            (None, None) => return None,
+        };
+        Some((value, node_range(hovered)))
+    }
+
+    /// The text of the declaration of `alias`, from the workspace or a package
+    fn alias_declaration(&self, alias: &TargetTypeAlias) -> Option<String> {
+        let is_alias =
+            |kind: SymbolKind, name: &str| kind == SymbolKind::STRUCT && name == alias.name;
+        match self.modules.get(&alias.module) {
+            Some(module) => module
+                .symbols
+                .iter()
+                .find(|s| is_alias(s.kind, &s.name))
+                .and_then(|s| s.signature.clone()),
+            None => self
+                .external_symbols
+                .get(&format!("{}.{}", alias.module, alias.name))?
+                .iter()
+                .find(|s| is_alias(s.kind, &s.name))
+                .and_then(|s| s.signature.clone()),
+        }
+    }
+}
+
+/// The record update `node` is the base or `|` of, and that node
+fn update_at<'a>(node: Node<'a>, source: &str) -> Option<(Node<'a>, Node<'a>)> {
+    std::iter::successors(Some(node), |n| n.parent())
+        .take(3)
+        .find_map(|n| {
+            let parent = n.parent()?;
+            let is_bar = !n.is_named() && &source[n.byte_range()] == "|";
+            (parent.kind() == "record_expr"
+                && (is_bar || parent.child_by_field_name("baseRecord") == Some(n)))
+            .then_some((parent, n))
+        })
+}
@@ -0,0 +1,255 @@
+//! Rename checks for the Elm workspace.
+//!
+//! Before a rename edit is returned, every file it touches is checked for a
+//! name the new name would collide with: a top-level declaration or
+//! constructor, an unqualified import, or a local binding in scope of one of
+//! the edits.
+//!
+//! Renames can optionally carry over to doc comments and string literals that
+//! mention the old name, since API docs usually refer to functions by name.
+
+use std::collections::HashMap;
+use tower_lsp::lsp_types::*;
+
+use super::{ExposingInfo, RenameConflict, Workspace};
+
+impl Workspace {
+    /// Names in the files touched by `changes` that `new_name` would clash with
+    pub fn rename_conflicts(
+        &self,
+        changes: &HashMap<Url, Vec<TextEdit>>,
+        new_name: &str,
+    ) -> Vec<RenameConflict> {
+        let mut conflicts = Vec::new();
+        let mut uris: Vec<&Url> = changes.keys().collect();
+        uris.sort();
+
+        for uri in uris {
+            let module = match self.get_module_at_uri(uri) {
+                Some(module) => module,
+                None => continue,
+            };
+
+            if let Some(range) = declared_range(&module.symbols, new_name) {
+                conflicts.push(RenameConflict {
+                    uri: uri.clone(),
+                    range,
+                    message: format!(
+                        "`{}` is already declared in module {}",
+                        new_name, module.module_name
+                    ),
+                });
+            }
+
+            for import in &module.imports {
+                if self.import_exposes(&import.module_name, &import.exposing, new_name) {
+                    conflicts.push(RenameConflict {
+                        uri: uri.clone(),
+                        range: Range::default(),
+                        message: format!(
+                            "`{}` is already imported from {} in module {}",
+                            new_name, import.module_name, module.module_name
+                        ),
+                    });
+                }
+            }
+
+            if let Some(range) = self.local_binding(uri, &changes[uri], new_name) {
+                conflicts.push(RenameConflict {
+                    uri: uri.clone(),
+                    range,
+                    message: format!(
+                        "`{}` is already bound locally in module {}",
+                        new_name, module.module_name
+                    ),
+                });
+            }
+        }
+
+        conflicts
+    }
+
+    /// Add edits renaming `old_name` inside doc comments and in string literals
+    /// equal to it, in every file `changes` already touches. Off unless
+    /// `requested` (or the `renameInDocs` setting) enables it.
+    pub fn rename_in_docs(
+        &self,
+        changes: &mut HashMap<Url, Vec<TextEdit>>,
+        old_name: &str,
+        new_name: &str,
+        requested: Option<bool>,
+    ) {
+        if !requested.unwrap_or(self.settings.rename_in_docs) || old_name == new_name {
+            return;
+        }
+        for (uri, edits) in changes.iter_mut() {
+            let (tree, source) = match (
+                self.type_checker.get_tree(uri.as_str()),
+                self.type_checker.get_source(uri.as_str()),
+            ) {
+                (Some(tree), Some(source)) => (tree, source),
+                _ => continue,
+            };
+            for edit in doc_and_string_edits(tree.root_node(), source, old_name, new_name) {
+                if !edits.iter().any(|e| e.range == edit.range) {
+                    edits.push(edit);
+                }
+            }
+        }
+    }
+
+    /// Whether an import brings `name` into scope unqualified
+    fn import_exposes(&self, module_name: &str, exposing: &ExposingInfo, name: &str) -> bool {
+        let target = self.get_module(module_name);
+        match exposing {
+            ExposingInfo::All => target.is_some_and(|m| {
+                m.symbols
+                    .iter()
+                    .any(|s| s.name == name || s.variants.iter().any(|v| v.name == name))
+                    && m.exposing
+                        .exposes(name, |t| declares_variant(&m.symbols, t, name))
+            }),
+            ExposingInfo::Explicit(_) => exposing.exposes(name, |type_name| {
+                target.is_some_and(|m| declares_variant(&m.symbols, type_name, name))
+            }),
+        }
+    }
+
+    /// A local binding named `name` in a top-level declaration containing one of `edits`
+    fn local_binding(&self, uri: &Url, edits: &[TextEdit], name: &str) -> Option<Range> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        let root = tree.root_node();
+
+        let mut cursor = root.walk();
+        let declarations: Vec<tree_sitter::Node> = root
+            .children(&mut cursor)
+            .filter(|n| n.kind() == "value_declaration")
+            .collect();
+
+        for declaration in declarations {
+            let start = declaration.start_position().row as u32;
+            let end = declaration.end_position().row as u32;
+            let touched = edits
+                .iter()
+                .any(|e| e.range.start.line >= start && e.range.start.line <= end);
+            if touched {
+                if let Some(node) = find_binding(declaration, source, name) {
+                    let (s, e) = (node.start_position(), node.end_position());
+                    return Some(Range::new(
+                        Position::new(s.row as u32, s.column as u32),
+                        Position::new(e.row as u32, e.column as u32),
+                    ));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Range of a top-level declaration or constructor called `name`
+fn declared_range(symbols: &[crate::document::ElmSymbol], name: &str) -> Option<Range> {
+    symbols.iter().find_map(|s| {
+        if s.name == name {
+            return Some(s.definition_range.unwrap_or(s.range));
+        }
+        s.variants.iter().find(|v| v.name == name).map(|v| v.range)
+    })
+}
+
+/// Whether `symbols` declare the type `type_name` with a constructor `name`
+fn declares_variant(symbols: &[crate::document::ElmSymbol], type_name: &str, name: &str) -> bool {
+    symbols
+        .iter()
+        .any(|s| s.name == type_name && s.variants.iter().any(|v| v.name == name))
+}
+
+/// A pattern variable or let-bound function named `name` inside `node`.
+/// The top-level declaration's own name is not a local binding.
+fn find_binding<'tree>(
+    node: tree_sitter::Node<'tree>,
+    source: &str,
+    name: &str,
+) -> Option<tree_sitter::Node<'tree>> {
+    let mut stack = vec![node];
+    while let Some(current) = stack.pop() {
+        let binds = match current.kind() {
+            "lower_pattern" => Some(current),
+            "function_declaration_left" if current.parent() != Some(node) => current.child(0),
+            _ => None,
+        };
+        if let Some(binding) = binds {
+            if &source[binding.byte_range()] == name {
+                return Some(binding);
+            }
+        }
+        let mut cursor = current.walk();
+        stack.extend(current.children(&mut cursor));
+    }
+    None
+}
+
+/// Edits for whole-word occurrences of `old_name` in doc comments, and for
+/// single-line string literals whose content is exactly `old_name`
+fn doc_and_string_edits(
+    root: tree_sitter::Node,
+    source: &str,
+    old_name: &str,
+    new_name: &str,
+) -> Vec<TextEdit> {
+    let mut edits = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        let text = &source[node.byte_range()];
+        match node.kind() {
+            "block_comment" if text.starts_with("{-|") => {
+                let start = node.start_position();
+                for (i, line) in text.split('\n').enumerate() {
+                    let offset = if i == 0 { start.column } else { 0 };
+                    for column in word_occurrences(line, old_name) {
+                        let line_number = (start.row + i) as u32;
+                        let column = (offset + column) as u32;
+                        edits.push(TextEdit {
+                            range: Range::new(
+                                Position::new(line_number, column),
+                                Position::new(line_number, column + old_name.len() as u32),
+                            ),
+                            new_text: new_name.to_string(),
+                        });
+                    }
+                }
+            }
+            "string_constant_expr"
+                if text.strip_prefix('"').and_then(|t| t.strip_suffix('"')) == Some(old_name) =>
+            {
+                let start = node.start_position();
+                let column = start.column as u32 + 1;
+                edits.push(TextEdit {
+                    range: Range::new(
+                        Position::new(start.row as u32, column),
+                        Position::new(start.row as u32, column + old_name.len() as u32),
+                    ),
+                    new_text: new_name.to_string(),
+                });
+            }
+            _ => {
+                let mut cursor = node.walk();
+                stack.extend(node.children(&mut cursor));
+            }
+        }
+    }
+    edits
+}
+
+/// Byte columns where `word` occurs in `line` not as part of a longer identifier
+fn word_occurrences(line: &str, word: &str) -> Vec<usize> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    line.match_indices(word)
+        .filter(|(i, _)| {
+            let before = line[..*i].chars().next_back();
+            let after = line[i + word.len()..].chars().next();
+            !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
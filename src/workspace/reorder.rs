@@ -0,0 +1,265 @@
+//! Reordering the variants of a custom type and the fields of a record alias.
+//!
+//! Each variant or field keeps its slot in the declaration and only the text
+//! in the slots moves, so the leading `=`/`|`, the commas and the layout stay
+//! where they were. A record alias also doubles as a positional constructor
+//! (`User "Ann" 42`), so its full call sites can be reordered to match.
+
+use std::collections::HashMap;
+use tower_lsp::lsp_types::*;
+
+use crate::binder::BoundSymbolKind;
+use crate::document::node_range;
+
+use super::{RefactorResult, Workspace};
+
+impl Workspace {
+    /// Reorder the variants of `type_name`, declared in the file at `uri`.
+    /// `order` lists every variant name; None sorts them alphabetically.
+    pub fn reorder_variants(
+        &self,
+        uri: &Url,
+        type_name: &str,
+        order: Option<&[String]>,
+    ) -> anyhow::Result<RefactorResult> {
+        let path = uri
+            .to_file_path()
+            .map_err(|_| anyhow::anyhow!("Invalid URI"))?;
+        self.ensure_not_generated(&path)?;
+        let content = self
+            .read_file_content(uri)
+            .ok_or_else(|| anyhow::anyhow!("Could not read {}", uri))?;
+        let tree = self
+            .parser
+            .parse(&content)
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse source"))?;
+
+        let declaration = match find_declaration(&tree, &content, "type_declaration", type_name) {
+            Some(declaration) => declaration,
+            None => {
+                return Ok(RefactorResult::error(&format!(
+                    "'{}' is not a custom type",
+                    type_name
+                )))
+            }
+        };
+        let mut cursor = declaration.walk();
+        let slots: Vec<tree_sitter::Node> = declaration
+            .children_by_field_name("unionVariant", &mut cursor)
+            .collect();
+        let names: Vec<String> = slots
+            .iter()
+            .filter_map(|v| v.child_by_field_name("name"))
+            .map(|n| content[n.byte_range()].to_string())
+            .collect();
+
+        let permutation = match permutation(&names, order) {
+            Ok(permutation) => permutation,
+            Err(message) => return Ok(RefactorResult::error(&message)),
+        };
+
+        let mut changes = HashMap::new();
+        let edits = slot_edits(&slots, &permutation, &content);
+        if !edits.is_empty() {
+            changes.insert(uri.clone(), edits);
+        }
+        let message = if changes.is_empty() {
+            format!("Variants of '{}' are already in order", type_name)
+        } else {
+            format!("Reordered {} variants of '{}'", names.len(), type_name)
+        };
+        Ok(RefactorResult::success(&message, changes, Vec::new()))
+    }
+
+    /// Reorder the fields of the record alias `alias_name`, declared in the
+    /// file at `uri`. `order` lists every field name; None sorts them
+    /// alphabetically. With `update_call_sites`, full positional constructor
+    /// calls are reordered too; other constructor usages (partial application,
+    /// passing `Alias` to a decoder) are reported as unchanged.
+    pub fn reorder_fields(
+        &self,
+        uri: &Url,
+        alias_name: &str,
+        order: Option<&[String]>,
+        update_call_sites: bool,
+    ) -> anyhow::Result<RefactorResult> {
+        let path = uri
+            .to_file_path()
+            .map_err(|_| anyhow::anyhow!("Invalid URI"))?;
+        self.ensure_not_generated(&path)?;
+        let content = self
+            .read_file_content(uri)
+            .ok_or_else(|| anyhow::anyhow!("Could not read {}", uri))?;
+        let tree = self
+            .parser
+            .parse(&content)
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse source"))?;
+
+        let record = find_declaration(&tree, &content, "type_alias_declaration", alias_name)
+            .and_then(|d| d.child_by_field_name("typeExpression"))
+            .and_then(|t| t.named_child(0))
+            .filter(|t| t.kind() == "record_type");
+        let record = match record {
+            Some(record) => record,
+            None => {
+                return Ok(RefactorResult::error(&format!(
+                    "'{}' is not a record type alias",
+                    alias_name
+                )))
+            }
+        };
+        let mut cursor = record.walk();
+        let slots: Vec<tree_sitter::Node> = record
+            .children_by_field_name("fieldType", &mut cursor)
+            .collect();
+        let names: Vec<String> = slots
+            .iter()
+            .filter_map(|f| f.child_by_field_name("name"))
+            .map(|n| content[n.byte_range()].to_string())
+            .collect();
+
+        let permutation = match permutation(&names, order) {
+            Ok(permutation) => permutation,
+            Err(message) => return Ok(RefactorResult::error(&message)),
+        };
+
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        let edits = slot_edits(&slots, &permutation, &content);
+        if edits.is_empty() {
+            return Ok(RefactorResult::success(
+                &format!("Fields of '{}' are already in order", alias_name),
+                changes,
+                Vec::new(),
+            ));
+        }
+        changes.insert(uri.clone(), edits);
+
+        let mut unchanged = Vec::new();
+        let mut call_sites = 0;
+        if update_call_sites {
+            let module_name = self.get_module_name_from_uri(uri);
+            let usages: Vec<_> = self
+                .find_module_aware_references(alias_name, &module_name, uri)
+                .into_iter()
+                .filter(|r| matches!(r.kind, Some(BoundSymbolKind::UnionConstructor)))
+                .collect();
+
+            let mut by_file: HashMap<Url, Vec<Range>> = HashMap::new();
+            for usage in usages {
+                by_file.entry(usage.uri).or_default().push(usage.range);
+            }
+            for (file_uri, ranges) in by_file {
+                let file_content = match self.read_file_content(&file_uri) {
+                    Some(c) => c,
+                    None => continue,
+                };
+                let file_tree = match self.parser.parse(&file_content) {
+                    Some(t) => t,
+                    None => continue,
+                };
+                for range in ranges {
+                    match call_arguments(&file_tree, range.start) {
+                        Some(args) if args.len() == names.len() => {
+                            call_sites += 1;
+                            changes
+                                .entry(file_uri.clone())
+                                .or_default()
+                                .extend(slot_edits(&args, &permutation, &file_content));
+                        }
+                        _ => unchanged.push(Location::new(file_uri.clone(), range)),
+                    }
+                }
+            }
+        }
+
+        let mut message = format!("Reordered {} fields of '{}'", names.len(), alias_name);
+        if call_sites > 0 {
+            message.push_str(&format!(", updated {} constructor call(s)", call_sites));
+        }
+        if !unchanged.is_empty() {
+            message.push_str(&format!(
+                ", {} constructor usage(s) need manual review",
+                unchanged.len()
+            ));
+        }
+        Ok(RefactorResult::success(&message, changes, unchanged))
+    }
+}
+
+/// Top-level declaration of `kind` named `name`
+fn find_declaration<'tree>(
+    tree: &'tree tree_sitter::Tree,
+    content: &str,
+    kind: &str,
+    name: &str,
+) -> Option<tree_sitter::Node<'tree>> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let found = root.children(&mut cursor).find(|n| {
+        n.kind() == kind
+            && n.child_by_field_name("name")
+                .is_some_and(|n| &content[n.byte_range()] == name)
+    });
+    found
+}
+
+/// For each new position, the current position of the item that goes there.
+/// `order` must name every item exactly once; None sorts alphabetically.
+fn permutation(names: &[String], order: Option<&[String]>) -> Result<Vec<usize>, String> {
+    let order: Vec<&String> = match order {
+        Some(order) => order.iter().collect(),
+        None => {
+            let mut sorted: Vec<&String> = names.iter().collect();
+            sorted.sort();
+            sorted
+        }
+    };
+    let mut permutation = Vec::with_capacity(names.len());
+    for name in &order {
+        match names.iter().position(|n| n == *name) {
+            Some(index) if !permutation.contains(&index) => permutation.push(index),
+            Some(_) => return Err(format!("'{}' is listed more than once", name)),
+            None => return Err(format!("'{}' is not one of {}", name, names.join(", "))),
+        }
+    }
+    if permutation.len() != names.len() {
+        return Err(format!(
+            "The new order must list all of {}",
+            names.join(", ")
+        ));
+    }
+    Ok(permutation)
+}
+
+/// Edits putting the text of `slots[permutation[i]]` into slot `i`
+fn slot_edits(slots: &[tree_sitter::Node], permutation: &[usize], content: &str) -> Vec<TextEdit> {
+    permutation
+        .iter()
+        .enumerate()
+        .filter(|(slot, from)| slot != *from)
+        .map(|(slot, &from)| TextEdit {
+            range: node_range(slots[slot]),
+            new_text: content[slots[from].byte_range()].to_string(),
+        })
+        .collect()
+}
+
+/// Arguments of the call whose function is the name at `position`,
+/// or None if it is not called there
+pub(super) fn call_arguments(
+    tree: &tree_sitter::Tree,
+    position: Position,
+) -> Option<Vec<tree_sitter::Node<'_>>> {
+    let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+    let mut node = tree.root_node().descendant_for_point_range(point, point)?;
+    while node.kind() != "value_expr" {
+        node = node.parent()?;
+    }
+    let call = node.parent().filter(|p| p.kind() == "function_call_expr")?;
+    if call.child_by_field_name("target")?.id() != node.id() {
+        return None;
+    }
+    let mut cursor = call.walk();
+    let args = call.children_by_field_name("arg", &mut cursor).collect();
+    Some(args)
+}
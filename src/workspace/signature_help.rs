@@ -0,0 +1,291 @@
+//! Signature help for function calls, including pipelines.
+//!
+//! Elm calls have no parentheses to anchor on, so the call is found from the
+//! syntax tree around the last non-blank character before the cursor. Besides
+//! plain `f a b` calls this accounts for arguments supplied by operators:
+//!
+//! - `x |> f a` and `f a <| x` fill the last parameter of `f`;
+//! - in `f a <| x`, the cursor in `x` is on the parameter after `a`;
+//! - in `succeed f |. spaces |= int |= int`, each `|=` fills the next
+//!   parameter of `f`.
+
+use tower_lsp::lsp_types::*;
+
+use super::{ElmModule, Workspace};
+
+/// A call the cursor is in: the function, which parameter the cursor is on,
+/// and how many trailing parameters an operator supplies
+struct CallSite<'a> {
+    target: tree_sitter::Node<'a>,
+    active: usize,
+    piped: usize,
+}
+
+impl Workspace {
+    /// Signature help for the call around `position`. Returns None outside a
+    /// call, for unknown functions, and once every parameter is supplied.
+    pub fn signature_help(&self, uri: &Url, position: Position) -> Option<SignatureHelp> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+
+        let line_start: usize = source
+            .split_inclusive('\n')
+            .take(position.line as usize)
+            .map(str::len)
+            .sum();
+        let offset = (line_start + position.character as usize).min(source.len());
+        let before = source.get(..offset)?;
+        let last = before.trim_end();
+        // Continuation lines are indented, so column 0 starts a new declaration
+        if last.is_empty() || position.character == 0 {
+            return None;
+        }
+        let anchor_byte = last.char_indices().next_back().map(|(i, _)| i)?;
+        let trailing_space = last.len() < before.len();
+        let anchor_point = byte_to_point(source, anchor_byte);
+        let anchor = tree
+            .root_node()
+            .named_descendant_for_point_range(anchor_point, anchor_point)?;
+
+        let call = find_call_site(anchor, last.len(), trailing_space, source)?;
+        let module = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.find_module_by_path(&path));
+        let (name, params, ret) = self.callee_signature(uri, module, call.target, source)?;
+        if call.active + call.piped >= params.len() {
+            return None;
+        }
+
+        let mut label = format!("{} : ", name);
+        let mut parameters = Vec::new();
+        for param in &params {
+            let start = label.encode_utf16().count() as u32;
+            label.push_str(param);
+            let end = label.encode_utf16().count() as u32;
+            parameters.push(ParameterInformation {
+                label: ParameterLabel::LabelOffsets([start, end]),
+                documentation: None,
+            });
+            label.push_str(" -> ");
+        }
+        label.push_str(&ret);
+
+        let documentation = (call.piped > 0).then(|| {
+            Documentation::String("The last argument is supplied by the pipeline.".to_string())
+        });
+        Some(SignatureHelp {
+            signatures: vec![SignatureInformation {
+                label,
+                documentation,
+                parameters: Some(parameters),
+                active_parameter: Some(call.active as u32),
+            }],
+            active_signature: Some(0),
+            active_parameter: Some(call.active as u32),
+        })
+    }
+
+    /// The name, parameter types and return type of the function `target`
+    /// names, from its annotation or else its inferred type
+    fn callee_signature(
+        &self,
+        uri: &Url,
+        module: Option<&ElmModule>,
+        target: tree_sitter::Node,
+        source: &str,
+    ) -> Option<(String, Vec<String>, String)> {
+        let name = &source[target.byte_range()];
+        let local = module
+            .filter(|_| !name.contains('.'))
+            .and_then(|m| self.symbols.get(&format!("{}.{}", m.module_name, name)))
+            .and_then(|symbols| symbols.first());
+        let symbol = local.or_else(|| {
+            let imports = module.map_or(&[][..], |m| m.imports.as_slice());
+            self.find_definition(&self.resolve_reference(name, imports))
+        });
+
+        let annotation = symbol
+            .filter(|s| matches!(s.kind, SymbolKind::FUNCTION | SymbolKind::INTERFACE))
+            .and_then(|s| s.signature.as_deref())
+            .and_then(|s| s.split_once(':'))
+            .map(|(_, tipe)| tipe.split_whitespace().collect::<Vec<_>>().join(" "));
+        let mut parts = match annotation {
+            Some(tipe) => split_arrows(&tipe),
+            None => match self.type_checker.get_type(uri.as_str(), target.id())? {
+                crate::types::Type::Function(function) => {
+                    let function = crate::types::Type::Function(function).with_readable_vars();
+                    split_arrows(&function.to_string())
+                }
+                _ => return None,
+            },
+        };
+        let ret = parts.pop()?;
+        if parts.is_empty() {
+            return None;
+        }
+        Some((name.to_string(), parts, ret))
+    }
+}
+
+/// Walk up from the node before the cursor to the call it belongs to.
+/// `anchor_end` is the byte after the last non-blank character.
+fn find_call_site<'a>(
+    anchor: tree_sitter::Node<'a>,
+    anchor_end: usize,
+    trailing_space: bool,
+    source: &str,
+) -> Option<CallSite<'a>> {
+    let mut child = anchor;
+    while let Some(parent) = child.parent() {
+        match parent.kind() {
+            "function_call_expr" => {
+                let target = parent.child_by_field_name("target")?;
+                let mut cursor = parent.walk();
+                let args: Vec<_> = parent.children_by_field_name("arg", &mut cursor).collect();
+                let active = match args.iter().position(|arg| arg.id() == child.id()) {
+                    Some(i) if trailing_space && args[i].end_byte() == anchor_end => i + 1,
+                    Some(i) => i,
+                    None => 0,
+                };
+                let piped = supplied_by_operator(parent, source);
+                return Some(CallSite {
+                    target,
+                    active,
+                    piped,
+                });
+            }
+            "bin_op_expr" => {
+                if let Some(site) = operand_call_site(parent, child, source) {
+                    return Some(site);
+                }
+                if child.kind() == "value_expr" {
+                    return Some(CallSite {
+                        target: child,
+                        active: 0,
+                        piped: supplied_by_operator(child, source),
+                    });
+                }
+            }
+            "value_declaration" | "let_in_expr" | "case_of_branch" | "file" => {
+                // A function name followed by a space, before its first argument
+                return (child.kind() == "value_expr" && trailing_space).then_some(CallSite {
+                    target: child,
+                    active: 0,
+                    piped: 0,
+                });
+            }
+            _ => {}
+        }
+        child = parent;
+    }
+    None
+}
+
+/// A call whose argument is the operand `child` of `bin_op`: the right side
+/// of `f a <| x`, or a value kept with `|=` in a parser pipeline
+fn operand_call_site<'a>(
+    bin_op: tree_sitter::Node<'a>,
+    child: tree_sitter::Node<'a>,
+    source: &str,
+) -> Option<CallSite<'a>> {
+    let mut cursor = bin_op.walk();
+    let parts: Vec<_> = bin_op.named_children(&mut cursor).collect();
+    let index = parts.iter().position(|p| p.id() == child.id())?;
+    let operator = |i: usize| {
+        parts
+            .get(i)
+            .filter(|p| p.kind() == "operator")
+            .map(|p| &source[p.byte_range()])
+    };
+
+    match operator(index.checked_sub(1)?)? {
+        "<|" => {
+            let function = parts.get(index.checked_sub(2)?)?;
+            let (target, active) = match function.kind() {
+                "function_call_expr" => {
+                    let mut cursor = function.walk();
+                    let count = function.children_by_field_name("arg", &mut cursor).count();
+                    (function.child_by_field_name("target")?, count)
+                }
+                "value_expr" => (*function, 0),
+                _ => return None,
+            };
+            Some(CallSite {
+                target,
+                active,
+                piped: 0,
+            })
+        }
+        "|=" => {
+            // Back to the start of the `|.`/`|=` chain, counting the kept values
+            let mut kept = 0;
+            let mut start = index;
+            while start >= 2 {
+                match operator(start - 1) {
+                    Some("|=") => kept += 1,
+                    Some("|.") => {}
+                    _ => break,
+                }
+                start -= 2;
+            }
+            let start = parts[start];
+            if start.kind() != "function_call_expr" {
+                return None;
+            }
+            let succeed = start.child_by_field_name("target")?;
+            if !source[succeed.byte_range()].ends_with("succeed") {
+                return None;
+            }
+            let mut cursor = start.walk();
+            let function = start.children_by_field_name("arg", &mut cursor).next()?;
+            Some(CallSite {
+                target: function,
+                active: kept - 1,
+                piped: 0,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// 1 when `node` is the function side of `x |> f` or `f <| x`
+fn supplied_by_operator(node: tree_sitter::Node, source: &str) -> usize {
+    let operator = |sibling: Option<tree_sitter::Node>| {
+        sibling
+            .filter(|s| s.kind() == "operator")
+            .map(|s| source[s.byte_range()].to_string())
+    };
+    let piped = operator(node.prev_named_sibling()).as_deref() == Some("|>")
+        || operator(node.next_named_sibling()).as_deref() == Some("<|");
+    usize::from(piped)
+}
+
+/// Split a type at its top-level arrows: `(a -> b) -> List a -> List b`
+/// becomes `(a -> b)`, `List a` and `List b`
+fn split_arrows(tipe: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    let bytes = tipe.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        match b {
+            b'(' | b'{' | b'[' => depth += 1,
+            b')' | b'}' | b']' => depth -= 1,
+            b'-' if depth == 0 && bytes.get(i + 1) == Some(&b'>') => {
+                parts.push(tipe[start..i].trim().to_string());
+                start = i + 2;
+            }
+            _ => {}
+        }
+    }
+    parts.push(tipe[start..].trim().to_string());
+    parts
+}
+
+fn byte_to_point(source: &str, byte: usize) -> tree_sitter::Point {
+    let before = &source[..byte];
+    let row = before.matches('\n').count();
+    let column = byte - before.rfind('\n').map_or(0, |i| i + 1);
+    tree_sitter::Point::new(row, column)
+}
@@ -0,0 +1,230 @@
+//! Snapshot tests of refactoring edits.
+//!
+//! Each test writes a small project, runs a refactoring on it, applies the
+//! edits it returns in memory and compares every file they change with
+//! `snapshots/<test>.snap`. A missing snapshot is recorded by the first run,
+//! and `UPDATE_SNAPSHOTS=1` records them all again, so a change in the edits
+//! shows up as a diff of the `.snap` files to review. Under `CI` a missing
+//! snapshot fails instead of being recorded.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tempfile::TempDir;
+use tower_lsp::lsp_types::*;
+
+use super::tests::apply_edits;
+use super::{VariantReplacement, Workspace};
+
+/// A project written to a temporary directory and indexed
+struct Fixture {
+    dir: TempDir,
+    workspace: Workspace,
+}
+
+impl Fixture {
+    /// A project with `files`, by path from its root, and an elm.json with
+    /// `src` as its source directory
+    fn new(files: &[(&str, &str)]) -> Self {
+        let dir = TempDir::new().unwrap();
+        let elm_json = r#"{ "source-directories": ["src"] }"#;
+        fs::write(dir.path().join("elm.json"), elm_json).unwrap();
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let mut workspace = Workspace::new(dir.path().to_path_buf());
+        workspace.initialize().unwrap();
+        Self { dir, workspace }
+    }
+
+    fn path(&self, path: &str) -> PathBuf {
+        self.dir.path().join(path)
+    }
+
+    fn uri(&self, path: &str) -> Url {
+        Url::from_file_path(self.path(path)).unwrap()
+    }
+
+    /// The files `changes` touch, by path from the root, with the edits applied
+    fn apply(&self, changes: &HashMap<Url, Vec<TextEdit>>) -> BTreeMap<String, String> {
+        let root = self.dir.path().canonicalize().unwrap();
+        changes
+            .iter()
+            .map(|(uri, edits)| {
+                let path = uri.to_file_path().unwrap().canonicalize().unwrap();
+                let before = fs::read_to_string(&path).unwrap();
+                let relative = path.strip_prefix(&root).unwrap();
+                let relative = relative.to_string_lossy().replace('\\', "/");
+                (relative, apply_edits(&before, edits))
+            })
+            .collect()
+    }
+}
+
+/// Compare `files` with the snapshot `name`, recording it when missing
+fn assert_snapshot(name: &str, files: &BTreeMap<String, String>) {
+    let actual: String = files
+        .iter()
+        .map(|(path, content)| format!("--- {}\n{}", path, content))
+        .collect();
+    let file = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("src/workspace/snapshots")
+        .join(format!("{}.snap", name));
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+
+    match fs::read_to_string(&file) {
+        Ok(expected) if !update => {
+            // A checkout may have turned the snapshot's line endings into CRLF
+            let expected = expected.replace("\r\n", "\n");
+            assert!(
+                expected == actual,
+                "snapshot {} differs, run with UPDATE_SNAPSHOTS=1 to accept:\n{}",
+                name,
+                first_difference(&expected, &actual)
+            );
+        }
+        Err(_) if !update && std::env::var_os("CI").is_some() => {
+            panic!("snapshot {} is missing", file.display());
+        }
+        _ => {
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(&file, actual).unwrap();
+        }
+    }
+}
+
+/// The first line where `expected` and `actual` differ, for failure messages
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (None, None) => return "the texts differ only in line endings".to_string(),
+            (e, a) if e != a => {
+                return format!(
+                    "line {}:\n- {}\n+ {}",
+                    line,
+                    e.unwrap_or("<end>"),
+                    a.unwrap_or("<end>")
+                );
+            }
+            _ => line += 1,
+        }
+    }
+}
+
+#[test]
+fn snapshot_move_function() {
+    let fixture = Fixture::new(&[
+        (
+            "src/Utils.elm",
+            "module Utils exposing (double, triple)\n\n\n{-| Twice `n`.\n-}\ndouble : Int -> Int\ndouble n =\n    n * 2\n\n\ntriple : Int -> Int\ntriple n =\n    double n + n\n",
+        ),
+        (
+            "src/Math.elm",
+            "module Math exposing (square)\n\nimport Dict\n\n\nsquare : Int -> Int\nsquare n =\n    n * n\n",
+        ),
+        (
+            "src/Main.elm",
+            "module Main exposing (main)\n\nimport Html exposing (text)\nimport Utils exposing (double)\n\n\nmain =\n    text (String.fromInt (double 21))\n",
+        ),
+    ]);
+    let result = fixture
+        .workspace
+        .move_function(
+            &fixture.uri("src/Utils.elm"),
+            "double",
+            &fixture.path("src/Math.elm"),
+        )
+        .unwrap();
+    assert_snapshot("move_function", &fixture.apply(&result.changes));
+}
+
+#[test]
+fn snapshot_remove_variant() {
+    let fixture = Fixture::new(&[
+        (
+            "src/Shape.elm",
+            "module Shape exposing (Shape(..), area)\n\n\ntype Shape\n    = Circle Float\n    | Square Float\n    | Point\n\n\narea : Shape -> Float\narea shape =\n    case shape of\n        Circle r ->\n            pi * r * r\n\n        Square side ->\n            side * side\n\n        Point ->\n            0\n",
+        ),
+        (
+            "src/Main.elm",
+            "module Main exposing (shapes)\n\nimport Shape exposing (Shape(..))\n\n\nshapes : List Shape\nshapes =\n    [ Circle 1, Square 2, Point ]\n",
+        ),
+    ]);
+    let result = fixture
+        .workspace
+        .remove_variant(
+            &fixture.uri("src/Shape.elm"),
+            "Shape",
+            "Square",
+            1,
+            3,
+            &VariantReplacement::DebugTodo,
+        )
+        .unwrap();
+    assert!(result.success, "{}", result.message);
+    assert_snapshot("remove_variant", &fixture.apply(&result.changes.unwrap()));
+}
+
+#[test]
+fn snapshot_remove_field() {
+    let fixture = Fixture::new(&[
+        (
+            "src/User.elm",
+            "module User exposing (User, guest)\n\n\ntype alias User =\n    { name : String\n    , age : Int\n    , email : String\n    }\n\n\nguest : User\nguest =\n    { name = \"Guest\"\n    , age = 0\n    , email = \"\"\n    }\n",
+        ),
+        (
+            "src/Main.elm",
+            "module Main exposing (label)\n\nimport User exposing (User)\n\n\nlabel : User -> String\nlabel user =\n    user.name ++ \" <\" ++ user.email ++ \">\"\n",
+        ),
+    ]);
+    let result = fixture
+        .workspace
+        .remove_field(&fixture.uri("src/User.elm"), "User", "age", 3)
+        .unwrap();
+    assert!(result.success, "{}", result.message);
+    assert_snapshot("remove_field", &fixture.apply(&result.changes.unwrap()));
+}
+
+#[test]
+fn snapshot_rename_field() {
+    let user = "module User exposing (User, describe)\n\n\ntype alias User =\n    { userId : Int, name : String }\n\n\ndescribe : User -> String\ndescribe { userId, name } =\n    name ++ \"#\" ++ String.fromInt userId\n";
+    let fixture = Fixture::new(&[
+        ("src/User.elm", user),
+        (
+            "src/Main.elm",
+            "module Main exposing (ids)\n\nimport User exposing (User)\n\n\nids : List User -> List Int\nids users =\n    List.map .userId users\n\n\nbump : User -> User\nbump user =\n    { user | userId = user.userId + 1 }\n",
+        ),
+    ]);
+    let uri = fixture.uri("src/User.elm");
+    let field = fixture
+        .workspace
+        .get_field_at_position(&uri, Position::new(4, 6), user)
+        .unwrap();
+    let changes = fixture
+        .workspace
+        .rename_field_edits(&field, "accountId")
+        .unwrap();
+    assert_snapshot("rename_field", &fixture.apply(&changes));
+}
+
+#[test]
+fn snapshot_rename_module_alias() {
+    let fixture = Fixture::new(&[(
+        "src/Main.elm",
+        "module Main exposing (decoder)\n\nimport Json.Decode as D\n\n\ndecoder : D.Decoder Int\ndecoder =\n    D.field \"count\" D.int\n",
+    )]);
+    let uri = fixture.uri("src/Main.elm");
+    let edits = fixture
+        .workspace
+        .rename_module_alias(&uri, Position::new(2, 22), "Decode")
+        .unwrap()
+        .unwrap();
+    let changes = HashMap::from([(uri, edits)]);
+    assert_snapshot("rename_module_alias", &fixture.apply(&changes));
+}
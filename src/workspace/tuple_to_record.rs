@@ -0,0 +1,707 @@
+//! Converting a tuple type into a named record alias.
+//!
+//! Every annotation whose parameters or result are exactly the chosen tuple
+//! type gets the new alias instead. The code that builds or takes apart those
+//! values follows: tuple literals returned by the function or passed at call
+//! sites become record literals, tuple patterns on parameters and on `let`
+//! bindings of the result become record patterns (the bound variables are
+//! renamed to the field names), and `Tuple.first`/`Tuple.second` on them
+//! become field accesses. Tuples nested in other types (`List ( a, b )`) are
+//! left alone, and anything that cannot be rewritten is listed for review.
+
+use std::collections::HashMap;
+use tower_lsp::lsp_types::*;
+
+use crate::binder::BoundSymbolKind;
+use crate::document::node_range;
+
+use super::import_edit::ImportSpec;
+use super::reorder::call_arguments;
+use super::{normalize_type, RefactorResult, Workspace};
+
+/// A function whose annotation now uses the record alias
+struct ConvertedFunction {
+    name: String,
+    module_name: String,
+    uri: Url,
+    /// Indices of the parameters that took the tuple
+    params: Vec<usize>,
+    /// Whether the result was the tuple
+    returns: bool,
+}
+
+/// What to convert and into what
+struct Conversion<'a> {
+    /// The tuple type, by [`tuple_key`]
+    key: String,
+    fields: &'a [String],
+}
+
+impl Workspace {
+    /// Replace the tuple type at `position` with a new record alias named
+    /// `alias_name`, declared next to it, whose fields are `field_names` in
+    /// tuple order
+    pub fn tuple_to_record(
+        &self,
+        uri: &Url,
+        position: Position,
+        alias_name: &str,
+        field_names: &[String],
+    ) -> anyhow::Result<RefactorResult> {
+        let path = uri
+            .to_file_path()
+            .map_err(|_| anyhow::anyhow!("Invalid URI"))?;
+        self.ensure_not_generated(&path)?;
+        let content = self
+            .read_file_content(uri)
+            .ok_or_else(|| anyhow::anyhow!("Could not read {}", uri))?;
+        let tree = self
+            .parser
+            .parse(&content)
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse source"))?;
+
+        let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+        let mut tuple = tree.root_node().descendant_for_point_range(point, point);
+        while let Some(node) = tuple.filter(|n| n.kind() != "tuple_type") {
+            tuple = node.parent();
+        }
+        let tuple = match tuple {
+            Some(tuple) => tuple,
+            None => return Ok(RefactorResult::error("Position is not on a tuple type")),
+        };
+        let mut cursor = tuple.walk();
+        let element_types: Vec<&str> = tuple
+            .children_by_field_name("typeExpression", &mut cursor)
+            .map(|t| content[t.byte_range()].trim())
+            .collect();
+
+        if let Err(message) = validate_names(alias_name, field_names, element_types.len()) {
+            return Ok(RefactorResult::error(&message));
+        }
+        let module_name = self.get_module_name_from_uri(uri);
+        let taken = self
+            .modules
+            .get(&module_name)
+            .is_some_and(|m| m.symbols.iter().any(|s| s.name == alias_name));
+        if taken {
+            return Ok(RefactorResult::error(&format!(
+                "'{}' is already declared in {}",
+                alias_name, module_name
+            )));
+        }
+
+        let conversion = Conversion {
+            key: tuple_key(&content[tuple.byte_range()]),
+            fields: field_names,
+        };
+
+        // 1. Annotations, parameter patterns and returned literals, per file
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        let mut converted = Vec::new();
+        let mut sources: HashMap<Url, (String, tree_sitter::Tree)> = HashMap::new();
+        for module in self.modules.values() {
+            if module.path.to_string_lossy().contains("/Evergreen/") {
+                continue;
+            }
+            let file_uri = match Url::from_file_path(&module.path) {
+                Ok(file_uri) => file_uri,
+                Err(_) => continue,
+            };
+            let file_content = match self.read_file_content(&file_uri) {
+                Some(c) => c,
+                None => continue,
+            };
+            let file_tree = match self.parser.parse(&file_content) {
+                Some(t) => t,
+                None => continue,
+            };
+
+            let type_name = if module.module_name == module_name {
+                alias_name.to_string()
+            } else {
+                let qualifier = module
+                    .imports
+                    .iter()
+                    .find(|i| i.module_name == module_name)
+                    .map(|i| i.alias.clone().unwrap_or_else(|| module_name.clone()))
+                    .unwrap_or_else(|| module_name.clone());
+                format!("{}.{}", qualifier, alias_name)
+            };
+
+            let mut edits = Vec::new();
+            for (name, params, returns) in convert_annotations(
+                file_tree.root_node(),
+                &file_content,
+                &conversion,
+                &type_name,
+                &mut edits,
+            ) {
+                converted.push(ConvertedFunction {
+                    name,
+                    module_name: module.module_name.clone(),
+                    uri: file_uri.clone(),
+                    params,
+                    returns,
+                });
+            }
+            if !edits.is_empty() {
+                changes.insert(file_uri.clone(), edits);
+            }
+            sources.insert(file_uri, (file_content, file_tree));
+        }
+
+        if converted.is_empty() {
+            return Ok(RefactorResult::error(&format!(
+                "No annotation takes or returns {} directly",
+                content[tuple.byte_range()].trim()
+            )));
+        }
+
+        // 2. Call sites of the converted functions
+        let mut unchanged = Vec::new();
+        for function in &converted {
+            let references = self
+                .find_module_aware_references(&function.name, &function.module_name, &function.uri)
+                .into_iter()
+                .filter(|r| matches!(r.kind, Some(BoundSymbolKind::Function) | None));
+            for reference in references {
+                let (file_content, file_tree) = match sources.get(&reference.uri) {
+                    Some(source) => source,
+                    None => continue,
+                };
+                let edits = changes.entry(reference.uri.clone()).or_default();
+                let handled = convert_call_site(
+                    file_tree,
+                    file_content,
+                    reference.range.start,
+                    function,
+                    &conversion,
+                    edits,
+                );
+                if !handled {
+                    unchanged.push(Location::new(reference.uri.clone(), reference.range));
+                }
+            }
+        }
+        changes.retain(|_, edits| !edits.is_empty());
+        for edits in changes.values_mut() {
+            edits.sort_by_key(|e| (e.range.start.line, e.range.start.character));
+            edits.dedup_by(|a, b| a.range == b.range);
+        }
+
+        // 3. The alias itself, and imports in the other modules that now use it
+        let declaration = format!(
+            "type alias {} =\n    {{ {}\n    }}\n\n\n",
+            alias_name,
+            field_names
+                .iter()
+                .zip(&element_types)
+                .map(|(field, ty)| format!("{} : {}", field, ty))
+                .collect::<Vec<_>>()
+                .join("\n    , ")
+        );
+        let at = alias_insertion_line(&tree, &content);
+        let used_elsewhere = changes.keys().any(|u| u != uri);
+        let defining_edits = changes.entry(uri.clone()).or_default();
+        defining_edits.push(TextEdit {
+            range: Range::new(Position::new(at, 0), Position::new(at, 0)),
+            new_text: declaration,
+        });
+        if used_elsewhere {
+            if let Some(edit) = expose_edit(&tree, &content, alias_name) {
+                defining_edits.push(edit);
+            }
+        }
+        let importing: Vec<Url> = changes
+            .keys()
+            .filter(|u| *u != uri)
+            .filter(|u| {
+                self.get_module_at_uri(u)
+                    .is_some_and(|m| !m.imports.iter().any(|i| i.module_name == module_name))
+            })
+            .cloned()
+            .collect();
+        for file_uri in importing {
+            let edits = self.import_edits(&file_uri, &[ImportSpec::module(&module_name)]);
+            changes.entry(file_uri).or_default().extend(edits);
+        }
+
+        let mut message = format!(
+            "Introduced record alias '{}' in {} function(s)",
+            alias_name,
+            converted.len()
+        );
+        if !unchanged.is_empty() {
+            message.push_str(&format!(
+                ", {} usage(s) need manual review",
+                unchanged.len()
+            ));
+        }
+        Ok(RefactorResult::success(&message, changes, unchanged))
+    }
+}
+
+fn validate_names(alias_name: &str, fields: &[String], arity: usize) -> Result<(), String> {
+    let is_identifier = |name: &str| name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if !alias_name.starts_with(|c: char| c.is_uppercase()) || !is_identifier(alias_name) {
+        return Err(format!("'{}' is not a valid type name", alias_name));
+    }
+    if fields.len() != arity {
+        return Err(format!(
+            "The tuple has {} elements but {} field names were given",
+            arity,
+            fields.len()
+        ));
+    }
+    for (i, field) in fields.iter().enumerate() {
+        if !field.starts_with(|c: char| c.is_lowercase()) || !is_identifier(field) {
+            return Err(format!("'{}' is not a valid field name", field));
+        }
+        if fields[..i].contains(field) {
+            return Err(format!("'{}' is listed more than once", field));
+        }
+    }
+    Ok(())
+}
+
+/// The type text `key` compares: without qualifiers, and without whitespace
+/// so the layout of the tuple does not matter
+fn tuple_key(text: &str) -> String {
+    normalize_type(text).replace(' ', "")
+}
+
+/// Rewrite every annotation below `node` whose parameters or result are the
+/// tuple, and the declarations they annotate. Returns the converted
+/// functions as (name, parameter indices, returns tuple).
+fn convert_annotations(
+    node: tree_sitter::Node,
+    source: &str,
+    conversion: &Conversion,
+    type_name: &str,
+    edits: &mut Vec<TextEdit>,
+) -> Vec<(String, Vec<usize>, bool)> {
+    let mut converted = Vec::new();
+    let mut cursor = node.walk();
+    let children: Vec<tree_sitter::Node> = node.named_children(&mut cursor).collect();
+    for (i, child) in children.iter().enumerate() {
+        if child.kind() != "type_annotation" {
+            converted.extend(convert_annotations(
+                *child, source, conversion, type_name, edits,
+            ));
+            continue;
+        }
+        let (name, annotation) = match (
+            child.child_by_field_name("name"),
+            child.child_by_field_name("typeExpression"),
+        ) {
+            (Some(name), Some(annotation)) => (&source[name.byte_range()], annotation),
+            _ => continue,
+        };
+
+        let mut cursor = annotation.walk();
+        let parts: Vec<tree_sitter::Node> = annotation
+            .children_by_field_name("part", &mut cursor)
+            .collect();
+        let matching: Vec<usize> = parts
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| {
+                p.kind() == "tuple_type" && tuple_key(&source[p.byte_range()]) == conversion.key
+            })
+            .map(|(index, _)| index)
+            .collect();
+        if matching.is_empty() {
+            continue;
+        }
+        for &index in &matching {
+            edits.push(TextEdit {
+                range: node_range(parts[index]),
+                new_text: type_name.to_string(),
+            });
+        }
+        let returns = matching.last() == Some(&(parts.len() - 1));
+        let params: Vec<usize> = matching
+            .into_iter()
+            .filter(|&index| index < parts.len() - 1)
+            .collect();
+
+        // The declaration right after the annotation
+        let declaration = children[i + 1..]
+            .iter()
+            .find(|n| n.kind() == "value_declaration");
+        if let Some(declaration) = declaration {
+            let left = declaration.child_by_field_name("functionDeclarationLeft");
+            let body = declaration.child_by_field_name("body");
+            if let (Some(left), Some(body)) = (left, body) {
+                let mut cursor = left.walk();
+                let patterns: Vec<tree_sitter::Node> = left
+                    .children_by_field_name("pattern", &mut cursor)
+                    .collect();
+                for &index in &params {
+                    if let Some(pattern) = patterns.get(index) {
+                        convert_bound_value(*pattern, body, source, conversion, edits);
+                    }
+                }
+                if returns {
+                    for literal in result_tuples(body) {
+                        convert_tuple_literal(literal, conversion, edits);
+                    }
+                }
+                // The body may hold let-bound annotations of its own
+                converted.extend(convert_annotations(
+                    body, source, conversion, type_name, edits,
+                ));
+            }
+        }
+        converted.push((name.to_string(), params, returns));
+    }
+    converted
+}
+
+/// Convert a pattern that binds a converted value within `scope`: a tuple
+/// pattern becomes a record pattern, a plain variable keeps its name but its
+/// `Tuple.first`/`Tuple.second` uses become field accesses
+fn convert_bound_value(
+    pattern: tree_sitter::Node,
+    scope: tree_sitter::Node,
+    source: &str,
+    conversion: &Conversion,
+    edits: &mut Vec<TextEdit>,
+) -> bool {
+    let pattern = match pattern.kind() {
+        "pattern" => match pattern.child_by_field_name("child") {
+            Some(child) => child,
+            None => return false,
+        },
+        _ => pattern,
+    };
+    match pattern.kind() {
+        "tuple_pattern" => convert_tuple_pattern(pattern, scope, source, conversion, edits),
+        "lower_pattern" => {
+            let name = &source[pattern.byte_range()];
+            convert_tuple_accessors(scope, name, source, conversion, edits);
+            true
+        }
+        "anything_pattern" => true,
+        _ => false,
+    }
+}
+
+/// `( a, _ )` → `{ first }`, renaming `a` to the field name within `scope`
+fn convert_tuple_pattern(
+    pattern: tree_sitter::Node,
+    scope: tree_sitter::Node,
+    source: &str,
+    conversion: &Conversion,
+    edits: &mut Vec<TextEdit>,
+) -> bool {
+    let mut cursor = pattern.walk();
+    let elements: Vec<tree_sitter::Node> = pattern
+        .children_by_field_name("pattern", &mut cursor)
+        .map(|p| p.child_by_field_name("child").unwrap_or(p))
+        .collect();
+    if elements.len() != conversion.fields.len() {
+        return false;
+    }
+
+    let mut bound = Vec::new();
+    let mut renames = Vec::new();
+    for (element, field) in elements.iter().zip(conversion.fields) {
+        match element.kind() {
+            "lower_pattern" => {
+                let name = &source[element.byte_range()];
+                if name != field {
+                    renames.push((name, field.as_str()));
+                }
+                bound.push(field.as_str());
+            }
+            "anything_pattern" => {}
+            _ => return false,
+        }
+    }
+
+    edits.push(TextEdit {
+        range: node_range(pattern),
+        new_text: if bound.is_empty() {
+            "_".to_string()
+        } else {
+            format!("{{ {} }}", bound.join(", "))
+        },
+    });
+    for (from, to) in renames {
+        rename_variable(scope, from, to, source, edits);
+    }
+    true
+}
+
+/// Rename unqualified uses of the variable `from` within `node`
+fn rename_variable(
+    node: tree_sitter::Node,
+    from: &str,
+    to: &str,
+    source: &str,
+    edits: &mut Vec<TextEdit>,
+) {
+    if matches!(node.kind(), "value_expr" | "record_base_identifier")
+        && &source[node.byte_range()] == from
+    {
+        edits.push(TextEdit {
+            range: node_range(node),
+            new_text: to.to_string(),
+        });
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        rename_variable(child, from, to, source, edits);
+    }
+}
+
+/// `Tuple.first name` → `name.first` for a converted 2-tuple
+fn convert_tuple_accessors(
+    node: tree_sitter::Node,
+    name: &str,
+    source: &str,
+    conversion: &Conversion,
+    edits: &mut Vec<TextEdit>,
+) {
+    if node.kind() == "function_call_expr" {
+        if let Some(field) = tuple_accessor_field(node, source, conversion) {
+            let mut cursor = node.walk();
+            let args: Vec<tree_sitter::Node> =
+                node.children_by_field_name("arg", &mut cursor).collect();
+            if let [arg] = args.as_slice() {
+                if &source[arg.byte_range()] == name {
+                    edits.push(TextEdit {
+                        range: node_range(node),
+                        new_text: format!("{}.{}", name, field),
+                    });
+                    return;
+                }
+            }
+        }
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        convert_tuple_accessors(child, name, source, conversion, edits);
+    }
+}
+
+/// The field `Tuple.first`/`Tuple.second` reads, if `call` is one of them
+fn tuple_accessor_field<'a>(
+    call: tree_sitter::Node,
+    source: &str,
+    conversion: &'a Conversion,
+) -> Option<&'a str> {
+    if conversion.fields.len() != 2 {
+        return None;
+    }
+    let target = call.child_by_field_name("target")?;
+    match &source[target.byte_range()] {
+        "Tuple.first" => Some(&conversion.fields[0]),
+        "Tuple.second" => Some(&conversion.fields[1]),
+        _ => None,
+    }
+}
+
+/// The tuple literals `expr` can evaluate to, looking through parentheses,
+/// `if`/`case` branches and `let` bodies
+fn result_tuples(expr: tree_sitter::Node) -> Vec<tree_sitter::Node> {
+    match expr.kind() {
+        "tuple_expr" => vec![expr],
+        "parenthesized_expr" => expr
+            .child_by_field_name("expression")
+            .map(result_tuples)
+            .unwrap_or_default(),
+        "let_in_expr" => expr
+            .child_by_field_name("body")
+            .map(result_tuples)
+            .unwrap_or_default(),
+        "if_else_expr" => {
+            let mut cursor = expr.walk();
+            let parts: Vec<tree_sitter::Node> = expr
+                .children_by_field_name("exprList", &mut cursor)
+                .collect();
+            // Conditions and results alternate; the last one is the `else`
+            parts
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| i % 2 == 1 || *i == parts.len() - 1)
+                .flat_map(|(_, part)| result_tuples(*part))
+                .collect()
+        }
+        "case_of_expr" => {
+            let mut cursor = expr.walk();
+            let branches: Vec<tree_sitter::Node> =
+                expr.children_by_field_name("branch", &mut cursor).collect();
+            branches
+                .into_iter()
+                .filter_map(|b| b.child_by_field_name("expr"))
+                .flat_map(result_tuples)
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// `( a, b )` → `{ first = a, second = b }`, editing only the punctuation so
+/// edits inside the elements stay valid
+fn convert_tuple_literal(
+    literal: tree_sitter::Node,
+    conversion: &Conversion,
+    edits: &mut Vec<TextEdit>,
+) -> bool {
+    let mut cursor = literal.walk();
+    let elements = literal.children_by_field_name("expr", &mut cursor).count();
+    if elements != conversion.fields.len() {
+        return false;
+    }
+    let mut cursor = literal.walk();
+    let mut field = 0;
+    for token in literal.children(&mut cursor) {
+        let new_text = match token.kind() {
+            "(" => format!("{{ {} =", conversion.fields[0]),
+            "," => {
+                field += 1;
+                format!(", {} =", conversion.fields[field])
+            }
+            ")" => "}".to_string(),
+            _ => continue,
+        };
+        edits.push(TextEdit {
+            range: node_range(token),
+            new_text,
+        });
+    }
+    true
+}
+
+/// Rewrite the call of `function` whose name is at `position`. Returns false
+/// if some of it has to be reviewed by hand.
+fn convert_call_site(
+    tree: &tree_sitter::Tree,
+    source: &str,
+    position: Position,
+    function: &ConvertedFunction,
+    conversion: &Conversion,
+    edits: &mut Vec<TextEdit>,
+) -> bool {
+    // Exposing lists and the annotation/declaration names need no change
+    let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+    let usage = tree
+        .root_node()
+        .descendant_for_point_range(point, point)
+        .and_then(|n| {
+            std::iter::successors(Some(n), |n| n.parent())
+                .take(3)
+                .find(|n| n.kind() == "value_expr")
+        });
+    let mut call = match usage {
+        Some(usage) => usage,
+        None => return true,
+    };
+
+    let mut handled = true;
+    if !function.params.is_empty() {
+        match call_arguments(tree, position) {
+            Some(args) => {
+                for &index in &function.params {
+                    match args.get(index) {
+                        Some(arg) if arg.kind() == "tuple_expr" => {
+                            handled &= convert_tuple_literal(*arg, conversion, edits);
+                        }
+                        // A variable is fine as long as it holds a converted value
+                        Some(arg) if arg.kind() == "value_expr" => {}
+                        _ => handled = false,
+                    }
+                }
+            }
+            None => handled = false,
+        }
+    }
+
+    if function.returns {
+        if let Some(parent) = call
+            .parent()
+            .filter(|p| p.kind() == "function_call_expr")
+            .filter(|p| p.child_by_field_name("target").map(|t| t.id()) == Some(call.id()))
+        {
+            call = parent;
+        }
+        while let Some(parent) = call.parent().filter(|p| p.kind() == "parenthesized_expr") {
+            call = parent;
+        }
+
+        match call.parent() {
+            // let ( a, b ) = f x
+            Some(declaration)
+                if declaration.kind() == "value_declaration"
+                    && declaration.child_by_field_name("pattern").is_some() =>
+            {
+                let pattern = declaration.child_by_field_name("pattern");
+                let scope = declaration.parent();
+                if let (Some(pattern), Some(scope)) = (pattern, scope) {
+                    handled &= convert_bound_value(pattern, scope, source, conversion, edits);
+                }
+            }
+            // Tuple.first (f x)
+            Some(accessor)
+                if accessor.kind() == "function_call_expr"
+                    && tuple_accessor_field(accessor, source, conversion).is_some() =>
+            {
+                let field = tuple_accessor_field(accessor, source, conversion).unwrap_or_default();
+                edits.push(TextEdit {
+                    range: node_range(accessor),
+                    new_text: format!("({}).{}", &source[call.byte_range()], field),
+                });
+            }
+            Some(subject) if subject.kind() == "case_of_expr" => handled = false,
+            _ => {}
+        }
+    }
+    handled
+}
+
+/// Line to insert the alias at: before the first declaration (and its doc comment)
+fn alias_insertion_line(tree: &tree_sitter::Tree, source: &str) -> u32 {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let first = root.named_children(&mut cursor).find(|n| {
+        matches!(
+            n.kind(),
+            "type_annotation"
+                | "value_declaration"
+                | "type_declaration"
+                | "type_alias_declaration"
+                | "port_annotation"
+        )
+    });
+    let first = match first {
+        Some(first) => first,
+        None => return root.end_position().row as u32,
+    };
+    let doc = first.prev_named_sibling().filter(|p| {
+        p.kind() == "block_comment"
+            && source[p.byte_range()].starts_with("{-|")
+            && p.prev_named_sibling()
+                .is_some_and(|q| q.kind() != "module_declaration")
+    });
+    doc.unwrap_or(first).start_position().row as u32
+}
+
+/// Add `name` to an explicit module exposing list
+pub(super) fn expose_edit(tree: &tree_sitter::Tree, source: &str, name: &str) -> Option<TextEdit> {
+    let root = tree.root_node();
+    let exposing = root
+        .child_by_field_name("moduleDeclaration")?
+        .child_by_field_name("exposing")?;
+    if source[exposing.byte_range()].contains("..") {
+        return None;
+    }
+    let end = exposing.end_position();
+    let close = Position::new(end.row as u32, end.column.saturating_sub(1) as u32);
+    Some(TextEdit {
+        range: Range::new(close, close),
+        new_text: format!(", {}", name),
+    })
+}
@@ -0,0 +1,68 @@
+//! Going to the declaration of a value's type.
+//!
+//! The type is the one inference found for the value under the cursor, a
+//! reference, a pattern variable or a declared name. A function goes to the
+//! type it returns. Only named types have a declaration: custom types and
+//! type aliases, from the workspace or its packages.
+
+use tower_lsp::lsp_types::{Position, Url};
+
+use super::{GlobalSymbol, Workspace};
+use crate::types::Type;
+
+impl Workspace {
+    /// The declaration of the type of the value at `position`
+    pub fn type_definition_at(&self, uri: &Url, position: Position) -> Option<&GlobalSymbol> {
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+        let node = tree.root_node().descendant_for_point_range(point, point)?;
+
+        // The name of a declaration has the type of the whole declaration;
+        // anything else the type of the value it spells out
+        let declared = node
+            .parent()
+            .filter(|p| p.kind() == "function_declaration_left" && p.named_child(0) == Some(node))
+            .and_then(|left| left.parent());
+        let ty = match declared {
+            Some(declaration) => self.type_checker.get_type(uri.as_str(), declaration.id()),
+            None => std::iter::successors(Some(node), |n| n.parent())
+                .take_while(|n| n.byte_range() == node.byte_range())
+                .find_map(|n| self.type_checker.get_type(uri.as_str(), n.id())),
+        }?;
+
+        let (module, name) = named_type(&ty)?;
+        let qualified = format!("{}.{}", module, name);
+        self.symbols
+            .get(&qualified)
+            .or_else(|| self.external_symbols.get(&qualified))
+            .and_then(|symbols| symbols.first())
+    }
+}
+
+/// The module and name of the type alias or custom type `ty` is; for a
+/// function, the one it returns
+fn named_type(ty: &Type) -> Option<(&str, &str)> {
+    match ty {
+        Type::Function(function) => match &function.alias {
+            Some(alias) => Some((&alias.module, &alias.name)),
+            None => named_type(&function.ret),
+        },
+        Type::Union(union) => Some(match &union.alias {
+            Some(alias) => (&alias.module, &alias.name),
+            None => (&union.module, &union.name),
+        }),
+        Type::Record(record) => {
+            let alias = record.alias.as_ref()?;
+            Some((&alias.module, &alias.name))
+        }
+        Type::Tuple(tuple) => {
+            let alias = tuple.alias.as_ref()?;
+            Some((&alias.module, &alias.name))
+        }
+        Type::Unit(alias) => {
+            let alias = alias.as_ref()?;
+            Some((&alias.module, &alias.name))
+        }
+        _ => None,
+    }
+}
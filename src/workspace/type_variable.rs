@@ -0,0 +1,196 @@
+//! References and rename for type variables, like `msg` in
+//! `view : Model -> Html msg`.
+//!
+//! A type variable means nothing outside its declaration, so its uses are
+//! the same-named variables of the enclosing annotation or type declaration.
+//! Let annotations inside a function share the variables of the function's
+//! annotation, so those are searched too.
+
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::document::node_range;
+use crate::parser::annotation_of;
+
+use super::extract::is_lower_identifier;
+use super::Workspace;
+
+/// The occurrences of a type variable within its declaration
+struct TypeVariableUses {
+    name: String,
+    /// The occurrence at the position asked about
+    at: Range,
+    ranges: Vec<Range>,
+    /// Every type variable name in the same scope
+    names: Vec<String>,
+}
+
+impl Workspace {
+    /// The type variable at `position` and its range there
+    pub fn type_variable_at(&self, uri: &Url, position: Position) -> Option<(String, Range)> {
+        let uses = self.type_variable_uses(uri, position)?;
+        Some((uses.name, uses.at))
+    }
+
+    /// The uses of the type variable at `position`, all in its declaration
+    pub fn type_variable_references(&self, uri: &Url, position: Position) -> Option<Vec<Location>> {
+        let uses = self.type_variable_uses(uri, position)?;
+        Some(
+            uses.ranges
+                .into_iter()
+                .map(|range| Location::new(uri.clone(), range))
+                .collect(),
+        )
+    }
+
+    /// Rename the type variable at `position` within its declaration;
+    /// Ok(None) when there is no type variable there
+    pub fn rename_type_variable(
+        &self,
+        uri: &Url,
+        position: Position,
+        new_name: &str,
+    ) -> anyhow::Result<Option<Vec<TextEdit>>> {
+        let Some(uses) = self.type_variable_uses(uri, position) else {
+            return Ok(None);
+        };
+        if !is_lower_identifier(new_name) {
+            anyhow::bail!("'{}' is not a valid type variable", new_name);
+        }
+        if new_name != uses.name && uses.names.iter().any(|name| name == new_name) {
+            anyhow::bail!(
+                "Type variable '{}' is already used in this declaration",
+                new_name
+            );
+        }
+        let edits = uses
+            .ranges
+            .into_iter()
+            .map(|range| TextEdit {
+                range,
+                new_text: new_name.to_string(),
+            })
+            .collect();
+        Ok(Some(edits))
+    }
+
+    fn type_variable_uses(&self, uri: &Url, position: Position) -> Option<TypeVariableUses> {
+        let source = self.read_file_content(uri)?;
+        let tree = self.parser.parse(&source)?;
+        let point = tree_sitter::Point::new(position.line as usize, position.character as usize);
+        let mut node = tree.root_node().descendant_for_point_range(point, point)?;
+        if node.kind() == "lower_case_identifier" {
+            node = node.parent()?;
+        }
+        if !is_type_variable(node) {
+            return None;
+        }
+        let name = &source[node.byte_range()];
+
+        let declaration = ancestors(node).find(|n| {
+            matches!(
+                n.kind(),
+                "type_annotation"
+                    | "port_annotation"
+                    | "type_declaration"
+                    | "type_alias_declaration"
+            )
+        })?;
+        let scope = if declaration.kind() == "type_annotation" {
+            annotation_scope(declaration, &source, name)
+        } else {
+            vec![declaration]
+        };
+
+        let mut variables = Vec::new();
+        for node in scope {
+            collect_type_variables(node, &mut variables);
+        }
+        let mut names: Vec<String> = variables
+            .iter()
+            .map(|n| source[n.byte_range()].to_string())
+            .collect();
+        names.sort();
+        names.dedup();
+        Some(TypeVariableUses {
+            name: name.to_string(),
+            at: node_range(node),
+            ranges: variables
+                .into_iter()
+                .filter(|n| &source[n.byte_range()] == name)
+                .map(node_range)
+                .collect(),
+            names,
+        })
+    }
+}
+
+/// A type variable in a type, a parameter of a type declaration, or the
+/// extended record of `{ r | field : Int }`
+fn is_type_variable(node: Node) -> bool {
+    match node.kind() {
+        "type_variable" | "lower_type_name" => true,
+        "record_base_identifier" => ancestors(node).any(|n| n.kind() == "type_expression"),
+        _ => false,
+    }
+}
+
+fn ancestors(node: Node) -> impl Iterator<Item = Node> {
+    std::iter::successors(Some(node), |n| n.parent())
+}
+
+/// The annotations sharing `name` with `annotation`: the outermost enclosing
+/// annotation that declares it, with the let annotations in its function
+fn annotation_scope<'a>(annotation: Node<'a>, source: &str, name: &str) -> Vec<Node<'a>> {
+    let mut root = annotation;
+    for declaration in ancestors(annotation).filter(|n| n.kind() == "value_declaration") {
+        if let Some(outer) = annotation_of(declaration, source) {
+            let mut variables = Vec::new();
+            collect_type_variables(outer, &mut variables);
+            if variables.iter().any(|n| &source[n.byte_range()] == name) {
+                root = outer;
+            }
+        }
+    }
+
+    let mut scope = vec![root];
+    if let Some(body) = declaration_of(root).and_then(|d| d.child_by_field_name("body")) {
+        collect_annotations(body, &mut scope);
+    }
+    scope
+}
+
+/// The declaration an annotation is written for
+fn declaration_of(annotation: Node) -> Option<Node> {
+    let mut sibling = annotation.next_named_sibling();
+    while let Some(node) = sibling {
+        match node.kind() {
+            "line_comment" | "block_comment" => sibling = node.next_named_sibling(),
+            "value_declaration" => return Some(node),
+            _ => return None,
+        }
+    }
+    None
+}
+
+fn collect_annotations<'a>(node: Node<'a>, found: &mut Vec<Node<'a>>) {
+    if node.kind() == "type_annotation" {
+        found.push(node);
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_annotations(child, found);
+    }
+}
+
+fn collect_type_variables<'a>(node: Node<'a>, found: &mut Vec<Node<'a>>) {
+    if is_type_variable(node) {
+        found.push(node);
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_type_variables(child, found);
+    }
+}
@@ -3,7 +3,7 @@
 //! Contains result types for move, rename, and removal operations.
 
 use std::collections::HashMap;
-use tower_lsp::lsp_types::{Range, TextEdit, Url};
+use tower_lsp::lsp_types::{Location, Range, TextEdit, Url};
 
 use crate::type_checker::FieldDefinition;
 
@@ -40,6 +40,19 @@ pub struct FileOperationResult {
     pub changes: HashMap<Url, Vec<TextEdit>>,
 }
 
+// ============================================================================
+// Rename Types
+// ============================================================================
+
+/// An existing name in a file touched by a rename that the new name would clash with
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RenameConflict {
+    pub uri: Url,
+    /// The clashing declaration or binding; the file start for imported names
+    pub range: Range,
+    pub message: String,
+}
+
 // ============================================================================
 // Variant Removal Types
 // ============================================================================
@@ -89,6 +102,21 @@ pub struct VariantUsage {
     pub constructor_usage_range: Option<Range>,
 }
 
+/// What constructor usages of a removed variant are replaced with.
+///
+/// JSON format examples:
+/// - `"DebugTodo"` → `Debug.todo` placeholder (the default)
+/// - `{"Expression": "Login \"\""}` → the given expression
+/// - `{"Variant": "Login"}` → another variant of the same type, taking the same
+///   arguments; case patterns are mapped to it as well
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum VariantReplacement {
+    #[default]
+    DebugTodo,
+    Expression(String),
+    Variant(String),
+}
+
 /// Result of a remove variant operation
 #[derive(Debug, serde::Serialize)]
 pub struct RemoveVariantResult {
@@ -326,3 +354,42 @@ impl RemoveFieldResult {
         }
     }
 }
+
+// ============================================================================
+// Refactoring Types
+// ============================================================================
+
+/// Result of a refactoring (reordering, tuple to record) that rewrites what it
+/// can and lists the rest for review
+#[derive(Debug, serde::Serialize)]
+pub struct RefactorResult {
+    pub success: bool,
+    pub message: String,
+    pub changes: Option<HashMap<Url, Vec<TextEdit>>>,
+    /// Constructor usages that could not be reordered automatically
+    pub unchanged_usages: Vec<Location>,
+}
+
+impl RefactorResult {
+    pub fn error(message: &str) -> Self {
+        Self {
+            success: false,
+            message: message.to_string(),
+            changes: None,
+            unchanged_usages: Vec::new(),
+        }
+    }
+
+    pub fn success(
+        message: &str,
+        changes: HashMap<Url, Vec<TextEdit>>,
+        unchanged_usages: Vec<Location>,
+    ) -> Self {
+        Self {
+            success: true,
+            message: message.to_string(),
+            changes: Some(changes),
+            unchanged_usages,
+        }
+    }
+}
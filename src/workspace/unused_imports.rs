@@ -0,0 +1,72 @@
+//! Unused imports of a workspace file, found by
+//! [`analysis::unused_import_diagnostics`] with what the index knows of the
+//! modules the file imports.
+
+use tower_lsp::lsp_types::*;
+
+use crate::analysis::{self, ImportedModules, QuickFix};
+
+use super::Workspace;
+
+/// The workspace index, as the unused import analysis asks it
+struct Index<'a>(&'a Workspace);
+
+impl ImportedModules for Index<'_> {
+    fn is_known(&self, module_name: &str) -> bool {
+        self.0.modules.contains_key(module_name)
+            || self.0.external_modules.contains_key(module_name)
+    }
+
+    fn in_workspace(&self, module_name: &str) -> bool {
+        self.0.modules.contains_key(module_name)
+    }
+
+    fn defines(&self, module_name: &str, name: &str) -> bool {
+        self.0.module_defines(module_name, name)
+    }
+
+    fn variants(&self, module_name: &str, type_name: &str) -> Vec<String> {
+        self.0
+            .modules
+            .get(module_name)
+            .into_iter()
+            .flat_map(|module| &module.symbols)
+            .filter(|symbol| symbol.name == type_name)
+            .flat_map(|symbol| &symbol.variants)
+            .map(|variant| variant.name.clone())
+            .collect()
+    }
+}
+
+impl Workspace {
+    /// Report unused imports and unused names in import exposing lists
+    pub fn unused_import_diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        match self.import_analysis_input(uri) {
+            Some((tree, source)) => analysis::unused_import_diagnostics(tree, source, &Index(self)),
+            None => Vec::new(),
+        }
+    }
+
+    /// Fixes for the diagnostics of [`Self::unused_import_diagnostics`] in
+    /// `range`
+    pub fn unused_import_fixes(&self, uri: &Url, range: Range) -> Vec<QuickFix> {
+        match self.import_analysis_input(uri) {
+            Some((tree, source)) => {
+                analysis::unused_import_fixes(tree, source, &Index(self), range)
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// The tree and source of the module at `uri`, unless it is generated
+    /// code, which is not for editing
+    fn import_analysis_input(&self, uri: &Url) -> Option<(&tree_sitter::Tree, &str)> {
+        let module = self.find_module_by_path(&uri.to_file_path().ok()?)?;
+        if module.generated {
+            return None;
+        }
+        let tree = self.type_checker.get_tree(uri.as_str())?;
+        let source = self.type_checker.get_source(uri.as_str())?;
+        Some((tree, source))
+    }
+}
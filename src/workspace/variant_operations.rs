@@ -6,18 +6,26 @@
 use std::collections::HashMap;
 use tower_lsp::lsp_types::*;
 
-use super::{ExposingInfo, RemoveVariantResult, UsageType, VariantUsage, Workspace};
+use super::import_edit::ImportSpec;
+use super::{
+    ExposingInfo, RemoveVariantResult, UsageType, VariantReplacement, VariantUsage, Workspace,
+};
 
 impl Workspace {
-    /// Remove a variant from a custom type
+    /// Remove a variant from a custom type, replacing its constructor usages
+    /// as `replacement` says
     pub fn remove_variant(
         &self,
         uri: &Url,
-        _type_name: &str,
+        type_name: &str,
         variant_name: &str,
         _variant_index: usize,
         total_variants: usize,
+        replacement: &VariantReplacement,
     ) -> anyhow::Result<RemoveVariantResult> {
+        if let Ok(path) = uri.to_file_path() {
+            self.ensure_not_generated(&path)?;
+        }
         // 1. Validate: can't remove if only 1 variant
         if total_variants <= 1 {
             return Ok(RemoveVariantResult::error(
@@ -43,140 +51,91 @@ impl Workspace {
             .filter(|u| u.usage_type == UsageType::PatternMatch)
             .collect();
 
-        // 3. Read file and find the variant line
+        // 3. Read and parse the file containing the type
         let path = uri
             .to_file_path()
             .map_err(|_| anyhow::anyhow!("Invalid URI"))?;
-        let content = std::fs::read_to_string(&path)?;
-        let lines: Vec<&str> = content.lines().collect();
-
-        // Find the variant in the source
-        let mut variant_line = None;
-        let mut is_first_variant = false;
-        let mut next_variant_line = None;
-
-        for (i, line) in lines.iter().enumerate() {
-            let trimmed = line.trim();
-            // Look for the variant name in a type declaration context
-            if (trimmed.starts_with('=') || trimmed.starts_with('|'))
-                && trimmed.contains(variant_name)
-            {
-                // Check if this is actually our variant (not just containing the name)
-                let parts: Vec<&str> = trimmed.split_whitespace().collect();
-                if parts.len() >= 2 && parts[1] == variant_name {
-                    variant_line = Some(i);
-                    is_first_variant = trimmed.starts_with('=');
-
-                    // Find next variant line if exists
-                    for (offset, next_line) in lines[(i + 1)..].iter().enumerate() {
-                        let next_trimmed = next_line.trim();
-                        if next_trimmed.starts_with('|') {
-                            next_variant_line = Some(i + 1 + offset);
-                            break;
-                        } else if !next_trimmed.is_empty() && !next_trimmed.starts_with('|') {
-                            // Hit something else (not a variant continuation)
-                            break;
-                        }
-                    }
-                    break;
-                } else if !parts.is_empty() && parts[0] == variant_name {
-                    // variant without = or | prefix (shouldn't happen but handle it)
-                    variant_line = Some(i);
-                    break;
-                }
-            }
-        }
-
-        let variant_line =
-            variant_line.ok_or_else(|| anyhow::anyhow!("Variant line not found in source"))?;
+        let content = self.source(&path)?;
+        let tree = self
+            .parser
+            .parse(&content)
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse source"))?;
 
         // 4. Create TextEdits to remove the variant from type definition
         let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
-        let type_def_edits = if is_first_variant {
-            // First variant: need to change next | to =
-            if let Some(next_line) = next_variant_line {
-                // Delete from start of variant line to start of next variant line
-                // and replace the | with =
-                let next_line_content = lines[next_line];
-                let new_next_line = next_line_content.replacen('|', "=", 1);
-
-                vec![
-                    TextEdit {
-                        range: Range {
-                            start: Position {
-                                line: variant_line as u32,
-                                character: 0,
-                            },
-                            end: Position {
-                                line: next_line as u32,
-                                character: 0,
-                            },
-                        },
-                        new_text: String::new(),
-                    },
-                    TextEdit {
-                        range: Range {
-                            start: Position {
-                                line: next_line as u32,
-                                character: 0,
-                            },
-                            end: Position {
-                                line: next_line as u32,
-                                character: next_line_content.len() as u32,
-                            },
-                        },
-                        new_text: new_next_line,
-                    },
-                ]
-            } else {
-                // Only variant (shouldn't happen - we checked total_variants > 1)
-                return Ok(RemoveVariantResult::error("Cannot determine next variant"));
+        let type_def_edits = variant_removal_edits(&tree, &content, variant_name)
+            .ok_or_else(|| anyhow::anyhow!("Variant not found in source"))?;
+
+        // A target variant must belong to the same type and take the same arguments
+        if let VariantReplacement::Variant(target) = replacement {
+            let arity = |name: &str| variant_arity(&tree, &content, type_name, name);
+            if target == variant_name || arity(target).is_none() {
+                return Ok(RemoveVariantResult::error(&format!(
+                    "'{}' is not another variant of '{}'",
+                    target, type_name
+                )));
             }
-        } else {
-            // Middle or last variant: just delete the line
-            vec![TextEdit {
-                range: Range {
-                    start: Position {
-                        line: variant_line as u32,
-                        character: 0,
-                    },
-                    end: Position {
-                        line: (variant_line + 1) as u32,
-                        character: 0,
-                    },
-                },
-                new_text: String::new(),
-            }]
-        };
+            if arity(target) != arity(variant_name) {
+                return Ok(RemoveVariantResult::error(&format!(
+                    "'{}' and '{}' take a different number of arguments",
+                    variant_name, target
+                )));
+            }
+        }
 
         changes.insert(uri.clone(), type_def_edits);
 
-        // 4b. Replace constructor usages with Debug.todo
+        // 4b. Replace constructor usages
         for usage in &constructor_usages {
             if let Some(range) = usage.constructor_usage_range {
                 let usage_uri =
                     Url::parse(&usage.uri).map_err(|_| anyhow::anyhow!("Invalid usage URI"))?;
 
-                let replacement =
-                    format!("(Debug.todo \"FIXME: Variant Removal: {}\")", variant_name);
-
-                changes.entry(usage_uri).or_default().push(TextEdit {
-                    range,
-                    new_text: replacement,
-                });
+                let edit = match replacement {
+                    VariantReplacement::DebugTodo => TextEdit {
+                        range,
+                        new_text: format!(
+                            "(Debug.todo \"FIXME: Variant Removal: {}\")",
+                            variant_name
+                        ),
+                    },
+                    VariantReplacement::Expression(code) => TextEdit {
+                        range,
+                        new_text: format!("({})", code),
+                    },
+                    // Only the constructor name changes; arguments stay as they are
+                    VariantReplacement::Variant(target) => TextEdit {
+                        range: name_range(usage, variant_name),
+                        new_text: target.clone(),
+                    },
+                };
+                changes.entry(usage_uri).or_default().push(edit);
             }
         }
 
-        // 5. Add edits to remove all pattern match branches
-        // Also collect removed pattern lines for useless wildcard detection
-        let mut removed_pattern_lines: Vec<u32> = Vec::new();
+        // 5. Add edits to remove all pattern match branches (or map them to
+        // the target variant). Also collect removed branch ranges for useless
+        // wildcard detection
+        let mut removed_branches: Vec<Range> = Vec::new();
+        let mut mapped_patterns = 0;
 
         for usage in &pattern_usages {
             if let Some(range) = usage.pattern_branch_range {
                 let usage_uri =
                     Url::parse(&usage.uri).map_err(|_| anyhow::anyhow!("Invalid usage URI"))?;
 
-                removed_pattern_lines.push(range.start.line);
+                if let VariantReplacement::Variant(target) = replacement {
+                    if !self.case_already_matches(usage, variant_name, target) {
+                        mapped_patterns += 1;
+                        changes.entry(usage_uri).or_default().push(TextEdit {
+                            range: name_range(usage, variant_name),
+                            new_text: target.clone(),
+                        });
+                        continue;
+                    }
+                }
+
+                removed_branches.push(range);
 
                 changes.entry(usage_uri).or_default().push(TextEdit {
                     range,
@@ -187,12 +146,8 @@ impl Workspace {
 
         // 5b. Find and remove useless wildcards
         // A wildcard is useless if after removal it would cover 0 remaining variants
-        let useless_wildcards = self.find_useless_wildcards(
-            &content,
-            variant_name,
-            total_variants,
-            &removed_pattern_lines,
-        );
+        let useless_wildcards =
+            self.find_useless_wildcards(&content, variant_name, total_variants, &removed_branches);
 
         let useless_wildcard_count = useless_wildcards.len();
         for wc_range in useless_wildcards {
@@ -205,10 +160,7 @@ impl Workspace {
         // 6. Sort edits in reverse order within each file to avoid offset issues
         Self::sort_edits_reverse(&mut changes);
 
-        let removed_branches = usages
-            .iter()
-            .filter(|u| u.usage_type == UsageType::PatternMatch && u.pattern_branch_range.is_some())
-            .count();
+        let removed_branches = removed_branches.len();
 
         let replaced_constructors = constructor_usages.len();
 
@@ -216,11 +168,19 @@ impl Workspace {
             let mut parts = vec![format!("Removed variant '{}'", variant_name)];
 
             if replaced_constructors > 0 {
+                let with = match replacement {
+                    VariantReplacement::DebugTodo => "Debug.todo".to_string(),
+                    VariantReplacement::Expression(code) => code.clone(),
+                    VariantReplacement::Variant(target) => target.clone(),
+                };
                 parts.push(format!(
-                    "replaced {} constructor usage(s) with Debug.todo",
-                    replaced_constructors
+                    "replaced {} constructor usage(s) with {}",
+                    replaced_constructors, with
                 ));
             }
+            if mapped_patterns > 0 {
+                parts.push(format!("mapped {} pattern(s)", mapped_patterns));
+            }
             if removed_branches > 0 {
                 parts.push(format!(
                     "removed {} pattern match branch(es)",
@@ -244,8 +204,68 @@ impl Workspace {
         Ok(RemoveVariantResult::success(&message, changes))
     }
 
+    /// Whether the case expression around a pattern usage of `variant_name`
+    /// already has a branch whose pattern is the same with `target` instead
+    fn case_already_matches(&self, usage: &VariantUsage, variant_name: &str, target: &str) -> bool {
+        let content = match Url::parse(&usage.uri)
+            .ok()
+            .and_then(|uri| self.read_file_content(&uri))
+        {
+            Some(content) => content,
+            None => return false,
+        };
+        let tree = match self.parser.parse(&content) {
+            Some(tree) => tree,
+            None => return false,
+        };
+        let point = tree_sitter::Point::new(usage.line as usize, usage.character as usize);
+        let mut current = tree.root_node().descendant_for_point_range(point, point);
+        while let Some(node) = current {
+            if node.kind() == "case_of_branch" {
+                break;
+            }
+            current = node.parent();
+        }
+        let (branch, case_node) = match current.and_then(|b| Some((b, b.parent()?))) {
+            Some(found) => found,
+            None => return false,
+        };
+        let pattern = match branch.child_by_field_name("pattern") {
+            Some(pattern) => pattern,
+            None => return false,
+        };
+
+        // The pattern as it reads once mapped to `target`
+        let name_start = content
+            .lines()
+            .take(usage.line as usize)
+            .map(|l| l.len() + 1)
+            .sum::<usize>()
+            + usage.character as usize;
+        let mapped = format!(
+            "{}{}{}",
+            &content[pattern.start_byte()..name_start],
+            target,
+            &content[name_start + variant_name.len()..pattern.end_byte()]
+        );
+        let normalize = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let mapped = normalize(&mapped);
+
+        let mut cursor = case_node.walk();
+        let found = case_node
+            .named_children(&mut cursor)
+            .filter(|b| b.kind() == "case_of_branch" && b.id() != branch.id())
+            .filter_map(|b| b.child_by_field_name("pattern"))
+            .any(|p| normalize(&content[p.byte_range()]) == mapped);
+        found
+    }
+
     /// Find the enclosing function for a given position in a file
-    fn find_enclosing_function(&self, uri: &Url, position: Position) -> Option<(String, String)> {
+    pub(super) fn find_enclosing_function(
+        &self,
+        uri: &Url,
+        position: Position,
+    ) -> Option<(String, String)> {
         // Find the module for this URI
         let path = uri.to_file_path().ok()?;
 
@@ -286,9 +306,7 @@ impl Workspace {
 
         // Get the variant definition line to skip it
         let source_path = source_uri.to_file_path().ok();
-        let source_content = source_path
-            .as_ref()
-            .and_then(|p| std::fs::read_to_string(p).ok());
+        let source_content = source_path.as_ref().and_then(|p| self.source(p).ok());
 
         // Group references by file for efficient batch processing
         let mut refs_by_file: HashMap<String, Vec<&super::SymbolReference>> = HashMap::new();
@@ -457,7 +475,7 @@ impl Workspace {
             .collect();
 
         for (module, module_uri) in self.iter_non_evergreen_modules() {
-            let content = match std::fs::read_to_string(&module.path) {
+            let content = match self.source(&module.path) {
                 Ok(c) => c,
                 Err(_) => continue,
             };
@@ -606,10 +624,10 @@ impl Workspace {
                 | "tuple_expr" | "list_expr" | "record_expr" => {
                     return UsageType::Constructor;
                 }
-                "type_declaration" | "union_variant" => {
-                    if n.parent().is_some_and(|p| p.kind() == "type_declaration") {
-                        return UsageType::Definition;
-                    }
+                "type_declaration" | "union_variant"
+                    if n.parent().is_some_and(|p| p.kind() == "type_declaration") =>
+                {
+                    return UsageType::Definition;
                 }
                 _ => {}
             }
@@ -636,29 +654,7 @@ impl Workspace {
         let mut current = Some(node);
         while let Some(n) = current {
             if n.kind() == "case_of_branch" {
-                let start = n.start_position();
-                let end = n.end_position();
-
-                let lines: Vec<&str> = content.lines().collect();
-                let end_line = end.row;
-                let end_char = if end_line + 1 < lines.len() {
-                    0
-                } else {
-                    lines.get(end_line).map(|l| l.len()).unwrap_or(0)
-                };
-
-                // Start at column 0 to delete the entire line including indentation
-                // This prevents leaving orphaned whitespace-only lines inside case expressions
-                return Some(Range {
-                    start: Position {
-                        line: start.row as u32,
-                        character: 0,
-                    },
-                    end: Position {
-                        line: (end_line + 1) as u32,
-                        character: end_char as u32,
-                    },
-                });
+                return Some(case_branch_removal_range(n, content));
             }
             current = n.parent();
         }
@@ -781,7 +777,12 @@ impl Workspace {
                 Some(function_name)
             },
             module_name: module_name.to_string(),
-            call_chain: Vec::new(),
+            // Blocking usages say how they are reached from the entry points
+            call_chain: if usage_type == UsageType::Constructor {
+                self.call_chain(uri, position)
+            } else {
+                Vec::new()
+            },
             usage_type,
             pattern_branch_range,
             constructor_usage_range,
@@ -797,7 +798,7 @@ impl Workspace {
         content: &str,
         _variant_name: &str,
         total_variants: usize,
-        removed_pattern_lines: &[u32],
+        removed_branches: &[Range],
     ) -> Vec<Range> {
         let mut useless_wildcards = Vec::new();
 
@@ -825,9 +826,12 @@ impl Workspace {
                     if child.kind() == "case_of_branch" {
                         branches.push(child);
 
-                        // Check if this branch is being removed (matched by pattern line)
+                        // Check if this branch is being removed
                         let branch_start = child.start_position().row as u32;
-                        if removed_pattern_lines.contains(&branch_start) {
+                        if removed_branches
+                            .iter()
+                            .any(|r| r.start.line <= branch_start && branch_start < r.end.line)
+                        {
                             continue;
                         }
 
@@ -852,28 +856,7 @@ impl Workspace {
             let remaining_variants = total_variants.saturating_sub(1);
             if has_wildcard && remaining_variants == explicit_count {
                 if let Some(wc_branch) = wildcard_branch {
-                    let start = wc_branch.start_position();
-                    let end = wc_branch.end_position();
-
-                    // Include the newline after if present
-                    let lines: Vec<&str> = content.lines().collect();
-                    let end_line = end.row;
-                    let end_char = if end_line + 1 < lines.len() {
-                        0 // Start of next line
-                    } else {
-                        lines.get(end_line).map(|l| l.len()).unwrap_or(0)
-                    };
-
-                    useless_wildcards.push(Range {
-                        start: Position {
-                            line: start.row as u32,
-                            character: start.column as u32,
-                        },
-                        end: Position {
-                            line: (end_line + 1) as u32,
-                            character: end_char as u32,
-                        },
-                    });
+                    useless_wildcards.push(case_branch_removal_range(wc_branch, content));
                 }
             }
         }
@@ -1143,33 +1126,6 @@ impl Workspace {
         })
     }
 
-    /// Find the position where new imports should be inserted in a file.
-    /// Returns the position at the end of the last import statement (or after module declaration).
-    fn find_import_insertion_point(&self, uri: &Url) -> Option<Position> {
-        let content = self.read_file_content(uri)?;
-        let lines: Vec<&str> = content.lines().collect();
-
-        // Find the last import line
-        let mut last_import_line = None;
-        let mut module_line = None;
-
-        for (i, line) in lines.iter().enumerate() {
-            let trimmed = line.trim();
-            if trimmed.starts_with("module ") {
-                module_line = Some(i);
-            } else if trimmed.starts_with("import ") {
-                last_import_line = Some(i);
-            }
-        }
-
-        // Insert after the last import, or after module declaration
-        let insert_line = last_import_line.or(module_line)?;
-        Some(Position {
-            line: (insert_line + 1) as u32,
-            character: 0,
-        })
-    }
-
     /// Check if a case expression has a wildcard pattern
     fn case_has_wildcard(&self, case_node: &tree_sitter::Node, content: &str) -> bool {
         let mut cursor = case_node.walk();
@@ -1178,10 +1134,10 @@ impl Workspace {
                 // Find the pattern in this branch
                 let mut branch_cursor = child.walk();
                 for branch_child in child.children(&mut branch_cursor) {
-                    if branch_child.kind() == "pattern" {
-                        if Self::is_wildcard_pattern(&branch_child, content) {
-                            return true;
-                        }
+                    if branch_child.kind() == "pattern"
+                        && Self::is_wildcard_pattern(&branch_child, content)
+                    {
+                        return true;
                     }
                 }
             }
@@ -1271,7 +1227,7 @@ impl Workspace {
         let path = uri
             .to_file_path()
             .map_err(|_| anyhow::anyhow!("Invalid URI"))?;
-        let content = std::fs::read_to_string(&path)?;
+        let content = self.source(&path)?;
         let lines: Vec<&str> = content.lines().collect();
 
         // Find the type definition and its last variant line
@@ -1396,28 +1352,14 @@ impl Workspace {
         // 3. Add imports to files (merge and dedupe per file)
         let mut imports_added = 0;
         for (file_uri, import_list) in imports_by_file {
-            // Dedupe imports
-            let mut unique_imports: Vec<String> = import_list.clone();
-            unique_imports.sort();
-            unique_imports.dedup();
-
-            if !unique_imports.is_empty() {
-                if let Some(import_pos) = self.find_import_insertion_point(&file_uri) {
-                    let import_text = unique_imports
-                        .iter()
-                        .map(|i| format!("import {}", i))
-                        .collect::<Vec<_>>()
-                        .join("\n");
-
-                    changes.entry(file_uri).or_default().push(TextEdit {
-                        range: Range {
-                            start: import_pos,
-                            end: import_pos,
-                        },
-                        new_text: format!("{}\n", import_text),
-                    });
-                    imports_added += 1;
-                }
+            let specs: Vec<ImportSpec> = import_list
+                .iter()
+                .filter_map(|i| ImportSpec::parse(i))
+                .collect();
+            let edits = self.import_edits(&file_uri, &specs);
+            if !edits.is_empty() {
+                changes.entry(file_uri).or_default().extend(edits);
+                imports_added += 1;
             }
         }
 
@@ -1455,3 +1397,189 @@ impl Workspace {
         Ok(super::AddVariantResult::success(&message, changes))
     }
 }
+
+/// Range of the variant name itself at a usage (without any module qualifier)
+fn name_range(usage: &VariantUsage, variant_name: &str) -> Range {
+    Range::new(
+        Position::new(usage.line, usage.character),
+        Position::new(usage.line, usage.character + variant_name.len() as u32),
+    )
+}
+
+/// Number of arguments `variant_name` of `type_name` takes, or None if the
+/// type has no such variant
+fn variant_arity(
+    tree: &tree_sitter::Tree,
+    content: &str,
+    type_name: &str,
+    variant_name: &str,
+) -> Option<usize> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let declaration = root.children(&mut cursor).find(|n| {
+        n.kind() == "type_declaration"
+            && n.child_by_field_name("name")
+                .is_some_and(|name| &content[name.byte_range()] == type_name)
+    })?;
+    let mut cursor = declaration.walk();
+    let variant = declaration.children(&mut cursor).find(|n| {
+        n.kind() == "union_variant"
+            && n.child_by_field_name("name")
+                .is_some_and(|name| &content[name.byte_range()] == variant_name)
+    })?;
+    let mut cursor = variant.walk();
+    let arity = variant.children_by_field_name("part", &mut cursor).count();
+    Some(arity)
+}
+
+fn is_comment(node: &tree_sitter::Node) -> bool {
+    matches!(node.kind(), "line_comment" | "block_comment")
+}
+
+/// End of a node's own content, ignoring trailing comments the grammar nests inside it
+fn content_end(node: tree_sitter::Node) -> tree_sitter::Point {
+    let mut cursor = node.walk();
+    node.children(&mut cursor)
+        .filter(|c| !is_comment(c))
+        .last()
+        .map(|c| c.end_position())
+        .unwrap_or_else(|| node.end_position())
+}
+
+/// True if only whitespace precedes the node on its line
+fn starts_line(node: tree_sitter::Node, content: &str) -> bool {
+    let start = node.start_byte();
+    let line_start = content[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    content[line_start..start].trim().is_empty()
+}
+
+fn point_to_position(point: tree_sitter::Point) -> Position {
+    Position::new(point.row as u32, point.column as u32)
+}
+
+fn whole_lines(start_row: usize, end_row: usize) -> Range {
+    Range::new(
+        Position::new(start_row as u32, 0),
+        Position::new((end_row + 1) as u32, 0),
+    )
+}
+
+/// Edits removing a variant from its type declaration.
+/// Comments above the variant are removed with it, while comments above
+/// neighbouring variants (which the grammar nests in the previous variant) are kept.
+fn variant_removal_edits(
+    tree: &tree_sitter::Tree,
+    content: &str,
+    variant_name: &str,
+) -> Option<Vec<TextEdit>> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let (variants, index) = root
+        .children(&mut cursor)
+        .filter(|n| n.kind() == "type_declaration")
+        .find_map(|decl| {
+            let mut decl_cursor = decl.walk();
+            let variants: Vec<_> = decl
+                .children_by_field_name("unionVariant", &mut decl_cursor)
+                .collect();
+            let index = variants.iter().position(|v| {
+                v.child_by_field_name("name")
+                    .is_some_and(|n| &content[n.byte_range()] == variant_name)
+            })?;
+            Some((variants, index))
+        })?;
+
+    let variant = variants[index];
+    let separator = variant.prev_sibling()?;
+    let own_end = content_end(variant);
+    let edit = |range: Range, new_text: &str| TextEdit {
+        range,
+        new_text: new_text.to_string(),
+    };
+
+    if index == 0 {
+        // First variant: the next `|` becomes the `=`
+        let next = *variants.get(1)?;
+        let next_separator = next.prev_sibling()?;
+        if starts_line(separator, content) && starts_line(next_separator, content) {
+            return Some(vec![
+                edit(whole_lines(separator.start_position().row, own_end.row), ""),
+                edit(
+                    Range::new(
+                        point_to_position(next_separator.start_position()),
+                        point_to_position(next_separator.end_position()),
+                    ),
+                    "=",
+                ),
+            ]);
+        }
+        // `= A | B` becomes `= B`
+        return Some(vec![edit(
+            Range::new(
+                point_to_position(variant.start_position()),
+                point_to_position(next.start_position()),
+            ),
+            "",
+        )]);
+    }
+
+    let previous = variants[index - 1];
+    let previous_end = content_end(previous);
+    if starts_line(separator, content) {
+        // Variant on its own lines: also drop the comments between it and the previous variant
+        let mut prev_cursor = previous.walk();
+        let start_row = previous
+            .children(&mut prev_cursor)
+            .filter(|c| is_comment(c) && c.start_position().row > previous_end.row)
+            .map(|c| c.start_position().row)
+            .min()
+            .unwrap_or(separator.start_position().row);
+        return Some(vec![edit(whole_lines(start_row, own_end.row), "")]);
+    }
+
+    // Inline variant: remove ` | B`
+    Some(vec![edit(
+        Range::new(point_to_position(previous_end), point_to_position(own_end)),
+        "",
+    )])
+}
+
+/// Whole-line range removing a case branch together with the comments directly
+/// above it, plus one neighbouring blank line so no double gap is left behind
+fn case_branch_removal_range(branch: tree_sitter::Node, content: &str) -> Range {
+    let lines: Vec<&str> = content.lines().collect();
+    let is_blank = |row: usize| lines.get(row).is_some_and(|l| l.trim().is_empty());
+
+    let mut start_row = branch.start_position().row;
+    let mut prev = branch.prev_sibling();
+    while let Some(comment) = prev.filter(is_comment) {
+        if comment.end_position().row + 1 != start_row {
+            break;
+        }
+        start_row = comment.start_position().row;
+        prev = comment.prev_sibling();
+    }
+
+    let mut end_row = branch.end_position().row;
+    if start_row > 0 && is_blank(start_row - 1) {
+        start_row -= 1;
+    } else if is_blank(end_row + 1) && branch.next_sibling().is_some() {
+        end_row += 1;
+    }
+
+    let end_char = if end_row + 1 < lines.len() {
+        0
+    } else {
+        lines.get(end_row).map(|l| l.len()).unwrap_or(0)
+    };
+    Range {
+        start: Position {
+            line: start_row as u32,
+            character: 0,
+        },
+        end: Position {
+            line: (end_row + 1) as u32,
+            character: end_char as u32,
+        },
+    }
+}
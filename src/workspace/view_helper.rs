@@ -0,0 +1,606 @@
+//! Extracting a piece of view code into a helper function.
+//!
+//! The selection must be `Html`, `Svg` or `Element` code inside a top-level
+//! declaration: its inferred type says so, or the declaration's annotation
+//! returns such a type and the selection is that body or a call to the view
+//! module, like `Html.div [] [ ... ]`. The helper takes the local variables
+//! the selection uses as parameters, in order of first use. When nothing in
+//! the selection produces messages, the helper returns `Html msg` so any
+//! page can use it.
+//!
+//! With its own Msg, the constructors of the module's Msg used in the
+//! selection move to a child Msg type, and the call goes through `Html.map`
+//! with a function turning child messages back into the parent's. That needs
+//! every message in the selection to come from one of those constructors: a
+//! call like `viewButton model` already builds parent messages.
+
+use std::collections::HashMap;
+use tower_lsp::lsp_types::*;
+
+use crate::document::node_range;
+
+use super::extract::{bound_names, indented_body, is_lower_identifier, selected_expression};
+use super::{ElmModule, RefactorResult, Workspace};
+
+/// Types of view code, whose last parameter is the message type
+const VIEW_TYPES: &[&str] = &["Html", "Svg", "Element"];
+
+/// The view type of the selection, as the module writes it
+struct ViewType {
+    /// The type without its message, like `Html` or `H.Html`
+    head: String,
+    /// How the module refers to the module defining the type, for its `map`
+    qualifier: String,
+    /// Like `Msg`, or a type variable when the view produces no messages
+    message: String,
+}
+
+impl ViewType {
+    fn with_message(&self, message: &str) -> String {
+        format!("{} {}", self.head, message)
+    }
+
+    /// The message type when it is a custom type, without its qualifier
+    fn message_type(&self) -> Option<&str> {
+        let name = self.message.rsplit('.').next()?;
+        name.starts_with(char::is_uppercase).then_some(name)
+    }
+}
+
+impl Workspace {
+    /// Move the view code selected by `range` to a new top-level function
+    /// `name` below the enclosing declaration. With `own_msg`, the messages
+    /// it produces move to a child Msg type, mapped back at the call
+    pub fn extract_view_helper(
+        &self,
+        uri: &Url,
+        range: Range,
+        name: &str,
+        own_msg: bool,
+    ) -> anyhow::Result<RefactorResult> {
+        let (tree, source) = match (
+            self.type_checker.get_tree(uri.as_str()),
+            self.type_checker.get_source(uri.as_str()),
+        ) {
+            (Some(tree), Some(source)) => (tree, source),
+            _ => return Err(anyhow::anyhow!("{} is not indexed", uri)),
+        };
+
+        let expr = match selected_expression(tree, range) {
+            Some(expr) => expr,
+            None => return Ok(RefactorResult::error("Selection is not an expression")),
+        };
+        let declaration = std::iter::successors(Some(expr), |n| n.parent())
+            .find(|n| n.parent().is_some_and(|p| p.kind() == "file"))
+            .filter(|n| n.kind() == "value_declaration");
+        let declaration = match declaration {
+            Some(declaration) => declaration,
+            None => {
+                return Ok(RefactorResult::error(
+                    "Selection is not inside a top-level declaration",
+                ))
+            }
+        };
+
+        if !is_lower_identifier(name) {
+            return Ok(RefactorResult::error(&format!(
+                "'{}' is not a valid function name",
+                name
+            )));
+        }
+        let module_name = self.get_module_name_from_uri(uri);
+        let module = match self.get_module(&module_name) {
+            Some(module) => module,
+            None => return Err(anyhow::anyhow!("{} is not indexed", uri)),
+        };
+        if module.symbols.iter().any(|s| s.name == name)
+            || bound_names(tree.root_node(), source, None)
+                .iter()
+                .any(|n| n == name)
+        {
+            return Ok(RefactorResult::error(&format!(
+                "'{}' is already defined in {}",
+                name, module_name
+            )));
+        }
+
+        let annotation = annotation_parts(declaration, source);
+        let view = match self.view_type(uri, expr, declaration, source, module, &annotation) {
+            Some(view) => view,
+            None => return Ok(RefactorResult::error("Selection is not view code")),
+        };
+
+        // Local variables become parameters
+        let locals = bound_names(declaration, source, Some(expr));
+        let mut parameters: Vec<tree_sitter::Node> = Vec::new();
+        let references = value_references(expr);
+        for reference in &references {
+            let text = &source[reference.byte_range()];
+            if locals.iter().any(|l| l == text)
+                && !parameters.iter().any(|p| &source[p.byte_range()] == text)
+            {
+                parameters.push(*reference);
+            }
+        }
+        let parameter_type = |parameter: &str| -> Option<String> {
+            let left = declaration.child_by_field_name("functionDeclarationLeft")?;
+            let position = left
+                .named_children(&mut left.walk())
+                .skip(1)
+                .position(|p| {
+                    p.kind() == "lower_pattern" && &source[p.byte_range()] == parameter
+                })?;
+            annotation.get(position).cloned()
+        };
+
+        // References that may produce messages: constructors of the message
+        // type, and values whose type mentions it
+        let variants = view
+            .message_type()
+            .map(|message| variants_of(tree, source, message))
+            .unwrap_or_default();
+        let mut producers = Vec::new();
+        if let Some(message) = view.message_type() {
+            for reference in references {
+                let text = &source[reference.byte_range()];
+                let (qualifier, value) = match text.rsplit_once('.') {
+                    Some((qualifier, value)) => (Some(qualifier), value),
+                    None => (None, text),
+                };
+                let produces = if value.starts_with(char::is_uppercase) {
+                    variants.is_empty() || variants.iter().any(|(v, _, _)| v == value)
+                } else if qualifier.is_none() && locals.iter().any(|l| l == value) {
+                    parameter_type(value).is_none_or(|ty| mentions(&ty, message))
+                } else if let Some(symbol) = module
+                    .symbols
+                    .iter()
+                    .find(|s| qualifier.is_none() && s.name == value)
+                {
+                    symbol
+                        .signature
+                        .as_deref()
+                        .is_none_or(|ty| mentions(ty, message))
+                } else {
+                    self.imported_signature(module, qualifier, value)
+                        .is_some_and(|ty| mentions(&ty, message))
+                };
+                if produces {
+                    producers.push(reference);
+                }
+            }
+        }
+
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        let edits = changes.entry(uri.clone()).or_default();
+        let call = std::iter::once(name)
+            .chain(parameters.iter().map(|p| &source[p.byte_range()]))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut replacements = Vec::new();
+        let mut after = String::new();
+        let (result, replacement) = if own_msg {
+            let message = match view.message_type() {
+                Some(message) if !variants.is_empty() => message,
+                _ => {
+                    return Ok(RefactorResult::error(&format!(
+                        "{} is not a custom type declared in {}",
+                        view.message, module_name
+                    )))
+                }
+            };
+
+            // Every message must come from a constructor that can move
+            let mut used: Vec<&(String, String, usize)> = Vec::new();
+            for producer in &producers {
+                let text = &source[producer.byte_range()];
+                let constructor = text.rsplit('.').next().unwrap_or(text);
+                match variants
+                    .iter()
+                    .find(|(variant, _, _)| variant == constructor)
+                {
+                    Some(variant) => {
+                        if !used.iter().any(|u| u.0 == variant.0) {
+                            used.push(variant);
+                        }
+                    }
+                    None => {
+                        return Ok(RefactorResult::error(&format!(
+                            "`{}` produces {} values, so the selection cannot have its own Msg",
+                            text, message
+                        )))
+                    }
+                }
+            }
+            if used.is_empty() {
+                return Ok(RefactorResult::error(
+                    "The selection produces no messages to move",
+                ));
+            }
+
+            let prefix = name
+                .strip_prefix("view")
+                .filter(|rest| rest.starts_with(char::is_uppercase))
+                .map(str::to_string)
+                .unwrap_or_else(|| capitalize(name));
+            let child = format!("{}Msg", prefix);
+            let mapper = format!("from{}", child);
+            let taken = declared_upper_names(tree, source);
+            let clash = std::iter::once(child.clone())
+                .chain(used.iter().map(|(u, _, _)| format!("{}{}", prefix, u)))
+                .find(|n| taken.contains(n))
+                .or_else(|| {
+                    module
+                        .symbols
+                        .iter()
+                        .any(|s| s.name == mapper)
+                        .then(|| mapper.clone())
+                });
+            if let Some(clash) = clash {
+                return Ok(RefactorResult::error(&format!(
+                    "'{}' is already defined in {}",
+                    clash, module_name
+                )));
+            }
+
+            for producer in &producers {
+                let text = &source[producer.byte_range()];
+                let constructor = text.rsplit('.').next().unwrap_or(text);
+                replacements.push((producer.byte_range(), format!("{}{}", prefix, constructor)));
+            }
+
+            // The child type takes the arguments of the constructors it copies
+            let child_variants: Vec<String> = used
+                .iter()
+                .map(|(variant, arguments, _)| {
+                    format!("{}{} {}", prefix, variant, arguments)
+                        .trim_end()
+                        .to_string()
+                })
+                .collect();
+            let branches: Vec<String> = used
+                .iter()
+                .map(|(variant, _, arity)| {
+                    let arguments: String = (b'a'..)
+                        .take(*arity)
+                        .map(|c| format!(" {}", c as char))
+                        .collect();
+                    format!(
+                        "        {}{}{} ->\n            {}{}",
+                        prefix, variant, arguments, variant, arguments
+                    )
+                })
+                .collect();
+            after = format!(
+                "\n\n\ntype {}\n    = {}\n\n\n{} : {} -> {}\n{} msg =\n    case msg of\n{}",
+                child,
+                child_variants.join("\n    | "),
+                mapper,
+                child,
+                view.message,
+                mapper,
+                branches.join("\n\n")
+            );
+
+            let mapped = if parameters.is_empty() {
+                call.clone()
+            } else {
+                format!("({})", call)
+            };
+            (
+                view.with_message(&child),
+                format!("{}.map {} {}", view.qualifier, mapper, mapped),
+            )
+        } else if producers.is_empty() && view.message_type().is_some() {
+            (view.with_message("msg"), call.clone())
+        } else {
+            (view.with_message(&view.message), call.clone())
+        };
+
+        // The annotation, when every parameter's type is known
+        let parameter_types: Option<Vec<String>> = parameters
+            .iter()
+            .map(|p| {
+                let inferred = self
+                    .type_checker
+                    .get_type(uri.as_str(), p.id())
+                    .filter(|ty| ty.is_known())
+                    .map(|ty| {
+                        let ty = ty.with_readable_vars();
+                        if ty.is_function() {
+                            format!("({})", ty)
+                        } else {
+                            ty.to_string()
+                        }
+                    });
+                inferred.or_else(|| parameter_type(&source[p.byte_range()]))
+            })
+            .collect();
+        let signature = parameter_types.map(|types| {
+            let mut parts = types;
+            parts.push(result);
+            format!("{} : {}\n", name, parts.join(" -> "))
+        });
+
+        let inner = Some(expr)
+            .filter(|e| e.kind() == "parenthesized_expr")
+            .and_then(|e| e.child_by_field_name("expression"))
+            .unwrap_or(expr);
+        let mut text = source[inner.byte_range()].to_string();
+        replacements.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+        for (range, new_text) in replacements {
+            let start = range.start - inner.start_byte();
+            let end = range.end - inner.start_byte();
+            text.replace_range(start..end, &new_text);
+        }
+        let body = indented_body(&text, inner.start_position().column);
+        let arguments: String = parameters
+            .iter()
+            .map(|p| format!(" {}", &source[p.byte_range()]))
+            .collect();
+        let end = node_range(declaration).end;
+        edits.push(TextEdit {
+            range: Range::new(end, end),
+            new_text: format!(
+                "\n\n\n{}{}{} =\n    {}{}",
+                signature.unwrap_or_default(),
+                name,
+                arguments,
+                body,
+                after
+            ),
+        });
+
+        // An argument, or a selection with its own parentheses, stays wrapped
+        let wrapped = expr.kind() == "parenthesized_expr"
+            || expr
+                .parent()
+                .is_some_and(|p| p.kind() == "function_call_expr");
+        let replacement = if wrapped && replacement.contains(' ') {
+            format!("({})", replacement)
+        } else {
+            replacement
+        };
+        edits.push(TextEdit {
+            range: node_range(expr),
+            new_text: replacement,
+        });
+
+        let message = if own_msg {
+            format!("Extracted '{}' with its own Msg type", name)
+        } else {
+            format!(
+                "Extracted '{}' with {} parameter(s)",
+                name,
+                parameters.len()
+            )
+        };
+        Ok(RefactorResult::success(&message, changes, Vec::new()))
+    }
+
+    /// The view type of `expr`, from its inferred type or else from the
+    /// annotation of the declaration it is in
+    fn view_type(
+        &self,
+        uri: &Url,
+        expr: tree_sitter::Node,
+        declaration: tree_sitter::Node,
+        source: &str,
+        module: &ElmModule,
+        annotation: &[String],
+    ) -> Option<ViewType> {
+        let inferred = self
+            .type_checker
+            .get_type(uri.as_str(), expr.id())
+            .filter(|ty| ty.is_known());
+        if let Some(crate::types::Type::Union(union)) = &inferred {
+            let (type_module, head, params) = match &union.alias {
+                Some(alias) => (&alias.module, &alias.name, &alias.parameters),
+                None => (&union.module, &union.name, &union.params),
+            };
+            if VIEW_TYPES.contains(&head.as_str()) {
+                let qualifier = module
+                    .imports
+                    .iter()
+                    .find(|import| &import.module_name == type_module)
+                    .map(|import| import.alias.clone().unwrap_or(import.module_name.clone()))
+                    .unwrap_or(type_module.clone());
+                return Some(ViewType {
+                    head: head.clone(),
+                    qualifier,
+                    message: params.last()?.with_readable_vars().to_string(),
+                });
+            }
+        }
+
+        // The annotation's result, like `Html Msg` or `H.Html Msg`
+        let result = annotation.last()?;
+        let (head, message) = result.rsplit_once(' ')?;
+        let (qualifier, type_name) = match head.rsplit_once('.') {
+            Some((qualifier, type_name)) => (qualifier.to_string(), type_name),
+            None => {
+                // Types and functions, never constructors, are looked up here
+                let import = module
+                    .imports
+                    .iter()
+                    .find(|import| import.exposing.exposes(head, |_| false));
+                let qualifier = import.map_or(head.to_string(), |import| {
+                    import.alias.clone().unwrap_or(import.module_name.clone())
+                });
+                (qualifier, head)
+            }
+        };
+        if !VIEW_TYPES.contains(&type_name) || message.contains(char::is_whitespace) {
+            return None;
+        }
+
+        // The whole body, or a call like `Html.div`
+        let body = declaration.child_by_field_name("body");
+        let inner = Some(expr)
+            .filter(|e| e.kind() == "parenthesized_expr")
+            .and_then(|e| e.child_by_field_name("expression"))
+            .unwrap_or(expr);
+        let target = Some(inner)
+            .filter(|e| e.kind() == "function_call_expr")
+            .and_then(|e| e.child_by_field_name("target"))
+            .map(|t| &source[t.byte_range()]);
+        let is_view_call = target.is_some_and(|target| match target.rsplit_once('.') {
+            Some((target_qualifier, _)) => target_qualifier == qualifier,
+            None => module.imports.iter().any(|import| {
+                import.alias.as_deref().unwrap_or(&import.module_name) == qualifier
+                    && import.exposing.exposes(target, |_| false)
+            }),
+        });
+        if !is_view_call && body.is_none_or(|b| b.id() != expr.id()) {
+            return None;
+        }
+        Some(ViewType {
+            head: head.to_string(),
+            qualifier,
+            message: message.to_string(),
+        })
+    }
+
+    /// The annotation of `name`, written as `qualifier.name` or exposed
+    /// unqualified by one of the module's imports
+    fn imported_signature(
+        &self,
+        module: &ElmModule,
+        qualifier: Option<&str>,
+        name: &str,
+    ) -> Option<String> {
+        module
+            .imports
+            .iter()
+            .filter(|import| match qualifier {
+                Some(qualifier) => {
+                    import.alias.as_deref().unwrap_or(&import.module_name) == qualifier
+                }
+                None => import.exposing.exposes(name, |_| false),
+            })
+            .filter_map(|import| self.get_module(&import.module_name))
+            .flat_map(|imported| &imported.symbols)
+            .find(|s| s.name == name)
+            .and_then(|s| s.signature.clone())
+    }
+}
+
+/// The parts of the annotation of `declaration` between its arrows
+fn annotation_parts(declaration: tree_sitter::Node, source: &str) -> Vec<String> {
+    let Some(type_expression) = declaration
+        .prev_named_sibling()
+        .filter(|n| n.kind() == "type_annotation")
+        .and_then(|n| n.child_by_field_name("typeExpression"))
+    else {
+        return Vec::new();
+    };
+    let mut cursor = type_expression.walk();
+    type_expression
+        .named_children(&mut cursor)
+        .filter(|part| part.kind() != "arrow" && !part.kind().ends_with("comment"))
+        .map(|part| {
+            let text = &source[part.byte_range()];
+            if part.kind() == "type_expression" {
+                format!("({})", text)
+            } else {
+                text.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Whether the written type `ty` mentions the type `name`
+fn mentions(ty: &str, name: &str) -> bool {
+    ty.split(|c: char| !c.is_alphanumeric() && c != '_' && c != '.')
+        .any(|word| word.rsplit('.').next() == Some(name))
+}
+
+/// Values and constructors referenced in `expr`, in source order
+fn value_references(expr: tree_sitter::Node) -> Vec<tree_sitter::Node> {
+    let mut references = Vec::new();
+    let mut stack = vec![expr];
+    while let Some(current) = stack.pop() {
+        if matches!(current.kind(), "value_expr" | "record_base_identifier") {
+            references.push(current);
+            continue;
+        }
+        let mut cursor = current.walk();
+        stack.extend(current.children(&mut cursor));
+    }
+    references.sort_by_key(|n| n.start_byte());
+    references
+}
+
+/// The variants of the custom type `type_name`: name, arguments as
+/// written, and how many there are
+fn variants_of(
+    tree: &tree_sitter::Tree,
+    source: &str,
+    type_name: &str,
+) -> Vec<(String, String, usize)> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let declaration = root.children(&mut cursor).find(|n| {
+        n.kind() == "type_declaration"
+            && n.child_by_field_name("name")
+                .is_some_and(|name| &source[name.byte_range()] == type_name)
+    });
+    let Some(declaration) = declaration else {
+        return Vec::new();
+    };
+    let mut cursor = declaration.walk();
+    declaration
+        .children(&mut cursor)
+        .filter(|c| c.kind() == "union_variant")
+        .filter_map(|variant| {
+            let name = variant.child_by_field_name("name")?;
+            let mut cursor = variant.walk();
+            let arity = variant
+                .named_children(&mut cursor)
+                .filter(|c| c.id() != name.id() && !c.kind().ends_with("comment"))
+                .count();
+            Some((
+                source[name.byte_range()].to_string(),
+                source[name.end_byte()..variant.end_byte()]
+                    .trim()
+                    .to_string(),
+                arity,
+            ))
+        })
+        .collect()
+}
+
+/// Names of the types and constructors the module declares
+fn declared_upper_names(tree: &tree_sitter::Tree, source: &str) -> Vec<String> {
+    let root = tree.root_node();
+    let mut names = Vec::new();
+    let mut cursor = root.walk();
+    for declaration in root.children(&mut cursor) {
+        if !matches!(
+            declaration.kind(),
+            "type_declaration" | "type_alias_declaration"
+        ) {
+            continue;
+        }
+        if let Some(name) = declaration.child_by_field_name("name") {
+            names.push(source[name.byte_range()].to_string());
+        }
+        let mut variants = declaration.walk();
+        for variant in declaration.children(&mut variants) {
+            if let Some(name) = variant
+                .child_by_field_name("name")
+                .filter(|_| variant.kind() == "union_variant")
+            {
+                names.push(source[name.byte_range()].to_string());
+            }
+        }
+    }
+    names
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,138 @@
+//! Finding the Elm files of a source directory.
+//!
+//! Build output and dependency folders (`elm-stuff`, `node_modules`) are never
+//! descended into, and `.gitignore` files are honoured the way git reads them:
+//! rules from a directory's `.gitignore` apply below that directory, later
+//! rules override earlier ones, and `!` re-includes a path.
+
+use std::path::{Path, PathBuf};
+
+use crate::config::glob_match;
+use crate::logging;
+
+/// Directories that never contain project sources
+const SKIPPED_DIRS: &[&str] = &["elm-stuff", "node_modules", ".git"];
+
+/// One line of a `.gitignore` file
+struct IgnoreRule {
+    /// Directory containing the `.gitignore`; the pattern is relative to it
+    base: PathBuf,
+    pattern: String,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl IgnoreRule {
+    fn parse(base: &Path, line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        // A pattern without an inner slash matches at any depth
+        let pattern = match line.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if line.contains('/') => line.to_string(),
+            None => format!("**/{}", line),
+        };
+        Some(Self {
+            base: base.to_path_buf(),
+            pattern,
+            negated,
+            dir_only,
+        })
+    }
+
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        path.strip_prefix(&self.base).is_ok_and(|relative| {
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            glob_match(&self.pattern, &relative)
+        })
+    }
+}
+
+fn read_gitignore(dir: &Path, rules: &mut Vec<IgnoreRule>) {
+    if let Ok(content) = std::fs::read_to_string(dir.join(".gitignore")) {
+        rules.extend(
+            content
+                .lines()
+                .filter_map(|line| IgnoreRule::parse(dir, line)),
+        );
+    }
+}
+
+fn is_ignored(rules: &[IgnoreRule], path: &Path, is_dir: bool) -> bool {
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.matches(path, is_dir))
+        .is_some_and(|rule| !rule.negated)
+}
+
+/// All `.elm` files under `dir`, skipping build artifacts and ignored paths.
+/// `.gitignore` files between `root` and `dir` apply as well.
+pub fn elm_files(dir: &Path, root: &Path) -> Vec<PathBuf> {
+    let mut rules = Vec::new();
+    if let Some(relative) = dir
+        .strip_prefix(root)
+        .ok()
+        .filter(|r| !r.as_os_str().is_empty())
+    {
+        let mut ancestor = root.to_path_buf();
+        read_gitignore(&ancestor, &mut rules);
+        for component in relative.parent().into_iter().flat_map(|p| p.components()) {
+            ancestor.push(component);
+            read_gitignore(&ancestor, &mut rules);
+        }
+    }
+
+    let mut files = Vec::new();
+    walk(dir, &mut rules, &mut files);
+    files
+}
+
+fn walk(dir: &Path, rules: &mut Vec<IgnoreRule>, files: &mut Vec<PathBuf>) {
+    let inherited = rules.len();
+    read_gitignore(dir, rules);
+
+    let mut entries: Vec<_> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).collect(),
+        Err(e) => {
+            tracing::warn!(target: logging::INDEX, "Failed to read {:?}: {}", dir, e);
+            rules.truncate(inherited);
+            return;
+        }
+    };
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        if is_ignored(rules, &path, is_dir) {
+            continue;
+        }
+        if is_dir {
+            let skipped = entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| SKIPPED_DIRS.contains(&name));
+            if !skipped {
+                walk(&path, rules, files);
+            }
+        } else if path.extension().is_some_and(|ext| ext == "elm") {
+            files.push(path);
+        }
+    }
+
+    rules.truncate(inherited);
+}